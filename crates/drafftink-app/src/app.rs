@@ -2,15 +2,21 @@
 
 use drafftink_core::canvas::Canvas;
 use drafftink_core::collaboration::CollaborationManager;
+use drafftink_core::crdt::HistoryEntry;
+use drafftink_core::diff::DocumentDiff;
+use drafftink_core::e2e::RoomKey;
 use drafftink_core::input::InputState;
+use drafftink_core::layout::{LAYOUT_ANIMATION_SECS, LayoutAnimation, LayoutOptions};
 use drafftink_core::shapes::Shape;
-use drafftink_core::sync::{AwarenessState, ConnectionState, SyncEvent};
+use drafftink_core::snap::SpacingAxis;
+use drafftink_core::sync::{
+    AwarenessState, ConnectionState, ErrorCode, SyncEvent, new_access_token, room_link,
+};
 use drafftink_core::tools::ToolKind;
-#[cfg(not(target_arch = "wasm32"))]
 use drafftink_render::PngRenderResult;
 use drafftink_render::{
-    AngleSnapInfo, GridStyle, RenderContext, Renderer, TextEditResult, TextEditState, TextKey,
-    TextModifiers, VelloRenderer,
+    AngleSnapInfo, DiffOverlay, GridStyle, PdfExportOptions, RenderContext, Renderer,
+    SvgExportOptions, TextEditResult, TextEditState, TextKey, TextModifiers, VelloRenderer,
 };
 use kurbo::{Point, Size, Vec2};
 use peniko::Color;
@@ -27,17 +33,28 @@ use winit::keyboard::{Key, NamedKey};
 use winit::window::{CursorIcon, Window, WindowId};
 
 use crate::event_handler::EventHandler;
-use crate::ui::{SelectedShapeProps, UiAction, UiState, render_ui};
+use crate::ui::{
+    ChangeItem, CheckpointItem, CompareView, HistoryItem, SelectedShapeProps, SvgImportMode,
+    UiAction, UiState, render_ui,
+};
 
 #[cfg(feature = "native")]
 pub mod file_ops {
+    use crate::ui::SvgImportMode;
+    use drafftink_core::bundle::BUNDLE_EXTENSION;
     use drafftink_core::canvas::CanvasDocument;
+    use drafftink_core::checkpoint;
+    use drafftink_core::storage::FileStorage;
+    use drafftink_core::templates::{self, BoardTemplate};
     use std::sync::Mutex;
 
     // Channel for receiving async file operation results
     static PENDING_DOCUMENT: Mutex<Option<CanvasDocument>> = Mutex::new(None);
+    static PENDING_COMPARE: Mutex<Option<CanvasDocument>> = Mutex::new(None);
+    static PENDING_TEMPLATES: Mutex<Option<Vec<BoardTemplate>>> = Mutex::new(None);
 
-    /// Save document to a JSON file using native file dialog (async, non-blocking).
+    /// Save document to a JSON file, or a bundle when given the bundle extension,
+    /// using native file dialog (async, non-blocking).
     pub fn save_document(document: &CanvasDocument, name: &str) {
         let doc = document.clone();
         let default_name = format!("{}.json", name);
@@ -45,12 +62,23 @@ pub mod file_ops {
             let dialog = rfd::FileDialog::new()
                 .set_title("Save Document")
                 .set_file_name(&default_name)
-                .add_filter("DrafftInk Document", &["json"]);
+                .add_filter("DrafftInk Document", &["json"])
+                .add_filter("DrafftInk Bundle", &[BUNDLE_EXTENSION]);
 
             if let Some(path) = dialog.save_file() {
-                match doc.to_json() {
-                    Ok(json) => {
-                        if let Err(e) = std::fs::write(&path, &json) {
+                let is_bundle = path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(BUNDLE_EXTENSION));
+                let data = if is_bundle {
+                    doc.to_bundle().map_err(|e| e.to_string())
+                } else {
+                    doc.to_json()
+                        .map(String::into_bytes)
+                        .map_err(|e| e.to_string())
+                };
+                match data {
+                    Ok(data) => {
+                        if let Err(e) = std::fs::write(&path, &data) {
                             log::error!("Failed to write file: {}", e);
                         } else {
                             log::info!("Saved document to: {:?}", path);
@@ -63,26 +91,50 @@ pub mod file_ops {
     }
 
     /// Load document from a JSON file using native file dialog (async, non-blocking).
+    /// SVG files without an embedded scene are imported in `svg_mode`.
     /// Use `take_pending_document()` to retrieve the result.
-    pub fn load_document() {
+    pub fn load_document(svg_mode: SvgImportMode) {
         std::thread::spawn(move || {
             let dialog = rfd::FileDialog::new()
                 .set_title("Open Document")
-                .add_filter("DrafftInk Document", &["json"])
-                .add_filter("Excalidraw", &["excalidraw"]);
+                .add_filter("DrafftInk Document", &["json", BUNDLE_EXTENSION])
+                .add_filter("Excalidraw", &["excalidraw"])
+                .add_filter("tldraw", &["tldr"])
+                .add_filter("draw.io", &["drawio"])
+                .add_filter("Mermaid", &["mmd", "mermaid"])
+                .add_filter("Graphviz", &["dot", "gv"])
+                .add_filter("SVG Image", &["svg"]);
 
             if let Some(path) = dialog.pick_file() {
+                let extension = path
+                    .extension()
+                    .map(|e| e.to_string_lossy().to_lowercase())
+                    .unwrap_or_default();
+                if extension == BUNDLE_EXTENSION {
+                    match std::fs::read(&path) {
+                        Ok(data) => match CanvasDocument::from_bundle(&data) {
+                            Ok(doc) => {
+                                log::info!("Loaded document from: {:?}", path);
+                                if let Ok(mut pending) = PENDING_DOCUMENT.lock() {
+                                    *pending = Some(doc);
+                                }
+                            }
+                            Err(e) => log::error!("Failed to parse document: {}", e),
+                        },
+                        Err(e) => log::error!("Failed to read file: {}", e),
+                    }
+                    return;
+                }
                 match std::fs::read_to_string(&path) {
                     Ok(content) => {
-                        let is_excalidraw = path
-                            .extension()
-                            .map(|e| e.to_string_lossy().to_lowercase() == "excalidraw")
-                            .unwrap_or(false);
-
-                        let result = if is_excalidraw {
-                            CanvasDocument::from_excalidraw(&content).map_err(|e| e.to_string())
-                        } else {
-                            CanvasDocument::from_json(&content).map_err(|e| e.to_string())
+                        let result = match extension.as_str() {
+                            "excalidraw" => CanvasDocument::from_excalidraw(&content),
+                            "tldr" => super::document_from_tldraw(&content),
+                            "drawio" => CanvasDocument::from_drawio(&content),
+                            "mmd" | "mermaid" => CanvasDocument::from_mermaid(&content),
+                            "dot" | "gv" => CanvasDocument::from_dot(&content),
+                            "svg" => super::document_from_svg(&content, svg_mode),
+                            _ => CanvasDocument::from_json(&content).map_err(|e| e.to_string()),
                         };
 
                         match result {
@@ -106,9 +158,127 @@ pub mod file_ops {
         PENDING_DOCUMENT.lock().ok().and_then(|mut p| p.take())
     }
 
+    /// Pick a DrafftInk document or bundle to compare with the board, using native
+    /// file dialog (async, non-blocking). Use `take_pending_compare()` to retrieve it.
+    pub fn compare_with_file() {
+        std::thread::spawn(move || {
+            let dialog = rfd::FileDialog::new()
+                .set_title("Compare with Document")
+                .add_filter("DrafftInk Document", &["json", BUNDLE_EXTENSION]);
+
+            if let Some(path) = dialog.pick_file() {
+                let is_bundle = path
+                    .extension()
+                    .is_some_and(|e| e.eq_ignore_ascii_case(BUNDLE_EXTENSION));
+                let result = if is_bundle {
+                    std::fs::read(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|data| {
+                            CanvasDocument::from_bundle(&data).map_err(|e| e.to_string())
+                        })
+                } else {
+                    std::fs::read_to_string(&path)
+                        .map_err(|e| e.to_string())
+                        .and_then(|json| {
+                            CanvasDocument::from_json(&json).map_err(|e| e.to_string())
+                        })
+                };
+                match result {
+                    Ok(doc) => {
+                        log::info!("Loaded document to compare from: {:?}", path);
+                        if let Ok(mut pending) = PENDING_COMPARE.lock() {
+                            *pending = Some(doc);
+                        }
+                    }
+                    Err(e) => log::error!("Failed to load document to compare: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Take the document picked by `compare_with_file()`.
+    pub fn take_pending_compare() -> Option<CanvasDocument> {
+        PENDING_COMPARE.lock().ok().and_then(|mut p| p.take())
+    }
+
     /// Load document by name (for native, just calls load_document).
     pub fn load_document_by_name(_name: &str) {
-        load_document()
+        load_document(SvgImportMode::default())
+    }
+
+    /// Save a copy of `state` as a new document named `name` in the local document
+    /// store, then open it. Use `take_pending_document()` to retrieve the copy.
+    pub fn fork_document(state: CanvasDocument, name: String) {
+        std::thread::spawn(move || {
+            let Ok(storage) = FileStorage::default_location() else {
+                log::error!("Document storage unavailable");
+                return;
+            };
+            match pollster::block_on(checkpoint::fork_to_storage(&storage, &state, &name)) {
+                Ok(doc) => {
+                    log::info!("Forked document as '{}'", name);
+                    if let Ok(mut pending) = PENDING_DOCUMENT.lock() {
+                        *pending = Some(doc);
+                    }
+                }
+                Err(e) => log::error!("Failed to fork document: {}", e),
+            }
+        });
+    }
+
+    /// Save a template to the local document store, then refresh the template list.
+    pub fn save_template(template: CanvasDocument) {
+        std::thread::spawn(move || {
+            let Ok(storage) = FileStorage::default_location() else {
+                log::error!("Template storage unavailable");
+                return;
+            };
+            match pollster::block_on(templates::save_template(&storage, &template)) {
+                Ok(key) => log::info!("Saved template '{}' as {}", template.name, key),
+                Err(e) => log::error!("Failed to save template: {}", e),
+            }
+            refresh_templates(&storage);
+        });
+    }
+
+    /// Delete a saved template, then refresh the template list.
+    pub fn delete_template(key: &str) {
+        let key = key.to_string();
+        std::thread::spawn(move || {
+            let Ok(storage) = FileStorage::default_location() else {
+                return;
+            };
+            if let Err(e) = pollster::block_on(templates::delete_template(&storage, &key)) {
+                log::error!("Failed to delete template: {}", e);
+            }
+            refresh_templates(&storage);
+        });
+    }
+
+    /// Load saved templates (async, non-blocking).
+    /// Use `take_pending_templates()` to retrieve the result.
+    pub fn list_templates_async() {
+        std::thread::spawn(move || {
+            if let Ok(storage) = FileStorage::default_location() {
+                refresh_templates(&storage);
+            }
+        });
+    }
+
+    fn refresh_templates(storage: &FileStorage) {
+        match pollster::block_on(templates::list_templates(storage)) {
+            Ok(list) => {
+                if let Ok(mut pending) = PENDING_TEMPLATES.lock() {
+                    *pending = Some(list);
+                }
+            }
+            Err(e) => log::error!("Failed to list templates: {}", e),
+        }
+    }
+
+    /// Take the saved templates from an async list operation.
+    pub fn take_pending_templates() -> Option<Vec<BoardTemplate>> {
+        PENDING_TEMPLATES.lock().ok().and_then(|mut p| p.take())
     }
 
     /// Export PNG to file using native file dialog (async, non-blocking).
@@ -131,6 +301,98 @@ pub mod file_ops {
         });
     }
 
+    /// Export SVG to file using native file dialog (async, non-blocking).
+    pub fn export_svg(svg: &str, name: &str) {
+        let data = svg.to_string();
+        let default_name = format!("{}.svg", name);
+        std::thread::spawn(move || {
+            let dialog = rfd::FileDialog::new()
+                .set_title("Export SVG")
+                .set_file_name(&default_name)
+                .add_filter("SVG Image", &["svg"]);
+
+            if let Some(path) = dialog.save_file() {
+                if let Err(e) = std::fs::write(&path, &data) {
+                    log::error!("Failed to write SVG: {}", e);
+                } else {
+                    log::info!("Exported SVG to: {:?}", path);
+                }
+            }
+        });
+    }
+
+    /// Export PDF to file using native file dialog (async, non-blocking).
+    pub fn export_pdf(pdf_data: &[u8], name: &str) {
+        let data = pdf_data.to_vec();
+        let default_name = format!("{}.pdf", name);
+        std::thread::spawn(move || {
+            let dialog = rfd::FileDialog::new()
+                .set_title("Export PDF")
+                .set_file_name(&default_name)
+                .add_filter("PDF Document", &["pdf"]);
+
+            if let Some(path) = dialog.save_file() {
+                if let Err(e) = std::fs::write(&path, &data) {
+                    log::error!("Failed to write PDF: {}", e);
+                } else {
+                    log::info!("Exported PDF to: {:?}", path);
+                }
+            }
+        });
+    }
+
+    /// Export an Excalidraw file using native file dialog (async, non-blocking).
+    pub fn export_excalidraw(json: &str, name: &str) {
+        let data = json.to_string();
+        let default_name = format!("{}.excalidraw", name);
+        std::thread::spawn(move || {
+            let dialog = rfd::FileDialog::new()
+                .set_title("Export Excalidraw")
+                .set_file_name(&default_name)
+                .add_filter("Excalidraw", &["excalidraw"]);
+
+            if let Some(path) = dialog.save_file() {
+                if let Err(e) = std::fs::write(&path, &data) {
+                    log::error!("Failed to write Excalidraw file: {}", e);
+                } else {
+                    log::info!("Exported Excalidraw file to: {:?}", path);
+                }
+            }
+        });
+    }
+
+    /// Export a draw.io file using native file dialog (async, non-blocking).
+    pub fn export_drawio(xml: &str, name: &str) {
+        let data = xml.to_string();
+        let default_name = format!("{}.drawio", name);
+        std::thread::spawn(move || {
+            let dialog = rfd::FileDialog::new()
+                .set_title("Export draw.io")
+                .set_file_name(&default_name)
+                .add_filter("draw.io", &["drawio"]);
+
+            if let Some(path) = dialog.save_file() {
+                if let Err(e) = std::fs::write(&path, &data) {
+                    log::error!("Failed to write draw.io file: {}", e);
+                } else {
+                    log::info!("Exported draw.io file to: {:?}", path);
+                }
+            }
+        });
+    }
+
+    /// Copy text to clipboard.
+    pub fn copy_text_to_clipboard(text: &str) {
+        match arboard::Clipboard::new() {
+            Ok(mut clipboard) => {
+                if let Err(e) = clipboard.set_text(text) {
+                    log::error!("Failed to copy text to clipboard: {}", e);
+                }
+            }
+            Err(e) => log::error!("Failed to access clipboard: {}", e),
+        }
+    }
+
     /// Copy PNG to clipboard.
     pub fn copy_png_to_clipboard(png_data: &[u8], width: u32, height: u32) {
         // arboard expects RGBA pixel data, not PNG encoded data
@@ -225,8 +487,11 @@ pub mod file_ops {
 
 #[cfg(target_arch = "wasm32")]
 pub mod file_ops {
+    use crate::ui::SvgImportMode;
     use drafftink_core::canvas::CanvasDocument;
+    use drafftink_core::checkpoint;
     use drafftink_core::storage::{IndexedDbStorage, Storage};
+    use drafftink_core::templates::{self, BoardTemplate};
     use std::cell::RefCell;
     use std::rc::Rc;
     use wasm_bindgen::prelude::*;
@@ -235,8 +500,10 @@ pub mod file_ops {
         static STORAGE: Rc<IndexedDbStorage> = Rc::new(IndexedDbStorage::new());
         static PENDING_DOCUMENT: RefCell<Option<CanvasDocument>> = const { RefCell::new(None) };
         static PENDING_DOCUMENT_LIST: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+        static PENDING_COMPARE: RefCell<Option<CanvasDocument>> = const { RefCell::new(None) };
         static PENDING_CLIPBOARD_TEXT: RefCell<Option<String>> = const { RefCell::new(None) };
         static PENDING_MATH_CLIPBOARD: RefCell<Option<String>> = const { RefCell::new(None) };
+        static PENDING_TEMPLATES: RefCell<Option<Vec<BoardTemplate>>> = const { RefCell::new(None) };
     }
 
     /// Request clipboard text read (async). Result will be available via take_pending_clipboard_text().
@@ -385,6 +652,23 @@ pub mod file_ops {
         });
     }
 
+    /// Save a copy of `state` as a new document named `name` in IndexedDB, then open
+    /// it. Use `take_pending_document()` to retrieve the copy.
+    pub fn fork_document(state: CanvasDocument, name: String) {
+        STORAGE.with(|storage| {
+            let storage = storage.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match checkpoint::fork_to_storage(&*storage, &state, &name).await {
+                    Ok(doc) => {
+                        log::info!("Forked document as '{}'", name);
+                        set_pending_document(doc);
+                    }
+                    Err(e) => log::error!("Failed to fork document: {:?}", e),
+                }
+            });
+        });
+    }
+
     /// List all documents in IndexedDB - triggers async list.
     pub fn list_documents_async() {
         STORAGE.with(|storage| {
@@ -393,8 +677,10 @@ pub mod file_ops {
                 let future = storage.list();
                 match future.await {
                     Ok(docs) => {
-                        let filtered: Vec<String> =
-                            docs.into_iter().filter(|id| id != "__last__").collect();
+                        let filtered: Vec<String> = docs
+                            .into_iter()
+                            .filter(|id| id != "__last__" && !templates::is_template_key(id))
+                            .collect();
                         PENDING_DOCUMENT_LIST.with(|list| {
                             *list.borrow_mut() = Some(filtered);
                         });
@@ -412,6 +698,60 @@ pub mod file_ops {
         PENDING_DOCUMENT_LIST.with(|list| list.borrow_mut().take())
     }
 
+    /// Save a template to IndexedDB, then refresh the template list.
+    pub fn save_template(template: CanvasDocument) {
+        STORAGE.with(|storage| {
+            let storage = storage.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                match templates::save_template(&*storage, &template).await {
+                    Ok(key) => log::info!("Saved template '{}' as {}", template.name, key),
+                    Err(e) => log::error!("Failed to save template: {:?}", e),
+                }
+                refresh_templates(&storage).await;
+            });
+        });
+    }
+
+    /// Delete a saved template from IndexedDB, then refresh the template list.
+    pub fn delete_template(key: &str) {
+        let key = key.to_string();
+        STORAGE.with(|storage| {
+            let storage = storage.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                if let Err(e) = templates::delete_template(&*storage, &key).await {
+                    log::error!("Failed to delete template: {:?}", e);
+                }
+                refresh_templates(&storage).await;
+            });
+        });
+    }
+
+    /// List saved templates in IndexedDB - triggers async list.
+    pub fn list_templates_async() {
+        STORAGE.with(|storage| {
+            let storage = storage.clone();
+            wasm_bindgen_futures::spawn_local(async move {
+                refresh_templates(&storage).await;
+            });
+        });
+    }
+
+    async fn refresh_templates(storage: &IndexedDbStorage) {
+        match templates::list_templates(storage).await {
+            Ok(list) => {
+                PENDING_TEMPLATES.with(|cell| {
+                    *cell.borrow_mut() = Some(list);
+                });
+            }
+            Err(e) => log::error!("Failed to list templates: {:?}", e),
+        }
+    }
+
+    /// Take the saved templates from an async list operation.
+    pub fn take_pending_templates() -> Option<Vec<BoardTemplate>> {
+        PENDING_TEMPLATES.with(|cell| cell.borrow_mut().take())
+    }
+
     /// Download document as JSON file (export).
     pub fn download_document(document: &CanvasDocument, name: &str) {
         match document.to_json() {
@@ -421,9 +761,64 @@ pub mod file_ops {
     }
 
     /// Upload document from file input - triggers async file picker.
+    /// SVG files without an embedded scene are imported in `svg_mode`.
     /// Use `take_pending_document()` to retrieve the loaded document.
-    pub fn upload_document_async() {
-        trigger_file_input_async();
+    pub fn upload_document_async(svg_mode: SvgImportMode) {
+        trigger_file_input_async(svg_mode);
+    }
+
+    /// Pick a DrafftInk document or bundle to compare with the board - triggers async
+    /// file picker. Use `take_pending_compare()` to retrieve it.
+    pub fn compare_with_file_async() {
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = compare_with_file_impl().await {
+                log::error!("Failed to load document to compare: {:?}", e);
+            }
+        });
+    }
+
+    async fn compare_with_file_impl() -> Result<(), JsValue> {
+        use drafftink_core::bundle::BUNDLE_EXTENSION;
+
+        let window = web_sys::window().ok_or("No window")?;
+        let document = window.document().ok_or("No document")?;
+
+        let input: web_sys::HtmlInputElement = document.create_element("input")?.dyn_into()?;
+        input.set_type("file");
+        input.set_accept(&format!(".json,.{}", BUNDLE_EXTENSION));
+        input.style().set_property("display", "none").ok();
+
+        document.body().ok_or("No body")?.append_child(&input)?;
+
+        let file = wait_for_file_selection(&input).await;
+        input.remove();
+        let file = file?;
+
+        let is_bundle = file
+            .name()
+            .to_lowercase()
+            .ends_with(&format!(".{}", BUNDLE_EXTENSION));
+        let doc = if is_bundle {
+            let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await?;
+            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+            CanvasDocument::from_bundle(&data).map_err(|e| e.to_string())
+        } else {
+            let text: String = wasm_bindgen_futures::JsFuture::from(file.text())
+                .await?
+                .as_string()
+                .ok_or("Failed to read file as text")?;
+            CanvasDocument::from_json(&text).map_err(|e| e.to_string())
+        }
+        .map_err(|e| JsValue::from_str(&format!("Failed to parse document: {}", e)))?;
+
+        log::info!("Document to compare loaded: {}", doc.name);
+        PENDING_COMPARE.with(|cell| *cell.borrow_mut() = Some(doc));
+        Ok(())
+    }
+
+    /// Take the document picked by `compare_with_file_async()`.
+    pub fn take_pending_compare() -> Option<CanvasDocument> {
+        PENDING_COMPARE.with(|cell| cell.borrow_mut().take())
     }
 
     /// Take the pending document loaded from async operations.
@@ -443,6 +838,30 @@ pub mod file_ops {
         download_binary_file(&format!("{}.png", name), png_data, "image/png");
     }
 
+    /// Export SVG (triggers browser download).
+    pub fn export_svg(svg: &str, name: &str) {
+        download_file(&format!("{}.svg", name), svg, "image/svg+xml");
+    }
+
+    /// Export PDF (triggers browser download).
+    pub fn export_pdf(pdf_data: &[u8], name: &str) {
+        download_binary_file(&format!("{}.pdf", name), pdf_data, "application/pdf");
+    }
+
+    /// Export an Excalidraw file (triggers browser download).
+    pub fn export_excalidraw(json: &str, name: &str) {
+        download_file(&format!("{}.excalidraw", name), json, "application/json");
+    }
+
+    /// Export a draw.io file (triggers browser download).
+    pub fn export_drawio(xml: &str, name: &str) {
+        download_file(
+            &format!("{}.drawio", name),
+            xml,
+            "application/vnd.jgraph.mxfile",
+        );
+    }
+
     /// Copy PNG to clipboard using the async Clipboard API.
     /// This spawns an async task since clipboard.write() returns a Promise.
     pub fn copy_png_to_clipboard(png_data: Vec<u8>) {
@@ -553,21 +972,21 @@ pub mod file_ops {
         web_sys::Url::revoke_object_url(&url).ok();
     }
 
-    fn trigger_file_input_async() {
-        wasm_bindgen_futures::spawn_local(async {
-            if let Err(e) = trigger_file_input_impl().await {
+    fn trigger_file_input_async(svg_mode: SvgImportMode) {
+        wasm_bindgen_futures::spawn_local(async move {
+            if let Err(e) = trigger_file_input_impl(svg_mode).await {
                 log::error!("Failed to load file: {:?}", e);
             }
         });
     }
 
-    async fn trigger_file_input_impl() -> Result<(), JsValue> {
+    async fn trigger_file_input_impl(svg_mode: SvgImportMode) -> Result<(), JsValue> {
         let window = web_sys::window().ok_or("No window")?;
         let document = window.document().ok_or("No document")?;
 
         let input: web_sys::HtmlInputElement = document.create_element("input")?.dyn_into()?;
         input.set_type("file");
-        input.set_accept(".json,.excalidraw");
+        input.set_accept(".json,.drafftink,.excalidraw,.tldr,.drawio,.mmd,.mermaid,.dot,.gv,.svg");
         input.style().set_property("display", "none").ok();
 
         document.body().ok_or("No body")?.append_child(&input)?;
@@ -578,7 +997,24 @@ pub mod file_ops {
 
         let file = file?;
         let filename = file.name();
-        let is_excalidraw = filename.to_lowercase().ends_with(".excalidraw");
+        let filename = filename.to_lowercase();
+        let is_excalidraw = filename.ends_with(".excalidraw");
+        let is_tldraw = filename.ends_with(".tldr");
+        let is_drawio = filename.ends_with(".drawio");
+        let is_mermaid = filename.ends_with(".mmd") || filename.ends_with(".mermaid");
+        let is_dot = filename.ends_with(".dot") || filename.ends_with(".gv");
+        let is_svg = filename.ends_with(".svg");
+
+        // Bundles are binary archives
+        if filename.ends_with(&format!(".{}", drafftink_core::bundle::BUNDLE_EXTENSION)) {
+            let buffer = wasm_bindgen_futures::JsFuture::from(file.array_buffer()).await?;
+            let data = js_sys::Uint8Array::new(&buffer).to_vec();
+            let doc = CanvasDocument::from_bundle(&data)
+                .map_err(|e| JsValue::from_str(&format!("Failed to parse document: {}", e)))?;
+            log::info!("Document loaded: {}", doc.name);
+            set_pending_document(doc);
+            return Ok(());
+        }
 
         // Read file content using File.text() which returns a Promise
         let text: String = wasm_bindgen_futures::JsFuture::from(file.text())
@@ -588,6 +1024,16 @@ pub mod file_ops {
 
         let doc_result = if is_excalidraw {
             CanvasDocument::from_excalidraw(&text).map_err(|e| e.to_string())
+        } else if is_tldraw {
+            super::document_from_tldraw(&text)
+        } else if is_drawio {
+            CanvasDocument::from_drawio(&text)
+        } else if is_mermaid {
+            CanvasDocument::from_mermaid(&text)
+        } else if is_dot {
+            CanvasDocument::from_dot(&text)
+        } else if is_svg {
+            super::document_from_svg(&text, svg_mode)
         } else {
             CanvasDocument::from_json(&text).map_err(|e| e.to_string())
         };
@@ -816,13 +1262,23 @@ pub mod file_ops {
         static PENDING_EXCALIDRAW_SHAPES: RefCell<Option<(Vec<drafftink_core::shapes::Shape>, kurbo::Point)>> = const { RefCell::new(None) };
     }
 
-    /// Try to paste shapes from clipboard text (Excalidraw format).
+    /// Try to paste shapes from clipboard text (Excalidraw, Mermaid, DOT or SVG).
+    /// SVG markup is imported in `svg_mode`.
     /// The result will be available via `take_pending_excalidraw_shapes()`.
-    pub fn paste_shapes_from_clipboard_async(cursor_world: kurbo::Point) {
+    pub fn paste_shapes_from_clipboard_async(cursor_world: kurbo::Point, svg_mode: SvgImportMode) {
         wasm_bindgen_futures::spawn_local(async move {
             if let Some(text) = read_clipboard_text_async().await {
                 if let Some(shapes) =
                     drafftink_core::canvas::CanvasDocument::shapes_from_excalidraw_clipboard(&text)
+                        .or_else(|| {
+                            drafftink_core::canvas::CanvasDocument::shapes_from_mermaid_clipboard(
+                                &text,
+                            )
+                        })
+                        .or_else(|| {
+                            drafftink_core::canvas::CanvasDocument::shapes_from_dot_clipboard(&text)
+                        })
+                        .or_else(|| super::svg_import_shapes(&text, svg_mode))
                 {
                     PENDING_EXCALIDRAW_SHAPES.with(|cell| {
                         *cell.borrow_mut() = Some((shapes, cursor_world));
@@ -963,6 +1419,26 @@ pub mod file_ops {
             }
         }
 
+        // Calculate viewport center in world coordinates
+        let viewport_center_x = (viewport_width / 2.0 - camera_offset_x) / camera_zoom;
+        let viewport_center_y = (viewport_height / 2.0 - camera_offset_y) / camera_zoom;
+
+        // SVGs exported by DrafftInk carry the scene in their metadata
+        if file_type == "image/svg+xml" || file_name.ends_with(".svg") {
+            let svg = String::from_utf8_lossy(&data).into_owned();
+            if let Some(json) = drafftink_render::extract_scene_from_svg(&svg) {
+                log::info!("Found embedded scene in SVG, loading as document");
+                add_pending_dropped_document(json);
+            } else {
+                log::info!("Dropped SVG: {}", file_name);
+                PENDING_DROPPED_SVGS.with(|cell| {
+                    cell.borrow_mut()
+                        .push((svg, Point::new(viewport_center_x, viewport_center_y)));
+                });
+            }
+            return Ok(());
+        }
+
         // Determine format
         let format = if file_type == "image/jpeg"
             || file_name.ends_with(".jpg")
@@ -981,10 +1457,6 @@ pub mod file_ops {
         let (width, height) = decode_image_dimensions(&blob_url).await?;
         web_sys::Url::revoke_object_url(&blob_url)?;
 
-        // Calculate viewport center in world coordinates
-        let viewport_center_x = (viewport_width / 2.0 - camera_offset_x) / camera_zoom;
-        let viewport_center_y = (viewport_height / 2.0 - camera_offset_y) / camera_zoom;
-
         let position = Point::new(
             viewport_center_x - width as f64 / 2.0,
             viewport_center_y - height as f64 / 2.0,
@@ -1033,6 +1505,17 @@ pub mod file_ops {
     pub fn take_pending_dropped_images() -> Vec<drafftink_core::shapes::Shape> {
         PENDING_DROPPED_IMAGES.with(|cell| std::mem::take(&mut *cell.borrow_mut()))
     }
+
+    // Thread-local storage for dropped SVG sources and the world point to center them at.
+    // They are imported by the app, which knows the SVG import mode.
+    thread_local! {
+        static PENDING_DROPPED_SVGS: RefCell<Vec<(String, kurbo::Point)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Take all pending dropped SVG sources.
+    pub fn take_pending_dropped_svgs() -> Vec<(String, kurbo::Point)> {
+        PENDING_DROPPED_SVGS.with(|cell| std::mem::take(&mut *cell.borrow_mut()))
+    }
 }
 
 /// Render a Vello scene to PNG bytes (native version - blocking).
@@ -1322,7 +1805,12 @@ pub fn spawn_png_export_async(
         readback_buffer.unmap();
 
         // Encode to PNG
-        let png_data = match encode_png(&rgba_data, width, height, scene_json.as_deref()) {
+        let image = PngRenderResult {
+            rgba_data,
+            width,
+            height,
+        };
+        let png_data = match encode_png(&image, scene_json.as_deref()) {
             Some(data) => data,
             None => {
                 log::error!("Failed to encode PNG");
@@ -1340,59 +1828,281 @@ pub fn spawn_png_export_async(
     });
 }
 
-/// MIME type keyword for embedded scene data in PNG tEXt chunks.
-const PNG_METADATA_KEYWORD: &str = "application/vnd.drafftink+json";
+/// Encode rendered pixels to PNG bytes with optional embedded scene JSON.
+fn encode_png(image: &PngRenderResult, scene_json: Option<&str>) -> Option<Vec<u8>> {
+    image
+        .to_png(scene_json)
+        .map_err(|e| log::error!("Failed to encode PNG: {:?}", e))
+        .ok()
+}
 
-/// Encode RGBA pixel data to PNG bytes with optional embedded scene JSON.
-fn encode_png(
-    rgba_data: &[u8],
-    width: u32,
-    height: u32,
-    scene_json: Option<&str>,
-) -> Option<Vec<u8>> {
-    let mut png_data = Vec::new();
-    {
-        let mut encoder = png::Encoder::new(&mut png_data, width, height);
-        encoder.set_color(png::ColorType::Rgba);
-        encoder.set_depth(png::BitDepth::Eight);
-
-        // Embed scene data as compressed zTXt chunk
-        if let Some(json) = scene_json {
-            if let Err(e) =
-                encoder.add_ztxt_chunk(PNG_METADATA_KEYWORD.to_string(), json.to_string())
-            {
-                log::warn!("Failed to add metadata chunk: {:?}", e);
+pub use drafftink_render::extract_scene_from_png;
+
+/// Seconds between recordings of local edits into the version history.
+const HISTORY_RECORD_SECS: f64 = 2.0;
+
+/// Describe recorded versions for the history panel.
+fn history_items(
+    entries: &[HistoryEntry],
+    own_peer: u64,
+    peer_names: &std::collections::HashMap<u64, String>,
+) -> Vec<HistoryItem> {
+    let now = unix_now();
+    entries
+        .iter()
+        .map(|entry| HistoryItem {
+            author: if entry.peer == own_peer {
+                "You".to_string()
+            } else {
+                peer_names
+                    .get(&entry.peer)
+                    .cloned()
+                    .unwrap_or_else(|| format!("Peer {:04x}", entry.peer & 0xffff))
+            },
+            when: format_age(now, entry.timestamp),
+            summary: entry.message.clone().unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Describe the checkpoints of a document for the checkpoints panel.
+fn checkpoint_items(document: &drafftink_core::canvas::CanvasDocument) -> Vec<CheckpointItem> {
+    let now = unix_now();
+    document
+        .checkpoints
+        .iter()
+        .map(|checkpoint| CheckpointItem {
+            id: checkpoint.id.clone(),
+            name: checkpoint.name.clone(),
+            when: format_age(now, checkpoint.created),
+        })
+        .collect()
+}
+
+/// Two versions of a document being compared.
+struct CompareState {
+    /// The older version.
+    old: drafftink_core::canvas::CanvasDocument,
+    /// The newer version, shown read-only in place of the board.
+    new: Canvas,
+    /// Changes from `old` to `new`.
+    diff: DocumentDiff,
+    /// The change the camera was last moved to.
+    focused: Option<drafftink_core::shapes::ShapeId>,
+}
+
+/// Where room links open when not running on the web.
+#[cfg(not(target_arch = "wasm32"))]
+const ROOM_LINK_BASE: &str = "https://drafft.ink/";
+
+/// Base URL of links to rooms: this page on the web, the hosted app otherwise.
+#[cfg(target_arch = "wasm32")]
+fn room_link_base() -> String {
+    crate::web::page_url().unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn room_link_base() -> String {
+    ROOM_LINK_BASE.to_string()
+}
+
+/// Label of the board when it is one side of a comparison.
+const CURRENT_BOARD_LABEL: &str = "Current board";
+
+/// Smallest area (in world units) the camera fits when moving to a change, so a
+/// small shape is shown with some of its surroundings.
+const FOCUS_MIN_SIZE: Size = Size::new(480.0, 320.0);
+
+/// Compare `old` with `new`: show `new` in place of the board, read-only, with the
+/// changes highlighted and listed in the compare panel. `target` is the checkpoint
+/// `new` comes from, or `None` for the board.
+///
+/// Takes the parts of the app state it changes, so it can be called while the UI
+/// holds the egui context.
+fn start_compare(
+    ui_state: &mut UiState,
+    compare: &mut Option<CompareState>,
+    history_preview: &mut Option<Canvas>,
+    board: &mut Canvas,
+    (old, old_label): (drafftink_core::canvas::CanvasDocument, String),
+    (new, new_label): (drafftink_core::canvas::CanvasDocument, String),
+    target: Option<String>,
+) {
+    let diff = DocumentDiff::between(&old, &new);
+    ui_state.compare = Some(CompareView {
+        old_label,
+        new_label,
+        summary: diff.to_string(),
+        changes: change_items(&old, &new, &diff),
+        focused: None,
+        targets: board
+            .document
+            .checkpoints
+            .iter()
+            .map(|checkpoint| (checkpoint.id.clone(), checkpoint.name.clone()))
+            .collect(),
+        target,
+    });
+    let mut canvas = Canvas::new();
+    canvas.document = new;
+    *compare = Some(CompareState {
+        old,
+        new: canvas,
+        diff,
+        focused: None,
+    });
+
+    // The compare panel takes the place of the history and checkpoints panels
+    ui_state.history_panel_open = false;
+    ui_state.checkpoints_panel_open = false;
+    *history_preview = None;
+    ui_state.history_preview = None;
+    board.clear_selection();
+}
+
+/// Describe the changes of a comparison for the compare panel.
+fn change_items(
+    old: &drafftink_core::canvas::CanvasDocument,
+    new: &drafftink_core::canvas::CanvasDocument,
+    diff: &DocumentDiff,
+) -> Vec<ChangeItem> {
+    diff.changes()
+        .map(|change| {
+            let shape = new
+                .get_shape(change.id)
+                .or_else(|| old.get_shape(change.id));
+            let color = DiffOverlay::color(change.kind).to_rgba8();
+            ChangeItem {
+                label: shape.map_or_else(|| "Shape".to_string(), shape_label),
+                kind: change.kind.to_string(),
+                color: egui::Color32::from_rgb(color.r, color.g, color.b),
             }
+        })
+        .collect()
+}
+
+/// Short description of a shape, e.g. `Text "Login"`.
+fn shape_label(shape: &Shape) -> String {
+    let quoted = |text: &str| {
+        let line = text.lines().next().unwrap_or_default();
+        let mut snippet: String = line.chars().take(24).collect();
+        if snippet.len() < line.len() {
+            snippet.push('…');
         }
+        format!("\"{}\"", snippet)
+    };
+    match shape {
+        Shape::Rectangle(_) => "Rectangle".to_string(),
+        Shape::Ellipse(_) => "Ellipse".to_string(),
+        Shape::Line(_) => "Line".to_string(),
+        Shape::Arrow(_) => "Arrow".to_string(),
+        Shape::Freehand(_) => "Drawing".to_string(),
+        Shape::Text(text) => format!("Text {}", quoted(&text.content)),
+        Shape::Group(group) => format!("Group of {}", group.children().len()),
+        Shape::Image(_) => "Image".to_string(),
+        Shape::Math(math) => format!("Math {}", quoted(&math.latex)),
+    }
+}
 
-        let mut writer = match encoder.write_header() {
-            Ok(w) => w,
-            Err(e) => {
-                log::error!("Failed to write PNG header: {:?}", e);
+/// Fit the camera to `bounds`, grown to at least [`FOCUS_MIN_SIZE`].
+fn focus_camera(camera: &mut drafftink_core::camera::Camera, bounds: kurbo::Rect, viewport: Size) {
+    let grow_x = ((FOCUS_MIN_SIZE.width - bounds.width()) / 2.0).max(0.0);
+    let grow_y = ((FOCUS_MIN_SIZE.height - bounds.height()) / 2.0).max(0.0);
+    camera.fit_to_bounds(bounds.inflate(grow_x, grow_y), viewport, 50.0);
+}
+
+/// Current Unix time in seconds.
+fn unix_now() -> i64 {
+    web_time::SystemTime::now()
+        .duration_since(web_time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Format the time since a Unix timestamp, e.g. "5 min ago".
+fn format_age(now: i64, timestamp: i64) -> String {
+    if timestamp <= 0 {
+        return "unknown time".to_string();
+    }
+    let seconds = (now - timestamp).max(0);
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{} min ago", seconds / 60),
+        3600..86400 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+/// Largest size of a placed image before it is scaled down to fit.
+const MAX_PLACED_IMAGE_SIZE: f64 = 800.0;
+
+/// Import SVG markup as editable shapes (grouped) or as a single vector image.
+/// Returns `None` if the text isn't an SVG document with drawable content.
+pub fn svg_import_shapes(source: &str, mode: SvgImportMode) -> Option<Vec<Shape>> {
+    use drafftink_core::canvas::CanvasDocument;
+
+    match mode {
+        SvgImportMode::Shapes => CanvasDocument::shapes_from_svg_clipboard(source),
+        SvgImportMode::Image => {
+            if !drafftink_core::svg::is_svg(source) {
                 return None;
             }
-        };
-
-        if let Err(e) = writer.write_image_data(rgba_data) {
-            log::error!("Failed to write PNG data: {:?}", e);
-            return None;
+            let mut image = drafftink_core::svg::svg_image(source).ok()?;
+            if image.width > MAX_PLACED_IMAGE_SIZE || image.height > MAX_PLACED_IMAGE_SIZE {
+                image = image.fit_within(MAX_PLACED_IMAGE_SIZE, MAX_PLACED_IMAGE_SIZE);
+            }
+            Some(vec![Shape::Image(image)])
         }
     }
+}
 
-    Some(png_data)
+/// Open a tldraw file as a document, logging the elements that could not be imported.
+pub fn document_from_tldraw(json: &str) -> Result<drafftink_core::canvas::CanvasDocument, String> {
+    let (doc, unsupported) = drafftink_core::canvas::CanvasDocument::from_tldraw(json)?;
+    if !unsupported.is_empty() {
+        log::warn!(
+            "tldraw import skipped {} unsupported elements: {}",
+            unsupported.total(),
+            unsupported
+        );
+    }
+    Ok(doc)
 }
 
-/// Extract embedded scene JSON from PNG data, if present.
-pub fn extract_scene_from_png(png_data: &[u8]) -> Option<String> {
-    let decoder = png::Decoder::new(std::io::Cursor::new(png_data));
-    let reader = decoder.read_info().ok()?;
+/// Open an SVG file as a document: the embedded DrafftInk scene if it has one,
+/// otherwise its content imported in `mode`.
+pub fn document_from_svg(
+    source: &str,
+    mode: SvgImportMode,
+) -> Result<drafftink_core::canvas::CanvasDocument, String> {
+    use drafftink_core::canvas::CanvasDocument;
 
-    for chunk in &reader.info().compressed_latin1_text {
-        if chunk.keyword == PNG_METADATA_KEYWORD {
-            return chunk.get_text().ok();
+    if let Some(json) = drafftink_render::extract_scene_from_svg(source) {
+        return CanvasDocument::from_json(&json).map_err(|e| e.to_string());
+    }
+    match mode {
+        SvgImportMode::Shapes => CanvasDocument::from_svg(source),
+        SvgImportMode::Image => {
+            let shapes = svg_import_shapes(source, mode)
+                .ok_or_else(|| "SVG has no drawable content".to_string())?;
+            let mut doc = CanvasDocument::new();
+            for shape in shapes {
+                doc.add_shape(shape);
+            }
+            Ok(doc)
         }
     }
-    None
+}
+
+/// Move shapes so the center of their combined bounds is at `center`.
+fn center_shapes_at(shapes: &mut [Shape], center: Point) {
+    let Some(bounds) = shapes.iter().map(|s| s.bounds()).reduce(|a, b| a.union(b)) else {
+        return;
+    };
+    let offset = center - bounds.center();
+    for shape in shapes {
+        shape.transform(kurbo::Affine::translate(offset));
+    }
 }
 
 /// Parse a CSS color string like "#ff0000" or "rgb(255, 0, 0)".
@@ -1475,6 +2185,21 @@ struct AppState {
     /// Remote peers in the current room (for cursor rendering).
     remote_peers: std::collections::HashMap<String, RemotePeer>,
 
+    /// Running auto-layout animation and when it started.
+    layout_animation: Option<(LayoutAnimation, web_time::Instant)>,
+
+    // Version history
+    /// Versions recorded in the CRDT, oldest first (refreshed while the panel is open).
+    history_entries: Vec<HistoryEntry>,
+    /// Past version shown instead of the board while previewing history.
+    history_preview: Option<Canvas>,
+    /// Comparison shown instead of the board in compare mode.
+    compare: Option<CompareState>,
+    /// When the local document was last recorded into the history.
+    last_history_record: web_time::Instant,
+    /// Display names of Loro peers, from their awareness updates.
+    peer_names: std::collections::HashMap<u64, String>,
+
     // Auto-save (WASM only)
     #[cfg(target_arch = "wasm32")]
     last_autosave: web_time::Instant,
@@ -1604,6 +2329,12 @@ impl App {
             collab: CollaborationManager::new(),
             websocket: None,
             remote_peers: std::collections::HashMap::new(),
+            layout_animation: None,
+            history_entries: Vec::new(),
+            history_preview: None,
+            compare: None,
+            last_history_record: web_time::Instant::now(),
+            peer_names: std::collections::HashMap::new(),
             #[cfg(target_arch = "wasm32")]
             last_autosave: web_time::Instant::now(),
             #[cfg(target_arch = "wasm32")]
@@ -1667,6 +2398,16 @@ impl App {
         // Update UI state
         state.ui_state.server_url = server_url.clone();
         state.ui_state.room_input = room.clone();
+        state.ui_state.token_input = params.token.clone().unwrap_or_default();
+        state.ui_state.key_input = params.key.clone().unwrap_or_default();
+        let key = match params.key.as_deref().map(RoomKey::decode).transpose() {
+            Ok(key) => key,
+            Err(e) => {
+                log::error!("Invalid room key in URL: {}", e);
+                state.ui_state.room_error = Some(e.to_string());
+                return;
+            }
+        };
 
         // Connect to WebSocket
         let mut ws = drafftink_core::sync::WasmWebSocket::new();
@@ -1677,7 +2418,8 @@ impl App {
                 state.ui_state.connection_state = ConnectionState::Connecting;
 
                 // Queue the join request (will be sent once connected)
-                state.collab.join_room(&room);
+                state.collab.set_room_key(key);
+                state.collab.join_room(&room, params.token.as_deref());
             }
             Err(e) => {
                 log::error!("WebSocket connect failed: {}", e);
@@ -1945,6 +2687,59 @@ impl ApplicationHandler for App {
                 if let Some(doc) = file_ops::take_pending_document() {
                     state.canvas.document = doc;
                     state.canvas.clear_selection();
+                    state.compare = None;
+                    state.ui_state.compare = None;
+                    state.needs_redraw = true;
+                }
+
+                // Check for a document picked to compare with the board
+                if let Some(doc) = file_ops::take_pending_compare() {
+                    let label = if doc.name.is_empty() {
+                        "File".to_string()
+                    } else {
+                        doc.name.clone()
+                    };
+                    let board = state.canvas.document.clone();
+                    start_compare(
+                        &mut state.ui_state,
+                        &mut state.compare,
+                        &mut state.history_preview,
+                        &mut state.canvas,
+                        (doc, label),
+                        (board, CURRENT_BOARD_LABEL.to_string()),
+                        None,
+                    );
+                    state.needs_redraw = true;
+                }
+
+                // Check for saved templates from async list
+                if let Some(templates) = file_ops::take_pending_templates() {
+                    state.ui_state.saved_templates = templates;
+                }
+
+                // Leave group editing if the entered group was removed (undo, sync, load)
+                state.canvas.prune_group_context();
+
+                // Advance auto-layout animation
+                if let Some((animation, started)) = state.layout_animation.take() {
+                    // Progress follows the clock, whatever the frame rate
+                    let progress = started.elapsed().as_secs_f64() / LAYOUT_ANIMATION_SECS;
+                    if progress >= 1.0 {
+                        animation.finish(&mut state.canvas.document);
+                        // Sync changes to collaborators
+                        if state.collab.is_in_room() {
+                            state.collab.sync_to_crdt(&state.canvas.document);
+                            state.collab.broadcast_sync();
+                            if let Some(ref ws) = state.websocket {
+                                for msg in state.collab.take_outgoing() {
+                                    let _ = ws.send(&msg);
+                                }
+                            }
+                        }
+                    } else {
+                        animation.apply(&mut state.canvas.document, progress);
+                        state.layout_animation = Some((animation, started));
+                    }
                     state.needs_redraw = true;
                 }
 
@@ -1999,7 +2794,7 @@ impl ApplicationHandler for App {
                             let _ = state.collab.crdt_mut().add_shape(&shape);
                         }
                     }
-                    log::info!("Pasted shapes from Excalidraw clipboard");
+                    log::info!("Pasted shapes from clipboard");
                     state.needs_redraw = true;
                 }
 
@@ -2017,6 +2812,28 @@ impl ApplicationHandler for App {
                     state.needs_redraw = true;
                 }
 
+                // Check for pending dropped SVGs (WASM)
+                #[cfg(target_arch = "wasm32")]
+                for (svg, center) in file_ops::take_pending_dropped_svgs() {
+                    let Some(mut shapes) = svg_import_shapes(&svg, state.ui_state.svg_import_mode)
+                    else {
+                        log::error!("Failed to import dropped SVG");
+                        continue;
+                    };
+                    center_shapes_at(&mut shapes, center);
+                    state.canvas.document.push_undo();
+                    state.canvas.clear_selection();
+                    for shape in shapes {
+                        let new_id = shape.id();
+                        state.canvas.document.add_shape(shape.clone());
+                        state.canvas.add_to_selection(new_id);
+                        if state.collab.is_in_room() {
+                            let _ = state.collab.crdt_mut().add_shape(&shape);
+                        }
+                    }
+                    state.needs_redraw = true;
+                }
+
                 // Check for pending dropped document (PNG with embedded scene)
                 #[cfg(target_arch = "wasm32")]
                 if let Some(json) = file_ops::take_pending_dropped_document() {
@@ -2054,7 +2871,7 @@ impl ApplicationHandler for App {
                             );
                             let new_text = edit_state.text();
                             if let Some(Shape::Text(text)) =
-                                state.canvas.document.get_shape_mut(text_id)
+                                state.canvas.document.find_shape_mut(text_id)
                             {
                                 text.content = new_text;
                                 text.sync_char_colors_after_edit(edit_char_pos, old_char_count);
@@ -2087,6 +2904,7 @@ impl ApplicationHandler for App {
                                 state.collab.set_room(None);
                                 state.collab.disable();
                                 state.ui_state.current_room = None;
+                                state.ui_state.clear_room_access();
                                 state.ui_state.peer_count = 0;
                                 state.remote_peers.clear();
                             }
@@ -2094,19 +2912,45 @@ impl ApplicationHandler for App {
                                 room,
                                 peer_count,
                                 initial_sync,
+                                protected,
+                                read_only,
                             } => {
                                 log::info!("Joined room: {} ({} peers)", room, peer_count);
                                 state.ui_state.current_room = Some(room.clone());
                                 state.ui_state.peer_count = peer_count;
+                                state.ui_state.room_error = None;
+                                state.ui_state.clear_room_access();
+                                state.ui_state.room_protected = protected;
+                                state.ui_state.room_read_only = read_only;
+                                // The token we joined with is the one we can share
+                                let token = state.ui_state.token_input.trim();
+                                if protected && !token.is_empty() {
+                                    let token = Some(token.to_string());
+                                    if read_only {
+                                        state.ui_state.view_token = token;
+                                    } else {
+                                        state.ui_state.edit_token = token;
+                                    }
+                                }
+
+                                state.ui_state.room_key =
+                                    state.collab.room_key().map(RoomKey::encode);
 
                                 // Update collaboration manager state
                                 state.collab.set_room(Some(room));
+                                state.collab.set_read_only(read_only);
 
-                                // Import initial state if provided
+                                // Open and import initial state if provided
                                 if let Some(data) = initial_sync {
-                                    if state.collab.import_updates(&data) {
-                                        state.collab.sync_from_crdt(&mut state.canvas.document);
-                                        log::info!("Imported initial sync data");
+                                    match state.collab.open_and_import(&data) {
+                                        Ok(_) => {
+                                            state.collab.sync_from_crdt(&mut state.canvas.document);
+                                            log::info!("Imported initial sync data");
+                                        }
+                                        Err(e) => {
+                                            log::warn!("Initial sync not imported: {}", e);
+                                            state.ui_state.room_error = Some(e);
+                                        }
                                     }
                                 }
 
@@ -2117,6 +2961,31 @@ impl ApplicationHandler for App {
                                     let _ = ws.send(&msg);
                                 }
                             }
+                            SyncEvent::AccessChanged {
+                                protected,
+                                read_only,
+                            } => {
+                                log::info!(
+                                    "Room access changed (protected: {}, read-only: {})",
+                                    protected,
+                                    read_only
+                                );
+                                if !protected {
+                                    state.ui_state.edit_token = None;
+                                    state.ui_state.view_token = None;
+                                }
+                                state.ui_state.room_protected = protected;
+                                state.ui_state.room_read_only = read_only;
+                                state.collab.set_read_only(read_only);
+                            }
+                            SyncEvent::Removed { room, message } => {
+                                log::warn!("Removed from room {}: {}", room, message);
+                                state.collab.set_room(None);
+                                state.remote_peers.clear();
+                                state.ui_state.current_room = None;
+                                state.ui_state.clear_room_access();
+                                state.ui_state.room_error = Some(message);
+                            }
                             SyncEvent::PeerJoined { peer_id } => {
                                 log::info!("Peer joined: {}", peer_id);
                                 state.ui_state.peer_count += 1;
@@ -2129,16 +2998,33 @@ impl ApplicationHandler for App {
                             }
                             SyncEvent::SyncReceived { from, data } => {
                                 log::debug!("Sync from {}: {} bytes", from, data.len());
-                                if state.collab.import_updates(&data) {
-                                    state.collab.sync_from_crdt(&mut state.canvas.document);
-                                    state.needs_redraw = true;
+                                // Data sealed with another key never reaches the CRDT
+                                match state.collab.open_and_import(&data) {
+                                    Ok(_) => {
+                                        state.collab.sync_from_crdt(&mut state.canvas.document);
+                                        state.needs_redraw = true;
+                                    }
+                                    Err(e) => {
+                                        log::warn!("Sync from {} not imported: {}", from, e);
+                                        state.ui_state.room_error = Some(e);
+                                    }
                                 }
                             }
                             SyncEvent::AwarenessReceived {
                                 from,
-                                peer_id: _,
+                                peer_id,
                                 state: awareness,
                             } => {
+                                let awareness = match state.collab.open_awareness(awareness) {
+                                    Ok(awareness) => awareness,
+                                    Err(e) => {
+                                        log::warn!("Awareness from {} ignored: {}", from, e);
+                                        continue;
+                                    }
+                                };
+                                if let Some(user) = &awareness.user {
+                                    state.peer_names.insert(peer_id, user.name.clone());
+                                }
                                 state.remote_peers.insert(
                                     from.clone(),
                                     RemotePeer {
@@ -2147,6 +3033,13 @@ impl ApplicationHandler for App {
                                     },
                                 );
                             }
+                            SyncEvent::Rejected { code, message } => {
+                                log::warn!("Server rejected a message ({:?}): {}", code, message);
+                                // Throttled messages are sent again with the next change
+                                if code != ErrorCode::RateLimited {
+                                    state.ui_state.room_error = Some(message);
+                                }
+                            }
                             SyncEvent::Error { message } => {
                                 log::error!("Sync error: {}", message);
                             }
@@ -2169,11 +3062,39 @@ impl ApplicationHandler for App {
 
                 // Update UI state from first selected shape's style
                 if let Some(&shape_id) = state.canvas.selection.first() {
-                    if let Some(shape) = state.canvas.document.get_shape(shape_id) {
+                    if let Some(shape) = state.canvas.document.find_shape(shape_id) {
                         state.ui_state.update_from_style(shape.style());
                     }
                 }
 
+                // Record local edits into the version history (rooms record on sync)
+                if !state.collab.is_in_room()
+                    && state.last_history_record.elapsed().as_secs_f64() >= HISTORY_RECORD_SECS
+                {
+                    state.collab.record_history(&state.canvas.document);
+                    state.last_history_record = web_time::Instant::now();
+                }
+                if state.ui_state.history_panel_open {
+                    state.history_entries = state.collab.history();
+                    state.ui_state.history = history_items(
+                        &state.history_entries,
+                        state.collab.peer_id(),
+                        &state.peer_names,
+                    );
+                    // The previewed version is gone if another document was opened
+                    if state
+                        .ui_state
+                        .history_preview
+                        .is_some_and(|index| index >= state.history_entries.len())
+                    {
+                        state.history_preview = None;
+                        state.ui_state.history_preview = None;
+                    }
+                }
+                if state.ui_state.checkpoints_panel_open {
+                    state.ui_state.checkpoints = checkpoint_items(&state.canvas.document);
+                }
+
                 // Sync current style to tool manager for preview shapes
                 state.canvas.tool_manager.current_style = state.ui_state.to_shape_style();
                 state.canvas.tool_manager.corner_radius = state.ui_state.corner_radius as f64;
@@ -2182,7 +3103,7 @@ impl ApplicationHandler for App {
                 let selection_count = state.canvas.selection.len();
                 let mut selected_props = if selection_count >= 1 {
                     let shape_id = state.canvas.selection[0];
-                    if let Some(shape) = state.canvas.document.get_shape(shape_id) {
+                    if let Some(shape) = state.canvas.document.find_shape(shape_id) {
                         SelectedShapeProps::from_shape_with_count(shape, selection_count)
                     } else {
                         SelectedShapeProps::default()
@@ -2279,7 +3200,7 @@ impl ApplicationHandler for App {
                                 let mut applied_to_text_range = false;
                                 if let Some((text_id, byte_range)) = &text_selection_state {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape_mut(*text_id)
+                                        state.canvas.document.find_shape_mut(*text_id)
                                     {
                                         // Convert byte range to char indices
                                         let start_char =
@@ -2306,7 +3227,7 @@ impl ApplicationHandler for App {
                                     let style = state.ui_state.to_shape_style();
                                     for &shape_id in &state.canvas.selection.clone() {
                                         if let Some(shape) =
-                                            state.canvas.document.get_shape_mut(shape_id)
+                                            state.canvas.document.find_shape_mut(shape_id)
                                         {
                                             shape.style_mut().stroke_color = style.stroke_color;
                                         }
@@ -2332,7 +3253,7 @@ impl ApplicationHandler for App {
                                 let has_selection = !state.canvas.selection.is_empty();
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         shape.style_mut().fill_color = style.fill_color;
                                     }
@@ -2353,7 +3274,7 @@ impl ApplicationHandler for App {
                                 let has_selection = !state.canvas.selection.is_empty();
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         shape.style_mut().stroke_width = width as f64;
                                     }
@@ -2440,7 +3361,7 @@ impl ApplicationHandler for App {
                             UiAction::LoadDocument => {
                                 #[cfg(not(target_arch = "wasm32"))]
                                 {
-                                    file_ops::load_document();
+                                    file_ops::load_document(state.ui_state.svg_import_mode);
                                 }
                                 #[cfg(target_arch = "wasm32")]
                                 {
@@ -2463,10 +3384,10 @@ impl ApplicationHandler for App {
                             UiAction::UploadDocument => {
                                 // Upload from file (WASM: triggers file picker, Native: same as load)
                                 #[cfg(target_arch = "wasm32")]
-                                file_ops::upload_document_async();
+                                file_ops::upload_document_async(state.ui_state.svg_import_mode);
                                 #[cfg(not(target_arch = "wasm32"))]
                                 {
-                                    file_ops::load_document();
+                                    file_ops::load_document(state.ui_state.svg_import_mode);
                                 }
                             }
                             UiAction::ClearDocument => {
@@ -2502,6 +3423,95 @@ impl ApplicationHandler for App {
                                 // Deferred - handled after egui run (needs render_cx access)
                                 deferred_action = Some(action);
                             }
+                            UiAction::ExportSvg => {
+                                let options = SvgExportOptions {
+                                    padding: state.ui_state.svg_padding,
+                                    background: (!state.ui_state.svg_transparent)
+                                        .then_some(Color::WHITE),
+                                    text_mode: state.ui_state.svg_text_mode,
+                                    ..Default::default()
+                                };
+                                let svg = if state.canvas.selection.is_empty() {
+                                    drafftink_render::export_document_svg(
+                                        &state.canvas.document,
+                                        &options,
+                                    )
+                                } else {
+                                    drafftink_render::export_selection_svg(
+                                        &state.canvas.document,
+                                        &state.canvas.selection,
+                                        &options,
+                                    )
+                                };
+                                match svg {
+                                    Some(svg) => {
+                                        file_ops::export_svg(&svg, &state.canvas.document.name)
+                                    }
+                                    None => log::info!("Nothing to export - document is empty"),
+                                }
+                            }
+                            UiAction::ExportPdf => {
+                                let options = PdfExportOptions {
+                                    page_size: state.ui_state.pdf_page_size,
+                                    margin: state.ui_state.pdf_margin,
+                                    ..Default::default()
+                                };
+                                // One page per frame, if the document has any
+                                let pdf = if state.canvas.selection.is_empty() {
+                                    drafftink_render::export_frames_pdf(
+                                        &state.canvas.document,
+                                        &options,
+                                    )
+                                } else {
+                                    drafftink_render::export_selection_pdf(
+                                        &state.canvas.document,
+                                        &state.canvas.selection,
+                                        &options,
+                                    )
+                                };
+                                match pdf {
+                                    Some(pdf) => {
+                                        file_ops::export_pdf(&pdf, &state.canvas.document.name)
+                                    }
+                                    None => log::info!("Nothing to export - document is empty"),
+                                }
+                            }
+                            UiAction::ExportExcalidraw => {
+                                if state.canvas.document.is_empty() {
+                                    log::info!("Nothing to export - document is empty");
+                                } else {
+                                    file_ops::export_excalidraw(
+                                        &state.canvas.document.to_excalidraw(),
+                                        &state.canvas.document.name,
+                                    );
+                                }
+                            }
+                            UiAction::ExportDrawio => {
+                                if state.canvas.document.is_empty() {
+                                    log::info!("Nothing to export - document is empty");
+                                } else {
+                                    file_ops::export_drawio(
+                                        &state.canvas.document.to_drawio(),
+                                        &state.canvas.document.name,
+                                    );
+                                }
+                            }
+                            UiAction::CopyExcalidraw => {
+                                match state
+                                    .canvas
+                                    .document
+                                    .to_excalidraw_clipboard(&state.canvas.selection)
+                                {
+                                    Some(json) => {
+                                        file_ops::copy_text_to_clipboard(&json);
+                                        log::info!(
+                                            "Copied {} shapes as Excalidraw",
+                                            state.canvas.selection.len()
+                                        );
+                                    }
+                                    None => log::info!("Nothing to copy - selection is empty"),
+                                }
+                            }
                             UiAction::ToggleGrid => {
                                 state.config.grid_style = state.config.grid_style.next();
                                 state.ui_state.grid_style = state.config.grid_style;
@@ -2554,7 +3564,7 @@ impl ApplicationHandler for App {
                                 use drafftink_core::shapes::Shape;
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         text.font_size = size as f64;
                                     }
@@ -2564,7 +3574,7 @@ impl ApplicationHandler for App {
                                 use drafftink_core::shapes::Shape;
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(Shape::Math(math)) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         math.font_size = size as f64;
                                         math.invalidate_cache();
@@ -2581,7 +3591,7 @@ impl ApplicationHandler for App {
                                 };
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         text.font_family = family;
                                     }
@@ -2596,7 +3606,7 @@ impl ApplicationHandler for App {
                                 };
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         text.font_weight = weight;
                                     }
@@ -2610,7 +3620,7 @@ impl ApplicationHandler for App {
                                 // Apply to selected shapes
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(Shape::Rectangle(rect)) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         rect.corner_radius = radius as f64;
                                     }
@@ -2630,6 +3640,30 @@ impl ApplicationHandler for App {
                                 state.ui_state.export_scale = scale;
                                 log::info!("Export scale: {}x", scale);
                             }
+                            UiAction::SetPdfPageSize(page_size) => {
+                                state.ui_state.pdf_page_size = page_size;
+                                log::info!("PDF page size: {}", page_size.name());
+                            }
+                            UiAction::SetPdfMargin(margin) => {
+                                state.ui_state.pdf_margin = margin;
+                                log::info!("PDF margin: {}pt", margin);
+                            }
+                            UiAction::SetSvgTextMode(mode) => {
+                                state.ui_state.svg_text_mode = mode;
+                                log::info!("SVG text: {}", mode.name());
+                            }
+                            UiAction::SetSvgPadding(padding) => {
+                                state.ui_state.svg_padding = padding;
+                                log::info!("SVG padding: {}", padding);
+                            }
+                            UiAction::SetSvgTransparent(transparent) => {
+                                state.ui_state.svg_transparent = transparent;
+                                log::info!("SVG transparent background: {}", transparent);
+                            }
+                            UiAction::SetSvgImportMode(mode) => {
+                                state.ui_state.svg_import_mode = mode;
+                                log::info!("SVG import mode: {}", mode.name());
+                            }
                             UiAction::SetSloppiness(level) => {
                                 use drafftink_core::shapes::Sloppiness;
                                 let sloppiness = match level {
@@ -2644,7 +3678,7 @@ impl ApplicationHandler for App {
                                 // Apply to selected shapes
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         shape.style_mut().sloppiness = sloppiness;
                                     }
@@ -2676,7 +3710,7 @@ impl ApplicationHandler for App {
                                 let has_selection = !state.canvas.selection.is_empty();
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         shape.style_mut().fill_pattern = fill_pattern;
                                     }
@@ -2705,7 +3739,7 @@ impl ApplicationHandler for App {
                                 // Apply to selected lines/arrows
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         match shape {
                                             Shape::Line(line) => {
@@ -2748,7 +3782,7 @@ impl ApplicationHandler for App {
                                 // Apply to selected lines/arrows
                                 for &shape_id in &state.canvas.selection.clone() {
                                     if let Some(shape) =
-                                        state.canvas.document.get_shape_mut(shape_id)
+                                        state.canvas.document.find_shape_mut(shape_id)
                                     {
                                         match shape {
                                             Shape::Line(line) => {
@@ -2841,10 +3875,18 @@ impl ApplicationHandler for App {
                                 state.remote_peers.clear();
                                 state.ui_state.connection_state = ConnectionState::Disconnected;
                                 state.ui_state.current_room = None;
+                                state.ui_state.clear_room_access();
                             }
-                            UiAction::JoinRoom(room) => {
+                            UiAction::JoinRoom(room, token, key) => {
                                 log::info!("Join room requested: {}", room);
-                                state.collab.join_room(&room);
+                                state.ui_state.room_error = None;
+                                match key.as_deref().map(RoomKey::decode).transpose() {
+                                    Ok(key) => {
+                                        state.collab.set_room_key(key);
+                                        state.collab.join_room(&room, token.as_deref());
+                                    }
+                                    Err(e) => state.ui_state.room_error = Some(e.to_string()),
+                                }
                                 // Send queued messages
                                 if let Some(ref ws) = state.websocket {
                                     for msg in state.collab.take_outgoing() {
@@ -2864,6 +3906,51 @@ impl ApplicationHandler for App {
                                 state.collab.set_room(None);
                                 state.remote_peers.clear();
                                 state.ui_state.current_room = None;
+                                state.ui_state.clear_room_access();
+                            }
+                            UiAction::ProtectRoom => {
+                                log::info!("Protecting room with new links");
+                                let edit_token = new_access_token();
+                                let view_token = new_access_token();
+                                state
+                                    .collab
+                                    .set_access(Some(edit_token.clone()), Some(view_token.clone()));
+                                // Rejoin with the new token after reconnecting
+                                state.ui_state.token_input = edit_token.clone();
+                                state.ui_state.edit_token = Some(edit_token);
+                                state.ui_state.view_token = Some(view_token);
+                                if let Some(ref ws) = state.websocket {
+                                    for msg in state.collab.take_outgoing() {
+                                        let _ = ws.send(&msg);
+                                    }
+                                }
+                            }
+                            UiAction::OpenRoom => {
+                                log::info!("Removing room protection");
+                                state.collab.set_access(None, None);
+                                state.ui_state.token_input.clear();
+                                if let Some(ref ws) = state.websocket {
+                                    for msg in state.collab.take_outgoing() {
+                                        let _ = ws.send(&msg);
+                                    }
+                                }
+                            }
+                            UiAction::CopyRoomLink(read_only) => {
+                                if let Some(ref room) = state.ui_state.current_room {
+                                    let token = if read_only {
+                                        &state.ui_state.view_token
+                                    } else {
+                                        &state.ui_state.edit_token
+                                    };
+                                    let link = room_link(
+                                        &room_link_base(),
+                                        room,
+                                        &state.ui_state.server_url,
+                                        token.as_deref(),
+                                        state.ui_state.room_key.as_deref(),
+                                    );
+                                    file_ops::copy_text_to_clipboard(&link);
+                                }
                             }
                             UiAction::SetUserName(name) => {
                                 log::info!("Set user name: {}", name);
@@ -2986,7 +4073,7 @@ impl ApplicationHandler for App {
                                     // Calculate bounds of selected shapes
                                     let mut result: Option<kurbo::Rect> = None;
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape(id) {
+                                        if let Some(shape) = state.canvas.document.find_shape(id) {
                                             let b = shape.bounds();
                                             result = Some(match result {
                                                 Some(r) => r.union(b),
@@ -3009,7 +4096,7 @@ impl ApplicationHandler for App {
                                     state.canvas.document.push_undo();
                                     let mut new_selection = Vec::new();
                                     for &id in &state.canvas.selection.clone() {
-                                        if let Some(shape) = state.canvas.document.get_shape(id) {
+                                        if let Some(shape) = state.canvas.document.find_shape(id) {
                                             let mut new_shape = shape.clone();
                                             // Generate a new unique ID for the duplicate
                                             new_shape.regenerate_id();
@@ -3041,7 +4128,7 @@ impl ApplicationHandler for App {
                                         .selection
                                         .iter()
                                         .filter_map(|&id| {
-                                            state.canvas.document.get_shape(id).cloned()
+                                            state.canvas.document.find_shape(id).cloned()
                                         })
                                         .collect();
                                     if let Ok(json) = serde_json::to_string(&shapes) {
@@ -3057,7 +4144,7 @@ impl ApplicationHandler for App {
                                         .selection
                                         .iter()
                                         .filter_map(|&id| {
-                                            state.canvas.document.get_shape(id).cloned()
+                                            state.canvas.document.find_shape(id).cloned()
                                         })
                                         .collect();
                                     if let Ok(json) = serde_json::to_string(&shapes) {
@@ -3066,7 +4153,7 @@ impl ApplicationHandler for App {
                                         // Delete the shapes
                                         state.canvas.document.push_undo();
                                         for &id in &state.canvas.selection.clone() {
-                                            state.canvas.document.remove_shape(id);
+                                            state.canvas.document.remove_nested_shape(id);
                                             if state.collab.is_in_room() {
                                                 let _ = state
                                                     .collab
@@ -3111,12 +4198,13 @@ impl ApplicationHandler for App {
                                         .canvas
                                         .selection
                                         .iter()
-                                        .filter_map(|&id| state.canvas.document.get_shape(id))
+                                        .filter_map(|&id| state.canvas.document.find_shape(id))
                                         .map(|s| s.bounds().x0)
                                         .fold(f64::INFINITY, f64::min);
                                     // Align all shapes to left
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape_mut(id)
+                                        if let Some(shape) =
+                                            state.canvas.document.find_shape_mut(id)
                                         {
                                             let bounds = shape.bounds();
                                             let delta = min_x - bounds.x0;
@@ -3134,11 +4222,12 @@ impl ApplicationHandler for App {
                                         .canvas
                                         .selection
                                         .iter()
-                                        .filter_map(|&id| state.canvas.document.get_shape(id))
+                                        .filter_map(|&id| state.canvas.document.find_shape(id))
                                         .map(|s| s.bounds().x1)
                                         .fold(f64::NEG_INFINITY, f64::max);
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape_mut(id)
+                                        if let Some(shape) =
+                                            state.canvas.document.find_shape_mut(id)
                                         {
                                             let bounds = shape.bounds();
                                             let delta = max_x - bounds.x1;
@@ -3156,11 +4245,12 @@ impl ApplicationHandler for App {
                                         .canvas
                                         .selection
                                         .iter()
-                                        .filter_map(|&id| state.canvas.document.get_shape(id))
+                                        .filter_map(|&id| state.canvas.document.find_shape(id))
                                         .map(|s| s.bounds().y0)
                                         .fold(f64::INFINITY, f64::min);
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape_mut(id)
+                                        if let Some(shape) =
+                                            state.canvas.document.find_shape_mut(id)
                                         {
                                             let bounds = shape.bounds();
                                             let delta = min_y - bounds.y0;
@@ -3178,11 +4268,12 @@ impl ApplicationHandler for App {
                                         .canvas
                                         .selection
                                         .iter()
-                                        .filter_map(|&id| state.canvas.document.get_shape(id))
+                                        .filter_map(|&id| state.canvas.document.find_shape(id))
                                         .map(|s| s.bounds().y1)
                                         .fold(f64::NEG_INFINITY, f64::max);
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape_mut(id)
+                                        if let Some(shape) =
+                                            state.canvas.document.find_shape_mut(id)
                                         {
                                             let bounds = shape.bounds();
                                             let delta = max_y - bounds.y1;
@@ -3199,7 +4290,7 @@ impl ApplicationHandler for App {
                                     // Calculate combined bounds center Y
                                     let mut combined: Option<kurbo::Rect> = None;
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape(id) {
+                                        if let Some(shape) = state.canvas.document.find_shape(id) {
                                             let b = shape.bounds();
                                             combined = Some(match combined {
                                                 Some(r) => r.union(b),
@@ -3211,7 +4302,7 @@ impl ApplicationHandler for App {
                                         let center_y = bounds.center().y;
                                         for &id in &state.canvas.selection {
                                             if let Some(shape) =
-                                                state.canvas.document.get_shape_mut(id)
+                                                state.canvas.document.find_shape_mut(id)
                                             {
                                                 let shape_center_y = shape.bounds().center().y;
                                                 let delta = center_y - shape_center_y;
@@ -3229,7 +4320,7 @@ impl ApplicationHandler for App {
                                     // Calculate combined bounds center X
                                     let mut combined: Option<kurbo::Rect> = None;
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape(id) {
+                                        if let Some(shape) = state.canvas.document.find_shape(id) {
                                             let b = shape.bounds();
                                             combined = Some(match combined {
                                                 Some(r) => r.union(b),
@@ -3241,7 +4332,7 @@ impl ApplicationHandler for App {
                                         let center_x = bounds.center().x;
                                         for &id in &state.canvas.selection {
                                             if let Some(shape) =
-                                                state.canvas.document.get_shape_mut(id)
+                                                state.canvas.document.find_shape_mut(id)
                                             {
                                                 let shape_center_x = shape.bounds().center().x;
                                                 let delta = center_x - shape_center_x;
@@ -3253,6 +4344,38 @@ impl ApplicationHandler for App {
                                     }
                                 }
                             }
+                            UiAction::DistributeHorizontal => {
+                                state.canvas.distribute_selected(SpacingAxis::Horizontal);
+                            }
+                            UiAction::DistributeVertical => {
+                                state.canvas.distribute_selected(SpacingAxis::Vertical);
+                            }
+                            UiAction::TidyUp(gap) => {
+                                state.canvas.tidy_up_selected(gap as f64);
+                            }
+                            UiAction::ArrangeInGrid(columns, gap) => {
+                                state.canvas.arrange_selected_in_grid(columns, gap as f64);
+                            }
+                            UiAction::AutoLayout(algorithm) => {
+                                // Settle a running animation before starting another
+                                if let Some((animation, _)) = state.layout_animation.take() {
+                                    animation.finish(&mut state.canvas.document);
+                                }
+                                let options = LayoutOptions {
+                                    algorithm,
+                                    elbow_connectors: state.ui_state.layout_elbow,
+                                    ..Default::default()
+                                };
+                                state.layout_animation = LayoutAnimation::new(
+                                    &state.canvas.document,
+                                    &state.canvas.selection,
+                                    &options,
+                                )
+                                .map(|animation| (animation, web_time::Instant::now()));
+                                if state.layout_animation.is_none() {
+                                    log::info!("Auto layout needs at least 2 selected shapes");
+                                }
+                            }
                             UiAction::ShowShortcuts => {
                                 state.ui_state.shortcuts_modal_open =
                                     !state.ui_state.shortcuts_modal_open;
@@ -3291,7 +4414,8 @@ impl ApplicationHandler for App {
                                 if !state.canvas.selection.is_empty() {
                                     state.canvas.document.push_undo();
                                     for &id in &state.canvas.selection {
-                                        if let Some(shape) = state.canvas.document.get_shape_mut(id)
+                                        if let Some(shape) =
+                                            state.canvas.document.find_shape_mut(id)
                                         {
                                             shape.style_mut().opacity = opacity as f64;
                                         }
@@ -3299,10 +4423,313 @@ impl ApplicationHandler for App {
                                     log::info!("Set opacity to {}%", (opacity * 100.0) as i32);
                                 }
                             }
+                            UiAction::ShowTemplateDialog => {
+                                state.ui_state.template_dialog_open = true;
+                                file_ops::list_templates_async();
+                            }
+                            UiAction::NewFromTemplate(template_id) => {
+                                let template = drafftink_core::templates::find_builtin(
+                                    &template_id,
+                                )
+                                .or_else(|| {
+                                    state
+                                        .ui_state
+                                        .saved_templates
+                                        .iter()
+                                        .find(|t| t.id == template_id)
+                                        .cloned()
+                                });
+                                if let Some(template) = template {
+                                    state.canvas.document = template.instantiate();
+                                    state.canvas.clear_selection();
+                                    state.canvas.fit_to_content();
+                                    state.ui_state.zoom_level = state.canvas.camera.zoom;
+                                    log::info!(
+                                        "Created document from template '{}'",
+                                        template.name
+                                    );
+                                    // Sync changes to collaborators
+                                    if state.collab.is_in_room() {
+                                        state.collab.sync_to_crdt(&state.canvas.document);
+                                        state.collab.broadcast_sync();
+                                        if let Some(ref ws) = state.websocket {
+                                            for msg in state.collab.take_outgoing() {
+                                                let _ = ws.send(&msg);
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            UiAction::SaveAsTemplate(name) => {
+                                let template = drafftink_core::templates::template_from_document(
+                                    &state.canvas.document,
+                                    &state.canvas.selection,
+                                    &name,
+                                );
+                                if template.is_empty() {
+                                    log::info!("Nothing to save as template");
+                                } else {
+                                    file_ops::save_template(template);
+                                }
+                            }
+                            UiAction::DeleteTemplate(template_id) => {
+                                file_ops::delete_template(&template_id);
+                            }
+                            UiAction::ToggleHistory => {
+                                state.ui_state.history_panel_open =
+                                    !state.ui_state.history_panel_open;
+                                state.ui_state.checkpoints_panel_open = false;
+                                state.history_preview = None;
+                                state.ui_state.history_preview = None;
+                                state.compare = None;
+                                state.ui_state.compare = None;
+                                // Make the latest edits show up right away
+                                if state.ui_state.history_panel_open && !state.collab.is_in_room() {
+                                    state.collab.record_history(&state.canvas.document);
+                                }
+                            }
+                            UiAction::PreviewVersion(index) => {
+                                if let Some(document) = state
+                                    .history_entries
+                                    .get(index)
+                                    .and_then(|entry| state.collab.document_at(&entry.version))
+                                {
+                                    let mut preview = Canvas::new();
+                                    preview.document = document;
+                                    state.history_preview = Some(preview);
+                                    state.ui_state.history_preview = Some(index);
+                                    state.canvas.clear_selection();
+                                }
+                            }
+                            UiAction::ExitPreview => {
+                                state.history_preview = None;
+                                state.ui_state.history_preview = None;
+                            }
+                            UiAction::RestoreVersion(index) => {
+                                state.history_preview = None;
+                                state.ui_state.history_preview = None;
+                                if let Some(version) = state
+                                    .history_entries
+                                    .get(index)
+                                    .map(|entry| entry.version.clone())
+                                {
+                                    if state
+                                        .collab
+                                        .restore_version(&mut state.canvas.document, &version)
+                                    {
+                                        state.canvas.clear_selection();
+                                        log::info!("Restored version {}", index + 1);
+                                        // Sync changes to collaborators
+                                        if state.collab.is_in_room() {
+                                            state.collab.broadcast_sync();
+                                            if let Some(ref ws) = state.websocket {
+                                                for msg in state.collab.take_outgoing() {
+                                                    let _ = ws.send(&msg);
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            UiAction::ToggleCheckpoints => {
+                                state.ui_state.checkpoints_panel_open =
+                                    !state.ui_state.checkpoints_panel_open;
+                                state.ui_state.checkpoint_notice = None;
+                                // The panels share a spot; leave any preview or comparison
+                                state.ui_state.history_panel_open = false;
+                                state.history_preview = None;
+                                state.ui_state.history_preview = None;
+                                state.compare = None;
+                                state.ui_state.compare = None;
+                            }
+                            UiAction::CreateCheckpoint(name) => {
+                                let name = if name.is_empty() {
+                                    format!(
+                                        "Checkpoint {}",
+                                        state.canvas.document.checkpoints.len() + 1
+                                    )
+                                } else {
+                                    name
+                                };
+                                state
+                                    .collab
+                                    .create_checkpoint(&mut state.canvas.document, &name);
+                                state.ui_state.checkpoint_name_input.clear();
+                                log::info!("Saved checkpoint '{}'", name);
+                                // Sync the checkpoint to collaborators
+                                if state.collab.is_in_room() {
+                                    state.collab.broadcast_sync();
+                                    if let Some(ref ws) = state.websocket {
+                                        for msg in state.collab.take_outgoing() {
+                                            let _ = ws.send(&msg);
+                                        }
+                                    }
+                                }
+                            }
+                            UiAction::CompareCheckpoint(id) => {
+                                match state
+                                    .collab
+                                    .checkpoint_document(&state.canvas.document, &id)
+                                {
+                                    Some(past) => {
+                                        let label = state
+                                            .canvas
+                                            .document
+                                            .checkpoint(&id)
+                                            .map_or_else(String::new, |c| c.name.clone());
+                                        let board = state.canvas.document.clone();
+                                        start_compare(
+                                            &mut state.ui_state,
+                                            &mut state.compare,
+                                            &mut state.history_preview,
+                                            &mut state.canvas,
+                                            (past, label),
+                                            (board, CURRENT_BOARD_LABEL.to_string()),
+                                            None,
+                                        );
+                                    }
+                                    None => {
+                                        state.ui_state.checkpoint_notice =
+                                            Some((id, "Not available outside its room".to_string()))
+                                    }
+                                }
+                            }
+                            UiAction::CompareVersion(index) => {
+                                if let Some(past) = state
+                                    .history_entries
+                                    .get(index)
+                                    .and_then(|entry| state.collab.document_at(&entry.version))
+                                {
+                                    let board = state.canvas.document.clone();
+                                    start_compare(
+                                        &mut state.ui_state,
+                                        &mut state.compare,
+                                        &mut state.history_preview,
+                                        &mut state.canvas,
+                                        (past, format!("Version {}", index + 1)),
+                                        (board, CURRENT_BOARD_LABEL.to_string()),
+                                        None,
+                                    );
+                                }
+                            }
+                            UiAction::CompareWithFile => {
+                                #[cfg(not(target_arch = "wasm32"))]
+                                file_ops::compare_with_file();
+                                #[cfg(target_arch = "wasm32")]
+                                file_ops::compare_with_file_async();
+                            }
+                            UiAction::SetCompareTarget(target) => {
+                                // The newer side: a checkpoint, or the board again
+                                let new = match &target {
+                                    Some(id) => state
+                                        .collab
+                                        .checkpoint_document(&state.canvas.document, id)
+                                        .zip(state.canvas.document.checkpoint(id))
+                                        .map(|(doc, checkpoint)| (doc, checkpoint.name.clone())),
+                                    None => Some((
+                                        state.canvas.document.clone(),
+                                        CURRENT_BOARD_LABEL.to_string(),
+                                    )),
+                                };
+                                match new {
+                                    Some(new) => {
+                                        if let (Some(compare), Some(view)) =
+                                            (state.compare.take(), state.ui_state.compare.take())
+                                        {
+                                            start_compare(
+                                                &mut state.ui_state,
+                                                &mut state.compare,
+                                                &mut state.history_preview,
+                                                &mut state.canvas,
+                                                (compare.old, view.old_label),
+                                                new,
+                                                target,
+                                            );
+                                        }
+                                    }
+                                    None => log::warn!("Checkpoint not available outside its room"),
+                                }
+                            }
+                            UiAction::FocusChange(index) => {
+                                if let Some(compare) = state.compare.as_mut() {
+                                    if let Some(change) = compare.diff.changes().nth(index) {
+                                        // Show where the shape was and where it is now
+                                        let bounds = [
+                                            compare.old.get_shape(change.id),
+                                            compare.new.document.get_shape(change.id),
+                                        ]
+                                        .into_iter()
+                                        .flatten()
+                                        .map(Shape::rotated_bounds)
+                                        .reduce(|a, b| a.union(b));
+                                        if let Some(bounds) = bounds {
+                                            focus_camera(
+                                                &mut state.canvas.camera,
+                                                bounds,
+                                                state.canvas.viewport_size,
+                                            );
+                                        }
+                                        compare.focused = Some(change.id);
+                                        if let Some(view) = state.ui_state.compare.as_mut() {
+                                            view.focused = Some(index);
+                                        }
+                                    }
+                                }
+                            }
+                            UiAction::ExitCompare => {
+                                state.compare = None;
+                                state.ui_state.compare = None;
+                            }
+                            UiAction::ForkCheckpoint(id) => {
+                                match state
+                                    .collab
+                                    .checkpoint_document(&state.canvas.document, &id)
+                                {
+                                    Some(past) => {
+                                        let name = past.name.clone();
+                                        file_ops::fork_document(past, name);
+                                    }
+                                    None => {
+                                        state.ui_state.checkpoint_notice =
+                                            Some((id, "Not available outside its room".to_string()))
+                                    }
+                                }
+                            }
+                            UiAction::DeleteCheckpoint(id) => {
+                                if state
+                                    .collab
+                                    .remove_checkpoint(&mut state.canvas.document, &id)
+                                    && state.collab.is_in_room()
+                                {
+                                    state.collab.broadcast_sync();
+                                    if let Some(ref ws) = state.websocket {
+                                        for msg in state.collab.take_outgoing() {
+                                            let _ = ws.send(&msg);
+                                        }
+                                    }
+                                }
+                            }
+                            UiAction::ExportVersion(index) => {
+                                if let Some(document) = state
+                                    .history_entries
+                                    .get(index)
+                                    .and_then(|entry| state.collab.document_at(&entry.version))
+                                {
+                                    let name = format!(
+                                        "{} (version {})",
+                                        state.canvas.document.name,
+                                        index + 1
+                                    );
+                                    #[cfg(target_arch = "wasm32")]
+                                    file_ops::download_document(&document, &name);
+                                    #[cfg(not(target_arch = "wasm32"))]
+                                    file_ops::save_document(&document, &name);
+                                }
+                            }
                             UiAction::UpdateMathLatex(shape_id, latex) => {
                                 state.canvas.document.push_undo();
                                 if let Some(Shape::Math(math)) =
-                                    state.canvas.document.get_shape_mut(shape_id)
+                                    state.canvas.document.find_shape_mut(shape_id)
                                 {
                                     math.set_latex(latex);
                                     log::info!("Updated math LaTeX");
@@ -3364,12 +4791,9 @@ impl ApplicationHandler for App {
                                             height,
                                         ) {
                                             let scene_json = state.canvas.document.to_json().ok();
-                                            if let Some(png_data) = encode_png(
-                                                &result.rgba_data,
-                                                result.width,
-                                                result.height,
-                                                scene_json.as_deref(),
-                                            ) {
+                                            if let Some(png_data) =
+                                                encode_png(&result, scene_json.as_deref())
+                                            {
                                                 file_ops::export_png(
                                                     &png_data,
                                                     &state.canvas.document.name,
@@ -3519,7 +4943,25 @@ impl ApplicationHandler for App {
 
                 let smart_guides = state.event_handler.smart_guides.clone();
 
-                let render_ctx = RenderContext::new(&state.canvas, viewport_size)
+                // Show the previewed or compared version instead of the board
+                if let Some(preview) = state.history_preview.as_mut() {
+                    preview.camera = state.canvas.camera.clone();
+                    preview.viewport_size = state.canvas.viewport_size;
+                }
+                if let Some(compare) = state.compare.as_mut() {
+                    compare.new.camera = state.canvas.camera.clone();
+                    compare.new.viewport_size = state.canvas.viewport_size;
+                }
+                let canvas = match (&state.compare, &state.history_preview) {
+                    (Some(compare), _) => &compare.new,
+                    (None, Some(preview)) => preview,
+                    (None, None) => &state.canvas,
+                };
+                let diff_overlay = state.compare.as_ref().map(|compare| {
+                    DiffOverlay::new(&compare.old, &compare.diff).with_focus(compare.focused)
+                });
+
+                let render_ctx = RenderContext::new(canvas, viewport_size)
                     .with_scale_factor(state.window.scale_factor())
                     .with_background(state.config.background_color)
                     .with_grid(state.config.grid_style)
@@ -3530,13 +4972,14 @@ impl ApplicationHandler for App {
                     .with_rotation_info(rotation_info)
                     .with_smart_guides(smart_guides)
                     .with_eraser_cursor(eraser_cursor)
-                    .with_laser_pointer(laser_pointer);
+                    .with_laser_pointer(laser_pointer)
+                    .with_diff_overlay(diff_overlay);
 
                 state.shape_renderer.build_scene(&render_ctx);
 
                 // Render text in edit mode (with cursor and selection)
                 if let Some(text_id) = state.event_handler.editing_text {
-                    if let Some(Shape::Text(text)) = state.canvas.document.get_shape(text_id) {
+                    if let Some(Shape::Text(text)) = state.canvas.document.find_shape(text_id) {
                         let camera_transform = state.canvas.camera.transform();
 
                         // Ensure edit state exists
@@ -3799,7 +5242,7 @@ impl ApplicationHandler for App {
                 if state.input.is_button_pressed(MouseButton::Left) {
                     // Handle text selection dragging first
                     if let Some(text_id) = state.event_handler.editing_text {
-                        if let Some(Shape::Text(text)) = state.canvas.document.get_shape(text_id) {
+                        if let Some(Shape::Text(text)) = state.canvas.document.find_shape(text_id) {
                             // Convert drag position to text-local coordinates
                             let local_x = (world_point.x - text.position.x) as f32;
                             let local_y = (world_point.y - text.position.y) as f32;
@@ -3896,8 +5339,13 @@ impl ApplicationHandler for App {
                 button,
                 ..
             } => {
-                // Skip canvas processing if egui wants the pointer
-                if egui_wants_input {
+                // Skip canvas processing if egui wants the pointer (or a past version is
+                // previewed or compared, or we only follow a room, which are read-only)
+                if egui_wants_input
+                    || state.history_preview.is_some()
+                    || state.compare.is_some()
+                    || state.collab.is_read_only()
+                {
                     state.needs_redraw = true;
                     state.window.request_redraw();
                     return;
@@ -3922,14 +5370,13 @@ impl ApplicationHandler for App {
                                 // Check if click is still on the text being edited
                                 let hits = state
                                     .canvas
-                                    .document
                                     .shapes_at_point(world_point, 5.0 / state.canvas.camera.zoom);
                                 let clicked_on_editing =
                                     hits.first().map(|&id| id == text_id).unwrap_or(false);
 
                                 if clicked_on_editing {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape(text_id)
+                                        state.canvas.document.find_shape(text_id)
                                     {
                                         // Convert click to text-local coordinates
                                         let local_x = (world_point.x - text.position.x) as f32;
@@ -3982,7 +5429,7 @@ impl ApplicationHandler for App {
                                 // Check if we just entered text edit mode
                                 if let Some(text_id) = state.event_handler.editing_text {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape(text_id)
+                                        state.canvas.document.find_shape(text_id)
                                     {
                                         let mut edit_state = TextEditState::new(
                                             &text.content,
@@ -4007,7 +5454,7 @@ impl ApplicationHandler for App {
                                 if let Some(math_id) = state.event_handler.pending_math_edit.take()
                                 {
                                     if let Some(Shape::Math(math)) =
-                                        state.canvas.document.get_shape(math_id)
+                                        state.canvas.document.find_shape(math_id)
                                     {
                                         state.ui_state.math_editor =
                                             Some((math_id, math.latex.clone()));
@@ -4053,7 +5500,7 @@ impl ApplicationHandler for App {
                             if state.text_edit_state.is_none() {
                                 if let Some(text_id) = state.event_handler.editing_text {
                                     if let Some(Shape::Text(text)) =
-                                        state.canvas.document.get_shape(text_id)
+                                        state.canvas.document.find_shape(text_id)
                                     {
                                         let mut edit_state = TextEditState::new(
                                             &text.content,
@@ -4182,8 +5629,13 @@ impl ApplicationHandler for App {
             }
 
             WindowEvent::KeyboardInput { event, .. } => {
-                // Skip canvas processing if egui wants keyboard
-                if egui_wants_input {
+                // Skip canvas processing if egui wants keyboard (or a past version is
+                // previewed or compared, or we only follow a room, which are read-only)
+                if egui_wants_input
+                    || state.history_preview.is_some()
+                    || state.compare.is_some()
+                    || state.collab.is_read_only()
+                {
                     state.needs_redraw = true;
                     state.window.request_redraw();
                     return;
@@ -4195,7 +5647,7 @@ impl ApplicationHandler for App {
                         // Initialize edit state if needed
                         if state.text_edit_state.is_none() {
                             if let Some(Shape::Text(text)) =
-                                state.canvas.document.get_shape(text_id)
+                                state.canvas.document.find_shape(text_id)
                             {
                                 let mut edit_state =
                                     TextEditState::new(&text.content, text.font_size as f32);
@@ -4286,7 +5738,7 @@ impl ApplicationHandler for App {
                                         // Sync content before exiting
                                         let new_text = edit_state.text();
                                         if let Some(Shape::Text(text)) =
-                                            state.canvas.document.get_shape_mut(text_id)
+                                            state.canvas.document.find_shape_mut(text_id)
                                         {
                                             text.content = new_text;
                                             text.sync_char_colors_after_edit(
@@ -4301,7 +5753,7 @@ impl ApplicationHandler for App {
                                         // Sync content back to the text shape
                                         let new_text = edit_state.text();
                                         if let Some(Shape::Text(text)) =
-                                            state.canvas.document.get_shape_mut(text_id)
+                                            state.canvas.document.find_shape_mut(text_id)
                                         {
                                             text.content = new_text;
                                             text.sync_char_colors_after_edit(
@@ -4321,7 +5773,7 @@ impl ApplicationHandler for App {
                                         // Sync content back (for cut operation)
                                         let new_text = edit_state.text();
                                         if let Some(Shape::Text(text)) =
-                                            state.canvas.document.get_shape_mut(text_id)
+                                            state.canvas.document.find_shape_mut(text_id)
                                         {
                                             text.content = new_text;
                                             text.sync_char_colors_after_edit(
@@ -4382,7 +5834,7 @@ impl ApplicationHandler for App {
                                 "o" | "O" => {
                                     #[cfg(not(target_arch = "wasm32"))]
                                     {
-                                        file_ops::load_document();
+                                        file_ops::load_document(state.ui_state.svg_import_mode);
                                     }
                                     #[cfg(target_arch = "wasm32")]
                                     {
@@ -4502,12 +5954,9 @@ impl ApplicationHandler for App {
                                                 ) {
                                                     let scene_json =
                                                         state.canvas.document.to_json().ok();
-                                                    if let Some(png_data) = encode_png(
-                                                        &result.rgba_data,
-                                                        result.width,
-                                                        result.height,
-                                                        scene_json.as_deref(),
-                                                    ) {
+                                                    if let Some(png_data) =
+                                                        encode_png(&result, scene_json.as_deref())
+                                                    {
                                                         file_ops::export_png(
                                                             &png_data,
                                                             &state.canvas.document.name,
@@ -4657,7 +6106,7 @@ impl ApplicationHandler for App {
                                             .selection
                                             .iter()
                                             .filter_map(|&id| {
-                                                state.canvas.document.get_shape(id).cloned()
+                                                state.canvas.document.find_shape(id).cloned()
                                             })
                                             .collect();
                                         if let Ok(json) = serde_json::to_string(&shapes) {
@@ -4674,7 +6123,7 @@ impl ApplicationHandler for App {
                                             .selection
                                             .iter()
                                             .filter_map(|&id| {
-                                                state.canvas.document.get_shape(id).cloned()
+                                                state.canvas.document.find_shape(id).cloned()
                                             })
                                             .collect();
                                         if let Ok(json) = serde_json::to_string(&shapes) {
@@ -4682,7 +6131,7 @@ impl ApplicationHandler for App {
                                             log::info!("Cut {} shapes", shapes.len());
                                             state.canvas.document.push_undo();
                                             for &id in &state.canvas.selection.clone() {
-                                                state.canvas.document.remove_shape(id);
+                                                state.canvas.document.remove_nested_shape(id);
                                                 if state.collab.is_in_room() {
                                                     let _ = state
                                                         .collab
@@ -4724,12 +6173,16 @@ impl ApplicationHandler for App {
                                         }
                                     }
 
-                                    // Try Excalidraw clipboard format from system clipboard (native)
+                                    // Try Excalidraw, Mermaid, DOT or SVG text from system clipboard (native)
                                     #[cfg(not(target_arch = "wasm32"))]
                                     if !pasted {
                                         if let Ok(mut cb) = arboard::Clipboard::new() {
                                             if let Ok(text) = cb.get_text() {
-                                                if let Some(shapes) = drafftink_core::canvas::CanvasDocument::shapes_from_excalidraw_clipboard(&text) {
+                                                if let Some(shapes) = drafftink_core::canvas::CanvasDocument::shapes_from_excalidraw_clipboard(&text)
+                                                    .or_else(|| drafftink_core::canvas::CanvasDocument::shapes_from_mermaid_clipboard(&text))
+                                                    .or_else(|| drafftink_core::canvas::CanvasDocument::shapes_from_dot_clipboard(&text))
+                                                    .or_else(|| svg_import_shapes(&text, state.ui_state.svg_import_mode))
+                                                {
                                                     // Center pasted shapes at mouse cursor
                                                     let cursor_world = state.canvas.camera.screen_to_world(
                                                         state.input.mouse_position(),
@@ -4756,7 +6209,7 @@ impl ApplicationHandler for App {
                                                             let _ = state.collab.crdt_mut().add_shape(&shape);
                                                         }
                                                     }
-                                                    log::info!("Pasted shapes from Excalidraw clipboard");
+                                                    log::info!("Pasted shapes from clipboard");
                                                     pasted = true;
                                                 }
                                             }
@@ -4789,7 +6242,10 @@ impl ApplicationHandler for App {
                                             .canvas
                                             .camera
                                             .screen_to_world(state.input.mouse_position());
-                                        file_ops::paste_shapes_from_clipboard_async(cursor_world);
+                                        file_ops::paste_shapes_from_clipboard_async(
+                                            cursor_world,
+                                            state.ui_state.svg_import_mode,
+                                        );
                                         let vw = state.canvas.viewport_size.width;
                                         let vh = state.canvas.viewport_size.height;
                                         let cox = state.canvas.camera.offset.x;
@@ -4806,7 +6262,8 @@ impl ApplicationHandler for App {
                                         state.canvas.document.push_undo();
                                         let mut new_selection = Vec::new();
                                         for &id in &state.canvas.selection.clone() {
-                                            if let Some(shape) = state.canvas.document.get_shape(id)
+                                            if let Some(shape) =
+                                                state.canvas.document.find_shape(id)
                                             {
                                                 let mut new_shape = shape.clone();
                                                 new_shape.regenerate_id();
@@ -4909,7 +6366,8 @@ impl ApplicationHandler for App {
                                         || state.ui_state.shortcuts_modal_open
                                         || state.ui_state.save_dialog_open
                                         || state.ui_state.open_dialog_open
-                                        || state.ui_state.open_recent_dialog_open;
+                                        || state.ui_state.open_recent_dialog_open
+                                        || state.ui_state.template_dialog_open;
 
                                     if had_open_dialog {
                                         state.ui_state.color_popover =
@@ -4920,6 +6378,10 @@ impl ApplicationHandler for App {
                                         state.ui_state.save_dialog_open = false;
                                         state.ui_state.open_dialog_open = false;
                                         state.ui_state.open_recent_dialog_open = false;
+                                        state.ui_state.template_dialog_open = false;
+                                    } else if state.canvas.editing_group().is_some() {
+                                        // Editing inside a group - step out one level
+                                        state.canvas.exit_group();
                                     } else {
                                         // No dialog open - switch to Select tool
                                         state.canvas.tool_manager.cancel();
@@ -4943,7 +6405,7 @@ impl ApplicationHandler for App {
                                         let translation = kurbo::Affine::translate(delta);
                                         for &id in &state.canvas.selection {
                                             if let Some(shape) =
-                                                state.canvas.document.get_shape_mut(id)
+                                                state.canvas.document.find_shape_mut(id)
                                             {
                                                 shape.transform(translation);
                                             }
@@ -4971,6 +6433,53 @@ impl ApplicationHandler for App {
                 // Handle dropped image files
                 if let Some(ext) = path.extension() {
                     let ext_str = ext.to_string_lossy().to_lowercase();
+                    if ext_str == "svg" {
+                        let Ok(svg) = std::fs::read_to_string(&path) else {
+                            log::error!("Failed to read dropped file: {:?}", path);
+                            return;
+                        };
+                        // SVGs exported by DrafftInk carry the scene in their metadata
+                        if let Some(json) = drafftink_render::extract_scene_from_svg(&svg) {
+                            match drafftink_core::canvas::CanvasDocument::from_json(&json) {
+                                Ok(doc) => {
+                                    log::info!("Found embedded scene in SVG, loading as document");
+                                    state.canvas.document = doc;
+                                    state.canvas.clear_selection();
+                                    state.canvas.camera.reset();
+                                    state.needs_redraw = true;
+                                    state.window.request_redraw();
+                                }
+                                Err(e) => log::error!("Failed to parse embedded document: {}", e),
+                            }
+                            return;
+                        }
+
+                        let Some(mut shapes) =
+                            svg_import_shapes(&svg, state.ui_state.svg_import_mode)
+                        else {
+                            log::error!("Failed to import dropped SVG: {:?}", path);
+                            return;
+                        };
+                        let viewport_center = state.canvas.camera.screen_to_world(Point::new(
+                            state.canvas.viewport_size.width / 2.0,
+                            state.canvas.viewport_size.height / 2.0,
+                        ));
+                        center_shapes_at(&mut shapes, viewport_center);
+                        state.canvas.document.push_undo();
+                        state.canvas.clear_selection();
+                        for shape in shapes {
+                            let new_id = shape.id();
+                            state.canvas.document.add_shape(shape.clone());
+                            state.canvas.add_to_selection(new_id);
+                            if state.collab.is_in_room() {
+                                let _ = state.collab.crdt_mut().add_shape(&shape);
+                            }
+                        }
+                        log::info!("Imported dropped SVG: {:?}", path.file_name());
+                        state.needs_redraw = true;
+                        state.window.request_redraw();
+                        return;
+                    }
                     if matches!(ext_str.as_str(), "png" | "jpg" | "jpeg" | "webp") {
                         if let Ok(data) = std::fs::read(&path) {
                             // Check for embedded scene data in PNG files
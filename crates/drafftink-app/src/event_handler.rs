@@ -15,7 +15,7 @@ use drafftink_core::snap::{
     snap_to_grid,
 };
 use drafftink_core::tools::ToolKind;
-use kurbo::{Point, Rect, Size};
+use kurbo::{Affine, Point, Rect, Size};
 
 /// Maximum number of snap candidates (like Inkscape's limit of 200).
 const MAX_SNAP_CANDIDATES: usize = 200;
@@ -50,8 +50,8 @@ fn collect_snap_candidates(
     let viewport = canvas.visible_world_bounds();
     let mut rects: Vec<Rect> = Vec::new();
     for shape in canvas
-        .document
-        .shapes_ordered()
+        .context_shapes()
+        .into_iter()
         .filter(|s| !exclude_ids.contains(&s.id()))
         .filter(|s| {
             !viewport
@@ -201,13 +201,13 @@ impl EventHandler {
     pub fn cancel(&mut self, canvas: &mut Canvas) {
         // Restore original shapes if manipulating
         if let Some(manip) = self.manipulation.take() {
-            if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+            if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                 *shape = manip.original_shape;
             }
         }
         if let Some(mm) = self.multi_move.take() {
             for (id, original) in mm.original_shapes {
-                if let Some(shape) = canvas.document.get_shape_mut(id) {
+                if let Some(shape) = canvas.document.find_shape_mut(id) {
                     *shape = original;
                 }
             }
@@ -234,7 +234,7 @@ impl EventHandler {
     /// This updates both the local state and the Canvas's WidgetManager.
     /// Stores the top-left handle position and size to preserve anchor after editing.
     pub fn enter_text_edit(&mut self, canvas: &mut Canvas, id: ShapeId) {
-        if let Some(shape) = canvas.document.get_shape(id) {
+        if let Some(shape) = canvas.document.find_shape(id) {
             // Store the actual top-left handle position
             self.text_edit_anchor = get_handles(shape)
                 .into_iter()
@@ -256,7 +256,7 @@ impl EventHandler {
         if let Some(id) = self.editing_text {
             let should_delete = canvas
                 .document
-                .get_shape(id)
+                .find_shape(id)
                 .map(|shape| {
                     if let Shape::Text(text) = shape {
                         text.content.trim().is_empty()
@@ -270,7 +270,7 @@ impl EventHandler {
                 canvas.remove_shape(id);
             } else if let Some(anchor) = self.text_edit_anchor {
                 // Update position to keep anchor fixed with current size
-                if let Some(Shape::Text(text)) = canvas.document.get_shape_mut(id) {
+                if let Some(Shape::Text(text)) = canvas.document.find_shape_mut(id) {
                     let bounds = text.bounds();
                     let half_w = bounds.width() / 2.0;
                     let half_h = bounds.height() / 2.0;
@@ -318,7 +318,7 @@ impl EventHandler {
                     .document
                     .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
                 if let Some(&id) = hits.first() {
-                    if let Some(shape @ Shape::Text(_)) = canvas.document.get_shape(id) {
+                    if let Some(shape @ Shape::Text(_)) = canvas.document.find_shape(id) {
                         if let Some(handle) = hit_test_handles(shape, world_point, handle_tolerance)
                         {
                             return Some(Some(handle));
@@ -330,17 +330,15 @@ impl EventHandler {
                 }
             }
             ToolKind::Select => {
+                let point = canvas.to_context_point(world_point);
                 for &shape_id in &canvas.selection {
-                    if let Some(shape) = canvas.document.get_shape(shape_id) {
-                        if let Some(handle) = hit_test_handles(shape, world_point, handle_tolerance)
-                        {
+                    if let Some(shape) = canvas.document.find_shape(shape_id) {
+                        if let Some(handle) = hit_test_handles(shape, point, handle_tolerance) {
                             return Some(Some(handle));
                         }
                     }
                 }
-                let hits = canvas
-                    .document
-                    .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                let hits = canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
                 if !hits.is_empty() {
                     return Some(None); // move
                 }
@@ -361,9 +359,7 @@ impl EventHandler {
     ) {
         // If we're editing text and click elsewhere, stop editing
         if self.editing_text.is_some() {
-            let hits = canvas
-                .document
-                .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+            let hits = canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
             let clicked_on_editing = hits
                 .first()
                 .map(|&id| Some(id) == self.editing_text)
@@ -383,7 +379,7 @@ impl EventHandler {
                     .document
                     .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
                 if let Some(&id) = hits.first() {
-                    if let Some(shape @ Shape::Text(_)) = canvas.document.get_shape(id) {
+                    if let Some(shape @ Shape::Text(_)) = canvas.document.find_shape(id) {
                         let boundary_tolerance = 8.0 / canvas.camera.zoom;
                         let handle_tolerance = HANDLE_HIT_TOLERANCE / canvas.camera.zoom;
 
@@ -424,20 +420,31 @@ impl EventHandler {
                 // If not clicking on text, will create new text on release
             }
             ToolKind::Select => {
+                // Handles and moves work in the coordinates of the group being edited
+                let mut point = canvas.to_context_point(world_point);
+
                 // Check for double-click on text shape to enter edit mode
                 if input.is_double_click() {
-                    let hits = canvas
-                        .document
-                        .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                    let hits = canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
                     if let Some(&id) = hits.first() {
-                        if let Some(Shape::Text(_)) = canvas.document.get_shape(id) {
+                        if let Some(Shape::Group(_)) = canvas.document.find_shape(id) {
+                            // Double-click on group - enter it and select the child under the cursor
+                            canvas.enter_group(id);
+                            let child_hits =
+                                canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                            if let Some(&child_id) = child_hits.first() {
+                                canvas.select(child_id);
+                            }
+                            return;
+                        }
+                        if let Some(Shape::Text(_)) = canvas.document.find_shape(id) {
                             // Double-click on text - enter edit mode
                             self.enter_text_edit(canvas, id);
                             canvas.clear_selection();
                             canvas.select(id);
                             return;
                         }
-                        if let Some(Shape::Math(_)) = canvas.document.get_shape(id) {
+                        if let Some(Shape::Math(_)) = canvas.document.find_shape(id) {
                             // Double-click on math - open editor
                             self.pending_math_edit = Some(id);
                             canvas.clear_selection();
@@ -449,13 +456,13 @@ impl EventHandler {
                     // Check for double-click on rotation handle to reset rotation
                     let handle_tolerance = HANDLE_HIT_TOLERANCE / canvas.camera.zoom;
                     for &shape_id in &canvas.selection {
-                        if let Some(shape) = canvas.document.get_shape(shape_id) {
+                        if let Some(shape) = canvas.document.find_shape(shape_id) {
                             if let Some(HandleKind::Rotate) =
-                                hit_test_handles(shape, world_point, handle_tolerance)
+                                hit_test_handles(shape, point, handle_tolerance)
                             {
                                 // Double-click on rotation handle - reset to 0°
                                 canvas.document.push_undo();
-                                if let Some(shape) = canvas.document.get_shape_mut(shape_id) {
+                                if let Some(shape) = canvas.document.find_shape_mut(shape_id) {
                                     shape.set_rotation(0.0);
                                 }
                                 return;
@@ -468,15 +475,14 @@ impl EventHandler {
                 let handle_tolerance = HANDLE_HIT_TOLERANCE / canvas.camera.zoom;
 
                 for &shape_id in &canvas.selection {
-                    if let Some(shape) = canvas.document.get_shape(shape_id) {
-                        if let Some(handle_kind) =
-                            hit_test_handles(shape, world_point, handle_tolerance)
+                    if let Some(shape) = canvas.document.find_shape(shape_id) {
+                        if let Some(handle_kind) = hit_test_handles(shape, point, handle_tolerance)
                         {
                             // Start handle manipulation
                             self.manipulation = Some(ManipulationState::new(
                                 shape_id,
                                 Some(handle_kind),
-                                world_point,
+                                point,
                                 shape.clone(),
                             ));
                             return;
@@ -485,9 +491,19 @@ impl EventHandler {
                 }
 
                 // Check for shape hit (for selection or move)
-                let hits = canvas
-                    .document
-                    .shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                let mut hits = canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                if hits.is_empty() && canvas.editing_group().is_some() {
+                    // Clicking outside the group being edited returns to top-level editing
+                    let outside_group = canvas
+                        .editing_group()
+                        .and_then(|id| canvas.document.find_shape(id))
+                        .is_none_or(|group| !group.bounds().contains(point));
+                    if outside_group {
+                        canvas.exit_all_groups();
+                        point = world_point;
+                        hits = canvas.shapes_at_point(world_point, 5.0 / canvas.camera.zoom);
+                    }
+                }
                 if let Some(&id) = hits.first() {
                     if input.shift() {
                         // Add to/toggle selection
@@ -507,14 +523,14 @@ impl EventHandler {
                         // Start move - use MultiMoveState for all selected shapes
                         let mut original_shapes = std::collections::HashMap::new();
                         for &shape_id in &canvas.selection {
-                            if let Some(shape) = canvas.document.get_shape(shape_id) {
+                            if let Some(shape) = canvas.document.find_shape(shape_id) {
                                 original_shapes.insert(shape_id, shape.clone());
                             }
                         }
 
                         if !original_shapes.is_empty() {
-                            // Alt/Option + drag = duplicate
-                            if input.alt() {
+                            // Alt/Option + drag = duplicate (top-level shapes only)
+                            if input.alt() && canvas.editing_group().is_none() {
                                 let mut mm =
                                     MultiMoveState::new_duplicate(world_point, original_shapes);
                                 // Create duplicates immediately with new IDs
@@ -532,8 +548,7 @@ impl EventHandler {
                                 }
                                 self.multi_move = Some(mm);
                             } else {
-                                self.multi_move =
-                                    Some(MultiMoveState::new(world_point, original_shapes));
+                                self.multi_move = Some(MultiMoveState::new(point, original_shapes));
                             }
                         }
                     }
@@ -614,18 +629,18 @@ impl EventHandler {
             if matches!(manip.handle, Some(HandleKind::Rotate)) {
                 // Rotation was already applied during drag, just need to push undo
                 // Get the current rotation from the shape
-                if let Some(shape) = canvas.document.get_shape(manip.shape_id) {
+                if let Some(shape) = canvas.document.find_shape(manip.shape_id) {
                     let current_rotation = shape.rotation();
                     let original_rotation = manip.original_shape.rotation();
 
                     // Only push undo if rotation actually changed
                     if (current_rotation - original_rotation).abs() > 0.001 {
                         // Restore original, push undo, then re-apply current rotation
-                        if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+                        if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                             *shape = manip.original_shape.clone();
                         }
                         canvas.document.push_undo();
-                        if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+                        if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                             shape.set_rotation(current_rotation);
                         }
                     }
@@ -641,14 +656,14 @@ impl EventHandler {
             if delta.x.abs() > 0.1 || delta.y.abs() > 0.1 {
                 // Push undo state before finalizing (restore original, then re-apply)
                 // First restore the original shape
-                if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+                if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                     *shape = manip.original_shape.clone();
                 }
                 // Now push undo and apply the final change
                 canvas.document.push_undo();
                 let new_shape =
                     apply_manipulation(&manip.original_shape, manip.handle, delta, input.shift());
-                if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+                if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                     *shape = new_shape;
                 }
             }
@@ -671,7 +686,7 @@ impl EventHandler {
                         if let Some(orig) = original_shape {
                             let mut new_shape = orig.clone();
                             new_shape.transform(translation);
-                            if let Some(shape) = canvas.document.get_shape_mut(dup_id) {
+                            if let Some(shape) = canvas.document.find_shape_mut(dup_id) {
                                 *shape = new_shape;
                             }
                         }
@@ -693,7 +708,7 @@ impl EventHandler {
                 if delta.x.abs() > 0.1 || delta.y.abs() > 0.1 {
                     // First restore all original shapes
                     for (shape_id, original_shape) in &mm.original_shapes {
-                        if let Some(shape) = canvas.document.get_shape_mut(*shape_id) {
+                        if let Some(shape) = canvas.document.find_shape_mut(*shape_id) {
                             *shape = original_shape.clone();
                         }
                     }
@@ -704,7 +719,7 @@ impl EventHandler {
                     for (shape_id, original_shape) in &mm.original_shapes {
                         let mut new_shape = original_shape.clone();
                         new_shape.transform(translation);
-                        if let Some(shape) = canvas.document.get_shape_mut(*shape_id) {
+                        if let Some(shape) = canvas.document.find_shape_mut(*shape_id) {
                             *shape = new_shape;
                         }
                     }
@@ -718,7 +733,7 @@ impl EventHandler {
             let rect = sel_rect.to_rect();
             // Only select if rectangle has meaningful size
             if rect.width() > 2.0 && rect.height() > 2.0 {
-                let shapes_in_rect = canvas.document.shapes_in_rect(rect);
+                let shapes_in_rect = canvas.shapes_in_rect(rect);
                 if !input.shift() {
                     canvas.clear_selection();
                 }
@@ -875,16 +890,28 @@ impl EventHandler {
         self.last_angle_snap = None;
         self.smart_guides.clear();
 
+        // Shapes inside a rotated group move in the group's coordinates. The grid and
+        // guides are drawn on the canvas, so they don't apply there.
+        let context_transform = canvas.context_transform();
+        let (world_point, grid_snap_enabled, smart_snap_enabled) = if context_transform
+            != Affine::IDENTITY
+            && (self.manipulation.is_some() || self.multi_move.is_some())
+        {
+            (context_transform.inverse() * world_point, false, false)
+        } else {
+            (world_point, grid_snap_enabled, smart_snap_enabled)
+        };
+
         // If we're manipulating a shape, update it
         if let Some(manip) = &mut self.manipulation {
             // Check if this is a rotation handle
             if matches!(manip.handle, Some(HandleKind::Rotate)) {
                 // Handle rotation - Shift key snaps to 15° increments
                 let snap_to_15deg = input.shift();
-                let center = manip.original_shape.bounds().center();
+                let center = context_transform * manip.original_shape.bounds().center();
 
                 // Apply rotation to the shape
-                if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+                if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                     let angle = apply_rotation(shape, world_point, snap_to_15deg);
 
                     // Update rotation state for helper line rendering
@@ -1060,7 +1087,7 @@ impl EventHandler {
                 adjusted_delta,
                 input.shift(),
             );
-            if let Some(shape) = canvas.document.get_shape_mut(manip.shape_id) {
+            if let Some(shape) = canvas.document.find_shape_mut(manip.shape_id) {
                 *shape = new_shape;
             }
             return;
@@ -1147,7 +1174,7 @@ impl EventHandler {
                     // Get the corresponding original shape
                     let original_shape = mm.original_shapes.values().nth(idx);
                     if let (Some(orig), Some(shape)) =
-                        (original_shape, canvas.document.get_shape_mut(dup_id))
+                        (original_shape, canvas.document.find_shape_mut(dup_id))
                     {
                         let mut new_shape = orig.clone();
                         new_shape.transform(translation);
@@ -1159,7 +1186,7 @@ impl EventHandler {
                 for (shape_id, original_shape) in &mm.original_shapes {
                     let mut new_shape = original_shape.clone();
                     new_shape.transform(translation);
-                    if let Some(shape) = canvas.document.get_shape_mut(*shape_id) {
+                    if let Some(shape) = canvas.document.find_shape_mut(*shape_id) {
                         *shape = new_shape;
                     }
                 }
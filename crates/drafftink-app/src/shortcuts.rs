@@ -61,7 +61,8 @@ impl ShortcutRegistry {
             Shortcut::new("V", true, false, "Paste shapes or image"),
             Shortcut::new("Delete", false, false, "Delete selected shapes"),
             Shortcut::new("Backspace", false, false, "Delete selected shapes"),
-            Shortcut::new("Escape", false, false, "Cancel current action"),
+            Shortcut::new("Escape", false, false, "Cancel current action / exit group"),
+            Shortcut::new("Double-click", false, false, "Edit inside a group"),
             Shortcut::new(
                 "Shift+Drag",
                 false,
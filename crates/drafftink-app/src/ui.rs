@@ -1,9 +1,13 @@
 //! UI components using egui.
 
+use drafftink_core::arrange::DEFAULT_ARRANGE_GAP;
+use drafftink_core::e2e::RoomKey;
+use drafftink_core::layout::{LayoutAlgorithm, LayoutDirection};
 use drafftink_core::shapes::{FillPattern, FontFamily, FontWeight, Shape, ShapeId, ShapeStyle};
 use drafftink_core::sync::ConnectionState;
+use drafftink_core::templates::{BoardTemplate, builtin_templates};
 use drafftink_core::tools::ToolKind;
-use drafftink_render::GridStyle;
+use drafftink_render::{GridStyle, PdfExportOptions, PdfPageSize, SvgExportOptions, SvgTextMode};
 use egui::{
     Align2, Color32, Context, CornerRadius, Frame, ImageSource, Margin, Pos2, Rect, Stroke, Vec2,
     include_image,
@@ -210,6 +214,29 @@ pub enum ColorPopover {
     BgFull,     // Full color grid for background
 }
 
+/// How dropped, pasted or opened SVG files are imported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SvgImportMode {
+    /// Convert paths, shapes and text into editable shapes, grouped.
+    #[default]
+    Shapes,
+    /// Place the whole SVG as a vector image.
+    Image,
+}
+
+impl SvgImportMode {
+    /// All modes, in menu order.
+    pub const ALL: [SvgImportMode; 2] = [SvgImportMode::Shapes, SvgImportMode::Image];
+
+    /// Short display name for the menu.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SvgImportMode::Shapes => "Shapes",
+            SvgImportMode::Image => "Image",
+        }
+    }
+}
+
 /// Peer info for UI display
 #[derive(Debug, Clone)]
 pub struct PeerInfo {
@@ -223,6 +250,58 @@ pub struct PeerInfo {
     pub has_cursor: bool,
 }
 
+/// A version listed in the history panel.
+#[derive(Debug, Clone, Default)]
+pub struct HistoryItem {
+    /// Who made the change ("You", a peer's name, or a short peer id).
+    pub author: String,
+    /// When the change was made, relative to now (e.g. "5 min ago").
+    pub when: String,
+    /// What changed (e.g. "2 added, 1 modified").
+    pub summary: String,
+}
+
+/// A checkpoint listed in the checkpoints panel.
+#[derive(Debug, Clone, Default)]
+pub struct CheckpointItem {
+    /// Checkpoint ID.
+    pub id: String,
+    /// Name given by the user.
+    pub name: String,
+    /// When the checkpoint was made, relative to now (e.g. "5 min ago").
+    pub when: String,
+}
+
+/// A changed shape listed in the compare panel.
+#[derive(Debug, Clone)]
+pub struct ChangeItem {
+    /// What the shape is (e.g. "Text \"Login\"").
+    pub label: String,
+    /// How it changed (e.g. "added" or "moved, restyled").
+    pub kind: String,
+    /// Highlight color of the change on the canvas.
+    pub color: Color32,
+}
+
+/// What the compare panel shows.
+#[derive(Debug, Clone, Default)]
+pub struct CompareView {
+    /// Name of the older side (e.g. a checkpoint or file name).
+    pub old_label: String,
+    /// Name of the newer side.
+    pub new_label: String,
+    /// Summary of the changes (e.g. "2 added, 1 modified").
+    pub summary: String,
+    /// The changes, in the order they can be stepped through.
+    pub changes: Vec<ChangeItem>,
+    /// Index (into `changes`) of the change the camera is on.
+    pub focused: Option<usize>,
+    /// Checkpoints the newer side can be switched to: ID and name.
+    pub targets: Vec<(String, String)>,
+    /// Checkpoint shown as the newer side, or `None` for the current board.
+    pub target: Option<String>,
+}
+
 /// UI state and actions.
 pub struct UiState {
     /// Currently selected tool (mirrored from canvas).
@@ -251,6 +330,18 @@ pub struct UiState {
     pub angle_snap_enabled: bool,
     /// Export scale factor (1 = 1x, 2 = 2x, 3 = 3x).
     pub export_scale: u8,
+    /// Page size for PDF export.
+    pub pdf_page_size: PdfPageSize,
+    /// Margin around PDF page content, in points.
+    pub pdf_margin: f64,
+    /// How text is written in SVG export.
+    pub svg_text_mode: SvgTextMode,
+    /// Space around SVG export content, in document units.
+    pub svg_padding: f64,
+    /// Whether SVG exports leave the background transparent.
+    pub svg_transparent: bool,
+    /// How SVG files are imported.
+    pub svg_import_mode: SvgImportMode,
     /// Current sloppiness level for new shapes.
     pub sloppiness: drafftink_core::shapes::Sloppiness,
     /// Current fill pattern for new shapes.
@@ -270,6 +361,22 @@ pub struct UiState {
     pub server_url: String,
     /// Room ID input for joining.
     pub room_input: String,
+    /// Access token input for joining protected rooms.
+    pub token_input: String,
+    /// Room key input for joining end-to-end encrypted rooms.
+    pub key_input: String,
+    /// Whether the current room is protected by tokens.
+    pub room_protected: bool,
+    /// Whether we can only follow the current room, not change it.
+    pub room_read_only: bool,
+    /// Edit token of the current room, if known, for sharing edit links.
+    pub edit_token: Option<String>,
+    /// View token of the current room, if known, for sharing view links.
+    pub view_token: Option<String>,
+    /// Key of the current room if it's end-to-end encrypted, for sharing links.
+    pub room_key: Option<String>,
+    /// Why we were refused or removed from a room.
+    pub room_error: Option<String>,
     /// Connected peers for presence display.
     pub peers: Vec<PeerInfo>,
     /// Local user's display name.
@@ -302,6 +409,35 @@ pub struct UiState {
     pub last_picked_fill: Option<Color32>,
     /// Math editor state: (shape_id, latex_input)
     pub math_editor: Option<(ShapeId, String)>,
+    /// Whether the template picker dialog is open.
+    pub template_dialog_open: bool,
+    /// Input for the name of a new template.
+    pub template_name_input: String,
+    /// Templates saved by the user (loaded from storage).
+    pub saved_templates: Vec<BoardTemplate>,
+    /// Number of columns for "arrange in grid".
+    pub arrange_columns: usize,
+    /// Gap between shapes for tidy-up and grid arrangement (world units).
+    pub arrange_gap: f32,
+    /// Re-route connectors as elbows when running auto-layout.
+    pub layout_elbow: bool,
+    /// Whether the version history panel is open.
+    pub history_panel_open: bool,
+    /// Versions shown in the history panel, oldest first.
+    pub history: Vec<HistoryItem>,
+    /// Index (into `history`) of the version being previewed.
+    pub history_preview: Option<usize>,
+    /// Whether the checkpoints panel is open.
+    pub checkpoints_panel_open: bool,
+    /// Checkpoints of the document, oldest first.
+    pub checkpoints: Vec<CheckpointItem>,
+    /// Name typed for a new checkpoint.
+    pub checkpoint_name_input: String,
+    /// A note shown under a checkpoint, such as why it can't be opened: checkpoint
+    /// ID and text.
+    pub checkpoint_notice: Option<(String, String)>,
+    /// The comparison being shown, if any.
+    pub compare: Option<CompareView>,
 }
 
 impl Default for UiState {
@@ -320,6 +456,12 @@ impl Default for UiState {
             smart_snap_enabled: false,
             angle_snap_enabled: false,
             export_scale: 2, // Default to 2x for good quality
+            pdf_page_size: PdfPageSize::default(),
+            pdf_margin: PdfExportOptions::default().margin,
+            svg_text_mode: SvgTextMode::default(),
+            svg_padding: SvgExportOptions::default().padding,
+            svg_transparent: false,
+            svg_import_mode: SvgImportMode::default(),
             sloppiness: drafftink_core::shapes::Sloppiness::Artist,
             fill_pattern: FillPattern::Solid,
             corner_radius: 0.0, // Sharp corners by default
@@ -330,6 +472,14 @@ impl Default for UiState {
             peer_count: 0,
             server_url: "ws://localhost:3030/ws".to_string(),
             room_input: String::new(),
+            token_input: String::new(),
+            key_input: String::new(),
+            room_protected: false,
+            room_read_only: false,
+            edit_token: None,
+            view_token: None,
+            room_key: None,
+            room_error: None,
             peers: Vec::new(),
             user_name: String::new(),
             user_color: "#6366f1".to_string(), // Indigo
@@ -346,11 +496,34 @@ impl Default for UiState {
             last_picked_stroke: None,
             last_picked_fill: None,
             math_editor: None,
+            template_dialog_open: false,
+            template_name_input: String::new(),
+            saved_templates: Vec::new(),
+            arrange_columns: 3,
+            arrange_gap: DEFAULT_ARRANGE_GAP as f32,
+            layout_elbow: false,
+            history_panel_open: false,
+            history: Vec::new(),
+            history_preview: None,
+            checkpoints_panel_open: false,
+            checkpoints: Vec::new(),
+            checkpoint_name_input: String::new(),
+            checkpoint_notice: None,
+            compare: None,
         }
     }
 }
 
 impl UiState {
+    /// Forget the access state of the room we left.
+    pub fn clear_room_access(&mut self) {
+        self.room_protected = false;
+        self.room_read_only = false;
+        self.edit_token = None;
+        self.view_token = None;
+        self.room_key = None;
+    }
+
     /// Update UI state from a shape's style.
     pub fn update_from_style(&mut self, style: &ShapeStyle) {
         let sc = style.stroke_color;
@@ -420,6 +593,16 @@ pub enum UiAction {
     ExportPng,
     /// Copy selection to clipboard as PNG.
     CopyPng,
+    /// Export document (or selection) as SVG file.
+    ExportSvg,
+    /// Export document (or selection) as PDF file.
+    ExportPdf,
+    /// Export document as an Excalidraw file.
+    ExportExcalidraw,
+    /// Copy selection to clipboard in Excalidraw's clipboard format.
+    CopyExcalidraw,
+    /// Export document as a draw.io file.
+    ExportDrawio,
     /// Toggle grid style (cycles through styles).
     ToggleGrid,
     /// Zoom in.
@@ -448,6 +631,18 @@ pub enum UiAction {
     SetCornerRadius(f32),
     /// Set export scale (1, 2, or 3).
     SetExportScale(u8),
+    /// Set page size for PDF export.
+    SetPdfPageSize(PdfPageSize),
+    /// Set the margin of PDF pages, in points.
+    SetPdfMargin(f64),
+    /// Set how text is written in SVG export.
+    SetSvgTextMode(SvgTextMode),
+    /// Set the padding of SVG export.
+    SetSvgPadding(f64),
+    /// Set whether SVG export leaves the background transparent.
+    SetSvgTransparent(bool),
+    /// Set how SVG files are imported.
+    SetSvgImportMode(SvgImportMode),
     /// Clear document (remove all shapes).
     ClearDocument,
     /// Show intro/welcome screen.
@@ -469,9 +664,15 @@ pub enum UiAction {
     /// Disconnect from collaboration server.
     Disconnect,
     /// Join a collaboration room.
-    JoinRoom(String), // room ID
+    JoinRoom(String, Option<String>, Option<String>), // room ID, access token, room key
     /// Leave current collaboration room.
     LeaveRoom,
+    /// Protect the current room with new edit and view tokens, revoking any old ones.
+    ProtectRoom,
+    /// Remove the current room's protection, opening it to everyone.
+    OpenRoom,
+    /// Copy a link to the current room (read-only or with edit access).
+    CopyRoomLink(bool), // read-only
     /// Set user display name.
     SetUserName(String),
     /// Set user color (hex string).
@@ -508,6 +709,16 @@ pub enum UiAction {
     AlignCenterH,
     /// Align selected shapes to vertical center.
     AlignCenterV,
+    /// Distribute selected shapes horizontally with equal gaps.
+    DistributeHorizontal,
+    /// Distribute selected shapes vertically with equal gaps.
+    DistributeVertical,
+    /// Snap selected shapes into a clean grid with the given gap.
+    TidyUp(f32),
+    /// Arrange selected shapes in a grid.
+    ArrangeInGrid(usize, f32), // columns, gap
+    /// Auto-layout selected shapes as a graph, using arrows between them as edges.
+    AutoLayout(LayoutAlgorithm),
     /// Show keyboard shortcuts help.
     ShowShortcuts,
     /// Toggle calligraphy mode for freehand tool.
@@ -522,6 +733,44 @@ pub enum UiAction {
     SetOpacity(f32),
     /// Update math shape LaTeX.
     UpdateMathLatex(ShapeId, String),
+    /// Show the template picker dialog.
+    ShowTemplateDialog,
+    /// Replace the board with a new document created from a template.
+    NewFromTemplate(String), // template ID
+    /// Save the selection (or whole board if nothing is selected) as a template.
+    SaveAsTemplate(String), // template name
+    /// Delete a user-saved template.
+    DeleteTemplate(String), // template ID
+    /// Open or close the version history panel.
+    ToggleHistory,
+    /// Show the board as it was at a version from the history panel.
+    PreviewVersion(usize),
+    /// Stop previewing and show the current board again.
+    ExitPreview,
+    /// Restore the board to a version (recorded as a new change).
+    RestoreVersion(usize),
+    /// Save a version as a document file.
+    ExportVersion(usize),
+    /// Open or close the checkpoints panel.
+    ToggleCheckpoints,
+    /// Add a checkpoint of the board with the given name.
+    CreateCheckpoint(String),
+    /// Compare a checkpoint with the board.
+    CompareCheckpoint(String), // checkpoint ID
+    /// Copy a checkpoint into a new document and open it.
+    ForkCheckpoint(String), // checkpoint ID
+    /// Delete a checkpoint.
+    DeleteCheckpoint(String), // checkpoint ID
+    /// Compare a version from the history panel with the board.
+    CompareVersion(usize),
+    /// Pick a document file and compare it with the board.
+    CompareWithFile,
+    /// Compare against a checkpoint instead of the board, or the board again.
+    SetCompareTarget(Option<String>), // checkpoint ID
+    /// Move the camera to a change in the compare panel.
+    FocusChange(usize),
+    /// Leave compare mode.
+    ExitCompare,
 }
 
 /// Tool definitions with SVG icons
@@ -621,9 +870,25 @@ pub fn render_ui(
     let properties_action = render_properties_panel(ctx, ui_state);
     let file_action = render_file_menu(ctx, ui_state);
     let bottom_action = render_bottom_toolbar(ctx, ui_state);
-    let right_panel_action = render_right_panel(ctx, selected_props);
+    let right_panel_action = render_right_panel(ctx, ui_state, selected_props);
     let math_action = render_math_editor(ctx, ui_state);
 
+    let history_action = if ui_state.history_panel_open {
+        render_history_panel(ctx, ui_state)
+    } else {
+        None
+    };
+    let checkpoints_action = if ui_state.checkpoints_panel_open {
+        render_checkpoints_panel(ctx, ui_state)
+    } else {
+        None
+    };
+    let compare_action = if ui_state.compare.is_some() {
+        render_compare_panel(ctx, ui_state)
+    } else {
+        None
+    };
+
     // Render presence panel (no actions returned)
     render_presence_panel(ctx, ui_state);
 
@@ -634,6 +899,9 @@ pub fn render_ui(
         .or(bottom_action)
         .or(right_panel_action)
         .or(math_action)
+        .or(history_action)
+        .or(checkpoints_action)
+        .or(compare_action)
 }
 
 /// Render the toolbar and return any triggered action.
@@ -1237,7 +1505,11 @@ fn grid_style_button(ui: &mut egui::Ui, style: GridStyle, tooltip: &str) -> bool
 }
 
 /// Render the right-side properties panel for selected shapes.
-fn render_right_panel(ctx: &Context, props: &SelectedShapeProps) -> Option<UiAction> {
+fn render_right_panel(
+    ctx: &Context,
+    ui_state: &mut UiState,
+    props: &SelectedShapeProps,
+) -> Option<UiAction> {
     // Show panel if shape is selected OR if a drawing tool is active
     if !props.has_selection && !props.is_drawing_tool {
         return None;
@@ -1760,6 +2032,110 @@ fn render_right_panel(ctx: &Context, props: &SelectedShapeProps) -> Option<UiAct
                                     {
                                         action = Some(UiAction::AlignBottom);
                                     }
+                                    if props.selection_count >= 3 {
+                                        if IconButton::new(
+                                            include_image!("../assets/distribute-h.svg"),
+                                            "Distribute Horizontally",
+                                        )
+                                        .show(ui)
+                                        {
+                                            action = Some(UiAction::DistributeHorizontal);
+                                        }
+                                        if IconButton::new(
+                                            include_image!("../assets/distribute-v.svg"),
+                                            "Distribute Vertically",
+                                        )
+                                        .show(ui)
+                                        {
+                                            action = Some(UiAction::DistributeVertical);
+                                        }
+                                    }
+                                });
+
+                                ui.add_space(8.0);
+                                ui.label(
+                                    egui::RichText::new("Arrange")
+                                        .size(11.0)
+                                        .color(Color32::from_gray(100)),
+                                );
+                                ui.horizontal(|ui| {
+                                    ui.spacing_mut().item_spacing = Vec2::new(4.0, 0.0);
+
+                                    if IconButton::new(
+                                        include_image!("../assets/tidy-up.svg"),
+                                        "Tidy Up",
+                                    )
+                                    .show(ui)
+                                    {
+                                        action = Some(UiAction::TidyUp(ui_state.arrange_gap));
+                                    }
+                                    if IconButton::new(
+                                        include_image!("../assets/arrange-grid.svg"),
+                                        "Arrange in Grid",
+                                    )
+                                    .show(ui)
+                                    {
+                                        action = Some(UiAction::ArrangeInGrid(
+                                            ui_state.arrange_columns,
+                                            ui_state.arrange_gap,
+                                        ));
+                                    }
+                                    ui.add(
+                                        egui::DragValue::new(&mut ui_state.arrange_columns)
+                                            .range(1..=20)
+                                            .suffix(" col"),
+                                    )
+                                    .on_hover_text("Columns");
+                                    ui.add(
+                                        egui::DragValue::new(&mut ui_state.arrange_gap)
+                                            .range(0.0..=200.0)
+                                            .speed(1.0)
+                                            .suffix(" gap"),
+                                    )
+                                    .on_hover_text("Gap between shapes");
+                                });
+
+                                ui.add_space(8.0);
+                                ui.label(
+                                    egui::RichText::new("Auto Layout")
+                                        .size(11.0)
+                                        .color(Color32::from_gray(100)),
+                                );
+                                ui.horizontal(|ui| {
+                                    ui.spacing_mut().item_spacing = Vec2::new(4.0, 0.0);
+
+                                    if IconButton::new(
+                                        include_image!("../assets/layout-down.svg"),
+                                        "Layered (Top-Down)",
+                                    )
+                                    .show(ui)
+                                    {
+                                        action = Some(UiAction::AutoLayout(
+                                            LayoutAlgorithm::Layered(LayoutDirection::TopDown),
+                                        ));
+                                    }
+                                    if IconButton::new(
+                                        include_image!("../assets/layout-right.svg"),
+                                        "Layered (Left-Right)",
+                                    )
+                                    .show(ui)
+                                    {
+                                        action = Some(UiAction::AutoLayout(
+                                            LayoutAlgorithm::Layered(LayoutDirection::LeftRight),
+                                        ));
+                                    }
+                                    if IconButton::new(
+                                        include_image!("../assets/layout-force.svg"),
+                                        "Force-Directed",
+                                    )
+                                    .show(ui)
+                                    {
+                                        action = Some(UiAction::AutoLayout(
+                                            LayoutAlgorithm::ForceDirected,
+                                        ));
+                                    }
+                                    ui.checkbox(&mut ui_state.layout_elbow, "Elbow")
+                                        .on_hover_text("Route connectors with right angles");
                                 });
                             }
                         }
@@ -1830,6 +2206,26 @@ fn render_file_menu(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
 
                         widgets_menu_separator(ui);
 
+                        if menu_item(ui, "New from Template...", "") {
+                            action = Some(UiAction::ShowTemplateDialog);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Version History", "") {
+                            action = Some(UiAction::ToggleHistory);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Checkpoints", "") {
+                            action = Some(UiAction::ToggleCheckpoints);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Compare with File...", "") {
+                            action = Some(UiAction::CompareWithFile);
+                            ui_state.menu_open = false;
+                        }
+
                         if menu_item(ui, "Clear", "") {
                             action = Some(UiAction::ClearDocument);
                             ui_state.menu_open = false;
@@ -1861,12 +2257,37 @@ fn render_file_menu(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
                             ui_state.menu_open = false;
                         }
 
+                        if menu_item(ui, "Export SVG", "") {
+                            action = Some(UiAction::ExportSvg);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Export PDF", "") {
+                            action = Some(UiAction::ExportPdf);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Export Excalidraw", "") {
+                            action = Some(UiAction::ExportExcalidraw);
+                            ui_state.menu_open = false;
+                        }
+
+                        if menu_item(ui, "Export draw.io", "") {
+                            action = Some(UiAction::ExportDrawio);
+                            ui_state.menu_open = false;
+                        }
+
                         // Copy as PNG (show disabled state if no selection)
                         if menu_item_enabled(ui, "Copy as PNG", "Ctrl+Shift+C", has_selection) {
                             action = Some(UiAction::CopyPng);
                             ui_state.menu_open = false;
                         }
 
+                        if menu_item_enabled(ui, "Copy as Excalidraw", "", has_selection) {
+                            action = Some(UiAction::CopyExcalidraw);
+                            ui_state.menu_open = false;
+                        }
+
                         // Export scale selector
                         ui.add_space(4.0);
                         ui.horizontal(|ui| {
@@ -1901,6 +2322,207 @@ fn render_file_menu(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
                             }
                         });
 
+                        // PDF page size selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("Page:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for page_size in PdfPageSize::ALL {
+                                let selected = ui_state.pdf_page_size == page_size;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(page_size.name()).size(11.0).color(
+                                        if selected {
+                                            Color32::WHITE
+                                        } else {
+                                            Color32::from_gray(80)
+                                        },
+                                    ),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetPdfPageSize(page_size));
+                                }
+                            }
+                        });
+
+                        // PDF margin selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("Margin:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for margin in [0.0, 18.0, 36.0, 72.0] {
+                                let label = format!("{}pt", margin);
+                                let selected = ui_state.pdf_margin == margin;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(&label).size(11.0).color(if selected {
+                                        Color32::WHITE
+                                    } else {
+                                        Color32::from_gray(80)
+                                    }),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetPdfMargin(margin));
+                                }
+                            }
+                        });
+
+                        // SVG text mode selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("SVG text:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for mode in SvgTextMode::ALL {
+                                let selected = ui_state.svg_text_mode == mode;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(mode.name()).size(11.0).color(
+                                        if selected {
+                                            Color32::WHITE
+                                        } else {
+                                            Color32::from_gray(80)
+                                        },
+                                    ),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetSvgTextMode(mode));
+                                }
+                            }
+                        });
+
+                        // SVG padding selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("SVG padding:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for padding in [0.0, 10.0, 20.0, 40.0] {
+                                let selected = ui_state.svg_padding == padding;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(format!("{}", padding))
+                                        .size(11.0)
+                                        .color(if selected {
+                                            Color32::WHITE
+                                        } else {
+                                            Color32::from_gray(80)
+                                        }),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetSvgPadding(padding));
+                                }
+                            }
+                        });
+
+                        // SVG background selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("SVG background:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for (name, transparent) in [("White", false), ("None", true)] {
+                                let selected = ui_state.svg_transparent == transparent;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(name).size(11.0).color(if selected {
+                                        Color32::WHITE
+                                    } else {
+                                        Color32::from_gray(80)
+                                    }),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetSvgTransparent(transparent));
+                                }
+                            }
+                        });
+
+                        // SVG import mode selector
+                        ui.horizontal(|ui| {
+                            ui.add_space(12.0); // Align with menu item text
+                            ui.label(
+                                egui::RichText::new("SVG import:")
+                                    .size(11.0)
+                                    .color(Color32::from_rgb(100, 116, 139)),
+                            );
+                            ui.add_space(4.0);
+                            for mode in SvgImportMode::ALL {
+                                let selected = ui_state.svg_import_mode == mode;
+                                let btn = egui::Button::new(
+                                    egui::RichText::new(mode.name()).size(11.0).color(
+                                        if selected {
+                                            Color32::WHITE
+                                        } else {
+                                            Color32::from_gray(80)
+                                        },
+                                    ),
+                                )
+                                .fill(if selected {
+                                    Color32::from_rgb(59, 130, 246)
+                                } else {
+                                    Color32::TRANSPARENT
+                                })
+                                .stroke(egui::Stroke::NONE)
+                                .corner_radius(egui::CornerRadius::same(4))
+                                .min_size(Vec2::new(24.0, 20.0));
+                                if ui.add(btn).clicked() {
+                                    action = Some(UiAction::SetSvgImportMode(mode));
+                                }
+                            }
+                        });
+
                         widgets_menu_separator(ui);
 
                         if menu_item(ui, "Keyboard Shortcuts", "?") {
@@ -1962,6 +2584,13 @@ fn render_file_menu(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
         }
     }
 
+    // Render template picker if open
+    if ui_state.template_dialog_open {
+        if let Some(template_action) = render_template_dialog(ctx, ui_state) {
+            action = Some(template_action);
+        }
+    }
+
     action
 }
 
@@ -2010,9 +2639,70 @@ fn hamburger_button(ui: &mut egui::Ui, is_open: bool) -> bool {
 }
 
 /// Collaboration button with connection status indicator.
-fn collab_button(ui: &mut egui::Ui, connection_state: ConnectionState, is_open: bool) -> bool {
-    let size = Vec2::new(32.0, 32.0);
-    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
+/// Sharing and access controls for the current room.
+fn render_room_access(ui: &mut egui::Ui, ui_state: &UiState) -> Option<UiAction> {
+    let mut action = None;
+    ui.add_space(4.0);
+    ui.label(
+        egui::RichText::new("Access")
+            .size(12.0)
+            .strong()
+            .color(Color32::from_gray(60)),
+    );
+    let status = match (ui_state.room_protected, ui_state.room_read_only) {
+        (_, true) => "View only: your changes are not shared",
+        (true, false) => "Protected: only people with a link can join. Revoking creates new links.",
+        (false, false) => "Open: anyone who knows the room name can edit",
+    };
+    ui.label(
+        egui::RichText::new(status)
+            .size(11.0)
+            .color(Color32::from_gray(100)),
+    );
+    if ui_state.room_key.is_some() {
+        ui.label(
+            egui::RichText::new("End-to-end encrypted: links include the room key")
+                .size(11.0)
+                .color(Color32::from_gray(100)),
+        );
+    }
+    ui.horizontal(|ui| {
+        if !ui_state.room_protected {
+            if secondary_btn(ui, "Copy Link") {
+                action = Some(UiAction::CopyRoomLink(false));
+            }
+            return;
+        }
+        if ui_state.edit_token.is_some() && secondary_btn(ui, "Copy Edit Link") {
+            action = Some(UiAction::CopyRoomLink(false));
+        }
+        if ui_state.view_token.is_some() && secondary_btn(ui, "Copy View Link") {
+            action = Some(UiAction::CopyRoomLink(true));
+        }
+    });
+    if !ui_state.room_read_only {
+        ui.horizontal(|ui| {
+            if !ui_state.room_protected {
+                if secondary_btn(ui, "Protect Room") {
+                    action = Some(UiAction::ProtectRoom);
+                }
+            } else {
+                // New tokens replace the old ones, so old links stop working
+                if secondary_btn(ui, "Revoke Links") {
+                    action = Some(UiAction::ProtectRoom);
+                }
+                if secondary_btn(ui, "Remove Protection") {
+                    action = Some(UiAction::OpenRoom);
+                }
+            }
+        });
+    }
+    action
+}
+
+fn collab_button(ui: &mut egui::Ui, connection_state: ConnectionState, is_open: bool) -> bool {
+    let size = Vec2::new(32.0, 32.0);
+    let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click());
 
     if ui.is_rect_visible(rect) {
         // Background
@@ -2258,6 +2948,32 @@ fn render_collaboration_modal(ctx: &Context, ui_state: &mut UiState) -> Option<U
                                 modal_width,
                                 "Enter room name",
                             );
+                            if ui_state.current_room.is_none() {
+                                input_text(
+                                    ui,
+                                    &mut ui_state.token_input,
+                                    modal_width,
+                                    "Access token (protected rooms only)",
+                                );
+                                ui.horizontal(|ui| {
+                                    input_text(
+                                        ui,
+                                        &mut ui_state.key_input,
+                                        modal_width - 90.0,
+                                        "Room key (encrypted rooms only)",
+                                    );
+                                    if secondary_btn(ui, "New Key") {
+                                        ui_state.key_input = RoomKey::generate().encode();
+                                    }
+                                });
+                            }
+                            if let Some(ref error) = ui_state.room_error {
+                                ui.label(
+                                    egui::RichText::new(error)
+                                        .size(11.0)
+                                        .color(Color32::from_rgb(239, 68, 68)),
+                                );
+                            }
 
                             ui.add_space(4.0);
 
@@ -2279,10 +2995,20 @@ fn render_collaboration_modal(ctx: &Context, ui_state: &mut UiState) -> Option<U
                                 .min_size(Vec2::new(modal_width, 36.0))
                                 .corner_radius(CornerRadius::same(6));
                                 if ui.add(join_btn).clicked() {
-                                    action = Some(UiAction::JoinRoom(ui_state.room_input.clone()));
+                                    let token = ui_state.token_input.trim();
+                                    let key = ui_state.key_input.trim();
+                                    action = Some(UiAction::JoinRoom(
+                                        ui_state.room_input.clone(),
+                                        (!token.is_empty()).then(|| token.to_string()),
+                                        (!key.is_empty()).then(|| key.to_string()),
+                                    ));
                                 }
                             }
 
+                            if ui_state.current_room.is_some() {
+                                action = render_room_access(ui, ui_state).or(action);
+                            }
+
                             ui.separator();
                             ui.add_space(4.0);
 
@@ -2426,6 +3152,334 @@ fn parse_color(color: &str) -> Color32 {
     Color32::from_rgb(100, 116, 139) // slate-500
 }
 
+/// Render the version history panel: newest version first, each with its author,
+/// age and summary. Clicking a version previews it.
+fn render_history_panel(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
+    let mut action = None;
+
+    egui::Area::new(egui::Id::new("history_panel"))
+        .anchor(Align2::LEFT_TOP, Vec2::new(72.0, 56.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            panel_frame().show(ui, |ui| {
+                ui.set_width(260.0);
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("Version History")
+                                .size(13.0)
+                                .strong()
+                                .color(Color32::from_gray(30)),
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if default_btn(ui, "X") {
+                                action = Some(UiAction::ToggleHistory);
+                            }
+                        });
+                    });
+
+                    if let Some(index) = ui_state.history_preview {
+                        ui.add_space(6.0);
+                        ui.label(
+                            egui::RichText::new("Previewing a past version (read-only)")
+                                .size(11.0)
+                                .color(Color32::from_rgb(59, 130, 246)),
+                        );
+                        ui.horizontal(|ui| {
+                            if primary_btn(ui, "Restore") {
+                                action = Some(UiAction::RestoreVersion(index));
+                            }
+                            if secondary_btn(ui, "Export") {
+                                action = Some(UiAction::ExportVersion(index));
+                            }
+                            if secondary_btn(ui, "Compare") {
+                                action = Some(UiAction::CompareVersion(index));
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            if secondary_btn(ui, "Back to current") {
+                                action = Some(UiAction::ExitPreview);
+                            }
+                        });
+                    }
+
+                    widgets_menu_separator(ui);
+
+                    if ui_state.history.is_empty() {
+                        ui.label(
+                            egui::RichText::new("No changes yet")
+                                .size(11.0)
+                                .color(Color32::from_gray(120)),
+                        );
+                        return;
+                    }
+
+                    egui::ScrollArea::vertical()
+                        .max_height(360.0)
+                        .show(ui, |ui| {
+                            let latest = ui_state.history.len() - 1;
+                            for (index, item) in ui_state.history.iter().enumerate().rev() {
+                                let selected = ui_state.history_preview == Some(index)
+                                    || (ui_state.history_preview.is_none() && index == latest);
+                                let title = if item.summary.is_empty() {
+                                    "Edited"
+                                } else {
+                                    item.summary.as_str()
+                                };
+                                let response = ui.selectable_label(
+                                    selected,
+                                    egui::RichText::new(title)
+                                        .size(12.0)
+                                        .color(Color32::from_gray(30)),
+                                );
+                                ui.label(
+                                    egui::RichText::new(format!("{} · {}", item.author, item.when))
+                                        .size(10.0)
+                                        .color(Color32::from_gray(120)),
+                                );
+                                if response.clicked() {
+                                    action = Some(if index == latest {
+                                        UiAction::ExitPreview
+                                    } else {
+                                        UiAction::PreviewVersion(index)
+                                    });
+                                }
+                                ui.add_space(4.0);
+                            }
+                        });
+                });
+            });
+        });
+
+    action
+}
+
+/// Render the checkpoints panel: a field to name a new checkpoint, then the
+/// checkpoints newest first, each of which can be compared, forked or deleted.
+fn render_checkpoints_panel(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
+    let mut action = None;
+
+    egui::Area::new(egui::Id::new("checkpoints_panel"))
+        .anchor(Align2::LEFT_TOP, Vec2::new(72.0, 56.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            panel_frame().show(ui, |ui| {
+                ui.set_width(260.0);
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("Checkpoints")
+                                .size(13.0)
+                                .strong()
+                                .color(Color32::from_gray(30)),
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if default_btn(ui, "X") {
+                                action = Some(UiAction::ToggleCheckpoints);
+                            }
+                        });
+                    });
+
+                    ui.add_space(6.0);
+                    ui.horizontal(|ui| {
+                        let response = input_text(
+                            ui,
+                            &mut ui_state.checkpoint_name_input,
+                            180.0,
+                            "e.g. before refactor",
+                        );
+                        let submitted =
+                            response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                        if primary_btn(ui, "Save") || submitted {
+                            action = Some(UiAction::CreateCheckpoint(
+                                ui_state.checkpoint_name_input.trim().to_string(),
+                            ));
+                        }
+                    });
+
+                    widgets_menu_separator(ui);
+
+                    if ui_state.checkpoints.is_empty() {
+                        ui.label(
+                            egui::RichText::new("No checkpoints yet")
+                                .size(11.0)
+                                .color(Color32::from_gray(120)),
+                        );
+                        return;
+                    }
+
+                    egui::ScrollArea::vertical()
+                        .max_height(360.0)
+                        .show(ui, |ui| {
+                            for item in ui_state.checkpoints.iter().rev() {
+                                ui.label(
+                                    egui::RichText::new(&item.name)
+                                        .size(12.0)
+                                        .color(Color32::from_gray(30)),
+                                );
+                                ui.label(
+                                    egui::RichText::new(&item.when)
+                                        .size(10.0)
+                                        .color(Color32::from_gray(120)),
+                                );
+                                if let Some((_, notice)) = ui_state
+                                    .checkpoint_notice
+                                    .as_ref()
+                                    .filter(|(id, _)| *id == item.id)
+                                {
+                                    ui.label(
+                                        egui::RichText::new(notice)
+                                            .size(10.0)
+                                            .color(Color32::from_rgb(239, 68, 68)),
+                                    );
+                                }
+                                ui.horizontal(|ui| {
+                                    if secondary_btn(ui, "Compare") {
+                                        action = Some(UiAction::CompareCheckpoint(item.id.clone()));
+                                    }
+                                    if secondary_btn(ui, "Fork") {
+                                        action = Some(UiAction::ForkCheckpoint(item.id.clone()));
+                                    }
+                                    if secondary_btn(ui, "Delete") {
+                                        action = Some(UiAction::DeleteCheckpoint(item.id.clone()));
+                                    }
+                                });
+                                ui.add_space(4.0);
+                            }
+                        });
+                });
+            });
+        });
+
+    action
+}
+
+/// Render the compare panel: the two sides being compared, a choice of the newer
+/// side, and the list of changes. Clicking a change moves the camera to it.
+fn render_compare_panel(ctx: &Context, ui_state: &UiState) -> Option<UiAction> {
+    let mut action = None;
+    let view = ui_state.compare.as_ref()?;
+
+    egui::Area::new(egui::Id::new("compare_panel"))
+        .anchor(Align2::LEFT_TOP, Vec2::new(72.0, 56.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            panel_frame().show(ui, |ui| {
+                ui.set_width(260.0);
+                ui.vertical(|ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new("Compare")
+                                .size(13.0)
+                                .strong()
+                                .color(Color32::from_gray(30)),
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            if default_btn(ui, "X") {
+                                action = Some(UiAction::ExitCompare);
+                            }
+                        });
+                    });
+
+                    ui.add_space(6.0);
+                    ui.label(
+                        egui::RichText::new(format!("{} → {}", view.old_label, view.new_label))
+                            .size(11.0)
+                            .color(Color32::from_gray(60)),
+                    );
+
+                    if !view.targets.is_empty() {
+                        let name_of = |target: &Option<String>| match target {
+                            Some(id) => view
+                                .targets
+                                .iter()
+                                .find(|(target_id, _)| target_id == id)
+                                .map_or("Checkpoint", |(_, name)| name.as_str()),
+                            None => "Current board",
+                        };
+                        let mut target = view.target.clone();
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("Against")
+                                    .size(11.0)
+                                    .color(Color32::from_gray(120)),
+                            );
+                            egui::ComboBox::from_id_salt("compare_target")
+                                .selected_text(name_of(&target))
+                                .width(180.0)
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut target, None, "Current board");
+                                    for (id, name) in &view.targets {
+                                        ui.selectable_value(&mut target, Some(id.clone()), name);
+                                    }
+                                });
+                        });
+                        if target != view.target {
+                            action = Some(UiAction::SetCompareTarget(target));
+                        }
+                    }
+
+                    ui.add_space(4.0);
+                    ui.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(&view.summary)
+                                .size(11.0)
+                                .color(Color32::from_rgb(59, 130, 246)),
+                        );
+                        if view.changes.is_empty() {
+                            return;
+                        }
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            let count = view.changes.len();
+                            if default_btn(ui, "Next") {
+                                let next = view.focused.map_or(0, |i| (i + 1) % count);
+                                action = Some(UiAction::FocusChange(next));
+                            }
+                            if default_btn(ui, "Prev") {
+                                let prev =
+                                    view.focused.map_or(count - 1, |i| (i + count - 1) % count);
+                                action = Some(UiAction::FocusChange(prev));
+                            }
+                        });
+                    });
+
+                    widgets_menu_separator(ui);
+
+                    egui::ScrollArea::vertical()
+                        .max_height(360.0)
+                        .show(ui, |ui| {
+                            for (index, item) in view.changes.iter().enumerate() {
+                                ui.horizontal(|ui| {
+                                    let (dot_rect, _) = ui.allocate_exact_size(
+                                        Vec2::new(8.0, 8.0),
+                                        egui::Sense::hover(),
+                                    );
+                                    ui.painter()
+                                        .circle_filled(dot_rect.center(), 4.0, item.color);
+                                    let response = ui.selectable_label(
+                                        view.focused == Some(index),
+                                        egui::RichText::new(&item.label)
+                                            .size(12.0)
+                                            .color(Color32::from_gray(30)),
+                                    );
+                                    ui.label(
+                                        egui::RichText::new(&item.kind)
+                                            .size(10.0)
+                                            .color(Color32::from_gray(120)),
+                                    );
+                                    if response.clicked() {
+                                        action = Some(UiAction::FocusChange(index));
+                                    }
+                                });
+                            }
+                        });
+                });
+            });
+        });
+
+    action
+}
+
 /// Render the presence panel showing connected users.
 fn render_presence_panel(ctx: &Context, ui_state: &UiState) {
     // Only show if in a room with peers
@@ -2820,6 +3874,129 @@ fn render_open_recent_dialog(ctx: &Context, ui_state: &mut UiState) -> Option<Ui
     action
 }
 
+/// Render the template picker dialog.
+fn render_template_dialog(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
+    let mut action = None;
+
+    // Backdrop
+    egui::Area::new(egui::Id::new("template_dialog_backdrop"))
+        .fixed_pos(Pos2::ZERO)
+        .order(egui::Order::Background)
+        .show(ctx, |ui| {
+            let screen_rect = ctx.input(|i| i.content_rect());
+            let response = ui.allocate_rect(screen_rect, egui::Sense::click());
+            ui.painter()
+                .rect_filled(screen_rect, 0.0, Color32::from_black_alpha(80));
+            if response.clicked() {
+                ui_state.template_dialog_open = false;
+            }
+        });
+
+    // Modal window
+    egui::Area::new(egui::Id::new("template_dialog"))
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            Frame::new()
+                .fill(Color32::WHITE)
+                .corner_radius(CornerRadius::same(12))
+                .stroke(Stroke::new(1.0, Color32::from_gray(200)))
+                .inner_margin(Margin::same(20))
+                .show(ui, |ui| {
+                    ui.set_width(340.0);
+                    ui.vertical(|ui| {
+                        ui.horizontal(|ui| {
+                            ui.label(
+                                egui::RichText::new("New from Template")
+                                    .size(16.0)
+                                    .strong()
+                                    .color(Color32::from_gray(30)),
+                            );
+                            ui.with_layout(
+                                egui::Layout::right_to_left(egui::Align::Center),
+                                |ui| {
+                                    if default_btn(ui, "X") {
+                                        ui_state.template_dialog_open = false;
+                                    }
+                                },
+                            );
+                        });
+
+                        ui.add_space(12.0);
+
+                        egui::ScrollArea::vertical()
+                            .max_height(320.0)
+                            .show(ui, |ui| {
+                                let templates: Vec<BoardTemplate> = builtin_templates()
+                                    .into_iter()
+                                    .chain(ui_state.saved_templates.iter().cloned())
+                                    .collect();
+                                for template in templates {
+                                    ui.horizontal(|ui| {
+                                        ui.vertical(|ui| {
+                                            ui.label(
+                                                egui::RichText::new(&template.name)
+                                                    .size(13.0)
+                                                    .color(Color32::from_gray(30)),
+                                            );
+                                            ui.label(
+                                                egui::RichText::new(&template.description)
+                                                    .size(11.0)
+                                                    .color(Color32::from_gray(120)),
+                                            );
+                                        });
+                                        ui.with_layout(
+                                            egui::Layout::right_to_left(egui::Align::Center),
+                                            |ui| {
+                                                if !template.builtin && secondary_btn(ui, "Delete")
+                                                {
+                                                    action = Some(UiAction::DeleteTemplate(
+                                                        template.id.clone(),
+                                                    ));
+                                                }
+                                                if primary_btn(ui, "Use") {
+                                                    action = Some(UiAction::NewFromTemplate(
+                                                        template.id.clone(),
+                                                    ));
+                                                    ui_state.template_dialog_open = false;
+                                                }
+                                            },
+                                        );
+                                    });
+                                    ui.add_space(6.0);
+                                }
+                            });
+
+                        widgets_menu_separator(ui);
+                        ui.add_space(8.0);
+
+                        ui.label(
+                            egui::RichText::new(if ui_state.selection_count > 0 {
+                                "Save selection as template:"
+                            } else {
+                                "Save board as template:"
+                            })
+                            .size(12.0)
+                            .color(Color32::from_gray(60)),
+                        );
+                        ui.horizontal(|ui| {
+                            input_text(ui, &mut ui_state.template_name_input, 250.0, "Name");
+                            if primary_btn(ui, "Save")
+                                && !ui_state.template_name_input.trim().is_empty()
+                            {
+                                action = Some(UiAction::SaveAsTemplate(
+                                    ui_state.template_name_input.trim().to_string(),
+                                ));
+                                ui_state.template_name_input.clear();
+                            }
+                        });
+                    });
+                });
+        });
+
+    action
+}
+
 /// Render the math equation editor dialog.
 fn render_math_editor(ctx: &Context, ui_state: &mut UiState) -> Option<UiAction> {
     let (shape_id, latex_input) = ui_state.math_editor.as_mut()?;
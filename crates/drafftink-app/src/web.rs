@@ -1,79 +1,80 @@
 //! WebAssembly entry point and platform-specific code.
 
+use drafftink_core::sync::percent_decode;
 use wasm_bindgen::prelude::*;
 
 /// URL parameters for auto-joining a room.
+#[derive(Default)]
 pub struct UrlParams {
     /// Room ID to join
     pub room: Option<String>,
     /// Server host:port (e.g., "localhost:3030")
     pub server: Option<String>,
+    /// Access token for protected rooms
+    pub token: Option<String>,
+    /// Room key for end-to-end encrypted rooms
+    pub key: Option<String>,
 }
 
-/// Parse URL query parameters for room and server.
-/// Supports formats like `?room=abc123&server=localhost:3030`
+/// Parse URL query parameters for room, server, access token and room key.
+/// Supports formats like `?room=abc123&server=localhost:3030&token=...`
 pub fn get_url_params() -> UrlParams {
     let window = match web_sys::window() {
         Some(w) => w,
-        None => {
-            return UrlParams {
-                room: None,
-                server: None,
-            };
-        }
+        None => return UrlParams::default(),
     };
     let location = window.location();
 
-    let mut room = None;
-    let mut server = None;
-
-    // Try query string first (?room=abc123&server=host:port)
-    if let Ok(search) = location.search() {
-        let params = parse_params(&search);
-        if room.is_none() {
-            room = params.0;
-        }
-        if server.is_none() {
-            server = params.1;
-        }
-    }
-
-    // Try hash fragment (#room=abc123&server=host:port)
-    if let Ok(hash) = location.hash() {
-        let params = parse_params(&hash);
-        if room.is_none() {
-            room = params.0;
-        }
-        if server.is_none() {
-            server = params.1;
-        }
+    let mut params = UrlParams::default();
+
+    // Try query string first (?room=abc123&server=host:port), then the hash
+    // fragment (#room=abc123&server=host:port), which shared links use
+    let sources = [location.search(), location.hash()];
+    for source in sources.into_iter().flatten() {
+        let parsed = parse_params(&source);
+        params.room = params.room.or(parsed.room);
+        params.server = params.server.or(parsed.server);
+        params.token = params.token.or(parsed.token);
+        params.key = params.key.or(parsed.key);
     }
 
-    UrlParams { room, server }
+    params
 }
 
-/// Parse room and server parameters from a query string or hash.
-fn parse_params(s: &str) -> (Option<String>, Option<String>) {
+/// Parse room, server, token and key parameters from a query string or hash.
+fn parse_params(s: &str) -> UrlParams {
     // Remove leading ? or #
     let s = s.trim_start_matches(|c| c == '?' || c == '#');
 
-    let mut room = None;
-    let mut server = None;
+    let mut params = UrlParams::default();
 
     for pair in s.split('&') {
         let mut parts = pair.splitn(2, '=');
         if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
             if !value.is_empty() {
+                let value = Some(percent_decode(value));
                 match key {
-                    "room" => room = Some(value.to_string()),
-                    "server" => server = Some(value.to_string()),
+                    "room" => params.room = value,
+                    "server" => params.server = value,
+                    "token" => params.token = value,
+                    "key" => params.key = value,
                     _ => {}
                 }
             }
         }
     }
 
-    (room, server)
+    params
+}
+
+/// The page's URL without query or fragment, as the base of links to rooms.
+pub fn page_url() -> Option<String> {
+    let location = web_sys::window()?.location();
+    Some(format!(
+        "{}{}",
+        location.origin().ok()?,
+        location.pathname().ok()?
+    ))
 }
 
 /// Legacy function for backward compatibility.
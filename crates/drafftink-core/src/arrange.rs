@@ -0,0 +1,189 @@
+//! Arrangement of multiple shapes: equal distribution, tidy-up and grid layout.
+//!
+//! All functions work on the visual bounds of the shapes and return one translation
+//! offset per input rect, in input order.
+
+use crate::snap::{GRID_SIZE, SpacingAxis, equal_spacing_gap};
+use kurbo::{Point, Rect, Vec2};
+
+/// Default gap between shapes for tidy-up and grid arrangement.
+pub const DEFAULT_ARRANGE_GAP: f64 = GRID_SIZE;
+
+/// Offsets that space bounds with equal gaps along an axis.
+/// Shapes keep their order along the axis and the selection keeps its extent.
+/// Fewer than 3 bounds are left unchanged.
+pub fn distribute(bounds: &[Rect], axis: SpacingAxis) -> Vec<Vec2> {
+    let mut offsets = vec![Vec2::ZERO; bounds.len()];
+    let Some(gap) = equal_spacing_gap(bounds, axis) else {
+        return offsets;
+    };
+
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    match axis {
+        SpacingAxis::Horizontal => {
+            order.sort_by(|&a, &b| bounds[a].center().x.total_cmp(&bounds[b].center().x));
+            let mut cursor = bounds.iter().map(|b| b.x0).fold(f64::INFINITY, f64::min);
+            for i in order {
+                offsets[i] = Vec2::new(cursor - bounds[i].x0, 0.0);
+                cursor += bounds[i].width() + gap;
+            }
+        }
+        SpacingAxis::Vertical => {
+            order.sort_by(|&a, &b| bounds[a].center().y.total_cmp(&bounds[b].center().y));
+            let mut cursor = bounds.iter().map(|b| b.y0).fold(f64::INFINITY, f64::min);
+            for i in order {
+                offsets[i] = Vec2::new(0.0, cursor - bounds[i].y0);
+                cursor += bounds[i].height() + gap;
+            }
+        }
+    }
+    offsets
+}
+
+/// Offsets that snap a loose arrangement into a clean grid with the given gap.
+/// Rows are detected from the current layout and keep their reading order.
+pub fn tidy_up(bounds: &[Rect], gap: f64) -> Vec<Vec2> {
+    layout_rows(bounds, &reading_rows(bounds), gap)
+}
+
+/// Offsets that arrange bounds in a grid with `columns` columns, in reading order.
+pub fn arrange_grid(bounds: &[Rect], columns: usize, gap: f64) -> Vec<Vec2> {
+    let order = reading_rows(bounds).concat();
+    let rows: Vec<Vec<usize>> = order
+        .chunks(columns.max(1))
+        .map(<[usize]>::to_vec)
+        .collect();
+    layout_rows(bounds, &rows, gap)
+}
+
+/// Split bounds into rows in reading order (top to bottom, then left to right).
+/// A shape starts a new row when its vertical center lies below the current row.
+fn reading_rows(bounds: &[Rect]) -> Vec<Vec<usize>> {
+    let mut order: Vec<usize> = (0..bounds.len()).collect();
+    order.sort_by(|&a, &b| {
+        bounds[a]
+            .center()
+            .y
+            .total_cmp(&bounds[b].center().y)
+            .then(bounds[a].x0.total_cmp(&bounds[b].x0))
+    });
+
+    let mut rows: Vec<Vec<usize>> = Vec::new();
+    let mut row_bottom = f64::NEG_INFINITY;
+    for i in order {
+        match rows.last_mut() {
+            Some(row) if bounds[i].center().y <= row_bottom => {
+                row.push(i);
+                row_bottom = row_bottom.max(bounds[i].y1);
+            }
+            _ => {
+                rows.push(vec![i]);
+                row_bottom = bounds[i].y1;
+            }
+        }
+    }
+    for row in &mut rows {
+        row.sort_by(|&a, &b| bounds[a].x0.total_cmp(&bounds[b].x0));
+    }
+    rows
+}
+
+/// Lay out rows of shapes as a grid anchored at the top-left of the current bounds.
+/// Columns are as wide as their widest shape; shapes are centered vertically in their row.
+fn layout_rows(bounds: &[Rect], rows: &[Vec<usize>], gap: f64) -> Vec<Vec2> {
+    let mut offsets = vec![Vec2::ZERO; bounds.len()];
+    let Some(extent) = bounds.iter().copied().reduce(|a, b| a.union(b)) else {
+        return offsets;
+    };
+
+    let columns = rows.iter().map(Vec::len).max().unwrap_or(0);
+    let mut column_widths = vec![0.0_f64; columns];
+    for row in rows {
+        for (col, &i) in row.iter().enumerate() {
+            column_widths[col] = column_widths[col].max(bounds[i].width());
+        }
+    }
+
+    let mut y = extent.y0;
+    for row in rows {
+        let row_height = row.iter().map(|&i| bounds[i].height()).fold(0.0, f64::max);
+        let mut x = extent.x0;
+        for (col, &i) in row.iter().enumerate() {
+            let target = Point::new(x, y + (row_height - bounds[i].height()) / 2.0);
+            offsets[i] = target - bounds[i].origin();
+            x += column_widths[col] + gap;
+        }
+        y += row_height + gap;
+    }
+    offsets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(bounds: &[Rect], offsets: &[Vec2]) -> Vec<Rect> {
+        bounds.iter().zip(offsets).map(|(b, &o)| *b + o).collect()
+    }
+
+    #[test]
+    fn test_distribute_horizontal() {
+        let bounds = [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(70.0, 5.0, 80.0, 15.0),
+            Rect::new(15.0, 0.0, 35.0, 10.0),
+            Rect::new(90.0, 0.0, 100.0, 10.0),
+        ];
+        let moved = apply(&bounds, &distribute(&bounds, SpacingAxis::Horizontal));
+        // Span 100, widths 50, three gaps of 50/3
+        let gap = 50.0 / 3.0;
+        assert_eq!(moved[0].x0, 0.0);
+        assert!((moved[2].x0 - (10.0 + gap)).abs() < 1e-9);
+        assert!((moved[1].x0 - (30.0 + 2.0 * gap)).abs() < 1e-9);
+        assert!((moved[3].x0 - 90.0).abs() < 1e-9);
+        // Only moved along the axis
+        assert_eq!(moved[1].y0, 5.0);
+    }
+
+    #[test]
+    fn test_distribute_needs_three() {
+        let bounds = [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(50.0, 0.0, 60.0, 10.0),
+        ];
+        let offsets = distribute(&bounds, SpacingAxis::Vertical);
+        assert!(offsets.iter().all(|o| *o == Vec2::ZERO));
+    }
+
+    #[test]
+    fn test_tidy_up_rows() {
+        let bounds = [
+            Rect::new(3.0, 2.0, 23.0, 22.0),
+            Rect::new(47.0, 6.0, 57.0, 16.0),
+            Rect::new(1.0, 48.0, 31.0, 58.0),
+            Rect::new(40.0, 52.0, 50.0, 62.0),
+        ];
+        let moved = apply(&bounds, &tidy_up(&bounds, 10.0));
+        // First row anchored at the top-left of the selection
+        assert_eq!(moved[0].origin(), Point::new(1.0, 2.0));
+        // Column 0 is 30 wide, so column 1 starts at 1 + 30 + 10
+        assert_eq!(moved[1].x0, 41.0);
+        assert_eq!(moved[3].x0, 41.0);
+        // Second row starts below the 20-tall first row plus the gap
+        assert_eq!(moved[2].y0, 32.0);
+        // Shorter shapes are centered in their row
+        assert_eq!(moved[1].center().y, moved[0].center().y);
+    }
+
+    #[test]
+    fn test_arrange_grid_columns() {
+        let bounds: Vec<Rect> = (0..5)
+            .map(|i| Rect::from_origin_size((i as f64 * 30.0, 0.0), (10.0, 10.0)))
+            .collect();
+        let moved = apply(&bounds, &arrange_grid(&bounds, 2, 5.0));
+        assert_eq!(moved[0].origin(), Point::new(0.0, 0.0));
+        assert_eq!(moved[1].origin(), Point::new(15.0, 0.0));
+        assert_eq!(moved[2].origin(), Point::new(0.0, 15.0));
+        assert_eq!(moved[4].origin(), Point::new(0.0, 30.0));
+    }
+}
@@ -0,0 +1,478 @@
+//! Content-addressed binary assets.
+//!
+//! Image bytes live in an [`Asset`], identified by the SHA-256 of its content. Images
+//! with the same content share one asset in memory, and saved documents, bundles,
+//! storage backends and the CRDT keep each asset once, keyed by its id.
+//!
+//! In a saved document (format version 2 and later) every image refers to its bytes
+//! with an `"asset"` id, and the bytes themselves sit base64-encoded in a top-level
+//! `"assets"` object. Storage backends and bundles leave that object out and keep the
+//! raw bytes next to the document instead. A single shape serialized on its own, as on
+//! the clipboard, still carries its bytes inline as `"data_base64"`.
+
+use crate::shapes::{Shape, ShapeId};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::sync::Arc;
+
+/// Key of the asset table in a document's top-level object.
+pub const ASSETS_KEY: &str = "assets";
+/// Key of an image's asset reference.
+pub(crate) const ASSET_REF_KEY: &str = "asset";
+/// Key of an image's inline base64 data.
+pub(crate) const INLINE_DATA_KEY: &str = "data_base64";
+
+/// Maximum group nesting walked in a document.
+const MAX_DEPTH: usize = 64;
+
+/// Immutable binary content, shared between every image that uses it.
+#[derive(Clone)]
+pub struct Asset {
+    id: String,
+    bytes: Arc<[u8]>,
+}
+
+impl Asset {
+    /// Create an asset from its bytes, computing its id.
+    pub fn new(bytes: impl Into<Arc<[u8]>>) -> Self {
+        let bytes = bytes.into();
+        Self {
+            id: asset_id(&bytes),
+            bytes,
+        }
+    }
+
+    /// Create an asset from base64-encoded bytes.
+    pub fn from_base64(data: &str) -> Option<Self> {
+        STANDARD.decode(data).ok().map(Self::new)
+    }
+
+    /// Content id: the lowercase hex SHA-256 of the bytes.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The raw bytes.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Size in bytes.
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether the asset has no content.
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// The bytes, base64-encoded.
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(&self.bytes)
+    }
+}
+
+impl fmt::Debug for Asset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Asset")
+            .field("id", &self.id)
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+impl PartialEq for Asset {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Asset {}
+
+/// Assets serialize inline, as base64.
+impl Serialize for Asset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_base64())
+    }
+}
+
+impl<'de> Deserialize<'de> for Asset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = String::deserialize(deserializer)?;
+        Self::from_base64(&data).ok_or_else(|| serde::de::Error::custom("invalid base64 data"))
+    }
+}
+
+/// Compute the id of some content.
+pub fn asset_id(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Whether `id` is a well-formed asset id, safe to use as a file or entry name.
+pub fn is_asset_id(id: &str) -> bool {
+    id.len() == 64 && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// A set of assets keyed by id, holding each content once.
+#[derive(Debug, Clone, Default)]
+pub struct AssetStore {
+    assets: BTreeMap<String, Asset>,
+}
+
+impl AssetStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Collect the assets used by `shapes`, including those inside groups.
+    pub fn from_shapes<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> Self {
+        let mut store = Self::new();
+        for asset in image_assets(shapes).into_values() {
+            store.insert(asset);
+        }
+        store
+    }
+
+    /// Add an asset, returning the stored copy. Content already in the store is kept,
+    /// so the result shares its bytes with every earlier insert of the same content.
+    pub fn insert(&mut self, asset: Asset) -> Asset {
+        self.assets.entry(asset.id.clone()).or_insert(asset).clone()
+    }
+
+    /// Get an asset by id.
+    pub fn get(&self, id: &str) -> Option<&Asset> {
+        self.assets.get(id)
+    }
+
+    /// Whether an asset is present.
+    pub fn contains(&self, id: &str) -> bool {
+        self.assets.contains_key(id)
+    }
+
+    /// Number of distinct assets.
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    /// Whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    /// Iterate over the assets in id order.
+    pub fn iter(&self) -> impl Iterator<Item = &Asset> {
+        self.assets.values()
+    }
+}
+
+/// Call `f` with the object of every image in a serialized document, including images
+/// nested in groups and images kept by checkpoints.
+///
+/// An image never changes its content, so an image in a checkpoint uses the same asset
+/// as the image with its id on the board.
+pub(crate) fn for_each_image(
+    doc: &mut Map<String, Value>,
+    f: &mut impl FnMut(&mut Map<String, Value>) -> Result<(), String>,
+) -> Result<(), String> {
+    for_each_image_in(doc, f)?;
+    let Some(checkpoints) = doc.get_mut("checkpoints").and_then(Value::as_array_mut) else {
+        return Ok(());
+    };
+    for checkpoint in checkpoints {
+        if let Some(snapshot) = checkpoint
+            .get_mut("snapshot")
+            .and_then(Value::as_object_mut)
+        {
+            for_each_image_in(snapshot, f)?;
+        }
+    }
+    Ok(())
+}
+
+/// Call `f` with every image in the `"shapes"` of `container`.
+fn for_each_image_in(
+    container: &mut Map<String, Value>,
+    f: &mut impl FnMut(&mut Map<String, Value>) -> Result<(), String>,
+) -> Result<(), String> {
+    let Some(shapes) = container.get_mut("shapes").and_then(Value::as_object_mut) else {
+        return Ok(());
+    };
+    for shape in shapes.values_mut() {
+        visit_images(shape, f, 0)?;
+    }
+    Ok(())
+}
+
+fn visit_images(
+    shape: &mut Value,
+    f: &mut impl FnMut(&mut Map<String, Value>) -> Result<(), String>,
+    depth: usize,
+) -> Result<(), String> {
+    if depth > MAX_DEPTH {
+        return Err("groups nested too deeply".to_string());
+    }
+    if let Some(image) = shape.get_mut("Image").and_then(Value::as_object_mut) {
+        return f(image);
+    }
+    if let Some(children) = shape
+        .get_mut("Group")
+        .and_then(|g| g.get_mut("children"))
+        .and_then(Value::as_array_mut)
+    {
+        for child in children {
+            visit_images(child, f, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// The asset of every image in `shapes`, including those inside groups, by shape id.
+pub(crate) fn image_assets<'a>(
+    shapes: impl IntoIterator<Item = &'a Shape>,
+) -> HashMap<ShapeId, Asset> {
+    fn collect(shape: &Shape, images: &mut HashMap<ShapeId, Asset>) {
+        match shape {
+            Shape::Image(image) => {
+                images.insert(image.id, image.asset.clone());
+            }
+            Shape::Group(group) => group.children().iter().for_each(|c| collect(c, images)),
+            _ => {}
+        }
+    }
+
+    let mut images = HashMap::new();
+    shapes
+        .into_iter()
+        .for_each(|shape| collect(shape, &mut images));
+    images
+}
+
+/// Ids of the assets a serialized document refers to.
+pub fn referenced(doc: &Value) -> BTreeSet<String> {
+    fn visit(shape: &Value, ids: &mut BTreeSet<String>, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        if let Some(id) = shape["Image"][ASSET_REF_KEY].as_str() {
+            ids.insert(id.to_string());
+        }
+        if let Some(children) = shape["Group"]["children"].as_array() {
+            children.iter().for_each(|c| visit(c, ids, depth + 1));
+        }
+    }
+
+    let mut ids = BTreeSet::new();
+    let checkpoints = doc["checkpoints"].as_array().into_iter().flatten();
+    let containers = std::iter::once(doc).chain(checkpoints.map(|c| &c["snapshot"]));
+    for container in containers {
+        if let Some(shapes) = container["shapes"].as_object() {
+            shapes.values().for_each(|shape| visit(shape, &mut ids, 0));
+        }
+    }
+    ids
+}
+
+/// Replace the inline data of every image in a serialized document with a reference
+/// to `assets`, keyed by shape id.
+pub(crate) fn detach(
+    doc: &mut Map<String, Value>,
+    assets: &HashMap<ShapeId, Asset>,
+) -> Result<(), String> {
+    for_each_image(doc, &mut |image| {
+        let asset = image
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse::<ShapeId>().ok())
+            .and_then(|id| assets.get(&id))
+            .ok_or("image without asset")?;
+        image.remove(INLINE_DATA_KEY);
+        image.insert(ASSET_REF_KEY.to_string(), Value::from(asset.id()));
+        Ok(())
+    })
+}
+
+/// Resolve the asset references of a serialized document with `lookup`.
+///
+/// Images get empty inline data, so they deserialize without copying or hashing
+/// their bytes; the returned map from shape id to asset is then applied with
+/// [`apply`].
+pub(crate) fn attach<'a>(
+    doc: &mut Map<String, Value>,
+    lookup: impl Fn(&str) -> Option<&'a Asset>,
+) -> Result<HashMap<ShapeId, Asset>, String> {
+    let mut resolved = HashMap::new();
+    for_each_image(doc, &mut |image| {
+        let Some(reference) = image.remove(ASSET_REF_KEY) else {
+            // Inline data is left to deserialize as is
+            return Ok(());
+        };
+        let id = reference
+            .as_str()
+            .ok_or("asset reference is not a string")?;
+        let asset = lookup(id).ok_or_else(|| format!("missing asset {id}"))?;
+        let shape_id = image
+            .get("id")
+            .and_then(Value::as_str)
+            .and_then(|id| id.parse::<ShapeId>().ok())
+            .ok_or("image without a valid id")?;
+        resolved.insert(shape_id, asset.clone());
+        image.insert(INLINE_DATA_KEY.to_string(), Value::from(""));
+        Ok(())
+    })?;
+    Ok(resolved)
+}
+
+/// Give every image in `shapes` its resolved asset from [`attach`].
+pub(crate) fn apply<'a>(
+    shapes: impl IntoIterator<Item = &'a mut Shape>,
+    resolved: &HashMap<ShapeId, Asset>,
+) {
+    for shape in shapes {
+        match shape {
+            Shape::Image(image) => {
+                if let Some(asset) = resolved.get(&image.id) {
+                    image.asset = asset.clone();
+                }
+            }
+            Shape::Group(group) => apply(group.children_mut(), resolved),
+            _ => {}
+        }
+    }
+}
+
+/// Read the top-level asset table of a serialized document, removing it.
+pub(crate) fn take_table(doc: &mut Map<String, Value>) -> Result<AssetStore, String> {
+    let mut store = AssetStore::new();
+    let Some(table) = doc.remove(ASSETS_KEY) else {
+        return Ok(store);
+    };
+    let table = match table {
+        Value::Object(table) => table,
+        _ => return Err("'assets' is not an object".to_string()),
+    };
+    for (id, data) in table {
+        let asset = data
+            .as_str()
+            .and_then(Asset::from_base64)
+            .ok_or_else(|| format!("invalid data for asset {id}"))?;
+        if asset.id() != id {
+            return Err(format!("asset {id} does not match its content"));
+        }
+        store.insert(asset);
+    }
+    Ok(store)
+}
+
+/// Write `assets` as the top-level asset table of a serialized document.
+pub(crate) fn put_table(doc: &mut Map<String, Value>, assets: &AssetStore) {
+    let table: Map<String, Value> = assets
+        .iter()
+        .map(|asset| (asset.id().to_string(), Value::from(asset.to_base64())))
+        .collect();
+    doc.insert(ASSETS_KEY.to_string(), Value::Object(table));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::CanvasDocument;
+    use crate::shapes::{Group, Image, ImageFormat, Rectangle};
+    use kurbo::Point;
+
+    fn image(data: &[u8]) -> Shape {
+        Shape::Image(Image::new(Point::ZERO, data, 2, 2, ImageFormat::Png))
+    }
+
+    #[test]
+    fn test_asset_id() {
+        // SHA-256 test vectors
+        assert_eq!(
+            asset_id(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            asset_id(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(is_asset_id(&asset_id(b"abc")));
+        assert!(!is_asset_id("../../etc/passwd"));
+        assert!(!is_asset_id(&asset_id(b"abc").to_uppercase()));
+    }
+
+    #[test]
+    fn test_store_deduplicates() {
+        let shapes = vec![
+            image(b"same"),
+            image(b"same"),
+            Shape::Group(Group::new(vec![image(b"same"), image(b"other")])),
+            Shape::Rectangle(Rectangle::new(Point::ZERO, 1.0, 1.0)),
+        ];
+        let store = AssetStore::from_shapes(&shapes);
+        assert_eq!(store.len(), 2);
+        assert!(store.contains(&asset_id(b"same")));
+        assert!(store.contains(&asset_id(b"other")));
+    }
+
+    #[test]
+    fn test_store_shares_bytes() {
+        let mut store = AssetStore::new();
+        let first = store.insert(Asset::new(b"same".as_slice()));
+        let second = store.insert(Asset::new(b"same".as_slice()));
+        assert!(Arc::ptr_eq(&first.bytes, &second.bytes));
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(first.id()).unwrap().bytes(), b"same");
+    }
+
+    #[test]
+    fn test_document_stores_each_asset_once() {
+        let mut doc = CanvasDocument::new();
+        for _ in 0..5 {
+            doc.add_shape(image(b"screenshot"));
+        }
+        let value = doc.to_value().unwrap();
+        let table = value[ASSETS_KEY].as_object().unwrap();
+        assert_eq!(table.len(), 1);
+        assert_eq!(referenced(&value).len(), 1);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(json.matches(&STANDARD.encode(b"screenshot")).count(), 1);
+
+        let loaded = CanvasDocument::from_value(value).unwrap();
+        let images: Vec<_> = loaded.shapes.values().filter_map(Shape::as_image).collect();
+        assert_eq!(images.len(), 5);
+        assert!(images.iter().all(|i| i.data() == b"screenshot"));
+        assert!(
+            images
+                .windows(2)
+                .all(|w| Arc::ptr_eq(&w[0].asset.bytes, &w[1].asset.bytes))
+        );
+    }
+
+    #[test]
+    fn test_missing_and_tampered_assets() {
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(image(b"content"));
+        let id = asset_id(b"content");
+
+        let mut value = doc.to_value().unwrap();
+        value[ASSETS_KEY] = serde_json::json!({});
+        assert!(CanvasDocument::from_value(value).is_err());
+
+        let mut value = doc.to_value().unwrap();
+        value[ASSETS_KEY][&id] = Value::from(STANDARD.encode(b"something else"));
+        assert!(CanvasDocument::from_value(value).is_err());
+
+        // Inline images, as on the clipboard, still load
+        let shape: Shape =
+            serde_json::from_str(&serde_json::to_string(&image(b"x")).unwrap()).unwrap();
+        assert_eq!(shape.as_image().unwrap().data(), b"x");
+    }
+}
@@ -0,0 +1,431 @@
+//! DrafftInk bundles: a document and its assets in a single zip archive.
+//!
+//! A bundle holds `document.json`, the document with images referring to assets by
+//! id (see [`crate::assets`]), and one `assets/<id>` entry with the raw bytes of each
+//! distinct asset. It is a plain zip file, so standard tools can inspect it. Entries
+//! this version doesn't know about are ignored when reading.
+
+use crate::assets::{self, Asset, AssetStore};
+use crate::canvas::CanvasDocument;
+use crate::format::FormatError;
+use thiserror::Error;
+
+/// File extension of bundles.
+pub const BUNDLE_EXTENSION: &str = "drafftink";
+
+/// Entry holding the document.
+const DOCUMENT_ENTRY: &str = "document.json";
+/// Directory of the asset entries.
+const ASSETS_DIR: &str = "assets/";
+
+/// Largest entry read from a bundle, after decompression.
+const MAX_ENTRY_SIZE: usize = 512 * 1024 * 1024;
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+const LOCAL_HEADER_SIZE: usize = 30;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const END_OF_DIRECTORY_SIZE: usize = 22;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// Version 2.0: deflate, directories.
+const ZIP_VERSION: u16 = 20;
+/// General purpose flag: names are UTF-8.
+const FLAG_UTF8: u16 = 1 << 11;
+const FLAG_ENCRYPTED: u16 = 1;
+/// 1980-01-01, the earliest date zip can hold; bundles don't record times.
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// Errors that can occur while reading or writing a bundle.
+#[derive(Debug, Error)]
+pub enum BundleError {
+    #[error("Invalid bundle archive: {0}")]
+    Archive(String),
+    #[error("Bundle has no {DOCUMENT_ENTRY}")]
+    MissingDocument,
+    #[error("Invalid {DOCUMENT_ENTRY}: {0}")]
+    Json(String),
+    #[error("Asset {0} does not match its content")]
+    AssetMismatch(String),
+    #[error(transparent)]
+    Format(#[from] FormatError),
+    #[error("Failed to serialize document: {0}")]
+    Serialization(String),
+}
+
+/// Result type for bundle operations.
+pub type BundleResult<T> = Result<T, BundleError>;
+
+/// Check whether the data looks like a zip archive.
+pub fn is_bundle(data: &[u8]) -> bool {
+    data.starts_with(&LOCAL_HEADER_SIGNATURE.to_le_bytes())
+}
+
+/// Write a document and its assets as a bundle.
+pub fn write_bundle(document: &CanvasDocument) -> BundleResult<Vec<u8>> {
+    let (value, store) = document
+        .to_parts()
+        .map_err(|e| BundleError::Serialization(e.to_string()))?;
+    let json =
+        serde_json::to_vec_pretty(&value).map_err(|e| BundleError::Serialization(e.to_string()))?;
+
+    let mut writer = ZipWriter::default();
+    writer.add(DOCUMENT_ENTRY, &json)?;
+    for asset in store.iter() {
+        writer.add(&format!("{ASSETS_DIR}{}", asset.id()), asset.bytes())?;
+    }
+    writer.finish()
+}
+
+/// Read a bundle, upgrading documents saved by older versions.
+pub fn read_bundle(data: &[u8]) -> BundleResult<CanvasDocument> {
+    let mut document = None;
+    let mut store = AssetStore::new();
+    for entry in read_entries(data)? {
+        if entry.name == DOCUMENT_ENTRY {
+            document = Some(entry.data()?);
+        } else if let Some(id) = entry.name.strip_prefix(ASSETS_DIR) {
+            if !assets::is_asset_id(id) {
+                continue;
+            }
+            let asset = Asset::new(entry.data()?);
+            if asset.id() != id {
+                return Err(BundleError::AssetMismatch(id.to_string()));
+            }
+            store.insert(asset);
+        }
+    }
+    let document = document.ok_or(BundleError::MissingDocument)?;
+    let value = serde_json::from_slice(&document).map_err(|e| BundleError::Json(e.to_string()))?;
+    Ok(CanvasDocument::from_parts(value, &store)?)
+}
+
+/// Accumulates entries and their central directory records.
+#[derive(Default)]
+struct ZipWriter {
+    out: Vec<u8>,
+    directory: Vec<u8>,
+    count: u16,
+}
+
+impl ZipWriter {
+    /// Append an entry, deflated if that makes it smaller.
+    fn add(&mut self, name: &str, data: &[u8]) -> BundleResult<()> {
+        let deflated = miniz_oxide::deflate::compress_to_vec(data, 6);
+        let (method, stored) = if deflated.len() < data.len() {
+            (METHOD_DEFLATED, deflated.as_slice())
+        } else {
+            (METHOD_STORED, data)
+        };
+        let too_large = || BundleError::Serialization("bundle exceeds 4 GiB".to_string());
+        let offset = u32::try_from(self.out.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(data.len()).map_err(|_| too_large())?;
+        let compressed_size = u32::try_from(stored.len()).map_err(|_| too_large())?;
+        self.count = self.count.checked_add(1).ok_or_else(too_large)?;
+        let crc = crc32(data);
+        let name = name.as_bytes();
+
+        // Fields shared by the local and central headers, from "version needed" on
+        let mut common = Vec::with_capacity(26);
+        for field in [ZIP_VERSION, FLAG_UTF8, method, 0, DOS_DATE] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        for field in [crc, compressed_size, size] {
+            common.extend_from_slice(&field.to_le_bytes());
+        }
+        common.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        common.extend_from_slice(&0u16.to_le_bytes());
+
+        self.out
+            .extend_from_slice(&LOCAL_HEADER_SIGNATURE.to_le_bytes());
+        self.out.extend_from_slice(&common);
+        self.out.extend_from_slice(name);
+        self.out.extend_from_slice(stored);
+
+        self.directory
+            .extend_from_slice(&CENTRAL_HEADER_SIGNATURE.to_le_bytes());
+        self.directory.extend_from_slice(&ZIP_VERSION.to_le_bytes());
+        self.directory.extend_from_slice(&common);
+        // Comment length, disk number, internal and external attributes
+        self.directory.extend_from_slice(&[0; 10]);
+        self.directory.extend_from_slice(&offset.to_le_bytes());
+        self.directory.extend_from_slice(name);
+        Ok(())
+    }
+
+    /// Append the central directory and return the archive.
+    fn finish(mut self) -> BundleResult<Vec<u8>> {
+        let too_large = || BundleError::Serialization("bundle exceeds 4 GiB".to_string());
+        let offset = u32::try_from(self.out.len()).map_err(|_| too_large())?;
+        let size = u32::try_from(self.directory.len()).map_err(|_| too_large())?;
+        self.out.append(&mut self.directory);
+        self.out
+            .extend_from_slice(&END_OF_DIRECTORY_SIGNATURE.to_le_bytes());
+        // This disk and the disk with the directory
+        self.out.extend_from_slice(&[0; 4]);
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&self.count.to_le_bytes());
+        self.out.extend_from_slice(&size.to_le_bytes());
+        self.out.extend_from_slice(&offset.to_le_bytes());
+        // Comment length
+        self.out.extend_from_slice(&[0; 2]);
+        Ok(self.out)
+    }
+}
+
+/// An entry of an archive being read.
+struct Entry<'a> {
+    name: String,
+    method: u16,
+    crc: u32,
+    size: usize,
+    raw: &'a [u8],
+}
+
+impl Entry<'_> {
+    /// Decompress and check the entry's content.
+    fn data(&self) -> BundleResult<Vec<u8>> {
+        let data = match self.method {
+            METHOD_STORED => self.raw.to_vec(),
+            METHOD_DEFLATED => {
+                miniz_oxide::inflate::decompress_to_vec_with_limit(self.raw, self.size)
+                    .map_err(|e| archive_error(&self.name, &format!("{e:?}")))?
+            }
+            method => {
+                return Err(archive_error(
+                    &self.name,
+                    &format!("unsupported compression method {method}"),
+                ));
+            }
+        };
+        if data.len() != self.size || crc32(&data) != self.crc {
+            return Err(archive_error(&self.name, "corrupted content"));
+        }
+        Ok(data)
+    }
+}
+
+fn archive_error(name: &str, message: &str) -> BundleError {
+    BundleError::Archive(format!("{name}: {message}"))
+}
+
+/// Read the entries listed in an archive's central directory.
+fn read_entries(data: &[u8]) -> BundleResult<Vec<Entry<'_>>> {
+    let invalid = |message: &str| BundleError::Archive(message.to_string());
+    let end = find_end_of_directory(data).ok_or_else(|| invalid("not a zip archive"))?;
+    let count = read_u16(data, end + 10).ok_or_else(|| invalid("truncated archive"))?;
+    let mut pos = read_u32(data, end + 16).ok_or_else(|| invalid("truncated archive"))? as usize;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let header = data
+            .get(pos..pos + CENTRAL_HEADER_SIZE)
+            .filter(|h| read_u32(h, 0) == Some(CENTRAL_HEADER_SIGNATURE))
+            .ok_or_else(|| invalid("invalid central directory"))?;
+        let field16 = |at| read_u16(header, at).unwrap_or_default();
+        let field32 = |at| read_u32(header, at).unwrap_or_default();
+        let flags = field16(8);
+        let method = field16(10);
+        let crc = field32(16);
+        let compressed_size = field32(20) as usize;
+        let size = field32(24) as usize;
+        let name_len = field16(28) as usize;
+        let extra_len = field16(30) as usize;
+        let comment_len = field16(32) as usize;
+        let local = field32(42) as usize;
+
+        let name = data
+            .get(pos + CENTRAL_HEADER_SIZE..pos + CENTRAL_HEADER_SIZE + name_len)
+            .ok_or_else(|| invalid("truncated central directory"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        pos += CENTRAL_HEADER_SIZE + name_len + extra_len + comment_len;
+
+        if flags & FLAG_ENCRYPTED != 0 {
+            return Err(archive_error(&name, "encrypted entries are not supported"));
+        }
+        if size > MAX_ENTRY_SIZE {
+            return Err(archive_error(&name, "entry too large"));
+        }
+        // The local header repeats the name and may carry a different extra field
+        let local_header = data
+            .get(local..local + LOCAL_HEADER_SIZE)
+            .filter(|h| read_u32(h, 0) == Some(LOCAL_HEADER_SIGNATURE))
+            .ok_or_else(|| archive_error(&name, "invalid local header"))?;
+        let start = local
+            + LOCAL_HEADER_SIZE
+            + read_u16(local_header, 26).unwrap_or_default() as usize
+            + read_u16(local_header, 28).unwrap_or_default() as usize;
+        let raw = data
+            .get(start..start + compressed_size)
+            .ok_or_else(|| archive_error(&name, "truncated content"))?;
+        entries.push(Entry {
+            name,
+            method,
+            crc,
+            size,
+            raw,
+        });
+    }
+    Ok(entries)
+}
+
+/// Find the end of central directory record, which is followed by a comment of at
+/// most 64 KiB.
+fn find_end_of_directory(data: &[u8]) -> Option<usize> {
+    let last = data.len().checked_sub(END_OF_DIRECTORY_SIZE)?;
+    let first = last.saturating_sub(u16::MAX as usize);
+    (first..=last)
+        .rev()
+        .find(|&pos| read_u32(data, pos) == Some(END_OF_DIRECTORY_SIGNATURE))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+/// CRC-32 lookup table (IEEE polynomial, reflected).
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC_TABLE[((crc ^ byte as u32) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{Image, ImageFormat, Rectangle, Shape};
+    use kurbo::Point;
+
+    fn document() -> CanvasDocument {
+        let mut doc = CanvasDocument::new();
+        doc.name = "Bundled".to_string();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 10.0, 20.0)));
+        for i in 0..5 {
+            let position = Point::new(i as f64 * 50.0, 0.0);
+            doc.add_shape(Shape::Image(Image::new(
+                position,
+                b"screenshot bytes",
+                2,
+                2,
+                ImageFormat::Png,
+            )));
+        }
+        doc
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn test_roundtrip_stores_each_asset_once() {
+        let doc = document();
+        let data = write_bundle(&doc).unwrap();
+        assert!(is_bundle(&data));
+
+        let entries = read_entries(&data).unwrap();
+        let names: Vec<_> = entries.iter().map(|e| e.name.as_str()).collect();
+        let asset = format!("{ASSETS_DIR}{}", assets::asset_id(b"screenshot bytes"));
+        assert_eq!(names, [DOCUMENT_ENTRY, asset.as_str()]);
+
+        let loaded = read_bundle(&data).unwrap();
+        assert_eq!(loaded.name, "Bundled");
+        assert_eq!(loaded.z_order, doc.z_order);
+        let images: Vec<_> = loaded.shapes.values().filter_map(Shape::as_image).collect();
+        assert_eq!(images.len(), 5);
+        assert!(images.iter().all(|i| i.data() == b"screenshot bytes"));
+    }
+
+    #[test]
+    fn test_corrupted_bundles() {
+        let data = write_bundle(&document()).unwrap();
+
+        // Flip a byte of the asset, which is stored uncompressed
+        let mut corrupted = data.clone();
+        let at = data
+            .windows(b"screenshot bytes".len())
+            .position(|w| w == b"screenshot bytes")
+            .unwrap();
+        corrupted[at] ^= 1;
+        assert!(matches!(
+            read_bundle(&corrupted),
+            Err(BundleError::Archive(_))
+        ));
+
+        assert!(matches!(
+            read_bundle(&data[..data.len() - 1]),
+            Err(BundleError::Archive(_))
+        ));
+        assert!(matches!(
+            read_bundle(b"{\"shapes\": {}}"),
+            Err(BundleError::Archive(_))
+        ));
+    }
+
+    #[test]
+    fn test_asset_must_match_its_id() {
+        let doc = document();
+        let (value, _) = doc.to_parts().unwrap();
+        let mut writer = ZipWriter::default();
+        writer
+            .add(DOCUMENT_ENTRY, &serde_json::to_vec(&value).unwrap())
+            .unwrap();
+        let id = assets::asset_id(b"screenshot bytes");
+        writer
+            .add(&format!("{ASSETS_DIR}{id}"), b"other bytes")
+            .unwrap();
+        assert!(matches!(
+            read_bundle(&writer.finish().unwrap()),
+            Err(BundleError::AssetMismatch(found)) if found == id
+        ));
+    }
+
+    #[test]
+    fn test_missing_parts() {
+        let writer = ZipWriter::default();
+        assert!(matches!(
+            read_bundle(&writer.finish().unwrap()),
+            Err(BundleError::MissingDocument)
+        ));
+
+        // A document without its assets can't be loaded
+        let (value, _) = document().to_parts().unwrap();
+        let mut writer = ZipWriter::default();
+        writer
+            .add(DOCUMENT_ENTRY, &serde_json::to_vec(&value).unwrap())
+            .unwrap();
+        writer.add("thumbnail.png", b"ignored").unwrap();
+        assert!(matches!(
+            read_bundle(&writer.finish().unwrap()),
+            Err(BundleError::Format(FormatError::Document(_)))
+        ));
+    }
+}
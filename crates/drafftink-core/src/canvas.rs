@@ -1,10 +1,16 @@
 //! Canvas document and state management.
 
+use crate::arrange;
+use crate::assets::{self, AssetStore};
+use crate::bundle::BundleResult;
 use crate::camera::Camera;
+use crate::checkpoint::Checkpoint;
+use crate::format::{self, FormatError, FormatResult};
 use crate::shapes::{Group, Shape, ShapeId, ShapeTrait};
+use crate::snap::SpacingAxis;
 use crate::tools::{ToolKind, ToolManager};
 use crate::widget::{EditingKind, WidgetManager, WidgetState};
-use kurbo::{Point, Rect};
+use kurbo::{Affine, Point, Rect};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use uuid::Uuid;
@@ -32,6 +38,8 @@ pub struct CanvasDocument {
     pub shapes: HashMap<ShapeId, Shape>,
     /// Z-order of shapes (back to front).
     pub z_order: Vec<ShapeId>,
+    /// Named checkpoints, oldest first.
+    pub checkpoints: Vec<Checkpoint>,
     /// Undo history stack.
     #[serde(skip)]
     undo_stack: Vec<DocumentSnapshot>,
@@ -54,6 +62,7 @@ impl CanvasDocument {
             name: "Untitled".to_string(),
             shapes: HashMap::new(),
             z_order: Vec::new(),
+            checkpoints: Vec::new(),
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
         }
@@ -140,6 +149,26 @@ impl CanvasDocument {
         self.shapes.remove(&id)
     }
 
+    /// Remove a shape that may be nested inside a group.
+    /// Nested shapes are removed from their parent group; a top-level group left
+    /// empty by the removal is removed as well.
+    pub fn remove_nested_shape(&mut self, id: ShapeId) -> Option<Shape> {
+        if self.shapes.contains_key(&id) {
+            return self.remove_shape(id);
+        }
+
+        let (group_id, removed) = self.shapes.iter_mut().find_map(|(&group_id, shape)| {
+            shape
+                .as_group_mut()?
+                .remove_shape(id)
+                .map(|removed| (group_id, removed))
+        })?;
+        if matches!(self.shapes.get(&group_id), Some(Shape::Group(g)) if g.children.is_empty()) {
+            self.remove_shape(group_id);
+        }
+        Some(removed)
+    }
+
     /// Clear all shapes from the document.
     pub fn clear(&mut self) {
         self.shapes.clear();
@@ -156,6 +185,35 @@ impl CanvasDocument {
         self.shapes.get_mut(&id)
     }
 
+    /// Find a shape by ID, including shapes nested inside groups.
+    pub fn find_shape(&self, id: ShapeId) -> Option<&Shape> {
+        self.shapes.get(&id).or_else(|| {
+            self.shapes
+                .values()
+                .filter_map(Shape::as_group)
+                .find_map(|group| group.find_shape(id))
+        })
+    }
+
+    /// Find a mutable shape by ID, including shapes nested inside groups.
+    pub fn find_shape_mut(&mut self, id: ShapeId) -> Option<&mut Shape> {
+        if self.shapes.contains_key(&id) {
+            return self.shapes.get_mut(&id);
+        }
+        self.shapes
+            .values_mut()
+            .filter_map(Shape::as_group_mut)
+            .find_map(|group| group.find_shape_mut(id))
+    }
+
+    /// Get the ID of the group directly containing a shape, or None for top-level shapes.
+    pub fn parent_group(&self, id: ShapeId) -> Option<ShapeId> {
+        self.shapes
+            .values()
+            .filter_map(Shape::as_group)
+            .find_map(|group| group.find_parent(id))
+    }
+
     /// Get shapes in z-order (back to front).
     pub fn shapes_ordered(&self) -> impl Iterator<Item = &Shape> {
         self.z_order.iter().filter_map(|id| self.shapes.get(id))
@@ -248,242 +306,213 @@ impl CanvasDocument {
         self.shapes.len()
     }
 
-    /// Serialize the document to JSON.
+    /// Serialize the document to JSON, stamped with the current format version.
+    /// Each distinct image is embedded once, in the asset table.
     pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        serde_json::to_string_pretty(self)
+        serde_json::to_string_pretty(&self.to_value()?)
     }
 
-    /// Deserialize a document from JSON.
-    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json)
+    /// Serialize the document to a JSON value, stamped with the current format version.
+    /// Each distinct image is embedded once, in the asset table.
+    pub fn to_value(&self) -> Result<serde_json::Value, serde_json::Error> {
+        let (mut value, store) = self.to_parts()?;
+        if let Some(doc) = value.as_object_mut() {
+            assets::put_table(doc, &store);
+        }
+        Ok(value)
+    }
+
+    /// Serialize the document without its image bytes, for keeping assets apart from
+    /// it: images refer to assets by id, and the returned store holds each once.
+    pub fn to_parts(&self) -> Result<(serde_json::Value, AssetStore), serde_json::Error> {
+        let images = assets::image_assets(self.all_shapes());
+        let mut value = serde_json::to_value(format::Versioned::new(self))?;
+        if let Some(doc) = value.as_object_mut() {
+            assets::detach(doc, &images).map_err(serde::ser::Error::custom)?;
+        }
+        Ok((value, AssetStore::from_shapes(self.all_shapes())))
+    }
+
+    /// Top-level shapes together with those kept by checkpoints.
+    fn all_shapes(&self) -> impl Iterator<Item = &Shape> {
+        self.shapes
+            .values()
+            .chain(self.checkpoints.iter().flat_map(Checkpoint::shapes))
+    }
+
+    /// Deserialize a document from JSON, upgrading documents saved by older versions.
+    pub fn from_json(json: &str) -> FormatResult<Self> {
+        let value = serde_json::from_str(json).map_err(|e| FormatError::Json(e.to_string()))?;
+        Self::from_value(value)
+    }
+
+    /// Deserialize a document from a JSON value, upgrading documents saved by older
+    /// versions.
+    pub fn from_value(value: serde_json::Value) -> FormatResult<Self> {
+        Self::from_parts(value, &AssetStore::new())
+    }
+
+    /// Deserialize a document saved with [`to_parts`](Self::to_parts), upgrading
+    /// documents saved by older versions. Assets missing from the document's own
+    /// asset table are looked up in `assets`.
+    pub fn from_parts(value: serde_json::Value, assets: &AssetStore) -> FormatResult<Self> {
+        let mut value = format::upgrade(value)?;
+        let doc = value.as_object_mut().ok_or(FormatError::NotAnObject)?;
+        let table = assets::take_table(doc).map_err(FormatError::Document)?;
+        let resolved = assets::attach(doc, |id| table.get(id).or_else(|| assets.get(id)))
+            .map_err(FormatError::Document)?;
+        let mut document: Self =
+            serde_json::from_value(value).map_err(|e| FormatError::Document(e.to_string()))?;
+        assets::apply(document.shapes.values_mut(), &resolved);
+        for checkpoint in &mut document.checkpoints {
+            assets::apply(checkpoint.shapes_mut(), &resolved);
+        }
+        Ok(document)
     }
 
-    /// Import from Excalidraw JSON format.
+    /// Serialize the document and its assets as a bundle archive.
+    pub fn to_bundle(&self) -> BundleResult<Vec<u8>> {
+        crate::bundle::write_bundle(self)
+    }
+
+    /// Read a document from a bundle archive.
+    pub fn from_bundle(data: &[u8]) -> BundleResult<Self> {
+        crate::bundle::read_bundle(data)
+    }
+
+    /// Import from Excalidraw JSON format (a file or clipboard payload).
     pub fn from_excalidraw(json: &str) -> Result<Self, String> {
-        use crate::shapes::{
-            Arrow, Ellipse, FillPattern, Freehand, Line, PathStyle, Rectangle, ShapeStyle,
-            Sloppiness, Text,
-        };
+        let shapes = crate::excalidraw::parse_excalidraw(json).map_err(|e| e.to_string())?;
+        let mut doc = Self::new();
+        for shape in shapes {
+            doc.add_shape(shape);
+        }
+        Ok(doc)
+    }
+
+    /// Try to parse clipboard text as Excalidraw clipboard format.
+    /// Returns shapes if the text is valid Excalidraw clipboard JSON.
+    pub fn shapes_from_excalidraw_clipboard(text: &str) -> Option<Vec<Shape>> {
+        if !crate::excalidraw::is_excalidraw_clipboard(text) {
+            return None;
+        }
+        let shapes = crate::excalidraw::parse_excalidraw(text).ok()?;
+        if shapes.is_empty() {
+            None
+        } else {
+            Some(shapes)
+        }
+    }
+
+    /// Export the document as an `.excalidraw` file.
+    pub fn to_excalidraw(&self) -> String {
+        crate::excalidraw::export_excalidraw(self.shapes_ordered())
+    }
 
-        let data: serde_json::Value =
-            serde_json::from_str(json).map_err(|e| format!("Invalid JSON: {}", e))?;
+    /// Export the given shapes as an Excalidraw clipboard payload, in z-order.
+    /// Returns None if none of the shapes exist.
+    pub fn to_excalidraw_clipboard(&self, ids: &[ShapeId]) -> Option<String> {
+        let shapes: Vec<&Shape> = self
+            .shapes_ordered()
+            .filter(|shape| ids.contains(&shape.id()))
+            .collect();
+        if shapes.is_empty() {
+            None
+        } else {
+            Some(crate::excalidraw::export_excalidraw_clipboard(shapes))
+        }
+    }
 
-        let elements = data
-            .get("elements")
-            .and_then(|e| e.as_array())
-            .ok_or("Missing 'elements' array")?;
+    /// Import a tldraw `.tldr` file, with a summary of the elements that were skipped.
+    pub fn from_tldraw(json: &str) -> Result<(Self, crate::tldraw::Unsupported), String> {
+        let import = crate::tldraw::parse_tldraw(json).map_err(|e| e.to_string())?;
+        let mut doc = Self::new();
+        for shape in import.shapes {
+            doc.add_shape(shape);
+        }
+        Ok((doc, import.unsupported))
+    }
 
+    /// Import a draw.io diagram (plain or compressed), every page stacked vertically.
+    pub fn from_drawio(source: &str) -> Result<Self, String> {
+        let shapes = crate::drawio::parse_drawio(source).map_err(|e| e.to_string())?;
         let mut doc = Self::new();
+        for shape in shapes {
+            doc.add_shape(shape);
+        }
+        Ok(doc)
+    }
 
-        for elem in elements {
-            // Skip deleted elements
-            if elem
-                .get("isDeleted")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false)
-            {
-                continue;
-            }
+    /// Export the document as a draw.io diagram, in z-order.
+    pub fn to_drawio(&self) -> String {
+        crate::drawio::export_drawio(self.shapes_ordered())
+    }
 
-            let elem_type = elem.get("type").and_then(|t| t.as_str()).unwrap_or("");
-            let x = elem.get("x").and_then(|v| v.as_f64()).unwrap_or(0.0);
-            let y = elem.get("y").and_then(|v| v.as_f64()).unwrap_or(0.0);
-
-            // Parse colors
-            let stroke_color = parse_excalidraw_color(
-                elem.get("strokeColor")
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("#000000"),
-            );
-            let bg_color = elem
-                .get("backgroundColor")
-                .and_then(|v| v.as_str())
-                .unwrap_or("transparent");
-            let fill_color = if bg_color == "transparent" {
-                None
-            } else {
-                Some(parse_excalidraw_color(bg_color))
-            };
-
-            let stroke_width = elem
-                .get("strokeWidth")
-                .and_then(|v| v.as_f64())
-                .unwrap_or(2.0);
-            let roughness = elem.get("roughness").and_then(|v| v.as_i64()).unwrap_or(1);
-            let sloppiness = match roughness {
-                0 => Sloppiness::Architect,
-                1 => Sloppiness::Artist,
-                _ => Sloppiness::Cartoonist,
-            };
-
-            let fill_pattern = match elem
-                .get("fillStyle")
-                .and_then(|v| v.as_str())
-                .unwrap_or("solid")
-            {
-                "hachure" => FillPattern::Hachure,
-                "cross-hatch" => FillPattern::CrossHatch,
-                "zigzag" => FillPattern::ZigZag,
-                _ => FillPattern::Solid,
-            };
-
-            let style = ShapeStyle {
-                stroke_color,
-                stroke_width,
-                fill_color,
-                fill_pattern,
-                sloppiness,
-                seed: elem.get("seed").and_then(|v| v.as_u64()).unwrap_or(0) as u32,
-                opacity: elem.get("opacity").and_then(|v| v.as_f64()).unwrap_or(1.0),
-            };
-
-            let shape: Option<Shape> = match elem_type {
-                "rectangle" | "diamond" => {
-                    let width = elem.get("width").and_then(|v| v.as_f64()).unwrap_or(100.0);
-                    let height = elem.get("height").and_then(|v| v.as_f64()).unwrap_or(100.0);
-                    let mut rect = Rectangle::new(Point::new(x, y), width, height);
-                    rect.style = style;
-                    // Handle roundness
-                    if elem.get("roundness").is_some() {
-                        rect.corner_radius = Rectangle::DEFAULT_ADAPTIVE_RADIUS
-                            .min(width / 4.0)
-                            .min(height / 4.0);
-                    }
-                    // Diamond is rendered as rotated rectangle (TODO: proper diamond shape)
-                    Some(Shape::Rectangle(rect))
-                }
-                "ellipse" => {
-                    let width = elem.get("width").and_then(|v| v.as_f64()).unwrap_or(100.0);
-                    let height = elem.get("height").and_then(|v| v.as_f64()).unwrap_or(100.0);
-                    let center = Point::new(x + width / 2.0, y + height / 2.0);
-                    let mut ellipse = Ellipse::new(center, width / 2.0, height / 2.0);
-                    ellipse.style = style;
-                    Some(Shape::Ellipse(ellipse))
-                }
-                "freedraw" => {
-                    let points = elem.get("points").and_then(|p| p.as_array());
-                    if let Some(pts) = points {
-                        let freehand_points: Vec<Point> = pts
-                            .iter()
-                            .filter_map(|p| p.as_array())
-                            .filter_map(|arr| {
-                                let px = arr.first().and_then(|v| v.as_f64())?;
-                                let py = arr.get(1).and_then(|v| v.as_f64())?;
-                                Some(Point::new(x + px, y + py))
-                            })
-                            .collect();
-                        if !freehand_points.is_empty() {
-                            let mut freehand = Freehand::from_points(freehand_points);
-                            freehand.style = style;
-                            Some(Shape::Freehand(freehand))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                "line" => {
-                    let points = elem.get("points").and_then(|p| p.as_array());
-                    if let Some(pts) = points {
-                        let line_points: Vec<Point> = pts
-                            .iter()
-                            .filter_map(|p| p.as_array())
-                            .filter_map(|arr| {
-                                let px = arr.first().and_then(|v| v.as_f64())?;
-                                let py = arr.get(1).and_then(|v| v.as_f64())?;
-                                Some(Point::new(x + px, y + py))
-                            })
-                            .collect();
-                        if line_points.len() >= 2 {
-                            let path_style =
-                                if elem.get("roundness").map(|r| !r.is_null()).unwrap_or(false) {
-                                    PathStyle::Flowing
-                                } else {
-                                    PathStyle::Direct
-                                };
-                            let mut line = Line::from_points(line_points, path_style);
-                            line.style = style;
-                            Some(Shape::Line(line))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                "arrow" => {
-                    let points = elem.get("points").and_then(|p| p.as_array());
-                    if let Some(pts) = points {
-                        let arrow_points: Vec<Point> = pts
-                            .iter()
-                            .filter_map(|p| p.as_array())
-                            .filter_map(|arr| {
-                                let px = arr.first().and_then(|v| v.as_f64())?;
-                                let py = arr.get(1).and_then(|v| v.as_f64())?;
-                                Some(Point::new(x + px, y + py))
-                            })
-                            .collect();
-                        if arrow_points.len() >= 2 {
-                            let elbowed = elem
-                                .get("elbowed")
-                                .and_then(|v| v.as_bool())
-                                .unwrap_or(false);
-                            let has_roundness =
-                                elem.get("roundness").map(|r| !r.is_null()).unwrap_or(false);
-                            let path_style = if elbowed {
-                                PathStyle::Angular
-                            } else if has_roundness {
-                                PathStyle::Flowing
-                            } else {
-                                PathStyle::Direct
-                            };
-                            let mut arrow = Arrow::from_points(arrow_points, path_style);
-                            arrow.style = style;
-                            Some(Shape::Arrow(arrow))
-                        } else {
-                            None
-                        }
-                    } else {
-                        None
-                    }
-                }
-                "text" => {
-                    let content = elem
-                        .get("text")
-                        .and_then(|v| v.as_str())
-                        .unwrap_or("")
-                        .to_string();
-                    let font_size = elem
-                        .get("fontSize")
-                        .and_then(|v| v.as_f64())
-                        .unwrap_or(20.0);
-                    let mut text = Text::new(Point::new(x, y), content);
-                    text.font_size = font_size;
-                    text.style = style;
-                    Some(Shape::Text(text))
-                }
-                _ => None,
-            };
+    /// Import a Mermaid flowchart or sequence diagram.
+    pub fn from_mermaid(source: &str) -> Result<Self, String> {
+        let shapes = crate::mermaid::parse_mermaid(source).map_err(|e| e.to_string())?;
+        let mut doc = Self::new();
+        for shape in shapes {
+            doc.add_shape(shape);
+        }
+        Ok(doc)
+    }
 
-            if let Some(s) = shape {
-                doc.add_shape(s);
-            }
+    /// Try to parse clipboard text as a Mermaid diagram.
+    /// Returns shapes if the text is a supported Mermaid diagram.
+    pub fn shapes_from_mermaid_clipboard(text: &str) -> Option<Vec<Shape>> {
+        if !crate::mermaid::is_mermaid(text) {
+            return None;
         }
+        let shapes = crate::mermaid::parse_mermaid(text).ok()?;
+        if shapes.is_empty() {
+            None
+        } else {
+            Some(shapes)
+        }
+    }
 
+    /// Import a Graphviz DOT graph.
+    pub fn from_dot(source: &str) -> Result<Self, String> {
+        let shapes = crate::dot::parse_dot(source).map_err(|e| e.to_string())?;
+        let mut doc = Self::new();
+        for shape in shapes {
+            doc.add_shape(shape);
+        }
         Ok(doc)
     }
 
-    /// Try to parse clipboard text as Excalidraw clipboard format.
-    /// Returns shapes if the text is valid Excalidraw clipboard JSON.
-    pub fn shapes_from_excalidraw_clipboard(text: &str) -> Option<Vec<Shape>> {
-        let data: serde_json::Value = serde_json::from_str(text).ok()?;
-        let clip_type = data.get("type").and_then(|t| t.as_str())?;
-        if clip_type != "excalidraw/clipboard" {
+    /// Try to parse clipboard text as a Graphviz DOT graph.
+    /// Returns shapes if the text is a valid DOT graph.
+    pub fn shapes_from_dot_clipboard(text: &str) -> Option<Vec<Shape>> {
+        if !crate::dot::is_dot(text) {
             return None;
         }
-        let doc = Self::from_excalidraw(text).ok()?;
-        let shapes: Vec<Shape> = doc.shapes_ordered().cloned().collect();
+        let shapes = crate::dot::parse_dot(text).ok()?;
+        if shapes.is_empty() {
+            None
+        } else {
+            Some(shapes)
+        }
+    }
+
+    /// Import an SVG document as editable shapes.
+    pub fn from_svg(source: &str) -> Result<Self, String> {
+        let graphic = crate::svg::parse_svg(source).map_err(|e| e.to_string())?;
+        let mut doc = Self::new();
+        for shape in graphic.to_shapes() {
+            doc.add_shape(shape);
+        }
+        Ok(doc)
+    }
+
+    /// Try to parse clipboard text as SVG markup.
+    /// Returns the editable shapes of the document, as a single group.
+    pub fn shapes_from_svg_clipboard(text: &str) -> Option<Vec<Shape>> {
+        if !crate::svg::is_svg(text) {
+            return None;
+        }
+        let shapes = crate::svg::parse_svg(text).ok()?.to_shapes();
         if shapes.is_empty() {
             None
         } else {
@@ -598,6 +627,8 @@ pub struct Canvas {
     pub viewport_size: kurbo::Size,
     /// Widget manager for UI state (selection, hover, editing).
     pub widgets: WidgetManager,
+    /// Groups entered for editing, outermost first.
+    group_path: Vec<ShapeId>,
 }
 
 impl Default for Canvas {
@@ -616,6 +647,7 @@ impl Canvas {
             selection: Vec::new(),
             viewport_size: kurbo::Size::new(800.0, 600.0),
             widgets: WidgetManager::new(),
+            group_path: Vec::new(),
         }
     }
 
@@ -628,6 +660,7 @@ impl Canvas {
             selection: Vec::new(),
             viewport_size: kurbo::Size::new(800.0, 600.0),
             widgets: WidgetManager::new(),
+            group_path: Vec::new(),
         }
     }
 
@@ -666,10 +699,11 @@ impl Canvas {
         self.widgets.clear_selection();
     }
 
-    /// Select all shapes.
+    /// Select all shapes (or all children of the group being edited).
     pub fn select_all(&mut self) {
         self.clear_selection();
-        for &id in &self.document.z_order {
+        let ids: Vec<ShapeId> = self.context_shapes().iter().map(|s| s.id()).collect();
+        for id in ids {
             self.selection.push(id);
             self.widgets.add_to_selection(id);
         }
@@ -720,7 +754,7 @@ impl Canvas {
     /// Delete selected shapes.
     pub fn delete_selected(&mut self) {
         for id in self.selection.drain(..).collect::<Vec<_>>() {
-            self.document.remove_shape(id);
+            self.document.remove_nested_shape(id);
             self.widgets.remove(id);
         }
     }
@@ -734,7 +768,7 @@ impl Canvas {
         // Get combined bounds of all selected shapes
         let mut combined_bounds: Option<kurbo::Rect> = None;
         for &id in &self.selection {
-            if let Some(shape) = self.document.get_shape(id) {
+            if let Some(shape) = self.document.find_shape(id) {
                 let bounds = shape.bounds();
                 combined_bounds = Some(match combined_bounds {
                     Some(cb) => cb.union(bounds),
@@ -750,7 +784,7 @@ impl Canvas {
 
         // Flip each selected shape around the combined center
         for &id in &self.selection {
-            if let Some(shape) = self.document.get_shape_mut(id) {
+            if let Some(shape) = self.document.find_shape_mut(id) {
                 // Create flip transform: translate to origin, scale -1 on x, translate back
                 let flip = kurbo::Affine::translate(kurbo::Vec2::new(center_x, 0.0))
                     * kurbo::Affine::scale_non_uniform(-1.0, 1.0)
@@ -769,7 +803,7 @@ impl Canvas {
         // Get combined bounds of all selected shapes
         let mut combined_bounds: Option<kurbo::Rect> = None;
         for &id in &self.selection {
-            if let Some(shape) = self.document.get_shape(id) {
+            if let Some(shape) = self.document.find_shape(id) {
                 let bounds = shape.bounds();
                 combined_bounds = Some(match combined_bounds {
                     Some(cb) => cb.union(bounds),
@@ -785,7 +819,7 @@ impl Canvas {
 
         // Flip each selected shape around the combined center
         for &id in &self.selection {
-            if let Some(shape) = self.document.get_shape_mut(id) {
+            if let Some(shape) = self.document.find_shape_mut(id) {
                 // Create flip transform: translate to origin, scale -1 on y, translate back
                 let flip = kurbo::Affine::translate(kurbo::Vec2::new(0.0, center_y))
                     * kurbo::Affine::scale_non_uniform(1.0, -1.0)
@@ -795,10 +829,122 @@ impl Canvas {
         }
     }
 
-    /// Remove a shape from the canvas.
+    /// Enter a group's editing context so its children can be edited individually.
+    /// Only groups in the current context can be entered. Returns true on success.
+    pub fn enter_group(&mut self, id: ShapeId) -> bool {
+        if !matches!(self.document.find_shape(id), Some(Shape::Group(_)))
+            || self.document.parent_group(id) != self.editing_group()
+        {
+            return false;
+        }
+        self.clear_selection();
+        self.group_path.push(id);
+        true
+    }
+
+    /// Exit the innermost group context and select that group.
+    /// Returns the ID of the group that was exited.
+    pub fn exit_group(&mut self) -> Option<ShapeId> {
+        let id = self.group_path.pop()?;
+        self.clear_selection();
+        if self.document.find_shape(id).is_some() {
+            self.select(id);
+        }
+        Some(id)
+    }
+
+    /// Exit all group contexts, returning to top-level editing.
+    pub fn exit_all_groups(&mut self) {
+        if !self.group_path.is_empty() {
+            self.group_path.clear();
+            self.clear_selection();
+        }
+    }
+
+    /// Get the group currently being edited (innermost), if any.
+    pub fn editing_group(&self) -> Option<ShapeId> {
+        self.group_path.last().copied()
+    }
+
+    /// Get the stack of entered groups, outermost first.
+    pub fn group_path(&self) -> &[ShapeId] {
+        &self.group_path
+    }
+
+    /// Drop group contexts whose group no longer exists
+    /// (e.g. after undo, a remote sync or loading another document).
+    pub fn prune_group_context(&mut self) {
+        let valid = self
+            .group_path
+            .iter()
+            .take_while(|&&id| matches!(self.document.find_shape(id), Some(Shape::Group(_))))
+            .count();
+        if valid < self.group_path.len() {
+            self.group_path.truncate(valid);
+            self.clear_selection();
+        }
+    }
+
+    /// Shapes that can be picked in the current context, back to front:
+    /// the children of the group being edited, or all top-level shapes.
+    pub fn context_shapes(&self) -> Vec<&Shape> {
+        match self
+            .editing_group()
+            .and_then(|id| self.document.find_shape(id))
+            .and_then(Shape::as_group)
+        {
+            Some(group) => group.children().iter().collect(),
+            None => self.document.shapes_ordered().collect(),
+        }
+    }
+
+    /// Transform from the coordinates of the group being edited to the canvas:
+    /// the rotations of the entered groups, outermost first.
+    pub fn context_transform(&self) -> Affine {
+        self.group_path
+            .iter()
+            .filter_map(|&id| self.document.find_shape(id)?.as_group())
+            .fold(Affine::IDENTITY, |transform, group| {
+                transform * group.child_transform()
+            })
+    }
+
+    /// Map a canvas point into the coordinates of the group being edited.
+    pub fn to_context_point(&self, point: Point) -> Point {
+        self.context_transform().inverse() * point
+    }
+
+    /// Find shapes at a point within the current editing context (front to back).
+    pub fn shapes_at_point(&self, point: Point, tolerance: f64) -> Vec<ShapeId> {
+        if self.group_path.is_empty() {
+            return self.document.shapes_at_point(point, tolerance);
+        }
+        let point = self.to_context_point(point);
+        self.context_shapes()
+            .iter()
+            .rev()
+            .filter(|s| s.hit_test(point, tolerance))
+            .map(|s| s.id())
+            .collect()
+    }
+
+    /// Find shapes intersecting a rectangle within the current editing context.
+    pub fn shapes_in_rect(&self, rect: Rect) -> Vec<ShapeId> {
+        if self.group_path.is_empty() {
+            return self.document.shapes_in_rect(rect);
+        }
+        let rect = self.context_transform().inverse().transform_rect_bbox(rect);
+        self.context_shapes()
+            .iter()
+            .filter(|s| s.intersects_rect(rect))
+            .map(|s| s.id())
+            .collect()
+    }
+
+    /// Remove a shape from the canvas (including a child of a group being edited).
     pub fn remove_shape(&mut self, id: ShapeId) {
         self.selection.retain(|&s| s != id);
-        self.document.remove_shape(id);
+        self.document.remove_nested_shape(id);
         self.widgets.remove(id);
     }
 
@@ -875,46 +1021,50 @@ impl Canvas {
 
         all_children
     }
-}
 
-/// Parse Excalidraw color string to SerializableColor.
-fn parse_excalidraw_color(color: &str) -> crate::shapes::SerializableColor {
-    use crate::shapes::SerializableColor;
+    /// Distribute the selected shapes with equal gaps along an axis.
+    /// Returns true if any shape moved.
+    pub fn distribute_selected(&mut self, axis: SpacingAxis) -> bool {
+        self.arrange_selected(|bounds| arrange::distribute(bounds, axis))
+    }
 
-    if color == "transparent" {
-        return SerializableColor::transparent();
+    /// Snap the selected shapes into a clean grid, keeping their rows.
+    /// Returns true if any shape moved.
+    pub fn tidy_up_selected(&mut self, gap: f64) -> bool {
+        self.arrange_selected(|bounds| arrange::tidy_up(bounds, gap))
     }
 
-    // Handle hex colors (#rgb, #rrggbb, #rrggbbaa)
-    if let Some(hex) = color.strip_prefix('#') {
-        let hex = hex.trim();
-        match hex.len() {
-            3 => {
-                // #rgb -> #rrggbb
-                let r = u8::from_str_radix(&hex[0..1], 16).unwrap_or(0) * 17;
-                let g = u8::from_str_radix(&hex[1..2], 16).unwrap_or(0) * 17;
-                let b = u8::from_str_radix(&hex[2..3], 16).unwrap_or(0) * 17;
-                return SerializableColor::new(r, g, b, 255);
-            }
-            6 => {
-                let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-                let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-                let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-                return SerializableColor::new(r, g, b, 255);
-            }
-            8 => {
-                let r = u8::from_str_radix(&hex[0..2], 16).unwrap_or(0);
-                let g = u8::from_str_radix(&hex[2..4], 16).unwrap_or(0);
-                let b = u8::from_str_radix(&hex[4..6], 16).unwrap_or(0);
-                let a = u8::from_str_radix(&hex[6..8], 16).unwrap_or(255);
-                return SerializableColor::new(r, g, b, a);
+    /// Arrange the selected shapes in a grid with the given number of columns.
+    /// Returns true if any shape moved.
+    pub fn arrange_selected_in_grid(&mut self, columns: usize, gap: f64) -> bool {
+        self.arrange_selected(|bounds| arrange::arrange_grid(bounds, columns, gap))
+    }
+
+    /// Move the selected shapes by offsets computed from their visual bounds,
+    /// as a single undo step.
+    fn arrange_selected(&mut self, layout: impl FnOnce(&[Rect]) -> Vec<kurbo::Vec2>) -> bool {
+        let (ids, bounds): (Vec<ShapeId>, Vec<Rect>) = self
+            .selection
+            .iter()
+            .filter_map(|&id| Some((id, self.document.find_shape(id)?.rotated_bounds())))
+            .unzip();
+        if ids.len() < 2 {
+            return false;
+        }
+
+        let offsets = layout(&bounds);
+        if offsets.iter().all(|o| o.hypot() < 0.01) {
+            return false;
+        }
+
+        self.document.push_undo();
+        for (id, offset) in ids.into_iter().zip(offsets) {
+            if let Some(shape) = self.document.find_shape_mut(id) {
+                shape.transform(kurbo::Affine::translate(offset));
             }
-            _ => {}
         }
+        true
     }
-
-    // Default to black
-    SerializableColor::black()
 }
 
 #[cfg(test)]
@@ -1102,4 +1252,164 @@ mod tests {
         assert!(!doc.can_redo());
         assert!(!doc.redo());
     }
+
+    #[test]
+    fn test_nested_shape_lookup_and_removal() {
+        let mut doc = CanvasDocument::new();
+        let rect1 = Rectangle::new(Point::new(0.0, 0.0), 50.0, 50.0);
+        let rect2 = Rectangle::new(Point::new(100.0, 0.0), 50.0, 50.0);
+        let (id1, id2) = (rect1.id(), rect2.id());
+        doc.add_shape(Shape::Rectangle(rect1));
+        doc.add_shape(Shape::Rectangle(rect2));
+        let group_id = doc.group_shapes(&[id1, id2]).unwrap();
+
+        // Plain lookups and removal only see top-level shapes
+        assert!(doc.get_shape(id1).is_none());
+        assert!(doc.get_shape_mut(id2).is_none());
+        assert!(doc.remove_shape(id1).is_none());
+        assert!(doc.find_shape(id1).is_some());
+        assert!(doc.find_shape_mut(id2).is_some());
+        assert!(doc.find_shape(group_id).is_some());
+        assert_eq!(doc.parent_group(id1), Some(group_id));
+        assert_eq!(doc.parent_group(group_id), None);
+
+        assert!(doc.remove_nested_shape(id1).is_some());
+        assert!(doc.find_shape(id1).is_none());
+        assert!(doc.get_shape(group_id).is_some());
+
+        // Removing the last child drops the empty group
+        assert!(doc.remove_nested_shape(id2).is_some());
+        assert!(doc.is_empty());
+        assert!(doc.z_order.is_empty());
+    }
+
+    #[test]
+    fn test_enter_and_exit_group() {
+        let mut canvas = Canvas::new();
+        let rect1 = Rectangle::new(Point::new(0.0, 0.0), 50.0, 50.0);
+        let rect2 = Rectangle::new(Point::new(100.0, 0.0), 50.0, 50.0);
+        let (id1, id2) = (rect1.id(), rect2.id());
+        canvas.document.add_shape(Shape::Rectangle(rect1));
+        canvas.document.add_shape(Shape::Rectangle(rect2));
+        let group_id = canvas.document.group_shapes(&[id1, id2]).unwrap();
+
+        // Outside the group, hits resolve to the group itself
+        assert_eq!(
+            canvas.shapes_at_point(Point::new(0.0, 25.0), 0.0),
+            vec![group_id]
+        );
+
+        // Children cannot be entered, only groups
+        assert!(!canvas.enter_group(id1));
+        assert!(canvas.enter_group(group_id));
+        assert_eq!(canvas.editing_group(), Some(group_id));
+        assert_eq!(
+            canvas.shapes_at_point(Point::new(0.0, 25.0), 0.0),
+            vec![id1]
+        );
+        assert_eq!(
+            canvas.shapes_in_rect(Rect::new(90.0, -10.0, 200.0, 60.0)),
+            vec![id2]
+        );
+
+        canvas.select_all();
+        assert_eq!(canvas.selection, vec![id1, id2]);
+
+        assert_eq!(canvas.exit_group(), Some(group_id));
+        assert_eq!(canvas.editing_group(), None);
+        assert_eq!(canvas.selection, vec![group_id]);
+    }
+
+    #[test]
+    fn test_rotated_group_context() {
+        let mut canvas = Canvas::new();
+        let rect1 = Rectangle::new(Point::new(0.0, 0.0), 50.0, 50.0);
+        let rect2 = Rectangle::new(Point::new(100.0, 0.0), 50.0, 50.0);
+        let (id1, id2) = (rect1.id(), rect2.id());
+        canvas.document.add_shape(Shape::Rectangle(rect1));
+        canvas.document.add_shape(Shape::Rectangle(rect2));
+        let group_id = canvas.document.group_shapes(&[id1, id2]).unwrap();
+
+        // A quarter turn about the group's center (75, 25) stands it upright
+        canvas
+            .document
+            .get_shape_mut(group_id)
+            .unwrap()
+            .set_rotation(std::f64::consts::FRAC_PI_2);
+        let bounds = canvas
+            .document
+            .get_shape(group_id)
+            .unwrap()
+            .rotated_bounds();
+        assert!((bounds.x0 - 50.0).abs() < 1e-9 && (bounds.x1 - 100.0).abs() < 1e-9);
+        assert!((bounds.y0 + 50.0).abs() < 1e-9 && (bounds.y1 - 100.0).abs() < 1e-9);
+
+        // Half a turn swaps the children on screen
+        canvas
+            .document
+            .get_shape_mut(group_id)
+            .unwrap()
+            .set_rotation(std::f64::consts::PI);
+        assert_eq!(
+            canvas.shapes_at_point(Point::new(0.0, 25.0), 0.0),
+            vec![group_id]
+        );
+        assert!(canvas.enter_group(group_id));
+        assert_eq!(
+            canvas.shapes_at_point(Point::new(0.0, 25.0), 0.5),
+            vec![id2]
+        );
+        assert_eq!(
+            canvas.shapes_in_rect(Rect::new(90.0, -10.0, 200.0, 60.0)),
+            vec![id1]
+        );
+        let local = canvas.to_context_point(Point::new(0.0, 25.0));
+        assert!((local.x - 150.0).abs() < 1e-9 && (local.y - 25.0).abs() < 1e-9);
+
+        // Entering and leaving the group keeps its rotation
+        assert_eq!(canvas.exit_group(), Some(group_id));
+        let group = canvas.document.get_shape(group_id).unwrap();
+        assert_eq!(group.rotation(), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn test_prune_group_context() {
+        let mut canvas = Canvas::new();
+        let rect1 = Rectangle::new(Point::new(0.0, 0.0), 50.0, 50.0);
+        let rect2 = Rectangle::new(Point::new(100.0, 0.0), 50.0, 50.0);
+        let (id1, id2) = (rect1.id(), rect2.id());
+        canvas.document.add_shape(Shape::Rectangle(rect1));
+        canvas.document.add_shape(Shape::Rectangle(rect2));
+        let group_id = canvas.document.group_shapes(&[id1, id2]).unwrap();
+        assert!(canvas.enter_group(group_id));
+
+        canvas.document.ungroup_shape(group_id);
+        canvas.prune_group_context();
+        assert!(canvas.group_path().is_empty());
+    }
+
+    #[test]
+    fn test_distribute_selected_single_undo() {
+        let mut canvas = Canvas::new();
+        let mut ids = Vec::new();
+        for x in [0.0, 20.0, 100.0] {
+            let rect = Rectangle::new(Point::new(x, 0.0), 10.0, 10.0);
+            ids.push(rect.id());
+            canvas.document.add_shape(Shape::Rectangle(rect));
+        }
+        for &id in &ids {
+            canvas.add_to_selection(id);
+        }
+
+        assert!(canvas.distribute_selected(SpacingAxis::Horizontal));
+        let x = canvas.document.get_shape(ids[1]).unwrap().bounds().x0;
+        assert!((x - 50.0).abs() < 1e-9);
+
+        // Already distributed: nothing to do
+        assert!(!canvas.distribute_selected(SpacingAxis::Horizontal));
+
+        assert!(canvas.document.undo());
+        assert_eq!(canvas.document.get_shape(ids[1]).unwrap().bounds().x0, 20.0);
+        assert!(!canvas.document.can_undo());
+    }
 }
@@ -0,0 +1,317 @@
+//! Named checkpoints.
+//!
+//! A checkpoint bookmarks a state of the board under a name, such as "before
+//! refactor", so it can be compared against or brought back later. Checkpoints are
+//! saved with the document. In a collaboration room a checkpoint records a version
+//! of the shared Loro document, which its history can rebuild; other documents have
+//! no lasting history, so their checkpoints keep a full copy of the shapes.
+
+use crate::canvas::CanvasDocument;
+use crate::shapes::{Shape, ShapeId};
+use crate::storage::{Storage, StorageResult};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use loro::Frontiers;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+#[cfg(target_arch = "wasm32")]
+use web_time::{SystemTime, UNIX_EPOCH};
+
+/// A named state of a document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// Unique checkpoint identifier.
+    pub id: String,
+    /// Name given by the user.
+    pub name: String,
+    /// Unix time of creation in seconds.
+    pub created: i64,
+    /// How the state is recorded.
+    #[serde(flatten)]
+    pub state: CheckpointState,
+}
+
+/// How a checkpoint records the state of its document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckpointState {
+    /// A version of the collaborative document: its Loro frontiers, encoded and
+    /// base64'd.
+    Version(String),
+    /// A copy of the shapes.
+    Snapshot(CheckpointSnapshot),
+}
+
+/// The shapes of a document at a checkpoint, laid out like the document's own.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CheckpointSnapshot {
+    /// Shapes keyed by ID.
+    pub shapes: HashMap<ShapeId, Shape>,
+    /// Z-order of the shapes (back to front).
+    pub z_order: Vec<ShapeId>,
+}
+
+impl Checkpoint {
+    /// Checkpoint a document by copying its shapes.
+    pub fn snapshot(name: &str, document: &CanvasDocument) -> Self {
+        Self::new(
+            name,
+            CheckpointState::Snapshot(CheckpointSnapshot {
+                shapes: document.shapes.clone(),
+                z_order: document.z_order.clone(),
+            }),
+        )
+    }
+
+    /// Checkpoint a version of the collaborative document.
+    pub fn version(name: &str, version: &Frontiers) -> Self {
+        Self::new(
+            name,
+            CheckpointState::Version(STANDARD.encode(version.encode())),
+        )
+    }
+
+    fn new(name: &str, state: CheckpointState) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name: name.to_string(),
+            created: unix_now(),
+            state,
+        }
+    }
+
+    /// The recorded version, for checkpoints of the collaborative document.
+    pub fn frontiers(&self) -> Option<Frontiers> {
+        match &self.state {
+            CheckpointState::Version(encoded) => {
+                Frontiers::decode(&STANDARD.decode(encoded).ok()?).ok()
+            }
+            CheckpointState::Snapshot(_) => None,
+        }
+    }
+
+    /// The copied shapes, for snapshot checkpoints.
+    pub fn shapes(&self) -> impl Iterator<Item = &Shape> {
+        let shapes = match &self.state {
+            CheckpointState::Snapshot(snapshot) => Some(snapshot.shapes.values()),
+            CheckpointState::Version(_) => None,
+        };
+        shapes.into_iter().flatten()
+    }
+
+    pub(crate) fn shapes_mut(&mut self) -> impl Iterator<Item = &mut Shape> {
+        let shapes = match &mut self.state {
+            CheckpointState::Snapshot(snapshot) => Some(snapshot.shapes.values_mut()),
+            CheckpointState::Version(_) => None,
+        };
+        shapes.into_iter().flatten()
+    }
+}
+
+impl CheckpointSnapshot {
+    /// The snapshot as a document named `name`.
+    pub fn to_document(&self, name: &str) -> CanvasDocument {
+        let mut document = CanvasDocument::new();
+        document.name = name.to_string();
+        for id in &self.z_order {
+            if let Some(shape) = self.shapes.get(id) {
+                document.add_shape(shape.clone());
+            }
+        }
+        document
+    }
+}
+
+impl CanvasDocument {
+    /// Add a checkpoint, returning its ID.
+    pub fn add_checkpoint(&mut self, checkpoint: Checkpoint) -> String {
+        let id = checkpoint.id.clone();
+        self.checkpoints.push(checkpoint);
+        id
+    }
+
+    /// Get a checkpoint by ID.
+    pub fn checkpoint(&self, id: &str) -> Option<&Checkpoint> {
+        self.checkpoints.iter().find(|c| c.id == id)
+    }
+
+    /// Remove a checkpoint. Returns whether it existed.
+    pub fn remove_checkpoint(&mut self, id: &str) -> bool {
+        let len = self.checkpoints.len();
+        self.checkpoints.retain(|c| c.id != id);
+        self.checkpoints.len() != len
+    }
+
+    /// A copy of the document under a new ID and name, without its checkpoints or
+    /// undo history. Shapes keep their IDs, so the copy can be compared with the
+    /// original.
+    pub fn fork(&self, name: &str) -> CanvasDocument {
+        let mut document = CanvasDocument::new();
+        document.name = name.to_string();
+        document.shapes = self.shapes.clone();
+        document.z_order = self.z_order.clone();
+        document
+    }
+}
+
+/// Save a copy of `state` (a checkpoint's document, or any other) to storage as a
+/// new document named `name`, stored under its name like documents saved from the
+/// app. Returns the new document.
+pub async fn fork_to_storage<S: Storage + ?Sized>(
+    storage: &S,
+    state: &CanvasDocument,
+    name: &str,
+) -> StorageResult<CanvasDocument> {
+    let document = state.fork(name);
+    storage.save(name, &document).await?;
+    Ok(document)
+}
+
+/// Name of a document forked from a checkpoint.
+pub fn fork_name(document_name: &str, checkpoint_name: &str) -> String {
+    format!("{} ({})", document_name, checkpoint_name)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{Image, ImageFormat, Rectangle};
+    use crate::storage::MemoryStorage;
+    use kurbo::Point;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        fn dummy_raw_waker() -> RawWaker {
+            fn no_op(_: *const ()) {}
+            fn clone(_: *const ()) -> RawWaker {
+                dummy_raw_waker()
+            }
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(dummy_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut f = std::pin::pin!(f);
+
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    fn rect(x: f64) -> Shape {
+        Shape::Rectangle(Rectangle::new(Point::new(x, 0.0), 10.0, 10.0))
+    }
+
+    #[test]
+    fn test_snapshot_keeps_old_state() {
+        let mut doc = CanvasDocument::new();
+        let shape = rect(0.0);
+        let first = shape.id();
+        doc.add_shape(shape);
+        let id = doc.add_checkpoint(Checkpoint::snapshot("before refactor", &doc));
+        doc.remove_shape(first);
+        doc.add_shape(rect(50.0));
+
+        let checkpoint = doc.checkpoint(&id).unwrap();
+        assert_eq!(checkpoint.name, "before refactor");
+        assert!(checkpoint.created > 0);
+        assert!(checkpoint.frontiers().is_none());
+        let CheckpointState::Snapshot(snapshot) = &checkpoint.state else {
+            panic!("expected a snapshot");
+        };
+        let past = snapshot.to_document("past");
+        assert_eq!(past.z_order, vec![first]);
+        assert_eq!(checkpoint.shapes().count(), 1);
+    }
+
+    #[test]
+    fn test_version_checkpoint_round_trips_frontiers() {
+        let doc = loro::LoroDoc::new();
+        doc.get_text("t").insert(0, "hello").unwrap();
+        doc.commit();
+        let version = doc.oplog_frontiers();
+
+        let checkpoint = Checkpoint::version("v1", &version);
+        assert_eq!(checkpoint.frontiers(), Some(version));
+        assert_eq!(checkpoint.shapes().count(), 0);
+
+        let broken = Checkpoint {
+            state: CheckpointState::Version("not base64!".to_string()),
+            ..checkpoint
+        };
+        assert!(broken.frontiers().is_none());
+    }
+
+    #[test]
+    fn test_checkpoints_are_saved_with_document() {
+        let mut doc = CanvasDocument::new();
+        let bytes = vec![0x89, b'P', b'N', b'G', 1, 2, 3];
+        doc.add_shape(Shape::Image(Image::new(
+            Point::ZERO,
+            &bytes,
+            10,
+            10,
+            ImageFormat::Png,
+        )));
+        doc.add_checkpoint(Checkpoint::snapshot("with image", &doc));
+
+        let json = doc.to_json().unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        // The image bytes are stored once, in the asset table
+        assert_eq!(value["assets"].as_object().unwrap().len(), 1);
+        assert_eq!(json.matches(&crate::assets::asset_id(&bytes)).count(), 3);
+
+        let loaded = CanvasDocument::from_json(&json).unwrap();
+        assert_eq!(loaded.checkpoints.len(), 1);
+        let Some(Shape::Image(image)) = loaded.checkpoints[0].shapes().next() else {
+            panic!("expected the image");
+        };
+        assert_eq!(image.asset.bytes(), bytes.as_slice());
+    }
+
+    #[test]
+    fn test_remove_checkpoint() {
+        let mut doc = CanvasDocument::new();
+        let id = doc.add_checkpoint(Checkpoint::snapshot("a", &doc));
+        assert!(doc.remove_checkpoint(&id));
+        assert!(!doc.remove_checkpoint(&id));
+        assert!(doc.checkpoints.is_empty());
+    }
+
+    #[test]
+    fn test_fork_to_storage() {
+        let storage = MemoryStorage::new();
+        let mut doc = CanvasDocument::new();
+        doc.name = "Architecture".to_string();
+        let shape = rect(0.0);
+        let shape_id = shape.id();
+        doc.add_shape(shape);
+        doc.add_checkpoint(Checkpoint::snapshot("before refactor", &doc));
+
+        let name = fork_name(&doc.name, "before refactor");
+        let fork = block_on(fork_to_storage(&storage, &doc, &name)).unwrap();
+        assert_ne!(fork.id, doc.id);
+        assert_eq!(fork.name, "Architecture (before refactor)");
+        assert_eq!(fork.z_order, vec![shape_id]);
+        assert!(fork.checkpoints.is_empty());
+
+        let stored = block_on(storage.load(&name)).unwrap();
+        assert_eq!(stored.id, fork.id);
+        assert_eq!(stored.z_order, fork.z_order);
+    }
+}
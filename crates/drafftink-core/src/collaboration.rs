@@ -6,7 +6,9 @@
 use serde_json;
 
 use crate::canvas::CanvasDocument;
-use crate::crdt::CrdtDocument;
+use crate::checkpoint::{Checkpoint, CheckpointState, fork_name};
+use crate::crdt::{CrdtDocument, Frontiers, HistoryEntry};
+use crate::e2e::{E2eError, E2eResult, RoomKey};
 use crate::shapes::{Shape, ShapeId};
 use crate::sync::{
     AwarenessState, ClientMessage, CursorPosition, ServerMessage, SyncEvent, base64_decode,
@@ -23,10 +25,16 @@ pub struct CollaborationManager {
     peer_id: u64,
     /// Current room ID (if connected).
     current_room: Option<String>,
+    /// Whether we joined the current room with a view token.
+    read_only: bool,
+    /// Key of the end-to-end encrypted room we're joining or in.
+    room_key: Option<RoomKey>,
     /// Local awareness state (cursor, user info).
     awareness: AwarenessState,
     /// Pending outgoing messages (JSON strings).
     outgoing: Vec<String>,
+    /// ID of the local document whose history the CRDT records outside of rooms.
+    history_document: Option<String>,
 }
 
 impl CollaborationManager {
@@ -39,8 +47,11 @@ impl CollaborationManager {
             enabled: false,
             peer_id,
             current_room: None,
+            read_only: false,
+            room_key: None,
             awareness: AwarenessState::default(),
             outgoing: Vec::new(),
+            history_document: None,
         }
     }
 
@@ -52,8 +63,11 @@ impl CollaborationManager {
             enabled: false,
             peer_id,
             current_room: None,
+            read_only: false,
+            room_key: None,
             awareness: AwarenessState::default(),
             outgoing: Vec::new(),
+            history_document: None,
         }
     }
 
@@ -95,45 +109,102 @@ impl CollaborationManager {
         if !self.enabled {
             return;
         }
+        let _ = self.crdt.apply_document(doc);
+    }
 
-        // Clear existing CRDT shapes
-        let _ = self.crdt.clear();
+    /// Sync CRDT state to local document.
+    /// This should be called when receiving remote updates.
+    pub fn sync_from_crdt(&self, doc: &mut CanvasDocument) {
+        self.crdt.read_into(doc);
+    }
 
-        // Set document name
-        let _ = self.crdt.set_name(&doc.name);
+    // --- History ---
 
-        // Add all shapes in z-order
-        for shape_id in &doc.z_order {
-            if let Some(shape) = doc.shapes.get(shape_id) {
-                let _ = self.crdt.add_shape(shape);
+    /// Record the local document in the CRDT so it has a version history even
+    /// when not in a room. Switching to another document starts a new history.
+    /// Returns whether a new version was recorded.
+    pub fn record_history(&mut self, doc: &CanvasDocument) -> bool {
+        if !self.is_in_room() && self.history_document.as_deref() != Some(doc.id.as_str()) {
+            if self.history_document.is_some() {
+                self.crdt = CrdtDocument::new();
+                self.peer_id = self.crdt.loro_doc().peer_id();
             }
+            self.history_document = Some(doc.id.clone());
         }
+        self.crdt.apply_document(doc).unwrap_or(false)
     }
 
-    /// Sync CRDT state to local document.
-    /// This should be called when receiving remote updates.
-    pub fn sync_from_crdt(&self, doc: &mut CanvasDocument) {
-        // Clear local document
-        doc.shapes.clear();
-        doc.z_order.clear();
-
-        // Update name
-        let name = self.crdt.name();
-        if !name.is_empty() {
-            doc.name = name;
+    /// All recorded changes, oldest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        self.crdt.history()
+    }
+
+    /// The document as it was at `version`.
+    pub fn document_at(&self, version: &Frontiers) -> Option<CanvasDocument> {
+        self.crdt.document_at(version)
+    }
+
+    /// Restore `doc` to `version` as a new change (undoable locally, synced to peers).
+    /// Returns whether anything changed.
+    pub fn restore_version(&mut self, doc: &mut CanvasDocument, version: &Frontiers) -> bool {
+        // Make sure the latest local edits are part of the history first
+        self.crdt.apply_document(doc).ok();
+        if !self.crdt.restore(version) {
+            return false;
         }
+        doc.push_undo();
+        self.crdt.read_into(doc);
+        true
+    }
 
-        // Rebuild from CRDT
-        let z_order = self.crdt.z_order();
-        for id_str in z_order {
-            if let Some(shape) = self.crdt.get_shape(&id_str) {
-                let shape_id = shape.id();
-                doc.shapes.insert(shape_id, shape);
-                doc.z_order.push(shape_id);
+    // --- Checkpoints ---
+
+    /// Add a named checkpoint of `doc`'s current state, returning its ID.
+    ///
+    /// In a room the checkpoint records the version of the shared document (and is
+    /// synced to peers); otherwise it keeps a copy of the shapes, since local history
+    /// doesn't outlive the session.
+    pub fn create_checkpoint(&mut self, doc: &mut CanvasDocument, name: &str) -> String {
+        if !self.is_in_room() {
+            return doc.add_checkpoint(Checkpoint::snapshot(name, doc));
+        }
+        // Make sure the version includes the latest local edits
+        self.crdt.apply_document(doc).ok();
+        let version = self.crdt.loro_doc().oplog_frontiers();
+        let id = doc.add_checkpoint(Checkpoint::version(name, &version));
+        self.crdt.apply_document(doc).ok();
+        id
+    }
+
+    /// The board as it was at a checkpoint of `doc`, named after both.
+    /// Returns `None` for unknown checkpoints, and for versions this client's
+    /// history doesn't contain (e.g. a room checkpoint opened outside the room).
+    pub fn checkpoint_document(&self, doc: &CanvasDocument, id: &str) -> Option<CanvasDocument> {
+        let checkpoint = doc.checkpoint(id)?;
+        let name = fork_name(&doc.name, &checkpoint.name);
+        match &checkpoint.state {
+            CheckpointState::Snapshot(snapshot) => Some(snapshot.to_document(&name)),
+            CheckpointState::Version(_) => {
+                let mut past = self.crdt.document_at(&checkpoint.frontiers()?)?;
+                past.name = name;
+                past.checkpoints.clear();
+                Some(past)
             }
         }
     }
 
+    /// Remove a checkpoint of `doc`, syncing the removal to peers in a room.
+    /// Returns whether it existed.
+    pub fn remove_checkpoint(&mut self, doc: &mut CanvasDocument, id: &str) -> bool {
+        if !doc.remove_checkpoint(id) {
+            return false;
+        }
+        if self.is_in_room() {
+            self.crdt.apply_document(doc).ok();
+        }
+        true
+    }
+
     // --- Shape Operations (synced to CRDT) ---
 
     /// Add a shape, syncing to CRDT if enabled.
@@ -247,19 +318,64 @@ impl CollaborationManager {
         self.current_room = room;
         if self.current_room.is_some() {
             self.enable();
+        } else {
+            self.read_only = false;
+            self.room_key = None;
         }
     }
 
-    /// Request to join a room. Queues the join message.
-    pub fn join_room(&mut self, room: &str) {
+    /// Check if we can only follow the current room, not change it.
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// Set whether we can only follow the current room (reported by the server).
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    /// Get the key of the end-to-end encrypted room, if any.
+    pub fn room_key(&self) -> Option<&RoomKey> {
+        self.room_key.as_ref()
+    }
+
+    /// Set the key of the room to join next (`None` for rooms that aren't end-to-end
+    /// encrypted). Everything sent to the room is sealed with it.
+    pub fn set_room_key(&mut self, key: Option<RoomKey>) {
+        self.room_key = key;
+    }
+
+    /// Request to join a room, with an access `token` for protected rooms. Queues
+    /// the join message, with the version of the CRDT so the server only sends
+    /// what it's missing.
+    pub fn join_room(&mut self, room: &str, token: Option<&str>) {
+        let version = (!self.crdt.loro_doc().oplog_frontiers().is_empty())
+            .then(|| base64_encode(&self.crdt.version().encode()));
         let msg = ClientMessage::Join {
             room: room.to_string(),
+            version,
+            token: token.map(str::to_string),
+            key_check: self.room_key.as_ref().map(RoomKey::check),
         };
         if let Ok(json) = serde_json::to_string(&msg) {
             self.outgoing.push(json);
         }
     }
 
+    /// Protect the current room with new tokens (revoking the old ones), or open it
+    /// to everyone with `None`. Queues the message.
+    pub fn set_access(&mut self, edit_token: Option<String>, view_token: Option<String>) {
+        if self.current_room.is_some() {
+            let msg = ClientMessage::SetAccess {
+                edit_token,
+                view_token,
+            };
+            if let Ok(json) = serde_json::to_string(&msg) {
+                self.outgoing.push(json);
+            }
+        }
+    }
+
     /// Request to leave the current room. Queues the leave message.
     pub fn leave_room(&mut self) {
         if self.current_room.is_some() {
@@ -268,6 +384,8 @@ impl CollaborationManager {
                 self.outgoing.push(json);
             }
             self.current_room = None;
+            self.read_only = false;
+            self.room_key = None;
         }
     }
 
@@ -309,9 +427,21 @@ impl CollaborationManager {
     /// Queue awareness broadcast.
     fn queue_awareness(&mut self) {
         if self.current_room.is_some() {
+            let state = match &self.room_key {
+                Some(key) => {
+                    let Ok(json) = serde_json::to_vec(&self.awareness) else {
+                        return;
+                    };
+                    AwarenessState {
+                        sealed: Some(base64_encode(&key.seal(&json))),
+                        ..Default::default()
+                    }
+                }
+                None => self.awareness.clone(),
+            };
             let msg = ClientMessage::Awareness {
                 peer_id: self.peer_id,
-                state: self.awareness.clone(),
+                state,
             };
             if let Ok(json) = serde_json::to_string(&msg) {
                 self.outgoing.push(json);
@@ -321,10 +451,14 @@ impl CollaborationManager {
 
     // --- Sync Broadcast ---
 
-    /// Queue a sync broadcast with current CRDT state.
+    /// Queue a sync broadcast with current CRDT state (unless read-only), sealed
+    /// in end-to-end encrypted rooms.
     pub fn broadcast_sync(&mut self) {
-        if self.current_room.is_some() && self.enabled {
-            let snapshot = self.crdt.export_snapshot();
+        if self.current_room.is_some() && self.enabled && !self.read_only {
+            let mut snapshot = self.crdt.export_snapshot();
+            if let Some(key) = &self.room_key {
+                snapshot = key.seal(&snapshot);
+            }
             let data = base64_encode(&snapshot);
             let msg = ClientMessage::Sync { data };
             if let Ok(json) = serde_json::to_string(&msg) {
@@ -335,6 +469,46 @@ impl CollaborationManager {
 
     // --- Incoming Message Handling ---
 
+    /// The CRDT data in sync data from the room, opened with the room key in end-to-end
+    /// encrypted rooms. Fails for data sealed with another key or damaged on the way.
+    fn open_sync(&self, data: &[u8]) -> E2eResult<Vec<u8>> {
+        match &self.room_key {
+            Some(key) => key.open(data),
+            None => Ok(data.to_vec()),
+        }
+    }
+
+    /// A peer's awareness state, opened with the room key in end-to-end encrypted
+    /// rooms.
+    pub fn open_awareness(&self, state: AwarenessState) -> E2eResult<AwarenessState> {
+        let Some(key) = &self.room_key else {
+            return Ok(state);
+        };
+        let sealed = state.sealed.ok_or(E2eError::Unencrypted)?;
+        let bytes = base64_decode(&sealed).ok_or(E2eError::Decrypt)?;
+        let json = key.open(&bytes)?;
+        let opened: AwarenessState =
+            serde_json::from_slice(&json).map_err(|_| E2eError::Decrypt)?;
+        Ok(AwarenessState {
+            sealed: None,
+            ..opened
+        })
+    }
+
+    /// Open sync data from the room, then import it into the CRDT. Data that can't be
+    /// opened with the room key never reaches the CRDT. Returns the opened data.
+    pub fn open_and_import(&mut self, data: &[u8]) -> Result<Vec<u8>, String> {
+        let bytes = self.open_sync(data).map_err(|e| e.to_string())?;
+        self.crdt.import(&bytes).map_err(|e| e.to_string())?;
+        Ok(bytes)
+    }
+
+    /// Decode sync data from a server message, then open and import it.
+    fn import_sync(&mut self, data: &str) -> Result<Vec<u8>, String> {
+        let bytes = base64_decode(data).ok_or("Invalid sync data: not base64")?;
+        self.open_and_import(&bytes)
+    }
+
     /// Handle an incoming server message.
     /// Returns a SyncEvent describing what happened.
     pub fn handle_message(&mut self, json: &str) -> Option<SyncEvent> {
@@ -345,48 +519,62 @@ impl CollaborationManager {
                 room,
                 peer_count,
                 initial_sync,
+                protected,
+                read_only,
             } => {
                 self.current_room = Some(room.clone());
+                self.read_only = read_only;
                 self.enable();
 
                 // Import initial state if provided
-                let initial_data = initial_sync.and_then(|s| {
-                    base64_decode(&s).and_then(|bytes| {
-                        if self.crdt.import(&bytes).is_ok() {
-                            Some(bytes)
-                        } else {
-                            None
-                        }
-                    })
-                });
+                let initial_data = initial_sync.and_then(|s| self.import_sync(&s).ok());
 
                 Some(SyncEvent::JoinedRoom {
                     room,
                     peer_count,
                     initial_sync: initial_data,
+                    protected,
+                    read_only,
                 })
             }
+            ServerMessage::Access {
+                protected,
+                read_only,
+            } => {
+                self.read_only = read_only;
+                Some(SyncEvent::AccessChanged {
+                    protected,
+                    read_only,
+                })
+            }
+            ServerMessage::Removed { room, message } => {
+                self.set_room(None);
+                Some(SyncEvent::Removed { room, message })
+            }
             ServerMessage::PeerJoined { peer_id } => Some(SyncEvent::PeerJoined { peer_id }),
             ServerMessage::PeerLeft { peer_id } => Some(SyncEvent::PeerLeft { peer_id }),
             ServerMessage::Sync { from, data } => {
-                // Decode and import CRDT data
-                if let Some(bytes) = base64_decode(&data) {
-                    if self.crdt.import(&bytes).is_ok() {
-                        return Some(SyncEvent::SyncReceived { from, data: bytes });
-                    }
-                }
-                None
+                // Decode, open and import CRDT data
+                Some(match self.import_sync(&data) {
+                    Ok(bytes) => SyncEvent::SyncReceived { from, data: bytes },
+                    Err(message) => SyncEvent::Error { message },
+                })
             }
             ServerMessage::Awareness {
                 from,
                 peer_id,
                 state,
-            } => Some(SyncEvent::AwarenessReceived {
-                from,
-                peer_id,
-                state,
+            } => Some(match self.open_awareness(state) {
+                Ok(state) => SyncEvent::AwarenessReceived {
+                    from,
+                    peer_id,
+                    state,
+                },
+                Err(e) => SyncEvent::Error {
+                    message: e.to_string(),
+                },
             }),
-            ServerMessage::Error { message } => Some(SyncEvent::Error { message }),
+            ServerMessage::Error { code, message } => Some(SyncEvent::Rejected { code, message }),
         }
     }
 }
@@ -497,4 +685,205 @@ mod tests {
         assert_eq!(doc.shapes.len(), 0);
         assert_eq!(manager.crdt().shape_count(), 0);
     }
+
+    #[test]
+    fn test_read_only_room_does_not_broadcast() {
+        let mut manager = CollaborationManager::new();
+        manager.join_room("room", Some("view-token"));
+        let join = manager.take_outgoing();
+        assert!(join[0].contains(r#""token":"view-token""#));
+
+        let joined =
+            r#"{"type":"joined","room":"room","peer_count":2,"protected":true,"read_only":true}"#;
+        manager.handle_message(joined);
+        assert!(manager.is_read_only());
+        manager.broadcast_sync();
+        assert!(!manager.has_outgoing());
+
+        // Revoked tokens remove us from the room
+        let removed = r#"{"type":"removed","room":"room","message":"revoked"}"#;
+        assert!(matches!(
+            manager.handle_message(removed),
+            Some(SyncEvent::Removed { .. })
+        ));
+        assert!(!manager.is_in_room());
+        assert!(!manager.is_read_only());
+    }
+
+    #[test]
+    fn test_encrypted_room_seals_payloads() {
+        let key = RoomKey::generate();
+        let mut sender = CollaborationManager::new();
+        sender.set_room_key(Some(key.clone()));
+        sender.join_room("room", None);
+        let join = sender.take_outgoing();
+        assert!(join[0].contains(&key.check()));
+
+        sender.set_room(Some("room".to_string()));
+        let mut doc = CanvasDocument::new();
+        sender.add_shape(
+            &mut doc,
+            Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)),
+        );
+        sender.set_cursor(10.0, 20.0);
+        sender.broadcast_sync();
+        let outgoing = sender.take_outgoing();
+        let relayed: Vec<String> = outgoing
+            .iter()
+            .map(|json| json.replacen('{', r#"{"from":"peer","#, 1))
+            .collect();
+        assert!(!relayed[0].contains(r#""cursor""#));
+
+        // Peers with the key read both
+        let mut peer = CollaborationManager::new();
+        peer.set_room_key(Some(key));
+        match peer.handle_message(&relayed[0]) {
+            Some(SyncEvent::AwarenessReceived { state, .. }) => {
+                assert_eq!(state.cursor, Some(CursorPosition { x: 10.0, y: 20.0 }));
+                assert!(state.sealed.is_none());
+            }
+            _ => panic!("Expected awareness"),
+        }
+        assert!(matches!(
+            peer.handle_message(&relayed[1]),
+            Some(SyncEvent::SyncReceived { .. })
+        ));
+        assert_eq!(peer.crdt().shape_count(), 1);
+
+        // A wrong key is an error, and leaves the CRDT alone
+        let mut stranger = CollaborationManager::new();
+        stranger.set_room_key(Some(RoomKey::generate()));
+        assert!(matches!(
+            stranger.handle_message(&relayed[1]),
+            Some(SyncEvent::Error { .. })
+        ));
+        assert_eq!(stranger.crdt().shape_count(), 0);
+    }
+
+    #[test]
+    fn test_damaged_sync_rejected() {
+        let key = RoomKey::generate();
+        let mut sender = CollaborationManager::new();
+        sender.set_room_key(Some(key.clone()));
+        sender.set_room(Some("room".to_string()));
+        let mut doc = CanvasDocument::new();
+        sender.add_shape(
+            &mut doc,
+            Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)),
+        );
+        let sealed = key.seal(&sender.crdt().export_snapshot());
+
+        let mut peer = CollaborationManager::new();
+        peer.set_room_key(Some(key));
+
+        // Sealed with another key
+        let wrong = RoomKey::generate().seal(&sender.crdt().export_snapshot());
+        assert!(peer.open_and_import(&wrong).is_err());
+
+        // Any changed byte fails authentication
+        for i in [0, sealed.len() / 2, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[i] ^= 1;
+            assert!(peer.open_and_import(&tampered).is_err());
+        }
+        let json = serde_json::json!({
+            "type": "sync",
+            "from": "peer",
+            "data": base64_encode(&sealed[..sealed.len() - 1]),
+        });
+        assert!(matches!(
+            peer.handle_message(&json.to_string()),
+            Some(SyncEvent::Error { .. })
+        ));
+
+        // Unsealed data in an encrypted room
+        assert!(
+            peer.open_and_import(&sender.crdt().export_snapshot())
+                .is_err()
+        );
+        assert_eq!(peer.crdt().shape_count(), 0);
+
+        let opened = peer.open_and_import(&sealed).unwrap();
+        assert_eq!(opened, sender.crdt().export_snapshot());
+        assert_eq!(peer.crdt().shape_count(), 1);
+    }
+
+    #[test]
+    fn test_record_history_per_document() {
+        let mut manager = CollaborationManager::new();
+
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)));
+        assert!(manager.record_history(&doc));
+        assert!(!manager.record_history(&doc));
+        assert!(!manager.history().is_empty());
+
+        // Another document starts a fresh history
+        let other = CanvasDocument::new();
+        assert!(manager.record_history(&other));
+        assert_eq!(manager.crdt().shape_count(), 0);
+    }
+
+    #[test]
+    fn test_restore_version() {
+        let mut manager = CollaborationManager::new();
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)));
+        manager.record_history(&doc);
+        let version = manager.crdt().loro_doc().oplog_frontiers();
+
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        assert!(manager.restore_version(&mut doc, &version));
+        assert_eq!(doc.shapes.len(), 1);
+        // The restore can be undone locally
+        assert!(doc.undo());
+        assert_eq!(doc.shapes.len(), 2);
+    }
+
+    #[test]
+    fn test_local_checkpoint_is_snapshot() {
+        let mut manager = CollaborationManager::new();
+        let mut doc = CanvasDocument::new();
+        doc.name = "Architecture".to_string();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)));
+        let id = manager.create_checkpoint(&mut doc, "before refactor");
+        assert!(matches!(
+            doc.checkpoint(&id).unwrap().state,
+            CheckpointState::Snapshot(_)
+        ));
+
+        doc.clear();
+        let past = manager.checkpoint_document(&doc, &id).unwrap();
+        assert_eq!(past.shapes.len(), 1);
+        assert_eq!(past.name, "Architecture (before refactor)");
+        assert!(manager.checkpoint_document(&doc, "missing").is_none());
+    }
+
+    #[test]
+    fn test_room_checkpoint_is_version() {
+        let mut manager = CollaborationManager::new();
+        manager.set_room(Some("room".to_string()));
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 100.0, 100.0)));
+        let id = manager.create_checkpoint(&mut doc, "v1");
+        assert!(doc.checkpoint(&id).unwrap().frontiers().is_some());
+        // The checkpoint is part of the shared document
+        assert_eq!(manager.crdt().checkpoints().len(), 1);
+
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        manager.sync_to_crdt(&doc);
+        let past = manager.checkpoint_document(&doc, &id).unwrap();
+        assert_eq!(past.shapes.len(), 1);
+        assert!(past.checkpoints.is_empty());
+
+        // A fresh client without the room's history can't rebuild it
+        assert!(
+            CollaborationManager::new()
+                .checkpoint_document(&doc, &id)
+                .is_none()
+        );
+
+        assert!(manager.remove_checkpoint(&mut doc, &id));
+        assert!(manager.crdt().checkpoints().is_empty());
+    }
 }
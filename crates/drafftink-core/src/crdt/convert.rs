@@ -1,5 +1,6 @@
 //! Conversion between Shape types and Loro values.
 
+use crate::assets::Asset;
 use crate::shapes::{
     Arrow, Ellipse, FillPattern, FontFamily, FontWeight, Freehand, Group, Image, ImageFormat, Line,
     Math, PathStyle, Rectangle, SerializableColor, Shape, ShapeStyle, ShapeTrait, Sloppiness,
@@ -21,6 +22,9 @@ const TYPE_IMAGE: &str = "image";
 
 // Group keys
 const KEY_CHILDREN: &str = "children";
+const KEY_PIVOT_X: &str = "pivot_x";
+const KEY_PIVOT_Y: &str = "pivot_y";
+const KEY_FRAME: &str = "frame";
 
 // Common keys
 const KEY_TYPE: &str = "type";
@@ -73,6 +77,8 @@ const KEY_CHAR_COLORS: &str = "char_colors";
 const KEY_SOURCE_WIDTH: &str = "source_width";
 const KEY_SOURCE_HEIGHT: &str = "source_height";
 const KEY_FORMAT: &str = "format";
+const KEY_ASSET: &str = "asset";
+// Inline image data, written before assets were stored apart
 const KEY_DATA_BASE64: &str = "data_base64";
 
 // Rotation key (shared by Rectangle, Ellipse, Text, Image)
@@ -114,6 +120,8 @@ fn get_id(map: &LoroMapValue) -> Option<Uuid> {
 }
 
 /// Convert a Shape to Loro map entries.
+///
+/// Images refer to their asset by id; the bytes belong in the document's assets map.
 pub fn shape_to_loro(shape: &Shape, map: &LoroMap) -> LoroResult<()> {
     match shape {
         Shape::Rectangle(rect) => {
@@ -219,6 +227,14 @@ pub fn shape_to_loro(shape: &Shape, map: &LoroMap) -> LoroResult<()> {
         Shape::Group(group) => {
             map.insert(KEY_TYPE, TYPE_GROUP)?;
             map.insert(KEY_ID, group.id().to_string())?;
+            map.insert(KEY_ROTATION, group.rotation)?;
+            if let Some(pivot) = group.pivot {
+                map.insert(KEY_PIVOT_X, pivot.x)?;
+                map.insert(KEY_PIVOT_Y, pivot.y)?;
+            }
+            if let Some(frame) = &group.frame {
+                map.insert(KEY_FRAME, frame.clone())?;
+            }
             let children_list = map.insert_container(KEY_CHILDREN, LoroList::new())?;
             for child in group.children() {
                 let child_map =
@@ -236,7 +252,7 @@ pub fn shape_to_loro(shape: &Shape, map: &LoroMap) -> LoroResult<()> {
             map.insert(KEY_SOURCE_WIDTH, image.source_width as i64)?;
             map.insert(KEY_SOURCE_HEIGHT, image.source_height as i64)?;
             map.insert(KEY_FORMAT, image_format_to_i64(image.format))?;
-            map.insert(KEY_DATA_BASE64, image.data_base64.clone())?;
+            map.insert(KEY_ASSET, image.asset.id())?;
             map.insert(KEY_ROTATION, image.rotation)?;
             style_to_loro(&image.style, map)?;
         }
@@ -279,8 +295,8 @@ fn style_to_loro(style: &ShapeStyle, map: &LoroMap) -> LoroResult<()> {
     Ok(())
 }
 
-/// Convert a Loro map to a Shape.
-pub fn shape_from_loro(map: &LoroMapValue) -> Option<Shape> {
+/// Convert a Loro map to a Shape, looking up image bytes in the document's `assets`.
+pub fn shape_from_loro(map: &LoroMapValue, assets: &LoroMapValue) -> Option<Shape> {
     let shape_type = get_string(map, KEY_TYPE)?;
 
     match shape_type.as_str() {
@@ -290,8 +306,8 @@ pub fn shape_from_loro(map: &LoroMapValue) -> Option<Shape> {
         TYPE_ARROW => arrow_from_loro(map),
         TYPE_FREEHAND => freehand_from_loro(map),
         TYPE_TEXT => text_from_loro(map),
-        TYPE_GROUP => group_from_loro(map),
-        TYPE_IMAGE => image_from_loro(map),
+        TYPE_GROUP => group_from_loro(map, assets),
+        TYPE_IMAGE => image_from_loro(map, assets),
         "math" => math_from_loro(map),
         _ => None,
     }
@@ -382,7 +398,7 @@ fn text_from_loro(map: &LoroMapValue) -> Option<Shape> {
     )))
 }
 
-fn group_from_loro(map: &LoroMapValue) -> Option<Shape> {
+fn group_from_loro(map: &LoroMapValue, assets: &LoroMapValue) -> Option<Shape> {
     let children_list = match map.get(KEY_CHILDREN)? {
         LoroValue::List(list) => list,
         _ => return None,
@@ -392,17 +408,34 @@ fn group_from_loro(map: &LoroMapValue) -> Option<Shape> {
         .iter()
         .filter_map(|v| {
             if let LoroValue::Map(child_map) = v {
-                shape_from_loro(&child_map.clone())
+                shape_from_loro(&child_map.clone(), assets)
             } else {
                 None
             }
         })
         .collect();
 
-    Some(Shape::Group(Group::reconstruct(get_id(map)?, children)))
+    let pivot = get_double(map, KEY_PIVOT_X)
+        .zip(get_double(map, KEY_PIVOT_Y))
+        .map(|(x, y)| Point::new(x, y));
+    Some(Shape::Group(Group::reconstruct(
+        get_id(map)?,
+        children,
+        get_double(map, KEY_ROTATION).unwrap_or(0.0),
+        pivot,
+        get_string(map, KEY_FRAME),
+    )))
 }
 
-fn image_from_loro(map: &LoroMapValue) -> Option<Shape> {
+fn image_from_loro(map: &LoroMapValue, assets: &LoroMapValue) -> Option<Shape> {
+    let asset = match get_string(map, KEY_ASSET) {
+        Some(id) => match assets.get(&id)? {
+            // Content from peers must match the id it is shared under
+            LoroValue::Binary(bytes) => Some(Asset::new(bytes.to_vec())).filter(|a| a.id() == id),
+            _ => None,
+        },
+        None => get_string(map, KEY_DATA_BASE64).and_then(|data| Asset::from_base64(&data)),
+    }?;
     Some(Shape::Image(Image::reconstruct(
         get_id(map)?,
         Point::new(get_double(map, KEY_X)?, get_double(map, KEY_Y)?),
@@ -413,7 +446,7 @@ fn image_from_loro(map: &LoroMapValue) -> Option<Shape> {
         get_i64(map, KEY_FORMAT)
             .map(i64_to_image_format)
             .unwrap_or(ImageFormat::Png),
-        get_string(map, KEY_DATA_BASE64)?,
+        asset,
         get_double(map, KEY_ROTATION).unwrap_or(0.0),
         style_from_loro(map)?,
     )))
@@ -637,6 +670,7 @@ fn image_format_to_i64(f: ImageFormat) -> i64 {
         ImageFormat::Png => 0,
         ImageFormat::Jpeg => 1,
         ImageFormat::WebP => 2,
+        ImageFormat::Svg => 3,
     }
 }
 
@@ -644,6 +678,7 @@ fn i64_to_image_format(v: i64) -> ImageFormat {
     match v {
         0 => ImageFormat::Png,
         1 => ImageFormat::Jpeg,
+        3 => ImageFormat::Svg,
         _ => ImageFormat::WebP,
     }
 }
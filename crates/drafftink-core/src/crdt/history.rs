@@ -0,0 +1,98 @@
+//! Version history of a CRDT document.
+//!
+//! Loro keeps every change, so any past version can be viewed, exported or
+//! restored. Versions are identified by their `Frontiers`.
+
+use super::schema::CrdtDocument;
+use crate::canvas::CanvasDocument;
+use loro::{ExportMode, Frontiers, ID};
+use std::borrow::Cow;
+
+/// One change in the history of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Loro peer that made the change.
+    pub peer: u64,
+    /// Unix time of the change in seconds (0 if the peer didn't record it).
+    pub timestamp: i64,
+    /// Lamport time of the change; orders changes causally across peers.
+    pub lamport: u32,
+    /// Summary of the change (e.g. "2 added, 1 modified"), if any.
+    pub message: Option<String>,
+    /// Number of operations in the change.
+    pub ops: usize,
+    /// Version of the document right after the change.
+    pub version: Frontiers,
+}
+
+impl CrdtDocument {
+    /// All changes of the document, oldest first.
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let doc = self.loro_doc();
+        let mut entries = Vec::new();
+        for (&peer, &end) in doc.oplog_vv().iter() {
+            let mut counter = 0;
+            while counter < end {
+                let Some(change) = doc.get_change(ID::new(peer, counter)) else {
+                    break;
+                };
+                let last = change.id.counter + change.len as i32 - 1;
+                entries.push(HistoryEntry {
+                    peer,
+                    timestamp: change.timestamp,
+                    lamport: change.lamport,
+                    message: change
+                        .message
+                        .as_deref()
+                        .filter(|m| !m.is_empty())
+                        .map(str::to_string),
+                    ops: change.len,
+                    version: Frontiers::from(ID::new(peer, last)),
+                });
+                counter = last + 1;
+            }
+        }
+        entries.sort_by_key(|entry| (entry.lamport, entry.peer));
+        entries
+    }
+
+    /// The document as it was at `version`, as a new CRDT document.
+    /// Returns `None` if the version isn't part of this document's history.
+    pub fn at(&self, version: &Frontiers) -> Option<CrdtDocument> {
+        let snapshot = self.export_snapshot_at(version)?;
+        CrdtDocument::from_snapshot(&snapshot).ok()
+    }
+
+    /// Export a snapshot of the document as it was at `version`.
+    pub fn export_snapshot_at(&self, version: &Frontiers) -> Option<Vec<u8>> {
+        self.loro_doc()
+            .export(ExportMode::SnapshotAt {
+                version: Cow::Borrowed(version),
+            })
+            .ok()
+    }
+
+    /// The local document as it was at `version`.
+    pub fn document_at(&self, version: &Frontiers) -> Option<CanvasDocument> {
+        let past = self.at(version)?;
+        let mut document = CanvasDocument::new();
+        past.read_into(&mut document);
+        Some(document)
+    }
+
+    /// Restore the document to `version` by recording a new change on top of the
+    /// current one, so the history in between is kept (and syncs to peers).
+    /// Returns whether anything changed.
+    pub fn restore(&mut self, version: &Frontiers) -> bool {
+        let Some(past) = self.at(version) else {
+            return false;
+        };
+        let mut document = CanvasDocument::new();
+        // Keep the current name if the old version had none
+        document.name = self.name();
+        past.read_into(&mut document);
+        // Checkpoints aren't part of the board's state, so restoring keeps them all
+        document.checkpoints = self.checkpoints();
+        self.apply_document(&document).unwrap_or(false)
+    }
+}
@@ -9,7 +9,9 @@
 //! LoroDoc
 //! ├── "name": LoroText (document name)
 //! ├── "shapes": LoroMap<ShapeId, LoroMap> (shape data)
-//! └── "z_order": LoroList<String> (shape IDs in z-order)
+//! ├── "z_order": LoroList<String> (shape IDs in z-order)
+//! ├── "assets": LoroMap<AssetId, Binary> (image bytes, stored once per content)
+//! └── "checkpoints": LoroMap<CheckpointId, String> (named checkpoints as JSON)
 //! ```
 //!
 //! Each shape in "shapes" is a LoroMap with:
@@ -17,15 +19,19 @@
 //! - "id": String (UUID)
 //! - Type-specific fields (position, dimensions, points, etc.)
 //! - Style fields (stroke_color, stroke_width, fill_color, sloppiness)
+//!
+//! Images store the id of their asset under "asset" instead of their bytes.
 
 mod convert;
+mod history;
 mod schema;
 
 pub use convert::{shape_from_loro, shape_to_loro};
-pub use schema::{CrdtDocument, NAME_KEY, SHAPES_KEY, Z_ORDER_KEY};
+pub use history::HistoryEntry;
+pub use schema::{ASSETS_KEY, CHECKPOINTS_KEY, CrdtDocument, NAME_KEY, SHAPES_KEY, Z_ORDER_KEY};
 
 // Re-export Loro types that may be useful for collaboration
-pub use loro::{ExportMode, VersionVector};
+pub use loro::{ExportMode, Frontiers, VersionVector};
 
 #[cfg(test)]
 mod tests {
@@ -196,6 +202,36 @@ mod tests {
         assert_eq!(doc.shape_count(), 1);
     }
 
+    #[test]
+    fn test_images_share_assets() {
+        use crate::shapes::{Image, ImageFormat};
+
+        let mut doc = CrdtDocument::new();
+        let image = || Shape::Image(Image::new(Point::ZERO, b"pixels", 1, 1, ImageFormat::Png));
+        doc.add_shape(&image()).expect("Failed to add shape");
+        assert_eq!(doc.asset_count(), 1);
+
+        // Undo leaves the asset alone, so it stays available to redo and other shapes
+        assert!(doc.undo());
+        assert_eq!(doc.shape_count(), 0);
+        assert_eq!(doc.asset_count(), 1);
+        assert!(doc.redo());
+
+        doc.add_shape(&image()).expect("Failed to add shape");
+        assert_eq!(doc.shape_count(), 2);
+        assert_eq!(doc.asset_count(), 1);
+
+        let bytes = doc.export_snapshot();
+        let doc2 = CrdtDocument::from_snapshot(&bytes).expect("Failed to import");
+        for shape in doc2.shapes_ordered() {
+            let Shape::Image(recovered) = shape else {
+                panic!("Expected Image, got different shape type");
+            };
+            assert_eq!(recovered.data(), b"pixels");
+        }
+        assert_eq!(doc2.shapes_ordered().len(), 2);
+    }
+
     #[test]
     fn test_crdt_undo_count() {
         let mut doc = CrdtDocument::new();
@@ -211,4 +247,185 @@ mod tests {
         assert!(doc.undo_count() > 0);
         assert_eq!(doc.redo_count(), 0);
     }
+
+    #[test]
+    fn test_apply_document_writes_differences() {
+        use crate::canvas::CanvasDocument;
+
+        let mut crdt = CrdtDocument::new();
+        let mut local = CanvasDocument::new();
+        local.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        local.add_shape(Shape::Rectangle(Rectangle::new(
+            Point::new(100.0, 0.0),
+            50.0,
+            50.0,
+        )));
+
+        assert!(crdt.apply_document(&local).unwrap());
+        assert_eq!(crdt.shape_count(), 2);
+        assert_eq!(crdt.name(), local.name);
+        // Nothing left to write
+        assert!(!crdt.apply_document(&local).unwrap());
+
+        let first = local.z_order[0];
+        local.remove_shape(first);
+        assert!(crdt.apply_document(&local).unwrap());
+        assert_eq!(crdt.shape_count(), 1);
+        assert!(!crdt.z_order().contains(&first.to_string()));
+
+        let mut roundtrip = CanvasDocument::new();
+        crdt.read_into(&mut roundtrip);
+        assert_eq!(roundtrip.z_order, local.z_order);
+    }
+
+    #[test]
+    fn test_group_rotation_roundtrip() {
+        use crate::canvas::CanvasDocument;
+        use crate::shapes::Group;
+
+        let mut crdt = CrdtDocument::new();
+        let mut local = CanvasDocument::new();
+        let mut group = Shape::Group(Group::new(vec![
+            Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)),
+            Shape::Rectangle(Rectangle::new(Point::new(100.0, 0.0), 50.0, 50.0)),
+        ]));
+        group.set_rotation(1.0);
+        let id = group.id();
+        local.add_shape(group);
+        assert!(crdt.apply_document(&local).unwrap());
+
+        let mut roundtrip = CanvasDocument::new();
+        crdt.read_into(&mut roundtrip);
+        let group = roundtrip.get_shape(id).and_then(Shape::as_group).unwrap();
+        assert!((group.rotation - 1.0).abs() < 0.001);
+        assert_eq!(group.pivot, Some(Point::new(75.0, 25.0)));
+
+        // Changing only the rotation is an edit to sync
+        local.get_shape_mut(id).unwrap().set_rotation(0.5);
+        assert!(crdt.apply_document(&local).unwrap());
+        assert!(!crdt.apply_document(&local).unwrap());
+    }
+
+    #[test]
+    fn test_frame_roundtrip() {
+        use crate::shapes::Group;
+
+        let mut doc = CrdtDocument::new();
+        let frame = Shape::Group(
+            Group::new(vec![Shape::Rectangle(Rectangle::new(
+                Point::ZERO,
+                50.0,
+                50.0,
+            ))])
+            .with_frame("Slide 1"),
+        );
+        let plain = Shape::Group(Group::new(vec![Shape::Rectangle(Rectangle::new(
+            Point::ZERO,
+            50.0,
+            50.0,
+        ))]));
+        doc.add_shape(&frame).unwrap();
+        doc.add_shape(&plain).unwrap();
+
+        let recovered = doc.get_shape(&frame.id().to_string()).unwrap();
+        assert_eq!(
+            recovered.as_group().unwrap().frame.as_deref(),
+            Some("Slide 1")
+        );
+        let recovered = doc.get_shape(&plain.id().to_string()).unwrap();
+        assert_eq!(recovered.as_group().unwrap().frame, None);
+    }
+
+    #[test]
+    fn test_history_and_past_versions() {
+        use crate::canvas::CanvasDocument;
+
+        let mut crdt = CrdtDocument::new();
+        let mut local = CanvasDocument::new();
+        local.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        crdt.apply_document(&local).unwrap();
+        let one_shape = crdt.loro_doc().oplog_frontiers();
+
+        local.add_shape(Shape::Rectangle(Rectangle::new(
+            Point::new(100.0, 0.0),
+            50.0,
+            50.0,
+        )));
+        crdt.apply_document(&local).unwrap();
+
+        let history = crdt.history();
+        let latest = history.last().expect("history is empty");
+        assert_eq!(latest.peer, crdt.loro_doc().peer_id());
+        assert_eq!(latest.version, crdt.loro_doc().oplog_frontiers());
+        assert!(latest.timestamp > 0);
+
+        let past = crdt.document_at(&one_shape).expect("version not found");
+        assert_eq!(past.shapes.len(), 1);
+        assert_eq!(past.z_order, local.z_order[..1]);
+        // Looking at the past doesn't change the present
+        assert_eq!(crdt.shape_count(), 2);
+    }
+
+    #[test]
+    fn test_restore_keeps_history() {
+        use crate::canvas::CanvasDocument;
+
+        let mut crdt = CrdtDocument::new();
+        let mut local = CanvasDocument::new();
+        local.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        crdt.apply_document(&local).unwrap();
+        let one_shape = crdt.loro_doc().oplog_frontiers();
+
+        local.add_shape(Shape::Rectangle(Rectangle::new(
+            Point::new(100.0, 0.0),
+            50.0,
+            50.0,
+        )));
+        crdt.apply_document(&local).unwrap();
+        let two_shapes = crdt.loro_doc().oplog_frontiers();
+
+        assert!(crdt.restore(&one_shape));
+        assert_eq!(crdt.shape_count(), 1);
+        assert_eq!(crdt.name(), local.name);
+        // Restoring is a new change; the newer version is still there
+        assert_eq!(crdt.document_at(&two_shapes).unwrap().shapes.len(), 2);
+        assert!(!crdt.restore(&one_shape));
+    }
+
+    #[test]
+    fn test_checkpoints_sync() {
+        use crate::canvas::CanvasDocument;
+        use crate::checkpoint::Checkpoint;
+
+        let mut crdt = CrdtDocument::new();
+        let mut local = CanvasDocument::new();
+        local.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 50.0, 50.0)));
+        crdt.apply_document(&local).unwrap();
+        let version = crdt.loro_doc().oplog_frontiers();
+        let checkpoint = local.add_checkpoint(Checkpoint::version("before refactor", &version));
+        local.add_checkpoint(Checkpoint::snapshot("copy", &local));
+        assert!(crdt.apply_document(&local).unwrap());
+        assert!(!crdt.apply_document(&local).unwrap());
+
+        // Peers see the checkpoints
+        let peer = CrdtDocument::from_snapshot(&crdt.export_snapshot()).unwrap();
+        let mut remote = CanvasDocument::new();
+        peer.read_into(&mut remote);
+        let names: Vec<&str> = remote.checkpoints.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names.len(), 2);
+        assert!(names.contains(&"before refactor") && names.contains(&"copy"));
+        let frontiers = remote.checkpoint(&checkpoint).unwrap().frontiers().unwrap();
+        assert_eq!(peer.document_at(&frontiers).unwrap().shapes.len(), 1);
+
+        // Restoring an older version keeps the checkpoints made since
+        local.clear();
+        crdt.apply_document(&local).unwrap();
+        assert!(crdt.restore(&version));
+        assert_eq!(crdt.shape_count(), 1);
+        assert_eq!(crdt.checkpoints().len(), 2);
+
+        local.remove_checkpoint(&checkpoint);
+        crdt.apply_document(&local).unwrap();
+        assert_eq!(crdt.checkpoints().len(), 1);
+    }
 }
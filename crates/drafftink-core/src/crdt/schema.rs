@@ -1,10 +1,15 @@
 //! Loro document schema and operations.
 
 use super::convert::{shape_from_loro, shape_to_loro};
+use crate::assets::AssetStore;
+use crate::canvas::CanvasDocument;
+use crate::checkpoint::Checkpoint;
 use crate::shapes::Shape;
 use loro::{
-    ExportMode, LoroDoc, LoroList, LoroMap, LoroResult, LoroValue, UndoManager, ValueOrContainer,
+    CommitOptions, ExportMode, LoroDoc, LoroList, LoroMap, LoroResult, LoroValue, UndoManager,
+    ValueOrContainer,
 };
+use std::collections::{HashMap, HashSet};
 
 /// Key for the shapes map in the document.
 pub const SHAPES_KEY: &str = "shapes";
@@ -12,6 +17,17 @@ pub const SHAPES_KEY: &str = "shapes";
 pub const Z_ORDER_KEY: &str = "z_order";
 /// Key for the document name.
 pub const NAME_KEY: &str = "name";
+/// Key for the assets map (asset id to bytes).
+pub const ASSETS_KEY: &str = "assets";
+/// Key for the checkpoints map (checkpoint id to checkpoint JSON).
+pub const CHECKPOINTS_KEY: &str = "checkpoints";
+
+/// Commit origin of asset writes, which undo leaves alone: assets are immutable and
+/// may be shared by shapes added later, so undoing a shape must not remove its bytes.
+const ASSET_ORIGIN: &str = "drafftink-assets";
+
+/// Consecutive local changes within this many seconds are merged into one history entry.
+const HISTORY_MERGE_INTERVAL_SECS: i64 = 10;
 
 /// A CRDT-backed document for collaborative editing.
 ///
@@ -28,20 +44,26 @@ impl CrdtDocument {
     /// Create a new empty CRDT document.
     pub fn new() -> Self {
         let doc = LoroDoc::new();
+        doc.set_record_timestamp(true);
+        doc.set_change_merge_interval(HISTORY_MERGE_INTERVAL_SECS);
         let mut undo_manager = UndoManager::new(&doc);
         // Set reasonable defaults for undo
         undo_manager.set_max_undo_steps(100);
         undo_manager.set_merge_interval(300); // Merge edits within 300ms
+        undo_manager.add_exclude_origin_prefix(ASSET_ORIGIN);
         Self { doc, undo_manager }
     }
 
     /// Create a CRDT document from a snapshot.
     pub fn from_snapshot(bytes: &[u8]) -> LoroResult<Self> {
         let doc = LoroDoc::new();
+        doc.set_record_timestamp(true);
+        doc.set_change_merge_interval(HISTORY_MERGE_INTERVAL_SECS);
         doc.import(bytes)?;
         let mut undo_manager = UndoManager::new(&doc);
         undo_manager.set_max_undo_steps(100);
         undo_manager.set_merge_interval(300);
+        undo_manager.add_exclude_origin_prefix(ASSET_ORIGIN);
         Ok(Self { doc, undo_manager })
     }
 
@@ -65,6 +87,63 @@ impl CrdtDocument {
         self.doc.get_list(Z_ORDER_KEY)
     }
 
+    /// Get the assets map.
+    fn assets_map(&self) -> LoroMap {
+        self.doc.get_map(ASSETS_KEY)
+    }
+
+    /// Get the checkpoints map.
+    fn checkpoints_map(&self) -> LoroMap {
+        self.doc.get_map(CHECKPOINTS_KEY)
+    }
+
+    /// Stored checkpoints as JSON, keyed by checkpoint id.
+    fn checkpoint_values(&self) -> HashMap<String, String> {
+        match self.checkpoints_map().get_value() {
+            LoroValue::Map(map) => map
+                .iter()
+                .filter_map(|(id, value)| match value {
+                    LoroValue::String(json) => Some((id.clone(), json.to_string())),
+                    _ => None,
+                })
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    /// Get the checkpoints, oldest first.
+    pub fn checkpoints(&self) -> Vec<Checkpoint> {
+        let mut checkpoints: Vec<Checkpoint> = self
+            .checkpoint_values()
+            .values()
+            .filter_map(|json| serde_json::from_str(json).ok())
+            .collect();
+        checkpoints.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+        checkpoints
+    }
+
+    /// Store the assets of a shape that the document doesn't have yet, each once.
+    fn store_assets(&mut self, shape: &Shape) -> LoroResult<()> {
+        let assets = self.assets_map();
+        let mut added = false;
+        for asset in AssetStore::from_shapes([shape]).iter() {
+            if assets.get(asset.id()).is_none() {
+                assets.insert(asset.id(), asset.bytes().to_vec())?;
+                added = true;
+            }
+        }
+        if added {
+            self.doc
+                .commit_with(CommitOptions::new().origin(ASSET_ORIGIN));
+        }
+        Ok(())
+    }
+
+    /// Get the number of distinct assets stored.
+    pub fn asset_count(&self) -> usize {
+        self.assets_map().len()
+    }
+
     /// Get the number of shapes in the document.
     pub fn shape_count(&self) -> usize {
         self.shapes_map().len()
@@ -87,6 +166,7 @@ impl CrdtDocument {
         let id = shape.id().to_string();
         let shapes = self.shapes_map();
         let z_order = self.z_order_list();
+        self.store_assets(shape)?;
 
         // Create a new map for the shape
         let shape_map = shapes.insert_container(&id, LoroMap::new())?;
@@ -131,9 +211,10 @@ impl CrdtDocument {
 
         // Get the deep value and extract the shape map
         let shapes_value = shapes.get_deep_value();
-        if let LoroValue::Map(map) = shapes_value {
+        let assets_value = self.assets_map().get_value();
+        if let (LoroValue::Map(map), LoroValue::Map(assets)) = (shapes_value, assets_value) {
             if let Some(LoroValue::Map(shape_map)) = map.get(id) {
-                return shape_from_loro(shape_map);
+                return shape_from_loro(shape_map, &assets);
             }
         }
 
@@ -144,6 +225,7 @@ impl CrdtDocument {
     pub fn update_shape(&mut self, shape: &Shape) -> LoroResult<()> {
         let id = shape.id().to_string();
         let shapes = self.shapes_map();
+        self.store_assets(shape)?;
 
         // Remove the old shape data and add new
         shapes.delete(&id)?;
@@ -154,6 +236,142 @@ impl CrdtDocument {
         Ok(())
     }
 
+    /// Make the CRDT match a local document, writing only what differs.
+    ///
+    /// All differences are committed as one change, described by a short summary
+    /// (e.g. "2 added, 1 modified") that shows up in the history.
+    /// Returns whether anything changed.
+    pub fn apply_document(&mut self, document: &CanvasDocument) -> LoroResult<bool> {
+        let shapes = self.shapes_map();
+        let stored = match shapes.get_deep_value() {
+            LoroValue::Map(map) => map,
+            _ => Default::default(),
+        };
+
+        let live: Vec<(String, &Shape)> = document
+            .z_order
+            .iter()
+            .filter_map(|id| Some((id.to_string(), document.shapes.get(id)?)))
+            .collect();
+        let live_keys: HashSet<&str> = live.iter().map(|(key, _)| key.as_str()).collect();
+        let z_order: Vec<String> = live.iter().map(|(key, _)| key.clone()).collect();
+
+        // Compare in Loro form, so fields that don't round-trip don't count as changes
+        let scratch = LoroDoc::new();
+        let scratch_shapes = scratch.get_map(SHAPES_KEY);
+        let mut changed = Vec::new();
+        for (key, shape) in &live {
+            let map = scratch_shapes.insert_container(key, LoroMap::new())?;
+            shape_to_loro(shape, &map)?;
+            if stored.get(key) != Some(&map.get_deep_value()) {
+                changed.push((key, *shape));
+            }
+        }
+        let removed: Vec<&String> = stored
+            .keys()
+            .filter(|key| !live_keys.contains(key.as_str()))
+            .collect();
+        let reordered = self.z_order() != z_order;
+        let renamed = self.name() != document.name;
+
+        // Checkpoints are stored as JSON, written whole when they change
+        let stored_checkpoints = self.checkpoint_values();
+        let mut live_checkpoints = HashMap::new();
+        for checkpoint in &document.checkpoints {
+            if let Ok(json) = serde_json::to_string(checkpoint) {
+                live_checkpoints.insert(checkpoint.id.as_str(), json);
+            }
+        }
+        let changed_checkpoints: Vec<(&str, String)> = live_checkpoints
+            .into_iter()
+            .filter(|(id, json)| stored_checkpoints.get(*id) != Some(json))
+            .collect();
+        let removed_checkpoints: Vec<&String> = stored_checkpoints
+            .keys()
+            .filter(|id| document.checkpoint(id).is_none())
+            .collect();
+        let checkpointed = !changed_checkpoints.is_empty() || !removed_checkpoints.is_empty();
+
+        if changed.is_empty() && removed.is_empty() && !reordered && !renamed && !checkpointed {
+            return Ok(false);
+        }
+
+        // Assets are committed separately (see ASSET_ORIGIN), so store them first
+        for (_, shape) in &changed {
+            self.store_assets(shape)?;
+        }
+
+        let mut added = 0;
+        for (key, shape) in &changed {
+            if stored.contains_key(key.as_str()) {
+                shapes.delete(key)?;
+            } else {
+                added += 1;
+            }
+            let map = shapes.insert_container(key, LoroMap::new())?;
+            shape_to_loro(shape, &map)?;
+        }
+        for key in &removed {
+            shapes.delete(key)?;
+        }
+        if reordered {
+            let list = self.z_order_list();
+            if !list.is_empty() {
+                list.delete(0, list.len())?;
+            }
+            for id in z_order {
+                list.push(LoroValue::String(id.into()))?;
+            }
+        }
+        if renamed {
+            let text = self.doc.get_text(NAME_KEY);
+            let len = text.len_unicode();
+            if len > 0 {
+                text.delete(0, len)?;
+            }
+            text.insert(0, &document.name)?;
+        }
+        if checkpointed {
+            let map = self.checkpoints_map();
+            for (id, json) in changed_checkpoints {
+                map.insert(id, json)?;
+            }
+            for id in removed_checkpoints {
+                map.delete(id)?;
+            }
+        }
+
+        let summary = change_summary(
+            added,
+            changed.len() - added,
+            removed.len(),
+            reordered,
+            renamed,
+            checkpointed,
+        );
+        self.doc.set_next_commit_message(&summary);
+        self.doc.commit();
+        Ok(true)
+    }
+
+    /// Replace the shapes, name and checkpoints of a local document with the CRDT's.
+    pub fn read_into(&self, document: &mut CanvasDocument) {
+        document.shapes.clear();
+        document.z_order.clear();
+        document.checkpoints = self.checkpoints();
+
+        let name = self.name();
+        if !name.is_empty() {
+            document.name = name;
+        }
+
+        for shape in self.shapes_ordered() {
+            let id = shape.id();
+            document.shapes.insert(id, shape);
+            document.z_order.push(id);
+        }
+    }
+
     /// Get all shapes in z-order.
     pub fn shapes_ordered(&self) -> Vec<Shape> {
         let z_order = self.z_order();
@@ -417,6 +635,37 @@ impl CrdtDocument {
     }
 }
 
+/// Describe a change for the history, e.g. "2 added, 1 removed".
+fn change_summary(
+    added: usize,
+    modified: usize,
+    removed: usize,
+    reordered: bool,
+    renamed: bool,
+    checkpointed: bool,
+) -> String {
+    let mut parts = Vec::new();
+    for (count, verb) in [
+        (added, "added"),
+        (modified, "modified"),
+        (removed, "removed"),
+    ] {
+        if count > 0 {
+            parts.push(format!("{} {}", count, verb));
+        }
+    }
+    if reordered && added == 0 && removed == 0 {
+        parts.push("reordered".to_string());
+    }
+    if renamed {
+        parts.push("renamed".to_string());
+    }
+    if checkpointed {
+        parts.push("checkpoints".to_string());
+    }
+    parts.join(", ")
+}
+
 impl Default for CrdtDocument {
     fn default() -> Self {
         Self::new()
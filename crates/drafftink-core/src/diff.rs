@@ -0,0 +1,413 @@
+//! Differences between two versions of a document.
+//!
+//! Shapes are matched by ID: a shape only in the newer document was added, one only
+//! in the older was removed, and one in both whose serialized form differs was
+//! modified. Modifications are sorted by which properties changed, so a compare view
+//! can tell a moved shape from a restyled or re-texted one.
+
+use crate::assets;
+use crate::canvas::CanvasDocument;
+use crate::shapes::{Shape, ShapeId};
+use serde_json::{Map, Value};
+use std::fmt;
+
+/// Shapes that differ between two documents.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DocumentDiff {
+    /// Shapes only in the newer document, in its z-order.
+    pub added: Vec<ShapeId>,
+    /// Shapes only in the older document, in its z-order.
+    pub removed: Vec<ShapeId>,
+    /// Shapes in both that changed, in the newer document's z-order.
+    pub modified: Vec<(ShapeId, Modification)>,
+}
+
+/// Which properties of a shape changed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modification {
+    /// Position, size, rotation or points.
+    pub moved: bool,
+    /// Colors, stroke, fill, font, image content or other appearance.
+    pub restyled: bool,
+    /// Text content or LaTeX source.
+    pub retexted: bool,
+}
+
+/// How a shape differs between two documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Modified(Modification),
+}
+
+/// A changed shape.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShapeChange {
+    pub id: ShapeId,
+    pub kind: ChangeKind,
+}
+
+impl DocumentDiff {
+    /// Compare `old` against `new`.
+    pub fn between(old: &CanvasDocument, new: &CanvasDocument) -> Self {
+        let mut diff = Self::default();
+        for (id, shape) in ordered(new) {
+            match old.shapes.get(&id) {
+                None => diff.added.push(id),
+                Some(before) => {
+                    let modification = classify(before, shape);
+                    if !modification.is_empty() {
+                        diff.modified.push((id, modification));
+                    }
+                }
+            }
+        }
+        diff.removed = ordered(old)
+            .filter(|(id, _)| !new.shapes.contains_key(id))
+            .map(|(id, _)| id)
+            .collect();
+        diff
+    }
+
+    /// Whether the documents have the same shapes.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.modified.is_empty()
+    }
+
+    /// Total number of changed shapes.
+    pub fn len(&self) -> usize {
+        self.added.len() + self.removed.len() + self.modified.len()
+    }
+
+    /// Every change: additions, then modifications, then removals.
+    pub fn changes(&self) -> impl Iterator<Item = ShapeChange> + '_ {
+        let added = self.added.iter().map(|&id| ShapeChange {
+            id,
+            kind: ChangeKind::Added,
+        });
+        let modified = self.modified.iter().map(|&(id, modification)| ShapeChange {
+            id,
+            kind: ChangeKind::Modified(modification),
+        });
+        let removed = self.removed.iter().map(|&id| ShapeChange {
+            id,
+            kind: ChangeKind::Removed,
+        });
+        added.chain(modified).chain(removed)
+    }
+
+    /// How a shape changed, if it did.
+    pub fn kind_of(&self, id: ShapeId) -> Option<ChangeKind> {
+        self.changes()
+            .find(|change| change.id == id)
+            .map(|c| c.kind)
+    }
+}
+
+impl Modification {
+    /// Whether nothing changed.
+    pub fn is_empty(&self) -> bool {
+        !(self.moved || self.restyled || self.retexted)
+    }
+
+    fn union(self, other: Self) -> Self {
+        Self {
+            moved: self.moved || other.moved,
+            restyled: self.restyled || other.restyled,
+            retexted: self.retexted || other.retexted,
+        }
+    }
+}
+
+/// What changed, e.g. "moved, restyled".
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts = [
+            (self.moved, "moved"),
+            (self.restyled, "restyled"),
+            (self.retexted, "re-texted"),
+        ];
+        let parts: Vec<&str> = parts
+            .iter()
+            .filter(|(changed, _)| *changed)
+            .map(|(_, what)| *what)
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// E.g. "added", "removed" or "moved, restyled".
+impl fmt::Display for ChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChangeKind::Added => write!(f, "added"),
+            ChangeKind::Removed => write!(f, "removed"),
+            ChangeKind::Modified(modification) => write!(f, "{}", modification),
+        }
+    }
+}
+
+/// A short summary, e.g. "2 added, 1 removed" or "no changes".
+impl fmt::Display for DocumentDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no changes");
+        }
+        let parts = [
+            (self.added.len(), "added"),
+            (self.removed.len(), "removed"),
+            (self.modified.len(), "modified"),
+        ];
+        let parts: Vec<String> = parts
+            .iter()
+            .filter(|(count, _)| *count > 0)
+            .map(|(count, what)| format!("{} {}", count, what))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// Top-level shapes of a document in z-order.
+fn ordered(document: &CanvasDocument) -> impl Iterator<Item = (ShapeId, &Shape)> {
+    document
+        .z_order
+        .iter()
+        .filter_map(|id| Some((*id, document.shapes.get(id)?)))
+}
+
+/// Serialized fields that hold a shape's text.
+const TEXT_FIELDS: &[&str] = &["content", "latex"];
+
+/// Serialized fields that hold a shape's geometry. Anything else but the ID is
+/// appearance.
+const GEOMETRY_FIELDS: &[&str] = &[
+    "position",
+    "width",
+    "height",
+    "corner_radius",
+    "center",
+    "radius_x",
+    "radius_y",
+    "start",
+    "end",
+    "intermediate_points",
+    "points",
+    "pressures",
+    "rotation",
+];
+
+/// Which properties differ between two versions of a shape. Groups are compared
+/// child by child; a child added to or removed from a group counts as moving it.
+fn classify(old: &Shape, new: &Shape) -> Modification {
+    if let (Shape::Group(old), Shape::Group(new)) = (old, new) {
+        let ids = |group: &crate::shapes::Group| -> Vec<ShapeId> {
+            group.children.iter().map(Shape::id).collect()
+        };
+        let mut modification = Modification {
+            moved: old.rotation != new.rotation || ids(old) != ids(new),
+            ..Default::default()
+        };
+        for child in &new.children {
+            if let Some(before) = old.children.iter().find(|c| c.id() == child.id()) {
+                modification = modification.union(classify(before, child));
+            }
+        }
+        return modification;
+    }
+
+    let (Some(old), Some(new)) = (fields(old), fields(new)) else {
+        // Can't tell what changed; call it a move so it still shows up
+        return Modification {
+            moved: true,
+            ..Default::default()
+        };
+    };
+    let mut modification = Modification::default();
+    for key in old.keys().chain(new.keys()) {
+        if key == "id" || old.get(key) == new.get(key) {
+            continue;
+        }
+        if TEXT_FIELDS.contains(&key.as_str()) {
+            modification.retexted = true;
+        } else if GEOMETRY_FIELDS.contains(&key.as_str()) {
+            modification.moved = true;
+        } else {
+            modification.restyled = true;
+        }
+    }
+    modification
+}
+
+/// A shape's serialized fields. Images refer to their asset by ID rather than
+/// carrying their bytes, so they compare by content.
+fn fields(shape: &Shape) -> Option<Map<String, Value>> {
+    let id = shape.id().to_string();
+    let mut shapes = Map::new();
+    shapes.insert(id.clone(), serde_json::to_value(shape).ok()?);
+    let mut container = Map::new();
+    container.insert("shapes".to_string(), Value::Object(shapes));
+    assets::detach(&mut container, &assets::image_assets([shape])).ok()?;
+
+    // Shapes serialize as {"Kind": {fields}}
+    let Value::Object(mut shapes) = container.remove("shapes")? else {
+        return None;
+    };
+    let Value::Object(tagged) = shapes.remove(&id)? else {
+        return None;
+    };
+    match tagged.into_iter().next()? {
+        (_, Value::Object(fields)) => Some(fields),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::{Image, ImageFormat, Rectangle, Text};
+    use kurbo::{Affine, Point};
+
+    fn rect(x: f64) -> Shape {
+        Shape::Rectangle(Rectangle::new(Point::new(x, 0.0), 10.0, 10.0))
+    }
+
+    #[test]
+    fn test_identical_documents() {
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(rect(0.0));
+        let diff = DocumentDiff::between(&doc, &doc.clone());
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "no changes");
+    }
+
+    #[test]
+    fn test_added_removed_modified() {
+        let mut old = CanvasDocument::new();
+        let kept = rect(0.0);
+        let moved = rect(20.0);
+        let gone = rect(40.0);
+        let (kept_id, moved_id, gone_id) = (kept.id(), moved.id(), gone.id());
+        old.add_shape(kept);
+        old.add_shape(moved);
+        old.add_shape(gone);
+
+        let mut new = old.clone();
+        new.remove_shape(gone_id);
+        new.get_shape_mut(moved_id)
+            .unwrap()
+            .transform(Affine::translate((5.0, 0.0)));
+        let text = Shape::Text(Text::new(Point::ZERO, "new".to_string()));
+        let text_id = text.id();
+        new.add_shape(text);
+
+        let diff = DocumentDiff::between(&old, &new);
+        assert_eq!(diff.added, vec![text_id]);
+        assert_eq!(diff.removed, vec![gone_id]);
+        let moved = Modification {
+            moved: true,
+            ..Default::default()
+        };
+        assert_eq!(diff.modified, vec![(moved_id, moved)]);
+        assert_eq!(diff.kind_of(kept_id), None);
+        assert_eq!(diff.len(), 3);
+        assert_eq!(diff.to_string(), "1 added, 1 removed, 1 modified");
+    }
+
+    #[test]
+    fn test_reordering_is_not_a_change() {
+        let mut old = CanvasDocument::new();
+        let (a, b) = (rect(0.0), rect(20.0));
+        let a_id = a.id();
+        old.add_shape(a);
+        old.add_shape(b);
+        let mut new = old.clone();
+        new.bring_to_front(a_id);
+        assert!(DocumentDiff::between(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn test_images_compare_by_content() {
+        let image = Image::new(Point::ZERO, &[1, 2, 3], 4, 4, ImageFormat::Png);
+        let mut old = CanvasDocument::new();
+        old.add_shape(Shape::Image(image.clone()));
+
+        // Same content loaded separately is no change
+        let mut new = CanvasDocument::new();
+        let mut same = image.clone();
+        same.asset = crate::assets::Asset::new(vec![1, 2, 3]);
+        new.add_shape(Shape::Image(same));
+        assert!(DocumentDiff::between(&old, &new).is_empty());
+
+        let mut other = image;
+        other.asset = crate::assets::Asset::new(vec![9]);
+        new.shapes.insert(other.id, Shape::Image(other.clone()));
+        let diff = DocumentDiff::between(&old, &new);
+        assert_eq!(diff.modified.len(), 1);
+        assert!(diff.modified[0].1.restyled);
+        assert!(!diff.modified[0].1.moved);
+    }
+
+    #[test]
+    fn test_classifies_modifications() {
+        let mut old = CanvasDocument::new();
+        let (restyled, moved) = (rect(0.0), rect(20.0));
+        let text = Shape::Text(Text::new(Point::ZERO, "before".to_string()));
+        let (restyled_id, moved_id, text_id) = (restyled.id(), moved.id(), text.id());
+        old.add_shape(restyled);
+        old.add_shape(moved);
+        old.add_shape(text);
+
+        let mut new = old.clone();
+        new.get_shape_mut(restyled_id)
+            .unwrap()
+            .style_mut()
+            .stroke_width = 8.0;
+        let Some(Shape::Rectangle(rect)) = new.get_shape_mut(moved_id) else {
+            panic!("expected the rectangle");
+        };
+        rect.width = 40.0;
+        rect.style.opacity = 0.5;
+        let Some(Shape::Text(text)) = new.get_shape_mut(text_id) else {
+            panic!("expected the text");
+        };
+        text.content = "after".to_string();
+
+        let diff = DocumentDiff::between(&old, &new);
+        let kinds: Vec<String> = diff.changes().map(|c| c.kind.to_string()).collect();
+        assert_eq!(kinds, vec!["restyled", "moved, restyled", "re-texted"]);
+    }
+
+    #[test]
+    fn test_group_changes_come_from_children() {
+        let child = rect(0.0);
+        let child_id = child.id();
+        let group = Shape::Group(crate::shapes::Group::new(vec![child, rect(20.0)]));
+        let group_id = group.id();
+        let mut old = CanvasDocument::new();
+        old.add_shape(group);
+
+        let mut new = old.clone();
+        let Some(Shape::Group(group)) = new.get_shape_mut(group_id) else {
+            panic!("expected the group");
+        };
+        let child = group
+            .children
+            .iter_mut()
+            .find(|c| c.id() == child_id)
+            .unwrap();
+        child.style_mut().stroke_width = 8.0;
+
+        let diff = DocumentDiff::between(&old, &new);
+        let changes: Vec<ShapeChange> = diff.changes().collect();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].id, group_id);
+        assert_eq!(
+            changes[0].kind,
+            ChangeKind::Modified(Modification {
+                restyled: true,
+                ..Default::default()
+            })
+        );
+    }
+}
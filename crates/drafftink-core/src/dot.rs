@@ -0,0 +1,1107 @@
+//! Graphviz DOT import.
+//!
+//! Parses `graph`/`digraph` sources into shapes: nodes become rectangles, ellipses or
+//! closed polygons with text labels, edges become arrows (or lines for undirected
+//! graphs). Positions from the file are ignored; the graph is placed with the layered
+//! layout, honoring `rankdir`.
+
+use crate::layout::{LayoutDirection, connector_endpoints, layered_diagram};
+use crate::mermaid::{centered_text, ellipse, polygon, rectangle, text_size};
+use crate::shapes::{Arrow, Line, PathStyle, SerializableColor, Shape, StrokeStyle};
+use crate::svg;
+use kurbo::{Point, Rect, Size};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while importing a DOT graph.
+#[derive(Debug, Error, PartialEq)]
+pub enum DotError {
+    #[error("Empty graph")]
+    Empty,
+    #[error("Line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// Result type for DOT import.
+pub type DotResult<T> = Result<T, DotError>;
+
+/// Font size for node labels.
+const LABEL_FONT_SIZE: f64 = 20.0;
+/// Font size for edge labels.
+const EDGE_FONT_SIZE: f64 = 16.0;
+/// Padding between a label and its node outline.
+const NODE_PADDING: Size = Size::new(24.0, 16.0);
+/// Minimum node size.
+const MIN_NODE_SIZE: Size = Size::new(80.0, 50.0);
+/// Points per inch, the unit of DOT `width`/`height`.
+const POINTS_PER_INCH: f64 = 72.0;
+/// Default stroke width; `penwidth` scales it.
+const BASE_STROKE_WIDTH: f64 = 2.0;
+/// Size of the loop drawn for self edges.
+const SELF_LOOP: f64 = 30.0;
+
+/// Check whether text looks like a DOT graph.
+pub fn is_dot(source: &str) -> bool {
+    let Ok(tokens) = tokenize(source) else {
+        return false;
+    };
+    let mut kinds = tokens.iter().map(|t| &t.kind);
+    let mut first = kinds.next();
+    if matches!(first, Some(Token::Id(id)) if id.eq_ignore_ascii_case("strict")) {
+        first = kinds.next();
+    }
+    match first {
+        Some(Token::Id(id)) => {
+            id.eq_ignore_ascii_case("graph") || id.eq_ignore_ascii_case("digraph")
+        }
+        _ => false,
+    }
+}
+
+/// Parse a DOT graph into shapes, laid out with the top-left corner at the origin.
+pub fn parse_dot(source: &str) -> DotResult<Vec<Shape>> {
+    let tokens = tokenize(source)?;
+    if tokens.is_empty() {
+        return Err(DotError::Empty);
+    }
+    let graph = Parser::new(tokens).parse_graph()?;
+    Ok(graph.to_shapes())
+}
+
+fn syntax(line: usize, message: impl Into<String>) -> DotError {
+    DotError::Syntax {
+        line,
+        message: message.into(),
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Lexer
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Identifier, numeral, quoted or HTML string.
+    Id(String),
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Semi,
+    Comma,
+    Equals,
+    Colon,
+    /// `->` or `--`.
+    EdgeOp,
+}
+
+#[derive(Debug, Clone)]
+struct Spanned {
+    kind: Token,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> DotResult<Vec<Spanned>> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<Spanned> = Vec::new();
+    let mut line = 1;
+    let mut i = 0;
+    let mut line_start = true;
+    // Set after `+` so the next quoted string is appended to the previous one
+    let mut concat = false;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\n' {
+            line += 1;
+            line_start = true;
+            i += 1;
+            continue;
+        }
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        // Comments and preprocessor-style `#` lines
+        if (c == '#' && line_start) || (c == '/' && chars.get(i + 1) == Some(&'/')) {
+            while i < chars.len() && chars[i] != '\n' {
+                i += 1;
+            }
+            continue;
+        }
+        if c == '/' && chars.get(i + 1) == Some(&'*') {
+            i += 2;
+            while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                if chars[i] == '\n' {
+                    line += 1;
+                }
+                i += 1;
+            }
+            i += 2;
+            continue;
+        }
+        line_start = false;
+
+        let token_line = line;
+        let kind = match c {
+            '{' => Token::LBrace,
+            '}' => Token::RBrace,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            ';' => Token::Semi,
+            ',' => Token::Comma,
+            '=' => Token::Equals,
+            ':' => Token::Colon,
+            '+' => {
+                concat = true;
+                i += 1;
+                continue;
+            }
+            '-' if matches!(chars.get(i + 1), Some('>' | '-')) => {
+                i += 1;
+                Token::EdgeOp
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax(token_line, "unterminated string")),
+                        Some('"') => break,
+                        Some('\\') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 1;
+                        }
+                        // Line continuation
+                        Some('\\') if chars.get(i + 1) == Some(&'\n') => {
+                            line += 1;
+                            i += 1;
+                        }
+                        Some(&ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            text.push(ch);
+                        }
+                    }
+                    i += 1;
+                }
+                i += 1;
+                let append = std::mem::take(&mut concat);
+                match tokens.last_mut() {
+                    Some(Spanned {
+                        kind: Token::Id(previous),
+                        ..
+                    }) if append => previous.push_str(&text),
+                    _ => tokens.push(Spanned {
+                        kind: Token::Id(text),
+                        line: token_line,
+                    }),
+                }
+                continue;
+            }
+            '<' => {
+                let start = i;
+                let mut depth = 0;
+                loop {
+                    match chars.get(i) {
+                        None => return Err(syntax(token_line, "unterminated HTML string")),
+                        Some('<') => depth += 1,
+                        Some('>') => {
+                            depth -= 1;
+                            if depth == 0 {
+                                break;
+                            }
+                        }
+                        Some('\n') => line += 1,
+                        _ => {}
+                    }
+                    i += 1;
+                }
+                let html: String = chars[start + 1..i].iter().collect();
+                Token::Id(html_to_text(&html))
+            }
+            c if is_id_char(c) || c == '-' => {
+                let start = i;
+                while chars.get(i + 1).is_some_and(|&next| is_id_char(next)) {
+                    i += 1;
+                }
+                Token::Id(chars[start..=i].iter().collect())
+            }
+            other => {
+                return Err(syntax(
+                    token_line,
+                    format!("unexpected character '{}'", other),
+                ));
+            }
+        };
+        tokens.push(Spanned {
+            kind,
+            line: token_line,
+        });
+        i += 1;
+    }
+    Ok(tokens)
+}
+
+fn is_id_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '.' || !c.is_ascii()
+}
+
+/// Reduce an HTML-like label to plain text, keeping line breaks.
+fn html_to_text(html: &str) -> String {
+    let mut text = String::new();
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = rest[start + 1..start + end].trim().to_lowercase();
+        if tag.starts_with("br") || tag == "/tr" {
+            text.push('\n');
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+    let text = text
+        .replace("&amp;", "&")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&nbsp;", " ");
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// ---------------------------------------------------------------------------
+// Parser
+// ---------------------------------------------------------------------------
+
+type Attrs = HashMap<String, String>;
+
+#[derive(Debug, Clone)]
+struct DotNode {
+    name: String,
+    attrs: Attrs,
+}
+
+#[derive(Debug, Clone)]
+struct DotEdge {
+    from: usize,
+    to: usize,
+    attrs: Attrs,
+}
+
+#[derive(Debug, Default)]
+struct Graph {
+    name: String,
+    directed: bool,
+    attrs: Attrs,
+    nodes: Vec<DotNode>,
+    ids: HashMap<String, usize>,
+    edges: Vec<DotEdge>,
+}
+
+/// Default attributes in effect for a graph or subgraph body.
+#[derive(Debug, Clone, Default)]
+struct Scope {
+    node: Attrs,
+    edge: Attrs,
+}
+
+struct Parser {
+    tokens: Vec<Spanned>,
+    pos: usize,
+    graph: Graph,
+}
+
+impl Parser {
+    fn new(tokens: Vec<Spanned>) -> Self {
+        Self {
+            tokens,
+            pos: 0,
+            graph: Graph::default(),
+        }
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|t| &t.kind)
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .or(self.tokens.last())
+            .map_or(1, |t| t.line)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &Token, what: &str) -> DotResult<()> {
+        if self.eat(token) {
+            Ok(())
+        } else {
+            Err(syntax(self.line(), format!("expected {}", what)))
+        }
+    }
+
+    /// Consume an identifier, optionally only if it matches a keyword (case-insensitive).
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Id(id)) if id.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn id(&mut self) -> DotResult<String> {
+        match self.peek() {
+            Some(Token::Id(id)) => {
+                let id = id.clone();
+                self.pos += 1;
+                Ok(id)
+            }
+            _ => Err(syntax(self.line(), "expected an identifier")),
+        }
+    }
+
+    fn parse_graph(mut self) -> DotResult<Graph> {
+        self.eat_keyword("strict");
+        if self.eat_keyword("digraph") {
+            self.graph.directed = true;
+        } else if !self.eat_keyword("graph") {
+            return Err(syntax(self.line(), "expected 'graph' or 'digraph'"));
+        }
+        if let Some(Token::Id(_)) = self.peek() {
+            self.graph.name = self.id()?;
+        }
+        self.expect(&Token::LBrace, "'{'")?;
+        let mut scope = Scope::default();
+        self.parse_stmt_list(&mut scope, true)?;
+        self.expect(&Token::RBrace, "'}'")?;
+        Ok(self.graph)
+    }
+
+    /// Parse statements up to (not including) the closing brace.
+    fn parse_stmt_list(&mut self, scope: &mut Scope, root: bool) -> DotResult<Vec<usize>> {
+        let mut members = Vec::new();
+        while !matches!(self.peek(), Some(Token::RBrace) | None) {
+            self.parse_stmt(scope, root, &mut members)?;
+            self.eat(&Token::Semi);
+        }
+        Ok(members)
+    }
+
+    fn parse_stmt(
+        &mut self,
+        scope: &mut Scope,
+        root: bool,
+        members: &mut Vec<usize>,
+    ) -> DotResult<()> {
+        // Attribute statements: `graph [..]`, `node [..]`, `edge [..]`
+        if matches!(
+            self.tokens.get(self.pos + 1).map(|t| &t.kind),
+            Some(Token::LBracket)
+        ) {
+            if self.eat_keyword("node") {
+                let attrs = self.parse_attr_lists()?;
+                scope.node.extend(attrs);
+                return Ok(());
+            }
+            if self.eat_keyword("edge") {
+                let attrs = self.parse_attr_lists()?;
+                scope.edge.extend(attrs);
+                return Ok(());
+            }
+            if self.eat_keyword("graph") {
+                let attrs = self.parse_attr_lists()?;
+                if root {
+                    self.graph.attrs.extend(attrs);
+                }
+                return Ok(());
+            }
+        }
+
+        // `key = value` graph attribute
+        if matches!(
+            self.tokens.get(self.pos + 1).map(|t| &t.kind),
+            Some(Token::Equals)
+        ) && matches!(self.peek(), Some(Token::Id(_)))
+        {
+            let key = self.id()?;
+            self.pos += 1;
+            let value = self.id()?;
+            if root {
+                self.graph.attrs.insert(key, value);
+            }
+            return Ok(());
+        }
+
+        // Node or edge statement
+        let mut operands = vec![self.parse_operand(scope, members)?];
+        while self.eat(&Token::EdgeOp) {
+            operands.push(self.parse_operand(scope, members)?);
+        }
+        let attrs = if self.peek() == Some(&Token::LBracket) {
+            self.parse_attr_lists()?
+        } else {
+            Attrs::new()
+        };
+
+        if operands.len() == 1 {
+            for &node in &operands[0] {
+                self.graph.nodes[node].attrs.extend(attrs.clone());
+            }
+            return Ok(());
+        }
+        let mut edge_attrs = scope.edge.clone();
+        edge_attrs.extend(attrs);
+        for pair in operands.windows(2) {
+            for &from in &pair[0] {
+                for &to in &pair[1] {
+                    self.graph.edges.push(DotEdge {
+                        from,
+                        to,
+                        attrs: edge_attrs.clone(),
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// A node ID (with optional port) or a subgraph; returns the node indices it stands for.
+    fn parse_operand(&mut self, scope: &Scope, members: &mut Vec<usize>) -> DotResult<Vec<usize>> {
+        if self.peek() == Some(&Token::LBrace) || self.eat_keyword("subgraph") {
+            if let Some(Token::Id(_)) = self.peek() {
+                self.id()?;
+            }
+            self.expect(&Token::LBrace, "'{'")?;
+            let mut inner = scope.clone();
+            let nodes = self.parse_stmt_list(&mut inner, false)?;
+            self.expect(&Token::RBrace, "'}'")?;
+            members.extend(&nodes);
+            return Ok(nodes);
+        }
+
+        let name = self.id()?;
+        // Ports (`node:port:compass`) only affect where edges attach
+        while self.eat(&Token::Colon) {
+            self.id()?;
+        }
+        let index = match self.graph.ids.get(&name) {
+            Some(&index) => index,
+            None => {
+                self.graph.nodes.push(DotNode {
+                    name: name.clone(),
+                    attrs: scope.node.clone(),
+                });
+                self.graph.ids.insert(name, self.graph.nodes.len() - 1);
+                self.graph.nodes.len() - 1
+            }
+        };
+        members.push(index);
+        Ok(vec![index])
+    }
+
+    /// One or more `[a=b, c=d]` lists.
+    fn parse_attr_lists(&mut self) -> DotResult<Attrs> {
+        let mut attrs = Attrs::new();
+        while self.eat(&Token::LBracket) {
+            while !self.eat(&Token::RBracket) {
+                let key = self.id()?;
+                let value = if self.eat(&Token::Equals) {
+                    self.id()?
+                } else {
+                    "true".to_string()
+                };
+                attrs.insert(key, value);
+                if !self.eat(&Token::Comma) {
+                    self.eat(&Token::Semi);
+                }
+                if self.peek().is_none() {
+                    return Err(syntax(self.line(), "expected ']'"));
+                }
+            }
+        }
+        Ok(attrs)
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Conversion to shapes
+// ---------------------------------------------------------------------------
+
+/// Node outline, from the DOT `shape` attribute.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum NodeShape {
+    Box,
+    Ellipse,
+    Circle,
+    DoubleCircle,
+    Point,
+    Diamond,
+    /// Regular polygon with the given number of sides.
+    Polygon(usize),
+    Triangle,
+    InvTriangle,
+    Parallelogram,
+    Trapezium,
+    InvTrapezium,
+    /// Label only (`plaintext`, `plain`, `none`).
+    Plain,
+}
+
+impl NodeShape {
+    fn from_name(name: &str) -> Self {
+        match name.to_lowercase().as_str() {
+            "box" | "rect" | "rectangle" | "square" | "record" | "mrecord" | "component"
+            | "note" | "tab" | "folder" | "box3d" | "cylinder" | "underline" => Self::Box,
+            "circle" => Self::Circle,
+            "doublecircle" => Self::DoubleCircle,
+            "point" => Self::Point,
+            "diamond" | "mdiamond" => Self::Diamond,
+            "pentagon" => Self::Polygon(5),
+            "hexagon" => Self::Polygon(6),
+            "septagon" => Self::Polygon(7),
+            "octagon" | "doubleoctagon" | "tripleoctagon" => Self::Polygon(8),
+            "triangle" => Self::Triangle,
+            "invtriangle" => Self::InvTriangle,
+            "parallelogram" => Self::Parallelogram,
+            "trapezium" => Self::Trapezium,
+            "invtrapezium" => Self::InvTrapezium,
+            "plaintext" | "plain" | "none" => Self::Plain,
+            _ => Self::Ellipse,
+        }
+    }
+}
+
+fn attr<'a>(attrs: &'a Attrs, key: &str) -> Option<&'a str> {
+    attrs.get(key).map(String::as_str).filter(|v| !v.is_empty())
+}
+
+fn has_style(attrs: &Attrs, style: &str) -> bool {
+    attr(attrs, "style").is_some_and(|s| s.split(',').any(|part| part.trim() == style))
+}
+
+/// Expand label escapes (`\n`, `\l`, `\r`, `\N`, `\G`) and record field separators.
+fn expand_label(label: &str, node_name: &str, graph_name: &str, record: bool) -> String {
+    let mut text = label
+        .replace("\\N", node_name)
+        .replace("\\G", graph_name)
+        .replace("\\n", "\n")
+        .replace("\\l", "\n")
+        .replace("\\r", "\n")
+        .replace("\\\\", "\\");
+    if record {
+        // `{a|<port> b}` -> one field per line
+        let mut fields = Vec::new();
+        for field in text.split(['|', '{', '}']) {
+            let field = match field.trim().strip_prefix('<') {
+                Some(rest) => rest.split_once('>').map_or(rest, |(_, f)| f),
+                None => field,
+            };
+            if !field.trim().is_empty() {
+                fields.push(field.trim().to_string());
+            }
+        }
+        text = fields.join("\n");
+    }
+    text.trim_end_matches('\n').to_string()
+}
+
+/// Parse a DOT color: a name, `#rrggbb[aa]` or an HSV triple. Color lists use the first entry.
+fn parse_color(value: &str) -> Option<SerializableColor> {
+    let value = value.split(':').next()?.split(';').next()?.trim();
+    if value.starts_with('#') {
+        return svg::parse_color(value);
+    }
+
+    let hsv: Vec<f64> = value
+        .split([',', ' '])
+        .filter(|s| !s.is_empty())
+        .map_while(|s| s.parse().ok())
+        .collect();
+    if hsv.len() == 3 {
+        return Some(hsv_to_color(hsv[0], hsv[1], hsv[2]));
+    }
+
+    // X11 names where Graphviz differs from CSS; the rest are CSS colors
+    let name = value.to_lowercase();
+    let (r, g, b) = match name.trim_start_matches("/x11/") {
+        "green" => (0, 255, 0),
+        "purple" => (160, 32, 240),
+        "gray" | "grey" => (192, 192, 192),
+        "navyblue" => (0, 0, 128),
+        "none" => return Some(SerializableColor::transparent()),
+        other => return svg::parse_color(other),
+    };
+    Some(SerializableColor::new(r, g, b, 255))
+}
+
+fn hsv_to_color(h: f64, s: f64, v: f64) -> SerializableColor {
+    let h = (h.clamp(0.0, 1.0) * 6.0) % 6.0;
+    let (s, v) = (s.clamp(0.0, 1.0), v.clamp(0.0, 1.0));
+    let c = v * s;
+    let x = c * (1.0 - ((h % 2.0) - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (c, x, 0.0),
+        1 => (x, c, 0.0),
+        2 => (0.0, c, x),
+        3 => (0.0, x, c),
+        4 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    let m = v - c;
+    let to_u8 = |channel: f64| ((channel + m) * 255.0).round() as u8;
+    SerializableColor::new(to_u8(r), to_u8(g), to_u8(b), 255)
+}
+
+impl Graph {
+    fn direction(&self) -> (LayoutDirection, bool) {
+        match attr(&self.attrs, "rankdir")
+            .map(str::to_uppercase)
+            .as_deref()
+        {
+            Some("LR") => (LayoutDirection::LeftRight, false),
+            Some("RL") => (LayoutDirection::LeftRight, true),
+            Some("BT") => (LayoutDirection::TopDown, true),
+            _ => (LayoutDirection::TopDown, false),
+        }
+    }
+
+    fn node_label(&self, node: &DotNode) -> String {
+        let label = attr(&node.attrs, "label").unwrap_or("\\N");
+        let record = attr(&node.attrs, "shape")
+            .is_some_and(|s| s.eq_ignore_ascii_case("record") || s.eq_ignore_ascii_case("mrecord"));
+        expand_label(label, &node.name, &self.name, record)
+    }
+
+    fn node_size(&self, node: &DotNode, shape: NodeShape, label: &str) -> Size {
+        if shape == NodeShape::Point {
+            return Size::new(12.0, 12.0);
+        }
+        let text = text_size(label, LABEL_FONT_SIZE);
+        let mut size = Size::new(
+            (text.width + NODE_PADDING.width * 2.0).max(MIN_NODE_SIZE.width),
+            (text.height + NODE_PADDING.height * 2.0).max(MIN_NODE_SIZE.height),
+        );
+        size = match shape {
+            NodeShape::Ellipse => Size::new(size.width * 1.2, size.height * 1.2),
+            NodeShape::Circle | NodeShape::DoubleCircle => {
+                let diameter = text.width.max(text.height) + NODE_PADDING.height * 2.0;
+                Size::new(diameter, diameter)
+            }
+            NodeShape::Diamond => Size::new(size.width * 1.4, size.height * 1.6),
+            NodeShape::Polygon(_) => Size::new(size.width * 1.3, size.height * 1.3),
+            NodeShape::Triangle | NodeShape::InvTriangle => {
+                Size::new(size.width * 1.6, size.height * 1.6)
+            }
+            NodeShape::Parallelogram | NodeShape::Trapezium | NodeShape::InvTrapezium => {
+                Size::new(size.width + size.height, size.height)
+            }
+            _ => size,
+        };
+        // `width`/`height` are minimums in inches
+        let inches = |key: &str| attr(&node.attrs, key).and_then(|v| v.parse::<f64>().ok());
+        if let Some(width) = inches("width") {
+            size.width = size.width.max(width * POINTS_PER_INCH);
+        }
+        if let Some(height) = inches("height") {
+            size.height = size.height.max(height * POINTS_PER_INCH);
+        }
+        if matches!(shape, NodeShape::Circle | NodeShape::DoubleCircle) {
+            let diameter = size.width.max(size.height);
+            size = Size::new(diameter, diameter);
+        }
+        size
+    }
+
+    fn to_shapes(&self) -> Vec<Shape> {
+        let visible: Vec<bool> = self
+            .nodes
+            .iter()
+            .map(|n| !has_style(&n.attrs, "invis"))
+            .collect();
+        let shapes_of: Vec<NodeShape> = self
+            .nodes
+            .iter()
+            .map(|n| NodeShape::from_name(attr(&n.attrs, "shape").unwrap_or("ellipse")))
+            .collect();
+        let labels: Vec<String> = self.nodes.iter().map(|n| self.node_label(n)).collect();
+        let sizes: Vec<Size> = self
+            .nodes
+            .iter()
+            .zip(&shapes_of)
+            .zip(&labels)
+            .map(|((node, &shape), label)| self.node_size(node, shape, label))
+            .collect();
+        let edges: Vec<(usize, usize)> = self.edges.iter().map(|e| (e.from, e.to)).collect();
+
+        let (direction, reversed) = self.direction();
+        let rects = layered_diagram(&sizes, &edges, direction, reversed, 40.0, 70.0);
+
+        let mut shapes = Vec::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if visible[i] {
+                shapes.extend(node_shapes(node, shapes_of[i], &labels[i], rects[i]));
+            }
+        }
+        for edge in &self.edges {
+            if !has_style(&edge.attrs, "invis") && visible[edge.from] && visible[edge.to] {
+                shapes.extend(self.edge_shapes(edge, rects[edge.from], rects[edge.to]));
+            }
+        }
+        shapes
+    }
+
+    fn edge_shapes(&self, edge: &DotEdge, from: Rect, to: Rect) -> Vec<Shape> {
+        let attrs = &edge.attrs;
+        let dir = attr(attrs, "dir").unwrap_or(if self.directed { "forward" } else { "none" });
+        let no_head = attr(attrs, "arrowhead").is_some_and(|h| h == "none");
+        let (head, backwards) = match dir {
+            "back" => (attr(attrs, "arrowtail") != Some("none"), true),
+            "none" => (false, false),
+            _ => (!no_head, false),
+        };
+
+        let points = if edge.from == edge.to {
+            let x = from.x1;
+            let y = from.center().y;
+            vec![
+                Point::new(x, y - SELF_LOOP / 2.0),
+                Point::new(x + SELF_LOOP, y - SELF_LOOP / 2.0),
+                Point::new(x + SELF_LOOP, y + SELF_LOOP / 2.0),
+                Point::new(x, y + SELF_LOOP / 2.0),
+            ]
+        } else {
+            let (start, end) = connector_endpoints(from, to, false);
+            vec![start, end]
+        };
+        let mut points = points;
+        if backwards {
+            points.reverse();
+        }
+        let label_at = if edge.from == edge.to {
+            Point::new(from.x1 + SELF_LOOP + 8.0, from.center().y)
+        } else {
+            points[0].midpoint(points[points.len() - 1])
+        };
+
+        let stroke_style = if has_style(attrs, "dashed") {
+            StrokeStyle::Dashed
+        } else if has_style(attrs, "dotted") {
+            StrokeStyle::Dotted
+        } else {
+            StrokeStyle::Solid
+        };
+        let mut connector = if head {
+            let mut arrow = Arrow::from_points(points, PathStyle::Direct);
+            arrow.stroke_style = stroke_style;
+            Shape::Arrow(arrow)
+        } else {
+            let mut line = Line::from_points(points, PathStyle::Direct);
+            line.stroke_style = stroke_style;
+            Shape::Line(line)
+        };
+        apply_stroke(connector.style_mut(), attrs);
+
+        let mut shapes = vec![connector];
+        if let Some(label) = attr(attrs, "label").or(attr(attrs, "xlabel")) {
+            let label = expand_label(label, "", &self.name, false);
+            let mut text = centered_text(label_at, &label, EDGE_FONT_SIZE);
+            if edge.from == edge.to {
+                // Keep self-loop labels to the right of the loop
+                text.position.x = label_at.x;
+            }
+            if let Some(color) = attr(attrs, "fontcolor").and_then(parse_color) {
+                text.style.stroke_color = color;
+            }
+            shapes.push(Shape::Text(text));
+        }
+        shapes
+    }
+}
+
+/// Apply `color`, `penwidth` and `style=bold` to a shape style.
+fn apply_stroke(style: &mut crate::shapes::ShapeStyle, attrs: &Attrs) {
+    if let Some(color) = attr(attrs, "color").and_then(parse_color) {
+        style.stroke_color = color;
+    }
+    let pen = attr(attrs, "penwidth")
+        .and_then(|w| w.parse::<f64>().ok())
+        .unwrap_or(1.0);
+    style.stroke_width = BASE_STROKE_WIDTH * pen;
+    if has_style(attrs, "bold") {
+        style.stroke_width = style.stroke_width.max(4.0);
+    }
+}
+
+/// Outline shape(s) and label for a node placed in `rect`.
+fn node_shapes(node: &DotNode, shape: NodeShape, label: &str, rect: Rect) -> Vec<Shape> {
+    let attrs = &node.attrs;
+    let center = rect.center();
+    let mut outlines = Vec::new();
+    match shape {
+        NodeShape::Box => {
+            let rounded = has_style(attrs, "rounded")
+                || attr(attrs, "shape").is_some_and(|s| s.eq_ignore_ascii_case("mrecord"));
+            let radius = if rounded { 12.0 } else { 0.0 };
+            outlines.push(Shape::Rectangle(rectangle(rect, radius)));
+        }
+        NodeShape::Ellipse | NodeShape::Circle | NodeShape::Point => {
+            outlines.push(Shape::Ellipse(ellipse(rect)));
+        }
+        NodeShape::DoubleCircle => {
+            outlines.push(Shape::Ellipse(ellipse(rect)));
+            outlines.push(Shape::Ellipse(ellipse(rect.inset(-5.0))));
+        }
+        NodeShape::Diamond => outlines.push(polygon(&[
+            Point::new(center.x, rect.y0),
+            Point::new(rect.x1, center.y),
+            Point::new(center.x, rect.y1),
+            Point::new(rect.x0, center.y),
+        ])),
+        NodeShape::Polygon(sides) => {
+            let points: Vec<Point> = (0..sides)
+                .map(|i| {
+                    let angle = std::f64::consts::TAU * i as f64 / sides as f64
+                        - std::f64::consts::FRAC_PI_2
+                        + std::f64::consts::PI / sides as f64;
+                    Point::new(
+                        center.x + rect.width() / 2.0 * angle.cos(),
+                        center.y + rect.height() / 2.0 * angle.sin(),
+                    )
+                })
+                .collect();
+            outlines.push(polygon(&points));
+        }
+        NodeShape::Triangle => outlines.push(polygon(&[
+            Point::new(center.x, rect.y0),
+            Point::new(rect.x1, rect.y1),
+            Point::new(rect.x0, rect.y1),
+        ])),
+        NodeShape::InvTriangle => outlines.push(polygon(&[
+            Point::new(rect.x0, rect.y0),
+            Point::new(rect.x1, rect.y0),
+            Point::new(center.x, rect.y1),
+        ])),
+        NodeShape::Parallelogram => {
+            let skew = rect.height() / 2.0;
+            outlines.push(polygon(&[
+                Point::new(rect.x0 + skew, rect.y0),
+                Point::new(rect.x1, rect.y0),
+                Point::new(rect.x1 - skew, rect.y1),
+                Point::new(rect.x0, rect.y1),
+            ]));
+        }
+        NodeShape::Trapezium | NodeShape::InvTrapezium => {
+            let inset = rect.height() / 2.0;
+            let (top, bottom) = if shape == NodeShape::Trapezium {
+                (inset, 0.0)
+            } else {
+                (0.0, inset)
+            };
+            outlines.push(polygon(&[
+                Point::new(rect.x0 + top, rect.y0),
+                Point::new(rect.x1 - top, rect.y0),
+                Point::new(rect.x1 - bottom, rect.y1),
+                Point::new(rect.x0 + bottom, rect.y1),
+            ]));
+        }
+        NodeShape::Plain => {}
+    }
+
+    let filled = has_style(attrs, "filled") || shape == NodeShape::Point;
+    let fill = attr(attrs, "fillcolor")
+        .or(attr(attrs, "color"))
+        .and_then(parse_color)
+        .or(filled.then(|| {
+            if shape == NodeShape::Point {
+                SerializableColor::black()
+            } else {
+                SerializableColor::new(211, 211, 211, 255)
+            }
+        }));
+    for (i, outline) in outlines.iter_mut().enumerate() {
+        let style = outline.style_mut();
+        apply_stroke(style, attrs);
+        // Only the outermost outline of double shapes is filled
+        if filled && i == 0 {
+            style.fill_color = fill;
+        }
+    }
+
+    let mut shapes = outlines;
+    if shape != NodeShape::Point && !label.is_empty() {
+        let mut text = centered_text(center, label, LABEL_FONT_SIZE);
+        if let Some(color) = attr(attrs, "fontcolor").and_then(parse_color) {
+            text.style.stroke_color = color;
+        }
+        shapes.push(Shape::Text(text));
+    }
+    shapes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(shapes: &[Shape]) -> Vec<String> {
+        shapes
+            .iter()
+            .filter_map(|s| match s {
+                Shape::Text(t) => Some(t.content.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn count(shapes: &[Shape], f: impl Fn(&Shape) -> bool) -> usize {
+        shapes.iter().filter(|s| f(s)).count()
+    }
+
+    #[test]
+    fn test_detect_dot() {
+        assert!(is_dot("digraph G { a -> b }"));
+        assert!(is_dot("// deps\nstrict graph { a -- b }"));
+        assert!(!is_dot("flowchart TD\nA --> B"));
+        assert!(!is_dot("{\"type\": \"excalidraw\"}"));
+    }
+
+    #[test]
+    fn test_digraph_shapes_and_labels() {
+        let shapes = parse_dot(
+            r##"digraph deps {
+                node [shape=box];
+                api [label="API\nserver", color=red];
+                db [shape=cylinder];
+                cache [shape=ellipse, style=filled, fillcolor="#336699"];
+                check [shape=diamond];
+                api -> db [label="reads"];
+                api -> cache -> check;
+            }"##,
+        )
+        .unwrap();
+
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 3);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Rectangle(_))), 2);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Ellipse(_))), 1);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Line(_))), 1);
+
+        let labels = texts(&shapes);
+        for expected in ["API\nserver", "db", "cache", "check", "reads"] {
+            assert!(labels.iter().any(|l| l == expected), "missing {expected}");
+        }
+
+        let api = shapes
+            .iter()
+            .find(|s| matches!(s, Shape::Rectangle(_)))
+            .unwrap();
+        assert_eq!(
+            api.style().stroke_color,
+            SerializableColor::new(255, 0, 0, 255)
+        );
+        let cache = shapes
+            .iter()
+            .find(|s| matches!(s, Shape::Ellipse(_)))
+            .unwrap();
+        assert_eq!(
+            cache.style().fill_color,
+            Some(SerializableColor::new(0x33, 0x66, 0x99, 255))
+        );
+    }
+
+    #[test]
+    fn test_undirected_and_edge_direction() {
+        let shapes = parse_dot("graph { a -- b; b -- c [dir=forward] }").unwrap();
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Line(_))), 1);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 1);
+
+        let shapes = parse_dot("digraph { a -> b [dir=back] }").unwrap();
+        let ellipses: Vec<Rect> = shapes
+            .iter()
+            .filter(|s| matches!(s, Shape::Ellipse(_)))
+            .map(|s| s.bounds())
+            .collect();
+        let Some(Shape::Arrow(arrow)) = shapes.iter().find(|s| matches!(s, Shape::Arrow(_))) else {
+            panic!("expected an arrow");
+        };
+        // Back edges point from b (lower layer) up to a
+        assert!(arrow.end.y < arrow.start.y);
+        assert!(ellipses[0].y1 < ellipses[1].y0);
+    }
+
+    #[test]
+    fn test_rankdir_and_subgraph_edges() {
+        let shapes =
+            parse_dot("digraph { rankdir=LR; a -> { b c }; subgraph cluster_x { d } c -> d }")
+                .unwrap();
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 3);
+        let bounds: Vec<Rect> = shapes
+            .iter()
+            .filter(|s| matches!(s, Shape::Ellipse(_)))
+            .map(|s| s.bounds())
+            .collect();
+        // a is left of b and c
+        assert!(bounds[0].x1 < bounds[1].x0);
+        assert!(bounds[0].x1 < bounds[2].x0);
+    }
+
+    #[test]
+    fn test_comments_concat_and_html_labels() {
+        let shapes = parse_dot(
+            "/* header */\ndigraph {\n# pragma\n a [label=\"multi\" + \"part\"] // trailing\n b [label=<<b>bold</b><br/>text>]\n a -> b\n}",
+        )
+        .unwrap();
+        let labels = texts(&shapes);
+        assert!(labels.contains(&"multipart".to_string()));
+        assert!(labels.contains(&"bold\ntext".to_string()));
+    }
+
+    #[test]
+    fn test_invisible_and_colors() {
+        let shapes = parse_dot("digraph { a -> b [style=invis]; c [style=invis] }").unwrap();
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 0);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Ellipse(_))), 2);
+
+        assert_eq!(
+            parse_color("navy"),
+            Some(SerializableColor::new(0, 0, 128, 255))
+        );
+        assert_eq!(
+            parse_color("#ff000080"),
+            Some(SerializableColor::new(255, 0, 0, 128))
+        );
+        assert_eq!(
+            parse_color("0.0 1.0 1.0"),
+            Some(SerializableColor::new(255, 0, 0, 255))
+        );
+        assert_eq!(
+            parse_color("red:blue"),
+            Some(SerializableColor::new(255, 0, 0, 255))
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert!(matches!(
+            parse_dot("digraph {\n a -> \n}").unwrap_err(),
+            DotError::Syntax { line: 3, .. }
+        ));
+        assert_eq!(parse_dot("  // nothing").unwrap_err(), DotError::Empty);
+    }
+}
@@ -0,0 +1,1595 @@
+//! draw.io import and export.
+//!
+//! Reads diagrams.net files (`.drawio`, mxGraph XML) into shapes, and writes shapes back
+//! out as mxGraph cells. Both plain and compressed diagrams (raw deflate, base64
+//! encoded) are read. Every page is imported, pages stacked top to bottom; export
+//! writes a single uncompressed page.
+//!
+//! - Vertices become rectangles (rounded with `rounded=1`), ellipses, diamonds
+//!   (`rhombus`), triangles and hexagons as closed lines, text or images. Other shapes
+//!   keep their geometry as rectangles. Labels are grouped with their shape.
+//! - Edges become arrows, or lines without arrowheads, through their waypoints.
+//!   Connected ends are moved to the terminal's outline, or to its fixed
+//!   `exitX`/`entryX` point. Edge labels are grouped with the edge.
+//! - Groups and containers become groups, with children placed relative to them.
+//! - Labels wrapped in `$$` become math.
+//!
+//! On export, groups become `group` cells and labeled groups become labeled cells.
+//! Arrow ends within [`CONNECTION_TOLERANCE`] of a shape are connected to it.
+
+use crate::excalidraw::{diamond_bounds, image_from_data_url, label_pair};
+use crate::layout::{CONNECTION_TOLERANCE, distance_to_rect};
+use crate::mermaid::{ellipse, polygon, rectangle};
+use crate::shapes::{
+    Arrow, FillPattern, FontFamily, FontWeight, Group, Line, Math, PathStyle, SerializableColor,
+    Shape, ShapeStyle, ShapeTrait, Sloppiness, StrokeStyle, Text,
+};
+use crate::svg::{Element, SvgError, color_hex, decode_entities, parse_color, parse_xml_root};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use kurbo::{Affine, Point, Rect, Vec2};
+use std::collections::HashMap;
+use std::fmt::Write;
+use thiserror::Error;
+
+/// Errors that can occur while importing a draw.io diagram.
+#[derive(Debug, Error, PartialEq)]
+pub enum DrawioError {
+    #[error("Not a draw.io diagram")]
+    NotDrawio,
+    #[error("{0}")]
+    Xml(String),
+    #[error("Invalid compressed diagram: {0}")]
+    Compressed(String),
+}
+
+impl From<SvgError> for DrawioError {
+    fn from(error: SvgError) -> Self {
+        match error {
+            SvgError::Xml { .. } => DrawioError::Xml(error.to_string()),
+            _ => DrawioError::NotDrawio,
+        }
+    }
+}
+
+/// Result type for draw.io import.
+pub type DrawioResult<T> = Result<T, DrawioError>;
+
+/// Vertical space between imported pages.
+const PAGE_GAP: f64 = 200.0;
+/// draw.io's default font size.
+const DEFAULT_FONT_SIZE: f64 = 12.0;
+/// draw.io's default space between a label and its bounds.
+const DEFAULT_SPACING: f64 = 2.0;
+/// Limit for parent chains and container nesting.
+const MAX_DEPTH: usize = 64;
+/// Limit for the size of an inflated page.
+const MAX_INFLATED_SIZE: usize = 64 * 1024 * 1024;
+/// Id of the root cell in exported files.
+const ROOT_ID: &str = "0";
+/// Id of the default layer in exported files.
+const LAYER_ID: &str = "1";
+
+/// Check whether text is a draw.io file or a bare mxGraph model.
+pub fn is_drawio(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    let body = match text.strip_prefix("<?xml") {
+        Some(rest) => rest
+            .split_once("?>")
+            .map_or("", |(_, body)| body.trim_start()),
+        None => text,
+    };
+    ["<mxfile", "<mxGraphModel"].iter().any(|tag| {
+        body.strip_prefix(tag).is_some_and(|rest| {
+            rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        })
+    })
+}
+
+/// Parse a draw.io file or bare mxGraph model into shapes, in z-order.
+pub fn parse_drawio(source: &str) -> DrawioResult<Vec<Shape>> {
+    let root = parse_xml_root(source)?;
+    let mut pages = Vec::new();
+    match root.name.as_str() {
+        "mxGraphModel" => pages.push(import_model(&root)),
+        "mxfile" => {
+            for diagram in root.elements().filter(|e| e.name == "diagram") {
+                if let Some(model) = diagram.elements().find(|e| e.name == "mxGraphModel") {
+                    pages.push(import_model(model));
+                    continue;
+                }
+                let data = diagram.text();
+                if data.trim().is_empty() {
+                    continue;
+                }
+                let model = parse_xml_root(&decompress(&data)?)?;
+                if model.name != "mxGraphModel" {
+                    return Err(DrawioError::NotDrawio);
+                }
+                pages.push(import_model(&model));
+            }
+        }
+        _ => return Err(DrawioError::NotDrawio),
+    }
+
+    let mut shapes = Vec::new();
+    let mut bottom: Option<f64> = None;
+    for mut page_shapes in pages {
+        let Some(bounds) = page_shapes
+            .iter()
+            .map(Shape::bounds)
+            .reduce(|a, b| a.union(b))
+        else {
+            continue;
+        };
+        // Each page goes below the previous one
+        let offset = bottom.map_or(0.0, |bottom| bottom + PAGE_GAP - bounds.y0);
+        if offset != 0.0 {
+            for shape in &mut page_shapes {
+                shape.transform(Affine::translate((0.0, offset)));
+            }
+        }
+        bottom = Some(bounds.y1 + offset);
+        shapes.extend(page_shapes);
+    }
+    Ok(shapes)
+}
+
+/// Export shapes as a single-page, uncompressed draw.io file.
+pub fn export_drawio<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> String {
+    let mut exporter = Exporter::default();
+    for shape in shapes {
+        exporter.shape(shape, LAYER_ID, Point::ZERO);
+    }
+    exporter.connect_edges();
+    exporter.finish()
+}
+
+/// Decode a compressed page: base64, then raw deflate, then URL encoding.
+fn decompress(data: &str) -> DrawioResult<String> {
+    let data: String = data.split_whitespace().collect();
+    let bytes = STANDARD
+        .decode(data)
+        .map_err(|e| DrawioError::Compressed(e.to_string()))?;
+    let inflated = miniz_oxide::inflate::decompress_to_vec_with_limit(&bytes, MAX_INFLATED_SIZE)
+        .map_err(|e| DrawioError::Compressed(format!("{:?}", e.status)))?;
+    let encoded = String::from_utf8(inflated)
+        .map_err(|_| DrawioError::Compressed("not UTF-8".to_string()))?;
+    percent_decode(&encoded)
+        .ok_or_else(|| DrawioError::Compressed("invalid URL encoding".to_string()))
+}
+
+/// Undo `encodeURIComponent`.
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = text.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+// ---------------------------------------------------------------------------
+// Import
+// ---------------------------------------------------------------------------
+
+/// A parsed `style` attribute: named styles and `key=value` pairs.
+#[derive(Debug, Default)]
+struct Style<'a> {
+    names: Vec<&'a str>,
+    values: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> Style<'a> {
+    fn parse(style: &'a str) -> Self {
+        let mut parsed = Self::default();
+        for entry in style.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+            match entry.split_once('=') {
+                Some((key, value)) => {
+                    parsed.values.insert(key.trim(), value.trim());
+                }
+                None => parsed.names.push(entry),
+            }
+        }
+        parsed
+    }
+
+    fn get(&self, key: &str) -> Option<&'a str> {
+        self.values.get(key).copied()
+    }
+
+    fn flag(&self, key: &str) -> bool {
+        self.get(key) == Some("1")
+    }
+
+    fn number(&self, key: &str, default: f64) -> f64 {
+        self.get(key)
+            .and_then(|value| value.parse().ok())
+            .filter(|value: &f64| value.is_finite())
+            .unwrap_or(default)
+    }
+
+    /// The shape, from `shape=` or a named style such as `ellipse`.
+    fn kind(&self) -> &'a str {
+        self.get("shape")
+            .or_else(|| self.names.first().copied())
+            .unwrap_or_default()
+    }
+
+    /// A color value; `default` when missing, None for `none`.
+    fn color(&self, key: &str, default: Option<SerializableColor>) -> Option<SerializableColor> {
+        match self.get(key) {
+            None | Some("default") => default,
+            Some("none") => None,
+            Some(value) => parse_color(value).or(default),
+        }
+    }
+}
+
+/// A cell of the graph model, with the id and label of a wrapping `UserObject`.
+struct Cell<'a> {
+    id: &'a str,
+    parent: Option<&'a str>,
+    label: &'a str,
+    style: Style<'a>,
+    vertex: bool,
+    edge: bool,
+    visible: bool,
+    source: Option<&'a str>,
+    target: Option<&'a str>,
+    geometry: Geometry,
+}
+
+#[derive(Debug, Default)]
+struct Geometry {
+    /// Bounds relative to the parent; for edges, `x` is the label position.
+    rect: Rect,
+    /// Whether the geometry is relative to an edge (edge labels).
+    relative: bool,
+    points: Vec<Point>,
+    source_point: Option<Point>,
+    target_point: Option<Point>,
+    offset: Vec2,
+}
+
+impl Geometry {
+    fn read(element: &Element) -> Self {
+        let number = |e: &Element, key: &str| {
+            e.attr(key)
+                .and_then(|v| v.parse::<f64>().ok())
+                .filter(|v| v.is_finite())
+                .unwrap_or(0.0)
+        };
+        let point = |e: &Element| Point::new(number(e, "x"), number(e, "y"));
+        let mut geometry = Self {
+            rect: Rect::from_origin_size(
+                point(element),
+                (number(element, "width"), number(element, "height")),
+            ),
+            relative: element.attr("relative") == Some("1"),
+            ..Self::default()
+        };
+        for child in element.elements() {
+            match (child.name.as_str(), child.attr("as")) {
+                ("mxPoint", Some("sourcePoint")) => geometry.source_point = Some(point(child)),
+                ("mxPoint", Some("targetPoint")) => geometry.target_point = Some(point(child)),
+                ("mxPoint", Some("offset")) => geometry.offset = point(child).to_vec2(),
+                ("Array", Some("points")) => {
+                    geometry.points = child
+                        .elements()
+                        .filter(|e| e.name == "mxPoint")
+                        .map(point)
+                        .collect();
+                }
+                _ => {}
+            }
+        }
+        geometry
+    }
+}
+
+/// The outline an edge end is clipped to.
+#[derive(Debug, Clone, Copy)]
+struct Terminal {
+    rect: Rect,
+    outline: Outline,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Outline {
+    Rect,
+    Ellipse,
+    Rhombus,
+}
+
+impl Terminal {
+    /// Where the ray from the center toward `toward` leaves the outline.
+    fn perimeter(&self, toward: Point) -> Point {
+        let center = self.rect.center();
+        let d = toward - center;
+        let (a, b) = (self.rect.width() / 2.0, self.rect.height() / 2.0);
+        if a <= 0.0 || b <= 0.0 {
+            return center;
+        }
+        let (u, v) = ((d.x / a).abs(), (d.y / b).abs());
+        let scale = match self.outline {
+            Outline::Rect => u.max(v),
+            Outline::Ellipse => u.hypot(v),
+            Outline::Rhombus => u + v,
+        };
+        if scale > 0.0 {
+            center + d / scale
+        } else {
+            center
+        }
+    }
+
+    /// A fixed connection point such as `exitX`/`exitY`, relative to the bounds.
+    fn fixed(&self, style: &Style, prefix: &str) -> Option<Point> {
+        let x = style.get(&format!("{prefix}X"))?.parse::<f64>().ok()?;
+        let y = style.get(&format!("{prefix}Y"))?.parse::<f64>().ok()?;
+        Some(Point::new(
+            self.rect.x0 + x * self.rect.width() + style.number(&format!("{prefix}Dx"), 0.0),
+            self.rect.y0 + y * self.rect.height() + style.number(&format!("{prefix}Dy"), 0.0),
+        ))
+    }
+}
+
+fn import_model(model: &Element) -> Vec<Shape> {
+    let mut cells = Vec::new();
+    if let Some(root) = model.elements().find(|e| e.name == "root") {
+        for element in root.elements() {
+            let cell = match element.name.as_str() {
+                "mxCell" => Some(element),
+                "UserObject" | "object" => element.elements().find(|e| e.name == "mxCell"),
+                _ => None,
+            };
+            if let Some(cell) = cell.and_then(|cell| read_cell(element, cell)) {
+                cells.push(cell);
+            }
+        }
+    }
+    Importer::new(cells).shapes()
+}
+
+fn read_cell<'a>(outer: &'a Element, cell: &'a Element) -> Option<Cell<'a>> {
+    Some(Cell {
+        id: outer.attr("id")?,
+        parent: cell.attr("parent"),
+        label: outer
+            .attr("label")
+            .or_else(|| cell.attr("value"))
+            .unwrap_or_default(),
+        style: Style::parse(cell.attr("style").unwrap_or_default()),
+        vertex: cell.attr("vertex") == Some("1"),
+        edge: cell.attr("edge") == Some("1"),
+        visible: cell.attr("visible") != Some("0"),
+        source: cell.attr("source"),
+        target: cell.attr("target"),
+        geometry: cell
+            .elements()
+            .find(|e| e.name == "mxGeometry")
+            .map(Geometry::read)
+            .unwrap_or_default(),
+    })
+}
+
+struct Importer<'a> {
+    cells: Vec<Cell<'a>>,
+    by_id: HashMap<&'a str, usize>,
+    children: HashMap<&'a str, Vec<usize>>,
+}
+
+impl<'a> Importer<'a> {
+    fn new(cells: Vec<Cell<'a>>) -> Self {
+        let mut by_id = HashMap::new();
+        let mut children: HashMap<&str, Vec<usize>> = HashMap::new();
+        for (i, cell) in cells.iter().enumerate() {
+            by_id.insert(cell.id, i);
+            if let Some(parent) = cell.parent {
+                children.entry(parent).or_default().push(i);
+            }
+        }
+        Self {
+            cells,
+            by_id,
+            children,
+        }
+    }
+
+    fn cell(&self, id: &str) -> Option<&Cell<'a>> {
+        self.by_id.get(id).map(|&i| &self.cells[i])
+    }
+
+    /// Shapes of the cells on visible layers, in document order.
+    fn shapes(&self) -> Vec<Shape> {
+        (0..self.cells.len())
+            .filter(|&i| {
+                let cell = &self.cells[i];
+                // Layers are the children of the root, which neither are vertices nor edges
+                cell.visible
+                    && match cell.parent.and_then(|p| self.cell(p)) {
+                        Some(parent) => !parent.vertex && !parent.edge && parent.visible,
+                        None => true,
+                    }
+            })
+            .filter_map(|i| self.shape(i, 0))
+            .collect()
+    }
+
+    /// Page position of the coordinate space of a cell's children.
+    fn origin(&self, id: &str, depth: usize) -> Point {
+        match self.cell(id) {
+            Some(cell) if cell.vertex && depth < MAX_DEPTH => {
+                self.parent_origin(cell, depth + 1) + cell.geometry.rect.origin().to_vec2()
+            }
+            _ => Point::ZERO,
+        }
+    }
+
+    fn parent_origin(&self, cell: &Cell, depth: usize) -> Point {
+        cell.parent
+            .map_or(Point::ZERO, |parent| self.origin(parent, depth))
+    }
+
+    /// Page bounds of a vertex.
+    fn bounds(&self, cell: &Cell) -> Rect {
+        cell.geometry.rect + self.parent_origin(cell, 0).to_vec2()
+    }
+
+    fn shape(&self, index: usize, depth: usize) -> Option<Shape> {
+        let cell = &self.cells[index];
+        if !cell.visible || depth > MAX_DEPTH {
+            return None;
+        }
+        if cell.edge {
+            return self.edge(cell);
+        }
+        // Relative vertices are edge labels, read with their edge
+        if !cell.vertex || cell.geometry.relative {
+            return None;
+        }
+        let own = self.vertex(cell);
+        let children: Vec<Shape> = self
+            .children
+            .get(cell.id)
+            .into_iter()
+            .flatten()
+            .filter_map(|&child| self.shape(child, depth + 1))
+            .collect();
+        if children.is_empty() {
+            return own;
+        }
+        let mut members: Vec<Shape> = own.into_iter().collect();
+        members.extend(children);
+        Some(Shape::Group(Group::new(members)))
+    }
+
+    fn vertex(&self, cell: &Cell) -> Option<Shape> {
+        let rect = self.bounds(cell);
+        let style = &cell.style;
+        let kind = style.kind();
+        let label = label_text(cell.label, style.flag("html"));
+        let opacity = opacity(style);
+        if kind == "group" {
+            return None;
+        }
+        if let Some(latex) = math_label(&label) {
+            let mut math = Math::new(rect.origin(), latex.to_string());
+            math.font_size = style.number("fontSize", DEFAULT_FONT_SIZE);
+            math.style.stroke_color = font_color(style);
+            math.style.opacity = opacity;
+            return Some(Shape::Math(math));
+        }
+        let no_paint =
+            style.get("strokeColor") == Some("none") && style.get("fillColor") == Some("none");
+        if matches!(kind, "text" | "edgeLabel") || no_paint {
+            return (!label.is_empty())
+                .then(|| Shape::Text(text(&label, rect, style, kind, opacity)));
+        }
+
+        let rotation = style.number("rotation", 0.0).to_radians();
+        let center = rect.center();
+        let mut shape = match kind {
+            "image" => {
+                let url = style.get("image").unwrap_or_default();
+                // draw.io leaves out `;base64`, which would end the style entry
+                let url = match url.split_once(',') {
+                    Some((header, data)) if !header.ends_with(";base64") => {
+                        format!("{header};base64,{data}")
+                    }
+                    _ => url.to_string(),
+                };
+                let mut image = image_from_data_url(&url, rect)?;
+                image.rotation = rotation;
+                image.style.opacity = opacity;
+                Shape::Image(image)
+            }
+            "ellipse" | "doubleEllipse" => {
+                let mut shape = ellipse(rect);
+                shape.rotation = rotation;
+                Shape::Ellipse(shape)
+            }
+            "line" => {
+                let mut line =
+                    Line::new(Point::new(rect.x0, center.y), Point::new(rect.x1, center.y));
+                line.stroke_style = stroke_style(style);
+                Shape::Line(line)
+            }
+            _ => match outline(kind, style, rect) {
+                Some(points) => polygon(&points),
+                None => {
+                    let mut shape = rectangle(rect, corner_radius(style, rect));
+                    shape.rotation = rotation;
+                    Shape::Rectangle(shape)
+                }
+            },
+        };
+        if !matches!(shape, Shape::Image(_)) {
+            let fill = (kind != "line").then(SerializableColor::white);
+            *shape.style_mut() = shape_style(style, fill);
+        }
+        if matches!(shape, Shape::Line(_)) && rotation != 0.0 {
+            shape.transform(Affine::rotate_about(rotation, center));
+        }
+        if label.is_empty() {
+            return Some(shape);
+        }
+        let text = text(&label, rect, style, kind, opacity);
+        Some(Shape::Group(Group::new(vec![shape, Shape::Text(text)])))
+    }
+
+    fn edge(&self, cell: &Cell) -> Option<Shape> {
+        let style = &cell.style;
+        let geometry = &cell.geometry;
+        let origin = self.parent_origin(cell, 0).to_vec2();
+        let waypoints: Vec<Point> = geometry.points.iter().map(|&p| p + origin).collect();
+        let source = cell.source.and_then(|id| self.terminal(id));
+        let target = cell.target.and_then(|id| self.terminal(id));
+        let source_fixed = source.and_then(|t| t.fixed(style, "exit"));
+        let target_fixed = target.and_then(|t| t.fixed(style, "entry"));
+
+        // What each end aims at, before clipping to the terminal
+        let start_ref = source_fixed
+            .or(source.map(|t| t.rect.center()))
+            .or(geometry.source_point.map(|p| p + origin))?;
+        let end_ref = target_fixed
+            .or(target.map(|t| t.rect.center()))
+            .or(geometry.target_point.map(|p| p + origin))?;
+        let start = match (source, source_fixed) {
+            (Some(terminal), None) => {
+                terminal.perimeter(waypoints.first().copied().unwrap_or(end_ref))
+            }
+            _ => start_ref,
+        };
+        let end = match (target, target_fixed) {
+            (Some(terminal), None) => {
+                terminal.perimeter(waypoints.last().copied().unwrap_or(start_ref))
+            }
+            _ => end_ref,
+        };
+
+        let mut points = vec![start];
+        points.extend(waypoints);
+        points.push(end);
+        let edge_style = style.get("edgeStyle").unwrap_or_default();
+        let path_style = if ["orthogonal", "elbow", "entityRelation", "isometric"]
+            .iter()
+            .any(|name| edge_style.contains(name))
+        {
+            PathStyle::Angular
+        } else if style.flag("curved") {
+            PathStyle::Flowing
+        } else {
+            PathStyle::Direct
+        };
+
+        // Labels are placed along the edge as routed in the file, before any reversal
+        let mut labels = Vec::new();
+        let label = label_text(cell.label, style.flag("html"));
+        if !label.is_empty() {
+            labels.push(edge_label(&label, &points, geometry, style));
+        }
+        for &child in self.children.get(cell.id).into_iter().flatten() {
+            let child = &self.cells[child];
+            let label = label_text(child.label, child.style.flag("html"));
+            if child.vertex && child.visible && !label.is_empty() {
+                labels.push(edge_label(&label, &points, &child.geometry, &child.style));
+            }
+        }
+
+        let head = |key: &str, default: &str| style.get(key).unwrap_or(default) != "none";
+        let (start_head, end_head) = (head("startArrow", "none"), head("endArrow", "classic"));
+        let mut shape = if start_head || end_head {
+            if !end_head {
+                points.reverse();
+            }
+            let mut arrow = Arrow::from_points(points, path_style);
+            arrow.stroke_style = stroke_style(style);
+            Shape::Arrow(arrow)
+        } else {
+            let mut line = Line::from_points(points, path_style);
+            line.stroke_style = stroke_style(style);
+            Shape::Line(line)
+        };
+        *shape.style_mut() = shape_style(style, None);
+        if labels.is_empty() {
+            return Some(shape);
+        }
+        let mut members = vec![shape];
+        members.extend(labels.into_iter().map(Shape::Text));
+        Some(Shape::Group(Group::new(members)))
+    }
+
+    fn terminal(&self, id: &str) -> Option<Terminal> {
+        let cell = self.cell(id).filter(|cell| cell.vertex)?;
+        let outline = match cell.style.kind() {
+            "ellipse" | "doubleEllipse" => Outline::Ellipse,
+            "rhombus" | "mxgraph.flowchart.decision" => Outline::Rhombus,
+            _ => Outline::Rect,
+        };
+        Some(Terminal {
+            rect: self.bounds(cell),
+            outline,
+        })
+    }
+}
+
+/// Outline of a polygonal vertex, or None for a rectangle.
+fn outline(kind: &str, style: &Style, rect: Rect) -> Option<Vec<Point>> {
+    let (x0, y0, x1, y1) = (rect.x0, rect.y0, rect.x1, rect.y1);
+    let c = rect.center();
+    let points = match kind {
+        "rhombus" | "mxgraph.flowchart.decision" => vec![
+            Point::new(c.x, y0),
+            Point::new(x1, c.y),
+            Point::new(c.x, y1),
+            Point::new(x0, c.y),
+        ],
+        "triangle" => match style.get("direction").unwrap_or("east") {
+            "north" => vec![Point::new(c.x, y0), Point::new(x1, y1), Point::new(x0, y1)],
+            "south" => vec![Point::new(x0, y0), Point::new(x1, y0), Point::new(c.x, y1)],
+            "west" => vec![Point::new(x1, y0), Point::new(x1, y1), Point::new(x0, c.y)],
+            _ => vec![Point::new(x0, y0), Point::new(x1, c.y), Point::new(x0, y1)],
+        },
+        "hexagon" => {
+            let inset = rect.width() * style.number("size", 0.25).clamp(0.0, 0.5);
+            vec![
+                Point::new(x0 + inset, y0),
+                Point::new(x1 - inset, y0),
+                Point::new(x1, c.y),
+                Point::new(x1 - inset, y1),
+                Point::new(x0 + inset, y1),
+                Point::new(x0, c.y),
+            ]
+        }
+        _ => return None,
+    };
+    Some(points)
+}
+
+/// Corner radius of a `rounded=1` rectangle, following mxGraph's `arcSize` rules.
+fn corner_radius(style: &Style, rect: Rect) -> f64 {
+    if !style.flag("rounded") {
+        return 0.0;
+    }
+    let min_side = rect.width().min(rect.height());
+    if style.flag("absoluteArcSize") {
+        (style.number("arcSize", 20.0) / 2.0).min(min_side / 2.0)
+    } else {
+        min_side * style.number("arcSize", 15.0) / 100.0
+    }
+}
+
+fn shape_style(style: &Style, fill: Option<SerializableColor>) -> ShapeStyle {
+    let sketch = style.flag("sketch");
+    ShapeStyle {
+        stroke_color: style
+            .color("strokeColor", Some(SerializableColor::black()))
+            .unwrap_or_else(SerializableColor::transparent),
+        stroke_width: style.number("strokeWidth", 1.0),
+        fill_color: style.color("fillColor", fill),
+        fill_pattern: if sketch {
+            match style.get("fillStyle").unwrap_or("auto") {
+                "solid" => FillPattern::Solid,
+                "zigzag" => FillPattern::ZigZag,
+                "cross-hatch" => FillPattern::CrossHatch,
+                "dots" => FillPattern::Dots,
+                "dashed" => FillPattern::Dashed,
+                "zigzag-line" => FillPattern::ZigZagLine,
+                _ => FillPattern::Hachure,
+            }
+        } else {
+            FillPattern::Solid
+        },
+        sloppiness: if sketch {
+            Sloppiness::Artist
+        } else {
+            Sloppiness::Architect
+        },
+        opacity: opacity(style),
+        ..ShapeStyle::default()
+    }
+}
+
+fn opacity(style: &Style) -> f64 {
+    (style.number("opacity", 100.0) / 100.0).clamp(0.0, 1.0)
+}
+
+fn stroke_style(style: &Style) -> StrokeStyle {
+    if !style.flag("dashed") {
+        return StrokeStyle::Solid;
+    }
+    // Dotted patterns have dashes no longer than the stroke is wide
+    let dash = style
+        .get("dashPattern")
+        .and_then(|pattern| pattern.split_whitespace().next())
+        .and_then(|dash| dash.parse::<f64>().ok());
+    match dash {
+        Some(dash) if dash <= 1.0 => StrokeStyle::Dotted,
+        _ => StrokeStyle::Dashed,
+    }
+}
+
+fn font_color(style: &Style) -> SerializableColor {
+    style
+        .color("fontColor", Some(SerializableColor::black()))
+        .unwrap_or_else(SerializableColor::transparent)
+}
+
+/// Map a CSS font family list to the closest font.
+fn font_family(family: Option<&str>) -> FontFamily {
+    let family = family.unwrap_or_default().to_ascii_lowercase();
+    if family.contains("architects") || family.contains("gelpen") {
+        FontFamily::GelPen
+    } else if family.contains("comic") || family.contains("vanilla") {
+        FontFamily::VanillaExtract
+    } else if ["times", "georgia", "garamond", "serif"]
+        .iter()
+        .any(|name| family.contains(name))
+        && !family.contains("sans")
+    {
+        FontFamily::GelPenSerif
+    } else {
+        FontFamily::NotoSans
+    }
+}
+
+/// A label placed inside `rect` by the style's alignment.
+fn text(content: &str, rect: Rect, style: &Style, kind: &str, opacity: f64) -> Text {
+    // fontStyle is a bit set; bit 0 is bold
+    let bold = style.number("fontStyle", 0.0) as u32 & 1 == 1;
+    let mut text = Text::new(Point::ZERO, content.to_string())
+        .with_font_size(style.number("fontSize", DEFAULT_FONT_SIZE))
+        .with_font_family(font_family(style.get("fontFamily")))
+        .with_font_weight(if bold {
+            FontWeight::Heavy
+        } else {
+            FontWeight::Regular
+        });
+    let size = text.bounds().size();
+    let spacing = style.number("spacing", DEFAULT_SPACING);
+
+    // Labels can sit outside the shape, e.g. below icons
+    let mut area = rect;
+    match style.get("labelPosition") {
+        Some("left") => area = area - Vec2::new(rect.width(), 0.0),
+        Some("right") => area = area + Vec2::new(rect.width(), 0.0),
+        _ => {}
+    }
+    match style.get("verticalLabelPosition") {
+        Some("top") => area = area - Vec2::new(0.0, rect.height()),
+        Some("bottom") => area = area + Vec2::new(0.0, rect.height()),
+        _ => {}
+    }
+    let x = match style.get("align") {
+        Some("left") => area.x0 + spacing,
+        Some("right") => area.x1 - size.width - spacing,
+        _ => area.center().x - size.width / 2.0,
+    };
+    let vertical_default = if kind == "swimlane" { "top" } else { "middle" };
+    let y = match style.get("verticalAlign").unwrap_or(vertical_default) {
+        "top" => area.y0 + spacing,
+        "bottom" => area.y1 - size.height - spacing,
+        _ => area.center().y - size.height / 2.0,
+    };
+    text.position = Point::new(x, y);
+    text.style.stroke_color = font_color(style);
+    text.style.opacity = opacity;
+    text
+}
+
+/// A label centered on the edge, at the geometry's relative position and offset.
+fn edge_label(content: &str, points: &[Point], geometry: &Geometry, style: &Style) -> Text {
+    // x runs from -1 at the source to 1 at the target
+    let t = ((geometry.rect.x0 + 1.0) / 2.0).clamp(0.0, 1.0);
+    let center = point_along(points, t) + geometry.offset;
+    let mut text = text(
+        content,
+        Rect::from_center_size(center, (0.0, 0.0)),
+        style,
+        "edgeLabel",
+        opacity(style),
+    );
+    let size = text.bounds().size();
+    text.position = center - size.to_vec2() / 2.0;
+    text
+}
+
+/// Point at fraction `t` of a polyline's length.
+fn point_along(points: &[Point], t: f64) -> Point {
+    let total: f64 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+    let mut remaining = total * t;
+    for w in points.windows(2) {
+        let length = w[0].distance(w[1]);
+        if length >= remaining && length > 0.0 {
+            return w[0].lerp(w[1], remaining / length);
+        }
+        remaining -= length;
+    }
+    points.last().copied().unwrap_or(Point::ZERO)
+}
+
+/// Plain text of a label, from HTML when `html=1`.
+fn label_text(value: &str, html: bool) -> String {
+    if !html {
+        return value.trim().to_string();
+    }
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let Some(len) = rest[start..].find('>') else {
+            rest = &rest[start..];
+            break;
+        };
+        let tag = rest[start + 1..start + len]
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        // Block elements and line breaks start a new line
+        if matches!(tag.as_str(), "br" | "div" | "p" | "li" | "tr") && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    decode_entities(&out)
+        .replace('\u{a0}', " ")
+        .trim()
+        .to_string()
+}
+
+/// LaTeX source of a `$$…$$` label.
+fn math_label(label: &str) -> Option<&str> {
+    let latex = label.strip_prefix("$$")?.strip_suffix("$$")?.trim();
+    (!latex.is_empty()).then_some(latex)
+}
+
+// ---------------------------------------------------------------------------
+// Export
+// ---------------------------------------------------------------------------
+
+/// An exported cell.
+struct ExportCell {
+    id: String,
+    value: String,
+    style: Vec<String>,
+    edge: bool,
+    parent: String,
+    source: Option<String>,
+    target: Option<String>,
+    /// Content of the `mxGeometry` element.
+    geometry: Geometry,
+}
+
+/// Builds the cell list, then connects edges to the vertices they touch.
+#[derive(Default)]
+struct Exporter {
+    cells: Vec<ExportCell>,
+    /// Cells edges may connect to, with their page bounds.
+    terminals: Vec<(usize, Rect)>,
+    /// Edge cells with their start and end points.
+    edges: Vec<(usize, Point, Point)>,
+    /// Whether any label is LaTeX.
+    math: bool,
+}
+
+impl Exporter {
+    /// Export a shape into `parent`, whose children are placed relative to `origin`.
+    fn shape(&mut self, shape: &Shape, parent: &str, origin: Point) {
+        let Shape::Group(group) = shape else {
+            self.leaf(shape, parent, origin, None);
+            return;
+        };
+        if let Some((container, Shape::Text(text))) = label_pair(group) {
+            self.leaf(container, parent, origin, Some(text));
+            return;
+        }
+        let bounds = shape.bounds();
+        let id = shape.id().to_string();
+        self.cells.push(ExportCell {
+            id: id.clone(),
+            value: String::new(),
+            style: vec!["group".to_string()],
+            edge: false,
+            parent: parent.to_string(),
+            source: None,
+            target: None,
+            geometry: Geometry {
+                rect: bounds - origin.to_vec2(),
+                ..Geometry::default()
+            },
+        });
+        for child in &group.children {
+            self.shape(child, &id, bounds.origin());
+        }
+    }
+
+    /// Export a non-group shape, with an optional label.
+    fn leaf(&mut self, shape: &Shape, parent: &str, origin: Point, label: Option<&Text>) {
+        let index = self.cells.len();
+        let offset = origin.to_vec2();
+        let mut style = Vec::new();
+        let mut geometry = Geometry::default();
+        let mut edge = false;
+        let mut value = String::new();
+        match shape {
+            Shape::Rectangle(rect) => {
+                if rect.corner_radius > 0.0 {
+                    style.push("rounded=1".to_string());
+                    style.push("absoluteArcSize=1".to_string());
+                    style.push(format!("arcSize={}", number(rect.corner_radius * 2.0)));
+                } else {
+                    style.push("rounded=0".to_string());
+                }
+                push_rotation(&mut style, rect.rotation);
+                geometry.rect = Rect::from_origin_size(rect.position, (rect.width, rect.height));
+            }
+            Shape::Ellipse(shape) => {
+                style.push("ellipse".to_string());
+                push_rotation(&mut style, shape.rotation);
+                geometry.rect = Rect::from_center_size(
+                    shape.center,
+                    (shape.radius_x * 2.0, shape.radius_y * 2.0),
+                );
+            }
+            Shape::Line(line) => match diamond_bounds(line) {
+                Some(bounds) => {
+                    style.push("rhombus".to_string());
+                    geometry.rect = bounds;
+                }
+                None => {
+                    edge = true;
+                    style.push("endArrow=none".to_string());
+                    push_path_style(&mut style, line.path_style, line.stroke_style);
+                    geometry = polyline(&line.all_points());
+                }
+            },
+            Shape::Arrow(arrow) => {
+                edge = true;
+                style.push("endArrow=classic".to_string());
+                style.push("startArrow=none".to_string());
+                push_path_style(&mut style, arrow.path_style, arrow.stroke_style);
+                geometry = polyline(&arrow.all_points());
+                self.edges.push((index, arrow.start, arrow.end));
+            }
+            Shape::Freehand(freehand) => {
+                edge = true;
+                style.push("endArrow=none".to_string());
+                push_path_style(&mut style, PathStyle::Flowing, StrokeStyle::Solid);
+                geometry = polyline(&freehand.points);
+            }
+            Shape::Text(text) => {
+                style.extend(
+                    ["text", "align=left", "verticalAlign=top", "spacing=0"].map(String::from),
+                );
+                push_rotation(&mut style, text.rotation);
+                geometry.rect = shape.bounds();
+            }
+            Shape::Math(math) => {
+                self.math = true;
+                style.extend(
+                    ["text", "align=left", "verticalAlign=top", "spacing=0"].map(String::from),
+                );
+                push_rotation(&mut style, math.rotation);
+                style.push(format!("fontSize={}", number(math.font_size)));
+                style.push(format!(
+                    "fontColor={}",
+                    drawio_color(math.style.stroke_color)
+                ));
+                value = format!("$${}$$", math.latex);
+                geometry.rect = shape.bounds();
+            }
+            Shape::Image(image) => {
+                style.push("shape=image".to_string());
+                style.push("imageAspect=0".to_string());
+                // draw.io's form of a data URL, without `;base64`
+                style.push(format!(
+                    "image=data:{},{}",
+                    image.format.mime_type(),
+                    image.asset.to_base64()
+                ));
+                push_rotation(&mut style, image.rotation);
+                geometry.rect = image.as_rect();
+            }
+            Shape::Group(_) => unreachable!("groups are exported by Exporter::shape"),
+        }
+
+        let shape_style = shape.style();
+        match shape {
+            Shape::Text(_) | Shape::Math(_) => {
+                style.push("strokeColor=none".to_string());
+                style.push("fillColor=none".to_string());
+            }
+            Shape::Image(_) => {}
+            _ => push_shape_style(&mut style, shape_style, !edge),
+        }
+        if shape_style.opacity < 1.0 {
+            style.push(format!("opacity={}", (shape_style.opacity * 100.0).round()));
+        }
+        let text = match shape {
+            Shape::Text(text) => Some(text),
+            _ => label,
+        };
+        if let Some(text) = text {
+            value = text.content.clone();
+            push_font(&mut style, text);
+            if label.is_some() {
+                style.push("whiteSpace=wrap".to_string());
+            }
+        }
+        if !edge {
+            self.terminals.push((index, shape.rotated_bounds()));
+        }
+
+        geometry.rect = geometry.rect - offset;
+        geometry.points.iter_mut().for_each(|p| *p -= offset);
+        geometry.source_point = geometry.source_point.map(|p| p - offset);
+        geometry.target_point = geometry.target_point.map(|p| p - offset);
+        self.cells.push(ExportCell {
+            id: shape.id().to_string(),
+            value,
+            style,
+            edge,
+            parent: parent.to_string(),
+            source: None,
+            target: None,
+            geometry,
+        });
+    }
+
+    /// Connect each arrow end to the closest vertex within [`CONNECTION_TOLERANCE`],
+    /// at a fixed point so draw.io keeps the end where it is.
+    fn connect_edges(&mut self) {
+        for (edge, start, end) in std::mem::take(&mut self.edges) {
+            for (prefix, point) in [("exit", start), ("entry", end)] {
+                let target = self
+                    .terminals
+                    .iter()
+                    .map(|&(i, bounds)| (i, bounds, distance_to_rect(bounds, point)))
+                    .filter(|&(_, _, d)| d <= CONNECTION_TOLERANCE)
+                    .min_by(|a, b| a.2.total_cmp(&b.2));
+                let Some((target, bounds, _)) = target else {
+                    continue;
+                };
+                if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+                    continue;
+                }
+                let x = ((point.x - bounds.x0) / bounds.width()).clamp(0.0, 1.0);
+                let y = ((point.y - bounds.y0) / bounds.height()).clamp(0.0, 1.0);
+                let target_id = self.cells[target].id.clone();
+                let cell = &mut self.cells[edge];
+                cell.style.push(format!("{prefix}X={}", number(x)));
+                cell.style.push(format!("{prefix}Y={}", number(y)));
+                cell.style.push(format!("{prefix}Dx=0"));
+                cell.style.push(format!("{prefix}Dy=0"));
+                if prefix == "exit" {
+                    cell.source = Some(target_id);
+                } else {
+                    cell.target = Some(target_id);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        out.push_str("<mxfile host=\"drafft.ink\">\n");
+        out.push_str("  <diagram id=\"page-1\" name=\"Page-1\">\n");
+        let _ = writeln!(
+            out,
+            "    <mxGraphModel grid=\"1\" gridSize=\"10\" guides=\"1\" connect=\"1\" arrows=\"1\" page=\"0\" math=\"{}\">",
+            u8::from(self.math)
+        );
+        out.push_str("      <root>\n");
+        let _ = writeln!(out, "        <mxCell id=\"{ROOT_ID}\" />");
+        let _ = writeln!(
+            out,
+            "        <mxCell id=\"{LAYER_ID}\" parent=\"{ROOT_ID}\" />"
+        );
+        for cell in &self.cells {
+            let _ = write!(
+                out,
+                "        <mxCell id=\"{}\" value=\"{}\" style=\"{};\" {}=\"1\" parent=\"{}\"",
+                escape(&cell.id),
+                escape(&cell.value),
+                escape(&cell.style.join(";")),
+                if cell.edge { "edge" } else { "vertex" },
+                escape(&cell.parent),
+            );
+            if let Some(source) = &cell.source {
+                let _ = write!(out, " source=\"{}\"", escape(source));
+            }
+            if let Some(target) = &cell.target {
+                let _ = write!(out, " target=\"{}\"", escape(target));
+            }
+            out.push_str(">\n");
+            write_geometry(&mut out, &cell.geometry, cell.edge);
+            out.push_str("        </mxCell>\n");
+        }
+        out.push_str("      </root>\n");
+        out.push_str("    </mxGraphModel>\n");
+        out.push_str("  </diagram>\n");
+        out.push_str("</mxfile>\n");
+        out
+    }
+}
+
+fn write_geometry(out: &mut String, geometry: &Geometry, edge: bool) {
+    let indent = "          ";
+    if edge {
+        let _ = writeln!(out, "{indent}<mxGeometry relative=\"1\" as=\"geometry\">");
+        for (point, name) in [
+            (geometry.source_point, "sourcePoint"),
+            (geometry.target_point, "targetPoint"),
+        ] {
+            if let Some(point) = point {
+                let _ = writeln!(
+                    out,
+                    "{indent}  <mxPoint x=\"{}\" y=\"{}\" as=\"{name}\" />",
+                    number(point.x),
+                    number(point.y)
+                );
+            }
+        }
+        if !geometry.points.is_empty() {
+            let _ = writeln!(out, "{indent}  <Array as=\"points\">");
+            for point in &geometry.points {
+                let _ = writeln!(
+                    out,
+                    "{indent}    <mxPoint x=\"{}\" y=\"{}\" />",
+                    number(point.x),
+                    number(point.y)
+                );
+            }
+            let _ = writeln!(out, "{indent}  </Array>");
+        }
+        let _ = writeln!(out, "{indent}</mxGeometry>");
+    } else {
+        let rect = geometry.rect;
+        let _ = writeln!(
+            out,
+            "{indent}<mxGeometry x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" as=\"geometry\" />",
+            number(rect.x0),
+            number(rect.y0),
+            number(rect.width()),
+            number(rect.height())
+        );
+    }
+}
+
+/// Edge geometry through a polyline's points.
+fn polyline(points: &[Point]) -> Geometry {
+    let (Some(&first), Some(&last)) = (points.first(), points.last()) else {
+        return Geometry::default();
+    };
+    Geometry {
+        source_point: Some(first),
+        target_point: Some(last),
+        points: points.get(1..points.len() - 1).unwrap_or_default().to_vec(),
+        ..Geometry::default()
+    }
+}
+
+fn push_rotation(style: &mut Vec<String>, rotation: f64) {
+    if rotation != 0.0 {
+        style.push(format!("rotation={}", number(rotation.to_degrees())));
+    }
+}
+
+fn push_path_style(style: &mut Vec<String>, path_style: PathStyle, stroke_style: StrokeStyle) {
+    match path_style {
+        PathStyle::Direct => style.push("rounded=0".to_string()),
+        PathStyle::Flowing => style.push("curved=1".to_string()),
+        PathStyle::Angular => style.push("edgeStyle=orthogonalEdgeStyle".to_string()),
+    }
+    match stroke_style {
+        StrokeStyle::Solid => {}
+        StrokeStyle::Dashed => style.push("dashed=1".to_string()),
+        StrokeStyle::Dotted => {
+            style.push("dashed=1".to_string());
+            style.push("dashPattern=1 4".to_string());
+        }
+    }
+}
+
+fn push_shape_style(style: &mut Vec<String>, shape_style: &ShapeStyle, fill: bool) {
+    style.push(format!(
+        "strokeColor={}",
+        drawio_color(shape_style.stroke_color)
+    ));
+    style.push(format!("strokeWidth={}", number(shape_style.stroke_width)));
+    if fill {
+        let fill = shape_style
+            .fill_color
+            .map_or_else(|| "none".to_string(), drawio_color);
+        style.push(format!("fillColor={fill}"));
+    }
+    if shape_style.sloppiness != Sloppiness::Architect {
+        style.push("sketch=1".to_string());
+        let fill_style = match shape_style.fill_pattern {
+            FillPattern::Solid => "solid",
+            FillPattern::Hachure => "hachure",
+            FillPattern::ZigZag => "zigzag",
+            FillPattern::CrossHatch => "cross-hatch",
+            FillPattern::Dots => "dots",
+            FillPattern::Dashed => "dashed",
+            FillPattern::ZigZagLine => "zigzag-line",
+        };
+        style.push(format!("fillStyle={fill_style}"));
+    }
+}
+
+fn push_font(style: &mut Vec<String>, text: &Text) {
+    style.push(format!("fontSize={}", number(text.font_size)));
+    style.push(format!(
+        "fontColor={}",
+        drawio_color(text.style.stroke_color)
+    ));
+    let family = match text.font_family {
+        FontFamily::GelPen => Some("Architects Daughter"),
+        FontFamily::GelPenSerif => Some("Georgia"),
+        FontFamily::VanillaExtract => Some("Comic Sans MS"),
+        FontFamily::NotoSans => None,
+    };
+    if let Some(family) = family {
+        style.push(format!("fontFamily={family}"));
+    }
+    if text.font_weight == FontWeight::Heavy {
+        style.push("fontStyle=1".to_string());
+    }
+}
+
+/// A draw.io color. draw.io colors are opaque (translucency goes in `opacity`), and
+/// transparent ones are `none`.
+fn drawio_color(color: SerializableColor) -> String {
+    color_hex(SerializableColor { a: 255, ..color })
+        .filter(|_| color.a > 0)
+        .unwrap_or_else(|| "none".to_string())
+}
+
+/// A coordinate rounded to two decimals, without trailing zeros.
+fn number(value: f64) -> String {
+    let rounded = (value * 100.0).round() / 100.0;
+    if rounded == 0.0 {
+        "0".to_string()
+    } else {
+        rounded.to_string()
+    }
+}
+
+/// Escape text for an XML attribute value.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#xa;"),
+            '\t' => out.push_str("&#x9;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mermaid::centered_text;
+    use crate::shapes::Ellipse;
+
+    fn model(cells: &str) -> String {
+        format!(
+            r#"<mxGraphModel><root><mxCell id="0"/><mxCell id="1" parent="0"/>{cells}</root></mxGraphModel>"#
+        )
+    }
+
+    fn near(a: Point, b: Point) -> bool {
+        a.distance(b) < 0.01
+    }
+
+    #[test]
+    fn test_detect_and_errors() {
+        assert!(is_drawio(
+            r#"<?xml version="1.0"?><mxfile host="x"></mxfile>"#
+        ));
+        assert!(is_drawio("<mxGraphModel><root/></mxGraphModel>"));
+        assert!(!is_drawio("<mxfiles/>"));
+        assert!(!is_drawio(r#"<svg xmlns="http://www.w3.org/2000/svg"/>"#));
+
+        assert_eq!(parse_drawio("<svg/>").unwrap_err(), DrawioError::NotDrawio);
+        assert!(matches!(
+            parse_drawio("<mxfile><diagram>").unwrap_err(),
+            DrawioError::Xml(_)
+        ));
+        assert!(matches!(
+            parse_drawio("<mxfile><diagram>not base64!</diagram></mxfile>").unwrap_err(),
+            DrawioError::Compressed(_)
+        ));
+        assert!(
+            parse_drawio("<mxfile><diagram/></mxfile>")
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_vertex_styles_and_labels() {
+        let source = model(concat!(
+            r#"<mxCell id="r" value="&lt;b&gt;Hello&lt;/b&gt;&lt;br&gt;World" "#,
+            r#"style="rounded=1;whiteSpace=wrap;html=1;fillColor=#dae8fc;strokeColor=#6c8ebf;" "#,
+            r#"vertex="1" parent="1"><mxGeometry width="120" height="60" as="geometry"/></mxCell>"#,
+            r#"<mxCell id="e" style="ellipse;fillColor=none;" vertex="1" parent="1">"#,
+            r#"<mxGeometry x="200" width="80" height="40" as="geometry"/></mxCell>"#,
+            r#"<mxCell id="d" style="rhombus;" vertex="1" parent="1">"#,
+            r#"<mxGeometry x="300" width="80" height="80" as="geometry"/></mxCell>"#,
+            r#"<UserObject id="t" label="Note"><mxCell style="text;strokeColor=none;fillColor=none;fontSize=18;fontStyle=1;" "#,
+            r#"vertex="1" parent="1"><mxGeometry y="100" width="60" height="30" as="geometry"/></mxCell></UserObject>"#,
+            r#"<mxCell id="g" style="group" vertex="1" parent="1">"#,
+            r#"<mxGeometry x="500" y="500" width="100" height="100" as="geometry"/></mxCell>"#,
+            r#"<mxCell id="c" vertex="1" parent="g"><mxGeometry x="10" y="20" width="30" height="30" as="geometry"/></mxCell>"#,
+        ));
+        let shapes = parse_drawio(&source).unwrap();
+        assert_eq!(shapes.len(), 5);
+
+        let Shape::Group(labeled) = &shapes[0] else {
+            panic!("expected a labeled group");
+        };
+        let (Shape::Rectangle(rect), Shape::Text(label)) =
+            (&labeled.children[0], &labeled.children[1])
+        else {
+            panic!("expected a rectangle and its label");
+        };
+        assert_eq!(rect.corner_radius, 9.0);
+        assert_eq!(
+            rect.style.fill_color,
+            Some(SerializableColor::new(0xda, 0xe8, 0xfc, 255))
+        );
+        assert_eq!(
+            rect.style.stroke_color,
+            SerializableColor::new(0x6c, 0x8e, 0xbf, 255)
+        );
+        assert_eq!(label.content, "Hello\nWorld");
+        assert!(rect.bounds().contains(label.bounds().center()));
+
+        let Shape::Ellipse(ellipse) = &shapes[1] else {
+            panic!("expected an ellipse");
+        };
+        assert!(near(ellipse.center, Point::new(240.0, 20.0)));
+        assert_eq!((ellipse.radius_x, ellipse.radius_y), (40.0, 20.0));
+        assert_eq!(ellipse.style.fill_color, None);
+
+        let Shape::Line(diamond) = &shapes[2] else {
+            panic!("expected a diamond");
+        };
+        assert_eq!(
+            diamond_bounds(diamond),
+            Some(Rect::new(300.0, 0.0, 380.0, 80.0))
+        );
+
+        let Shape::Text(text) = &shapes[3] else {
+            panic!("expected text");
+        };
+        assert_eq!(text.content, "Note");
+        assert_eq!(text.font_size, 18.0);
+        assert_eq!(text.font_weight, FontWeight::Heavy);
+
+        // Children of groups are placed relative to them
+        let Shape::Group(group) = &shapes[4] else {
+            panic!("expected a group");
+        };
+        let [Shape::Rectangle(child)] = group.children.as_slice() else {
+            panic!("expected one child");
+        };
+        assert_eq!(child.position, Point::new(510.0, 520.0));
+        assert_eq!(child.style.fill_color, Some(SerializableColor::white()));
+    }
+
+    #[test]
+    fn test_edges_with_waypoints_and_labels() {
+        let source = model(concat!(
+            r#"<mxCell id="a" vertex="1" parent="1"><mxGeometry width="100" height="50" as="geometry"/></mxCell>"#,
+            r#"<mxCell id="b" style="ellipse;" vertex="1" parent="1">"#,
+            r#"<mxGeometry x="300" width="100" height="50" as="geometry"/></mxCell>"#,
+            r#"<mxCell id="e1" style="edgeStyle=orthogonalEdgeStyle;" edge="1" parent="1" source="a" target="b">"#,
+            r#"<mxGeometry relative="1" as="geometry"><Array as="points">"#,
+            r#"<mxPoint x="150" y="100"/><mxPoint x="250" y="100"/></Array></mxGeometry></mxCell>"#,
+            r#"<mxCell id="l" value="yes" style="edgeLabel;" vertex="1" parent="e1">"#,
+            r#"<mxGeometry x="-1" relative="1" as="geometry"><mxPoint as="offset"/></mxGeometry></mxCell>"#,
+            r#"<mxCell id="e2" style="endArrow=none;dashed=1;exitX=1;exitY=0.5;" edge="1" parent="1" source="a">"#,
+            r#"<mxGeometry relative="1" as="geometry"><mxPoint x="200" y="200" as="targetPoint"/></mxGeometry></mxCell>"#,
+            r#"<mxCell id="e3" style="startArrow=classic;endArrow=none;" edge="1" parent="1">"#,
+            r#"<mxGeometry relative="1" as="geometry"><mxPoint y="300" as="sourcePoint"/>"#,
+            r#"<mxPoint x="100" y="300" as="targetPoint"/></mxGeometry></mxCell>"#,
+        ));
+        let shapes = parse_drawio(&source).unwrap();
+        assert_eq!(shapes.len(), 5);
+
+        let Shape::Group(labeled) = &shapes[2] else {
+            panic!("expected a labeled edge");
+        };
+        let (Shape::Arrow(arrow), Shape::Text(label)) =
+            (&labeled.children[0], &labeled.children[1])
+        else {
+            panic!("expected an arrow and its label");
+        };
+        assert_eq!(arrow.path_style, PathStyle::Angular);
+        assert_eq!(
+            arrow.intermediate_points,
+            vec![Point::new(150.0, 100.0), Point::new(250.0, 100.0)]
+        );
+        // Ends sit on the outlines, aimed at the nearest waypoint
+        assert!(near(arrow.start, Point::new(50.0 + 100.0 / 3.0, 50.0)));
+        let toward = Vec2::new(-100.0, 75.0) / 13f64.sqrt();
+        assert!(near(arrow.end, Point::new(350.0, 25.0) + toward));
+        assert_eq!(label.content, "yes");
+        assert!(near(label.bounds().center(), arrow.start));
+
+        let Shape::Line(line) = &shapes[3] else {
+            panic!("expected a line");
+        };
+        assert_eq!(line.start, Point::new(100.0, 25.0));
+        assert_eq!(line.end, Point::new(200.0, 200.0));
+        assert_eq!(line.stroke_style, StrokeStyle::Dashed);
+
+        // Only a start arrowhead: the points are reversed
+        let Shape::Arrow(reversed) = &shapes[4] else {
+            panic!("expected an arrow");
+        };
+        assert_eq!(reversed.start, Point::new(100.0, 300.0));
+        assert_eq!(reversed.end, Point::new(0.0, 300.0));
+    }
+
+    #[test]
+    fn test_compressed_pages() {
+        // An ellipse labeled "Größe", deflated and base64 encoded by draw.io's scheme
+        let compressed = "jVFBDoMgEHzN3ik0pr1Kq6c+gugqJGsxSFv9fSlgTQ8mPUB2ZpjJ7gJCDnPt1KhvtkUCcQUhnbU+VcMskQg4My2IC3DOwgFe7aiHqLJRObz7fwwqGZ6KHpiY2oEUUBaf+1xh0ie/UNaD34xTAGVniKQl66Igug6Lpgl8jkTncd5tK1K5pxrtgN4t4clqSF2zJcE8BHuZ1utEnTKl0fQ6Zx4zp6aE+2/utoFQ5CWscFt21H7+4g0=";
+        let source = format!(
+            r#"<mxfile><diagram id="p1" name="One">{compressed}</diagram><diagram id="p2" name="Two">{}</diagram></mxfile>"#,
+            model(
+                r#"<mxCell id="r" vertex="1" parent="1"><mxGeometry width="50" height="50" as="geometry"/></mxCell>"#
+            )
+        );
+        let shapes = parse_drawio(&source).unwrap();
+        assert_eq!(shapes.len(), 2);
+
+        let Shape::Group(labeled) = &shapes[0] else {
+            panic!("expected a labeled ellipse");
+        };
+        let (Shape::Ellipse(ellipse), Shape::Text(label)) =
+            (&labeled.children[0], &labeled.children[1])
+        else {
+            panic!("expected an ellipse and its label");
+        };
+        assert_eq!(label.content, "Größe");
+        assert!(near(ellipse.center, Point::new(50.0, 40.0)));
+        assert_eq!(
+            ellipse.style.fill_color,
+            Some(SerializableColor::new(0xff, 0xe6, 0xcc, 255))
+        );
+
+        // The second page goes below the first
+        let Shape::Rectangle(rect) = &shapes[1] else {
+            panic!("expected a rectangle");
+        };
+        assert_eq!(rect.position, Point::new(0.0, 60.0 + PAGE_GAP));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut boxed = rectangle(Rect::new(0.0, 0.0, 100.0, 50.0), 8.0);
+        let fill = Some(SerializableColor::new(0xff, 0xf2, 0xcc, 255));
+        boxed.style.fill_color = fill;
+        let boxed = Shape::Rectangle(boxed);
+        let labeled = Shape::Group(Group::new(vec![
+            boxed.clone(),
+            Shape::Text(centered_text(Point::new(50.0, 25.0), "Start", 16.0)),
+        ]));
+        let diamond = polygon(&[
+            Point::new(250.0, 0.0),
+            Point::new(300.0, 50.0),
+            Point::new(250.0, 100.0),
+            Point::new(200.0, 50.0),
+        ]);
+        let mut arrow = Arrow::new(Point::new(100.0, 25.0), Point::new(200.0, 50.0));
+        arrow.intermediate_points = vec![Point::new(150.0, 25.0)];
+        let group = Shape::Group(Group::new(vec![
+            Shape::Ellipse(Ellipse::new(Point::new(500.0, 500.0), 20.0, 10.0)),
+            Shape::Ellipse(Ellipse::new(Point::new(560.0, 520.0), 20.0, 10.0)),
+        ]));
+        let text = Text::new(
+            Point::new(0.0, 300.0),
+            "Bold\n\"quoted\" & more".to_string(),
+        )
+        .with_font_weight(FontWeight::Heavy);
+        let shapes = vec![
+            labeled,
+            diamond.clone(),
+            Shape::Arrow(arrow),
+            group,
+            Shape::Text(text),
+        ];
+
+        let xml = export_drawio(&shapes);
+        assert!(is_drawio(&xml));
+        assert!(xml.contains(&format!(r#"source="{}""#, boxed.id())));
+        assert!(xml.contains(&format!(r#"target="{}""#, diamond.id())));
+
+        let imported = parse_drawio(&xml).unwrap();
+        assert_eq!(imported.len(), 5);
+        let Shape::Group(group) = &imported[0] else {
+            panic!("expected a labeled rectangle");
+        };
+        let (Shape::Rectangle(rect), Shape::Text(label)) = (&group.children[0], &group.children[1])
+        else {
+            panic!("expected a rectangle and its label");
+        };
+        assert_eq!(rect.corner_radius, 8.0);
+        assert_eq!(rect.style.fill_color, fill);
+        assert_eq!(label.content, "Start");
+        assert_eq!(label.font_size, 16.0);
+
+        let Shape::Line(line) = &imported[1] else {
+            panic!("expected a diamond");
+        };
+        assert_eq!(
+            diamond_bounds(line),
+            Some(Rect::new(200.0, 0.0, 300.0, 100.0))
+        );
+
+        let Shape::Arrow(arrow) = &imported[2] else {
+            panic!("expected an arrow");
+        };
+        assert!(near(arrow.start, Point::new(100.0, 25.0)));
+        assert!(near(arrow.end, Point::new(200.0, 50.0)));
+        assert_eq!(arrow.intermediate_points, vec![Point::new(150.0, 25.0)]);
+
+        let Shape::Group(group) = &imported[3] else {
+            panic!("expected a group");
+        };
+        let Shape::Ellipse(second) = &group.children[1] else {
+            panic!("expected an ellipse");
+        };
+        assert!(near(second.center, Point::new(560.0, 520.0)));
+
+        let Shape::Text(text) = &imported[4] else {
+            panic!("expected text");
+        };
+        assert_eq!(text.content, "Bold\n\"quoted\" & more");
+        assert_eq!(text.font_weight, FontWeight::Heavy);
+        assert!(near(text.position, Point::new(0.0, 300.0)));
+    }
+}
@@ -0,0 +1,1377 @@
+//! Excalidraw import and export.
+//!
+//! Reads `.excalidraw` files and `excalidraw/clipboard` payloads into shapes, and writes
+//! shapes back out in the same format. Excalidraw concepts without a DrafftInk
+//! counterpart are mapped onto the shape tree:
+//!
+//! - `groupIds` become nested groups, and groups become `groupIds` again.
+//! - Text bound to a container is grouped with it. On export, a group of exactly one
+//!   container and one text centered on it becomes bound text again.
+//! - Diamonds become closed four-point lines, which are exported as diamonds.
+//! - Frames become a group of the frame outline, its name and its children, and such
+//!   groups become frames again, with `frameId` set on their children.
+//! - Arrow bindings are not stored. On export, an arrow endpoint within
+//!   [`CONNECTION_TOLERANCE`] of a shape is bound to it.
+//! - Embedded `files` become image shapes, and images are embedded as `files`.
+//!
+//! Properties Excalidraw has no field for (font weight, the extra sloppiness level and
+//! fill patterns, LaTeX source) travel in the element's `customData`.
+
+use crate::json::{bool_field, f64_field, str_field};
+use crate::layout::{CONNECTION_TOLERANCE, distance_to_rect};
+use crate::shapes::{
+    Arrow, Ellipse, FillPattern, FontFamily, FontWeight, Freehand, Group, Image, ImageFormat, Line,
+    Math, PathStyle, Rectangle, SerializableColor, Shape, ShapeStyle, Sloppiness, StrokeStyle,
+    Text,
+};
+use crate::svg::{color_hex, parse_color};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use kurbo::{Affine, Point, Rect};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value, json};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while importing an Excalidraw scene.
+#[derive(Debug, Error, PartialEq)]
+pub enum ExcalidrawError {
+    #[error("Invalid JSON: {0}")]
+    Json(String),
+    #[error("Missing 'elements' array")]
+    MissingElements,
+}
+
+/// Result type for Excalidraw import.
+pub type ExcalidrawResult<T> = Result<T, ExcalidrawError>;
+
+/// Value of `type` in an Excalidraw clipboard payload.
+pub const CLIPBOARD_TYPE: &str = "excalidraw/clipboard";
+/// Value of `source` in exported files.
+const SOURCE: &str = "https://drafft.ink";
+/// Key of DrafftInk properties inside an element's `customData`.
+const CUSTOM_DATA_KEY: &str = "drafftink";
+/// Excalidraw's default line height, as a multiple of the font size.
+const LINE_HEIGHT: f64 = 1.25;
+/// Font size of frame names.
+const FRAME_LABEL_SIZE: f64 = 14.0;
+/// Color of frame outlines and names.
+const FRAME_COLOR: SerializableColor = SerializableColor {
+    r: 0xbb,
+    g: 0xbb,
+    b: 0xbb,
+    a: 255,
+};
+/// Distance within which a point matches a diamond vertex.
+const VERTEX_TOLERANCE: f64 = 0.5;
+
+/// Parse an Excalidraw file or clipboard payload into shapes, in z-order.
+pub fn parse_excalidraw(json: &str) -> ExcalidrawResult<Vec<Shape>> {
+    let data: Value =
+        serde_json::from_str(json).map_err(|e| ExcalidrawError::Json(e.to_string()))?;
+    let elements = data
+        .get("elements")
+        .and_then(Value::as_array)
+        .ok_or(ExcalidrawError::MissingElements)?;
+    let empty = Map::new();
+    let files = data
+        .get("files")
+        .and_then(Value::as_object)
+        .unwrap_or(&empty);
+
+    let live: Vec<&Value> = elements
+        .iter()
+        .filter(|e| !bool_field(e, "isDeleted"))
+        .collect();
+    let by_id: HashMap<&str, &Value> = live
+        .iter()
+        .filter_map(|e| Some((str_field(e, "id")?, *e)))
+        .collect();
+
+    let mut roots = Vec::new();
+    for elem in &live {
+        let path = group_path(elem, &by_id);
+        for shape in element_shapes(elem, files) {
+            insert_node(&mut roots, &path, shape);
+        }
+    }
+    Ok(roots
+        .into_iter()
+        .filter_map(|node| node.into_shape(&by_id))
+        .collect())
+}
+
+/// Check whether text is an Excalidraw clipboard payload.
+pub fn is_excalidraw_clipboard(text: &str) -> bool {
+    serde_json::from_str::<Value>(text)
+        .is_ok_and(|data| data.get("type").and_then(Value::as_str) == Some(CLIPBOARD_TYPE))
+}
+
+/// Export shapes as the contents of an `.excalidraw` file.
+pub fn export_excalidraw<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> String {
+    let (elements, files) = export_elements(shapes);
+    let scene = json!({
+        "type": "excalidraw",
+        "version": 2,
+        "source": SOURCE,
+        "elements": elements,
+        "appState": {
+            "gridSize": null,
+            "viewBackgroundColor": "#ffffff",
+        },
+        "files": files,
+    });
+    format!("{:#}", scene)
+}
+
+/// Export shapes as an Excalidraw clipboard payload.
+pub fn export_excalidraw_clipboard<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> String {
+    let (elements, files) = export_elements(shapes);
+    let payload = json!({
+        "type": CLIPBOARD_TYPE,
+        "elements": elements,
+        "files": files,
+    });
+    payload.to_string()
+}
+
+/// DrafftInk properties stored in `customData`.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Extras {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    fill_pattern: Option<FillPattern>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sloppiness: Option<Sloppiness>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    font_weight: Option<FontWeight>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latex: Option<String>,
+}
+
+impl Extras {
+    fn of(elem: &Value) -> Self {
+        elem.get("customData")
+            .and_then(|data| data.get(CUSTOM_DATA_KEY))
+            .and_then(|extras| serde_json::from_value(extras.clone()).ok())
+            .unwrap_or_default()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fill_pattern.is_none()
+            && self.sloppiness.is_none()
+            && self.font_weight.is_none()
+            && self.latex.is_none()
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Import
+// ---------------------------------------------------------------------------
+
+/// A shape or a group being assembled from `groupIds`.
+enum Node {
+    Shape(Shape),
+    Group(String, Vec<Node>),
+}
+
+impl Node {
+    /// Build the shape, marking groups keyed by a frame with the frame's name.
+    fn into_shape(self, by_id: &HashMap<&str, &Value>) -> Option<Shape> {
+        match self {
+            Node::Shape(shape) => Some(shape),
+            Node::Group(key, children) => {
+                let mut shapes: Vec<Shape> = children
+                    .into_iter()
+                    .filter_map(|child| child.into_shape(by_id))
+                    .collect();
+                let frame = key
+                    .strip_prefix("frame:")
+                    .and_then(|id| by_id.get(id))
+                    .map(|elem| frame_name(elem));
+                match (shapes.len(), frame) {
+                    (0, _) => None,
+                    (_, Some(name)) => Some(Shape::Group(Group::new(shapes).with_frame(name))),
+                    (1, None) => shapes.pop(),
+                    _ => Some(Shape::Group(Group::new(shapes))),
+                }
+            }
+        }
+    }
+}
+
+/// Insert a shape under the group keys in `path` (outermost first).
+fn insert_node(nodes: &mut Vec<Node>, path: &[String], shape: Shape) {
+    let Some((key, rest)) = path.split_first() else {
+        nodes.push(Node::Shape(shape));
+        return;
+    };
+    let existing = nodes.iter_mut().rev().find_map(|node| match node {
+        Node::Group(k, children) if k == key => Some(children),
+        _ => None,
+    });
+    match existing {
+        Some(children) => insert_node(children, rest, shape),
+        None => {
+            let mut children = Vec::new();
+            insert_node(&mut children, rest, shape);
+            nodes.push(Node::Group(key.clone(), children));
+        }
+    }
+}
+
+/// Group keys an element belongs to, outermost first: its frame, its `groupIds`, then
+/// the pairing of a container with its bound text.
+fn group_path(elem: &Value, by_id: &HashMap<&str, &Value>) -> Vec<String> {
+    let id = str_field(elem, "id").unwrap_or_default();
+    let container = str_field(elem, "containerId").and_then(|c| by_id.get(c).copied());
+    // Bound text follows its container's groups and frame
+    let owner = container.unwrap_or(elem);
+
+    let mut path = Vec::new();
+    if str_field(elem, "type") == Some("frame") || str_field(elem, "type") == Some("magicframe") {
+        path.push(format!("frame:{}", id));
+    } else if let Some(frame) = str_field(owner, "frameId").filter(|f| by_id.contains_key(f)) {
+        path.push(format!("frame:{}", frame));
+    }
+    if let Some(groups) = owner.get("groupIds").and_then(Value::as_array) {
+        path.extend(
+            groups
+                .iter()
+                .rev()
+                .filter_map(Value::as_str)
+                .map(|g| format!("group:{}", g)),
+        );
+    }
+    if let Some(container) = container {
+        path.push(format!(
+            "label:{}",
+            str_field(container, "id").unwrap_or_default()
+        ));
+    } else if has_bound_text(elem, by_id) {
+        path.push(format!("label:{}", id));
+    }
+    path
+}
+
+fn has_bound_text(elem: &Value, by_id: &HashMap<&str, &Value>) -> bool {
+    elem.get("boundElements")
+        .and_then(Value::as_array)
+        .is_some_and(|bound| {
+            bound.iter().any(|b| {
+                str_field(b, "type") == Some("text")
+                    && str_field(b, "id").is_some_and(|id| by_id.contains_key(id))
+            })
+        })
+}
+
+/// Convert one element into shapes (frames produce an outline and a name).
+fn element_shapes(elem: &Value, files: &Map<String, Value>) -> Vec<Shape> {
+    let extras = Extras::of(elem);
+    let style = import_style(elem, &extras);
+    let x = f64_field(elem, "x", 0.0);
+    let y = f64_field(elem, "y", 0.0);
+    let width = f64_field(elem, "width", 100.0);
+    let height = f64_field(elem, "height", 100.0);
+    let angle = f64_field(elem, "angle", 0.0);
+    let rect = Rect::new(x, y, x + width, y + height);
+
+    let shape = match str_field(elem, "type").unwrap_or_default() {
+        "rectangle" => {
+            let mut shape = Rectangle::new(rect.origin(), width, height);
+            shape.corner_radius = corner_radius(elem.get("roundness"), width.min(height));
+            shape.rotation = angle;
+            shape.style = style;
+            Shape::Rectangle(shape)
+        }
+        "diamond" => {
+            let center = rect.center();
+            let vertices = [
+                Point::new(center.x, rect.y0),
+                Point::new(rect.x1, center.y),
+                Point::new(center.x, rect.y1),
+                Point::new(rect.x0, center.y),
+                Point::new(center.x, rect.y0),
+            ];
+            let rotate = Affine::rotate_about(angle, center);
+            let mut shape = Line::from_points(
+                vertices.iter().map(|&p| rotate * p).collect(),
+                PathStyle::Direct,
+            );
+            shape.style = style;
+            Shape::Line(shape)
+        }
+        "ellipse" => {
+            let mut shape = Ellipse::new(rect.center(), width / 2.0, height / 2.0);
+            shape.rotation = angle;
+            shape.style = style;
+            Shape::Ellipse(shape)
+        }
+        "freedraw" => {
+            let points = linear_points(elem);
+            if points.is_empty() {
+                return Vec::new();
+            }
+            let pressures: Vec<f64> = elem
+                .get("pressures")
+                .and_then(Value::as_array)
+                .map(|p| p.iter().filter_map(Value::as_f64).collect())
+                .unwrap_or_default();
+            let simulated = elem
+                .get("simulatePressure")
+                .and_then(Value::as_bool)
+                .unwrap_or(true);
+            let mut shape = if !simulated && pressures.len() == points.len() {
+                Freehand::from_points_with_pressure(points, pressures)
+            } else {
+                Freehand::from_points(points)
+            };
+            shape.style = style;
+            Shape::Freehand(shape)
+        }
+        kind @ ("line" | "arrow") => {
+            let mut points = linear_points(elem);
+            if points.len() < 2 {
+                return Vec::new();
+            }
+            if bool_field(elem, "polygon") && points.first() != points.last() {
+                points.push(points[0]);
+            }
+            let path_style = if bool_field(elem, "elbowed") {
+                PathStyle::Angular
+            } else if elem.get("roundness").is_some_and(|r| !r.is_null()) {
+                PathStyle::Flowing
+            } else {
+                PathStyle::Direct
+            };
+            let stroke_style = match str_field(elem, "strokeStyle") {
+                Some("dashed") => StrokeStyle::Dashed,
+                Some("dotted") => StrokeStyle::Dotted,
+                _ => StrokeStyle::Solid,
+            };
+            // Our arrows have a single head at the end
+            let head = |key: &str| match elem.get(key) {
+                Some(value) => !value.is_null(),
+                None => key == "endArrowhead",
+            };
+            let (start_head, end_head) = if kind == "arrow" {
+                (head("startArrowhead"), head("endArrowhead"))
+            } else {
+                (false, false)
+            };
+            if end_head || start_head {
+                if !end_head {
+                    points.reverse();
+                }
+                let mut shape = Arrow::from_points(points, path_style);
+                shape.stroke_style = stroke_style;
+                shape.style = style;
+                Shape::Arrow(shape)
+            } else {
+                let mut shape = Line::from_points(points, path_style);
+                shape.stroke_style = stroke_style;
+                shape.style = style;
+                Shape::Line(shape)
+            }
+        }
+        "text" => {
+            let font_size = f64_field(elem, "fontSize", Text::DEFAULT_FONT_SIZE);
+            if let Some(latex) = extras.latex {
+                let mut shape = Math::new(rect.origin(), latex);
+                shape.font_size = font_size;
+                shape.rotation = angle;
+                shape.style = style;
+                Shape::Math(shape)
+            } else {
+                let content = str_field(elem, "text").unwrap_or_default().to_string();
+                let family = elem
+                    .get("fontFamily")
+                    .and_then(Value::as_u64)
+                    .map(font_family)
+                    .unwrap_or_default();
+                let mut shape = Text::new(rect.origin(), content)
+                    .with_font_size(font_size)
+                    .with_font_family(family)
+                    .with_font_weight(extras.font_weight.unwrap_or_default());
+                shape.rotation = angle;
+                shape.style = style;
+                Shape::Text(shape)
+            }
+        }
+        "image" => {
+            let Some(image) = str_field(elem, "fileId")
+                .and_then(|id| files.get(id))
+                .and_then(|file| str_field(file, "dataURL"))
+                .and_then(|url| image_from_data_url(url, rect))
+            else {
+                return Vec::new();
+            };
+            let mut image = image;
+            image.rotation = angle;
+            image.style.opacity = style.opacity;
+            Shape::Image(image)
+        }
+        "frame" | "magicframe" => {
+            return frame_shapes(rect, frame_name(elem));
+        }
+        _ => return Vec::new(),
+    };
+    vec![shape]
+}
+
+fn import_style(elem: &Value, extras: &Extras) -> ShapeStyle {
+    let stroke_color = str_field(elem, "strokeColor")
+        .and_then(parse_color)
+        .unwrap_or_else(SerializableColor::black);
+    let fill_color = match str_field(elem, "backgroundColor").unwrap_or("transparent") {
+        "transparent" => None,
+        color => Some(parse_color(color).unwrap_or_else(SerializableColor::black)),
+    };
+    let sloppiness = match elem.get("roughness").and_then(Value::as_f64).unwrap_or(1.0) {
+        r if r < 0.5 => Sloppiness::Architect,
+        r if r < 1.5 => Sloppiness::Artist,
+        _ => Sloppiness::Cartoonist,
+    };
+    let fill_pattern = match str_field(elem, "fillStyle").unwrap_or("solid") {
+        "hachure" => FillPattern::Hachure,
+        "cross-hatch" => FillPattern::CrossHatch,
+        "zigzag" => FillPattern::ZigZag,
+        _ => FillPattern::Solid,
+    };
+
+    ShapeStyle {
+        stroke_color,
+        stroke_width: f64_field(elem, "strokeWidth", 2.0),
+        fill_color,
+        fill_pattern: extras.fill_pattern.unwrap_or(fill_pattern),
+        sloppiness: extras.sloppiness.unwrap_or(sloppiness),
+        seed: elem.get("seed").and_then(Value::as_u64).unwrap_or(0) as u32,
+        // Excalidraw opacity is a percentage
+        opacity: (f64_field(elem, "opacity", 100.0) / 100.0).clamp(0.0, 1.0),
+    }
+}
+
+/// Corner radius for an Excalidraw `roundness`, following Excalidraw's own rules.
+fn corner_radius(roundness: Option<&Value>, min_side: f64) -> f64 {
+    let Some(roundness) = roundness.filter(|r| !r.is_null()) else {
+        return 0.0;
+    };
+    // Type 3 is the adaptive radius; types 1 and 2 are proportional
+    if roundness.get("type").and_then(Value::as_u64) == Some(3) {
+        let fixed = roundness
+            .get("value")
+            .and_then(Value::as_f64)
+            .unwrap_or(Rectangle::DEFAULT_ADAPTIVE_RADIUS);
+        if min_side > fixed / Rectangle::DEFAULT_PROPORTIONAL_RADIUS {
+            return fixed;
+        }
+    }
+    min_side * Rectangle::DEFAULT_PROPORTIONAL_RADIUS
+}
+
+/// Absolute points of a linear or freedraw element, with its rotation applied.
+fn linear_points(elem: &Value) -> Vec<Point> {
+    let x = f64_field(elem, "x", 0.0);
+    let y = f64_field(elem, "y", 0.0);
+    let points: Vec<Point> = elem
+        .get("points")
+        .and_then(Value::as_array)
+        .map(|pts| {
+            pts.iter()
+                .filter_map(Value::as_array)
+                .filter_map(|p| {
+                    let px = p.first().and_then(Value::as_f64)?;
+                    let py = p.get(1).and_then(Value::as_f64)?;
+                    Some(Point::new(x + px, y + py))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    let angle = f64_field(elem, "angle", 0.0);
+    if angle == 0.0 || points.is_empty() {
+        return points;
+    }
+    let rotate = Affine::rotate_about(angle, bounding_rect(&points).center());
+    points.into_iter().map(|p| rotate * p).collect()
+}
+
+/// The name of a frame element.
+fn frame_name(elem: &Value) -> &str {
+    str_field(elem, "name").unwrap_or("Frame")
+}
+
+/// The outline of a frame and its name above it.
+pub(crate) fn frame_shapes(rect: Rect, name: &str) -> Vec<Shape> {
+    let mut outline = Rectangle::new(rect.origin(), rect.width(), rect.height());
+    outline.style.stroke_color = FRAME_COLOR;
+    outline.style.stroke_width = 1.0;
+    outline.style.sloppiness = Sloppiness::Architect;
+    let label_height = FRAME_LABEL_SIZE * LINE_HEIGHT;
+    let mut label = Text::new(
+        Point::new(rect.x0, rect.y0 - label_height - 4.0),
+        name.to_string(),
+    )
+    .with_font_size(FRAME_LABEL_SIZE)
+    .with_font_family(FontFamily::NotoSans);
+    label.style.stroke_color = FRAME_COLOR;
+    vec![Shape::Rectangle(outline), Shape::Text(label)]
+}
+
+/// Build an image shape from a `data:` URL, placed in `rect`.
+pub(crate) fn image_from_data_url(url: &str, rect: Rect) -> Option<Image> {
+    let (header, payload) = url.strip_prefix("data:")?.split_once(',')?;
+    let bytes = if header.ends_with(";base64") {
+        STANDARD.decode(payload.trim()).ok()?
+    } else {
+        payload.as_bytes().to_vec()
+    };
+    let mime = header.split(';').next().unwrap_or_default();
+    let format = ImageFormat::from_magic_bytes(&bytes).or_else(|| {
+        let ext = mime.strip_prefix("image/")?;
+        ImageFormat::from_extension(ext.trim_end_matches("+xml"))
+    })?;
+    let image = Image::new(
+        rect.origin(),
+        &bytes,
+        rect.width().round() as u32,
+        rect.height().round() as u32,
+        format,
+    );
+    Some(image.with_size(rect.width(), rect.height()))
+}
+
+/// Map an Excalidraw `fontFamily` code to the closest font.
+fn font_family(code: u64) -> FontFamily {
+    match code {
+        // Virgil
+        1 => FontFamily::GelPenSerif,
+        // Helvetica, Cascadia, Nunito
+        2 | 3 | 6 => FontFamily::NotoSans,
+        // Lilita One, Comic Shanns
+        7 | 8 => FontFamily::VanillaExtract,
+        // Excalifont and anything newer
+        _ => FontFamily::GelPen,
+    }
+}
+
+fn bounding_rect(points: &[Point]) -> Rect {
+    points
+        .iter()
+        .map(|&p| Rect::from_points(p, p))
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO)
+}
+
+// ---------------------------------------------------------------------------
+// Export
+// ---------------------------------------------------------------------------
+
+/// Builds the element list, then binds arrows to the shapes they touch.
+#[derive(Default)]
+struct Exporter {
+    elements: Vec<Map<String, Value>>,
+    files: Map<String, Value>,
+    /// Elements arrows may bind to, with their bounds.
+    bindable: Vec<(usize, Rect)>,
+    /// Arrow elements with their start and end points.
+    arrows: Vec<(usize, Point, Point)>,
+    /// Id of the frame being exported.
+    frame: Option<String>,
+}
+
+fn export_elements<'a>(shapes: impl IntoIterator<Item = &'a Shape>) -> (Vec<Value>, Value) {
+    let mut exporter = Exporter::default();
+    let mut groups = Vec::new();
+    for shape in shapes {
+        exporter.shape(shape, &mut groups);
+    }
+    exporter.bind_arrows();
+    let elements = exporter.elements.into_iter().map(Value::Object).collect();
+    (elements, Value::Object(exporter.files))
+}
+
+impl Exporter {
+    /// Export a shape; `groups` holds the enclosing group ids, outermost first.
+    fn shape(&mut self, shape: &Shape, groups: &mut Vec<String>) {
+        let Shape::Group(group) = shape else {
+            self.leaf(shape, groups);
+            return;
+        };
+        if let Some(name) = &group.frame {
+            self.frame(shape, group, name);
+            return;
+        }
+        if let Some((container, text)) = label_pair(group) {
+            let container = self.leaf(container, groups);
+            let text = self.leaf(text, groups);
+            self.bind_text(container, text);
+            return;
+        }
+        groups.push(shape.id().to_string());
+        for child in &group.children {
+            self.shape(child, groups);
+        }
+        groups.pop();
+    }
+
+    /// Export a frame group as a frame element followed by its children. The outline
+    /// and name from [`frame_shapes`] become the frame itself.
+    fn frame(&mut self, shape: &Shape, group: &Group, name: &str) {
+        let outline = group.children.windows(2).position(|pair| {
+            matches!(pair, [Shape::Rectangle(_), Shape::Text(label)] if label.content == name)
+        });
+        let bounds = match outline.map(|i| &group.children[i]) {
+            Some(Shape::Rectangle(rect)) => {
+                Rect::from_origin_size(rect.position, (rect.width, rect.height))
+            }
+            _ => shape.bounds(),
+        };
+        let id = shape.id().to_string();
+        let mut elem = element(&id, "frame", bounds, 0.0, &ShapeStyle::default());
+        elem.insert("name".into(), json!(name));
+        self.elements.push(elem);
+
+        // Excalidraw frames can't be grouped, so only groups inside the frame are kept
+        let parent = self.frame.replace(id);
+        let mut groups = Vec::new();
+        for (i, child) in group.children.iter().enumerate() {
+            if outline.is_none_or(|o| i != o && i != o + 1) {
+                self.shape(child, &mut groups);
+            }
+        }
+        self.frame = parent;
+    }
+
+    /// Export a non-group shape, returning its element index.
+    fn leaf(&mut self, shape: &Shape, groups: &[String]) -> usize {
+        let mut extras = Extras::default();
+        let id = shape.id().to_string();
+        let index = self.elements.len();
+        let mut elem = match shape {
+            Shape::Rectangle(rect) => {
+                let bounds = Rect::from_origin_size(rect.position, (rect.width, rect.height));
+                let mut elem = element(&id, "rectangle", bounds, rect.rotation, &rect.style);
+                if rect.corner_radius > 0.0 {
+                    elem.insert(
+                        "roundness".into(),
+                        json!({ "type": 3, "value": rect.corner_radius }),
+                    );
+                }
+                self.bindable.push((index, shape.rotated_bounds()));
+                elem
+            }
+            Shape::Ellipse(ellipse) => {
+                let bounds = Rect::from_center_size(
+                    ellipse.center,
+                    (ellipse.radius_x * 2.0, ellipse.radius_y * 2.0),
+                );
+                self.bindable.push((index, shape.rotated_bounds()));
+                element(&id, "ellipse", bounds, ellipse.rotation, &ellipse.style)
+            }
+            Shape::Line(line) => match diamond_bounds(line) {
+                Some(bounds) => {
+                    self.bindable.push((index, bounds));
+                    element(&id, "diamond", bounds, 0.0, &line.style)
+                }
+                None => {
+                    let mut elem = linear(&id, "line", &line.all_points(), &line.style);
+                    set_path_style(&mut elem, line.path_style, line.stroke_style);
+                    if line.is_closed() {
+                        elem.insert("polygon".into(), json!(true));
+                    }
+                    elem
+                }
+            },
+            Shape::Arrow(arrow) => {
+                let mut elem = linear(&id, "arrow", &arrow.all_points(), &arrow.style);
+                set_path_style(&mut elem, arrow.path_style, arrow.stroke_style);
+                elem.insert("endArrowhead".into(), json!("arrow"));
+                self.arrows.push((index, arrow.start, arrow.end));
+                elem
+            }
+            Shape::Freehand(freehand) => {
+                let mut elem = linear(&id, "freedraw", &freehand.points, &freehand.style);
+                elem.insert("pressures".into(), json!(freehand.pressures));
+                elem.insert("simulatePressure".into(), json!(!freehand.has_pressure()));
+                elem
+            }
+            Shape::Text(text) => {
+                if text.font_weight != FontWeight::Regular {
+                    extras.font_weight = Some(text.font_weight);
+                }
+                self.bindable.push((index, shape.rotated_bounds()));
+                let mut elem = text_element(
+                    &id,
+                    shape.bounds(),
+                    text.rotation,
+                    &text.style,
+                    &text.content,
+                );
+                elem.insert("fontSize".into(), json!(text.font_size));
+                elem.insert(
+                    "fontFamily".into(),
+                    json!(font_family_code(text.font_family)),
+                );
+                elem
+            }
+            Shape::Math(math) => {
+                extras.latex = Some(math.latex.clone());
+                self.bindable.push((index, shape.rotated_bounds()));
+                let mut elem =
+                    text_element(&id, shape.bounds(), math.rotation, &math.style, &math.latex);
+                elem.insert("fontSize".into(), json!(math.font_size));
+                elem
+            }
+            Shape::Image(image) => {
+                let mut style = image.style.clone();
+                style.stroke_color = SerializableColor::transparent();
+                style.fill_color = None;
+                let mut elem = element(&id, "image", image.as_rect(), image.rotation, &style);
+                elem.insert("fileId".into(), json!(id));
+                elem.insert("status".into(), json!("saved"));
+                elem.insert("scale".into(), json!([1, 1]));
+                elem.insert("crop".into(), Value::Null);
+                self.files.insert(
+                    id.clone(),
+                    json!({
+                        "id": id,
+                        "mimeType": image.format.mime_type(),
+                        "dataURL": format!(
+                            "data:{};base64,{}",
+                            image.format.mime_type(),
+                            image.asset.to_base64()
+                        ),
+                        "created": 1,
+                        "lastRetrieved": 1,
+                    }),
+                );
+                self.bindable.push((index, shape.rotated_bounds()));
+                elem
+            }
+            Shape::Group(_) => unreachable!("groups are exported by Exporter::shape"),
+        };
+
+        let style = shape.style();
+        if matches!(
+            style.fill_pattern,
+            FillPattern::Dots | FillPattern::Dashed | FillPattern::ZigZagLine
+        ) {
+            extras.fill_pattern = Some(style.fill_pattern);
+        }
+        if style.sloppiness == Sloppiness::Drunk {
+            extras.sloppiness = Some(Sloppiness::Drunk);
+        }
+        if !extras.is_empty() {
+            elem.insert(
+                "customData".into(),
+                json!({ CUSTOM_DATA_KEY: serde_json::to_value(&extras).unwrap_or_default() }),
+            );
+        }
+        // Excalidraw lists group ids innermost first
+        elem.insert(
+            "groupIds".into(),
+            json!(groups.iter().rev().collect::<Vec<_>>()),
+        );
+        if let Some(frame) = &self.frame {
+            elem.insert("frameId".into(), json!(frame));
+        }
+        self.elements.push(elem);
+        index
+    }
+
+    /// Make the text at `text` the bound label of the element at `container`.
+    fn bind_text(&mut self, container: usize, text: usize) {
+        let container_id = self.elements[container]["id"].clone();
+        let text_id = self.elements[text]["id"].clone();
+        push_bound(
+            &mut self.elements[container],
+            json!({ "id": text_id, "type": "text" }),
+        );
+        let label = &mut self.elements[text];
+        label.insert("containerId".into(), container_id);
+        label.insert("textAlign".into(), json!("center"));
+        label.insert("verticalAlign".into(), json!("middle"));
+        self.bindable.retain(|&(i, _)| i != text);
+    }
+
+    /// Bind each arrow endpoint to the closest shape within [`CONNECTION_TOLERANCE`].
+    fn bind_arrows(&mut self) {
+        for (arrow, start, end) in std::mem::take(&mut self.arrows) {
+            for (key, point) in [("startBinding", start), ("endBinding", end)] {
+                let target = self
+                    .bindable
+                    .iter()
+                    .map(|&(i, bounds)| (i, distance_to_rect(bounds, point)))
+                    .filter(|&(_, d)| d <= CONNECTION_TOLERANCE)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                let Some((target, gap)) = target else {
+                    continue;
+                };
+                let target_id = self.elements[target]["id"].clone();
+                let arrow_id = self.elements[arrow]["id"].clone();
+                self.elements[arrow].insert(
+                    key.into(),
+                    json!({ "elementId": target_id, "focus": 0.0, "gap": gap }),
+                );
+                push_bound(
+                    &mut self.elements[target],
+                    json!({ "id": arrow_id, "type": "arrow" }),
+                );
+            }
+        }
+    }
+}
+
+/// A group of one container and one text centered on it, exported as bound text.
+pub(crate) fn label_pair(group: &Group) -> Option<(&Shape, &Shape)> {
+    let [a, b] = group.children.as_slice() else {
+        return None;
+    };
+    let (container, text) = match (a, b) {
+        (Shape::Text(_), Shape::Text(_)) => return None,
+        (Shape::Text(_), _) => (b, a),
+        (_, Shape::Text(_)) => (a, b),
+        _ => return None,
+    };
+    let center = text.bounds().center();
+    let centered = match container {
+        Shape::Rectangle(_) | Shape::Ellipse(_) => container.bounds().contains(center),
+        Shape::Line(line) => diamond_bounds(line).is_some_and(|b| b.contains(center)),
+        Shape::Arrow(arrow) => {
+            polyline_midpoint(&arrow.all_points()).distance(center) <= CONNECTION_TOLERANCE
+        }
+        _ => false,
+    };
+    centered.then_some((container, text))
+}
+
+/// Bounds of a closed line whose four vertices are the edge midpoints of its bounds.
+pub(crate) fn diamond_bounds(line: &Line) -> Option<Rect> {
+    if !line.is_closed() || line.intermediate_points.len() != 3 {
+        return None;
+    }
+    let points = line.all_points();
+    let bounds = bounding_rect(&points);
+    if bounds.width() <= 0.0 || bounds.height() <= 0.0 {
+        return None;
+    }
+    let center = bounds.center();
+    let vertices = [
+        Point::new(center.x, bounds.y0),
+        Point::new(bounds.x1, center.y),
+        Point::new(center.x, bounds.y1),
+        Point::new(bounds.x0, center.y),
+    ];
+    let mut matched = [false; 4];
+    for point in &points[..4] {
+        let i = vertices
+            .iter()
+            .position(|v| v.distance(*point) <= VERTEX_TOLERANCE)?;
+        matched[i] = true;
+    }
+    matched.iter().all(|&m| m).then_some(bounds)
+}
+
+/// Point halfway along a polyline.
+fn polyline_midpoint(points: &[Point]) -> Point {
+    let total: f64 = points.windows(2).map(|w| w[0].distance(w[1])).sum();
+    let mut remaining = total / 2.0;
+    for w in points.windows(2) {
+        let length = w[0].distance(w[1]);
+        if length >= remaining && length > 0.0 {
+            return w[0].lerp(w[1], remaining / length);
+        }
+        remaining -= length;
+    }
+    points.first().copied().unwrap_or(Point::ZERO)
+}
+
+/// Common element fields.
+fn element(
+    id: &str,
+    kind: &str,
+    bounds: Rect,
+    angle: f64,
+    style: &ShapeStyle,
+) -> Map<String, Value> {
+    let fill = style
+        .fill_color
+        .map_or_else(|| "transparent".to_string(), excalidraw_color);
+    let roughness = match style.sloppiness {
+        Sloppiness::Architect => 0,
+        Sloppiness::Artist => 1,
+        Sloppiness::Cartoonist | Sloppiness::Drunk => 2,
+    };
+    let value = json!({
+        "id": id,
+        "type": kind,
+        "x": bounds.x0,
+        "y": bounds.y0,
+        "width": bounds.width(),
+        "height": bounds.height(),
+        "angle": angle,
+        "strokeColor": excalidraw_color(style.stroke_color),
+        "backgroundColor": fill,
+        "fillStyle": fill_style(style.fill_pattern),
+        "strokeWidth": style.stroke_width,
+        "strokeStyle": "solid",
+        "roughness": roughness,
+        "opacity": (style.opacity * 100.0).round(),
+        "groupIds": [],
+        "frameId": null,
+        "roundness": null,
+        "seed": style.seed,
+        "version": 1,
+        "versionNonce": style.seed,
+        "isDeleted": false,
+        "boundElements": null,
+        "updated": 1,
+        "link": null,
+        "locked": false,
+    });
+    let Value::Object(map) = value else {
+        unreachable!("json! object literal")
+    };
+    map
+}
+
+/// A line, arrow or freedraw element, with points relative to the first one.
+fn linear(id: &str, kind: &str, points: &[Point], style: &ShapeStyle) -> Map<String, Value> {
+    let origin = points.first().copied().unwrap_or(Point::ZERO);
+    let bounds = bounding_rect(points);
+    let mut elem = element(id, kind, bounds, 0.0, style);
+    elem.insert("x".into(), json!(origin.x));
+    elem.insert("y".into(), json!(origin.y));
+    let relative: Vec<[f64; 2]> = points
+        .iter()
+        .map(|p| [p.x - origin.x, p.y - origin.y])
+        .collect();
+    elem.insert("points".into(), json!(relative));
+    elem.insert("lastCommittedPoint".into(), Value::Null);
+    if kind != "freedraw" {
+        elem.insert("startBinding".into(), Value::Null);
+        elem.insert("endBinding".into(), Value::Null);
+        elem.insert("startArrowhead".into(), Value::Null);
+        elem.insert("endArrowhead".into(), Value::Null);
+    }
+    elem
+}
+
+fn set_path_style(elem: &mut Map<String, Value>, path_style: PathStyle, stroke: StrokeStyle) {
+    let stroke = match stroke {
+        StrokeStyle::Solid => "solid",
+        StrokeStyle::Dashed => "dashed",
+        StrokeStyle::Dotted => "dotted",
+    };
+    elem.insert("strokeStyle".into(), json!(stroke));
+    match path_style {
+        PathStyle::Direct => {}
+        PathStyle::Flowing => {
+            elem.insert("roundness".into(), json!({ "type": 2 }));
+        }
+        PathStyle::Angular => {
+            elem.insert("elbowed".into(), json!(true));
+        }
+    }
+}
+
+fn text_element(
+    id: &str,
+    bounds: Rect,
+    angle: f64,
+    style: &ShapeStyle,
+    content: &str,
+) -> Map<String, Value> {
+    let mut elem = element(id, "text", bounds, angle, style);
+    elem.insert("backgroundColor".into(), json!("transparent"));
+    elem.insert("text".into(), json!(content));
+    elem.insert("originalText".into(), json!(content));
+    elem.insert(
+        "fontFamily".into(),
+        json!(font_family_code(FontFamily::NotoSans)),
+    );
+    elem.insert("textAlign".into(), json!("left"));
+    elem.insert("verticalAlign".into(), json!("top"));
+    elem.insert("containerId".into(), Value::Null);
+    elem.insert("autoResize".into(), json!(true));
+    elem.insert("lineHeight".into(), json!(LINE_HEIGHT));
+    elem
+}
+
+fn push_bound(elem: &mut Map<String, Value>, entry: Value) {
+    match elem.get_mut("boundElements") {
+        Some(Value::Array(list)) => {
+            if !list.contains(&entry) {
+                list.push(entry);
+            }
+        }
+        _ => {
+            elem.insert("boundElements".into(), json!([entry]));
+        }
+    }
+}
+
+fn fill_style(pattern: FillPattern) -> &'static str {
+    match pattern {
+        FillPattern::Solid => "solid",
+        FillPattern::CrossHatch => "cross-hatch",
+        FillPattern::ZigZag => "zigzag",
+        // Hachure, and patterns Excalidraw lacks (kept in customData)
+        _ => "hachure",
+    }
+}
+
+/// Excalidraw `fontFamily` code for a font, the inverse of [`font_family`].
+fn font_family_code(family: FontFamily) -> u64 {
+    match family {
+        FontFamily::GelPen => 5,
+        FontFamily::NotoSans => 2,
+        FontFamily::GelPenSerif => 1,
+        FontFamily::VanillaExtract => 8,
+    }
+}
+
+/// An Excalidraw color: hex, or `transparent`.
+fn excalidraw_color(color: SerializableColor) -> String {
+    color_hex(color).unwrap_or_else(|| "transparent".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scene(elements: Value) -> String {
+        json!({ "type": "excalidraw", "version": 2, "elements": elements }).to_string()
+    }
+
+    fn exported(json: &str) -> Vec<Value> {
+        let data: Value = serde_json::from_str(json).unwrap();
+        data["elements"].as_array().unwrap().clone()
+    }
+
+    #[test]
+    fn test_import_elements() {
+        let json = scene(json!([
+            {
+                "id": "r", "type": "rectangle", "x": 0, "y": 0, "width": 200, "height": 150,
+                "roundness": { "type": 3 }, "opacity": 50, "roughness": 0,
+                "backgroundColor": "#ffc9c9", "fillStyle": "cross-hatch",
+            },
+            { "id": "small", "type": "rectangle", "x": 0, "y": 0, "width": 40, "height": 40,
+              "roundness": { "type": 3 } },
+            { "id": "d", "type": "diamond", "x": 300, "y": 0, "width": 100, "height": 60 },
+            { "id": "gone", "type": "ellipse", "isDeleted": true },
+            {
+                "id": "a", "type": "arrow", "x": 10, "y": 10, "strokeStyle": "dashed",
+                "points": [[0, 0], [100, 0]], "startArrowhead": "arrow", "endArrowhead": null,
+            },
+            { "id": "l", "type": "arrow", "x": 0, "y": 0, "points": [[0, 0], [5, 5]],
+              "startArrowhead": null, "endArrowhead": null },
+            { "id": "t", "type": "text", "x": 5, "y": 5, "text": "Hi", "fontSize": 28,
+              "fontFamily": 1, "strokeColor": "#1e1e1e" },
+        ]));
+        let shapes = parse_excalidraw(&json).unwrap();
+        assert_eq!(shapes.len(), 6);
+
+        let Shape::Rectangle(rect) = &shapes[0] else {
+            panic!("expected a rectangle");
+        };
+        assert_eq!(rect.corner_radius, Rectangle::DEFAULT_ADAPTIVE_RADIUS);
+        assert_eq!(rect.style.opacity, 0.5);
+        assert_eq!(rect.style.sloppiness, Sloppiness::Architect);
+        assert_eq!(rect.style.fill_pattern, FillPattern::CrossHatch);
+        assert_eq!(
+            rect.style.fill_color,
+            Some(SerializableColor::new(0xff, 0xc9, 0xc9, 255))
+        );
+        let Shape::Rectangle(small) = &shapes[1] else {
+            panic!("expected a rectangle");
+        };
+        assert_eq!(small.corner_radius, 10.0);
+
+        let Shape::Line(diamond) = &shapes[2] else {
+            panic!("expected a closed line");
+        };
+        assert!(diamond.is_closed());
+        assert_eq!(
+            diamond_bounds(diamond),
+            Some(Rect::new(300.0, 0.0, 400.0, 60.0))
+        );
+
+        // Only a start arrowhead: the arrow is reversed
+        let Shape::Arrow(arrow) = &shapes[3] else {
+            panic!("expected an arrow");
+        };
+        assert_eq!(arrow.end, Point::new(10.0, 10.0));
+        assert_eq!(arrow.stroke_style, StrokeStyle::Dashed);
+        assert!(matches!(shapes[4], Shape::Line(_)));
+
+        let Shape::Text(text) = &shapes[5] else {
+            panic!("expected a text");
+        };
+        assert_eq!(text.font_size, 28.0);
+        assert_eq!(text.font_family, FontFamily::GelPenSerif);
+        assert_eq!(
+            text.style.stroke_color,
+            SerializableColor::new(0x1e, 0x1e, 0x1e, 255)
+        );
+    }
+
+    #[test]
+    fn test_import_groups_labels_and_frames() {
+        let json = scene(json!([
+            { "id": "f", "type": "frame", "x": 0, "y": 0, "width": 500, "height": 300,
+              "name": "Sprint" },
+            { "id": "box", "type": "rectangle", "x": 10, "y": 10, "width": 100, "height": 50,
+              "groupIds": ["inner", "outer"], "frameId": "f",
+              "boundElements": [{ "id": "label", "type": "text" }] },
+            { "id": "label", "type": "text", "x": 40, "y": 25, "text": "Box",
+              "containerId": "box", "groupIds": [] },
+            { "id": "other", "type": "ellipse", "x": 200, "y": 10, "width": 50, "height": 50,
+              "groupIds": ["outer"], "frameId": "f" },
+            { "id": "free", "type": "ellipse", "x": 600, "y": 0, "width": 10, "height": 10 },
+        ]));
+        let shapes = parse_excalidraw(&json).unwrap();
+        assert_eq!(shapes.len(), 2);
+        assert!(matches!(shapes[1], Shape::Ellipse(_)));
+
+        // Frame: outline, name, then the "outer" group
+        let Shape::Group(frame) = &shapes[0] else {
+            panic!("expected the frame group");
+        };
+        assert_eq!(frame.children.len(), 3);
+        assert!(matches!(frame.children[0], Shape::Rectangle(_)));
+        let Shape::Text(name) = &frame.children[1] else {
+            panic!("expected the frame name");
+        };
+        assert_eq!(name.content, "Sprint");
+        assert_eq!(frame.frame.as_deref(), Some("Sprint"));
+
+        // "outer" holds the labeled box (the "inner" group) and the ellipse
+        let Shape::Group(outer) = &frame.children[2] else {
+            panic!("expected the outer group");
+        };
+        assert_eq!(outer.children.len(), 2);
+        assert!(matches!(outer.children[1], Shape::Ellipse(_)));
+        let Shape::Group(labeled) = &outer.children[0] else {
+            panic!("expected the labeled box");
+        };
+        assert!(matches!(labeled.children[0], Shape::Rectangle(_)));
+        assert!(matches!(labeled.children[1], Shape::Text(_)));
+        assert!(label_pair(labeled).is_some());
+        assert!(labeled.frame.is_none());
+    }
+
+    #[test]
+    fn test_export_frames() {
+        let json = scene(json!([
+            { "id": "f", "type": "frame", "x": 0, "y": 0, "width": 500, "height": 300,
+              "name": "Sprint" },
+            { "id": "box", "type": "rectangle", "x": 10, "y": 10, "width": 100, "height": 50,
+              "groupIds": ["g"], "frameId": "f" },
+            { "id": "other", "type": "ellipse", "x": 200, "y": 10, "width": 50, "height": 50,
+              "groupIds": ["g"], "frameId": "f" },
+        ]));
+        let shapes = parse_excalidraw(&json).unwrap();
+        let frame_id = shapes[0].id().to_string();
+        let json = export_excalidraw(&shapes);
+
+        // The outline and name fold back into one frame element
+        let elements = exported(&json);
+        assert_eq!(elements.len(), 3);
+        assert_eq!(elements[0]["type"], "frame");
+        assert_eq!(elements[0]["name"], "Sprint");
+        assert_eq!(elements[0]["width"], 500.0);
+        assert!(elements[0]["frameId"].is_null());
+        for child in &elements[1..] {
+            assert_eq!(child["frameId"], frame_id.as_str());
+            assert_eq!(child["groupIds"].as_array().unwrap().len(), 1);
+        }
+
+        let reimported = parse_excalidraw(&json).unwrap();
+        assert_eq!(reimported.len(), 1);
+        let Shape::Group(frame) = &reimported[0] else {
+            panic!("expected the frame group");
+        };
+        assert_eq!(frame.frame.as_deref(), Some("Sprint"));
+        assert_eq!(frame.children.len(), 3);
+        assert_eq!(
+            frame.children[0].bounds(),
+            Rect::new(0.0, 0.0, 500.0, 300.0)
+        );
+        let Shape::Group(inner) = &frame.children[2] else {
+            panic!("expected the inner group");
+        };
+        assert_eq!(inner.children.len(), 2);
+    }
+
+    #[test]
+    fn test_import_images() {
+        let png = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
+        let json = json!({
+            "type": "excalidraw",
+            "elements": [
+                { "id": "i", "type": "image", "x": 5, "y": 6, "width": 64, "height": 32,
+                  "fileId": "file1", "angle": 0.5 },
+                { "id": "missing", "type": "image", "fileId": "nope" },
+            ],
+            "files": {
+                "file1": {
+                    "mimeType": "image/png",
+                    "id": "file1",
+                    "dataURL": format!("data:image/png;base64,{}", STANDARD.encode(png)),
+                },
+            },
+        })
+        .to_string();
+        let shapes = parse_excalidraw(&json).unwrap();
+        assert_eq!(shapes.len(), 1);
+        let Shape::Image(image) = &shapes[0] else {
+            panic!("expected an image");
+        };
+        assert_eq!(image.format, ImageFormat::Png);
+        assert_eq!(image.as_rect(), Rect::new(5.0, 6.0, 69.0, 38.0));
+        assert_eq!(image.rotation, 0.5);
+        assert_eq!(image.data(), png);
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let mut left = Rectangle::new(Point::new(0.0, 0.0), 100.0, 60.0);
+        left.corner_radius = 12.0;
+        left.style.fill_pattern = FillPattern::Dots;
+        left.style.sloppiness = Sloppiness::Drunk;
+        let label = Text::new(Point::new(30.0, 20.0), "Start".to_string());
+        let labeled = Shape::Group(Group::new(vec![Shape::Rectangle(left), Shape::Text(label)]));
+
+        let diamond = crate::mermaid::polygon(&[
+            Point::new(250.0, 0.0),
+            Point::new(300.0, 30.0),
+            Point::new(250.0, 60.0),
+            Point::new(200.0, 30.0),
+        ]);
+        let mut arrow = Arrow::new(Point::new(104.0, 30.0), Point::new(198.0, 30.0));
+        arrow.path_style = PathStyle::Angular;
+        let heavy = Text::new(Point::new(0.0, 200.0), "Bold".to_string())
+            .with_font_weight(FontWeight::Heavy)
+            .with_font_family(FontFamily::VanillaExtract);
+        let math = Math::new(Point::new(0.0, 300.0), "x^2".to_string());
+        let ink = Freehand::from_points_with_pressure(
+            vec![Point::new(0.0, 400.0), Point::new(10.0, 410.0)],
+            vec![0.25, 0.75],
+        );
+        let grouped = Shape::Group(Group::new(vec![
+            Shape::Text(heavy),
+            Shape::Math(math),
+            Shape::Freehand(ink),
+        ]));
+        let shapes = vec![labeled, diamond, Shape::Arrow(arrow), grouped];
+
+        let json = export_excalidraw(&shapes);
+        let elements = exported(&json);
+        let kinds: Vec<&str> = elements
+            .iter()
+            .map(|e| e["type"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                "rectangle",
+                "text",
+                "diamond",
+                "arrow",
+                "text",
+                "text",
+                "freedraw"
+            ]
+        );
+
+        // Bound text and arrow bindings
+        let (rect, text, arrow) = (&elements[0], &elements[1], &elements[3]);
+        assert_eq!(text["containerId"], rect["id"]);
+        assert_eq!(arrow["startBinding"]["elementId"], rect["id"]);
+        assert_eq!(arrow["endBinding"]["elementId"], elements[2]["id"]);
+        assert_eq!(arrow["elbowed"], json!(true));
+        let bound = rect["boundElements"].as_array().unwrap();
+        assert_eq!(bound.len(), 2);
+        assert_eq!(rect["roundness"], json!({ "type": 3, "value": 12.0 }));
+        assert_eq!(rect["groupIds"], json!([]));
+        assert_eq!(elements[4]["groupIds"].as_array().unwrap().len(), 1);
+
+        // Everything comes back
+        let shapes = parse_excalidraw(&json).unwrap();
+        assert_eq!(shapes.len(), 4);
+        let Shape::Group(labeled) = &shapes[0] else {
+            panic!("expected the labeled rectangle");
+        };
+        let Shape::Rectangle(rect) = &labeled.children[0] else {
+            panic!("expected a rectangle");
+        };
+        assert_eq!(rect.corner_radius, 12.0);
+        assert_eq!(rect.style.fill_pattern, FillPattern::Dots);
+        assert_eq!(rect.style.sloppiness, Sloppiness::Drunk);
+        assert!(matches!(&shapes[1], Shape::Line(line) if diamond_bounds(line).is_some()));
+        assert!(matches!(&shapes[2], Shape::Arrow(a) if a.path_style == PathStyle::Angular));
+
+        let Shape::Group(grouped) = &shapes[3] else {
+            panic!("expected a group");
+        };
+        let Shape::Text(heavy) = &grouped.children[0] else {
+            panic!("expected a text");
+        };
+        assert_eq!(heavy.font_weight, FontWeight::Heavy);
+        assert_eq!(heavy.font_family, FontFamily::VanillaExtract);
+        assert!(matches!(&grouped.children[1], Shape::Math(m) if m.latex == "x^2"));
+        let Shape::Freehand(ink) = &grouped.children[2] else {
+            panic!("expected a freehand");
+        };
+        assert_eq!(ink.pressures, vec![0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_clipboard_and_errors() {
+        let image = Image::new(
+            Point::ZERO,
+            b"<svg width='4' height='4'/>",
+            4,
+            4,
+            ImageFormat::Svg,
+        );
+        let payload = export_excalidraw_clipboard([&Shape::Image(image)]);
+        assert!(is_excalidraw_clipboard(&payload));
+        assert!(!is_excalidraw_clipboard(&export_excalidraw([])));
+        let data: Value = serde_json::from_str(&payload).unwrap();
+        let file_id = data["elements"][0]["fileId"].as_str().unwrap();
+        assert!(
+            data["files"][file_id]["dataURL"]
+                .as_str()
+                .unwrap()
+                .starts_with("data:image/svg+xml;base64,")
+        );
+        let shapes = parse_excalidraw(&payload).unwrap();
+        assert!(matches!(&shapes[0], Shape::Image(i) if i.format == ImageFormat::Svg));
+
+        assert!(matches!(
+            parse_excalidraw("nope"),
+            Err(ExcalidrawError::Json(_))
+        ));
+        assert_eq!(
+            parse_excalidraw("{}").unwrap_err(),
+            ExcalidrawError::MissingElements
+        );
+        assert_eq!(
+            parse_color("#abc"),
+            Some(SerializableColor::new(0xaa, 0xbb, 0xcc, 255))
+        );
+        let translucent = SerializableColor::new(1, 2, 3, 128);
+        assert_eq!(excalidraw_color(translucent), "#01020380");
+        assert_eq!(
+            parse_color(&excalidraw_color(translucent)),
+            Some(translucent)
+        );
+        assert_eq!(
+            excalidraw_color(SerializableColor::transparent()),
+            "transparent"
+        );
+    }
+}
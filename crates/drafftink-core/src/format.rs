@@ -0,0 +1,400 @@
+//! Versioned document format.
+//!
+//! Saved documents carry a top-level `format_version`. Loading reads the JSON as a
+//! value, upgrades it one version at a time through [`MIGRATIONS`], and only then
+//! deserializes it into a [`CanvasDocument`](crate::canvas::CanvasDocument). Documents
+//! written by a newer build are refused with [`FormatError::TooNew`] instead of being
+//! half-loaded.
+//!
+//! Documents saved before versioning existed have no `format_version` and are
+//! version 0.
+//!
+//! Changing the serialized shape of a document means:
+//!
+//! 1. bumping [`FORMAT_VERSION`],
+//! 2. appending a migration from the previous version to [`MIGRATIONS`], and
+//! 3. freezing sample documents of the new version in `corpus/v<N>/`.
+//!
+//! Every file in the corpus must keep loading; the tests check this for all versions.
+
+use crate::assets::{self, ASSET_REF_KEY, ASSETS_KEY, Asset, INLINE_DATA_KEY};
+use serde::Serialize;
+use serde_json::{Map, Value, json};
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Version written by this build.
+pub const FORMAT_VERSION: u32 = 3;
+
+/// Key of the version in a document's top-level object.
+pub const VERSION_KEY: &str = "format_version";
+
+/// Errors that can occur while loading a document.
+#[derive(Debug, Error, PartialEq)]
+pub enum FormatError {
+    #[error("Invalid JSON: {0}")]
+    Json(String),
+    #[error("Document is not a JSON object")]
+    NotAnObject,
+    #[error("Invalid format version: {0}")]
+    InvalidVersion(String),
+    #[error(
+        "Document uses format version {found}, but this version of DrafftInk only reads up to {supported}. Please update DrafftInk to open it."
+    )]
+    TooNew { found: u32, supported: u32 },
+    #[error("Failed to upgrade document from format version {from}: {message}")]
+    Migration { from: u32, message: String },
+    #[error("Invalid document: {0}")]
+    Document(String),
+}
+
+/// Result type for document loading.
+pub type FormatResult<T> = Result<T, FormatError>;
+
+/// Upgrades a document from version `i` to `i + 1`, where `i` is its index.
+type Migration = fn(&mut Map<String, Value>) -> Result<(), String>;
+
+/// Migrations in order, one per version step.
+const MIGRATIONS: &[Migration] = &[migrate_v0, migrate_v1, migrate_v2];
+
+const _: () = assert!(MIGRATIONS.len() == FORMAT_VERSION as usize);
+
+/// Read a document's format version; unversioned documents are version 0.
+pub fn version_of(doc: &Value) -> FormatResult<u32> {
+    let object = doc.as_object().ok_or(FormatError::NotAnObject)?;
+    match object.get(VERSION_KEY) {
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| FormatError::InvalidVersion(version.to_string())),
+    }
+}
+
+/// Upgrade a document to [`FORMAT_VERSION`].
+pub fn upgrade(mut doc: Value) -> FormatResult<Value> {
+    let version = version_of(&doc)?;
+    if version > FORMAT_VERSION {
+        return Err(FormatError::TooNew {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    let object = doc.as_object_mut().ok_or(FormatError::NotAnObject)?;
+    for (from, migrate) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let from = from as u32;
+        migrate(object).map_err(|message| FormatError::Migration { from, message })?;
+        object.insert(VERSION_KEY.to_string(), json!(from + 1));
+    }
+    Ok(doc)
+}
+
+/// A document serialized with [`FORMAT_VERSION`] as its first field.
+#[derive(Serialize)]
+pub(crate) struct Versioned<'a, T> {
+    format_version: u32,
+    #[serde(flatten)]
+    document: &'a T,
+}
+
+impl<'a, T> Versioned<'a, T> {
+    pub(crate) fn new(document: &'a T) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            document,
+        }
+    }
+}
+
+/// Version 0 to 1: documents from before versioning.
+///
+/// Writes out every field older builds left to serde defaults, so later migrations
+/// can rely on them, and repairs the z-order, which could miss shapes or list
+/// deleted ones.
+fn migrate_v0(doc: &mut Map<String, Value>) -> Result<(), String> {
+    let shapes = doc
+        .get_mut("shapes")
+        .and_then(Value::as_object_mut)
+        .ok_or("missing 'shapes' object")?;
+    for shape in shapes.values_mut() {
+        fill_shape_defaults(shape, 0)?;
+    }
+    let mut ids: Vec<String> = shapes.keys().cloned().collect();
+
+    let listed = doc
+        .get("z_order")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    let mut seen = HashSet::new();
+    let mut z_order: Vec<String> = listed
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|id| ids.iter().any(|known| known == id) && seen.insert(id.to_string()))
+        .map(str::to_string)
+        .collect();
+    // Shapes missing from the z-order go on top, in a stable order
+    ids.retain(|id| !seen.contains(id));
+    ids.sort();
+    z_order.extend(ids);
+    doc.insert("z_order".to_string(), json!(z_order));
+    Ok(())
+}
+
+/// Insert the fields of a version 0 shape that could be missing.
+fn fill_shape_defaults(shape: &mut Value, depth: usize) -> Result<(), String> {
+    if depth > 64 {
+        return Err("groups nested too deeply".to_string());
+    }
+    let Some((kind, body)) = shape.as_object_mut().and_then(|o| o.iter_mut().next()) else {
+        return Err("shape is not an object".to_string());
+    };
+    let body = body
+        .as_object_mut()
+        .ok_or_else(|| format!("{kind} is not an object"))?;
+    let defaults = match kind.as_str() {
+        "Rectangle" | "Ellipse" | "Image" | "Math" | "Group" => vec![("rotation", json!(0.0))],
+        "Text" => vec![("rotation", json!(0.0)), ("char_colors", json!([]))],
+        "Line" | "Arrow" => vec![
+            ("intermediate_points", json!([])),
+            ("path_style", json!("Direct")),
+            ("stroke_style", json!("Solid")),
+        ],
+        "Freehand" => vec![("pressures", json!([]))],
+        _ => return Err(format!("unknown shape type '{kind}'")),
+    };
+    for (key, value) in defaults {
+        body.entry(key).or_insert(value);
+    }
+    if let Some(style) = body.get_mut("style").and_then(Value::as_object_mut) {
+        style.entry("fill_pattern").or_insert(json!("Solid"));
+        style.entry("opacity").or_insert(json!(1.0));
+    }
+    if kind == "Group" {
+        let children = body
+            .get_mut("children")
+            .and_then(Value::as_array_mut)
+            .ok_or("group without 'children'")?;
+        for child in children {
+            fill_shape_defaults(child, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+/// Version 1 to 2: images refer to content-addressed assets.
+///
+/// Moves the inline base64 data of every image into the document's asset table,
+/// where each distinct content is stored once.
+fn migrate_v1(doc: &mut Map<String, Value>) -> Result<(), String> {
+    let mut table = Map::new();
+    assets::for_each_image(doc, &mut |image| {
+        let data = match image.remove(INLINE_DATA_KEY) {
+            Some(Value::String(data)) => data,
+            _ => return Err("image without data".to_string()),
+        };
+        let asset = Asset::from_base64(&data).ok_or("invalid image data")?;
+        image.insert(ASSET_REF_KEY.to_string(), json!(asset.id()));
+        table
+            .entry(asset.id().to_string())
+            .or_insert(Value::String(data));
+        Ok(())
+    })?;
+    doc.insert(ASSETS_KEY.to_string(), Value::Object(table));
+    Ok(())
+}
+
+/// Version 2 to 3: documents carry named checkpoints.
+fn migrate_v2(doc: &mut Map<String, Value>) -> Result<(), String> {
+    doc.entry("checkpoints").or_insert_with(|| json!([]));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas::CanvasDocument;
+    use crate::shapes::{Rectangle, Shape};
+    use kurbo::Point;
+    use std::path::Path;
+
+    fn corpus(version: u32) -> Vec<(String, String)> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("corpus")
+            .join(format!("v{version}"));
+        let mut files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap_or_else(|_| panic!("missing corpus directory {}", dir.display()))
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .collect();
+        files.sort();
+        files
+            .into_iter()
+            .map(|path| {
+                let name = path.file_name().unwrap().to_string_lossy().into_owned();
+                (name, std::fs::read_to_string(&path).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_corpus_loads() {
+        for version in 0..=FORMAT_VERSION {
+            let files = corpus(version);
+            assert!(
+                !files.is_empty(),
+                "no corpus documents for version {version}"
+            );
+            for (name, json) in files {
+                let value: Value = serde_json::from_str(&json).unwrap();
+                assert_eq!(version_of(&value), Ok(version), "{name}");
+
+                let doc = CanvasDocument::from_json(&json)
+                    .unwrap_or_else(|e| panic!("v{version}/{name}: {e}"));
+                assert!(!doc.is_empty(), "v{version}/{name}");
+                assert_eq!(doc.z_order.len(), doc.shapes.len(), "v{version}/{name}");
+                assert!(doc.z_order.iter().all(|id| doc.shapes.contains_key(id)));
+
+                // Saving writes the current version, and loads back the same shapes
+                let saved = doc.to_json().unwrap();
+                let value: Value = serde_json::from_str(&saved).unwrap();
+                assert_eq!(version_of(&value), Ok(FORMAT_VERSION));
+                let reloaded = CanvasDocument::from_json(&saved).unwrap();
+                assert_eq!(reloaded.z_order, doc.z_order, "v{version}/{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_v0_migration() {
+        let doc = json!({
+            "id": "legacy",
+            "name": "Legacy",
+            "shapes": {
+                "6f1c0a3e-0000-4000-8000-000000000001": { "Line": {
+                    "id": "6f1c0a3e-0000-4000-8000-000000000001",
+                    "start": { "x": 0.0, "y": 0.0 },
+                    "end": { "x": 10.0, "y": 0.0 },
+                    "style": {
+                        "stroke_color": { "r": 0, "g": 0, "b": 0, "a": 255 },
+                        "stroke_width": 2.0,
+                        "fill_color": null,
+                        "sloppiness": "Artist"
+                    }
+                }}
+            },
+            "z_order": ["6f1c0a3e-0000-4000-8000-00000000dead"]
+        });
+        let upgraded = upgrade(doc).unwrap();
+        assert_eq!(upgraded[VERSION_KEY], json!(FORMAT_VERSION));
+        let line = &upgraded["shapes"]["6f1c0a3e-0000-4000-8000-000000000001"]["Line"];
+        assert_eq!(line["intermediate_points"], json!([]));
+        assert_eq!(line["path_style"], json!("Direct"));
+        assert_eq!(line["style"]["fill_pattern"], json!("Solid"));
+        assert_eq!(line["style"]["opacity"], json!(1.0));
+        // The deleted shape is dropped and the unlisted one added
+        assert_eq!(
+            upgraded["z_order"],
+            json!(["6f1c0a3e-0000-4000-8000-000000000001"])
+        );
+    }
+
+    #[test]
+    fn test_v1_migration() {
+        let image = |id: &str| {
+            json!({ "Image": {
+                "id": id,
+                "data_base64": "AAEC",
+            }})
+        };
+        let doc = json!({
+            "format_version": 1,
+            "shapes": {
+                "a": image("a"),
+                "g": { "Group": { "id": "g", "children": [image("b")] } },
+            },
+        });
+        let upgraded = upgrade(doc).unwrap();
+        let id = crate::assets::asset_id(&[0, 1, 2]);
+        assert_eq!(upgraded[ASSETS_KEY], json!({ id.clone(): "AAEC" }));
+        assert_eq!(
+            upgraded["shapes"]["a"]["Image"],
+            json!({ "id": "a", "asset": id })
+        );
+        assert_eq!(
+            upgraded["shapes"]["g"]["Group"]["children"][0]["Image"]["asset"],
+            json!(id)
+        );
+
+        let broken = json!({
+            "format_version": 1,
+            "shapes": { "a": { "Image": { "id": "a", "data_base64": "not base64!" } } },
+        });
+        assert!(matches!(
+            upgrade(broken),
+            Err(FormatError::Migration { from: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_v2_migration() {
+        let doc = json!({ "format_version": 2, "assets": {}, "shapes": {}, "z_order": [] });
+        let upgraded = upgrade(doc).unwrap();
+        assert_eq!(upgraded["checkpoints"], json!([]));
+        assert_eq!(upgraded[VERSION_KEY], json!(3));
+    }
+
+    #[test]
+    fn test_newer_versions_are_refused() {
+        let doc = json!({ "format_version": FORMAT_VERSION + 1, "shapes": {} });
+        let error = upgrade(doc).unwrap_err();
+        assert_eq!(
+            error,
+            FormatError::TooNew {
+                found: FORMAT_VERSION + 1,
+                supported: FORMAT_VERSION,
+            }
+        );
+        assert!(error.to_string().contains("Please update DrafftInk"));
+
+        let json = format!(r#"{{"format_version": {}}}"#, FORMAT_VERSION + 1);
+        assert!(matches!(
+            CanvasDocument::from_json(&json),
+            Err(FormatError::TooNew { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_documents() {
+        assert_eq!(version_of(&json!([])), Err(FormatError::NotAnObject));
+        assert_eq!(
+            version_of(&json!({ "format_version": "two" })),
+            Err(FormatError::InvalidVersion("\"two\"".to_string()))
+        );
+        assert!(matches!(
+            CanvasDocument::from_json("{ nope"),
+            Err(FormatError::Json(_))
+        ));
+        assert!(matches!(
+            upgrade(json!({ "shapes": { "x": { "Hexagon": {} } } })),
+            Err(FormatError::Migration { from: 0, .. })
+        ));
+        assert!(matches!(
+            CanvasDocument::from_json(r#"{"format_version": 1, "shapes": {}}"#),
+            Err(FormatError::Document(_))
+        ));
+    }
+
+    #[test]
+    fn test_saved_documents_are_versioned() {
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(Point::ZERO, 10.0, 20.0)));
+        let value = doc.to_value().unwrap();
+        assert_eq!(value[VERSION_KEY], json!(FORMAT_VERSION));
+
+        // Current documents pass through unchanged
+        assert_eq!(upgrade(value.clone()).unwrap(), value);
+        let loaded = CanvasDocument::from_value(value).unwrap();
+        assert_eq!(loaded.z_order, doc.z_order);
+    }
+}
@@ -0,0 +1,18 @@
+//! Field accessors shared by the JSON importers (Excalidraw, tldraw).
+
+use serde_json::Value;
+
+/// A string field, if present.
+pub(crate) fn str_field<'a>(value: &'a Value, key: &str) -> Option<&'a str> {
+    value.get(key).and_then(Value::as_str)
+}
+
+/// A number field, or `default` if missing or not a number.
+pub(crate) fn f64_field(value: &Value, key: &str, default: f64) -> f64 {
+    value.get(key).and_then(Value::as_f64).unwrap_or(default)
+}
+
+/// A boolean field, or false if missing or not a boolean.
+pub(crate) fn bool_field(value: &Value, key: &str) -> bool {
+    value.get(key).and_then(Value::as_bool).unwrap_or(false)
+}
@@ -0,0 +1,860 @@
+//! Automatic graph layout for connected diagrams.
+//!
+//! Selected shapes are treated as nodes and arrows whose endpoints touch them as edges.
+//! A layout computes a translation per node; [`LayoutAnimation`] moves the nodes there
+//! over time and re-routes the connecting arrows as it goes.
+
+use crate::canvas::CanvasDocument;
+use crate::shapes::{Arrow, PathStyle, Shape, ShapeId, ShapeTrait};
+use kurbo::{Affine, Point, Rect, Size, Vec2};
+use std::collections::HashSet;
+
+/// Maximum distance between an arrow endpoint and a shape for the arrow to connect to it.
+pub const CONNECTION_TOLERANCE: f64 = 24.0;
+
+/// Gap left between a re-routed connector and the shapes it connects.
+const CONNECTOR_GAP: f64 = 4.0;
+
+/// Duration of the layout animation in seconds.
+pub const LAYOUT_ANIMATION_SECS: f64 = 0.4;
+
+/// Flow direction of a layered layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayoutDirection {
+    /// Layers stacked from top to bottom.
+    #[default]
+    TopDown,
+    /// Layers placed from left to right.
+    LeftRight,
+}
+
+/// Layout algorithm.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutAlgorithm {
+    /// Sugiyama-style layered layout following the arrow direction.
+    Layered(LayoutDirection),
+    /// Force-directed (spring) layout.
+    ForceDirected,
+}
+
+/// Options for [`Graph::layout`].
+#[derive(Debug, Clone, Copy)]
+pub struct LayoutOptions {
+    /// Algorithm to run.
+    pub algorithm: LayoutAlgorithm,
+    /// Gap between neighbouring nodes in the same layer.
+    pub node_gap: f64,
+    /// Gap between consecutive layers.
+    pub layer_gap: f64,
+    /// Re-route connectors as elbow (right-angle) paths.
+    pub elbow_connectors: bool,
+}
+
+impl Default for LayoutOptions {
+    fn default() -> Self {
+        Self {
+            algorithm: LayoutAlgorithm::Layered(LayoutDirection::TopDown),
+            node_gap: 40.0,
+            layer_gap: 80.0,
+            elbow_connectors: false,
+        }
+    }
+}
+
+/// A node of the layout graph.
+#[derive(Debug, Clone)]
+pub struct GraphNode {
+    /// Shape ID of the node.
+    pub id: ShapeId,
+    /// Visual bounds of the shape.
+    pub bounds: Rect,
+}
+
+/// A directed edge of the layout graph, backed by an arrow.
+#[derive(Debug, Clone)]
+pub struct GraphEdge {
+    /// Shape ID of the arrow.
+    pub arrow: ShapeId,
+    /// Index of the source node.
+    pub from: usize,
+    /// Index of the target node.
+    pub to: usize,
+}
+
+/// Graph of shapes connected by arrows.
+#[derive(Debug, Clone, Default)]
+pub struct Graph {
+    /// Nodes, in selection order.
+    pub nodes: Vec<GraphNode>,
+    /// Edges between distinct nodes.
+    pub edges: Vec<GraphEdge>,
+}
+
+impl Graph {
+    /// Build a graph from the selected shapes.
+    /// Lines and arrows are never nodes; any top-level arrow whose endpoints touch two
+    /// different nodes becomes an edge, whether or not it is selected.
+    pub fn from_selection(doc: &CanvasDocument, selection: &[ShapeId]) -> Self {
+        let nodes: Vec<GraphNode> = selection
+            .iter()
+            .filter_map(|&id| doc.get_shape(id))
+            .filter(|shape| !matches!(shape, Shape::Arrow(_) | Shape::Line(_)))
+            .map(|shape| GraphNode {
+                id: shape.id(),
+                bounds: shape.rotated_bounds(),
+            })
+            .collect();
+
+        let edges = doc
+            .shapes_ordered()
+            .filter_map(|shape| match shape {
+                Shape::Arrow(arrow) => Some(arrow),
+                _ => None,
+            })
+            .filter_map(|arrow| {
+                let from = attached_node(&nodes, arrow.start)?;
+                let to = attached_node(&nodes, arrow.end)?;
+                (from != to).then_some(GraphEdge {
+                    arrow: arrow.id(),
+                    from,
+                    to,
+                })
+            })
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    /// Compute the translation for each node (in node order).
+    /// The result keeps the top-left corner of the layered layout, or the centroid of the
+    /// force-directed layout, where the nodes currently are.
+    pub fn layout(&self, options: &LayoutOptions) -> Vec<Vec2> {
+        if self.nodes.len() < 2 {
+            return vec![Vec2::ZERO; self.nodes.len()];
+        }
+        let bounds: Vec<Rect> = self.nodes.iter().map(|n| n.bounds).collect();
+        let edges: Vec<(usize, usize)> = self.edges.iter().map(|e| (e.from, e.to)).collect();
+
+        match options.algorithm {
+            LayoutAlgorithm::Layered(direction) => {
+                let centers = layered_layout(
+                    &bounds,
+                    &edges,
+                    direction,
+                    options.node_gap,
+                    options.layer_gap,
+                );
+                let target = union_at_centers(&bounds, &centers);
+                let current = union(&bounds);
+                let anchor = current.origin() - target.origin();
+                centers
+                    .iter()
+                    .zip(&bounds)
+                    .map(|(&c, b)| c + anchor - b.center())
+                    .collect()
+            }
+            LayoutAlgorithm::ForceDirected => {
+                let centers = force_layout(&bounds, &edges, options.node_gap);
+                let anchor =
+                    centroid(bounds.iter().map(|b| b.center())) - centroid(centers.iter().copied());
+                centers
+                    .iter()
+                    .zip(&bounds)
+                    .map(|(&c, b)| c + anchor - b.center())
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Find the node whose bounds are closest to `point`, within [`CONNECTION_TOLERANCE`].
+fn attached_node(nodes: &[GraphNode], point: Point) -> Option<usize> {
+    nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (i, distance_to_rect(node.bounds, point)))
+        .filter(|&(_, d)| d <= CONNECTION_TOLERANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(i, _)| i)
+}
+
+/// Distance from a point to a rectangle (0 inside).
+pub(crate) fn distance_to_rect(rect: Rect, point: Point) -> f64 {
+    let dx = (rect.x0 - point.x).max(point.x - rect.x1).max(0.0);
+    let dy = (rect.y0 - point.y).max(point.y - rect.y1).max(0.0);
+    dx.hypot(dy)
+}
+
+fn union(bounds: &[Rect]) -> Rect {
+    bounds
+        .iter()
+        .copied()
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Rect::ZERO)
+}
+
+fn union_at_centers(bounds: &[Rect], centers: &[Point]) -> Rect {
+    let moved: Vec<Rect> = bounds
+        .iter()
+        .zip(centers)
+        .map(|(b, &c)| Rect::from_center_size(c, b.size()))
+        .collect();
+    union(&moved)
+}
+
+fn centroid(points: impl Iterator<Item = Point>) -> Point {
+    let (sum, count) = points.fold((Vec2::ZERO, 0usize), |(sum, n), p| {
+        (sum + p.to_vec2(), n + 1)
+    });
+    if count == 0 {
+        Point::ZERO
+    } else {
+        (sum / count as f64).to_point()
+    }
+}
+
+/// Layered (Sugiyama-style) layout. Returns the new center of each node.
+///
+/// Cycles are broken by reversing back edges, layers are assigned by longest path,
+/// long edges get virtual nodes, and crossings are reduced with barycenter sweeps.
+pub(crate) fn layered_layout(
+    bounds: &[Rect],
+    edges: &[(usize, usize)],
+    direction: LayoutDirection,
+    node_gap: f64,
+    layer_gap: f64,
+) -> Vec<Point> {
+    let n = bounds.len();
+    // Sizes along the layer (cross) axis and the flow (main) axis
+    let (cross_size, main_size): (Vec<f64>, Vec<f64>) = bounds
+        .iter()
+        .map(|b| match direction {
+            LayoutDirection::TopDown => (b.width(), b.height()),
+            LayoutDirection::LeftRight => (b.height(), b.width()),
+        })
+        .unzip();
+    let cross_pos: Vec<f64> = bounds
+        .iter()
+        .map(|b| match direction {
+            LayoutDirection::TopDown => b.center().x,
+            LayoutDirection::LeftRight => b.center().y,
+        })
+        .collect();
+
+    let edges = acyclic_edges(n, edges);
+    let layer = assign_layers(n, &edges);
+
+    // Virtual nodes split edges spanning several layers: (layer, predecessor)
+    let mut node_layer: Vec<usize> = layer.clone();
+    let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut virtual_cross: Vec<f64> = Vec::new();
+    for &(from, to) in &edges {
+        let mut prev = from;
+        for l in (layer[from] + 1)..layer[to] {
+            let v = node_layer.len();
+            node_layer.push(l);
+            preds.push(vec![prev]);
+            virtual_cross.push((cross_pos[from] + cross_pos[to]) / 2.0);
+            prev = v;
+        }
+        preds[to].push(prev);
+    }
+    let total = node_layer.len();
+    let cross_size_of = |v: usize| if v < n { cross_size[v] } else { 0.0 };
+    let initial_cross = |v: usize| {
+        if v < n {
+            cross_pos[v]
+        } else {
+            virtual_cross[v - n]
+        }
+    };
+
+    let layer_count = layer.iter().max().map_or(0, |&m| m + 1);
+    let mut layers: Vec<Vec<usize>> = vec![Vec::new(); layer_count];
+    for (v, &l) in node_layer.iter().enumerate() {
+        layers[l].push(v);
+    }
+    // Start from the current arrangement so the result keeps the user's ordering
+    for nodes in &mut layers {
+        nodes.sort_by(|&a, &b| initial_cross(a).total_cmp(&initial_cross(b)));
+    }
+
+    let mut succs: Vec<Vec<usize>> = vec![Vec::new(); total];
+    for (v, ps) in preds.iter().enumerate() {
+        for &p in ps {
+            succs[p].push(v);
+        }
+    }
+
+    // Barycenter sweeps (down using predecessors, up using successors)
+    let mut position = vec![0.0; total];
+    for nodes in &layers {
+        for (i, &v) in nodes.iter().enumerate() {
+            position[v] = i as f64;
+        }
+    }
+    for sweep in 0..8 {
+        let down = sweep % 2 == 0;
+        let order: Vec<usize> = if down {
+            (1..layer_count).collect()
+        } else {
+            (0..layer_count.saturating_sub(1)).rev().collect()
+        };
+        for l in order {
+            let neighbours = if down { &preds } else { &succs };
+            let barycenter: Vec<(usize, f64)> = layers[l]
+                .iter()
+                .map(|&v| {
+                    let adj = &neighbours[v];
+                    let value = if adj.is_empty() {
+                        position[v]
+                    } else {
+                        adj.iter().map(|&u| position[u]).sum::<f64>() / adj.len() as f64
+                    };
+                    (v, value)
+                })
+                .collect();
+            let mut sorted = barycenter;
+            sorted.sort_by(|a, b| a.1.total_cmp(&b.1));
+            layers[l] = sorted.into_iter().map(|(v, _)| v).collect();
+            for (i, &v) in layers[l].iter().enumerate() {
+                position[v] = i as f64;
+            }
+        }
+    }
+
+    // Assign coordinates: layers centered on the cross axis, stacked on the main axis
+    let mut centers = vec![Point::ZERO; n];
+    let mut main = 0.0;
+    for nodes in &layers {
+        let thickness = nodes
+            .iter()
+            .filter(|&&v| v < n)
+            .map(|&v| main_size[v])
+            .fold(0.0, f64::max);
+        let width: f64 = nodes.iter().map(|&v| cross_size_of(v)).sum::<f64>()
+            + node_gap * nodes.len().saturating_sub(1) as f64;
+        let mut cross = -width / 2.0;
+        for &v in nodes {
+            let size = cross_size_of(v);
+            if v < n {
+                let (c, m) = (cross + size / 2.0, main + thickness / 2.0);
+                centers[v] = match direction {
+                    LayoutDirection::TopDown => Point::new(c, m),
+                    LayoutDirection::LeftRight => Point::new(m, c),
+                };
+            }
+            cross += size + node_gap;
+        }
+        main += thickness + layer_gap;
+    }
+    centers
+}
+
+/// Layered placement for imported diagrams, given node sizes.
+/// `reversed` flips the flow (bottom-to-top or right-to-left). Returns node bounds
+/// with the top-left corner of the diagram at the origin.
+pub(crate) fn layered_diagram(
+    sizes: &[Size],
+    edges: &[(usize, usize)],
+    direction: LayoutDirection,
+    reversed: bool,
+    node_gap: f64,
+    layer_gap: f64,
+) -> Vec<Rect> {
+    let bounds: Vec<Rect> = sizes
+        .iter()
+        .map(|&size| Rect::from_origin_size(Point::ZERO, size))
+        .collect();
+    let mut centers = layered_layout(&bounds, edges, direction, node_gap, layer_gap);
+    if reversed {
+        for center in &mut centers {
+            match direction {
+                LayoutDirection::TopDown => center.y = -center.y,
+                LayoutDirection::LeftRight => center.x = -center.x,
+            }
+        }
+    }
+    let rects: Vec<Rect> = centers
+        .iter()
+        .zip(sizes)
+        .map(|(&c, &s)| Rect::from_center_size(c, s))
+        .collect();
+    let origin = rects
+        .iter()
+        .copied()
+        .reduce(|a, b| a.union(b))
+        .map_or(Vec2::ZERO, |extent| extent.origin().to_vec2());
+    rects.into_iter().map(|r| r - origin).collect()
+}
+
+/// Remove self-loops and duplicates, and reverse edges that close a cycle (found by DFS).
+fn acyclic_edges(n: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    let mut adjacency: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut seen = HashSet::new();
+    for &(from, to) in edges {
+        if from != to && seen.insert((from, to)) {
+            adjacency[from].push(to);
+        }
+    }
+
+    // 0 = unvisited, 1 = on stack, 2 = done
+    let mut state = vec![0u8; n];
+    let mut result = Vec::new();
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0usize)];
+        state[root] = 1;
+        while let Some((v, next)) = stack.pop() {
+            if let Some(&to) = adjacency[v].get(next) {
+                stack.push((v, next + 1));
+                match state[to] {
+                    0 => {
+                        result.push((v, to));
+                        state[to] = 1;
+                        stack.push((to, 0));
+                    }
+                    1 => result.push((to, v)), // back edge
+                    _ => result.push((v, to)),
+                }
+            } else {
+                state[v] = 2;
+            }
+        }
+    }
+    let mut unique = HashSet::new();
+    result.retain(|&e| unique.insert(e));
+    result
+}
+
+/// Longest-path layering of a DAG: sources are in layer 0.
+fn assign_layers(n: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+    let mut indegree = vec![0usize; n];
+    let mut succs: Vec<Vec<usize>> = vec![Vec::new(); n];
+    for &(from, to) in edges {
+        succs[from].push(to);
+        indegree[to] += 1;
+    }
+    let mut layer = vec![0usize; n];
+    let mut queue: Vec<usize> = (0..n).filter(|&v| indegree[v] == 0).collect();
+    while let Some(v) = queue.pop() {
+        for &to in &succs[v] {
+            layer[to] = layer[to].max(layer[v] + 1);
+            indegree[to] -= 1;
+            if indegree[to] == 0 {
+                queue.push(to);
+            }
+        }
+    }
+    layer
+}
+
+/// Force-directed (Fruchterman-Reingold) layout starting from the current positions,
+/// followed by overlap removal. Returns the new center of each node.
+fn force_layout(bounds: &[Rect], edges: &[(usize, usize)], node_gap: f64) -> Vec<Point> {
+    const ITERATIONS: usize = 300;
+    let n = bounds.len();
+    let radius: Vec<f64> = bounds
+        .iter()
+        .map(|b| b.width().hypot(b.height()) / 2.0)
+        .collect();
+    let ideal = 2.0 * radius.iter().sum::<f64>() / n as f64 + node_gap;
+
+    let mut pos: Vec<Vec2> = bounds.iter().map(|b| b.center().to_vec2()).collect();
+    // Spread coincident nodes on a circle so forces have a direction
+    for i in 0..n {
+        if (0..i).any(|j| (pos[i] - pos[j]).hypot() < 1e-6) {
+            let angle = i as f64 * std::f64::consts::TAU / n as f64;
+            pos[i] += Vec2::new(angle.cos(), angle.sin()) * ideal;
+        }
+    }
+
+    let mut temperature = ideal * 2.0;
+    let cooling = temperature / ITERATIONS as f64;
+    for _ in 0..ITERATIONS {
+        let mut disp = vec![Vec2::ZERO; n];
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let delta = pos[i] - pos[j];
+                let dist = delta.hypot().max(0.01);
+                let force = ideal * ideal / dist;
+                let push = delta / dist * force;
+                disp[i] += push;
+                disp[j] -= push;
+            }
+        }
+        for &(from, to) in edges {
+            if from == to {
+                continue;
+            }
+            let delta = pos[from] - pos[to];
+            let dist = delta.hypot().max(0.01);
+            let pull = delta / dist * (dist * dist / ideal);
+            disp[from] -= pull;
+            disp[to] += pull;
+        }
+        for (p, d) in pos.iter_mut().zip(&disp) {
+            let len = d.hypot();
+            if len > 0.0 {
+                *p += *d / len * len.min(temperature);
+            }
+        }
+        temperature = (temperature - cooling).max(0.01);
+    }
+
+    // Push overlapping nodes apart along the axis of least overlap
+    for _ in 0..50 {
+        let mut moved = false;
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let a = Rect::from_center_size(pos[i].to_point(), bounds[i].size());
+                let b = Rect::from_center_size(pos[j].to_point(), bounds[j].size());
+                let overlap = a.inflate(node_gap / 2.0, node_gap / 2.0).intersect(b);
+                if overlap.width() <= 0.0 || overlap.height() <= 0.0 {
+                    continue;
+                }
+                moved = true;
+                let delta = pos[j] - pos[i];
+                let shift = if overlap.width() < overlap.height() {
+                    Vec2::new(overlap.width().copysign(delta.x) / 2.0, 0.0)
+                } else {
+                    Vec2::new(0.0, overlap.height().copysign(delta.y) / 2.0)
+                };
+                pos[i] -= shift;
+                pos[j] += shift;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    pos.into_iter().map(Vec2::to_point).collect()
+}
+
+/// Re-route an arrow between two shapes' bounds.
+/// Elbow connectors leave and enter through the facing sides and use automatic
+/// right-angle routing; otherwise the arrow is a straight line between the bounds.
+pub fn route_connector(arrow: &mut Arrow, from: Rect, to: Rect, elbow: bool) {
+    let (start, end) = connector_endpoints(from, to, elbow);
+    arrow.start = start;
+    arrow.end = end;
+    arrow.intermediate_points.clear();
+    if elbow {
+        // Angular arrows without intermediate points are routed with `elbow::compute_elbow_path`
+        arrow.path_style = PathStyle::Angular;
+    }
+}
+
+/// Start and end points of a connector between two shapes' bounds.
+/// Elbow connectors use the midpoints of the facing sides; straight connectors
+/// point from center to center and stop at the bounds.
+pub fn connector_endpoints(from: Rect, to: Rect, elbow: bool) -> (Point, Point) {
+    let from = from.inflate(CONNECTOR_GAP, CONNECTOR_GAP);
+    let to = to.inflate(CONNECTOR_GAP, CONNECTOR_GAP);
+    if elbow {
+        facing_sides(from, to)
+    } else {
+        (
+            boundary_point(from, to.center()),
+            boundary_point(to, from.center()),
+        )
+    }
+}
+
+/// Midpoints of the sides of `from` and `to` that face each other.
+fn facing_sides(from: Rect, to: Rect) -> (Point, Point) {
+    let delta = to.center() - from.center();
+    if delta.x.abs() > delta.y.abs() {
+        let (fx, tx) = if delta.x > 0.0 {
+            (from.x1, to.x0)
+        } else {
+            (from.x0, to.x1)
+        };
+        (
+            Point::new(fx, from.center().y),
+            Point::new(tx, to.center().y),
+        )
+    } else {
+        let (fy, ty) = if delta.y > 0.0 {
+            (from.y1, to.y0)
+        } else {
+            (from.y0, to.y1)
+        };
+        (
+            Point::new(from.center().x, fy),
+            Point::new(to.center().x, ty),
+        )
+    }
+}
+
+/// Point where the ray from the center of `rect` toward `toward` leaves the rectangle.
+fn boundary_point(rect: Rect, toward: Point) -> Point {
+    let center = rect.center();
+    let delta = toward - center;
+    let sx = if delta.x.abs() > f64::EPSILON {
+        rect.width() / 2.0 / delta.x.abs()
+    } else {
+        f64::INFINITY
+    };
+    let sy = if delta.y.abs() > f64::EPSILON {
+        rect.height() / 2.0 / delta.y.abs()
+    } else {
+        f64::INFINITY
+    };
+    let scale = sx.min(sy);
+    if scale.is_finite() {
+        center + delta * scale.min(1.0)
+    } else {
+        center
+    }
+}
+
+/// Animates nodes from their current positions to a computed layout,
+/// re-routing connecting arrows on every step.
+#[derive(Debug, Clone)]
+pub struct LayoutAnimation {
+    /// Nodes with their original shape and target translation.
+    nodes: Vec<(Shape, Vec2)>,
+    /// Arrows with their original shape and the indices of the nodes they connect.
+    edges: Vec<(Shape, usize, usize)>,
+    /// Route connectors as elbows.
+    elbow: bool,
+}
+
+impl LayoutAnimation {
+    /// Run the layout on the selected shapes of `doc` and prepare the animation.
+    /// Returns None if fewer than 2 nodes are selected.
+    pub fn new(
+        doc: &CanvasDocument,
+        selection: &[ShapeId],
+        options: &LayoutOptions,
+    ) -> Option<Self> {
+        let graph = Graph::from_selection(doc, selection);
+        if graph.nodes.len() < 2 {
+            return None;
+        }
+        let offsets = graph.layout(options);
+        let nodes = graph
+            .nodes
+            .iter()
+            .zip(offsets)
+            .filter_map(|(node, offset)| Some((doc.get_shape(node.id)?.clone(), offset)))
+            .collect();
+        let edges = graph
+            .edges
+            .iter()
+            .filter_map(|edge| Some((doc.get_shape(edge.arrow)?.clone(), edge.from, edge.to)))
+            .collect();
+        Some(Self {
+            nodes,
+            edges,
+            elbow: options.elbow_connectors,
+        })
+    }
+
+    /// Place nodes at `progress` (0.0 = start, 1.0 = final layout) with ease-out timing.
+    pub fn apply(&self, doc: &mut CanvasDocument, progress: f64) {
+        let t = progress.clamp(0.0, 1.0);
+        let eased = 1.0 - (1.0 - t).powi(3);
+
+        let mut current_bounds = Vec::with_capacity(self.nodes.len());
+        for (original, offset) in &self.nodes {
+            let mut shape = original.clone();
+            shape.transform(Affine::translate(*offset * eased));
+            current_bounds.push(shape.rotated_bounds());
+            if let Some(target) = doc.get_shape_mut(original.id()) {
+                *target = shape;
+            }
+        }
+        for (original, from, to) in &self.edges {
+            if let (Shape::Arrow(arrow), Some(target)) =
+                (original, doc.get_shape_mut(original.id()))
+            {
+                let mut arrow = arrow.clone();
+                route_connector(
+                    &mut arrow,
+                    current_bounds[*from],
+                    current_bounds[*to],
+                    self.elbow,
+                );
+                *target = Shape::Arrow(arrow);
+            }
+        }
+    }
+
+    /// Restore all affected shapes to their state before the animation.
+    pub fn restore(&self, doc: &mut CanvasDocument) {
+        let originals = self
+            .nodes
+            .iter()
+            .map(|(shape, _)| shape)
+            .chain(self.edges.iter().map(|(shape, _, _)| shape));
+        for original in originals {
+            if let Some(target) = doc.get_shape_mut(original.id()) {
+                *target = original.clone();
+            }
+        }
+    }
+
+    /// Apply the final layout as a single undo step.
+    pub fn finish(&self, doc: &mut CanvasDocument) {
+        self.restore(doc);
+        doc.push_undo();
+        self.apply(doc, 1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shapes::Rectangle;
+
+    fn boxes(doc: &mut CanvasDocument, origins: &[(f64, f64)]) -> Vec<ShapeId> {
+        origins
+            .iter()
+            .map(|&(x, y)| {
+                let rect = Rectangle::new(Point::new(x, y), 100.0, 50.0);
+                let id = rect.id();
+                doc.add_shape(Shape::Rectangle(rect));
+                id
+            })
+            .collect()
+    }
+
+    fn connect(doc: &mut CanvasDocument, from: ShapeId, to: ShapeId) -> ShapeId {
+        let a = doc.get_shape(from).unwrap().bounds().center();
+        let b = doc.get_shape(to).unwrap().bounds().center();
+        let arrow = Arrow::new(a, b);
+        let id = arrow.id();
+        doc.add_shape(Shape::Arrow(arrow));
+        id
+    }
+
+    #[test]
+    fn test_graph_from_selection() {
+        let mut doc = CanvasDocument::new();
+        let ids = boxes(&mut doc, &[(0.0, 0.0), (300.0, 200.0), (600.0, 0.0)]);
+        let arrow = connect(&mut doc, ids[0], ids[1]);
+        // Arrow to an unselected shape is not an edge
+        connect(&mut doc, ids[1], ids[2]);
+
+        let graph = Graph::from_selection(&doc, &[ids[0], ids[1], arrow]);
+        assert_eq!(graph.nodes.len(), 2);
+        assert_eq!(graph.edges.len(), 1);
+        assert_eq!(graph.edges[0].arrow, arrow);
+        assert_eq!((graph.edges[0].from, graph.edges[0].to), (0, 1));
+    }
+
+    #[test]
+    fn test_layered_top_down() {
+        let mut doc = CanvasDocument::new();
+        // A chain a -> b -> c placed in reverse vertical order, plus a -> c
+        let ids = boxes(&mut doc, &[(0.0, 500.0), (50.0, 250.0), (20.0, 0.0)]);
+        connect(&mut doc, ids[0], ids[1]);
+        connect(&mut doc, ids[1], ids[2]);
+        connect(&mut doc, ids[0], ids[2]);
+
+        let graph = Graph::from_selection(&doc, &ids);
+        let offsets = graph.layout(&LayoutOptions::default());
+        let moved: Vec<Rect> = graph
+            .nodes
+            .iter()
+            .zip(&offsets)
+            .map(|(n, &o)| n.bounds + o)
+            .collect();
+
+        // One node per layer, in arrow order, separated by the layer gap
+        assert!((moved[1].y0 - moved[0].y1 - 80.0).abs() < 1e-9);
+        assert!((moved[2].y0 - moved[1].y1 - 80.0).abs() < 1e-9);
+        // Anchored at the top-left of the original selection
+        assert_eq!(union(&moved).origin(), Point::new(0.0, 0.0));
+    }
+
+    #[test]
+    fn test_layered_handles_cycles() {
+        let mut doc = CanvasDocument::new();
+        let ids = boxes(&mut doc, &[(0.0, 0.0), (300.0, 0.0)]);
+        connect(&mut doc, ids[0], ids[1]);
+        connect(&mut doc, ids[1], ids[0]);
+
+        let graph = Graph::from_selection(&doc, &ids);
+        let options = LayoutOptions {
+            algorithm: LayoutAlgorithm::Layered(LayoutDirection::LeftRight),
+            ..Default::default()
+        };
+        let offsets = graph.layout(&options);
+        let a = graph.nodes[0].bounds + offsets[0];
+        let b = graph.nodes[1].bounds + offsets[1];
+        assert!(b.x0 >= a.x1 + 80.0 - 1e-9);
+    }
+
+    #[test]
+    fn test_force_layout_removes_overlap() {
+        let mut doc = CanvasDocument::new();
+        let ids = boxes(
+            &mut doc,
+            &[(0.0, 0.0), (10.0, 10.0), (20.0, 0.0), (5.0, 30.0)],
+        );
+        connect(&mut doc, ids[0], ids[1]);
+        connect(&mut doc, ids[2], ids[3]);
+
+        let graph = Graph::from_selection(&doc, &ids);
+        let options = LayoutOptions {
+            algorithm: LayoutAlgorithm::ForceDirected,
+            ..Default::default()
+        };
+        let offsets = graph.layout(&options);
+        let moved: Vec<Rect> = graph
+            .nodes
+            .iter()
+            .zip(&offsets)
+            .map(|(n, &o)| n.bounds + o)
+            .collect();
+        for i in 0..moved.len() {
+            for j in (i + 1)..moved.len() {
+                let overlap = moved[i].intersect(moved[j]);
+                assert!(overlap.width() <= 1e-6 || overlap.height() <= 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn test_route_connector_elbow() {
+        let mut arrow = Arrow::new(Point::ZERO, Point::ZERO);
+        let from = Rect::new(0.0, 0.0, 100.0, 50.0);
+        let to = Rect::new(0.0, 200.0, 100.0, 250.0);
+        route_connector(&mut arrow, from, to, true);
+        assert_eq!(arrow.start, Point::new(50.0, 54.0));
+        assert_eq!(arrow.end, Point::new(50.0, 196.0));
+        assert_eq!(arrow.path_style, PathStyle::Angular);
+
+        route_connector(&mut arrow, from, Rect::new(300.0, 0.0, 400.0, 50.0), false);
+        assert_eq!(arrow.start, Point::new(104.0, 25.0));
+        assert_eq!(arrow.end, Point::new(296.0, 25.0));
+    }
+
+    #[test]
+    fn test_animation_single_undo() {
+        let mut doc = CanvasDocument::new();
+        let ids = boxes(&mut doc, &[(0.0, 400.0), (0.0, 0.0)]);
+        let arrow = connect(&mut doc, ids[0], ids[1]);
+        let animation = LayoutAnimation::new(&doc, &ids, &LayoutOptions::default()).unwrap();
+
+        animation.apply(&mut doc, 0.5);
+        animation.finish(&mut doc);
+        let first = doc.get_shape(ids[0]).unwrap().bounds();
+        let second = doc.get_shape(ids[1]).unwrap().bounds();
+        assert!(first.y1 < second.y0);
+        let Some(Shape::Arrow(routed)) = doc.get_shape(arrow) else {
+            panic!("arrow missing");
+        };
+        assert!(routed.start.y < routed.end.y);
+
+        assert!(doc.undo());
+        assert_eq!(doc.get_shape(ids[0]).unwrap().bounds().y0, 400.0);
+        assert!(!doc.can_undo());
+    }
+}
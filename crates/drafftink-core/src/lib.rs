@@ -2,17 +2,33 @@
 //!
 //! Platform-agnostic core data structures and logic for the DrafftInk whiteboard.
 
+pub mod arrange;
+pub mod assets;
+pub mod bundle;
 pub mod camera;
 pub mod canvas;
+pub mod checkpoint;
 pub mod collaboration;
 pub mod crdt;
+pub mod diff;
+pub mod dot;
+pub mod drawio;
+pub mod e2e;
 pub mod elbow;
+pub mod excalidraw;
+pub mod format;
 pub mod input;
+mod json;
+pub mod layout;
+pub mod mermaid;
 pub mod selection;
 pub mod shapes;
 pub mod snap;
 pub mod storage;
+pub mod svg;
 pub mod sync;
+pub mod templates;
+pub mod tldraw;
 pub mod tools;
 pub mod widget;
 
@@ -0,0 +1,1200 @@
+//! Mermaid diagram import.
+//!
+//! Converts Mermaid `flowchart`/`graph` and `sequenceDiagram` sources into shapes.
+//! Flowchart nodes become rectangles, ellipses or closed polygons with text labels and
+//! are placed with the layered layout; sequence diagrams become participant boxes,
+//! lifelines and message arrows.
+
+use crate::layout::{LayoutDirection, connector_endpoints, layered_diagram};
+use crate::shapes::{
+    Arrow, Ellipse, Line, PathStyle, Rectangle, SerializableColor, Shape, ShapeTrait, StrokeStyle,
+    Text,
+};
+use kurbo::{Point, Rect, Size};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while importing a Mermaid diagram.
+#[derive(Debug, Error, PartialEq)]
+pub enum MermaidError {
+    #[error("Empty diagram")]
+    Empty,
+    #[error("Unsupported diagram type: {0}")]
+    Unsupported(String),
+    #[error("Line {line}: {message}")]
+    Syntax { line: usize, message: String },
+}
+
+/// Result type for Mermaid import.
+pub type MermaidResult<T> = Result<T, MermaidError>;
+
+/// Font size for node and participant labels.
+const LABEL_FONT_SIZE: f64 = 20.0;
+/// Font size for edge and message labels.
+const EDGE_FONT_SIZE: f64 = 16.0;
+/// Padding between a label and its node outline.
+const NODE_PADDING: Size = Size::new(24.0, 16.0);
+/// Minimum node size.
+const MIN_NODE_SIZE: Size = Size::new(100.0, 50.0);
+
+const NOTE_FILL: SerializableColor = SerializableColor {
+    r: 254,
+    g: 249,
+    b: 195,
+    a: 255,
+};
+const FRAME_COLOR: SerializableColor = SerializableColor {
+    r: 148,
+    g: 163,
+    b: 184,
+    a: 255,
+};
+
+/// Check whether text looks like a supported Mermaid diagram.
+pub fn is_mermaid(source: &str) -> bool {
+    diagram_header(&strip_fences(source)).is_some_and(|header| {
+        let keyword = header.split_whitespace().next().unwrap_or("");
+        matches!(keyword, "flowchart" | "graph" | "sequenceDiagram")
+    })
+}
+
+/// Parse a Mermaid diagram into shapes, laid out with the top-left corner near the origin.
+pub fn parse_mermaid(source: &str) -> MermaidResult<Vec<Shape>> {
+    let source = strip_fences(source);
+    let header = diagram_header(&source).ok_or(MermaidError::Empty)?;
+    let mut words = header.split_whitespace();
+    let keyword = words.next().unwrap_or("");
+    let body = statements(&source).skip(1);
+
+    match keyword {
+        "flowchart" | "graph" => {
+            let direction = words.next().unwrap_or("TD");
+            let flowchart = Flowchart::parse(direction, body)?;
+            Ok(flowchart.to_shapes())
+        }
+        "sequenceDiagram" => {
+            let diagram = SequenceDiagram::parse(body)?;
+            Ok(diagram.to_shapes())
+        }
+        other => Err(MermaidError::Unsupported(other.to_string())),
+    }
+}
+
+/// Remove Markdown code fences (```` ```mermaid ````) around a diagram.
+fn strip_fences(source: &str) -> String {
+    source
+        .lines()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Statements with their 1-based line numbers, skipping blank lines, comments and directives.
+fn statements(source: &str) -> impl Iterator<Item = (usize, &str)> {
+    source
+        .lines()
+        .enumerate()
+        .flat_map(|(i, line)| line.split(';').map(move |stmt| (i + 1, stmt.trim())))
+        .filter(|(_, stmt)| !stmt.is_empty() && !stmt.starts_with("%%"))
+}
+
+fn diagram_header(source: &str) -> Option<&str> {
+    statements(source).next().map(|(_, stmt)| stmt)
+}
+
+/// Normalize label text: strip quotes and turn `<br>` tags into newlines.
+fn clean_label(text: &str) -> String {
+    let text = text.trim();
+    let text = text
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+        .unwrap_or(text);
+    text.replace("<br/>", "\n")
+        .replace("<br />", "\n")
+        .replace("<br>", "\n")
+        .replace("#quot;", "\"")
+}
+
+/// Create a text shape centered at a point.
+pub(crate) fn centered_text(center: Point, content: &str, font_size: f64) -> Text {
+    let mut text = Text::new(Point::ZERO, content.to_string()).with_font_size(font_size);
+    let size = text.bounds().size();
+    text.position = Point::new(center.x - size.width / 2.0, center.y - size.height / 2.0);
+    text
+}
+
+/// Measure the size of a label.
+pub(crate) fn text_size(content: &str, font_size: f64) -> Size {
+    Text::new(Point::ZERO, content.to_string())
+        .with_font_size(font_size)
+        .bounds()
+        .size()
+}
+
+// ---------------------------------------------------------------------------
+// Flowchart
+// ---------------------------------------------------------------------------
+
+/// Node outline in a flowchart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeShape {
+    Rect,
+    Rounded,
+    Stadium,
+    Subroutine,
+    Cylinder,
+    Circle,
+    DoubleCircle,
+    Asymmetric,
+    Rhombus,
+    Hexagon,
+    Parallelogram,
+    ParallelogramAlt,
+    Trapezoid,
+    TrapezoidAlt,
+}
+
+#[derive(Debug, Clone)]
+struct FlowNode {
+    label: String,
+    shape: NodeShape,
+}
+
+/// Line style of a flowchart link.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkStroke {
+    Normal,
+    Thick,
+    Dotted,
+    Invisible,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Link {
+    stroke: LinkStroke,
+    /// Whether the link ends in an arrowhead.
+    head: bool,
+    label: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+struct FlowEdge {
+    from: usize,
+    to: usize,
+    link: Link,
+}
+
+#[derive(Debug, Default)]
+struct Flowchart {
+    direction: String,
+    nodes: Vec<FlowNode>,
+    ids: HashMap<String, usize>,
+    edges: Vec<FlowEdge>,
+}
+
+impl Flowchart {
+    fn parse<'a>(
+        direction: &str,
+        statements: impl Iterator<Item = (usize, &'a str)>,
+    ) -> MermaidResult<Self> {
+        let mut chart = Flowchart {
+            direction: direction.to_uppercase(),
+            ..Default::default()
+        };
+        for (line, stmt) in statements {
+            let keyword = stmt.split_whitespace().next().unwrap_or("");
+            if matches!(
+                keyword,
+                "subgraph"
+                    | "end"
+                    | "direction"
+                    | "classDef"
+                    | "class"
+                    | "style"
+                    | "linkStyle"
+                    | "click"
+            ) {
+                continue;
+            }
+            chart
+                .parse_statement(stmt)
+                .map_err(|message| MermaidError::Syntax { line, message })?;
+        }
+        Ok(chart)
+    }
+
+    /// Parse a chain like `A & B --> C -- text --> D[Label]`.
+    fn parse_statement(&mut self, stmt: &str) -> Result<(), String> {
+        let mut cursor = Cursor::new(stmt);
+        let mut previous = self.parse_node_group(&mut cursor)?;
+        loop {
+            cursor.skip_ws();
+            if cursor.at_end() {
+                return Ok(());
+            }
+            let link = cursor
+                .parse_link()
+                .ok_or_else(|| format!("expected a link at '{}'", cursor.rest()))?;
+            let next = self.parse_node_group(&mut cursor)?;
+            for &from in &previous {
+                for &to in &next {
+                    self.edges.push(FlowEdge {
+                        from,
+                        to,
+                        link: link.clone(),
+                    });
+                }
+            }
+            previous = next;
+        }
+    }
+
+    /// Parse one or more node references joined by `&`.
+    fn parse_node_group(&mut self, cursor: &mut Cursor) -> Result<Vec<usize>, String> {
+        let mut group = vec![self.parse_node(cursor)?];
+        loop {
+            cursor.skip_ws();
+            if !cursor.eat("&") {
+                return Ok(group);
+            }
+            group.push(self.parse_node(cursor)?);
+        }
+    }
+
+    fn parse_node(&mut self, cursor: &mut Cursor) -> Result<usize, String> {
+        cursor.skip_ws();
+        let id = cursor.parse_id();
+        if id.is_empty() {
+            return Err(format!("expected a node at '{}'", cursor.rest()));
+        }
+        let definition = cursor.parse_node_shape()?;
+        // Skip a `:::className` suffix
+        if cursor.eat(":::") {
+            cursor.parse_id();
+        }
+
+        let index = match self.ids.get(&id) {
+            Some(&index) => index,
+            None => {
+                self.nodes.push(FlowNode {
+                    label: id.clone(),
+                    shape: NodeShape::Rect,
+                });
+                self.ids.insert(id, self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        if let Some((shape, label)) = definition {
+            self.nodes[index] = FlowNode { label, shape };
+        }
+        Ok(index)
+    }
+
+    fn direction(&self) -> (LayoutDirection, bool) {
+        match self.direction.as_str() {
+            "LR" => (LayoutDirection::LeftRight, false),
+            "RL" => (LayoutDirection::LeftRight, true),
+            "BT" => (LayoutDirection::TopDown, true),
+            _ => (LayoutDirection::TopDown, false),
+        }
+    }
+
+    fn to_shapes(&self) -> Vec<Shape> {
+        let sizes: Vec<Size> = self.nodes.iter().map(node_size).collect();
+        let edges: Vec<(usize, usize)> = self.edges.iter().map(|e| (e.from, e.to)).collect();
+        let (direction, reversed) = self.direction();
+        let node_rects = layered_diagram(&sizes, &edges, direction, reversed, 50.0, 70.0);
+
+        let mut shapes = Vec::new();
+        for (node, rect) in self.nodes.iter().zip(&node_rects) {
+            shapes.extend(node_shapes(node, *rect));
+        }
+        for edge in &self.edges {
+            if edge.link.stroke == LinkStroke::Invisible {
+                continue;
+            }
+            shapes.extend(edge_shapes(
+                &edge.link,
+                node_rects[edge.from],
+                node_rects[edge.to],
+            ));
+        }
+        shapes
+    }
+}
+
+fn node_size(node: &FlowNode) -> Size {
+    let label = text_size(&node.label, LABEL_FONT_SIZE);
+    let width = (label.width + NODE_PADDING.width * 2.0).max(MIN_NODE_SIZE.width);
+    let height = (label.height + NODE_PADDING.height * 2.0).max(MIN_NODE_SIZE.height);
+    match node.shape {
+        NodeShape::Circle | NodeShape::DoubleCircle => {
+            let diameter = label.width.max(label.height) + NODE_PADDING.height * 2.0;
+            Size::new(diameter, diameter)
+        }
+        NodeShape::Rhombus => Size::new(width * 1.4, height * 1.6),
+        NodeShape::Hexagon => Size::new(width + height, height),
+        NodeShape::Parallelogram
+        | NodeShape::ParallelogramAlt
+        | NodeShape::Trapezoid
+        | NodeShape::TrapezoidAlt => Size::new(width + height, height),
+        _ => Size::new(width, height),
+    }
+}
+
+/// Outline shape(s) and label for a node placed in `rect`.
+fn node_shapes(node: &FlowNode, rect: Rect) -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    let center = rect.center();
+    match node.shape {
+        NodeShape::Rect | NodeShape::Asymmetric => {
+            shapes.push(Shape::Rectangle(rectangle(rect, 0.0)));
+        }
+        NodeShape::Rounded => shapes.push(Shape::Rectangle(rectangle(rect, 12.0))),
+        NodeShape::Stadium => {
+            shapes.push(Shape::Rectangle(rectangle(rect, rect.height() / 2.0)));
+        }
+        NodeShape::Cylinder => shapes.push(Shape::Rectangle(rectangle(rect, 16.0))),
+        NodeShape::Subroutine => {
+            shapes.push(Shape::Rectangle(rectangle(rect, 0.0)));
+            shapes.push(Shape::Rectangle(rectangle(rect.inset(-8.0), 0.0)));
+        }
+        NodeShape::Circle => shapes.push(Shape::Ellipse(ellipse(rect))),
+        NodeShape::DoubleCircle => {
+            shapes.push(Shape::Ellipse(ellipse(rect)));
+            shapes.push(Shape::Ellipse(ellipse(rect.inset(-6.0))));
+        }
+        NodeShape::Rhombus => shapes.push(polygon(&[
+            Point::new(center.x, rect.y0),
+            Point::new(rect.x1, center.y),
+            Point::new(center.x, rect.y1),
+            Point::new(rect.x0, center.y),
+        ])),
+        NodeShape::Hexagon => {
+            let inset = rect.height() / 2.0;
+            shapes.push(polygon(&[
+                Point::new(rect.x0 + inset, rect.y0),
+                Point::new(rect.x1 - inset, rect.y0),
+                Point::new(rect.x1, center.y),
+                Point::new(rect.x1 - inset, rect.y1),
+                Point::new(rect.x0 + inset, rect.y1),
+                Point::new(rect.x0, center.y),
+            ]));
+        }
+        NodeShape::Parallelogram | NodeShape::ParallelogramAlt => {
+            let skew = rect.height() / 2.0;
+            let (top, bottom) = if node.shape == NodeShape::Parallelogram {
+                (skew, 0.0)
+            } else {
+                (0.0, skew)
+            };
+            shapes.push(polygon(&[
+                Point::new(rect.x0 + top, rect.y0),
+                Point::new(rect.x1 - bottom, rect.y0),
+                Point::new(rect.x1 - top, rect.y1),
+                Point::new(rect.x0 + bottom, rect.y1),
+            ]));
+        }
+        NodeShape::Trapezoid | NodeShape::TrapezoidAlt => {
+            let inset = rect.height() / 2.0;
+            let (top, bottom) = if node.shape == NodeShape::Trapezoid {
+                (inset, 0.0)
+            } else {
+                (0.0, inset)
+            };
+            shapes.push(polygon(&[
+                Point::new(rect.x0 + top, rect.y0),
+                Point::new(rect.x1 - top, rect.y0),
+                Point::new(rect.x1 - bottom, rect.y1),
+                Point::new(rect.x0 + bottom, rect.y1),
+            ]));
+        }
+    }
+    shapes.push(Shape::Text(centered_text(
+        center,
+        &node.label,
+        LABEL_FONT_SIZE,
+    )));
+    shapes
+}
+
+/// Connector (and optional label) between two node rects.
+fn edge_shapes(link: &Link, from: Rect, to: Rect) -> Vec<Shape> {
+    let (start, end) = connector_endpoints(from, to, false);
+    let mut connector = if link.head {
+        Shape::Arrow(Arrow::new(start, end))
+    } else {
+        Shape::Line(Line::new(start, end))
+    };
+    match (&mut connector, link.stroke) {
+        (Shape::Arrow(arrow), LinkStroke::Dotted) => arrow.stroke_style = StrokeStyle::Dashed,
+        (Shape::Line(line), LinkStroke::Dotted) => line.stroke_style = StrokeStyle::Dashed,
+        _ => {}
+    }
+    if link.stroke == LinkStroke::Thick {
+        connector.style_mut().stroke_width = 4.0;
+    }
+
+    let mut shapes = vec![connector];
+    if let Some(label) = &link.label {
+        let mid = start.midpoint(end);
+        shapes.push(Shape::Text(centered_text(mid, label, EDGE_FONT_SIZE)));
+    }
+    shapes
+}
+
+pub(crate) fn rectangle(rect: Rect, corner_radius: f64) -> Rectangle {
+    let mut shape = Rectangle::new(rect.origin(), rect.width(), rect.height());
+    shape.corner_radius = corner_radius;
+    shape
+}
+
+pub(crate) fn ellipse(rect: Rect) -> Ellipse {
+    Ellipse::new(rect.center(), rect.width() / 2.0, rect.height() / 2.0)
+}
+
+/// A closed polygon drawn as a polyline.
+pub(crate) fn polygon(points: &[Point]) -> Shape {
+    let mut closed = points.to_vec();
+    closed.push(points[0]);
+    Shape::Line(Line::from_points(closed, PathStyle::Direct))
+}
+
+/// Character cursor over a flowchart statement.
+struct Cursor<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_ws(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Node ID: letters, digits and underscores, with single dashes inside.
+    fn parse_id(&mut self) -> String {
+        let rest = self.rest();
+        let mut end = 0;
+        let mut chars = rest.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            let next_is_word = chars
+                .peek()
+                .is_some_and(|&(_, n)| n.is_alphanumeric() || n == '_');
+            if c.is_alphanumeric() || c == '_' || (c == '-' && end > 0 && next_is_word) {
+                end = i + c.len_utf8();
+            } else {
+                break;
+            }
+        }
+        self.pos += end;
+        rest[..end].to_string()
+    }
+
+    /// Parse an optional node shape definition such as `[text]`, `((text))` or `{text}`.
+    fn parse_node_shape(&mut self) -> Result<Option<(NodeShape, String)>, String> {
+        const SHAPES: &[(&str, &str, NodeShape)] = &[
+            ("(((", ")))", NodeShape::DoubleCircle),
+            ("((", "))", NodeShape::Circle),
+            ("([", "])", NodeShape::Stadium),
+            ("[[", "]]", NodeShape::Subroutine),
+            ("[(", ")]", NodeShape::Cylinder),
+            ("{{", "}}", NodeShape::Hexagon),
+            ("[/", "/]", NodeShape::Parallelogram),
+            ("[/", "\\]", NodeShape::Trapezoid),
+            ("[\\", "\\]", NodeShape::ParallelogramAlt),
+            ("[\\", "/]", NodeShape::TrapezoidAlt),
+            ("[", "]", NodeShape::Rect),
+            ("(", ")", NodeShape::Rounded),
+            ("{", "}", NodeShape::Rhombus),
+            (">", "]", NodeShape::Asymmetric),
+        ];
+        for &(open, close, shape) in SHAPES {
+            if !self.rest().starts_with(open) {
+                continue;
+            }
+            let body = &self.rest()[open.len()..];
+            let text_len = if let Some(quoted) = body.strip_prefix('"') {
+                // Quoted labels may contain brackets
+                quoted.find('"').map(|i| i + 2)
+            } else {
+                body.find(close)
+            };
+            let Some(text_len) = text_len else {
+                continue;
+            };
+            if !body[text_len..].starts_with(close) {
+                continue;
+            }
+            let label = clean_label(&body[..text_len]);
+            self.pos += open.len() + text_len + close.len();
+            return Ok(Some((shape, label)));
+        }
+        match self.peek() {
+            Some('[' | '(' | '{') => Err(format!("unterminated node shape at '{}'", self.rest())),
+            _ => Ok(None),
+        }
+    }
+
+    /// Parse a link such as `-->`, `---`, `-.->`, `==>`, `-- text -->` or `-->|text|`.
+    fn parse_link(&mut self) -> Option<Link> {
+        let start = self.pos;
+        // Optional head at the start of a bidirectional link
+        if self.rest().starts_with('<') {
+            self.pos += 1;
+        }
+
+        let (stroke, body_char) = match self.peek()? {
+            '-' if self.rest().starts_with("-.") => (LinkStroke::Dotted, '-'),
+            '-' => (LinkStroke::Normal, '-'),
+            '=' => (LinkStroke::Thick, '='),
+            '~' => (LinkStroke::Invisible, '~'),
+            _ => {
+                self.pos = start;
+                return None;
+            }
+        };
+        let body_len = self
+            .rest()
+            .find(|c: char| c != body_char && c != '.')
+            .unwrap_or(self.rest().len());
+        if body_len < 2 {
+            self.pos = start;
+            return None;
+        }
+        self.pos += body_len;
+
+        let mut head = self.eat_head();
+        let mut label = None;
+
+        // Inline label form: `-- text -->`, `-. text .->`, `== text ==>`
+        if !head && body_len == 2 && self.peek().is_some_and(char::is_whitespace) {
+            let terminators: &[&str] = match stroke {
+                LinkStroke::Dotted => &[".->", ".-"],
+                LinkStroke::Thick => &["==>", "==="],
+                _ => &["-->", "---", "--x", "--o"],
+            };
+            if let Some((offset, terminator)) = terminators
+                .iter()
+                .filter_map(|t| self.rest().find(t).map(|i| (i, *t)))
+                .min_by_key(|&(i, t)| (i, std::cmp::Reverse(t.len())))
+            {
+                label = Some(clean_label(&self.rest()[..offset]));
+                self.pos += offset + terminator.len();
+                // Consume the rest of a longer link body
+                while self.peek() == Some(body_char) {
+                    self.pos += 1;
+                }
+                head = terminator.ends_with(['>', 'x', 'o']) || self.eat_head();
+            }
+        }
+
+        // Pipe label form: `-->|text|`
+        self.skip_ws();
+        if self.rest().starts_with('|') {
+            let body = &self.rest()[1..];
+            if let Some(end) = body.find('|') {
+                label = Some(clean_label(&body[..end]));
+                self.pos += end + 2;
+            }
+        }
+
+        Some(Link {
+            stroke,
+            head,
+            label: label.filter(|l| !l.is_empty()),
+        })
+    }
+
+    /// Consume an arrowhead (`>`, `x` or `o`) at the end of a link.
+    fn eat_head(&mut self) -> bool {
+        let mut chars = self.rest().chars();
+        match (chars.next(), chars.next()) {
+            (Some('>'), _) => {
+                self.pos += 1;
+                true
+            }
+            (Some('x' | 'o'), next) if !next.is_some_and(|n| n.is_alphanumeric() || n == '_') => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Sequence diagram
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Clone)]
+struct Participant {
+    label: String,
+    actor: bool,
+}
+
+#[derive(Debug, Clone)]
+enum NotePlacement {
+    LeftOf(usize),
+    RightOf(usize),
+    Over(usize, usize),
+}
+
+#[derive(Debug, Clone)]
+enum SequenceEvent {
+    Message {
+        from: usize,
+        to: usize,
+        text: String,
+        dashed: bool,
+        head: bool,
+    },
+    Note {
+        placement: NotePlacement,
+        text: String,
+    },
+    /// Start of a `loop`/`alt`/`opt`/... block.
+    BlockStart(String),
+    /// `else`/`and`/`option` separator inside a block.
+    BlockDivider(String),
+    BlockEnd,
+}
+
+#[derive(Debug, Default)]
+struct SequenceDiagram {
+    participants: Vec<Participant>,
+    ids: HashMap<String, usize>,
+    events: Vec<SequenceEvent>,
+}
+
+/// Message arrows, longest first so prefixes don't shadow longer operators.
+const MESSAGE_ARROWS: &[(&str, bool, bool)] = &[
+    // (operator, dashed, arrowhead)
+    ("-->>", true, true),
+    ("--x", true, true),
+    ("--)", true, true),
+    ("-->", true, false),
+    ("->>", false, true),
+    ("-x", false, true),
+    ("-)", false, true),
+    ("->", false, false),
+];
+
+const BLOCK_KEYWORDS: &[&str] = &["loop", "alt", "opt", "par", "critical", "break", "rect"];
+const DIVIDER_KEYWORDS: &[&str] = &["else", "and", "option"];
+
+impl SequenceDiagram {
+    fn parse<'a>(statements: impl Iterator<Item = (usize, &'a str)>) -> MermaidResult<Self> {
+        let mut diagram = SequenceDiagram::default();
+        for (line, stmt) in statements {
+            diagram
+                .parse_statement(stmt)
+                .map_err(|message| MermaidError::Syntax { line, message })?;
+        }
+        Ok(diagram)
+    }
+
+    fn parse_statement(&mut self, stmt: &str) -> Result<(), String> {
+        let (keyword, rest) = stmt
+            .split_once(char::is_whitespace)
+            .map(|(k, r)| (k, r.trim()))
+            .unwrap_or((stmt, ""));
+
+        match keyword {
+            "participant" | "actor" => {
+                let (id, label) = match rest.split_once(" as ") {
+                    Some((id, label)) => (id.trim(), clean_label(label)),
+                    None => (rest, clean_label(rest)),
+                };
+                let index = self.participant(id);
+                self.participants[index].label = label;
+                self.participants[index].actor = keyword == "actor";
+                Ok(())
+            }
+            "create" | "destroy" => self.parse_statement(rest),
+            "autonumber" | "activate" | "deactivate" | "title" | "box" => Ok(()),
+            "end" => {
+                self.events.push(SequenceEvent::BlockEnd);
+                Ok(())
+            }
+            k if BLOCK_KEYWORDS.contains(&k) => {
+                let label = if k == "rect" {
+                    String::new()
+                } else {
+                    format!("{} {}", k, clean_label(rest)).trim().to_string()
+                };
+                self.events.push(SequenceEvent::BlockStart(label));
+                Ok(())
+            }
+            k if DIVIDER_KEYWORDS.contains(&k) => {
+                self.events
+                    .push(SequenceEvent::BlockDivider(clean_label(rest)));
+                Ok(())
+            }
+            k if k.eq_ignore_ascii_case("note") => self.parse_note(rest),
+            _ => self.parse_message(stmt),
+        }
+    }
+
+    fn participant(&mut self, id: &str) -> usize {
+        if let Some(&index) = self.ids.get(id) {
+            return index;
+        }
+        self.participants.push(Participant {
+            label: clean_label(id),
+            actor: false,
+        });
+        self.ids.insert(id.to_string(), self.participants.len() - 1);
+        self.participants.len() - 1
+    }
+
+    fn parse_note(&mut self, rest: &str) -> Result<(), String> {
+        let (target, text) = rest
+            .split_once(':')
+            .ok_or_else(|| format!("note without text: '{}'", rest))?;
+        let target = target.trim();
+        let lower = target.to_lowercase();
+        let placement = if let Some(id) = lower.strip_prefix("left of") {
+            NotePlacement::LeftOf(self.participant(target[target.len() - id.len()..].trim()))
+        } else if let Some(id) = lower.strip_prefix("right of") {
+            NotePlacement::RightOf(self.participant(target[target.len() - id.len()..].trim()))
+        } else if let Some(ids) = lower.strip_prefix("over") {
+            let ids = &target[target.len() - ids.len()..];
+            let mut parts = ids.split(',').map(str::trim);
+            let first = self.participant(parts.next().unwrap_or(""));
+            let last = parts.next().map_or(first, |id| self.participant(id));
+            NotePlacement::Over(first, last)
+        } else {
+            return Err(format!("unknown note placement: '{}'", target));
+        };
+        self.events.push(SequenceEvent::Note {
+            placement,
+            text: clean_label(text),
+        });
+        Ok(())
+    }
+
+    fn parse_message(&mut self, stmt: &str) -> Result<(), String> {
+        let (arrow_part, text) = stmt.split_once(':').unwrap_or((stmt, ""));
+        let found = arrow_part.char_indices().find_map(|(i, _)| {
+            MESSAGE_ARROWS
+                .iter()
+                .find(|(op, _, _)| arrow_part[i..].starts_with(op))
+                .map(|&(op, dashed, head)| (i, op, dashed, head))
+        });
+        let Some((index, op, dashed, head)) = found else {
+            return Err(format!("unrecognized statement: '{}'", stmt));
+        };
+        let from = arrow_part[..index].trim();
+        // Strip activation markers (`->>+B`, `-->>-A`)
+        let to = arrow_part[index + op.len()..]
+            .trim()
+            .trim_start_matches(['+', '-'])
+            .trim();
+        if from.is_empty() || to.is_empty() {
+            return Err(format!("message needs a sender and receiver: '{}'", stmt));
+        }
+        let from = self.participant(from);
+        let to = self.participant(to);
+        self.events.push(SequenceEvent::Message {
+            from,
+            to,
+            text: clean_label(text),
+            dashed,
+            head,
+        });
+        Ok(())
+    }
+
+    fn to_shapes(&self) -> Vec<Shape> {
+        const BOX_HEIGHT: f64 = 50.0;
+        const COLUMN_GAP: f64 = 60.0;
+        const ROW: f64 = 60.0;
+        const SELF_LOOP: f64 = 40.0;
+
+        // Column centers: wide enough for boxes and the labels of messages between neighbours
+        let widths: Vec<f64> = self
+            .participants
+            .iter()
+            .map(|p| {
+                (text_size(&p.label, LABEL_FONT_SIZE).width + NODE_PADDING.width * 2.0)
+                    .max(MIN_NODE_SIZE.width)
+            })
+            .collect();
+        let mut centers: Vec<f64> = Vec::with_capacity(widths.len());
+        for (i, width) in widths.iter().enumerate() {
+            let x = match i {
+                0 => width / 2.0,
+                _ => {
+                    let label_width = self
+                        .events
+                        .iter()
+                        .filter_map(|event| match event {
+                            SequenceEvent::Message { from, to, text, .. }
+                                if from.min(to) == &(i - 1) && from.max(to) == &i =>
+                            {
+                                Some(text_size(text, EDGE_FONT_SIZE).width + 40.0)
+                            }
+                            _ => None,
+                        })
+                        .fold(0.0, f64::max);
+                    let spacing = (widths[i - 1] / 2.0 + width / 2.0 + COLUMN_GAP).max(label_width);
+                    centers[i - 1] + spacing
+                }
+            };
+            centers.push(x);
+        }
+        let diagram_left = -20.0;
+        let diagram_right = centers.last().copied().unwrap_or(0.0)
+            + widths.last().copied().unwrap_or(0.0) / 2.0
+            + 20.0;
+
+        let mut shapes = Vec::new();
+        let mut y = BOX_HEIGHT + ROW / 2.0;
+        let mut open_blocks: Vec<(f64, String)> = Vec::new();
+
+        for event in &self.events {
+            match event {
+                SequenceEvent::Message {
+                    from,
+                    to,
+                    text,
+                    dashed,
+                    head,
+                } => {
+                    let label_height = if text.is_empty() {
+                        0.0
+                    } else {
+                        text_size(text, EDGE_FONT_SIZE).height
+                    };
+                    y += label_height;
+                    let x0 = centers[*from];
+                    let x1 = centers[*to];
+                    let mut connector = if from == to {
+                        let points = vec![
+                            Point::new(x0, y),
+                            Point::new(x0 + SELF_LOOP, y),
+                            Point::new(x0 + SELF_LOOP, y + SELF_LOOP / 2.0),
+                            Point::new(x0, y + SELF_LOOP / 2.0),
+                        ];
+                        if *head {
+                            Shape::Arrow(Arrow::from_points(points, PathStyle::Direct))
+                        } else {
+                            Shape::Line(Line::from_points(points, PathStyle::Direct))
+                        }
+                    } else if *head {
+                        Shape::Arrow(Arrow::new(Point::new(x0, y), Point::new(x1, y)))
+                    } else {
+                        Shape::Line(Line::new(Point::new(x0, y), Point::new(x1, y)))
+                    };
+                    if *dashed {
+                        match &mut connector {
+                            Shape::Arrow(arrow) => arrow.stroke_style = StrokeStyle::Dashed,
+                            Shape::Line(line) => line.stroke_style = StrokeStyle::Dashed,
+                            _ => {}
+                        }
+                    }
+                    shapes.push(connector);
+                    if !text.is_empty() {
+                        let label_x = if from == to {
+                            x0 + SELF_LOOP + text_size(text, EDGE_FONT_SIZE).width / 2.0 + 8.0
+                        } else {
+                            (x0 + x1) / 2.0
+                        };
+                        let label_y = if from == to {
+                            y + SELF_LOOP / 4.0
+                        } else {
+                            y - label_height / 2.0 - 4.0
+                        };
+                        shapes.push(Shape::Text(centered_text(
+                            Point::new(label_x, label_y),
+                            text,
+                            EDGE_FONT_SIZE,
+                        )));
+                    }
+                    y += if from == to {
+                        SELF_LOOP / 2.0 + ROW
+                    } else {
+                        ROW
+                    };
+                }
+                SequenceEvent::Note { placement, text } => {
+                    let size = text_size(text, EDGE_FONT_SIZE);
+                    let note_size = Size::new(
+                        size.width + NODE_PADDING.width,
+                        size.height + NODE_PADDING.height,
+                    );
+                    let (x0, x1) = match *placement {
+                        NotePlacement::LeftOf(p) => {
+                            (centers[p] - 10.0 - note_size.width, centers[p] - 10.0)
+                        }
+                        NotePlacement::RightOf(p) => {
+                            (centers[p] + 10.0, centers[p] + 10.0 + note_size.width)
+                        }
+                        NotePlacement::Over(a, b) => {
+                            let (left, right) =
+                                (centers[a].min(centers[b]), centers[a].max(centers[b]));
+                            let mid = (left + right) / 2.0;
+                            let half = (note_size.width / 2.0).max((right - left) / 2.0 + 30.0);
+                            (mid - half, mid + half)
+                        }
+                    };
+                    let rect = Rect::new(x0, y - ROW / 4.0, x1, y - ROW / 4.0 + note_size.height);
+                    let mut note = rectangle(rect, 0.0);
+                    note.style.fill_color = Some(NOTE_FILL);
+                    shapes.push(Shape::Rectangle(note));
+                    shapes.push(Shape::Text(centered_text(
+                        rect.center(),
+                        text,
+                        EDGE_FONT_SIZE,
+                    )));
+                    y = rect.y1 + ROW / 2.0;
+                }
+                SequenceEvent::BlockStart(label) => {
+                    open_blocks.push((y - ROW / 4.0, label.clone()));
+                    y += ROW / 2.0;
+                }
+                SequenceEvent::BlockDivider(label) => {
+                    let mut divider = Line::new(
+                        Point::new(diagram_left, y - ROW / 4.0),
+                        Point::new(diagram_right, y - ROW / 4.0),
+                    );
+                    divider.stroke_style = StrokeStyle::Dashed;
+                    divider.style.stroke_color = FRAME_COLOR;
+                    shapes.push(Shape::Line(divider));
+                    if !label.is_empty() {
+                        let mut text = Text::new(
+                            Point::new(diagram_left + 8.0, y - ROW / 4.0 + 4.0),
+                            format!("[{}]", label),
+                        )
+                        .with_font_size(EDGE_FONT_SIZE);
+                        text.style.stroke_color = FRAME_COLOR;
+                        shapes.push(Shape::Text(text));
+                    }
+                    y += ROW / 2.0;
+                }
+                SequenceEvent::BlockEnd => {
+                    if let Some((top, label)) = open_blocks.pop() {
+                        let depth = open_blocks.len() as f64 * 8.0;
+                        let rect = Rect::new(
+                            diagram_left + depth,
+                            top,
+                            diagram_right - depth,
+                            y - ROW / 4.0,
+                        );
+                        let mut frame = rectangle(rect, 0.0);
+                        frame.style.stroke_color = FRAME_COLOR;
+                        shapes.push(Shape::Rectangle(frame));
+                        if !label.is_empty() {
+                            let mut text =
+                                Text::new(Point::new(rect.x0 + 8.0, rect.y0 + 4.0), label)
+                                    .with_font_size(EDGE_FONT_SIZE);
+                            text.style.stroke_color = FRAME_COLOR;
+                            shapes.push(Shape::Text(text));
+                        }
+                        y += ROW / 4.0;
+                    }
+                }
+            }
+        }
+
+        // Participants and lifelines (drawn first so they sit below messages)
+        let bottom = y;
+        let mut header = Vec::new();
+        for (i, participant) in self.participants.iter().enumerate() {
+            let rect = Rect::from_center_size(
+                Point::new(centers[i], BOX_HEIGHT / 2.0),
+                Size::new(widths[i], BOX_HEIGHT),
+            );
+            if participant.actor {
+                header.push(Shape::Ellipse(ellipse(rect)));
+            } else {
+                header.push(Shape::Rectangle(rectangle(rect, 6.0)));
+            }
+            header.push(Shape::Text(centered_text(
+                rect.center(),
+                &participant.label,
+                LABEL_FONT_SIZE,
+            )));
+            let mut lifeline = Line::new(
+                Point::new(centers[i], BOX_HEIGHT),
+                Point::new(centers[i], bottom),
+            );
+            lifeline.stroke_style = StrokeStyle::Dashed;
+            lifeline.style.stroke_color = FRAME_COLOR;
+            header.push(Shape::Line(lifeline));
+        }
+        header.extend(shapes);
+        header
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(shapes: &[Shape], f: impl Fn(&Shape) -> bool) -> usize {
+        shapes.iter().filter(|s| f(s)).count()
+    }
+
+    fn texts(shapes: &[Shape]) -> Vec<String> {
+        shapes
+            .iter()
+            .filter_map(|s| match s {
+                Shape::Text(t) => Some(t.content.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_mermaid() {
+        assert!(is_mermaid("flowchart LR\n A --> B"));
+        assert!(is_mermaid("```mermaid\n%% comment\ngraph TD;\nA-->B\n```"));
+        assert!(is_mermaid("sequenceDiagram\n A->>B: hi"));
+        assert!(!is_mermaid("pie title Pets"));
+        assert!(!is_mermaid("hello world"));
+    }
+
+    #[test]
+    fn test_flowchart_nodes_and_links() {
+        let shapes = parse_mermaid(
+            "flowchart TD\n\
+             A[Start] --> B{Is it?}\n\
+             B -->|Yes| C(Done)\n\
+             B -- No --> D((Retry))\n\
+             D -.-> A\n\
+             C --- E",
+        )
+        .unwrap();
+
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 4);
+        // Rhombus polygon + undirected link
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Line(_))), 2);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Ellipse(_))), 1);
+        // A, C and the implicit E
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Rectangle(_))), 3);
+
+        let labels = texts(&shapes);
+        for expected in ["Start", "Is it?", "Done", "Retry", "E", "Yes", "No"] {
+            assert!(labels.iter().any(|l| l == expected), "missing {expected}");
+        }
+        let dashed = shapes
+            .iter()
+            .any(|s| matches!(s, Shape::Arrow(a) if a.stroke_style == StrokeStyle::Dashed));
+        assert!(dashed);
+    }
+
+    #[test]
+    fn test_flowchart_chains_and_groups() {
+        let shapes = parse_mermaid("graph LR; A & B --> C --> D").unwrap();
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 3);
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Rectangle(_))), 4);
+    }
+
+    #[test]
+    fn test_flowchart_layout_direction() {
+        let top_down = parse_mermaid("flowchart TD\nA --> B").unwrap();
+        let left_right = parse_mermaid("flowchart LR\nA --> B").unwrap();
+        let rects = |shapes: &[Shape]| -> Vec<Rect> {
+            shapes
+                .iter()
+                .filter(|s| matches!(s, Shape::Rectangle(_)))
+                .map(|s| s.bounds())
+                .collect()
+        };
+        let td = rects(&top_down);
+        assert!(td[1].y0 > td[0].y1);
+        let lr = rects(&left_right);
+        assert!(lr[1].x0 > lr[0].x1);
+        // Layout starts at the origin
+        assert_eq!(td[0].y0, 0.0);
+    }
+
+    #[test]
+    fn test_quoted_and_multiline_labels() {
+        let shapes = parse_mermaid("flowchart TD\nA[\"Hello [world]\"] --> B[one<br>two]").unwrap();
+        let labels = texts(&shapes);
+        assert!(labels.contains(&"Hello [world]".to_string()));
+        assert!(labels.contains(&"one\ntwo".to_string()));
+    }
+
+    #[test]
+    fn test_flowchart_syntax_error() {
+        let err = parse_mermaid("flowchart TD\nA --> B\nC[unterminated --> D").unwrap_err();
+        assert!(matches!(err, MermaidError::Syntax { line: 3, .. }));
+    }
+
+    #[test]
+    fn test_sequence_diagram() {
+        let shapes = parse_mermaid(
+            "sequenceDiagram\n\
+             participant A as Alice\n\
+             actor B as Bob\n\
+             A->>B: Hello Bob\n\
+             B-->>A: Hi Alice\n\
+             loop Every minute\n\
+             A->>A: Think\n\
+             end\n\
+             Note right of B: Nice",
+        )
+        .unwrap();
+
+        let labels = texts(&shapes);
+        for expected in [
+            "Alice",
+            "Bob",
+            "Hello Bob",
+            "Hi Alice",
+            "Think",
+            "loop Every minute",
+            "Nice",
+        ] {
+            assert!(labels.iter().any(|l| l == expected), "missing {expected}");
+        }
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Arrow(_))), 3);
+        // Bob is an actor
+        assert_eq!(count(&shapes, |s| matches!(s, Shape::Ellipse(_))), 1);
+        let dashed_reply = shapes
+            .iter()
+            .any(|s| matches!(s, Shape::Arrow(a) if a.stroke_style == StrokeStyle::Dashed));
+        assert!(dashed_reply);
+    }
+
+    #[test]
+    fn test_unsupported_diagram() {
+        assert_eq!(
+            parse_mermaid("pie title Pets").unwrap_err(),
+            MermaidError::Unsupported("pie".to_string())
+        );
+        assert_eq!(
+            parse_mermaid("  \n%% nothing").unwrap_err(),
+            MermaidError::Empty
+        );
+    }
+}
@@ -1028,7 +1028,7 @@ mod tests {
             source_width: 200,
             source_height: 200,
             format: ImageFormat::Png,
-            data_base64: String::new(),
+            asset: crate::assets::Asset::new(Vec::new()),
             rotation: 0.0,
             style: ShapeStyle::default(),
         };
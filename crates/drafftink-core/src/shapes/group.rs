@@ -12,9 +12,16 @@ pub struct Group {
     pub(crate) id: ShapeId,
     /// Child shapes in this group.
     pub children: Vec<Shape>,
-    /// Rotation angle in radians.
+    /// Rotation angle in radians (set with [`Group::set_rotation`]).
     #[serde(default)]
     pub rotation: f64,
+    /// Point the group is rotated about: the center of its children when it was
+    /// rotated, so editing a child doesn't move its siblings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pivot: Option<Point>,
+    /// Name of the frame this group was imported from, if it is one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame: Option<String>,
     /// Style properties (not directly used, but kept for consistency).
     style: ShapeStyle,
 }
@@ -26,20 +33,36 @@ impl Group {
             id: Uuid::new_v4(),
             children,
             rotation: 0.0,
+            pivot: None,
+            frame: None,
             style: ShapeStyle::default(),
         }
     }
 
     /// Reconstruct a group with a specific ID (for CRDT/storage).
-    pub(crate) fn reconstruct(id: ShapeId, children: Vec<Shape>) -> Self {
+    pub(crate) fn reconstruct(
+        id: ShapeId,
+        children: Vec<Shape>,
+        rotation: f64,
+        pivot: Option<Point>,
+        frame: Option<String>,
+    ) -> Self {
         Self {
             id,
             children,
-            rotation: 0.0,
+            rotation,
+            pivot,
+            frame,
             style: ShapeStyle::default(),
         }
     }
 
+    /// Mark this group as a frame with the given name.
+    pub fn with_frame(mut self, name: impl Into<String>) -> Self {
+        self.frame = Some(name.into());
+        self
+    }
+
     /// Get the children of this group.
     pub fn children(&self) -> &[Shape] {
         &self.children
@@ -97,6 +120,62 @@ impl Group {
         }
         None
     }
+
+    /// Set the rotation angle in radians. The pivot is fixed at the center of the
+    /// children when the group is first rotated, and released when it's unrotated.
+    pub fn set_rotation(&mut self, rotation: f64) {
+        self.rotation = rotation;
+        if rotation.abs() <= 0.001 {
+            self.pivot = None;
+        } else if self.pivot.is_none() {
+            self.pivot = Some(self.bounds().center());
+        }
+    }
+
+    /// Transform from the children's coordinates to the group's: the group's
+    /// rotation about its pivot.
+    pub fn child_transform(&self) -> Affine {
+        if self.rotation.abs() > 0.001 {
+            let pivot = self.pivot.unwrap_or_else(|| self.bounds().center());
+            Affine::rotate_about(self.rotation, pivot)
+        } else {
+            Affine::IDENTITY
+        }
+    }
+
+    /// Find the ID of the group that directly contains a shape (this group or a nested one).
+    pub fn find_parent(&self, id: ShapeId) -> Option<ShapeId> {
+        for child in &self.children {
+            if child.id() == id {
+                return Some(self.id);
+            }
+            if let Shape::Group(group) = child {
+                if let Some(parent) = group.find_parent(id) {
+                    return Some(parent);
+                }
+            }
+        }
+        None
+    }
+
+    /// Remove a shape by ID from this group (including nested groups).
+    /// Nested groups left empty by the removal are dropped as well.
+    pub fn remove_shape(&mut self, id: ShapeId) -> Option<Shape> {
+        if let Some(pos) = self.children.iter().position(|c| c.id() == id) {
+            return Some(self.children.remove(pos));
+        }
+        for i in 0..self.children.len() {
+            if let Shape::Group(group) = &mut self.children[i] {
+                if let Some(removed) = group.remove_shape(id) {
+                    if group.children.is_empty() {
+                        self.children.remove(i);
+                    }
+                    return Some(removed);
+                }
+            }
+        }
+        None
+    }
 }
 
 impl ShapeTrait for Group {
@@ -117,7 +196,8 @@ impl ShapeTrait for Group {
     }
 
     fn hit_test(&self, point: Point, tolerance: f64) -> bool {
-        // Hit if any child is hit
+        // Hit if any child is hit, where the group's rotation drew it
+        let point = self.child_transform().inverse() * point;
         for child in &self.children {
             if child.hit_test(point, tolerance) {
                 return true;
@@ -145,10 +225,13 @@ impl ShapeTrait for Group {
     }
 
     fn transform(&mut self, affine: Affine) {
-        // Transform all children
+        // Transform all children, and the pivot along with them
         for child in &mut self.children {
             child.transform(affine);
         }
+        if let Some(pivot) = &mut self.pivot {
+            *pivot = affine * *pivot;
+        }
     }
 
     fn clone_box(&self) -> Box<dyn ShapeTrait + Send + Sync> {
@@ -159,7 +242,7 @@ impl ShapeTrait for Group {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::shapes::Rectangle;
+    use crate::shapes::{Ellipse, Rectangle};
 
     #[test]
     fn test_group_creation() {
@@ -212,6 +295,52 @@ mod tests {
         assert!(outer_group.hit_test(Point::new(50.0, 25.0), 0.0));
     }
 
+    #[test]
+    fn test_find_parent_and_remove_nested() {
+        let rect = Shape::Rectangle(Rectangle::new(Point::new(0.0, 0.0), 10.0, 10.0));
+        let ellipse = Shape::Ellipse(Ellipse::new(Point::new(50.0, 50.0), 5.0, 5.0));
+        let rect_id = rect.id();
+        let ellipse_id = ellipse.id();
+        let inner = Group::new(vec![rect]);
+        let inner_id = inner.id();
+        let mut outer = Group::new(vec![Shape::Group(inner), ellipse]);
+
+        assert_eq!(outer.find_parent(rect_id), Some(inner_id));
+        assert_eq!(outer.find_parent(ellipse_id), Some(outer.id()));
+        assert_eq!(outer.find_parent(outer.id()), None);
+
+        // Removing the only child of the nested group drops the nested group too
+        let removed = outer.remove_shape(rect_id).unwrap();
+        assert_eq!(removed.id(), rect_id);
+        assert_eq!(outer.children().len(), 1);
+        assert!(outer.find_shape(inner_id).is_none());
+        assert!(outer.remove_shape(rect_id).is_none());
+    }
+
+    #[test]
+    fn test_pivot_fixed_while_editing_children() {
+        let rect1 = Rectangle::new(Point::new(0.0, 0.0), 100.0, 100.0);
+        let rect2 = Rectangle::new(Point::new(200.0, 0.0), 100.0, 100.0);
+        let mut group = Group::new(vec![Shape::Rectangle(rect1), Shape::Rectangle(rect2)]);
+
+        group.set_rotation(1.0);
+        assert_eq!(group.pivot, Some(Point::new(150.0, 50.0)));
+        let before = group.child_transform();
+
+        // Growing one child changes the bounds but not the rotation origin
+        if let Shape::Rectangle(r) = &mut group.children[1] {
+            r.width = 400.0;
+        }
+        assert_eq!(group.child_transform(), before);
+
+        // Moving the group moves the pivot with it
+        group.transform(Affine::translate((10.0, 20.0)));
+        assert_eq!(group.pivot, Some(Point::new(160.0, 70.0)));
+
+        group.set_rotation(0.0);
+        assert_eq!(group.pivot, None);
+    }
+
     #[test]
     fn test_ungroup() {
         let rect1 = Rectangle::new(Point::new(0.0, 0.0), 100.0, 50.0);
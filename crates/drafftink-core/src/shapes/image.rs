@@ -1,6 +1,7 @@
-//! Image shape for embedding raster images.
+//! Image shape for embedding raster and SVG images.
 
 use super::{ShapeId, ShapeStyle, ShapeTrait};
+use crate::assets::Asset;
 use kurbo::{Affine, BezPath, Point, Rect, Shape as KurboShape};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
@@ -14,6 +15,8 @@ pub enum ImageFormat {
     Jpeg,
     /// WebP format.
     WebP,
+    /// SVG document, rendered as vector graphics.
+    Svg,
 }
 
 impl ImageFormat {
@@ -23,6 +26,7 @@ impl ImageFormat {
             ImageFormat::Png => "image/png",
             ImageFormat::Jpeg => "image/jpeg",
             ImageFormat::WebP => "image/webp",
+            ImageFormat::Svg => "image/svg+xml",
         }
     }
 
@@ -32,6 +36,7 @@ impl ImageFormat {
             "png" => Some(ImageFormat::Png),
             "jpg" | "jpeg" => Some(ImageFormat::Jpeg),
             "webp" => Some(ImageFormat::WebP),
+            "svg" => Some(ImageFormat::Svg),
             _ => None,
         }
     }
@@ -57,11 +62,16 @@ impl ImageFormat {
             return Some(ImageFormat::WebP);
         }
 
+        // SVG: XML text with an <svg> root
+        if std::str::from_utf8(data).is_ok_and(crate::svg::is_svg) {
+            return Some(ImageFormat::Svg);
+        }
+
         None
     }
 }
 
-/// An image shape that displays a raster image.
+/// An image shape that displays a raster image or SVG document.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Image {
     pub(crate) id: ShapeId,
@@ -77,9 +87,10 @@ pub struct Image {
     pub source_height: u32,
     /// Image format.
     pub format: ImageFormat,
-    /// Image data as base64-encoded string (for CRDT compatibility).
-    /// We use base64 string instead of Vec<u8> for easier JSON/CRDT serialization.
-    pub data_base64: String,
+    /// Image data, shared with every image of the same content.
+    /// Serialized inline as base64; documents store it once in their asset table.
+    #[serde(rename = "data_base64")]
+    pub asset: Asset,
     /// Rotation angle in radians (around center).
     #[serde(default)]
     pub rotation: f64,
@@ -92,7 +103,7 @@ impl Image {
     ///
     /// # Arguments
     /// * `position` - Top-left corner position
-    /// * `data` - Raw image bytes (PNG, JPEG, or WebP)
+    /// * `data` - Raw image bytes (PNG, JPEG, WebP, or SVG text)
     /// * `source_width` - Original image width in pixels
     /// * `source_height` - Original image height in pixels
     /// * `format` - Image format
@@ -103,8 +114,6 @@ impl Image {
         source_height: u32,
         format: ImageFormat,
     ) -> Self {
-        use base64::{Engine, engine::general_purpose::STANDARD};
-
         Self {
             id: Uuid::new_v4(),
             position,
@@ -113,7 +122,7 @@ impl Image {
             source_width,
             source_height,
             format,
-            data_base64: STANDARD.encode(data),
+            asset: Asset::new(data),
             rotation: 0.0,
             style: ShapeStyle::default(),
         }
@@ -129,7 +138,7 @@ impl Image {
         source_width: u32,
         source_height: u32,
         format: ImageFormat,
-        data_base64: String,
+        asset: Asset,
         rotation: f64,
         style: ShapeStyle,
     ) -> Self {
@@ -141,7 +150,7 @@ impl Image {
             source_width,
             source_height,
             format,
-            data_base64,
+            asset,
             rotation,
             style,
         }
@@ -172,10 +181,9 @@ impl Image {
         self
     }
 
-    /// Get the raw image data (decoded from base64).
-    pub fn data(&self) -> Option<Vec<u8>> {
-        use base64::{Engine, engine::general_purpose::STANDARD};
-        STANDARD.decode(&self.data_base64).ok()
+    /// Get the raw image data.
+    pub fn data(&self) -> &[u8] {
+        self.asset.bytes()
     }
 
     /// Get the bounding rectangle.
@@ -190,8 +198,7 @@ impl Image {
 
     /// Get the size of the image data in bytes.
     pub fn data_size(&self) -> usize {
-        // Base64 is ~4/3 the size of raw data
-        self.data_base64.len() * 3 / 4
+        self.asset.len()
     }
 }
 
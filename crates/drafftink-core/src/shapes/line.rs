@@ -116,6 +116,11 @@ impl Line {
         )
     }
 
+    /// Whether the polyline ends where it starts, enclosing an area that can be filled.
+    pub fn is_closed(&self) -> bool {
+        !self.intermediate_points.is_empty() && self.start == self.end
+    }
+
     /// Get as a kurbo Line.
     pub fn as_kurbo(&self) -> KurboLine {
         KurboLine::new(self.start, self.end)
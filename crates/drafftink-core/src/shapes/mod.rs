@@ -248,6 +248,11 @@ impl ShapeStyle {
     pub fn set_fill(&mut self, color: Option<Color>) {
         self.fill_color = color.map(|c| c.into());
     }
+
+    /// Replace the hand-drawn seed with a freshly generated one.
+    pub fn regenerate_seed(&mut self) {
+        self.seed = generate_seed();
+    }
 }
 
 impl Default for ShapeStyle {
@@ -491,6 +496,18 @@ impl Shape {
         }
     }
 
+    /// Give this shape, and every child of a group, a fresh ID and hand-drawn seed.
+    /// Used when instantiating templates so copies never share identity with the source.
+    pub fn regenerate_identity(&mut self) {
+        self.regenerate_id();
+        self.style_mut().regenerate_seed();
+        if let Shape::Group(group) = self {
+            for child in group.children_mut() {
+                child.regenerate_identity();
+            }
+        }
+    }
+
     /// Check if this shape is an image.
     pub fn is_image(&self) -> bool {
         matches!(self, Shape::Image(_))
@@ -517,6 +534,30 @@ impl Shape {
         }
     }
 
+    /// Get the axis-aligned bounds of the shape as drawn, including its rotation
+    /// about the bounds center.
+    pub fn rotated_bounds(&self) -> Rect {
+        match self {
+            Shape::Group(g) => g
+                .children()
+                .iter()
+                .map(Shape::rotated_bounds)
+                .reduce(|a, b| a.union(b))
+                .map_or(Rect::ZERO, |bounds| {
+                    g.child_transform().transform_rect_bbox(bounds)
+                }),
+            _ => {
+                let bounds = self.bounds();
+                let rotation = self.rotation();
+                if rotation.abs() > 0.001 {
+                    Affine::rotate_about(rotation, bounds.center()).transform_rect_bbox(bounds)
+                } else {
+                    bounds
+                }
+            }
+        }
+    }
+
     /// Set the rotation angle in radians.
     pub fn set_rotation(&mut self, rotation: f64) {
         match self {
@@ -525,7 +566,7 @@ impl Shape {
             Shape::Text(t) => t.rotation = rotation,
             Shape::Image(i) => i.rotation = rotation,
             Shape::Math(m) => m.rotation = rotation,
-            Shape::Group(g) => g.rotation = rotation,
+            Shape::Group(g) => g.set_rotation(rotation),
             _ => {}
         }
     }
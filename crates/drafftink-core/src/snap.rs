@@ -45,6 +45,53 @@ pub enum SmartGuideKind {
     EqualSpacingV,
 }
 
+/// Axis along which shapes are spaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpacingAxis {
+    /// Left to right.
+    Horizontal,
+    /// Top to bottom.
+    Vertical,
+}
+
+/// Gap between `first` and `second` along an axis.
+/// Returns None if `first` does not end before `second` starts.
+pub fn spacing_gap(first: Rect, second: Rect, axis: SpacingAxis) -> Option<f64> {
+    let gap = match axis {
+        SpacingAxis::Horizontal => second.x0 - first.x1,
+        SpacingAxis::Vertical => second.y0 - first.y1,
+    };
+    (gap > 0.0).then_some(gap)
+}
+
+/// Gap that spaces `bounds` equally between the outermost two along an axis.
+/// The result is negative when the shapes are too large to fit without overlapping.
+/// Returns None for fewer than 3 bounds.
+pub fn equal_spacing_gap(bounds: &[Rect], axis: SpacingAxis) -> Option<f64> {
+    if bounds.len() < 3 {
+        return None;
+    }
+    let (start, end, total) = match axis {
+        SpacingAxis::Horizontal => (
+            bounds.iter().map(|b| b.x0).fold(f64::INFINITY, f64::min),
+            bounds
+                .iter()
+                .map(|b| b.x1)
+                .fold(f64::NEG_INFINITY, f64::max),
+            bounds.iter().map(|b| b.width()).sum::<f64>(),
+        ),
+        SpacingAxis::Vertical => (
+            bounds.iter().map(|b| b.y0).fold(f64::INFINITY, f64::min),
+            bounds
+                .iter()
+                .map(|b| b.y1)
+                .fold(f64::NEG_INFINITY, f64::max),
+            bounds.iter().map(|b| b.height()).sum::<f64>(),
+        ),
+    };
+    Some((end - start - total) / (bounds.len() - 1) as f64)
+}
+
 /// Result of smart guide detection.
 #[derive(Debug, Clone, Default)]
 pub struct SmartGuideResult {
@@ -123,10 +170,9 @@ pub fn detect_smart_guides(
 
                 // Horizontal spacing: check both orderings (a left of b, b left of a)
                 for (left, right) in [(a, b), (b, a)] {
-                    if left.x1 >= right.x0 {
+                    let Some(gap) = spacing_gap(*left, *right, SpacingAxis::Horizontal) else {
                         continue; // Not horizontally separated
-                    }
-                    let gap = right.x0 - left.x1;
+                    };
 
                     // Skip if gap is too large to be useful
                     if gap > EQUAL_SPACING_SNAP_RADIUS {
@@ -213,10 +259,9 @@ pub fn detect_smart_guides(
 
                 // Vertical spacing: check both orderings (a above b, b above a)
                 for (top, bottom) in [(a, b), (b, a)] {
-                    if top.y1 >= bottom.y0 {
+                    let Some(gap) = spacing_gap(*top, *bottom, SpacingAxis::Vertical) else {
                         continue; // Not vertically separated
-                    }
-                    let gap = bottom.y0 - top.y1;
+                    };
 
                     // Skip if gap is too large
                     if gap > EQUAL_SPACING_SNAP_RADIUS {
@@ -979,6 +1024,33 @@ pub fn snap_line_endpoint_isometric(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_spacing_gap() {
+        let a = Rect::new(0.0, 0.0, 10.0, 10.0);
+        let b = Rect::new(30.0, 0.0, 40.0, 10.0);
+        assert_eq!(spacing_gap(a, b, SpacingAxis::Horizontal), Some(20.0));
+        assert_eq!(spacing_gap(b, a, SpacingAxis::Horizontal), None);
+        assert_eq!(spacing_gap(a, b, SpacingAxis::Vertical), None);
+    }
+
+    #[test]
+    fn test_equal_spacing_gap() {
+        let bounds = [
+            Rect::new(0.0, 0.0, 10.0, 10.0),
+            Rect::new(15.0, 0.0, 35.0, 10.0),
+            Rect::new(90.0, 0.0, 100.0, 10.0),
+        ];
+        // Span 100, widths 40, two gaps
+        assert_eq!(
+            equal_spacing_gap(&bounds, SpacingAxis::Horizontal),
+            Some(30.0)
+        );
+        assert_eq!(
+            equal_spacing_gap(&bounds[..2], SpacingAxis::Horizontal),
+            None
+        );
+    }
+
     #[test]
     fn test_snap_to_grid() {
         let result = snap_to_grid(Point::new(23.0, 47.0), 20.0);
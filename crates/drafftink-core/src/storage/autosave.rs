@@ -146,8 +146,8 @@ impl<S: Storage> AutoSaveManager<S> {
     /// List all saved document IDs.
     pub async fn list_documents(&self) -> StorageResult<Vec<String>> {
         let mut docs = self.storage.list().await?;
-        // Filter out the special "last document" key
-        docs.retain(|id| id != LAST_DOCUMENT_KEY);
+        // Filter out the special "last document" key and saved templates
+        docs.retain(|id| id != LAST_DOCUMENT_KEY && !crate::templates::is_template_key(id));
         Ok(docs)
     }
 
@@ -1,13 +1,20 @@
 //! File-based storage implementation for native platforms.
 
 use super::{BoxFuture, Storage, StorageError, StorageResult};
+use crate::assets::{self, Asset, AssetStore};
 use crate::canvas::CanvasDocument;
+use std::collections::BTreeSet;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// Subdirectory holding the assets shared by all documents.
+const ASSETS_DIR: &str = "assets";
 
 /// File-based storage for native platforms.
 ///
-/// Stores documents as JSON files in a specified directory.
+/// Stores documents as JSON files in a specified directory. Image bytes are kept
+/// once per distinct content in an `assets/` subdirectory shared by all documents,
+/// one file per asset named by its id.
 pub struct FileStorage {
     /// Base directory for document storage.
     base_path: PathBuf,
@@ -59,19 +66,113 @@ impl FileStorage {
     pub fn base_path(&self) -> &PathBuf {
         &self.base_path
     }
+
+    /// Get the directory of shared assets.
+    fn assets_path(&self) -> PathBuf {
+        self.base_path.join(ASSETS_DIR)
+    }
+
+    /// Write the assets not stored yet. Assets are named by their content, so an
+    /// existing file never needs rewriting.
+    fn write_assets(assets_path: &Path, store: &AssetStore) -> StorageResult<()> {
+        if store.is_empty() {
+            return Ok(());
+        }
+        fs::create_dir_all(assets_path)
+            .map_err(|e| StorageError::Io(format!("Failed to create asset directory: {}", e)))?;
+        for asset in store.iter() {
+            let path = assets_path.join(asset.id());
+            if path.exists() {
+                continue;
+            }
+            // Write under a temporary name so an interrupted save never leaves a
+            // truncated asset behind
+            let partial = path.with_extension("partial");
+            fs::write(&partial, asset.bytes())
+                .and_then(|_| fs::rename(&partial, &path))
+                .map_err(|e| {
+                    StorageError::Io(format!("Failed to write {}: {}", path.display(), e))
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Read the stored assets among `ids`. Missing ones are left to fail the load.
+    fn read_assets(assets_path: &Path, ids: &BTreeSet<String>) -> StorageResult<AssetStore> {
+        let mut store = AssetStore::new();
+        for id in ids.iter().filter(|id| assets::is_asset_id(id)) {
+            let path = assets_path.join(id);
+            if !path.exists() {
+                continue;
+            }
+            let bytes = fs::read(&path).map_err(|e| {
+                StorageError::Io(format!("Failed to read {}: {}", path.display(), e))
+            })?;
+            store.insert(Asset::new(bytes));
+        }
+        Ok(store)
+    }
+
+    /// Delete the assets no stored document refers to anymore.
+    fn remove_unused_assets(base_path: &Path) -> StorageResult<()> {
+        let assets_path = base_path.join(ASSETS_DIR);
+        if !assets_path.exists() {
+            return Ok(());
+        }
+        let read_dir = |path: &Path| {
+            fs::read_dir(path)
+                .map_err(|e| StorageError::Io(format!("Failed to read directory: {}", e)))
+        };
+
+        let mut used = BTreeSet::new();
+        for entry in read_dir(base_path)?.flatten() {
+            let path = entry.path();
+            if path.extension().is_none_or(|e| e != "json") {
+                continue;
+            }
+            // A document that can't be read keeps every asset, rather than losing its own
+            let value = fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str(&json).ok());
+            let Some(value) = value else {
+                return Ok(());
+            };
+            used.extend(assets::referenced(&value));
+        }
+
+        for entry in read_dir(&assets_path)?.flatten() {
+            let name = entry.file_name();
+            if !name.to_str().is_some_and(|name| used.contains(name)) {
+                fs::remove_file(entry.path()).map_err(|e| {
+                    StorageError::Io(format!(
+                        "Failed to delete {}: {}",
+                        entry.path().display(),
+                        e
+                    ))
+                })?;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Storage for FileStorage {
     fn save(&self, id: &str, document: &CanvasDocument) -> BoxFuture<'_, StorageResult<()>> {
         let path = self.document_path(id);
-        let json = match document.to_json() {
-            Ok(j) => j,
+        let assets_path = self.assets_path();
+        let parts = document.to_parts().and_then(|(value, store)| {
+            serde_json::to_string_pretty(&value).map(|json| (json, store))
+        });
+        let (json, store) = match parts {
+            Ok(parts) => parts,
             Err(e) => {
                 return Box::pin(async move { Err(StorageError::Serialization(e.to_string())) });
             }
         };
 
         Box::pin(async move {
+            // Assets first, so the document never refers to missing ones
+            Self::write_assets(&assets_path, &store)?;
             fs::write(&path, json)
                 .map_err(|e| StorageError::Io(format!("Failed to write {}: {}", path.display(), e)))
         })
@@ -79,6 +180,7 @@ impl Storage for FileStorage {
 
     fn load(&self, id: &str) -> BoxFuture<'_, StorageResult<CanvasDocument>> {
         let path = self.document_path(id);
+        let assets_path = self.assets_path();
         let id_owned = id.to_string();
 
         Box::pin(async move {
@@ -89,15 +191,19 @@ impl Storage for FileStorage {
             let json = fs::read_to_string(&path).map_err(|e| {
                 StorageError::Io(format!("Failed to read {}: {}", path.display(), e))
             })?;
-
-            CanvasDocument::from_json(&json).map_err(|e| {
+            let parse_error = |e: &dyn std::fmt::Display| {
                 StorageError::Serialization(format!("Failed to parse {}: {}", path.display(), e))
-            })
+            };
+
+            let value = serde_json::from_str(&json).map_err(|e| parse_error(&e))?;
+            let store = Self::read_assets(&assets_path, &assets::referenced(&value))?;
+            CanvasDocument::from_parts(value, &store).map_err(|e| parse_error(&e))
         })
     }
 
     fn delete(&self, id: &str) -> BoxFuture<'_, StorageResult<()>> {
         let path = self.document_path(id);
+        let base = self.base_path.clone();
 
         Box::pin(async move {
             if path.exists() {
@@ -105,7 +211,7 @@ impl Storage for FileStorage {
                     StorageError::Io(format!("Failed to delete {}: {}", path.display(), e))
                 })?;
             }
-            Ok(())
+            Self::remove_unused_assets(&base)
         })
     }
 
@@ -226,6 +332,45 @@ mod tests {
         assert!(!block_on(storage.exists("test")).unwrap());
     }
 
+    #[test]
+    fn test_file_storage_shares_assets() {
+        use crate::shapes::{Image, ImageFormat, Shape};
+        use kurbo::Point;
+
+        let dir = tempdir().unwrap();
+        let storage = FileStorage::new(dir.path().to_path_buf()).unwrap();
+        let image = || Shape::Image(Image::new(Point::ZERO, b"pixels", 1, 1, ImageFormat::Png));
+        let asset_files = || fs::read_dir(storage.assets_path()).unwrap().count();
+
+        let mut first = CanvasDocument::new();
+        first.add_shape(image());
+        first.add_shape(image());
+        let mut second = CanvasDocument::new();
+        second.add_shape(image());
+        block_on(storage.save("first", &first)).unwrap();
+        block_on(storage.save("second", &second)).unwrap();
+        assert_eq!(asset_files(), 1);
+
+        // Documents refer to the asset instead of embedding it
+        let json = fs::read_to_string(storage.document_path("first")).unwrap();
+        assert!(json.contains(&assets::asset_id(b"pixels")));
+        assert!(!json.contains("data_base64"));
+
+        let loaded = block_on(storage.load("first")).unwrap();
+        assert!(
+            loaded
+                .shapes
+                .values()
+                .all(|s| s.as_image().unwrap().data() == b"pixels")
+        );
+
+        // The asset stays while any document uses it
+        block_on(storage.delete("first")).unwrap();
+        assert_eq!(asset_files(), 1);
+        block_on(storage.delete("second")).unwrap();
+        assert_eq!(asset_files(), 0);
+    }
+
     #[test]
     fn test_file_storage_sanitizes_id() {
         let dir = tempdir().unwrap();
@@ -1,14 +1,23 @@
 //! IndexedDB storage implementation using rexie.
 
 use super::{BoxFuture, Storage, StorageError, StorageResult};
+use crate::assets::{self, Asset, AssetStore};
 use crate::canvas::CanvasDocument;
+use js_sys::Uint8Array;
 use rexie::{ObjectStore, Rexie, TransactionMode};
+use serde::Serialize;
+use std::collections::BTreeSet;
 use wasm_bindgen::JsValue;
 
 const DB_NAME: &str = "drafftink";
 const STORE_NAME: &str = "documents";
+/// Store of the assets shared by all documents, keyed by asset id.
+const ASSETS_STORE_NAME: &str = "assets";
 
 /// IndexedDB-based storage using rexie.
+///
+/// Image bytes are kept once per distinct content in a separate object store
+/// shared by all documents.
 pub struct IndexedDbStorage;
 
 impl IndexedDbStorage {
@@ -21,14 +30,36 @@ impl IndexedDbStorage {
     async fn get_db(&self) -> StorageResult<Rexie> {
         // Build database with rexie (no caching since rexie is lightweight)
         let rexie = Rexie::builder(DB_NAME)
-            .version(1)
+            .version(2)
             .add_object_store(ObjectStore::new(STORE_NAME))
+            .add_object_store(ObjectStore::new(ASSETS_STORE_NAME))
             .build()
             .await
             .map_err(|e| StorageError::Other(e.to_string()))?;
 
         Ok(rexie)
     }
+
+    /// Asset ids of every stored document, or `None` if one of them can't be read.
+    async fn used_assets(store: &rexie::Store) -> StorageResult<Option<BTreeSet<String>>> {
+        let keys = store
+            .get_all_keys(None, None)
+            .await
+            .map_err(|e| StorageError::Other(e.to_string()))?;
+        let mut used = BTreeSet::new();
+        for key in keys {
+            let value = store
+                .get(key)
+                .await
+                .map_err(|e| StorageError::Other(e.to_string()))?
+                .and_then(|val| serde_wasm_bindgen::from_value::<serde_json::Value>(val).ok());
+            match value {
+                Some(value) => used.extend(assets::referenced(&value)),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(used))
+    }
 }
 
 impl Default for IndexedDbStorage {
@@ -45,15 +76,39 @@ impl Storage for IndexedDbStorage {
         Box::pin(async move {
             let db = self.get_db().await?;
             let transaction = db
-                .transaction(&[STORE_NAME], TransactionMode::ReadWrite)
+                .transaction(&[STORE_NAME, ASSETS_STORE_NAME], TransactionMode::ReadWrite)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
 
             let store = transaction
                 .store(STORE_NAME)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
+            let assets_store = transaction
+                .store(ASSETS_STORE_NAME)
+                .map_err(|e| StorageError::Other(e.to_string()))?;
 
-            // Use serde-wasm-bindgen for better performance
-            let js_val = serde_wasm_bindgen::to_value(&doc_clone)
+            // Store the versioned JSON form as plain objects, so loading can migrate it,
+            // and each asset the store doesn't have yet as raw bytes
+            let (value, asset_store) = doc_clone
+                .to_parts()
+                .map_err(|e| StorageError::Serialization(e.to_string()))?;
+            let stored: BTreeSet<String> = assets_store
+                .get_all_keys(None, None)
+                .await
+                .map_err(|e| StorageError::Other(e.to_string()))?
+                .into_iter()
+                .filter_map(|key| key.as_string())
+                .collect();
+            for asset in asset_store.iter().filter(|a| !stored.contains(a.id())) {
+                assets_store
+                    .put(
+                        &Uint8Array::from(asset.bytes()).into(),
+                        Some(&JsValue::from_str(asset.id())),
+                    )
+                    .await
+                    .map_err(|e| StorageError::Other(e.to_string()))?;
+            }
+            let js_val = value
+                .serialize(&serde_wasm_bindgen::Serializer::json_compatible())
                 .map_err(|e| StorageError::Serialization(e.to_string()))?;
 
             store
@@ -76,12 +131,15 @@ impl Storage for IndexedDbStorage {
         Box::pin(async move {
             let db = self.get_db().await?;
             let transaction = db
-                .transaction(&[STORE_NAME], TransactionMode::ReadOnly)
+                .transaction(&[STORE_NAME, ASSETS_STORE_NAME], TransactionMode::ReadOnly)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
 
             let store = transaction
                 .store(STORE_NAME)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
+            let assets_store = transaction
+                .store(ASSETS_STORE_NAME)
+                .map_err(|e| StorageError::Other(e.to_string()))?;
 
             let js_val = store
                 .get(JsValue::from_str(&id))
@@ -89,8 +147,23 @@ impl Storage for IndexedDbStorage {
                 .map_err(|e| StorageError::Other(e.to_string()))?;
 
             match js_val {
-                Some(val) => serde_wasm_bindgen::from_value(val)
-                    .map_err(|e| StorageError::Serialization(e.to_string())),
+                Some(val) => {
+                    let value: serde_json::Value = serde_wasm_bindgen::from_value(val)
+                        .map_err(|e| StorageError::Serialization(e.to_string()))?;
+                    // Missing assets are left to fail the load
+                    let mut asset_store = AssetStore::new();
+                    for asset_id in assets::referenced(&value) {
+                        let bytes = assets_store
+                            .get(JsValue::from_str(&asset_id))
+                            .await
+                            .map_err(|e| StorageError::Other(e.to_string()))?;
+                        if let Some(bytes) = bytes {
+                            asset_store.insert(Asset::new(Uint8Array::new(&bytes).to_vec()));
+                        }
+                    }
+                    CanvasDocument::from_parts(value, &asset_store)
+                        .map_err(|e| StorageError::Serialization(e.to_string()))
+                }
                 None => Err(StorageError::NotFound(id)),
             }
         })
@@ -102,18 +175,37 @@ impl Storage for IndexedDbStorage {
         Box::pin(async move {
             let db = self.get_db().await?;
             let transaction = db
-                .transaction(&[STORE_NAME], TransactionMode::ReadWrite)
+                .transaction(&[STORE_NAME, ASSETS_STORE_NAME], TransactionMode::ReadWrite)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
 
             let store = transaction
                 .store(STORE_NAME)
                 .map_err(|e| StorageError::Other(e.to_string()))?;
+            let assets_store = transaction
+                .store(ASSETS_STORE_NAME)
+                .map_err(|e| StorageError::Other(e.to_string()))?;
 
             store
                 .delete(JsValue::from_str(&id))
                 .await
                 .map_err(|e| StorageError::Other(e.to_string()))?;
 
+            // Drop the assets no remaining document refers to
+            if let Some(used) = Self::used_assets(&store).await? {
+                let asset_ids = assets_store
+                    .get_all_keys(None, None)
+                    .await
+                    .map_err(|e| StorageError::Other(e.to_string()))?;
+                for asset_id in asset_ids {
+                    if !asset_id.as_string().is_some_and(|a| used.contains(&a)) {
+                        assets_store
+                            .delete(asset_id)
+                            .await
+                            .map_err(|e| StorageError::Other(e.to_string()))?;
+                    }
+                }
+            }
+
             transaction
                 .done()
                 .await
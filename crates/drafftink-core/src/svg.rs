@@ -0,0 +1,1925 @@
+//! SVG import.
+//!
+//! Reads the static content of an SVG document into an [`SvgGraphic`]: filled and
+//! stroked paths, text runs and embedded raster images in viewport coordinates.
+//! The graphic can be turned into editable shapes with [`SvgGraphic::to_shapes`], or
+//! the whole document kept as an [`ImageFormat::Svg`] image with [`svg_image`] that
+//! renders sharply at any zoom.
+//!
+//! Supported: basic shapes, paths, text and tspans, `<use>`, nested `<svg>`,
+//! transforms, presentation attributes, `style` attributes and simple `<style>` rules
+//! (tag, class and id selectors). Gradients are flattened to their average stop color;
+//! clip paths, masks, filters, markers and patterns are ignored.
+
+use crate::mermaid::text_size;
+use crate::shapes::{
+    Ellipse, FontFamily, FontWeight, Group, Image, ImageFormat, Line, PathStyle, Rectangle,
+    SerializableColor, Shape, ShapeStyle, Sloppiness, Text,
+};
+use kurbo::{
+    Affine, BezPath, Ellipse as KurboEllipse, PathEl, Point, Rect, RoundedRect,
+    Shape as KurboShape, Size,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors that can occur while importing an SVG document.
+#[derive(Debug, Error, PartialEq)]
+pub enum SvgError {
+    #[error("Not an SVG document")]
+    NotSvg,
+    #[error("Malformed XML at byte {offset}: {message}")]
+    Xml { offset: usize, message: String },
+    #[error("SVG has no drawable content")]
+    Empty,
+}
+
+/// Result type for SVG import.
+pub type SvgResult<T> = Result<T, SvgError>;
+
+/// Limit for element nesting and `<use>` indirection.
+const MAX_DEPTH: usize = 64;
+/// Tolerance used when flattening curves into line points.
+const FLATTEN_TOLERANCE: f64 = 0.5;
+/// Initial font size, as in browsers.
+const DEFAULT_FONT_SIZE: f64 = 16.0;
+/// Approximate ascent as a fraction of the font size, to place text by its top edge.
+const TEXT_ASCENT: f64 = 0.8;
+
+/// Drawable content of an SVG document.
+#[derive(Debug, Clone)]
+pub struct SvgGraphic {
+    /// Size of the root viewport. Items are in viewport coordinates.
+    pub size: Size,
+    /// Items in paint order.
+    pub items: Vec<SvgItem>,
+}
+
+/// One drawable item of an [`SvgGraphic`].
+#[derive(Debug, Clone)]
+pub enum SvgItem {
+    Path(SvgPath),
+    Text(SvgText),
+    Image(SvgImage),
+}
+
+/// A filled and/or stroked path.
+#[derive(Debug, Clone)]
+pub struct SvgPath {
+    pub path: BezPath,
+    pub fill: Option<SerializableColor>,
+    /// Fill with the even-odd rule instead of non-zero.
+    pub even_odd: bool,
+    pub stroke: Option<SerializableColor>,
+    pub stroke_width: f64,
+    /// The element the path came from, when it maps onto a native shape.
+    pub kind: SvgPathKind,
+}
+
+/// Source element of an [`SvgPath`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SvgPathKind {
+    Path,
+    /// An axis-aligned `<rect>` with its corner radius.
+    Rect {
+        rect: Rect,
+        radius: f64,
+    },
+    /// An axis-aligned `<circle>` or `<ellipse>`, by its bounding box.
+    Ellipse(Rect),
+}
+
+/// A single-line text run.
+#[derive(Debug, Clone)]
+pub struct SvgText {
+    /// Start of the baseline.
+    pub position: Point,
+    pub content: String,
+    pub font_size: f64,
+    pub bold: bool,
+    pub color: SerializableColor,
+}
+
+/// An embedded raster (or nested SVG) image.
+#[derive(Debug, Clone)]
+pub struct SvgImage {
+    pub rect: Rect,
+    pub format: ImageFormat,
+    pub data: Vec<u8>,
+}
+
+/// Check whether text is an SVG document.
+pub fn is_svg(text: &str) -> bool {
+    let mut parser = XmlParser::new(text.trim_start_matches('\u{feff}'));
+    parser.skip_misc().is_ok()
+        && parser.rest().strip_prefix("<svg").is_some_and(|rest| {
+            rest.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        })
+}
+
+/// Parse an SVG document.
+pub fn parse_svg(source: &str) -> SvgResult<SvgGraphic> {
+    let root = parse_xml(source)?;
+
+    let mut ids = HashMap::new();
+    let mut css = String::new();
+    index(&root, &mut ids, &mut css);
+    let mut rules = Vec::new();
+    parse_stylesheet(&css, &mut rules);
+    // Later rules of equal specificity win, so keep source order within each level
+    rules.sort_by_key(|rule| rule.specificity);
+    let gradients = gradient_colors(&ids);
+
+    let mut converter = Converter {
+        ids,
+        rules,
+        gradients,
+        items: Vec::new(),
+    };
+    let (size, transform) = viewport(&root);
+    converter.element(&root, transform, &Style::default(), 0);
+
+    if converter.items.is_empty() {
+        return Err(SvgError::Empty);
+    }
+    let size = size.unwrap_or_else(|| {
+        // Without a declared size, the viewport extends to the content
+        let bounds = converter
+            .items
+            .iter()
+            .map(SvgItem::bounds)
+            .reduce(|a, b| a.union(b))
+            .unwrap_or_default();
+        Size::new(bounds.x1.max(1.0), bounds.y1.max(1.0))
+    });
+    Ok(SvgGraphic {
+        size,
+        items: converter.items,
+    })
+}
+
+/// Create an image shape that displays an SVG document at its intrinsic size,
+/// with the top-left corner at the origin.
+pub fn svg_image(source: &str) -> SvgResult<Image> {
+    let graphic = parse_svg(source)?;
+    let mut image = Image::new(
+        Point::ZERO,
+        source.as_bytes(),
+        graphic.size.width.ceil() as u32,
+        graphic.size.height.ceil() as u32,
+        ImageFormat::Svg,
+    );
+    image.width = graphic.size.width;
+    image.height = graphic.size.height;
+    Ok(image)
+}
+
+impl SvgGraphic {
+    /// Convert to editable shapes, grouped when there is more than one.
+    ///
+    /// Rectangles, circles and ellipses become native shapes; other paths become one
+    /// closed or open polyline per subpath, so holes in compound paths are filled.
+    pub fn to_shapes(&self) -> Vec<Shape> {
+        let mut shapes: Vec<Shape> = self.items.iter().flat_map(SvgItem::to_shapes).collect();
+        if shapes.len() > 1 {
+            shapes = vec![Shape::Group(Group::new(shapes))];
+        }
+        shapes
+    }
+}
+
+impl SvgItem {
+    /// Approximate bounds of the item, including stroke width.
+    pub fn bounds(&self) -> Rect {
+        match self {
+            SvgItem::Path(path) => {
+                let bounds = path.path.bounding_box();
+                if path.stroke.is_some() {
+                    bounds.inflate(path.stroke_width / 2.0, path.stroke_width / 2.0)
+                } else {
+                    bounds
+                }
+            }
+            SvgItem::Text(text) => {
+                let size = text_size(&text.content, text.font_size);
+                let top = text.position.y - TEXT_ASCENT * text.font_size;
+                Rect::from_origin_size(Point::new(text.position.x, top), size)
+            }
+            SvgItem::Image(image) => image.rect,
+        }
+    }
+
+    fn to_shapes(&self) -> Vec<Shape> {
+        match self {
+            SvgItem::Path(path) => path.to_shapes(),
+            SvgItem::Text(text) => vec![Shape::Text(text.to_text())],
+            SvgItem::Image(image) => vec![Shape::Image(image.to_image())],
+        }
+    }
+}
+
+impl SvgText {
+    /// A sans-serif text shape positioned so its baseline matches the run.
+    pub fn to_text(&self) -> Text {
+        let top_left = Point::new(
+            self.position.x,
+            self.position.y - TEXT_ASCENT * self.font_size,
+        );
+        let mut text = Text::new(top_left, self.content.clone())
+            .with_font_size(self.font_size)
+            .with_font_family(FontFamily::NotoSans);
+        if self.bold {
+            text = text.with_font_weight(FontWeight::Heavy);
+        }
+        text.style.stroke_color = self.color;
+        text
+    }
+}
+
+impl SvgImage {
+    /// An image shape covering the image's rectangle.
+    pub fn to_image(&self) -> Image {
+        let mut image = Image::new(
+            self.rect.origin(),
+            &self.data,
+            self.rect.width().round().max(1.0) as u32,
+            self.rect.height().round().max(1.0) as u32,
+            self.format,
+        );
+        image.width = self.rect.width();
+        image.height = self.rect.height();
+        image
+    }
+}
+
+impl SvgPath {
+    fn style(&self) -> ShapeStyle {
+        ShapeStyle {
+            stroke_color: self.stroke.unwrap_or(SerializableColor::transparent()),
+            stroke_width: self.stroke_width,
+            fill_color: self.fill,
+            sloppiness: Sloppiness::Architect,
+            ..ShapeStyle::default()
+        }
+    }
+
+    fn to_shapes(&self) -> Vec<Shape> {
+        let style = self.style();
+        match self.kind {
+            SvgPathKind::Rect { rect, radius } => {
+                let mut rectangle = Rectangle::new(rect.origin(), rect.width(), rect.height());
+                rectangle.corner_radius = radius;
+                rectangle.style = style;
+                vec![Shape::Rectangle(rectangle)]
+            }
+            SvgPathKind::Ellipse(rect) => {
+                let mut ellipse =
+                    Ellipse::new(rect.center(), rect.width() / 2.0, rect.height() / 2.0);
+                ellipse.style = style;
+                vec![Shape::Ellipse(ellipse)]
+            }
+            SvgPathKind::Path => subpaths(&self.path)
+                .into_iter()
+                .filter_map(|(mut points, closed)| {
+                    // Filled subpaths are closed implicitly
+                    if (closed || self.fill.is_some()) && points.len() > 2 {
+                        points.push(points[0]);
+                    }
+                    if points.len() < 2 {
+                        return None;
+                    }
+                    let mut line = Line::from_points(points, PathStyle::Direct);
+                    line.style = style.clone();
+                    Some(Shape::Line(line))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Flatten a path into polylines, one per subpath, with a flag for explicit closing.
+/// The closing point is not repeated.
+fn subpaths(path: &BezPath) -> Vec<(Vec<Point>, bool)> {
+    let mut subpaths: Vec<(Vec<Point>, bool)> = Vec::new();
+    kurbo::flatten(path, FLATTEN_TOLERANCE, |el| match el {
+        PathEl::MoveTo(p) => subpaths.push((vec![p], false)),
+        PathEl::LineTo(p) => match subpaths.last_mut() {
+            Some((points, _)) if points.last().is_none_or(|last| last.distance(p) > 1e-6) => {
+                points.push(p)
+            }
+            _ => {}
+        },
+        PathEl::ClosePath => {
+            if let Some((points, closed)) = subpaths.last_mut() {
+                if points.len() > 2 && points[0].distance(points[points.len() - 1]) < 1e-6 {
+                    points.pop();
+                }
+                *closed = true;
+            }
+        }
+        _ => {}
+    });
+    subpaths
+}
+
+// ---------------------------------------------------------------------------
+// Conversion
+// ---------------------------------------------------------------------------
+
+/// Paint of a fill or stroke.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    None,
+    Color(SerializableColor),
+    CurrentColor,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TextAnchor {
+    Start,
+    Middle,
+    End,
+}
+
+/// Computed style of an element.
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Paint,
+    stroke: Paint,
+    color: SerializableColor,
+    stroke_width: f64,
+    fill_opacity: f64,
+    stroke_opacity: f64,
+    /// Product of the `opacity` of the element and its ancestors.
+    opacity: f64,
+    even_odd: bool,
+    font_size: f64,
+    bold: bool,
+    anchor: TextAnchor,
+    visible: bool,
+    /// `display` is not inherited; `false` skips the element and its children.
+    display: bool,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Self {
+            fill: Paint::Color(SerializableColor::black()),
+            stroke: Paint::None,
+            color: SerializableColor::black(),
+            stroke_width: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            even_odd: false,
+            font_size: DEFAULT_FONT_SIZE,
+            bold: false,
+            anchor: TextAnchor::Start,
+            visible: true,
+            display: true,
+        }
+    }
+}
+
+impl Style {
+    /// Resolve a paint to a color with the opacities applied.
+    fn color(&self, paint: Paint, opacity: f64) -> Option<SerializableColor> {
+        let color = match paint {
+            Paint::None => return None,
+            Paint::Color(color) => color,
+            Paint::CurrentColor => self.color,
+        };
+        let alpha = (color.a as f64 * opacity * self.opacity).round();
+        (alpha >= 1.0).then_some(SerializableColor {
+            a: alpha as u8,
+            ..color
+        })
+    }
+}
+
+/// A text run being collected from a `<text>` element.
+struct TextRun {
+    /// Baseline start; advanced past the text when the run is flushed.
+    origin: Point,
+    content: String,
+    /// Style of the first non-blank content.
+    style: Option<Style>,
+}
+
+struct Converter<'a> {
+    ids: HashMap<&'a str, &'a Element>,
+    rules: Vec<CssRule>,
+    gradients: HashMap<&'a str, SerializableColor>,
+    items: Vec<SvgItem>,
+}
+
+impl<'a> Converter<'a> {
+    fn element(&mut self, element: &'a Element, transform: Affine, parent: &Style, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let style = self.style(element, parent);
+        if !style.display {
+            return;
+        }
+        let transform = match element.attr("transform") {
+            Some(value) => transform * parse_transform(value),
+            None => transform,
+        };
+
+        match element.name.as_str() {
+            "g" | "a" | "switch" => self.children(element, transform, &style, depth),
+            "svg" if depth == 0 => self.children(element, transform, &style, depth),
+            "svg" => {
+                let (x, y) = (
+                    length(element, "x", style.font_size),
+                    length(element, "y", style.font_size),
+                );
+                let (_, viewport_transform) = viewport(element);
+                let transform = transform * Affine::translate((x, y)) * viewport_transform;
+                self.children(element, transform, &style, depth);
+            }
+            "use" => {
+                let Some(target) = element
+                    .href()
+                    .and_then(|href| href.strip_prefix('#'))
+                    .and_then(|id| self.ids.get(id).copied())
+                else {
+                    return;
+                };
+                let (x, y) = (
+                    length(element, "x", style.font_size),
+                    length(element, "y", style.font_size),
+                );
+                let transform = transform * Affine::translate((x, y));
+                if target.name == "symbol" {
+                    let (_, symbol_transform) = viewport(target);
+                    self.children(target, transform * symbol_transform, &style, depth + 1);
+                } else {
+                    self.element(target, transform, &style, depth + 1);
+                }
+            }
+            "text" => {
+                let mut run = TextRun {
+                    origin: Point::ZERO,
+                    content: String::new(),
+                    style: None,
+                };
+                self.text(element, transform, &style, &mut run);
+                self.flush_text(&mut run, transform);
+            }
+            "image" => self.image(element, transform, &style),
+            _ => {
+                if let Some((path, kind)) = geometry(element, style.font_size) {
+                    self.path(path, kind, transform, &style);
+                }
+            }
+        }
+    }
+
+    fn children(&mut self, element: &'a Element, transform: Affine, style: &Style, depth: usize) {
+        for child in element.elements() {
+            self.element(child, transform, style, depth + 1);
+        }
+    }
+
+    /// Compute an element's style: presentation attributes, then `<style>` rules by
+    /// specificity, then the `style` attribute.
+    fn style(&self, element: &Element, parent: &Style) -> Style {
+        let mut style = parent.clone();
+        style.display = true;
+        for (name, value) in &element.attrs {
+            self.apply(&mut style, parent, name, value);
+        }
+        for rule in self
+            .rules
+            .iter()
+            .filter(|rule| rule.selector.matches(element))
+        {
+            for (name, value) in &rule.declarations {
+                self.apply(&mut style, parent, name, value);
+            }
+        }
+        if let Some(inline) = element.attr("style") {
+            for (name, value) in parse_declarations(inline) {
+                self.apply(&mut style, parent, &name, &value);
+            }
+        }
+        style
+    }
+
+    fn apply(&self, style: &mut Style, parent: &Style, name: &str, value: &str) {
+        let value = value.trim();
+        if value == "inherit" {
+            return;
+        }
+        match name {
+            "fill" => {
+                if let Some(paint) = self.paint(value) {
+                    style.fill = paint;
+                }
+            }
+            "stroke" => {
+                if let Some(paint) = self.paint(value) {
+                    style.stroke = paint;
+                }
+            }
+            "color" => {
+                if let Some(color) = parse_color(value) {
+                    style.color = color;
+                }
+            }
+            "stroke-width" => {
+                if let Some(width) = parse_length(value, parent.font_size) {
+                    style.stroke_width = width.max(0.0);
+                }
+            }
+            "opacity" => {
+                if let Some(opacity) = parse_opacity(value) {
+                    style.opacity = parent.opacity * opacity;
+                }
+            }
+            "fill-opacity" => {
+                if let Some(opacity) = parse_opacity(value) {
+                    style.fill_opacity = opacity;
+                }
+            }
+            "stroke-opacity" => {
+                if let Some(opacity) = parse_opacity(value) {
+                    style.stroke_opacity = opacity;
+                }
+            }
+            "fill-rule" => style.even_odd = value == "evenodd",
+            "font-size" => {
+                let size = match value.strip_suffix('%') {
+                    Some(percent) => percent
+                        .trim()
+                        .parse::<f64>()
+                        .ok()
+                        .map(|p| parent.font_size * p / 100.0),
+                    None => parse_length(value, parent.font_size),
+                };
+                if let Some(size) = size.filter(|s| *s > 0.0) {
+                    style.font_size = size;
+                }
+            }
+            "font-weight" => {
+                style.bold = matches!(value, "bold" | "bolder")
+                    || value.parse::<u32>().is_ok_and(|weight| weight >= 600);
+            }
+            "text-anchor" => {
+                style.anchor = match value {
+                    "middle" => TextAnchor::Middle,
+                    "end" => TextAnchor::End,
+                    _ => TextAnchor::Start,
+                };
+            }
+            "display" => style.display = value != "none",
+            "visibility" => style.visible = value == "visible",
+            _ => {}
+        }
+    }
+
+    /// Parse a paint value. Unresolvable references without a fallback paint nothing.
+    fn paint(&self, value: &str) -> Option<Paint> {
+        match value {
+            "none" => return Some(Paint::None),
+            "currentColor" | "currentcolor" => return Some(Paint::CurrentColor),
+            _ => {}
+        }
+        if let Some(rest) = value.strip_prefix("url(") {
+            let (reference, fallback) = rest.split_once(')')?;
+            let id = reference
+                .trim()
+                .trim_matches(['"', '\''])
+                .trim_start_matches('#');
+            return Some(match self.gradients.get(id) {
+                Some(color) => Paint::Color(*color),
+                None => self.paint(fallback.trim()).unwrap_or(Paint::None),
+            });
+        }
+        parse_color(value).map(Paint::Color)
+    }
+
+    fn path(&mut self, mut path: BezPath, kind: SvgPathKind, transform: Affine, style: &Style) {
+        if !style.visible {
+            return;
+        }
+        let fill = style.color(style.fill, style.fill_opacity);
+        let stroke = style
+            .color(style.stroke, style.stroke_opacity)
+            .filter(|_| style.stroke_width > 0.0);
+        if fill.is_none() && stroke.is_none() {
+            return;
+        }
+
+        let scale = transform.determinant().abs().sqrt();
+        let [_, b, c, ..] = transform.as_coeffs();
+        let axis_aligned = b.abs() < 1e-9 && c.abs() < 1e-9;
+        let kind = match kind {
+            SvgPathKind::Rect { rect, radius } if axis_aligned => SvgPathKind::Rect {
+                rect: transform.transform_rect_bbox(rect),
+                radius: radius * scale,
+            },
+            SvgPathKind::Ellipse(rect) if axis_aligned => {
+                SvgPathKind::Ellipse(transform.transform_rect_bbox(rect))
+            }
+            _ => SvgPathKind::Path,
+        };
+        path.apply_affine(transform);
+
+        self.items.push(SvgItem::Path(SvgPath {
+            path,
+            fill,
+            even_odd: style.even_odd,
+            stroke,
+            stroke_width: style.stroke_width * scale,
+            kind,
+        }));
+    }
+
+    /// Collect the text of a `<text>` or `<tspan>`, starting a new run wherever
+    /// the position is set explicitly.
+    fn text(&mut self, element: &'a Element, transform: Affine, style: &Style, run: &mut TextRun) {
+        // Only the first value of coordinate lists is used
+        let first = |name: &str| {
+            let value = element.attr(name)?;
+            let first = value.split([',', ' ']).find(|v| !v.is_empty())?;
+            parse_length(first, style.font_size)
+        };
+        let (x, y) = (first("x"), first("y"));
+        let (dx, dy) = (first("dx").unwrap_or(0.0), first("dy").unwrap_or(0.0));
+        if x.is_some() || y.is_some() || dx != 0.0 || dy != 0.0 {
+            self.flush_text(run, transform);
+            run.origin = Point::new(
+                x.unwrap_or(run.origin.x) + dx,
+                y.unwrap_or(run.origin.y) + dy,
+            );
+        }
+
+        for child in &element.children {
+            match child {
+                Node::Text(text) => {
+                    if run.style.is_none() && !text.trim().is_empty() {
+                        run.style = Some(style.clone());
+                    }
+                    for c in text.chars() {
+                        if !c.is_whitespace() {
+                            run.content.push(c);
+                        } else if !run.content.ends_with(' ') {
+                            run.content.push(' ');
+                        }
+                    }
+                }
+                Node::Element(child) if matches!(child.name.as_str(), "tspan" | "a") => {
+                    let child_style = self.style(child, style);
+                    if child_style.display {
+                        self.text(child, transform, &child_style, run);
+                    }
+                }
+                Node::Element(_) => {}
+            }
+        }
+    }
+
+    fn flush_text(&mut self, run: &mut TextRun, transform: Affine) {
+        let content = run.content.trim().to_string();
+        run.content.clear();
+        let Some(style) = run.style.take() else {
+            return;
+        };
+        if content.is_empty() {
+            return;
+        }
+
+        let width = text_size(&content, style.font_size).width;
+        let start = match style.anchor {
+            TextAnchor::Start => run.origin.x,
+            TextAnchor::Middle => run.origin.x - width / 2.0,
+            TextAnchor::End => run.origin.x - width,
+        };
+        run.origin.x = start + width;
+
+        let Some(color) = style.color(style.fill, style.fill_opacity) else {
+            return;
+        };
+        if !style.visible {
+            return;
+        }
+        self.items.push(SvgItem::Text(SvgText {
+            position: transform * Point::new(start, run.origin.y),
+            content,
+            font_size: style.font_size * transform.determinant().abs().sqrt(),
+            bold: style.bold,
+            color,
+        }));
+    }
+
+    fn image(&mut self, element: &Element, transform: Affine, style: &Style) {
+        if !style.visible {
+            return;
+        }
+        let Some((format, data)) = element.href().and_then(decode_data_uri) else {
+            return;
+        };
+        let (x, y) = (
+            length(element, "x", style.font_size),
+            length(element, "y", style.font_size),
+        );
+        let (width, height) = (
+            length(element, "width", style.font_size),
+            length(element, "height", style.font_size),
+        );
+        if width <= 0.0 || height <= 0.0 {
+            return;
+        }
+        let rect = Rect::new(x, y, x + width, y + height);
+        self.items.push(SvgItem::Image(SvgImage {
+            rect: transform.transform_rect_bbox(rect),
+            format,
+            data,
+        }));
+    }
+}
+
+/// Length attribute of an element, 0 if missing or relative.
+fn length(element: &Element, name: &str, font_size: f64) -> f64 {
+    element
+        .attr(name)
+        .and_then(|value| parse_length(value, font_size))
+        .unwrap_or(0.0)
+}
+
+/// Outline of a basic shape or path element in its user space.
+fn geometry(element: &Element, font_size: f64) -> Option<(BezPath, SvgPathKind)> {
+    let len = |name: &str| element.attr(name).and_then(|v| parse_length(v, font_size));
+    match element.name.as_str() {
+        "path" => {
+            let path = BezPath::from_svg(element.attr("d")?).ok()?;
+            Some((path, SvgPathKind::Path))
+        }
+        "rect" => {
+            let (x, y) = (len("x").unwrap_or(0.0), len("y").unwrap_or(0.0));
+            let (width, height) = (len("width")?, len("height")?);
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+            let rect = Rect::new(x, y, x + width, y + height);
+            let (rx, ry) = match (len("rx"), len("ry")) {
+                (Some(rx), Some(ry)) => (rx, ry),
+                (Some(r), None) | (None, Some(r)) => (r, r),
+                (None, None) => (0.0, 0.0),
+            };
+            let radius = rx.min(ry).clamp(0.0, width.min(height) / 2.0);
+            let path = if radius > 0.0 {
+                RoundedRect::from_rect(rect, radius).to_path(0.1)
+            } else {
+                rect.to_path(0.1)
+            };
+            Some((path, SvgPathKind::Rect { rect, radius }))
+        }
+        "circle" | "ellipse" => {
+            let center = Point::new(len("cx").unwrap_or(0.0), len("cy").unwrap_or(0.0));
+            let (rx, ry) = if element.name == "circle" {
+                let r = len("r")?;
+                (r, r)
+            } else {
+                match (len("rx"), len("ry")) {
+                    (Some(rx), Some(ry)) => (rx, ry),
+                    (Some(r), None) | (None, Some(r)) => (r, r),
+                    (None, None) => return None,
+                }
+            };
+            if rx <= 0.0 || ry <= 0.0 {
+                return None;
+            }
+            let rect = Rect::from_center_size(center, (rx * 2.0, ry * 2.0));
+            let path = KurboEllipse::from_rect(rect).to_path(0.1);
+            Some((path, SvgPathKind::Ellipse(rect)))
+        }
+        "line" => {
+            let mut path = BezPath::new();
+            path.move_to((len("x1").unwrap_or(0.0), len("y1").unwrap_or(0.0)));
+            path.line_to((len("x2").unwrap_or(0.0), len("y2").unwrap_or(0.0)));
+            Some((path, SvgPathKind::Path))
+        }
+        "polyline" | "polygon" => {
+            let numbers = number_list(element.attr("points")?);
+            let mut points = numbers
+                .chunks_exact(2)
+                .map(|pair| Point::new(pair[0], pair[1]));
+            let mut path = BezPath::new();
+            path.move_to(points.next()?);
+            for point in points {
+                path.line_to(point);
+            }
+            if element.name == "polygon" {
+                path.close_path();
+            }
+            Some((path, SvgPathKind::Path))
+        }
+        _ => None,
+    }
+}
+
+/// Size of an `<svg>` or `<symbol>` viewport and the transform from its user space.
+fn viewport(element: &Element) -> (Option<Size>, Affine) {
+    let len = |name: &str| {
+        element
+            .attr(name)
+            .and_then(|v| parse_length(v, DEFAULT_FONT_SIZE))
+            .filter(|v| *v > 0.0)
+    };
+    let view_box = element
+        .attr("viewBox")
+        .map(number_list)
+        .filter(|v| v.len() == 4 && v[2] > 0.0 && v[3] > 0.0)
+        .map(|v| Rect::new(v[0], v[1], v[0] + v[2], v[1] + v[3]));
+    let size = match (len("width"), len("height"), view_box) {
+        (Some(width), Some(height), _) => Some(Size::new(width, height)),
+        (Some(width), None, Some(vb)) => Some(Size::new(width, width * vb.height() / vb.width())),
+        (None, Some(height), Some(vb)) => {
+            Some(Size::new(height * vb.width() / vb.height(), height))
+        }
+        (None, None, Some(vb)) => Some(vb.size()),
+        _ => None,
+    };
+    let transform = match (view_box, size) {
+        (Some(view_box), Some(size)) => {
+            view_box_transform(view_box, size, element.attr("preserveAspectRatio"))
+        }
+        _ => Affine::IDENTITY,
+    };
+    (size, transform)
+}
+
+/// Map a view box into a viewport of `size` per `preserveAspectRatio`.
+fn view_box_transform(view_box: Rect, size: Size, aspect: Option<&str>) -> Affine {
+    let origin = Affine::translate(-view_box.origin().to_vec2());
+    let sx = size.width / view_box.width();
+    let sy = size.height / view_box.height();
+    let mut words = aspect.unwrap_or("").split_whitespace();
+    let align = words.next().unwrap_or("xMidYMid");
+    if align == "none" {
+        return Affine::scale_non_uniform(sx, sy) * origin;
+    }
+    let scale = if words.next() == Some("slice") {
+        sx.max(sy)
+    } else {
+        sx.min(sy)
+    };
+    let fraction = |part: Option<&str>| match part {
+        Some("Min") => 0.0,
+        Some("Max") => 1.0,
+        _ => 0.5,
+    };
+    let ax = fraction(align.get(1..4));
+    let ay = fraction(align.get(5..8));
+    let tx = (size.width - view_box.width() * scale) * ax;
+    let ty = (size.height - view_box.height() * scale) * ay;
+    Affine::translate((tx, ty)) * Affine::scale(scale) * origin
+}
+
+/// Decode a base64 `data:` URI holding an image.
+fn decode_data_uri(href: &str) -> Option<(ImageFormat, Vec<u8>)> {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+
+    let (meta, payload) = href.trim().strip_prefix("data:")?.split_once(',')?;
+    if !meta.ends_with(";base64") {
+        return None;
+    }
+    let payload: String = payload.split_whitespace().collect();
+    let data = STANDARD.decode(payload).ok()?;
+    let format = ImageFormat::from_magic_bytes(&data)?;
+    Some((format, data))
+}
+
+/// Index elements by id and gather the text of all `<style>` elements.
+fn index<'a>(element: &'a Element, ids: &mut HashMap<&'a str, &'a Element>, css: &mut String) {
+    if let Some(id) = element.attr("id") {
+        ids.entry(id).or_insert(element);
+    }
+    if element.name == "style" {
+        css.push_str(&element.text());
+        css.push('\n');
+    }
+    for child in element.elements() {
+        index(child, ids, css);
+    }
+}
+
+/// Average stop color of each gradient, following `href` to inherited stops.
+fn gradient_colors<'a>(ids: &HashMap<&'a str, &'a Element>) -> HashMap<&'a str, SerializableColor> {
+    let has_stops = |element: &Element| element.elements().any(|e| e.name == "stop");
+    ids.iter()
+        .filter(|(_, element)| element.name.ends_with("Gradient"))
+        .filter_map(|(&id, &element)| {
+            let mut gradient = element;
+            for _ in 0..8 {
+                if has_stops(gradient) {
+                    break;
+                }
+                match gradient
+                    .href()
+                    .and_then(|href| href.strip_prefix('#'))
+                    .and_then(|href| ids.get(href).copied())
+                {
+                    Some(next) => gradient = next,
+                    None => break,
+                }
+            }
+            average_stop_color(gradient).map(|color| (id, color))
+        })
+        .collect()
+}
+
+fn average_stop_color(gradient: &Element) -> Option<SerializableColor> {
+    let stops: Vec<SerializableColor> = gradient
+        .elements()
+        .filter(|e| e.name == "stop")
+        .map(|stop| {
+            let inline = stop
+                .attr("style")
+                .map(parse_declarations)
+                .unwrap_or_default();
+            let get = |name: &str| {
+                inline
+                    .iter()
+                    .find(|(key, _)| key == name)
+                    .map(|(_, value)| value.as_str())
+                    .or_else(|| stop.attr(name))
+            };
+            let color = get("stop-color")
+                .and_then(parse_color)
+                .unwrap_or(SerializableColor::black());
+            let opacity = get("stop-opacity").and_then(parse_opacity).unwrap_or(1.0);
+            SerializableColor {
+                a: (color.a as f64 * opacity).round() as u8,
+                ..color
+            }
+        })
+        .collect();
+    if stops.is_empty() {
+        return None;
+    }
+    let average = |channel: fn(&SerializableColor) -> u8| {
+        let sum: f64 = stops.iter().map(|c| channel(c) as f64).sum();
+        (sum / stops.len() as f64).round() as u8
+    };
+    Some(SerializableColor::new(
+        average(|c| c.r),
+        average(|c| c.g),
+        average(|c| c.b),
+        average(|c| c.a),
+    ))
+}
+
+// ---------------------------------------------------------------------------
+// Values
+// ---------------------------------------------------------------------------
+
+/// Split a leading number off `text`.
+fn leading_number(text: &str) -> Option<(f64, &str)> {
+    let bytes = text.as_bytes();
+    let digits = |mut i: usize| {
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+        i
+    };
+    let mut i = 0;
+    if matches!(bytes.first(), Some(b'+' | b'-')) {
+        i += 1;
+    }
+    let int_end = digits(i);
+    let mut end = int_end;
+    if end < bytes.len() && bytes[end] == b'.' {
+        let frac_end = digits(end + 1);
+        if frac_end > end + 1 || int_end > i {
+            end = frac_end;
+        }
+    }
+    if end == i {
+        return None;
+    }
+    if end < bytes.len() && matches!(bytes[end], b'e' | b'E') {
+        let mut j = end + 1;
+        if j < bytes.len() && matches!(bytes[j], b'+' | b'-') {
+            j += 1;
+        }
+        let exp_end = digits(j);
+        if exp_end > j {
+            end = exp_end;
+        }
+    }
+    Some((text[..end].parse().ok()?, &text[end..]))
+}
+
+/// Parse numbers separated by whitespace and/or commas, stopping at the first error.
+fn number_list(text: &str) -> Vec<f64> {
+    let mut numbers = Vec::new();
+    let mut rest = text;
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+        match leading_number(rest) {
+            Some((number, tail)) => {
+                numbers.push(number);
+                rest = tail;
+            }
+            None => return numbers,
+        }
+    }
+}
+
+/// Parse an absolute or font-relative length in pixels. Percentages are not supported.
+fn parse_length(value: &str, font_size: f64) -> Option<f64> {
+    let (number, unit) = leading_number(value.trim())?;
+    let scale = match unit.trim() {
+        "" | "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "mm" => 96.0 / 25.4,
+        "cm" => 96.0 / 2.54,
+        "in" => 96.0,
+        "em" => font_size,
+        "ex" => font_size / 2.0,
+        "rem" => DEFAULT_FONT_SIZE,
+        _ => return None,
+    };
+    Some(number * scale)
+}
+
+/// Parse an opacity as a number or percentage, clamped to 0..=1.
+fn parse_opacity(value: &str) -> Option<f64> {
+    let (number, unit) = leading_number(value.trim())?;
+    let opacity = match unit.trim() {
+        "" => number,
+        "%" => number / 100.0,
+        _ => return None,
+    };
+    Some(opacity.clamp(0.0, 1.0))
+}
+
+/// Parse a transform list; unknown or malformed functions are skipped.
+fn parse_transform(text: &str) -> Affine {
+    let mut transform = Affine::IDENTITY;
+    let mut rest = text;
+    while let Some(open) = rest.find('(') {
+        let name = rest[..open].trim_matches(|c: char| c.is_whitespace() || c == ',');
+        let Some(close) = rest[open..].find(')') else {
+            break;
+        };
+        let args = number_list(&rest[open + 1..open + close]);
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0.0);
+        let step = match (name, args.len()) {
+            ("matrix", 6) => Affine::new([arg(0), arg(1), arg(2), arg(3), arg(4), arg(5)]),
+            ("translate", 1 | 2) => Affine::translate((arg(0), arg(1))),
+            ("scale", 1) => Affine::scale(arg(0)),
+            ("scale", 2) => Affine::scale_non_uniform(arg(0), arg(1)),
+            ("rotate", 1) => Affine::rotate(arg(0).to_radians()),
+            ("rotate", 3) => Affine::rotate_about(arg(0).to_radians(), Point::new(arg(1), arg(2))),
+            ("skewX", 1) => Affine::skew(arg(0).to_radians().tan(), 0.0),
+            ("skewY", 1) => Affine::skew(0.0, arg(0).to_radians().tan()),
+            _ => Affine::IDENTITY,
+        };
+        transform *= step;
+        rest = &rest[open + close + 1..];
+    }
+    transform
+}
+
+/// Parse a CSS color: hex, `rgb()`/`rgba()` or a named color.
+pub(crate) fn parse_color(value: &str) -> Option<SerializableColor> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        let digits: Vec<u8> = hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()?;
+        return match digits.len() {
+            3 | 4 => Some(SerializableColor::new(
+                digits[0] * 17,
+                digits[1] * 17,
+                digits[2] * 17,
+                digits.get(3).map_or(255, |a| a * 17),
+            )),
+            6 | 8 => {
+                let byte = |i: usize| digits[i] * 16 + digits[i + 1];
+                let alpha = if digits.len() == 8 { byte(6) } else { 255 };
+                Some(SerializableColor::new(byte(0), byte(2), byte(4), alpha))
+            }
+            _ => None,
+        };
+    }
+
+    let lower = value.to_ascii_lowercase();
+    if let Some(args) = lower
+        .strip_prefix("rgba(")
+        .or_else(|| lower.strip_prefix("rgb("))
+        .and_then(|args| args.strip_suffix(')'))
+    {
+        let parts: Vec<&str> = args
+            .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+            .filter(|part| !part.is_empty())
+            .collect();
+        if parts.len() < 3 {
+            return None;
+        }
+        let channel = |part: &str| {
+            let (number, unit) = leading_number(part)?;
+            let value = if unit == "%" { number * 2.55 } else { number };
+            Some(value.round().clamp(0.0, 255.0) as u8)
+        };
+        let alpha = match parts.get(3) {
+            Some(part) => (parse_opacity(part)? * 255.0).round() as u8,
+            None => 255,
+        };
+        return Some(SerializableColor::new(
+            channel(parts[0])?,
+            channel(parts[1])?,
+            channel(parts[2])?,
+            alpha,
+        ));
+    }
+
+    if lower == "transparent" {
+        return Some(SerializableColor::transparent());
+    }
+    NAMED_COLORS
+        .binary_search_by_key(&lower.as_str(), |&(name, _)| name)
+        .ok()
+        .map(|i| {
+            let rgb = NAMED_COLORS[i].1;
+            SerializableColor::new((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8, 255)
+        })
+}
+
+/// Format a color as `#rrggbb`, or `#rrggbbaa` when translucent, so that
+/// [`parse_color`] reads it back unchanged. Fully transparent colors give `None`:
+/// each format has its own word for them (`transparent`, `none`).
+pub(crate) fn color_hex(color: SerializableColor) -> Option<String> {
+    let rgb = format!("#{:02x}{:02x}{:02x}", color.r, color.g, color.b);
+    match color.a {
+        0 => None,
+        255 => Some(rgb),
+        alpha => Some(format!("{rgb}{alpha:02x}")),
+    }
+}
+
+/// CSS named colors, sorted by name.
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xF0F8FF),
+    ("antiquewhite", 0xFAEBD7),
+    ("aqua", 0x00FFFF),
+    ("aquamarine", 0x7FFFD4),
+    ("azure", 0xF0FFFF),
+    ("beige", 0xF5F5DC),
+    ("bisque", 0xFFE4C4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xFFEBCD),
+    ("blue", 0x0000FF),
+    ("blueviolet", 0x8A2BE2),
+    ("brown", 0xA52A2A),
+    ("burlywood", 0xDEB887),
+    ("cadetblue", 0x5F9EA0),
+    ("chartreuse", 0x7FFF00),
+    ("chocolate", 0xD2691E),
+    ("coral", 0xFF7F50),
+    ("cornflowerblue", 0x6495ED),
+    ("cornsilk", 0xFFF8DC),
+    ("crimson", 0xDC143C),
+    ("cyan", 0x00FFFF),
+    ("darkblue", 0x00008B),
+    ("darkcyan", 0x008B8B),
+    ("darkgoldenrod", 0xB8860B),
+    ("darkgray", 0xA9A9A9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xA9A9A9),
+    ("darkkhaki", 0xBDB76B),
+    ("darkmagenta", 0x8B008B),
+    ("darkolivegreen", 0x556B2F),
+    ("darkorange", 0xFF8C00),
+    ("darkorchid", 0x9932CC),
+    ("darkred", 0x8B0000),
+    ("darksalmon", 0xE9967A),
+    ("darkseagreen", 0x8FBC8F),
+    ("darkslateblue", 0x483D8B),
+    ("darkslategray", 0x2F4F4F),
+    ("darkslategrey", 0x2F4F4F),
+    ("darkturquoise", 0x00CED1),
+    ("darkviolet", 0x9400D3),
+    ("deeppink", 0xFF1493),
+    ("deepskyblue", 0x00BFFF),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1E90FF),
+    ("firebrick", 0xB22222),
+    ("floralwhite", 0xFFFAF0),
+    ("forestgreen", 0x228B22),
+    ("fuchsia", 0xFF00FF),
+    ("gainsboro", 0xDCDCDC),
+    ("ghostwhite", 0xF8F8FF),
+    ("gold", 0xFFD700),
+    ("goldenrod", 0xDAA520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xADFF2F),
+    ("grey", 0x808080),
+    ("honeydew", 0xF0FFF0),
+    ("hotpink", 0xFF69B4),
+    ("indianred", 0xCD5C5C),
+    ("indigo", 0x4B0082),
+    ("ivory", 0xFFFFF0),
+    ("khaki", 0xF0E68C),
+    ("lavender", 0xE6E6FA),
+    ("lavenderblush", 0xFFF0F5),
+    ("lawngreen", 0x7CFC00),
+    ("lemonchiffon", 0xFFFACD),
+    ("lightblue", 0xADD8E6),
+    ("lightcoral", 0xF08080),
+    ("lightcyan", 0xE0FFFF),
+    ("lightgoldenrodyellow", 0xFAFAD2),
+    ("lightgray", 0xD3D3D3),
+    ("lightgreen", 0x90EE90),
+    ("lightgrey", 0xD3D3D3),
+    ("lightpink", 0xFFB6C1),
+    ("lightsalmon", 0xFFA07A),
+    ("lightseagreen", 0x20B2AA),
+    ("lightskyblue", 0x87CEFA),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xB0C4DE),
+    ("lightyellow", 0xFFFFE0),
+    ("lime", 0x00FF00),
+    ("limegreen", 0x32CD32),
+    ("linen", 0xFAF0E6),
+    ("magenta", 0xFF00FF),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66CDAA),
+    ("mediumblue", 0x0000CD),
+    ("mediumorchid", 0xBA55D3),
+    ("mediumpurple", 0x9370DB),
+    ("mediumseagreen", 0x3CB371),
+    ("mediumslateblue", 0x7B68EE),
+    ("mediumspringgreen", 0x00FA9A),
+    ("mediumturquoise", 0x48D1CC),
+    ("mediumvioletred", 0xC71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xF5FFFA),
+    ("mistyrose", 0xFFE4E1),
+    ("moccasin", 0xFFE4B5),
+    ("navajowhite", 0xFFDEAD),
+    ("navy", 0x000080),
+    ("oldlace", 0xFDF5E6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6B8E23),
+    ("orange", 0xFFA500),
+    ("orangered", 0xFF4500),
+    ("orchid", 0xDA70D6),
+    ("palegoldenrod", 0xEEE8AA),
+    ("palegreen", 0x98FB98),
+    ("paleturquoise", 0xAFEEEE),
+    ("palevioletred", 0xDB7093),
+    ("papayawhip", 0xFFEFD5),
+    ("peachpuff", 0xFFDAB9),
+    ("peru", 0xCD853F),
+    ("pink", 0xFFC0CB),
+    ("plum", 0xDDA0DD),
+    ("powderblue", 0xB0E0E6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xFF0000),
+    ("rosybrown", 0xBC8F8F),
+    ("royalblue", 0x4169E1),
+    ("saddlebrown", 0x8B4513),
+    ("salmon", 0xFA8072),
+    ("sandybrown", 0xF4A460),
+    ("seagreen", 0x2E8B57),
+    ("seashell", 0xFFF5EE),
+    ("sienna", 0xA0522D),
+    ("silver", 0xC0C0C0),
+    ("skyblue", 0x87CEEB),
+    ("slateblue", 0x6A5ACD),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xFFFAFA),
+    ("springgreen", 0x00FF7F),
+    ("steelblue", 0x4682B4),
+    ("tan", 0xD2B48C),
+    ("teal", 0x008080),
+    ("thistle", 0xD8BFD8),
+    ("tomato", 0xFF6347),
+    ("turquoise", 0x40E0D0),
+    ("violet", 0xEE82EE),
+    ("wheat", 0xF5DEB3),
+    ("white", 0xFFFFFF),
+    ("whitesmoke", 0xF5F5F5),
+    ("yellow", 0xFFFF00),
+    ("yellowgreen", 0x9ACD32),
+];
+
+// ---------------------------------------------------------------------------
+// Stylesheets
+// ---------------------------------------------------------------------------
+
+/// A compound selector of tag, id and classes (no combinators).
+#[derive(Debug, Default)]
+struct Selector {
+    tag: Option<String>,
+    id: Option<String>,
+    classes: Vec<String>,
+}
+
+impl Selector {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.is_empty() || text.contains(|c: char| c.is_whitespace() || ">+~[:(".contains(c)) {
+            return None;
+        }
+        let mut selector = Selector::default();
+        let tag_len = text.find(['.', '#']).unwrap_or(text.len());
+        let tag = &text[..tag_len];
+        if !tag.is_empty() && tag != "*" {
+            selector.tag = Some(tag.to_string());
+        }
+        let mut rest = &text[tag_len..];
+        while let Some(marker) = rest.chars().next() {
+            let body = &rest[1..];
+            let len = body.find(['.', '#']).unwrap_or(body.len());
+            let name = &body[..len];
+            if name.is_empty() {
+                return None;
+            }
+            if marker == '.' {
+                selector.classes.push(name.to_string());
+            } else {
+                selector.id = Some(name.to_string());
+            }
+            rest = &body[len..];
+        }
+        Some(selector)
+    }
+
+    fn specificity(&self) -> u32 {
+        self.id.is_some() as u32 * 100 + self.classes.len() as u32 * 10 + self.tag.is_some() as u32
+    }
+
+    fn matches(&self, element: &Element) -> bool {
+        if self.tag.as_ref().is_some_and(|tag| *tag != element.name) {
+            return false;
+        }
+        if self
+            .id
+            .as_deref()
+            .is_some_and(|id| element.attr("id") != Some(id))
+        {
+            return false;
+        }
+        let classes = element.attr("class").unwrap_or("");
+        self.classes
+            .iter()
+            .all(|class| classes.split_whitespace().any(|c| c == class))
+    }
+}
+
+struct CssRule {
+    selector: Selector,
+    specificity: u32,
+    declarations: Vec<(String, String)>,
+}
+
+/// Parse the rules of a stylesheet. At-rules and unsupported selectors are skipped.
+fn parse_stylesheet(css: &str, rules: &mut Vec<CssRule>) {
+    let css = strip_css_comments(css);
+    let mut rest = css.as_str();
+    while let Some(open) = rest.find('{') {
+        let prelude = rest[..open].trim();
+        let mut depth = 0;
+        let mut close = None;
+        for (i, c) in rest[open..].char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        close = Some(open + i);
+                        break;
+                    }
+                }
+                _ => {}
+            }
+        }
+        let Some(close) = close else {
+            break;
+        };
+        if !prelude.starts_with('@') {
+            let declarations = parse_declarations(&rest[open + 1..close]);
+            for selector in prelude.split(',').filter_map(Selector::parse) {
+                rules.push(CssRule {
+                    specificity: selector.specificity(),
+                    selector,
+                    declarations: declarations.clone(),
+                });
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+}
+
+/// Parse `name: value` declarations separated by semicolons.
+fn parse_declarations(text: &str) -> Vec<(String, String)> {
+    text.split(';')
+        .filter_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            let name = name.trim().to_ascii_lowercase();
+            let value = value.trim().trim_end_matches("!important").trim();
+            (!name.is_empty()).then(|| (name, value.to_string()))
+        })
+        .collect()
+}
+
+fn strip_css_comments(css: &str) -> String {
+    let mut out = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("/*") {
+        out.push_str(&rest[..start]);
+        rest = match rest[start + 2..].find("*/") {
+            Some(end) => &rest[start + 2 + end + 2..],
+            None => "",
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+// ---------------------------------------------------------------------------
+// XML
+// ---------------------------------------------------------------------------
+
+#[derive(Debug, Default)]
+pub(crate) struct Element {
+    /// Local name, without a namespace prefix.
+    pub(crate) name: String,
+    /// Attributes with names as written (e.g. `xlink:href`).
+    pub(crate) attrs: Vec<(String, String)>,
+    pub(crate) children: Vec<Node>,
+}
+
+#[derive(Debug)]
+pub(crate) enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub(crate) fn attr(&self, name: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn href(&self) -> Option<&str> {
+        self.attr("href").or_else(|| self.attr("xlink:href"))
+    }
+
+    pub(crate) fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Parse a document whose root element is `<svg>`.
+fn parse_xml(source: &str) -> SvgResult<Element> {
+    let root = parse_xml_root(source)?;
+    if root.name != "svg" {
+        return Err(SvgError::NotSvg);
+    }
+    Ok(root)
+}
+
+/// Parse an XML document of any kind, returning its root element.
+pub(crate) fn parse_xml_root(source: &str) -> SvgResult<Element> {
+    let mut parser = XmlParser::new(source.trim_start_matches('\u{feff}'));
+    parser.skip_misc()?;
+    if !parser.rest().starts_with('<') {
+        return Err(SvgError::NotSvg);
+    }
+    parser.element(0)
+}
+
+/// A small non-validating XML parser: elements, attributes, text and CDATA.
+/// Comments, processing instructions and doctype declarations are skipped.
+struct XmlParser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> XmlParser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self { source, pos: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.pos..]
+    }
+
+    fn error(&self, message: impl Into<String>) -> SvgError {
+        SvgError::Xml {
+            offset: self.pos,
+            message: message.into(),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn skip_past(&mut self, end: &str) -> SvgResult<()> {
+        match self.rest().find(end) {
+            Some(i) => {
+                self.pos += i + end.len();
+                Ok(())
+            }
+            None => Err(self.error(format!("missing `{end}`"))),
+        }
+    }
+
+    /// Skip whitespace, the XML declaration, comments and doctype declarations.
+    fn skip_misc(&mut self) -> SvgResult<()> {
+        loop {
+            self.skip_whitespace();
+            let rest = self.rest();
+            if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if rest.starts_with("<!") {
+                self.skip_declaration()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Skip a `<!...>` declaration, including an internal subset in brackets.
+    fn skip_declaration(&mut self) -> SvgResult<()> {
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '>' if depth <= 0 => {
+                    self.pos += i + 1;
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+        Err(self.error("unterminated declaration"))
+    }
+
+    fn expect(&mut self, c: char) -> SvgResult<()> {
+        if self.rest().starts_with(c) {
+            self.pos += c.len_utf8();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected `{c}`")))
+        }
+    }
+
+    fn name(&mut self) -> SvgResult<&'a str> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '='))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn element(&mut self, depth: usize) -> SvgResult<Element> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("elements nested too deeply"));
+        }
+        self.expect('<')?;
+        let name = self.name()?;
+        let mut element = Element {
+            name: name.rsplit(':').next().unwrap_or(name).to_string(),
+            ..Element::default()
+        };
+
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(element);
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect('=')?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(self.error("expected a quoted attribute value")),
+            };
+            self.pos += 1;
+            let Some(len) = self.rest().find(quote) else {
+                return Err(self.error("unterminated attribute value"));
+            };
+            let value = decode_entities(&self.rest()[..len]);
+            self.pos += len + 1;
+            element.attrs.push((key.to_string(), value));
+        }
+
+        loop {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return Err(self.error(format!("unclosed element <{name}>")));
+            }
+            if rest.starts_with("</") {
+                self.pos += 2;
+                let end = self.name()?;
+                if end != name {
+                    return Err(self.error(format!("expected </{name}>, found </{end}>")));
+                }
+                self.skip_whitespace();
+                self.expect('>')?;
+                return Ok(element);
+            } else if rest.starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if let Some(cdata) = rest.strip_prefix("<![CDATA[") {
+                let Some(len) = cdata.find("]]>") else {
+                    return Err(self.error("unterminated CDATA section"));
+                };
+                element.children.push(Node::Text(cdata[..len].to_string()));
+                self.pos += "<![CDATA[".len() + len + "]]>".len();
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if rest.starts_with("<!") {
+                self.skip_declaration()?;
+            } else if rest.starts_with('<') {
+                let child = self.element(depth + 1)?;
+                element.children.push(Node::Element(child));
+            } else {
+                let len = rest.find('<').unwrap_or(rest.len());
+                element
+                    .children
+                    .push(Node::Text(decode_entities(&rest[..len])));
+                self.pos += len;
+            }
+        }
+    }
+}
+
+/// Decode predefined and numeric character references; unknown ones are kept.
+pub(crate) fn decode_entities(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let decoded = rest.find(';').and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some('\u{a0}'),
+                _ => entity
+                    .strip_prefix("#x")
+                    .or_else(|| entity.strip_prefix("#X"))
+                    .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                    .or_else(|| entity.strip_prefix('#').and_then(|d| d.parse().ok()))
+                    .and_then(char::from_u32),
+            };
+            c.map(|c| (c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(graphic: &SvgGraphic) -> Vec<&SvgPath> {
+        graphic
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SvgItem::Path(path) => Some(path),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_svg() {
+        assert!(is_svg("<svg xmlns=\"http://www.w3.org/2000/svg\"/>"));
+        assert!(is_svg(
+            "<?xml version=\"1.0\"?>\n<!-- icon -->\n<!DOCTYPE svg [ <!ENTITY a \"b\"> ]>\n<svg>"
+        ));
+        assert!(!is_svg("<svgfoo/>"));
+        assert!(!is_svg("<html><svg/></html>"));
+        assert!(!is_svg("{\"type\": \"excalidraw\"}"));
+    }
+
+    #[test]
+    fn test_color_round_trip() {
+        assert_eq!(
+            parse_color("rgb(255 0 0 / 50%)"),
+            Some(SerializableColor::new(255, 0, 0, 128))
+        );
+        assert_eq!(
+            parse_color("#0f08"),
+            Some(SerializableColor::new(0, 255, 0, 136))
+        );
+        for color in [
+            SerializableColor::new(0x12, 0xab, 0xef, 255),
+            SerializableColor::new(1, 2, 3, 128),
+        ] {
+            assert_eq!(parse_color(&color_hex(color).unwrap()), Some(color));
+        }
+        assert_eq!(
+            color_hex(SerializableColor::new(0x12, 0xab, 0xef, 255)).as_deref(),
+            Some("#12abef")
+        );
+        assert_eq!(color_hex(SerializableColor::transparent()), None);
+    }
+
+    #[test]
+    fn test_basic_shapes_and_view_box() {
+        let graphic = parse_svg(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="0 0 100 50">
+                <rect x="10" y="10" width="20" height="10" rx="2" fill="#ff0000"/>
+                <g transform="translate(50 0)">
+                    <circle cx="10" cy="25" r="5" fill="none" stroke="blue" stroke-width="2"/>
+                </g>
+                <polygon points="0,0 10,0 5,8"/>
+                <path d="M0 40 L10 40 Q 15 45 20 40"/>
+            </svg>"##,
+        )
+        .unwrap();
+
+        assert_eq!(graphic.size, Size::new(200.0, 100.0));
+        let paths = paths(&graphic);
+        assert_eq!(paths.len(), 4);
+
+        // The view box is scaled 2x into the viewport
+        assert_eq!(
+            paths[0].kind,
+            SvgPathKind::Rect {
+                rect: Rect::new(20.0, 20.0, 60.0, 40.0),
+                radius: 4.0,
+            }
+        );
+        assert_eq!(paths[0].fill, Some(SerializableColor::new(255, 0, 0, 255)));
+        assert_eq!(paths[0].stroke, None);
+
+        assert_eq!(
+            paths[1].kind,
+            SvgPathKind::Ellipse(Rect::new(110.0, 40.0, 130.0, 60.0))
+        );
+        assert_eq!(paths[1].fill, None);
+        assert_eq!(
+            paths[1].stroke,
+            Some(SerializableColor::new(0, 0, 255, 255))
+        );
+        assert!((paths[1].stroke_width - 4.0).abs() < 1e-9);
+
+        assert_eq!(paths[2].kind, SvgPathKind::Path);
+        assert_eq!(paths[2].fill, Some(SerializableColor::black()));
+    }
+
+    #[test]
+    fn test_styles_classes_and_gradients() {
+        let graphic = parse_svg(
+            r##"<svg viewBox="0 0 100 100">
+                <style><![CDATA[
+                    /* theme */
+                    .accent { fill: rgb(0, 128, 0) }
+                    rect#special.accent { fill: #123 !important; }
+                    @media print { rect { fill: red } }
+                ]]></style>
+                <defs>
+                    <linearGradient id="base"><stop offset="0" stop-color="#000"/><stop offset="1" style="stop-color: #fff"/></linearGradient>
+                    <linearGradient id="fade" xlink:href="#base"/>
+                </defs>
+                <g fill="orange" opacity="0.5">
+                    <rect width="10" height="10"/>
+                    <rect class="accent" width="10" height="10" style="fill-opacity: 50%"/>
+                    <rect id="special" class="accent" width="10" height="10"/>
+                    <rect width="10" height="10" fill="url(#fade)"/>
+                    <rect width="10" height="10" style="display: none"/>
+                </g>
+            </svg>"##,
+        )
+        .unwrap();
+
+        assert!(NAMED_COLORS.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        let fills: Vec<_> = paths(&graphic).iter().map(|p| p.fill).collect();
+        assert_eq!(
+            fills,
+            vec![
+                Some(SerializableColor::new(255, 165, 0, 128)),
+                Some(SerializableColor::new(0, 128, 0, 64)),
+                Some(SerializableColor::new(0x11, 0x22, 0x33, 128)),
+                Some(SerializableColor::new(128, 128, 128, 128)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_text_runs_and_to_shapes() {
+        let graphic = parse_svg(
+            r#"<svg width="300" height="200">
+                <text x="10" y="30" font-size="20" fill="red">Fish &amp; <tspan font-weight="bold">chips</tspan></text>
+                <text x="10" y="80"><tspan x="10">one</tspan><tspan x="10" dy="1.5em">two</tspan></text>
+                <path d="M 100 100 h 50 v 50 z M 200 100 l 20 0" fill="none" stroke="black"/>
+            </svg>"#,
+        )
+        .unwrap();
+
+        let texts: Vec<&SvgText> = graphic
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                SvgItem::Text(text) => Some(text),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(texts.len(), 3);
+        assert_eq!(texts[0].content, "Fish & chips");
+        assert_eq!(texts[0].position, Point::new(10.0, 30.0));
+        assert_eq!(texts[0].color, SerializableColor::new(255, 0, 0, 255));
+        assert_eq!(texts[2].content, "two");
+        assert_eq!(texts[2].position, Point::new(10.0, 104.0));
+
+        let shapes = graphic.to_shapes();
+        assert_eq!(shapes.len(), 1);
+        let Shape::Group(group) = &shapes[0] else {
+            panic!("expected a group");
+        };
+        let lines: Vec<&Line> = group
+            .children()
+            .iter()
+            .filter_map(|s| match s {
+                Shape::Line(line) => Some(line),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].is_closed());
+        assert!(!lines[1].is_closed());
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(parse_svg("<html/>").unwrap_err(), SvgError::NotSvg);
+        assert_eq!(parse_svg("hello").unwrap_err(), SvgError::NotSvg);
+        assert!(matches!(
+            parse_svg("<svg><g></svg>").unwrap_err(),
+            SvgError::Xml { .. }
+        ));
+        assert_eq!(
+            parse_svg("<svg><defs><rect width=\"1\" height=\"1\"/></defs></svg>").unwrap_err(),
+            SvgError::Empty
+        );
+    }
+}
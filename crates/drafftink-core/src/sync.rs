@@ -10,10 +10,30 @@ use serde::{Deserialize, Serialize};
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
     /// Join a room
-    Join { room: String },
+    Join {
+        room: String,
+        /// Version already held (base64 encoded Loro version vector), so the server
+        /// only sends the updates that are missing
+        #[serde(skip_serializing_if = "Option::is_none")]
+        version: Option<String>,
+        /// Edit or view token, for protected rooms
+        #[serde(skip_serializing_if = "Option::is_none")]
+        token: Option<String>,
+        /// Check of the room key, for end-to-end encrypted rooms (see
+        /// [`RoomKey::check`](crate::e2e::RoomKey::check))
+        #[serde(skip_serializing_if = "Option::is_none")]
+        key_check: Option<String>,
+    },
     /// Leave current room
     Leave,
-    /// Sync CRDT data (base64 encoded Loro bytes)
+    /// Protect the current room with new tokens, revoking the old ones, or open it
+    /// to everyone if `edit_token` is `None`. Needs edit access.
+    SetAccess {
+        edit_token: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        view_token: Option<String>,
+    },
+    /// Sync CRDT data (base64 encoded Loro bytes, sealed in encrypted rooms)
     Sync { data: String },
     /// Awareness update (cursor position, selection, etc.)
     Awareness {
@@ -34,7 +54,17 @@ pub enum ServerMessage {
         /// Initial sync data (if room has history)
         #[serde(skip_serializing_if = "Option::is_none")]
         initial_sync: Option<String>,
+        /// Whether the room is protected by tokens
+        #[serde(default)]
+        protected: bool,
+        /// Whether we joined with a view token
+        #[serde(default)]
+        read_only: bool,
     },
+    /// The room's access changed
+    Access { protected: bool, read_only: bool },
+    /// We were refused or removed from a room, e.g. for a missing or revoked token
+    Removed { room: String, message: String },
     /// Peer joined the room
     PeerJoined { peer_id: String },
     /// Peer left the room
@@ -48,8 +78,43 @@ pub enum ServerMessage {
         #[serde(flatten)]
         state: AwarenessState,
     },
-    /// Error message
-    Error { message: String },
+    /// The server rejected a message
+    Error {
+        #[serde(default)]
+        code: ErrorCode,
+        message: String,
+    },
+}
+
+/// Why the server rejected a message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Not a valid protocol message
+    InvalidMessage,
+    /// Larger than the server accepts
+    MessageTooLarge,
+    /// Too many messages; the message was dropped
+    RateLimited,
+    /// The room ID is not accepted
+    InvalidRoom,
+    /// The room has as many peers as it may
+    RoomFull,
+    /// The server has as many rooms as it may
+    ServerFull,
+    /// Our role doesn't allow the request, or we don't own the open room we tried
+    /// to protect
+    Forbidden,
+    /// Access tokens that are too short
+    InvalidToken,
+    /// Sync data sent with read-only access
+    ReadOnly,
+    /// Sync data that can't be decoded or merged
+    InvalidSyncData,
+    /// A code this version doesn't know
+    #[default]
+    #[serde(other)]
+    Unknown,
 }
 
 /// Awareness state for a peer
@@ -61,20 +126,94 @@ pub struct AwarenessState {
     /// User name/color
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<UserInfo>,
+    /// The cursor and user sealed with the room key (base64 encoded), in place of
+    /// the plain fields in end-to-end encrypted rooms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CursorPosition {
     pub x: f64,
     pub y: f64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UserInfo {
     pub name: String,
     pub color: String,
 }
 
+/// A new random token for protecting a room.
+pub fn new_access_token() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+/// A link that opens `room` on `server`, with an access `token` for protected rooms
+/// and the encoded room `key` for end-to-end encrypted ones. The parameters go in
+/// the fragment of `base`, so they never reach the web server hosting the app.
+pub fn room_link(
+    base: &str,
+    room: &str,
+    server: &str,
+    token: Option<&str>,
+    key: Option<&str>,
+) -> String {
+    let base = base.split('#').next().unwrap_or(base);
+    let mut link = format!(
+        "{}#room={}&server={}",
+        base,
+        percent_encode(room),
+        percent_encode(server)
+    );
+    if let Some(token) = token {
+        link.push_str("&token=");
+        link.push_str(&percent_encode(token));
+    }
+    if let Some(key) = key {
+        link.push_str("&key=");
+        link.push_str(&percent_encode(key));
+    }
+    link
+}
+
+/// Percent-encode all but unreserved URL characters.
+pub fn percent_encode(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    for b in s.bytes() {
+        if b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b'~') {
+            result.push(b as char);
+        } else {
+            result.push_str(&format!("%{:02X}", b));
+        }
+    }
+    result
+}
+
+/// Decode percent-encoded text. Invalid escapes are kept as they are.
+pub fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(b) => {
+                result.push(b);
+                i += 3;
+            }
+            None => {
+                result.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&result).into_owned()
+}
+
 /// Connection state
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConnectionState {
@@ -96,7 +235,13 @@ pub enum SyncEvent {
         room: String,
         peer_count: usize,
         initial_sync: Option<Vec<u8>>,
+        protected: bool,
+        read_only: bool,
     },
+    /// The room's access changed
+    AccessChanged { protected: bool, read_only: bool },
+    /// We were refused or removed from a room
+    Removed { room: String, message: String },
     /// A peer joined the room
     PeerJoined { peer_id: String },
     /// A peer left the room
@@ -109,6 +254,8 @@ pub enum SyncEvent {
         peer_id: u64,
         state: AwarenessState,
     },
+    /// The server rejected a message (the connection stays open)
+    Rejected { code: ErrorCode, message: String },
     /// Error occurred
     Error { message: String },
 }
@@ -252,14 +399,28 @@ mod wasm_client {
                                 room,
                                 peer_count,
                                 initial_sync,
+                                protected,
+                                read_only,
                             } => {
                                 let data = initial_sync.and_then(|s| super::base64_decode(&s));
                                 SyncEvent::JoinedRoom {
                                     room,
                                     peer_count,
                                     initial_sync: data,
+                                    protected,
+                                    read_only,
                                 }
                             }
+                            ServerMessage::Access {
+                                protected,
+                                read_only,
+                            } => SyncEvent::AccessChanged {
+                                protected,
+                                read_only,
+                            },
+                            ServerMessage::Removed { room, message } => {
+                                SyncEvent::Removed { room, message }
+                            }
                             ServerMessage::PeerJoined { peer_id } => {
                                 SyncEvent::PeerJoined { peer_id }
                             }
@@ -280,7 +441,9 @@ mod wasm_client {
                                 peer_id,
                                 state,
                             },
-                            ServerMessage::Error { message } => SyncEvent::Error { message },
+                            ServerMessage::Error { code, message } => {
+                                SyncEvent::Rejected { code, message }
+                            }
                         };
                         events_msg.borrow_mut().push(event);
                     }
@@ -510,6 +673,8 @@ mod native_client {
                                                 room,
                                                 peer_count,
                                                 initial_sync,
+                                                protected,
+                                                read_only,
                                             } => {
                                                 let data = initial_sync
                                                     .and_then(|s| super::base64_decode(&s));
@@ -517,8 +682,20 @@ mod native_client {
                                                     room,
                                                     peer_count,
                                                     initial_sync: data,
+                                                    protected,
+                                                    read_only,
                                                 }
                                             }
+                                            ServerMessage::Access {
+                                                protected,
+                                                read_only,
+                                            } => SyncEvent::AccessChanged {
+                                                protected,
+                                                read_only,
+                                            },
+                                            ServerMessage::Removed { room, message } => {
+                                                SyncEvent::Removed { room, message }
+                                            }
                                             ServerMessage::PeerJoined { peer_id } => {
                                                 SyncEvent::PeerJoined { peer_id }
                                             }
@@ -541,8 +718,8 @@ mod native_client {
                                                 peer_id,
                                                 state,
                                             },
-                                            ServerMessage::Error { message } => {
-                                                SyncEvent::Error { message }
+                                            ServerMessage::Error { code, message } => {
+                                                SyncEvent::Rejected { code, message }
                                             }
                                         };
                                         let _ = event_tx.send(event);
@@ -703,10 +880,64 @@ mod tests {
     fn test_client_message_serialize() {
         let msg = ClientMessage::Join {
             room: "test-room".to_string(),
+            version: None,
+            token: None,
+            key_check: None,
         };
         let json = serde_json::to_string(&msg).unwrap();
         assert!(json.contains("join"));
         assert!(json.contains("test-room"));
+        assert!(!json.contains("version"));
+    }
+
+    #[test]
+    fn test_room_link() {
+        let link = room_link(
+            "https://drafft.ink/#old",
+            "team board&more",
+            "wss://sync.example.com/ws",
+            Some("abc123"),
+            None,
+        );
+        assert_eq!(
+            link,
+            "https://drafft.ink/#room=team%20board%26more&server=wss%3A%2F%2Fsync.example.com%2Fws&token=abc123"
+        );
+        let link = room_link("https://drafft.ink/", "r", "ws://s", None, Some("k-_0"));
+        assert_eq!(
+            link,
+            "https://drafft.ink/#room=r&server=ws%3A%2F%2Fs&key=k-_0"
+        );
+        assert_eq!(percent_decode("team%20board%26more"), "team board&more");
+        assert_eq!(percent_decode("caf%C3%A9%zz"), "café%zz");
+        assert_eq!(new_access_token().len(), 32);
+    }
+
+    #[test]
+    fn test_error_codes() {
+        let json = r#"{"type":"error","code":"rate_limited","message":"Slow down"}"#;
+        let msg: ServerMessage = serde_json::from_str(json).unwrap();
+        assert!(matches!(
+            msg,
+            ServerMessage::Error {
+                code: ErrorCode::RateLimited,
+                ..
+            }
+        ));
+        // Older servers send no code, newer ones may send codes we don't know
+        for json in [
+            r#"{"type":"error","message":"Oops"}"#,
+            r#"{"type":"error","code":"brand_new","message":"Oops"}"#,
+        ] {
+            let msg: ServerMessage = serde_json::from_str(json).unwrap();
+            assert!(matches!(
+                msg,
+                ServerMessage::Error {
+                    code: ErrorCode::Unknown,
+                    ..
+                }
+            ));
+        }
     }
 
     #[test]
@@ -0,0 +1,498 @@
+//! Board templates.
+//!
+//! Templates are plain `CanvasDocument`s used as a starting skeleton for new
+//! boards. A handful are built in; users can save their own (the whole board or
+//! a selection) into any `Storage` backend. Instantiating a template always gives
+//! every shape a fresh ID and hand-drawn seed.
+
+use crate::canvas::CanvasDocument;
+use crate::shapes::{
+    Arrow, Ellipse, FontWeight, Line, Rectangle, SerializableColor, Shape, ShapeId, Sloppiness,
+    StrokeStyle, Text,
+};
+use crate::storage::{Storage, StorageResult};
+use kurbo::Point;
+
+/// Storage key prefix for user-saved templates.
+pub const TEMPLATE_KEY_PREFIX: &str = "__template__";
+
+/// A board template that can be instantiated into a new document.
+#[derive(Debug, Clone)]
+pub struct BoardTemplate {
+    /// Stable identifier (`builtin:<name>` for built-ins, storage key for saved ones).
+    pub id: String,
+    /// Display name.
+    pub name: String,
+    /// Short description shown in the picker.
+    pub description: String,
+    /// Whether this template ships with the application.
+    pub builtin: bool,
+    /// Template contents.
+    pub document: CanvasDocument,
+}
+
+impl BoardTemplate {
+    /// Create a new document from this template with fresh shape IDs and seeds.
+    pub fn instantiate(&self) -> CanvasDocument {
+        instantiate_document(&self.document, &self.name)
+    }
+}
+
+/// Copy every shape of `source` into a new document, regenerating IDs and seeds.
+/// Z-order is preserved.
+pub fn instantiate_document(source: &CanvasDocument, name: &str) -> CanvasDocument {
+    let mut doc = CanvasDocument::new();
+    doc.name = name.to_string();
+    for shape in source.shapes_ordered() {
+        let mut shape = shape.clone();
+        shape.regenerate_identity();
+        doc.add_shape(shape);
+    }
+    doc
+}
+
+/// Build a template document from the given shapes of `document`.
+/// An empty selection captures the whole board.
+pub fn template_from_document(
+    document: &CanvasDocument,
+    selection: &[ShapeId],
+    name: &str,
+) -> CanvasDocument {
+    let mut template = if selection.is_empty() {
+        let mut doc = CanvasDocument::new();
+        for shape in document.shapes_ordered() {
+            doc.add_shape(shape.clone());
+        }
+        doc
+    } else {
+        // Keep the document's z-order rather than selection order
+        let ordered: Vec<ShapeId> = document
+            .z_order
+            .iter()
+            .copied()
+            .filter(|id| selection.contains(id))
+            .collect();
+        document.export_selection(&ordered)
+    };
+    template.name = name.to_string();
+    template
+}
+
+/// Storage key for a saved template document ID.
+pub fn template_key(template_id: &str) -> String {
+    format!("{}{}", TEMPLATE_KEY_PREFIX, template_id)
+}
+
+/// Check whether a storage key belongs to a saved template.
+pub fn is_template_key(key: &str) -> bool {
+    key.starts_with(TEMPLATE_KEY_PREFIX)
+}
+
+/// Save a template document to storage. Returns the storage key used.
+pub async fn save_template<S: Storage + ?Sized>(
+    storage: &S,
+    template: &CanvasDocument,
+) -> StorageResult<String> {
+    let key = template_key(&template.id);
+    storage.save(&key, template).await?;
+    Ok(key)
+}
+
+/// Load all user-saved templates from storage, sorted by name.
+pub async fn list_templates<S: Storage + ?Sized>(storage: &S) -> StorageResult<Vec<BoardTemplate>> {
+    let mut templates = Vec::new();
+    for key in storage.list().await? {
+        if !is_template_key(&key) {
+            continue;
+        }
+        match storage.load(&key).await {
+            Ok(document) => templates.push(BoardTemplate {
+                id: key,
+                name: document.name.clone(),
+                description: format!("{} shapes", document.len()),
+                builtin: false,
+                document,
+            }),
+            Err(e) => log::warn!("Skipping unreadable template '{}': {}", key, e),
+        }
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+/// Delete a user-saved template by storage key.
+pub async fn delete_template<S: Storage + ?Sized>(storage: &S, key: &str) -> StorageResult<()> {
+    storage.delete(key).await
+}
+
+/// All templates shipped with the application.
+pub fn builtin_templates() -> Vec<BoardTemplate> {
+    vec![
+        builtin(
+            "kanban",
+            "Kanban",
+            "To do, in progress and done columns",
+            kanban(),
+        ),
+        builtin(
+            "retro",
+            "Retrospective",
+            "Went well, to improve and action items",
+            retro(),
+        ),
+        builtin(
+            "matrix",
+            "2x2 Matrix",
+            "Urgent / important prioritization grid",
+            matrix(),
+        ),
+        builtin(
+            "flowchart",
+            "Flowchart",
+            "Start, steps, decision and end",
+            flowchart(),
+        ),
+        builtin(
+            "sequence",
+            "Sequence Diagram",
+            "Participant lanes with messages",
+            sequence(),
+        ),
+    ]
+}
+
+/// Find a built-in template by ID.
+pub fn find_builtin(id: &str) -> Option<BoardTemplate> {
+    builtin_templates().into_iter().find(|t| t.id == id)
+}
+
+fn builtin(id: &str, name: &str, description: &str, shapes: Vec<Shape>) -> BoardTemplate {
+    let mut document = CanvasDocument::new();
+    document.id = format!("builtin:{}", id);
+    document.name = name.to_string();
+    for shape in shapes {
+        document.add_shape(shape);
+    }
+    BoardTemplate {
+        id: document.id.clone(),
+        name: name.to_string(),
+        description: description.to_string(),
+        builtin: true,
+        document,
+    }
+}
+
+// Palette shared by the built-in templates (Tailwind 100/500 shades).
+const SLATE: SerializableColor = SerializableColor {
+    r: 100,
+    g: 116,
+    b: 139,
+    a: 255,
+};
+const YELLOW: SerializableColor = SerializableColor {
+    r: 254,
+    g: 249,
+    b: 195,
+    a: 255,
+};
+const GREEN: SerializableColor = SerializableColor {
+    r: 220,
+    g: 252,
+    b: 231,
+    a: 255,
+};
+const RED: SerializableColor = SerializableColor {
+    r: 254,
+    g: 226,
+    b: 226,
+    a: 255,
+};
+const BLUE: SerializableColor = SerializableColor {
+    r: 219,
+    g: 234,
+    b: 254,
+    a: 255,
+};
+const GRAY: SerializableColor = SerializableColor {
+    r: 241,
+    g: 245,
+    b: 249,
+    a: 255,
+};
+
+fn panel(x: f64, y: f64, w: f64, h: f64, fill: SerializableColor) -> Shape {
+    let mut rect = Rectangle::new(Point::new(x, y), w, h);
+    rect.corner_radius = 12.0;
+    rect.style.stroke_color = SLATE;
+    rect.style.fill_color = Some(fill);
+    rect.style.sloppiness = Sloppiness::Architect;
+    Shape::Rectangle(rect)
+}
+
+fn card(x: f64, y: f64, w: f64, h: f64, fill: SerializableColor) -> Shape {
+    let mut rect = Rectangle::new(Point::new(x, y), w, h);
+    rect.corner_radius = 6.0;
+    rect.style.stroke_color = SLATE;
+    rect.style.fill_color = Some(fill);
+    Shape::Rectangle(rect)
+}
+
+fn label(x: f64, y: f64, content: &str) -> Shape {
+    Shape::Text(Text::new(Point::new(x, y), content.to_string()))
+}
+
+fn heading(x: f64, y: f64, content: &str) -> Shape {
+    Shape::Text(
+        Text::new(Point::new(x, y), content.to_string())
+            .with_font_size(28.0)
+            .with_font_weight(FontWeight::Heavy),
+    )
+}
+
+fn arrow(from: Point, to: Point) -> Shape {
+    let mut arrow = Arrow::new(from, to);
+    arrow.style.stroke_color = SLATE;
+    Shape::Arrow(arrow)
+}
+
+fn kanban() -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    for (i, title) in ["To Do", "In Progress", "Done"].iter().enumerate() {
+        let x = i as f64 * 320.0;
+        shapes.push(panel(x, 0.0, 300.0, 640.0, GRAY));
+        shapes.push(heading(x + 20.0, 20.0, title));
+    }
+    shapes.push(card(20.0, 80.0, 260.0, 100.0, YELLOW));
+    shapes.push(label(36.0, 96.0, "Task"));
+    shapes.push(card(20.0, 200.0, 260.0, 100.0, YELLOW));
+    shapes.push(label(36.0, 216.0, "Task"));
+    shapes
+}
+
+fn retro() -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    let columns = [
+        ("Went well", GREEN),
+        ("To improve", RED),
+        ("Action items", BLUE),
+    ];
+    for (i, (title, fill)) in columns.into_iter().enumerate() {
+        let x = i as f64 * 340.0;
+        shapes.push(panel(x, 0.0, 320.0, 560.0, fill));
+        shapes.push(heading(x + 20.0, 20.0, title));
+        shapes.push(card(x + 20.0, 80.0, 280.0, 80.0, YELLOW));
+    }
+    shapes
+}
+
+fn matrix() -> Vec<Shape> {
+    let size = 320.0;
+    let quadrants = [
+        (0.0, 0.0, "Do first", RED),
+        (size, 0.0, "Schedule", BLUE),
+        (0.0, size, "Delegate", YELLOW),
+        (size, size, "Eliminate", GRAY),
+    ];
+    let mut shapes = Vec::new();
+    for (x, y, title, fill) in quadrants {
+        shapes.push(panel(x, y, size, size, fill));
+        shapes.push(heading(x + 20.0, y + 20.0, title));
+    }
+    shapes.push(arrow(Point::new(-40.0, size * 2.0), Point::new(-40.0, 0.0)));
+    shapes.push(label(-120.0, size - 12.0, "Urgent"));
+    shapes.push(arrow(
+        Point::new(0.0, size * 2.0 + 40.0),
+        Point::new(size * 2.0, size * 2.0 + 40.0),
+    ));
+    shapes.push(label(size - 50.0, size * 2.0 + 56.0, "Important"));
+    shapes
+}
+
+fn flowchart() -> Vec<Shape> {
+    let mut shapes = Vec::new();
+
+    let mut start = Ellipse::new(Point::new(100.0, 40.0), 80.0, 40.0);
+    start.style.fill_color = Some(GREEN);
+    shapes.push(Shape::Ellipse(start));
+    shapes.push(label(76.0, 28.0, "Start"));
+
+    shapes.push(card(20.0, 160.0, 160.0, 80.0, BLUE));
+    shapes.push(label(76.0, 188.0, "Step"));
+
+    let mut decision = Rectangle::new(Point::new(20.0, 320.0), 160.0, 80.0);
+    decision.corner_radius = 40.0;
+    decision.style.fill_color = Some(YELLOW);
+    shapes.push(Shape::Rectangle(decision));
+    shapes.push(label(62.0, 348.0, "Decision?"));
+
+    let mut end = Ellipse::new(Point::new(100.0, 520.0), 80.0, 40.0);
+    end.style.fill_color = Some(RED);
+    shapes.push(Shape::Ellipse(end));
+    shapes.push(label(82.0, 508.0, "End"));
+
+    shapes.push(arrow(Point::new(100.0, 80.0), Point::new(100.0, 160.0)));
+    shapes.push(arrow(Point::new(100.0, 240.0), Point::new(100.0, 320.0)));
+    shapes.push(arrow(Point::new(100.0, 400.0), Point::new(100.0, 480.0)));
+    shapes.push(label(110.0, 430.0, "yes"));
+    shapes.push(arrow(Point::new(180.0, 360.0), Point::new(300.0, 360.0)));
+    shapes.push(label(220.0, 330.0, "no"));
+    shapes
+}
+
+fn sequence() -> Vec<Shape> {
+    let mut shapes = Vec::new();
+    let lane_x = [100.0, 380.0, 660.0];
+    for (x, name) in lane_x.iter().zip(["Client", "Server", "Database"]) {
+        shapes.push(card(x - 80.0, 0.0, 160.0, 60.0, BLUE));
+        shapes.push(label(x - 40.0, 18.0, name));
+        let mut lifeline = Line::new(Point::new(*x, 60.0), Point::new(*x, 560.0));
+        lifeline.stroke_style = StrokeStyle::Dashed;
+        lifeline.style.stroke_color = SLATE;
+        shapes.push(Shape::Line(lifeline));
+    }
+    shapes.push(arrow(
+        Point::new(lane_x[0], 140.0),
+        Point::new(lane_x[1], 140.0),
+    ));
+    shapes.push(label(lane_x[0] + 20.0, 110.0, "request"));
+    shapes.push(arrow(
+        Point::new(lane_x[1], 220.0),
+        Point::new(lane_x[2], 220.0),
+    ));
+    shapes.push(label(lane_x[1] + 20.0, 190.0, "query"));
+    let mut reply = Arrow::new(Point::new(lane_x[1], 320.0), Point::new(lane_x[0], 320.0));
+    reply.stroke_style = StrokeStyle::Dashed;
+    reply.style.stroke_color = SLATE;
+    shapes.push(Shape::Arrow(reply));
+    shapes.push(label(lane_x[0] + 20.0, 290.0, "response"));
+    shapes
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::shapes::{Group, ShapeTrait};
+    use crate::storage::MemoryStorage;
+    use std::collections::HashSet;
+
+    fn block_on<F: std::future::Future>(f: F) -> F::Output {
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        fn dummy_raw_waker() -> RawWaker {
+            fn no_op(_: *const ()) {}
+            fn clone(_: *const ()) -> RawWaker {
+                dummy_raw_waker()
+            }
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, no_op, no_op, no_op);
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+
+        let waker = unsafe { Waker::from_raw(dummy_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut f = std::pin::pin!(f);
+
+        loop {
+            match f.as_mut().poll(&mut cx) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    #[test]
+    fn test_builtin_templates() {
+        let templates = builtin_templates();
+        assert_eq!(templates.len(), 5);
+        let ids: HashSet<_> = templates.iter().map(|t| t.id.clone()).collect();
+        assert_eq!(ids.len(), templates.len());
+        for template in &templates {
+            assert!(template.builtin);
+            assert!(!template.document.is_empty(), "{} is empty", template.name);
+        }
+        assert!(find_builtin("builtin:kanban").is_some());
+        assert!(find_builtin("builtin:missing").is_none());
+    }
+
+    #[test]
+    fn test_instantiate_regenerates_ids_and_seeds() {
+        let template = find_builtin("builtin:retro").unwrap();
+        let doc = template.instantiate();
+
+        assert_eq!(doc.len(), template.document.len());
+        assert_ne!(doc.id, template.document.id);
+        for id in &doc.z_order {
+            assert!(!template.document.shapes.contains_key(id));
+        }
+        let source_seeds: HashSet<u32> = template
+            .document
+            .shapes
+            .values()
+            .map(|s| s.style().seed)
+            .collect();
+        assert!(
+            doc.shapes
+                .values()
+                .all(|s| !source_seeds.contains(&s.style().seed))
+        );
+    }
+
+    #[test]
+    fn test_instantiate_regenerates_group_children() {
+        let mut source = CanvasDocument::new();
+        let child = Shape::Rectangle(Rectangle::new(Point::new(0.0, 0.0), 10.0, 10.0));
+        let child_id = child.id();
+        let group = Group::new(vec![child]);
+        let group_id = group.id();
+        source.add_shape(Shape::Group(group));
+
+        let doc = instantiate_document(&source, "Copy");
+        assert_eq!(doc.name, "Copy");
+        let copied = doc.shapes_ordered().next().unwrap().as_group().unwrap();
+        assert_ne!(copied.id(), group_id);
+        assert_ne!(copied.children()[0].id(), child_id);
+    }
+
+    #[test]
+    fn test_template_from_selection_keeps_z_order() {
+        let mut doc = CanvasDocument::new();
+        let a = Shape::Rectangle(Rectangle::new(Point::new(0.0, 0.0), 10.0, 10.0));
+        let b = Shape::Rectangle(Rectangle::new(Point::new(20.0, 0.0), 10.0, 10.0));
+        let c = Shape::Rectangle(Rectangle::new(Point::new(40.0, 0.0), 10.0, 10.0));
+        let (a_id, c_id) = (a.id(), c.id());
+        doc.add_shape(a);
+        doc.add_shape(b);
+        doc.add_shape(c);
+
+        let template = template_from_document(&doc, &[c_id, a_id], "Pair");
+        assert_eq!(template.name, "Pair");
+        assert_eq!(template.z_order, vec![a_id, c_id]);
+
+        let whole = template_from_document(&doc, &[], "All");
+        assert_eq!(whole.len(), 3);
+    }
+
+    #[test]
+    fn test_save_and_list_templates() {
+        let storage = MemoryStorage::new();
+        let mut doc = CanvasDocument::new();
+        doc.add_shape(Shape::Rectangle(Rectangle::new(
+            Point::new(0.0, 0.0),
+            10.0,
+            10.0,
+        )));
+        block_on(storage.save("regular", &doc)).unwrap();
+
+        let template = template_from_document(&doc, &[], "Mine");
+        let key = block_on(save_template(&storage, &template)).unwrap();
+        assert!(is_template_key(&key));
+
+        let templates = block_on(list_templates(&storage)).unwrap();
+        assert_eq!(templates.len(), 1);
+        assert_eq!(templates[0].id, key);
+        assert_eq!(templates[0].name, "Mine");
+        assert!(!templates[0].builtin);
+
+        block_on(delete_template(&storage, &key)).unwrap();
+        assert!(block_on(list_templates(&storage)).unwrap().is_empty());
+    }
+}
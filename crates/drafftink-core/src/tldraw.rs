@@ -0,0 +1,1220 @@
+//! tldraw import.
+//!
+//! Reads tldraw `.tldr` files (v2 and later, a flat list of `records`) into shapes.
+//! Every page is imported, pages stacked top to bottom.
+//!
+//! - `geo` shapes become rectangles, ellipses or closed polygons. Labels are grouped
+//!   with their shape, like bound text from Excalidraw.
+//! - Arrows honor their bindings, both `binding` records and the inline terminals of
+//!   older files. Bound ends are moved to their anchor and clipped to the target's
+//!   bounds. Bent arrows get a flowing midpoint.
+//! - `draw` and `highlight` strokes become freehand paths, with pen pressure.
+//! - Notes become a filled square with their text; frames become a group of the
+//!   outline, the name and the children; groups stay groups.
+//! - Images are read from `data:` URL assets.
+//!
+//! Everything else (embeds, bookmarks, videos, linked images, custom shapes) is
+//! counted in [`Unsupported`].
+
+use crate::excalidraw::{frame_shapes, image_from_data_url};
+use crate::json::{bool_field, f64_field, str_field};
+use crate::mermaid::text_size;
+use crate::shapes::{
+    Arrow, Ellipse, FillPattern, FontFamily, Freehand, Group, Line, PathStyle, Rectangle,
+    SerializableColor, Shape, ShapeStyle, ShapeTrait, Sloppiness, StrokeStyle, Text,
+};
+use kurbo::{Affine, Point, Rect, Size, Vec2};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::f64::consts::PI;
+use std::fmt;
+use thiserror::Error;
+
+/// Errors that can occur while importing a tldraw file.
+#[derive(Debug, Error, PartialEq)]
+pub enum TldrawError {
+    #[error("Invalid JSON: {0}")]
+    Json(String),
+    #[error("Not a tldraw file")]
+    NotTldraw,
+    #[error("tldraw v1 files are not supported")]
+    Legacy,
+}
+
+/// Result type for tldraw import.
+pub type TldrawResult<T> = Result<T, TldrawError>;
+
+/// Elements skipped during import, counted by kind.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Unsupported(BTreeMap<String, usize>);
+
+impl Unsupported {
+    /// Whether everything was imported.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Total number of skipped elements.
+    pub fn total(&self) -> usize {
+        self.0.values().sum()
+    }
+
+    /// Skipped element kinds with their counts, sorted by kind.
+    pub fn counts(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.0.iter().map(|(kind, &count)| (kind.as_str(), count))
+    }
+
+    fn add(&mut self, kind: impl Into<String>) {
+        *self.0.entry(kind.into()).or_default() += 1;
+    }
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (kind, count)) in self.counts().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{} × {}", count, kind)?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of importing a tldraw file.
+#[derive(Debug, Clone)]
+pub struct TldrawImport {
+    /// Imported shapes, in z-order.
+    pub shapes: Vec<Shape>,
+    /// Elements that could not be imported.
+    pub unsupported: Unsupported,
+}
+
+/// Vertical gap between imported pages.
+const PAGE_GAP: f64 = 200.0;
+/// Size of a note.
+const NOTE_SIZE: f64 = 200.0;
+/// Maximum nesting of frames and groups followed when placing shapes.
+const MAX_DEPTH: usize = 64;
+
+/// Light-theme colors: name, stroke, tint and note fill.
+const PALETTE: &[(&str, u32, u32, u32)] = &[
+    ("black", 0x1d1d1d, 0xe8e8e8, 0xfce19c),
+    ("blue", 0x4465e9, 0xdce1f8, 0x8aa3ff),
+    ("green", 0x099268, 0xd3e9e3, 0x6fc896),
+    ("grey", 0x9fa8b2, 0xeceef0, 0xc0cad3),
+    ("light-blue", 0x4ba1f1, 0xddedfa, 0x9bc4fd),
+    ("light-green", 0x4cb05e, 0xdbf0e0, 0x98d08a),
+    ("light-red", 0xf87777, 0xf4dadb, 0xf7a5a1),
+    ("light-violet", 0xe085f4, 0xf5eafa, 0xdfb0f9),
+    ("orange", 0xe16919, 0xf8e2d4, 0xfaa475),
+    ("red", 0xe03131, 0xf4dadb, 0xfc8282),
+    ("violet", 0xae3ec9, 0xecdcf2, 0xdb91fd),
+    ("white", 0xffffff, 0xf5f5f5, 0xffffff),
+    ("yellow", 0xf1ac4b, 0xf9f0e6, 0xfed49a),
+];
+/// Fill of `semi` shapes.
+const SEMI_FILL: u32 = 0xf5f5f5;
+
+/// Check whether text looks like a tldraw file.
+pub fn is_tldraw(text: &str) -> bool {
+    serde_json::from_str::<Value>(text)
+        .is_ok_and(|data| data.get("tldrawFileFormatVersion").is_some())
+}
+
+/// Parse a tldraw file into shapes.
+pub fn parse_tldraw(json: &str) -> TldrawResult<TldrawImport> {
+    let data: Value = serde_json::from_str(json).map_err(|e| TldrawError::Json(e.to_string()))?;
+    if data.get("tldrawFileFormatVersion").is_none() {
+        let legacy = data.get("document").and_then(|d| d.get("pages")).is_some();
+        return Err(if legacy {
+            TldrawError::Legacy
+        } else {
+            TldrawError::NotTldraw
+        });
+    }
+    let records = data
+        .get("records")
+        .and_then(Value::as_array)
+        .ok_or(TldrawError::NotTldraw)?;
+
+    let mut importer = Importer::new(records);
+    let mut shapes = Vec::new();
+    let mut bottom: Option<f64> = None;
+    for page in importer.pages.clone() {
+        let mut page_shapes = importer.children_of(page);
+        let Some(bounds) = union_bounds(&page_shapes) else {
+            continue;
+        };
+        // Each page goes below the previous one
+        let offset = bottom.map_or(0.0, |bottom| bottom + PAGE_GAP - bounds.y0);
+        if offset != 0.0 {
+            for shape in &mut page_shapes {
+                shape.transform(Affine::translate((0.0, offset)));
+            }
+        }
+        bottom = Some(bounds.y1 + offset);
+        shapes.extend(page_shapes);
+    }
+    Ok(TldrawImport {
+        shapes,
+        unsupported: importer.unsupported,
+    })
+}
+
+/// A shape's placement on the page.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    /// Maps the shape's local coordinates to page coordinates.
+    transform: Affine,
+    /// Total rotation in radians.
+    rotation: f64,
+    /// Opacity including the parents'.
+    opacity: f64,
+}
+
+impl Placement {
+    fn point(&self, x: f64, y: f64) -> Point {
+        self.transform * Point::new(x, y)
+    }
+
+    /// Top-left corner of an unrotated `size` box whose center matches the local box.
+    fn origin(&self, size: Size) -> Point {
+        self.point(size.width / 2.0, size.height / 2.0) - size.to_vec2() / 2.0
+    }
+}
+
+/// One end of an arrow.
+struct Terminal {
+    /// Position in the arrow's local coordinates.
+    point: Point,
+    binding: Option<Binding>,
+}
+
+struct Binding {
+    target: String,
+    /// Anchor in the target's normalized coordinates.
+    anchor: Point,
+    /// Whether the arrow ends exactly at the anchor instead of at the outline.
+    exact: bool,
+}
+
+struct Importer<'a> {
+    records: HashMap<&'a str, &'a Value>,
+    /// Shape records by parent id, in z-order.
+    children: HashMap<&'a str, Vec<&'a Value>>,
+    /// Page ids, in order.
+    pages: Vec<&'a str>,
+    /// Arrow bindings by arrow id and terminal.
+    bindings: HashMap<(&'a str, &'a str), Binding>,
+    placements: HashMap<&'a str, Placement>,
+    unsupported: Unsupported,
+}
+
+impl<'a> Importer<'a> {
+    fn new(records: &'a [Value]) -> Self {
+        let mut by_id = HashMap::new();
+        let mut children: HashMap<&str, Vec<&Value>> = HashMap::new();
+        let mut pages = Vec::new();
+        let mut bindings = HashMap::new();
+        for record in records {
+            let Some(id) = str_field(record, "id") else {
+                continue;
+            };
+            by_id.insert(id, record);
+            match str_field(record, "typeName") {
+                Some("shape") => {
+                    let parent = str_field(record, "parentId").unwrap_or_default();
+                    children.entry(parent).or_default().push(record);
+                }
+                Some("page") => pages.push(record),
+                Some("binding") if str_field(record, "type") == Some("arrow") => {
+                    let props = &record["props"];
+                    let (Some(arrow), Some(target), Some(terminal)) = (
+                        str_field(record, "fromId"),
+                        str_field(record, "toId"),
+                        str_field(props, "terminal"),
+                    ) else {
+                        continue;
+                    };
+                    bindings.insert((arrow, terminal), binding(target, props));
+                }
+                _ => {}
+            }
+        }
+        // Fractional indices sort as plain strings
+        let index = |r: &&Value| str_field(r, "index").unwrap_or_default().to_string();
+        for siblings in children.values_mut() {
+            siblings.sort_by_key(index);
+        }
+        pages.sort_by_key(index);
+
+        let mut importer = Self {
+            records: by_id,
+            children,
+            pages: pages.iter().filter_map(|p| str_field(p, "id")).collect(),
+            bindings,
+            placements: HashMap::new(),
+            unsupported: Unsupported::default(),
+        };
+        let shapes: Vec<&str> = importer
+            .children
+            .values()
+            .flatten()
+            .filter_map(|r| str_field(r, "id"))
+            .collect();
+        for id in shapes {
+            importer.place(id, 0);
+        }
+        importer
+    }
+
+    /// Compute (and cache) the placement of a shape from its parents.
+    fn place(&mut self, id: &'a str, depth: usize) -> Placement {
+        if let Some(&placement) = self.placements.get(id) {
+            return placement;
+        }
+        let root = Placement {
+            transform: Affine::IDENTITY,
+            rotation: 0.0,
+            opacity: 1.0,
+        };
+        let Some(record) = self.records.get(id).copied() else {
+            return root;
+        };
+        if str_field(record, "typeName") != Some("shape") {
+            return root;
+        }
+        let parent = match str_field(record, "parentId") {
+            Some(parent) if depth < MAX_DEPTH => self.place(parent, depth + 1),
+            _ => root,
+        };
+        let rotation = f64_field(record, "rotation", 0.0);
+        let placement = Placement {
+            transform: parent.transform
+                * Affine::translate((f64_field(record, "x", 0.0), f64_field(record, "y", 0.0)))
+                * Affine::rotate(rotation),
+            rotation: parent.rotation + rotation,
+            opacity: parent.opacity * f64_field(record, "opacity", 1.0),
+        };
+        self.placements.insert(id, placement);
+        placement
+    }
+
+    /// Import the children of a page or shape, in z-order.
+    fn children_of(&mut self, parent: &str) -> Vec<Shape> {
+        let children = self.children.get(parent).cloned().unwrap_or_default();
+        children
+            .into_iter()
+            .flat_map(|record| self.shape(record))
+            .collect()
+    }
+
+    /// Import one shape record (with its children for frames and groups).
+    fn shape(&mut self, record: &'a Value) -> Vec<Shape> {
+        let id = str_field(record, "id").unwrap_or_default();
+        let kind = str_field(record, "type").unwrap_or_default();
+        let props = &record["props"];
+        let placement = self.placements[id];
+        let style = shape_style(props, placement.opacity);
+
+        let shape = match kind {
+            "geo" => {
+                let size = Size::new(
+                    f64_field(props, "w", 100.0),
+                    f64_field(props, "h", 100.0) + f64_field(props, "growY", 0.0),
+                );
+                let geo = str_field(props, "geo").unwrap_or("rectangle");
+                let mut shape = match geo {
+                    "ellipse" | "oval" | "cloud" | "heart" => {
+                        let mut ellipse = Ellipse::new(
+                            placement.point(size.width / 2.0, size.height / 2.0),
+                            size.width / 2.0,
+                            size.height / 2.0,
+                        );
+                        ellipse.rotation = placement.rotation;
+                        Shape::Ellipse(ellipse)
+                    }
+                    _ => match geo_outline(geo, size) {
+                        Some(outline) => {
+                            let mut points: Vec<Point> =
+                                outline.iter().map(|&p| placement.transform * p).collect();
+                            points.push(points[0]);
+                            Shape::Line(Line::from_points(points, PathStyle::Direct))
+                        }
+                        None => rectangle(&placement, size),
+                    },
+                };
+                *shape.style_mut() = style;
+                let color = str_field(props, "labelColor").unwrap_or("black");
+                self.labeled(
+                    shape,
+                    props,
+                    placement.point(size.width / 2.0, size.height / 2.0),
+                    color,
+                    label_font_size(props),
+                )
+            }
+            "note" => {
+                let size = Size::new(NOTE_SIZE, NOTE_SIZE + f64_field(props, "growY", 0.0));
+                let mut shape = rectangle(&placement, size);
+                let color = str_field(props, "color").unwrap_or("black");
+                *shape.style_mut() = ShapeStyle {
+                    stroke_color: SerializableColor::transparent(),
+                    fill_color: Some(rgb(palette(color).3)),
+                    fill_pattern: FillPattern::Solid,
+                    sloppiness: Sloppiness::Architect,
+                    ..style
+                };
+                self.labeled(
+                    shape,
+                    props,
+                    placement.point(size.width / 2.0, size.height / 2.0),
+                    "black",
+                    label_font_size(props),
+                )
+            }
+            "text" => {
+                let content = label(props);
+                let scale = f64_field(props, "scale", 1.0);
+                let mut text = Text::new(Point::ZERO, content)
+                    .with_font_size(font_size(props, &[18.0, 24.0, 36.0, 44.0]) * scale)
+                    .with_font_family(font_family(props));
+                let size = text.bounds().size();
+                text.position = placement.origin(size);
+                text.rotation = placement.rotation;
+                text.style = style;
+                text.style.fill_color = None;
+                Shape::Text(text)
+            }
+            "draw" | "highlight" => {
+                let mut points = Vec::new();
+                let mut pressures = Vec::new();
+                let segments = props.get("segments").and_then(Value::as_array);
+                for point in segments
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|s| s.get("points").and_then(Value::as_array))
+                    .flatten()
+                {
+                    points.push(
+                        placement.point(f64_field(point, "x", 0.0), f64_field(point, "y", 0.0)),
+                    );
+                    pressures.push(f64_field(point, "z", 0.5).clamp(0.0, 1.0));
+                }
+                if points.is_empty() {
+                    self.unsupported.add(kind);
+                    return Vec::new();
+                }
+                if bool_field(props, "isClosed") {
+                    points.push(points[0]);
+                    pressures.push(pressures[0]);
+                }
+                // Without a pen, tldraw stores a constant placeholder pressure
+                let mut freehand = if bool_field(props, "isPen") {
+                    Freehand::from_points_with_pressure(points, pressures)
+                } else {
+                    Freehand::from_points(points)
+                };
+                freehand.style = style;
+                freehand.style.fill_color = None;
+                if kind == "highlight" {
+                    freehand.style.stroke_width *= 4.0;
+                    freehand.style.opacity *= 0.5;
+                }
+                Shape::Freehand(freehand)
+            }
+            "line" => {
+                let handles = props
+                    .get("points")
+                    .or_else(|| props.get("handles"))
+                    .and_then(Value::as_object);
+                let mut handles: Vec<&Value> =
+                    handles.into_iter().flat_map(|h| h.values()).collect();
+                handles.sort_by_key(|h| str_field(h, "index").unwrap_or_default().to_string());
+                let points: Vec<Point> = handles
+                    .iter()
+                    .map(|h| placement.point(f64_field(h, "x", 0.0), f64_field(h, "y", 0.0)))
+                    .collect();
+                if points.len() < 2 {
+                    self.unsupported.add(kind);
+                    return Vec::new();
+                }
+                let path_style = match str_field(props, "spline") {
+                    Some("cubic") => PathStyle::Flowing,
+                    _ => PathStyle::Direct,
+                };
+                let mut line = Line::from_points(points, path_style);
+                line.stroke_style = stroke_style(props);
+                line.style = style;
+                line.style.fill_color = None;
+                Shape::Line(line)
+            }
+            "arrow" => return self.arrow(id, props, &placement, style),
+            "image" => {
+                let size = Size::new(f64_field(props, "w", 100.0), f64_field(props, "h", 100.0));
+                let src = str_field(props, "assetId")
+                    .and_then(|asset| self.records.get(asset))
+                    .and_then(|asset| str_field(&asset["props"], "src"));
+                let image = match src {
+                    Some(src) if src.starts_with("data:") => image_from_data_url(
+                        src,
+                        Rect::from_origin_size(placement.origin(size), size),
+                    ),
+                    Some(_) => {
+                        self.unsupported.add("image (linked)");
+                        return Vec::new();
+                    }
+                    None => None,
+                };
+                let Some(mut image) = image else {
+                    self.unsupported.add("image (missing asset)");
+                    return Vec::new();
+                };
+                image.rotation = placement.rotation;
+                image.style.opacity = placement.opacity;
+                Shape::Image(image)
+            }
+            "frame" => {
+                let size = Size::new(f64_field(props, "w", 100.0), f64_field(props, "h", 100.0));
+                let name = str_field(props, "name")
+                    .filter(|n| !n.is_empty())
+                    .unwrap_or("Frame");
+                let mut shapes =
+                    frame_shapes(Rect::from_origin_size(placement.origin(size), size), name);
+                if let Shape::Rectangle(outline) = &mut shapes[0] {
+                    outline.rotation = placement.rotation;
+                }
+                shapes.extend(self.children_of(id));
+                Shape::Group(Group::new(shapes).with_frame(name))
+            }
+            "group" => {
+                let children = self.children_of(id);
+                if children.len() < 2 {
+                    return children;
+                }
+                Shape::Group(Group::new(children))
+            }
+            _ => {
+                self.unsupported.add(kind);
+                return Vec::new();
+            }
+        };
+        vec![shape]
+    }
+
+    fn arrow(
+        &mut self,
+        id: &'a str,
+        props: &Value,
+        placement: &Placement,
+        style: ShapeStyle,
+    ) -> Vec<Shape> {
+        let start = self.terminal(id, props, "start");
+        let end = self.terminal(id, props, "end");
+        let mut start_point = placement.transform * start.point;
+        let mut end_point = placement.transform * end.point;
+        let start_anchor = start.binding.as_ref().and_then(|b| self.anchor(b));
+        let end_anchor = end.binding.as_ref().and_then(|b| self.anchor(b));
+        if let Some((anchor, _)) = start_anchor {
+            start_point = anchor;
+        }
+        if let Some((anchor, _)) = end_anchor {
+            end_point = anchor;
+        }
+        // Stop bound ends at the target's outline
+        let (raw_start, raw_end) = (start_point, end_point);
+        if let Some((_, Some(bounds))) = start_anchor {
+            start_point = clip_entry(raw_end, raw_start, bounds);
+        }
+        if let Some((_, Some(bounds))) = end_anchor {
+            end_point = clip_entry(raw_start, raw_end, bounds);
+        }
+
+        let bend = f64_field(props, "bend", 0.0);
+        let chord = end_point - start_point;
+        let mut points = vec![start_point];
+        let middle = if bend.abs() > f64::EPSILON && chord.hypot() > 0.0 {
+            let u = chord / chord.hypot();
+            let middle = start_point.midpoint(end_point) + Vec2::new(-u.y, u.x) * bend;
+            points.push(middle);
+            middle
+        } else {
+            start_point.lerp(end_point, f64_field(props, "labelPosition", 0.5))
+        };
+        points.push(end_point);
+        let path_style = if points.len() > 2 {
+            PathStyle::Flowing
+        } else {
+            PathStyle::Direct
+        };
+
+        let head = |key: &str, default: &str| str_field(props, key).unwrap_or(default) != "none";
+        let (start_head, end_head) = (
+            head("arrowheadStart", "none"),
+            head("arrowheadEnd", "arrow"),
+        );
+        let mut shape = if start_head || end_head {
+            if !end_head {
+                points.reverse();
+            }
+            let mut arrow = Arrow::from_points(points, path_style);
+            arrow.stroke_style = stroke_style(props);
+            Shape::Arrow(arrow)
+        } else {
+            let mut line = Line::from_points(points, path_style);
+            line.stroke_style = stroke_style(props);
+            Shape::Line(line)
+        };
+        *shape.style_mut() = ShapeStyle {
+            fill_color: None,
+            ..style
+        };
+        let color = str_field(props, "labelColor")
+            .or_else(|| str_field(props, "color"))
+            .unwrap_or("black");
+        let size = font_size(props, &[18.0, 20.0, 24.0, 28.0]);
+        vec![self.labeled(shape, props, middle, color, size)]
+    }
+
+    /// Read an arrow terminal from a binding record or the arrow's own props.
+    fn terminal(&mut self, arrow: &'a str, props: &Value, key: &'a str) -> Terminal {
+        let value = &props[key];
+        let point = Point::new(f64_field(value, "x", 0.0), f64_field(value, "y", 0.0));
+        // Older files store the binding inline
+        let binding = if str_field(value, "type") == Some("binding") {
+            str_field(value, "boundShapeId").map(|target| binding(target, value))
+        } else {
+            self.bindings.remove(&(arrow, key))
+        };
+        Terminal { point, binding }
+    }
+
+    /// Page position of a binding's anchor and, unless exact, the bounds to clip to.
+    fn anchor(&self, binding: &Binding) -> Option<(Point, Option<Rect>)> {
+        let record = self.records.get(binding.target.as_str())?;
+        let placement = self.placements.get(binding.target.as_str())?;
+        let size = local_size(record)?;
+        let point = placement.point(
+            binding.anchor.x * size.width,
+            binding.anchor.y * size.height,
+        );
+        let bounds = [
+            Point::ZERO,
+            Point::new(size.width, 0.0),
+            Point::new(size.width, size.height),
+            Point::new(0.0, size.height),
+        ]
+        .map(|p| placement.transform * p);
+        let bounds = bounds
+            .iter()
+            .fold(Rect::from_points(bounds[0], bounds[0]), |r, &p| {
+                r.union_pt(p)
+            });
+        Some((point, (!binding.exact).then_some(bounds)))
+    }
+
+    /// Group a shape with its label, if it has one.
+    fn labeled(
+        &self,
+        shape: Shape,
+        props: &Value,
+        center: Point,
+        color: &str,
+        font_size: f64,
+    ) -> Shape {
+        let content = label(props);
+        if content.trim().is_empty() {
+            return shape;
+        }
+        let mut text = Text::new(Point::ZERO, content.trim().to_string())
+            .with_font_size(font_size)
+            .with_font_family(font_family(props));
+        let size = text.bounds().size();
+        text.position = center - size.to_vec2() / 2.0;
+        text.style.stroke_color = rgb(palette(color).1);
+        text.style.opacity = shape.style().opacity;
+        Shape::Group(Group::new(vec![shape, Shape::Text(text)]))
+    }
+}
+
+fn binding(target: &str, props: &Value) -> Binding {
+    let anchor = &props["normalizedAnchor"];
+    // Imprecise bindings point at the target's center
+    let anchor = if bool_field(props, "isPrecise") {
+        Point::new(f64_field(anchor, "x", 0.5), f64_field(anchor, "y", 0.5))
+    } else {
+        Point::new(0.5, 0.5)
+    };
+    Binding {
+        target: target.to_string(),
+        anchor,
+        exact: bool_field(props, "isExact"),
+    }
+}
+
+/// Size of a shape in its own coordinates, for binding anchors.
+fn local_size(record: &Value) -> Option<Size> {
+    let props = &record["props"];
+    let grow = f64_field(props, "growY", 0.0);
+    match str_field(record, "type")? {
+        "note" => Some(Size::new(NOTE_SIZE, NOTE_SIZE + grow)),
+        "text" => {
+            let size = font_size(props, &[18.0, 24.0, 36.0, 44.0]) * f64_field(props, "scale", 1.0);
+            Some(text_size(&label(props), size))
+        }
+        _ => Some(Size::new(
+            props.get("w").and_then(Value::as_f64)?,
+            props.get("h").and_then(Value::as_f64)? + grow,
+        )),
+    }
+}
+
+/// A rectangle covering the local box `size`, rotated with the shape.
+fn rectangle(placement: &Placement, size: Size) -> Shape {
+    let mut rect = Rectangle::new(placement.origin(size), size.width, size.height);
+    rect.rotation = placement.rotation;
+    Shape::Rectangle(rect)
+}
+
+/// Outline of a polygonal `geo` shape in local coordinates, or None for a rectangle.
+fn geo_outline(geo: &str, size: Size) -> Option<Vec<Point>> {
+    let (w, h) = (size.width, size.height);
+    let inset = (w * 0.25).min(h * 0.5);
+    let outline = match geo {
+        "triangle" => vec![
+            Point::new(w / 2.0, 0.0),
+            Point::new(w, h),
+            Point::new(0.0, h),
+        ],
+        "diamond" => vec![
+            Point::new(w / 2.0, 0.0),
+            Point::new(w, h / 2.0),
+            Point::new(w / 2.0, h),
+            Point::new(0.0, h / 2.0),
+        ],
+        "pentagon" => regular_polygon(size, 5),
+        "hexagon" => regular_polygon(size, 6),
+        "octagon" => regular_polygon(size, 8),
+        "star" => (0..10)
+            .map(|i| {
+                let angle = -PI / 2.0 + PI * i as f64 / 5.0;
+                let r = if i % 2 == 0 { 1.0 } else { 0.4 };
+                Point::new(
+                    w / 2.0 * (1.0 + r * angle.cos()),
+                    h / 2.0 * (1.0 + r * angle.sin()),
+                )
+            })
+            .collect(),
+        "rhombus" => vec![
+            Point::new(inset, 0.0),
+            Point::new(w, 0.0),
+            Point::new(w - inset, h),
+            Point::new(0.0, h),
+        ],
+        "rhombus-2" => vec![
+            Point::new(0.0, 0.0),
+            Point::new(w - inset, 0.0),
+            Point::new(w, h),
+            Point::new(inset, h),
+        ],
+        "trapezoid" => vec![
+            Point::new(inset, 0.0),
+            Point::new(w - inset, 0.0),
+            Point::new(w, h),
+            Point::new(0.0, h),
+        ],
+        "arrow-right" | "arrow-left" | "arrow-up" | "arrow-down" => {
+            // Block arrow pointing right in a (length × thickness) box, then oriented
+            let vertical = geo == "arrow-up" || geo == "arrow-down";
+            let (length, thickness) = if vertical { (h, w) } else { (w, h) };
+            let head = (thickness / 2.0).min(length / 2.0);
+            let right = [
+                (0.0, 0.25),
+                (length - head, 0.25),
+                (length - head, 0.0),
+                (length, 0.5),
+                (length - head, 1.0),
+                (length - head, 0.75),
+                (0.0, 0.75),
+            ];
+            right
+                .iter()
+                .map(|&(along, across)| {
+                    let across = across * thickness;
+                    match geo {
+                        "arrow-right" => Point::new(along, across),
+                        "arrow-left" => Point::new(length - along, across),
+                        "arrow-down" => Point::new(across, along),
+                        _ => Point::new(across, length - along),
+                    }
+                })
+                .collect()
+        }
+        _ => return None,
+    };
+    Some(outline)
+}
+
+fn regular_polygon(size: Size, sides: usize) -> Vec<Point> {
+    (0..sides)
+        .map(|i| {
+            let angle = -PI / 2.0 + 2.0 * PI * i as f64 / sides as f64;
+            Point::new(
+                size.width / 2.0 * (1.0 + angle.cos()),
+                size.height / 2.0 * (1.0 + angle.sin()),
+            )
+        })
+        .collect()
+}
+
+/// Where the segment from `from` to `to` enters `rect` (`to` if `from` is inside).
+fn clip_entry(from: Point, to: Point, rect: Rect) -> Point {
+    if rect.contains(from) {
+        return to;
+    }
+    let d = to - from;
+    let mut enter: f64 = 0.0;
+    for (p, q) in [
+        (-d.x, from.x - rect.x0),
+        (d.x, rect.x1 - from.x),
+        (-d.y, from.y - rect.y0),
+        (d.y, rect.y1 - from.y),
+    ] {
+        if p < 0.0 {
+            enter = enter.max(q / p);
+        }
+    }
+    from + d * enter.clamp(0.0, 1.0)
+}
+
+fn shape_style(props: &Value, opacity: f64) -> ShapeStyle {
+    let color = palette(str_field(props, "color").unwrap_or("black"));
+    let (fill_color, fill_pattern) = match str_field(props, "fill").unwrap_or("none") {
+        "semi" => (Some(rgb(SEMI_FILL)), FillPattern::Solid),
+        "solid" => (Some(rgb(color.2)), FillPattern::Solid),
+        "pattern" => (Some(rgb(color.2)), FillPattern::Hachure),
+        "fill" => (Some(rgb(color.1)), FillPattern::Solid),
+        "lined-fill" => (Some(rgb(color.1)), FillPattern::Hachure),
+        _ => (None, FillPattern::Solid),
+    };
+    let stroke_width = match str_field(props, "size").unwrap_or("m") {
+        "s" => 2.0,
+        "l" => 5.0,
+        "xl" => 10.0,
+        _ => 3.5,
+    };
+    ShapeStyle {
+        stroke_color: rgb(color.1),
+        stroke_width: stroke_width * f64_field(props, "scale", 1.0),
+        fill_color,
+        fill_pattern,
+        // The hand-drawn `draw` dash is tldraw's default
+        sloppiness: match str_field(props, "dash").unwrap_or("draw") {
+            "draw" => Sloppiness::Artist,
+            _ => Sloppiness::Architect,
+        },
+        opacity,
+        ..ShapeStyle::default()
+    }
+}
+
+fn stroke_style(props: &Value) -> StrokeStyle {
+    match str_field(props, "dash") {
+        Some("dashed") => StrokeStyle::Dashed,
+        Some("dotted") => StrokeStyle::Dotted,
+        _ => StrokeStyle::Solid,
+    }
+}
+
+/// Font size for a `size` prop, from a table of small to extra large sizes.
+fn font_size(props: &Value, sizes: &[f64; 4]) -> f64 {
+    match str_field(props, "size").unwrap_or("m") {
+        "s" => sizes[0],
+        "l" => sizes[2],
+        "xl" => sizes[3],
+        _ => sizes[1],
+    }
+}
+
+/// Font size of labels on geo shapes and notes.
+fn label_font_size(props: &Value) -> f64 {
+    font_size(props, &[18.0, 22.0, 26.0, 32.0])
+}
+
+fn font_family(props: &Value) -> FontFamily {
+    match str_field(props, "font").unwrap_or("draw") {
+        "sans" | "mono" => FontFamily::NotoSans,
+        "serif" => FontFamily::GelPenSerif,
+        _ => FontFamily::GelPen,
+    }
+}
+
+/// Plain text of a shape: `text`, or the flattened `richText` of newer files.
+fn label(props: &Value) -> String {
+    if let Some(text) = str_field(props, "text") {
+        return text.to_string();
+    }
+    let mut text = String::new();
+    if let Some(rich) = props.get("richText") {
+        rich_text(rich, &mut text);
+    }
+    text
+}
+
+fn rich_text(node: &Value, out: &mut String) {
+    match str_field(node, "type") {
+        Some("text") => out.push_str(str_field(node, "text").unwrap_or_default()),
+        Some("hardBreak") => out.push('\n'),
+        kind => {
+            // Blocks inside documents and lists go on their own lines
+            let blocks = matches!(
+                kind,
+                Some("doc" | "bulletList" | "orderedList" | "listItem")
+            );
+            let children = node.get("content").and_then(Value::as_array);
+            for (i, child) in children.into_iter().flatten().enumerate() {
+                if blocks && i > 0 {
+                    out.push('\n');
+                }
+                rich_text(child, out);
+            }
+        }
+    }
+}
+
+/// Stroke, tint and note fill for a color name (black for unknown names).
+fn palette(name: &str) -> (&'static str, u32, u32, u32) {
+    PALETTE
+        .iter()
+        .find(|(n, ..)| *n == name)
+        .copied()
+        .unwrap_or(PALETTE[0])
+}
+
+fn rgb(hex: u32) -> SerializableColor {
+    SerializableColor::new((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255)
+}
+
+fn union_bounds(shapes: &[Shape]) -> Option<Rect> {
+    shapes.iter().map(Shape::bounds).reduce(|a, b| a.union(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn file(records: Value) -> String {
+        let mut all =
+            vec![json!({ "id": "page:page", "typeName": "page", "name": "Page 1", "index": "a1" })];
+        all.extend(records.as_array().unwrap().iter().cloned());
+        json!({ "tldrawFileFormatVersion": 1, "schema": {}, "records": all }).to_string()
+    }
+
+    fn shape(id: &str, kind: &str, x: f64, y: f64, props: Value) -> Value {
+        json!({
+            "id": id, "typeName": "shape", "type": kind, "parentId": "page:page",
+            "index": id, "x": x, "y": y, "rotation": 0, "opacity": 1, "props": props,
+        })
+    }
+
+    #[test]
+    fn test_detect_and_errors() {
+        assert!(is_tldraw(&file(json!([]))));
+        assert!(!is_tldraw("{\"type\": \"excalidraw\"}"));
+        assert!(!is_tldraw("graph { a -- b }"));
+        assert!(matches!(parse_tldraw("nope"), Err(TldrawError::Json(_))));
+        assert_eq!(parse_tldraw("{}").unwrap_err(), TldrawError::NotTldraw);
+        assert_eq!(
+            parse_tldraw("{\"document\": {\"pages\": {}}}").unwrap_err(),
+            TldrawError::Legacy
+        );
+        assert!(parse_tldraw(&file(json!([]))).unwrap().shapes.is_empty());
+    }
+
+    #[test]
+    fn test_geo_notes_and_text() {
+        let mut rotated = shape(
+            "shape:b",
+            "geo",
+            100.0,
+            0.0,
+            json!({
+                "geo": "rectangle", "w": 100, "h": 50, "color": "blue", "fill": "solid",
+                "dash": "solid", "size": "l",
+            }),
+        );
+        rotated["rotation"] = json!(PI / 2.0);
+        let json = file(json!([
+            shape(
+                "shape:a",
+                "geo",
+                0.0,
+                0.0,
+                json!({
+                    "geo": "diamond", "w": 100, "h": 60, "text": "Decide", "fill": "pattern",
+                })
+            ),
+            rotated,
+            shape(
+                "shape:c",
+                "note",
+                0.0,
+                300.0,
+                json!({
+                    "color": "yellow", "richText": { "type": "doc", "content": [
+                        { "type": "paragraph", "content": [{ "type": "text", "text": "One" }] },
+                        { "type": "paragraph", "content": [{ "type": "text", "text": "Two" }] },
+                    ]},
+                })
+            ),
+            shape(
+                "shape:d",
+                "text",
+                0.0,
+                600.0,
+                json!({
+                    "text": "Hello", "size": "s", "font": "serif", "color": "red",
+                })
+            ),
+            shape(
+                "shape:e",
+                "geo",
+                0.0,
+                0.0,
+                json!({ "geo": "cloud", "w": 80, "h": 40 })
+            ),
+        ]));
+        let shapes = parse_tldraw(&json).unwrap().shapes;
+        assert_eq!(shapes.len(), 5);
+
+        // Labeled diamond: a closed polygon grouped with its centered label
+        let Shape::Group(labeled) = &shapes[0] else {
+            panic!("expected a labeled shape");
+        };
+        let Shape::Line(diamond) = &labeled.children[0] else {
+            panic!("expected a polygon");
+        };
+        assert!(diamond.is_closed());
+        assert_eq!(diamond.start, Point::new(50.0, 0.0));
+        assert_eq!(diamond.style.fill_pattern, FillPattern::Hachure);
+        assert_eq!(diamond.style.sloppiness, Sloppiness::Artist);
+        let Shape::Text(label) = &labeled.children[1] else {
+            panic!("expected a label");
+        };
+        assert_eq!(label.content, "Decide");
+        assert!((label.bounds().center() - Point::new(50.0, 30.0)).hypot() < 1e-9);
+
+        // Rotated about its top-left corner in tldraw, about its center here
+        let Shape::Rectangle(rect) = &shapes[1] else {
+            panic!("expected a rectangle");
+        };
+        assert!((rect.bounds().center() - Point::new(75.0, 50.0)).hypot() < 1e-9);
+        assert_eq!(rect.rotation, PI / 2.0);
+        assert_eq!(rect.style.stroke_color, rgb(0x4465e9));
+        assert_eq!(rect.style.fill_color, Some(rgb(0xdce1f8)));
+        assert_eq!(rect.style.stroke_width, 5.0);
+        assert_eq!(rect.style.sloppiness, Sloppiness::Architect);
+
+        let Shape::Group(note) = &shapes[2] else {
+            panic!("expected a note");
+        };
+        assert_eq!(note.children[0].style().fill_color, Some(rgb(0xfed49a)));
+        assert!(matches!(&note.children[1], Shape::Text(t) if t.content == "One\nTwo"));
+
+        let Shape::Text(text) = &shapes[3] else {
+            panic!("expected a text");
+        };
+        assert_eq!(text.position, Point::new(0.0, 600.0));
+        assert_eq!(text.font_size, 18.0);
+        assert_eq!(text.font_family, FontFamily::GelPenSerif);
+        assert_eq!(text.style.stroke_color, rgb(0xe03131));
+        assert!(matches!(shapes[4], Shape::Ellipse(_)));
+    }
+
+    #[test]
+    fn test_arrows_and_bindings() {
+        let target = |id: &str, x: f64| {
+            shape(
+                id,
+                "geo",
+                x,
+                0.0,
+                json!({ "geo": "rectangle", "w": 100, "h": 100 }),
+            )
+        };
+        let json = file(json!([
+            target("shape:a", 0.0),
+            target("shape:b", 300.0),
+            // Current format: terminals are points, bindings are records
+            shape("shape:arrow", "arrow", 0.0, 0.0, json!({
+                "start": { "x": 0, "y": 0 }, "end": { "x": 10, "y": 10 }, "text": "calls",
+            })),
+            {
+                "id": "binding:1", "typeName": "binding", "type": "arrow",
+                "fromId": "shape:arrow", "toId": "shape:a",
+                "props": { "terminal": "start", "normalizedAnchor": { "x": 0.5, "y": 0.5 },
+                           "isExact": false, "isPrecise": false },
+            },
+            {
+                "id": "binding:2", "typeName": "binding", "type": "arrow",
+                "fromId": "shape:arrow", "toId": "shape:b",
+                "props": { "terminal": "end", "normalizedAnchor": { "x": 0.5, "y": 0.25 },
+                           "isExact": false, "isPrecise": true },
+            },
+            // Older format: bindings inline, only a start arrowhead, bent
+            shape("shape:old", "arrow", 0.0, 500.0, json!({
+                "start": { "type": "point", "x": 0, "y": 0 },
+                "end": { "type": "binding", "boundShapeId": "shape:b",
+                         "normalizedAnchor": { "x": 0.5, "y": 0.5 }, "isExact": true },
+                "bend": 20, "arrowheadStart": "arrow", "arrowheadEnd": "none", "dash": "dashed",
+            })),
+            shape("shape:plain", "arrow", 0.0, 800.0, json!({
+                "start": { "x": 0, "y": 0 }, "end": { "x": 50, "y": 0 },
+                "arrowheadEnd": "none",
+            })),
+        ]));
+        let shapes = parse_tldraw(&json).unwrap().shapes;
+        assert_eq!(shapes.len(), 5);
+
+        // Fractional indices sort the arrow between its targets
+        let Shape::Group(labeled) = &shapes[1] else {
+            panic!("expected a labeled arrow");
+        };
+        let Shape::Arrow(arrow) = &labeled.children[0] else {
+            panic!("expected an arrow");
+        };
+        // From the center of A towards the precise anchor on B, clipped to both outlines
+        assert!((arrow.start - Point::new(100.0, 275.0 / 6.0)).hypot() < 1e-9);
+        assert!((arrow.end - Point::new(300.0, 175.0 / 6.0)).hypot() < 1e-9);
+        assert!(matches!(&labeled.children[1], Shape::Text(t) if t.content == "calls"));
+
+        // Reversed so the head is at the end; exact binding ends at the anchor
+        let Shape::Arrow(old) = &shapes[3] else {
+            panic!("expected an arrow");
+        };
+        assert_eq!(old.start, Point::new(350.0, 50.0));
+        assert_eq!(old.end, Point::new(0.0, 500.0));
+        assert_eq!(old.intermediate_points.len(), 1);
+        assert_eq!(old.path_style, PathStyle::Flowing);
+        assert_eq!(old.stroke_style, StrokeStyle::Dashed);
+        assert!(matches!(shapes[4], Shape::Line(_)));
+    }
+
+    #[test]
+    fn test_strokes_frames_and_pages() {
+        let mut child = shape(
+            "shape:child",
+            "geo",
+            10.0,
+            20.0,
+            json!({ "w": 10, "h": 10 }),
+        );
+        child["parentId"] = json!("shape:frame");
+        let mut grouped = shape(
+            "shape:g2",
+            "line",
+            5.0,
+            5.0,
+            json!({
+                "points": {
+                    "b": { "id": "b", "index": "a2", "x": 10, "y": 0 },
+                    "a": { "id": "a", "index": "a1", "x": 0, "y": 0 },
+                },
+                "spline": "cubic",
+            }),
+        );
+        grouped["parentId"] = json!("shape:group");
+        let mut grouped_too = shape("shape:g1", "geo", 0.0, 0.0, json!({ "w": 10, "h": 10 }));
+        grouped_too["parentId"] = json!("shape:group");
+        let mut second = shape("shape:p2", "geo", 0.0, 0.0, json!({ "w": 10, "h": 10 }));
+        second["parentId"] = json!("page:two");
+
+        let json = file(json!([
+            { "id": "page:two", "typeName": "page", "name": "Page 2", "index": "a2" },
+            shape("shape:draw", "draw", 100.0, 100.0, json!({
+                "segments": [{ "type": "free", "points": [
+                    { "x": 0, "y": 0, "z": 0.2 }, { "x": 5, "y": 5, "z": 0.9 },
+                ]}],
+                "isPen": true,
+            })),
+            shape("shape:frame", "frame", 200.0, 0.0, json!({ "w": 300, "h": 200, "name": "Ideas" })),
+            child,
+            shape("shape:group", "group", 0.0, 400.0, json!({})),
+            grouped,
+            grouped_too,
+            second,
+        ]));
+        let shapes = parse_tldraw(&json).unwrap().shapes;
+        assert_eq!(shapes.len(), 4);
+
+        let Shape::Freehand(ink) = &shapes[0] else {
+            panic!("expected a freehand");
+        };
+        assert_eq!(
+            ink.points,
+            vec![Point::new(100.0, 100.0), Point::new(105.0, 105.0)]
+        );
+        assert_eq!(ink.pressures, vec![0.2, 0.9]);
+
+        // Frame: outline, name, then the child relative to the frame
+        let Shape::Group(frame) = &shapes[1] else {
+            panic!("expected a frame");
+        };
+        assert_eq!(frame.children.len(), 3);
+        assert!(matches!(&frame.children[1], Shape::Text(t) if t.content == "Ideas"));
+        assert_eq!(frame.frame.as_deref(), Some("Ideas"));
+        assert_eq!(
+            frame.children[2].bounds(),
+            Rect::new(210.0, 20.0, 220.0, 30.0)
+        );
+
+        // Group children sorted by index, line points sorted by index
+        let Shape::Group(group) = &shapes[2] else {
+            panic!("expected a group");
+        };
+        assert!(matches!(group.children[0], Shape::Rectangle(_)));
+        let Shape::Line(line) = &group.children[1] else {
+            panic!("expected a line");
+        };
+        assert_eq!(line.start, Point::new(5.0, 405.0));
+        assert_eq!(line.end, Point::new(15.0, 405.0));
+        assert_eq!(line.path_style, PathStyle::Flowing);
+
+        // The second page sits below the first
+        assert_eq!(shapes[3].bounds().y0, 410.0 + PAGE_GAP);
+    }
+
+    #[test]
+    fn test_images_and_unsupported() {
+        let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAYAAAAfFcSJAAAADUlEQVR42mNk+M9QDwADhgGAWjR9awAAAABJRU5ErkJggg==";
+        let json = file(json!([
+            {
+                "id": "asset:1", "typeName": "asset", "type": "image",
+                "props": { "src": format!("data:image/png;base64,{}", png), "w": 1, "h": 1 },
+            },
+            {
+                "id": "asset:2", "typeName": "asset", "type": "image",
+                "props": { "src": "https://example.com/cat.png", "w": 1, "h": 1 },
+            },
+            shape("shape:img", "image", 10.0, 20.0, json!({ "w": 40, "h": 30, "assetId": "asset:1" })),
+            shape("shape:linked", "image", 0.0, 0.0, json!({ "w": 40, "h": 30, "assetId": "asset:2" })),
+            shape("shape:embed1", "embed", 0.0, 0.0, json!({ "w": 40, "h": 30 })),
+            shape("shape:embed2", "embed", 0.0, 0.0, json!({ "w": 40, "h": 30 })),
+            shape("shape:video", "video", 0.0, 0.0, json!({ "w": 40, "h": 30 })),
+        ]));
+        let import = parse_tldraw(&json).unwrap();
+        assert_eq!(import.shapes.len(), 1);
+        let Shape::Image(image) = &import.shapes[0] else {
+            panic!("expected an image");
+        };
+        assert_eq!(image.as_rect(), Rect::new(10.0, 20.0, 50.0, 50.0));
+
+        assert_eq!(import.unsupported.total(), 4);
+        assert_eq!(
+            import.unsupported.to_string(),
+            "2 × embed, 1 × image (linked), 1 × video"
+        );
+    }
+}
@@ -0,0 +1,862 @@
+//! CPU renderer for headless rendering.
+//!
+//! Rasterizes into an RGBA buffer without a GPU, for PNG export from tools and
+//! golden-image tests. Shapes go through the same drawing code as the vector
+//! exporters, so hand-drawn strokes, fill patterns and math match them; text is
+//! laid out with Parley like on the canvas.
+
+use crate::diff_overlay::DiffOutline;
+use crate::fonts::{
+    GELPEN_HEAVY, GELPEN_LIGHT, GELPEN_REGULAR, GELPEN_SERIF_HEAVY, GELPEN_SERIF_LIGHT,
+    GELPEN_SERIF_MEDIUM, GlyphPath, NOTO_SANS, NOTO_SANS_BOLD, NOTO_SANS_ITALIC, VANILLA_EXTRACT,
+    bundled_font,
+};
+use crate::raster::{Paint, Pixmap, Texture};
+use crate::renderer::{GridStyle, PngRenderResult, RenderContext, Renderer, ShapeRenderer};
+use crate::rex_backend::MathSink;
+use crate::vector::{VectorSink, content_bounds, draw_shape, draw_svg_image, selected_shapes};
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::selection::{HandleKind, get_handles};
+use drafftink_core::shapes::{Image, ImageFormat, Shape, ShapeId, ShapeTrait, Text};
+use kurbo::{Affine, BezPath, Circle, Point, Rect, Shape as KurboShape, Stroke};
+use parley::layout::PositionedLayoutItem;
+use parley::{FontContext, LayoutContext};
+use peniko::{Brush, Color, Fill};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Padding around exported content, in logical pixels (as in the Vello export).
+const EXPORT_PADDING: f64 = 20.0;
+
+/// CPU renderer that rasterizes to an RGBA buffer.
+///
+/// Implements [`Renderer`] like [`crate::VelloRenderer`]: [`Renderer::build_scene`]
+/// draws the background, grid, shapes, selection and pointer overlays into
+/// [`CpuRenderer::output`]. Snap and alignment guides are only drawn on the GPU.
+pub struct CpuRenderer {
+    pixmap: Pixmap,
+    /// Transform stack for nested groups; the last entry is current.
+    transforms: Vec<Affine>,
+    /// Selection highlight color.
+    selection_color: Color,
+    /// Current zoom level (for zoom-independent UI elements and the hand-drawn effect).
+    zoom: f64,
+    /// Font context for text rendering.
+    font_cx: FontContext,
+    /// Layout context for text rendering.
+    layout_cx: LayoutContext<Brush>,
+    /// Decoded images by asset ID (`None` if the image failed to decode).
+    image_cache: HashMap<String, Option<Arc<Texture>>>,
+}
+
+impl Default for CpuRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CpuRenderer {
+    /// Create a new CPU renderer.
+    pub fn new() -> Self {
+        let mut font_cx = FontContext::new();
+        for data in [
+            GELPEN_REGULAR,
+            GELPEN_LIGHT,
+            GELPEN_HEAVY,
+            VANILLA_EXTRACT,
+            GELPEN_SERIF_LIGHT,
+            GELPEN_SERIF_MEDIUM,
+            GELPEN_SERIF_HEAVY,
+            NOTO_SANS,
+            NOTO_SANS_BOLD,
+            NOTO_SANS_ITALIC,
+        ] {
+            font_cx
+                .collection
+                .register_fonts(parley::fontique::Blob::new(Arc::new(data)), None);
+        }
+
+        Self {
+            pixmap: Pixmap::new(0, 0),
+            transforms: vec![Affine::IDENTITY],
+            selection_color: Color::from_rgba8(59, 130, 246, 255),
+            font_cx,
+            layout_cx: LayoutContext::new(),
+            zoom: 1.0,
+            image_cache: HashMap::new(),
+        }
+    }
+
+    /// The pixels drawn by the last [`Renderer::build_scene`] or export.
+    pub fn output(&self) -> PngRenderResult {
+        PngRenderResult {
+            rgba_data: self.pixmap.to_rgba8(),
+            width: self.pixmap.width(),
+            height: self.pixmap.height(),
+        }
+    }
+
+    /// Render a document for export (shapes only, on white).
+    ///
+    /// `scale` is the export resolution multiplier (1 = 1x, 2 = 2x, 3 = 3x).
+    /// Returns `None` if the document is empty.
+    pub fn render_document(
+        &mut self,
+        document: &CanvasDocument,
+        scale: f64,
+    ) -> Option<PngRenderResult> {
+        let shapes: Vec<&Shape> = document.shapes_ordered().collect();
+        self.render_export(&shapes, scale)
+    }
+
+    /// Render the selected shapes for export.
+    ///
+    /// `scale` is the export resolution multiplier (1 = 1x, 2 = 2x, 3 = 3x).
+    /// Returns `None` if nothing is selected.
+    pub fn render_selection(
+        &mut self,
+        document: &CanvasDocument,
+        selection: &[ShapeId],
+        scale: f64,
+    ) -> Option<PngRenderResult> {
+        let shapes = selected_shapes(document, selection);
+        self.render_export(&shapes, scale)
+    }
+
+    fn render_export(&mut self, shapes: &[&Shape], scale: f64) -> Option<PngRenderResult> {
+        let bounds = content_bounds(shapes)?.inflate(EXPORT_PADDING, EXPORT_PADDING);
+        let width = (bounds.width() * scale).ceil() as u32;
+        let height = (bounds.height() * scale).ceil() as u32;
+        if width == 0 || height == 0 {
+            return None;
+        }
+
+        self.pixmap = Pixmap::new(width, height);
+        self.pixmap.clear(Color::WHITE);
+        self.zoom = scale;
+        let transform = Affine::scale(scale) * Affine::translate((-bounds.x0, -bounds.y0));
+        for shape in shapes {
+            self.render_shape(shape, transform, false);
+        }
+        Some(self.output())
+    }
+
+    /// The current transform.
+    fn transform(&self) -> Affine {
+        self.transforms.last().copied().unwrap_or_default()
+    }
+
+    fn fill_solid(&mut self, path: &BezPath, transform: Affine, color: Color) {
+        self.pixmap
+            .fill(path, transform, Fill::NonZero, Paint::Solid(color));
+    }
+
+    fn stroke_path(&mut self, path: &BezPath, transform: Affine, color: Color, stroke: &Stroke) {
+        self.pixmap.stroke(path, transform, stroke, color);
+    }
+
+    /// Decode an image, caching the result by asset ID.
+    fn texture(&mut self, image: &Image) -> Option<Arc<Texture>> {
+        self.image_cache
+            .entry(image.asset.id().to_string())
+            .or_insert_with(|| {
+                let decoded = ::image::load_from_memory(image.data()).ok()?.to_rgba8();
+                let (width, height) = decoded.dimensions();
+                Some(Arc::new(Texture::from_rgba8(width, height, &decoded)))
+            })
+            .clone()
+    }
+
+    /// Render a placeholder for images that couldn't be loaded.
+    fn render_image_placeholder(&mut self, image: &Image, transform: Affine) {
+        let bounds = image.bounds();
+        let rect_path = bounds.to_path(0.1);
+        let stroke = Stroke::new(2.0);
+        self.fill_solid(&rect_path, transform, Color::from_rgba8(200, 200, 200, 255));
+
+        let mut x_path = BezPath::new();
+        x_path.move_to(Point::new(bounds.x0, bounds.y0));
+        x_path.line_to(Point::new(bounds.x1, bounds.y1));
+        x_path.move_to(Point::new(bounds.x1, bounds.y0));
+        x_path.line_to(Point::new(bounds.x0, bounds.y1));
+        self.stroke_path(
+            &x_path,
+            transform,
+            Color::from_rgba8(150, 150, 150, 255),
+            &stroke,
+        );
+        self.stroke_path(
+            &rect_path,
+            transform,
+            Color::from_rgba8(100, 100, 100, 255),
+            &stroke,
+        );
+    }
+
+    /// Lay out text with Parley and fill the glyph outlines.
+    fn render_text(&mut self, text: &Text, transform: Affine) {
+        use parley::StyleProperty;
+
+        let font = bundled_font(&text.font_family, &text.font_weight);
+        let mut builder =
+            self.layout_cx
+                .ranged_builder(&mut self.font_cx, &text.content, 1.0, false);
+        builder.push_default(StyleProperty::FontSize(text.font_size as f32));
+        builder.push_default(StyleProperty::Brush(Brush::Solid(
+            text.style.stroke_with_opacity(),
+        )));
+        builder.push_default(StyleProperty::FontWeight(if font.bold {
+            parley::FontWeight::BOLD
+        } else {
+            parley::FontWeight::NORMAL
+        }));
+        if font.italic {
+            builder.push_default(StyleProperty::FontStyle(parley::FontStyle::Italic));
+        }
+        builder.push_default(StyleProperty::FontStack(parley::FontStack::Single(
+            parley::FontFamily::Named(font.name.into()),
+        )));
+        let mut byte_offset = 0;
+        for (char_idx, ch) in text.content.chars().enumerate() {
+            if let Some(Some(color)) = text.char_colors.get(char_idx) {
+                let color: Color = (*color).into();
+                builder.push(
+                    StyleProperty::Brush(Brush::Solid(color)),
+                    byte_offset..byte_offset + ch.len_utf8(),
+                );
+            }
+            byte_offset += ch.len_utf8();
+        }
+
+        let mut layout = builder.build(&text.content);
+        layout.break_all_lines(None);
+        layout.align(
+            None,
+            parley::Alignment::Start,
+            parley::AlignmentOptions::default(),
+        );
+        text.set_cached_size(layout.width() as f64, layout.height() as f64);
+
+        let text_transform = transform * Affine::translate((text.position.x, text.position.y));
+        let mut glyph_count = 0;
+        for line in layout.lines() {
+            for item in line.items() {
+                let PositionedLayoutItem::GlyphRun(glyph_run) = item else {
+                    continue;
+                };
+                let run = glyph_run.run();
+                let font_data = run.font();
+                let Ok(face) = ttf_parser::Face::parse(font_data.data.data(), font_data.index)
+                else {
+                    continue;
+                };
+                let Brush::Solid(color) = glyph_run.style().brush else {
+                    continue;
+                };
+                // Outlines are in font units, y up
+                let skew = run.synthesis().skew().map_or(Affine::IDENTITY, |angle| {
+                    Affine::skew(angle.to_radians().tan() as f64, 0.0)
+                });
+                let em = Affine::scale_non_uniform(1.0, -1.0)
+                    * skew
+                    * Affine::scale(run.font_size() as f64 / face.units_per_em() as f64);
+
+                let mut x = glyph_run.offset();
+                let y = glyph_run.baseline();
+                for glyph in glyph_run.glyphs() {
+                    let origin = Affine::translate(((x + glyph.x) as f64, (y - glyph.y) as f64));
+                    x += glyph.advance;
+                    glyph_count += 1;
+                    let mut outline = GlyphPath(BezPath::new());
+                    let id = ttf_parser::GlyphId(glyph.id as u16);
+                    if face.outline_glyph(id, &mut outline).is_some() {
+                        self.fill_solid(&outline.0, text_transform * origin * em, color);
+                    }
+                }
+            }
+        }
+
+        if glyph_count == 0 && !text.content.is_empty() {
+            let width = text.content.len() as f64 * text.font_size * 0.6;
+            let height = text.font_size * 1.2;
+            let rect = Rect::new(
+                text.position.x,
+                text.position.y,
+                text.position.x + width.max(20.0),
+                text.position.y + height,
+            );
+            self.fill_solid(
+                &rect.to_path(0.1),
+                transform,
+                Color::from_rgba8(255, 100, 100, 100),
+            );
+        }
+    }
+
+    /// Render shape-specific selection handles at a constant screen size.
+    fn render_shape_handles(&mut self, shape: &Shape, transform: Affine) {
+        let handle_size = 16.0 / self.zoom;
+        let thick = Stroke::new(2.0 / self.zoom);
+        let thin = Stroke::new(1.5 / self.zoom);
+        let color = self.selection_color;
+
+        // Lines and arrows only show their endpoint handles
+        if !matches!(shape, Shape::Line(_) | Shape::Arrow(_)) {
+            let bounds = shape.bounds();
+            let dash_len = 4.0 / self.zoom;
+            let stroke = Stroke::new(1.0 / self.zoom).with_dashes(0.0, [dash_len, dash_len]);
+            let box_transform = transform * Affine::rotate_about(shape.rotation(), bounds.center());
+            self.stroke_path(&bounds.to_path(0.1), box_transform, color, &stroke);
+        }
+
+        for handle in get_handles(shape) {
+            let pos = handle.position;
+            match handle.kind {
+                HandleKind::Endpoint(_) | HandleKind::IntermediatePoint(_) => {
+                    let path = Circle::new(pos, handle_size / 2.0).to_path(0.1);
+                    self.fill_solid(&path, transform, Color::WHITE);
+                    self.stroke_path(&path, transform, color, &thick);
+                }
+                HandleKind::SegmentMidpoint(_) => {
+                    let path = Circle::new(pos, handle_size / 3.0).to_path(0.1);
+                    self.fill_solid(&path, transform, Color::from_rgba8(200, 220, 255, 200));
+                    self.stroke_path(&path, transform, color, &thin);
+                }
+                HandleKind::Corner(_) | HandleKind::Edge(_) => {
+                    let half = handle_size / 2.0;
+                    let rect = Rect::new(pos.x - half, pos.y - half, pos.x + half, pos.y + half);
+                    let path = rect.to_path(0.1);
+                    self.fill_solid(&path, transform, Color::WHITE);
+                    self.stroke_path(&path, transform, color, &thin);
+                }
+                HandleKind::Rotate => {
+                    let radius = handle_size / 2.0;
+                    let path = Circle::new(pos, radius).to_path(0.1);
+                    self.fill_solid(&path, transform, Color::WHITE);
+                    self.stroke_path(&path, transform, color, &thick);
+
+                    // A 270° arc inside the handle
+                    let arc = kurbo::Arc::new(
+                        pos,
+                        (radius * 0.5, radius * 0.5),
+                        -std::f64::consts::FRAC_PI_4,
+                        std::f64::consts::PI * 1.5,
+                        0.0,
+                    );
+                    let arc_path: BezPath = arc.path_elements(0.1).collect();
+                    self.stroke_path(&arc_path, transform, color, &thin);
+                }
+            }
+        }
+    }
+
+    /// Render a dashed rectangle: the selection marquee or the outline of the
+    /// group being edited.
+    fn render_dashed_rect(&mut self, rect: Rect, transform: Affine, dash: f64, fill: bool) {
+        let path = rect.to_path(0.1);
+        if fill {
+            self.fill_solid(&path, transform, Color::from_rgba8(59, 130, 246, 25));
+        }
+        let dash_len = dash / self.zoom;
+        let stroke = Stroke::new(1.0 / self.zoom).with_dashes(0.0, [dash_len, dash_len]);
+        self.stroke_path(&path, transform, self.selection_color, &stroke);
+    }
+
+    /// Render the outline of a changed shape; the focused change is bolder and
+    /// tinted.
+    fn render_diff_outline(&mut self, outline: &DiffOutline, transform: Affine) {
+        let (padding, width) = if outline.focused {
+            (8.0, 3.0)
+        } else {
+            (4.0, 1.5)
+        };
+        let rect = outline
+            .rect
+            .inflate(padding / self.zoom, padding / self.zoom);
+        let path = rect.to_path(0.1);
+        if outline.focused {
+            self.fill_solid(&path, transform, outline.color.with_alpha(0.12));
+        }
+        let mut stroke = Stroke::new(width / self.zoom);
+        if outline.dashed {
+            let dash_len = 6.0 / self.zoom;
+            stroke = stroke.with_dashes(0.0, [dash_len, dash_len]);
+        }
+        self.stroke_path(&path, transform, outline.color, &stroke);
+    }
+
+    /// Render the eraser cursor and laser pointer.
+    fn render_pointers(&mut self, ctx: &RenderContext, transform: Affine) {
+        if let Some((pos, radius)) = ctx.eraser_cursor {
+            let circle = Circle::new(pos, radius).to_path(0.1);
+            self.fill_solid(&circle, transform, Color::from_rgba8(255, 100, 100, 50));
+            self.stroke_path(
+                &circle,
+                transform,
+                Color::from_rgba8(200, 50, 50, 200),
+                &Stroke::new(2.0 / self.zoom),
+            );
+        }
+
+        if let Some((pos, ref trail)) = ctx.laser_pointer {
+            for (point, alpha) in trail {
+                let color = Color::from_rgba8(255, 0, 0, (*alpha * 255.0) as u8);
+                let dot = Circle::new(*point, 4.0 / self.zoom * *alpha).to_path(0.1);
+                self.fill_solid(&dot, transform, color);
+            }
+            for (radius, color) in [
+                (12.0, Color::from_rgba8(255, 0, 0, 100)),
+                (6.0, Color::from_rgba8(255, 50, 50, 255)),
+                (2.0, Color::WHITE),
+            ] {
+                let dot = Circle::new(pos, radius / self.zoom).to_path(0.1);
+                self.fill_solid(&dot, transform, color);
+            }
+        }
+    }
+
+    /// Render the background grid over the viewport.
+    fn render_grid_style(
+        &mut self,
+        style: GridStyle,
+        viewport: Rect,
+        transform: Affine,
+        grid_size: f64,
+    ) {
+        let inv = transform.inverse();
+        let world_tl = inv * Point::new(viewport.x0, viewport.y0);
+        let world_br = inv * Point::new(viewport.x1, viewport.y1);
+        let start_x = (world_tl.x / grid_size).floor() * grid_size;
+        let start_y = (world_tl.y / grid_size).floor() * grid_size;
+        let end_x = (world_br.x / grid_size).ceil() * grid_size;
+        let end_y = (world_br.y / grid_size).ceil() * grid_size;
+
+        let mut path = BezPath::new();
+        let mut y = start_y;
+        while y <= end_y {
+            let mut x = start_x;
+            while x <= end_x {
+                match style {
+                    GridStyle::Lines if y == start_y => {
+                        path.move_to((x, start_y));
+                        path.line_to((x, end_y));
+                    }
+                    GridStyle::CrossPlus => {
+                        path.move_to((x - 3.0, y));
+                        path.line_to((x + 3.0, y));
+                        path.move_to((x, y - 3.0));
+                        path.line_to((x, y + 3.0));
+                    }
+                    GridStyle::Dots => {
+                        path.extend(Rect::new(x - 1.5, y - 1.5, x + 1.5, y + 1.5).to_path(0.1));
+                    }
+                    _ => {}
+                }
+                x += grid_size;
+            }
+            if matches!(style, GridStyle::Lines | GridStyle::HorizontalLines) {
+                path.move_to((start_x, y));
+                path.line_to((end_x, y));
+            }
+            y += grid_size;
+        }
+
+        match style {
+            GridStyle::None => {}
+            GridStyle::Lines | GridStyle::HorizontalLines => self.stroke_path(
+                &path,
+                transform,
+                Color::from_rgba8(200, 200, 200, 100),
+                &Stroke::new(0.5),
+            ),
+            GridStyle::CrossPlus => self.stroke_path(
+                &path,
+                transform,
+                Color::from_rgba8(180, 180, 180, 60),
+                &Stroke::new(1.0),
+            ),
+            GridStyle::Dots => {
+                self.fill_solid(&path, transform, Color::from_rgba8(160, 160, 160, 70))
+            }
+        }
+    }
+}
+
+impl MathSink for CpuRenderer {
+    fn fill_path(&mut self, transform: Affine, color: Color, path: &BezPath) {
+        self.fill_solid(path, self.transform() * transform, color);
+    }
+}
+
+impl VectorSink for CpuRenderer {
+    fn begin_group(&mut self, transform: Affine) {
+        self.transforms.push(self.transform() * transform);
+    }
+
+    fn end_group(&mut self) {
+        self.transforms.pop();
+    }
+
+    fn begin_clip(&mut self, path: &BezPath) {
+        self.pixmap.push_clip(path, self.transform());
+    }
+
+    fn end_clip(&mut self) {
+        self.pixmap.pop_clip();
+    }
+
+    fn fill(&mut self, path: &BezPath, color: Color) {
+        self.fill_solid(path, self.transform(), color);
+    }
+
+    fn fill_even_odd(&mut self, path: &BezPath, color: Color) {
+        self.pixmap
+            .fill(path, self.transform(), Fill::EvenOdd, Paint::Solid(color));
+    }
+
+    fn stroke(&mut self, path: &BezPath, color: Color, width: f64, dashes: Option<[f64; 2]>) {
+        let mut stroke = Stroke::new(width);
+        if let Some(dashes) = dashes {
+            stroke = stroke.with_dashes(0.0, dashes);
+        }
+        self.stroke_path(path, self.transform(), color, &stroke);
+    }
+
+    fn text(&mut self, text: &Text) {
+        self.render_text(text, self.transform());
+    }
+
+    fn image(&mut self, image: &Image) {
+        let transform = self.transform();
+        if image.format == ImageFormat::Svg {
+            if !draw_svg_image(self, image) {
+                self.render_image_placeholder(image, transform);
+            }
+            return;
+        }
+        let Some(texture) = self.texture(image) else {
+            self.render_image_placeholder(image, transform);
+            return;
+        };
+        let bounds = image.bounds();
+        let image_transform = transform
+            * Affine::translate((bounds.x0, bounds.y0))
+            * Affine::scale_non_uniform(
+                bounds.width() / texture.width() as f64,
+                bounds.height() / texture.height() as f64,
+            );
+        let paint = Paint::Image {
+            texture: &texture,
+            transform: image_transform,
+        };
+        self.pixmap
+            .fill(&bounds.to_path(0.1), transform, Fill::NonZero, paint);
+    }
+
+    fn zoom(&self) -> f64 {
+        self.zoom
+    }
+}
+
+impl Renderer for CpuRenderer {
+    fn build_scene(&mut self, ctx: &RenderContext) {
+        let width = ctx.viewport_size.width.ceil().max(0.0) as u32;
+        let height = ctx.viewport_size.height.ceil().max(0.0) as u32;
+        if self.pixmap.width() != width || self.pixmap.height() != height {
+            self.pixmap = Pixmap::new(width, height);
+        }
+        self.pixmap.clear(self.background_color(ctx));
+        self.selection_color = ctx.selection_color;
+        self.zoom = ctx.canvas.camera.zoom;
+
+        let camera_transform = ctx.canvas.camera.transform();
+        let viewport = Rect::new(0.0, 0.0, ctx.viewport_size.width, ctx.viewport_size.height);
+        self.render_grid_style(ctx.grid_style, viewport, camera_transform, 20.0);
+
+        let world_viewport = camera_transform.inverse().transform_rect_bbox(viewport);
+        if let Some(overlay) = ctx.diff_overlay {
+            for ghost in overlay.ghosts() {
+                self.render_shape(&ghost, camera_transform, false);
+            }
+        }
+        for shape in ctx.canvas.document.shapes_ordered() {
+            if ctx.editing_shape_id == Some(shape.id()) {
+                continue;
+            }
+            let shape_bounds = shape.bounds().inflate(1.0, 1.0);
+            if !shape_bounds.intersect(world_viewport).is_zero_area() {
+                let is_selected = ctx.canvas.is_selected(shape.id());
+                self.render_shape(shape, camera_transform, is_selected);
+            }
+        }
+
+        // When editing inside a group, dim everything else and redraw the group's children on top
+        if let Some(Shape::Group(group)) = ctx
+            .canvas
+            .editing_group()
+            .and_then(|id| ctx.canvas.document.find_shape(id))
+        {
+            let veil = ctx.background_color.with_alpha(0.65);
+            self.fill_solid(&world_viewport.to_path(0.1), camera_transform, veil);
+            let context_transform = camera_transform * ctx.canvas.context_transform();
+            let outline = group.bounds().inflate(6.0 / self.zoom, 6.0 / self.zoom);
+            self.render_dashed_rect(outline, context_transform, 6.0, false);
+            for child in group.children() {
+                if ctx.editing_shape_id == Some(child.id()) {
+                    continue;
+                }
+                let is_selected = ctx.canvas.is_selected(child.id());
+                self.render_shape(child, context_transform, is_selected);
+            }
+        }
+
+        if let Some(preview) = ctx.canvas.tool_manager.preview_shape() {
+            self.render_shape(&preview, camera_transform, false);
+        }
+
+        if let Some(overlay) = ctx.diff_overlay {
+            for outline in overlay.outlines(&ctx.canvas.document) {
+                self.render_diff_outline(&outline, camera_transform);
+            }
+        }
+
+        if let Some(rect) = ctx.selection_rect {
+            self.render_dashed_rect(rect, camera_transform, 4.0, true);
+        }
+
+        self.render_pointers(ctx, camera_transform);
+    }
+}
+
+impl ShapeRenderer for CpuRenderer {
+    fn render_shape(&mut self, shape: &Shape, transform: Affine, selected: bool) {
+        self.transforms = vec![transform];
+        draw_shape(self, shape);
+        if selected {
+            self.render_shape_handles(shape, transform);
+        }
+    }
+
+    fn render_grid(&mut self, viewport: Rect, transform: Affine, grid_size: f64) {
+        self.render_grid_style(GridStyle::Lines, viewport, transform, grid_size);
+    }
+
+    fn render_selection_handles(&mut self, bounds: Rect, transform: Affine) {
+        let stroke = Stroke::new(1.5);
+        let path = bounds.to_path(0.1);
+        self.stroke_path(&path, transform, self.selection_color, &Stroke::new(2.0));
+        for corner in [
+            Point::new(bounds.x0, bounds.y0),
+            Point::new(bounds.x1, bounds.y0),
+            Point::new(bounds.x1, bounds.y1),
+            Point::new(bounds.x0, bounds.y1),
+        ] {
+            let handle = Rect::from_center_size(corner, (16.0, 16.0)).to_path(0.1);
+            self.fill_solid(&handle, transform, Color::WHITE);
+            self.stroke_path(&handle, transform, self.selection_color, &stroke);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drafftink_core::canvas::Canvas;
+    use drafftink_core::shapes::{
+        Arrow, Ellipse, FillPattern, Line, Rectangle, SerializableColor, Sloppiness, StrokeStyle,
+    };
+
+    /// Clean shapes, so the golden image doesn't depend on the hand-drawn jitter.
+    fn golden_document() -> CanvasDocument {
+        let mut document = CanvasDocument::new();
+        let mut rect = Rectangle::new(Point::new(0.0, 0.0), 120.0, 80.0);
+        rect.style.sloppiness = Sloppiness::Architect;
+        rect.style.fill_color = Some(SerializableColor::new(255, 200, 100, 255));
+        rect.style.fill_pattern = FillPattern::Solid;
+        rect.corner_radius = 12.0;
+        document.add_shape(Shape::Rectangle(rect));
+
+        let mut ellipse = Ellipse::new(Point::new(170.0, 40.0), 40.0, 30.0);
+        ellipse.style.sloppiness = Sloppiness::Architect;
+        ellipse.style.stroke_color = SerializableColor::new(30, 90, 200, 255);
+        ellipse.rotation = 0.5;
+        document.add_shape(Shape::Ellipse(ellipse));
+
+        let mut line = Line::new(Point::new(0.0, 110.0), Point::new(210.0, 110.0));
+        line.style.sloppiness = Sloppiness::Architect;
+        line.stroke_style = StrokeStyle::Dashed;
+        document.add_shape(Shape::Line(line));
+
+        let mut arrow = Arrow::new(Point::new(10.0, 140.0), Point::new(200.0, 130.0));
+        arrow.style.sloppiness = Sloppiness::Architect;
+        arrow.style.stroke_width = 3.0;
+        document.add_shape(Shape::Arrow(arrow));
+        document
+    }
+
+    fn pixel(image: &PngRenderResult, x: u32, y: u32) -> [u8; 4] {
+        let i = ((y * image.width + x) * 4) as usize;
+        image.rgba_data[i..i + 4].try_into().unwrap()
+    }
+
+    #[test]
+    fn test_render_document() {
+        let mut renderer = CpuRenderer::new();
+        assert!(
+            renderer
+                .render_document(&CanvasDocument::new(), 1.0)
+                .is_none()
+        );
+
+        let mut document = CanvasDocument::new();
+        let mut rect = Rectangle::new(Point::new(0.0, 0.0), 100.0, 50.0);
+        rect.style.sloppiness = Sloppiness::Architect;
+        rect.style.fill_color = Some(SerializableColor::new(255, 0, 0, 255));
+        rect.style.fill_pattern = FillPattern::Solid;
+        document.add_shape(Shape::Rectangle(rect));
+
+        let image = renderer.render_document(&document, 2.0).unwrap();
+        let expected = (document.bounds().unwrap().width() + 2.0 * EXPORT_PADDING) * 2.0;
+        assert_eq!(image.width as f64, expected.ceil());
+        assert_eq!(pixel(&image, 0, 0), [255, 255, 255, 255]);
+        let center = (image.width / 2, image.height / 2);
+        assert_eq!(pixel(&image, center.0, center.1), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_build_scene() {
+        let mut renderer = CpuRenderer::new();
+        let mut canvas = Canvas::new();
+        let mut rect = Rectangle::new(Point::new(100.0, 100.0), 200.0, 150.0);
+        rect.style.fill_color = Some(SerializableColor::new(0, 0, 0, 255));
+        rect.style.fill_pattern = FillPattern::Solid;
+        canvas.document.add_shape(Shape::Rectangle(rect));
+
+        let background = Color::from_rgba8(10, 20, 30, 255);
+        let ctx = RenderContext::new(&canvas, kurbo::Size::new(400.0, 300.0))
+            .with_background(background)
+            .with_grid(GridStyle::None);
+        renderer.build_scene(&ctx);
+        let image = renderer.output();
+        assert_eq!((image.width, image.height), (400, 300));
+        assert_eq!(pixel(&image, 5, 5), [10, 20, 30, 255]);
+        assert_eq!(pixel(&image, 200, 175), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn test_selection_and_handles() {
+        let mut canvas = Canvas::new();
+        let rect = Rectangle::new(Point::new(100.0, 100.0), 200.0, 150.0);
+        let id = rect.id();
+        canvas.document.add_shape(Shape::Rectangle(rect));
+
+        let mut renderer = CpuRenderer::new();
+        let ctx =
+            RenderContext::new(&canvas, kurbo::Size::new(400.0, 300.0)).with_grid(GridStyle::None);
+        renderer.build_scene(&ctx);
+        let plain = renderer.output();
+
+        canvas.select(id);
+        let ctx =
+            RenderContext::new(&canvas, kurbo::Size::new(400.0, 300.0)).with_grid(GridStyle::None);
+        renderer.build_scene(&ctx);
+        let selected = renderer.output();
+        // The corner handle is a white square with a blue border
+        let corner = canvas.camera.transform() * Point::new(100.0, 100.0);
+        let (x, y) = (corner.x as u32 - 4, corner.y as u32 - 4);
+        assert_eq!(pixel(&plain, x, y), [250, 250, 250, 255]);
+        assert_eq!(pixel(&selected, x, y), [255, 255, 255, 255]);
+
+        let exported = renderer
+            .render_selection(&canvas.document, &[id], 1.0)
+            .unwrap();
+        assert_eq!(exported.width, 240);
+    }
+
+    #[test]
+    fn test_diff_overlay() {
+        use crate::diff_overlay::DiffOverlay;
+        use drafftink_core::diff::DocumentDiff;
+
+        let clean_rect = |x: f64| {
+            let mut rect = Rectangle::new(Point::new(x, 100.0), 80.0, 80.0);
+            rect.style.sloppiness = Sloppiness::Architect;
+            rect.style.fill_color = Some(SerializableColor::new(0, 0, 0, 255));
+            rect.style.fill_pattern = FillPattern::Solid;
+            Shape::Rectangle(rect)
+        };
+        let (removed, added) = (clean_rect(40.0), clean_rect(240.0));
+        let removed_id = removed.id();
+        let mut old = CanvasDocument::new();
+        old.add_shape(removed);
+
+        let mut canvas = Canvas::new();
+        canvas.document.add_shape(added);
+        let diff = DocumentDiff::between(&old, &canvas.document);
+        let overlay = DiffOverlay::new(&old, &diff);
+        let ctx = RenderContext::new(&canvas, kurbo::Size::new(400.0, 300.0))
+            .with_grid(GridStyle::None)
+            .with_diff_overlay(Some(overlay));
+        let mut renderer = CpuRenderer::new();
+        renderer.build_scene(&ctx);
+        let image = renderer.output();
+
+        // The removed shape is a faint red ghost
+        let inside = canvas.camera.transform() * Point::new(80.0, 140.0);
+        let [r, g, _, _] = pixel(&image, inside.x as u32, inside.y as u32);
+        assert!(r > g && g > 150, "ghost pixel {:?}", (r, g));
+
+        // The added shape is outlined in green, just outside its bounds
+        let added = overlay.outlines(&canvas.document)[0];
+        assert_eq!(added.color, crate::diff_overlay::ADDED_COLOR);
+        let edge =
+            canvas.camera.transform() * Point::new(added.rect.x0 - 4.0 / canvas.camera.zoom, 140.0);
+        let [r, g, _, _] = pixel(&image, edge.x as u32, edge.y as u32);
+        assert!(g > r, "outline pixel {:?}", (r, g));
+
+        let outlines = overlay
+            .with_focus(Some(removed_id))
+            .outlines(&canvas.document);
+        assert_eq!(outlines.len(), 2);
+        assert!(outlines.iter().any(|o| o.focused && o.dashed));
+    }
+
+    #[test]
+    fn test_png_encoding() {
+        let mut renderer = CpuRenderer::new();
+        let document = golden_document();
+        let image = renderer.render_document(&document, 1.0).unwrap();
+        let png = image.to_png(None).unwrap();
+        let decoded = ::image::load_from_memory(&png).unwrap().to_rgba8();
+        assert_eq!(decoded.dimensions(), (image.width, image.height));
+        assert_eq!(decoded.into_raw(), image.rgba_data);
+        assert!(crate::extract_scene_from_png(&png).is_none());
+
+        let json = document.to_json().unwrap();
+        let png = image.to_png(Some(&json)).unwrap();
+        assert_eq!(crate::extract_scene_from_png(&png), Some(json));
+    }
+
+    /// Compare against `golden/shapes.png`. Set `DRAFFTINK_UPDATE_GOLDEN=1` to
+    /// rewrite the file after an intended change.
+    #[test]
+    fn test_golden_shapes() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/golden/shapes.png");
+        let mut renderer = CpuRenderer::new();
+        let image = renderer.render_document(&golden_document(), 2.0).unwrap();
+        if std::env::var_os("DRAFFTINK_UPDATE_GOLDEN").is_some() {
+            std::fs::write(path, image.to_png(None).unwrap()).unwrap();
+            return;
+        }
+
+        let golden = ::image::open(path).unwrap().to_rgba8();
+        assert_eq!(golden.dimensions(), (image.width, image.height));
+        let differing = golden
+            .as_raw()
+            .chunks(4)
+            .zip(image.rgba_data.chunks(4))
+            .filter(|(a, b)| a.iter().zip(b.iter()).any(|(a, b)| a.abs_diff(*b) > 2))
+            .count();
+        assert_eq!(differing, 0, "{differing} pixels differ from {path}");
+    }
+}
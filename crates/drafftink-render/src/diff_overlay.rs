@@ -0,0 +1,145 @@
+//! Highlights for comparing two versions of a document.
+//!
+//! The canvas shows the newer version. Shapes removed since the older one are drawn
+//! beneath it as faded red ghosts, and every change gets an outline: green for
+//! added, red for removed and amber for modified, with a dashed amber outline where
+//! a moved shape used to be.
+
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::diff::{ChangeKind, DocumentDiff};
+use drafftink_core::shapes::{FillPattern, Rectangle, SerializableColor, Shape, ShapeId};
+use kurbo::Rect;
+use peniko::Color;
+
+/// Outline color of added shapes.
+pub const ADDED_COLOR: Color = Color::from_rgba8(34, 197, 94, 255);
+/// Outline and ghost color of removed shapes.
+pub const REMOVED_COLOR: Color = Color::from_rgba8(239, 68, 68, 255);
+/// Outline color of modified shapes.
+pub const MODIFIED_COLOR: Color = Color::from_rgba8(245, 158, 11, 255);
+
+/// Opacity of the shapes drawn as ghosts.
+const GHOST_OPACITY: f64 = 0.45;
+
+/// The differences to highlight on a canvas holding the newer document.
+#[derive(Debug, Clone, Copy)]
+pub struct DiffOverlay<'a> {
+    /// The older document, where removed shapes and old positions come from.
+    pub old: &'a CanvasDocument,
+    /// Changes from `old` to the canvas document.
+    pub diff: &'a DocumentDiff,
+    /// A change to emphasize, such as the one picked from a list.
+    pub focused: Option<ShapeId>,
+}
+
+/// An outline around a changed shape, in world coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DiffOutline {
+    /// Bounds of the shape, before padding.
+    pub rect: Rect,
+    pub color: Color,
+    /// Dashed outlines mark where a shape was rather than where it is.
+    pub dashed: bool,
+    /// Whether this is the focused change.
+    pub focused: bool,
+}
+
+impl<'a> DiffOverlay<'a> {
+    pub fn new(old: &'a CanvasDocument, diff: &'a DocumentDiff) -> Self {
+        Self {
+            old,
+            diff,
+            focused: None,
+        }
+    }
+
+    /// Emphasize one change.
+    pub fn with_focus(mut self, focused: Option<ShapeId>) -> Self {
+        self.focused = focused;
+        self
+    }
+
+    /// Color used for a kind of change.
+    pub fn color(kind: ChangeKind) -> Color {
+        match kind {
+            ChangeKind::Added => ADDED_COLOR,
+            ChangeKind::Removed => REMOVED_COLOR,
+            ChangeKind::Modified(_) => MODIFIED_COLOR,
+        }
+    }
+
+    /// Removed shapes restyled as ghosts, in the older document's z-order. Draw
+    /// them before the canvas shapes.
+    pub fn ghosts(&self) -> impl Iterator<Item = Shape> + '_ {
+        self.diff
+            .removed
+            .iter()
+            .filter_map(|id| self.old.get_shape(*id))
+            .map(ghost)
+    }
+
+    /// Outlines of every change, given the newer document. Draw them after the
+    /// canvas shapes.
+    pub fn outlines(&self, new: &CanvasDocument) -> Vec<DiffOutline> {
+        let mut outlines = Vec::new();
+        for change in self.diff.changes() {
+            let focused = self.focused == Some(change.id);
+            let color = Self::color(change.kind);
+            let mut outline = |shape: Option<&Shape>, dashed: bool| {
+                if let Some(shape) = shape {
+                    outlines.push(DiffOutline {
+                        rect: shape.rotated_bounds(),
+                        color,
+                        dashed,
+                        focused,
+                    });
+                }
+            };
+            match change.kind {
+                ChangeKind::Added => outline(new.get_shape(change.id), false),
+                ChangeKind::Removed => outline(self.old.get_shape(change.id), true),
+                ChangeKind::Modified(modification) => {
+                    if modification.moved {
+                        outline(self.old.get_shape(change.id), true);
+                    }
+                    outline(new.get_shape(change.id), false);
+                }
+            }
+        }
+        outlines
+    }
+}
+
+/// A faded red copy of a shape. Images become a tinted rectangle over their bounds,
+/// since their pixels can't be faded through the style.
+fn ghost(shape: &Shape) -> Shape {
+    let tint = |alpha: u8| {
+        let rgba = REMOVED_COLOR.to_rgba8();
+        SerializableColor::new(rgba.r, rgba.g, rgba.b, alpha)
+    };
+    let mut ghost = match shape {
+        Shape::Image(image) => {
+            let mut rect = Rectangle::new(image.position, image.width, image.height);
+            rect.rotation = image.rotation;
+            rect.style.fill_color = Some(tint(60));
+            rect.style.fill_pattern = FillPattern::Solid;
+            return Shape::Rectangle(rect);
+        }
+        Shape::Group(group) => {
+            let mut group = group.clone();
+            group.children = group.children.iter().map(ghost).collect();
+            return Shape::Group(group);
+        }
+        _ => shape.clone(),
+    };
+    if let Shape::Text(text) = &mut ghost {
+        text.char_colors.clear();
+    }
+    let style = ghost.style_mut();
+    style.stroke_color = tint(255);
+    if style.fill_color.is_some() {
+        style.fill_color = Some(tint(60));
+    }
+    style.opacity *= GHOST_OPACITY;
+    ghost
+}
@@ -0,0 +1,113 @@
+//! Bundled fonts shared by the renderers and exporters.
+
+use drafftink_core::shapes::{FontFamily, FontWeight};
+use kurbo::{BezPath, Point};
+
+/// Embedded GelPen fonts (Regular, Light, Heavy variants)
+pub(crate) static GELPEN_REGULAR: &[u8] = include_bytes!("../assets/GelPen.ttf");
+pub(crate) static GELPEN_LIGHT: &[u8] = include_bytes!("../assets/GelPenLight.ttf");
+pub(crate) static GELPEN_HEAVY: &[u8] = include_bytes!("../assets/GelPenHeavy.ttf");
+/// Embedded VanillaExtract font
+pub(crate) static VANILLA_EXTRACT: &[u8] = include_bytes!("../assets/VanillaExtract.ttf");
+/// Embedded GelPenSerif fonts for handwritten style
+pub(crate) static GELPEN_SERIF_LIGHT: &[u8] = include_bytes!("../assets/GelPenSerifLight.ttf");
+pub(crate) static GELPEN_SERIF_MEDIUM: &[u8] = include_bytes!("../assets/GelPenSerifMedium.ttf");
+pub(crate) static GELPEN_SERIF_HEAVY: &[u8] = include_bytes!("../assets/GelPenSerifHeavy.ttf");
+/// Embedded XITS Math font for LaTeX rendering
+pub(crate) static XITS_MATH: &[u8] = include_bytes!("../assets/rex-xits.otf");
+/// Embedded Noto Sans for UI elements
+pub(crate) static NOTO_SANS: &[u8] = include_bytes!("../assets/NotoSans-Regular.ttf");
+pub(crate) static NOTO_SANS_BOLD: &[u8] = include_bytes!("../assets/NotoSans-Bold.ttf");
+pub(crate) static NOTO_SANS_ITALIC: &[u8] = include_bytes!("../assets/NotoSans-Italic.ttf");
+
+/// All bundled font files.
+static ALL_FONTS: [&[u8]; 11] = [
+    GELPEN_REGULAR,
+    GELPEN_LIGHT,
+    GELPEN_HEAVY,
+    VANILLA_EXTRACT,
+    GELPEN_SERIF_LIGHT,
+    GELPEN_SERIF_MEDIUM,
+    GELPEN_SERIF_HEAVY,
+    XITS_MATH,
+    NOTO_SANS,
+    NOTO_SANS_BOLD,
+    NOTO_SANS_ITALIC,
+];
+
+/// The bundled font whose data `data` is, if any (compared by address).
+pub(crate) fn bundled_data(data: &[u8]) -> Option<&'static [u8]> {
+    ALL_FONTS
+        .iter()
+        .copied()
+        .find(|font| std::ptr::eq(*font, data))
+}
+
+/// A concrete bundled font face.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct BundledFont {
+    /// Family name the face is registered under.
+    pub name: &'static str,
+    /// Whether the face is selected with a bold weight.
+    pub bold: bool,
+    /// Whether the face is selected with an italic style.
+    pub italic: bool,
+    /// Font file data.
+    pub data: &'static [u8],
+}
+
+/// Resolve a text family and weight to the bundled face used to draw it.
+pub(crate) fn bundled_font(family: &FontFamily, weight: &FontWeight) -> BundledFont {
+    let (name, bold, italic, data) = match (family, weight) {
+        (FontFamily::GelPen, FontWeight::Light) => ("GelPenLight", false, false, GELPEN_LIGHT),
+        (FontFamily::GelPen, FontWeight::Regular) => ("GelPen", false, false, GELPEN_REGULAR),
+        (FontFamily::GelPen, FontWeight::Heavy) => ("GelPenHeavy", false, false, GELPEN_HEAVY),
+        (FontFamily::NotoSans, FontWeight::Light) => ("Noto Sans", false, true, NOTO_SANS_ITALIC),
+        (FontFamily::NotoSans, FontWeight::Regular) => ("Noto Sans", false, false, NOTO_SANS),
+        (FontFamily::NotoSans, FontWeight::Heavy) => ("Noto Sans", true, false, NOTO_SANS_BOLD),
+        (FontFamily::GelPenSerif, FontWeight::Light) => {
+            ("GelPenSerifLight", false, false, GELPEN_SERIF_LIGHT)
+        }
+        (FontFamily::GelPenSerif, FontWeight::Regular) => {
+            ("GelPenSerif", false, false, GELPEN_SERIF_MEDIUM)
+        }
+        (FontFamily::GelPenSerif, FontWeight::Heavy) => {
+            ("GelPenSerifHeavy", false, false, GELPEN_SERIF_HEAVY)
+        }
+        (FontFamily::VanillaExtract, _) => ("Vanilla Extract", false, false, VANILLA_EXTRACT),
+    };
+    BundledFont {
+        name,
+        bold,
+        italic,
+        data,
+    }
+}
+
+/// Collects a glyph outline from ttf-parser into a path, in font units (y up).
+pub(crate) struct GlyphPath(pub BezPath);
+
+impl ttf_parser::OutlineBuilder for GlyphPath {
+    fn move_to(&mut self, x: f32, y: f32) {
+        self.0.move_to(Point::new(x as f64, y as f64));
+    }
+    fn line_to(&mut self, x: f32, y: f32) {
+        self.0.line_to(Point::new(x as f64, y as f64));
+    }
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        self.0.quad_to(
+            Point::new(x1 as f64, y1 as f64),
+            Point::new(x as f64, y as f64),
+        );
+    }
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        self.0.curve_to(
+            Point::new(x1 as f64, y1 as f64),
+            Point::new(x2 as f64, y2 as f64),
+            Point::new(x as f64, y as f64),
+        );
+    }
+    fn close(&mut self) {
+        self.0.close_path();
+    }
+}
@@ -1,21 +1,43 @@
 //! DrafftInk Render Library
 //!
 //! Renderer abstraction and implementations for DrafftInk.
-//! The default implementation uses Vello for GPU-accelerated rendering.
+//! The default implementation uses Vello for GPU-accelerated rendering; the
+//! `cpu-renderer` feature adds a CPU rasterizer for headless rendering.
 
+mod diff_overlay;
+mod fonts;
+pub mod pdf;
 mod renderer;
+mod sketch;
+pub mod svg;
 pub mod text_editor;
+mod vector;
 
+#[cfg(feature = "cpu-renderer")]
+mod cpu_impl;
+#[cfg(feature = "cpu-renderer")]
+mod raster;
 #[cfg(feature = "vello-renderer")]
 mod vello_impl;
 
-#[cfg(feature = "vello-renderer")]
 pub mod rex_backend;
 
+pub use diff_overlay::{ADDED_COLOR, DiffOutline, DiffOverlay, MODIFIED_COLOR, REMOVED_COLOR};
+pub use pdf::{
+    PdfExportOptions, PdfPageSize, export_document_pdf, export_frames_pdf, export_pages_pdf,
+    export_selection_pdf,
+};
 pub use renderer::{
-    AngleSnapInfo, GridStyle, RenderContext, Renderer, RendererError, RotationInfo,
+    AngleSnapInfo, GridStyle, PNG_SCENE_KEYWORD, PngRenderResult, RenderContext, Renderer,
+    RendererError, RotationInfo, extract_scene_from_png,
+};
+pub use svg::{
+    SvgExportOptions, SvgTextMode, export_document_svg, export_selection_svg,
+    extract_scene_from_svg,
 };
 pub use text_editor::{TextEditResult, TextEditState, TextKey, TextModifiers};
 
+#[cfg(feature = "cpu-renderer")]
+pub use cpu_impl::CpuRenderer;
 #[cfg(feature = "vello-renderer")]
-pub use vello_impl::{PngRenderResult, VelloRenderer};
+pub use vello_impl::VelloRenderer;
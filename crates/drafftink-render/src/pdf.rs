@@ -0,0 +1,873 @@
+//! PDF export of documents, selections and page sets.
+//!
+//! Output is vector: shapes are drawn with the same geometry as the SVG exporter,
+//! text uses the bundled fonts embedded as CID fonts (with a ToUnicode map, so text
+//! can be selected and searched), and math glyphs use the embedded XITS Math font.
+
+use crate::fonts::bundled_font;
+use crate::rex_backend::{MathGlyph, MathSink};
+use crate::vector::{
+    VectorSink, color_runs, content_bounds, draw_shape, draw_svg_image, layout_lines, num,
+    selected_shapes,
+};
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::shapes::{Image, ImageFormat, Shape, ShapeId, ShapeTrait, Text};
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape as KurboShape, Size};
+use miniz_oxide::deflate::compress_to_vec_zlib;
+use peniko::Color;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write as _;
+
+/// Compression level for PDF streams.
+const COMPRESSION_LEVEL: u8 = 6;
+
+/// Page size of exported PDFs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PdfPageSize {
+    /// ISO A4 (210 × 297 mm).
+    A4,
+    /// US Letter (8.5 × 11 in).
+    Letter,
+    /// Each page is sized to its content plus margins.
+    #[default]
+    FitToContent,
+}
+
+impl PdfPageSize {
+    /// All presets, in menu order.
+    pub const ALL: [PdfPageSize; 3] = [
+        PdfPageSize::A4,
+        PdfPageSize::Letter,
+        PdfPageSize::FitToContent,
+    ];
+
+    /// Display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            PdfPageSize::A4 => "A4",
+            PdfPageSize::Letter => "Letter",
+            PdfPageSize::FitToContent => "Fit",
+        }
+    }
+
+    /// Portrait page size in points, or `None` for fit-to-content.
+    pub fn dimensions(self) -> Option<Size> {
+        match self {
+            PdfPageSize::A4 => Some(Size::new(595.28, 841.89)),
+            PdfPageSize::Letter => Some(Size::new(612.0, 792.0)),
+            PdfPageSize::FitToContent => None,
+        }
+    }
+}
+
+/// Options for PDF export.
+#[derive(Debug, Clone)]
+pub struct PdfExportOptions {
+    /// Page size preset. Fixed sizes switch to landscape for wide content.
+    pub page_size: PdfPageSize,
+    /// Margin around the content, in points.
+    pub margin: f64,
+    /// Page background, or `None` to leave pages unpainted.
+    pub background: Option<Color>,
+}
+
+impl Default for PdfExportOptions {
+    fn default() -> Self {
+        Self {
+            page_size: PdfPageSize::default(),
+            margin: 36.0,
+            background: Some(Color::WHITE),
+        }
+    }
+}
+
+/// Export the whole document as a single-page PDF. Returns `None` if the document is empty.
+pub fn export_document_pdf(
+    document: &CanvasDocument,
+    options: &PdfExportOptions,
+) -> Option<Vec<u8>> {
+    let shapes: Vec<&Shape> = document.shapes_ordered().collect();
+    export_pages(&document.name, &[shapes], options)
+}
+
+/// Export the selected shapes as a single-page PDF. Returns `None` if none of the shapes exist.
+pub fn export_selection_pdf(
+    document: &CanvasDocument,
+    selection: &[ShapeId],
+    options: &PdfExportOptions,
+) -> Option<Vec<u8>> {
+    let shapes = selected_shapes(document, selection);
+    export_pages(&document.name, &[shapes], options)
+}
+
+/// Export one page per set of shapes (e.g. one per frame). Empty sets are skipped;
+/// returns `None` if no page has content.
+pub fn export_pages_pdf(
+    document: &CanvasDocument,
+    pages: &[Vec<ShapeId>],
+    options: &PdfExportOptions,
+) -> Option<Vec<u8>> {
+    let pages: Vec<Vec<&Shape>> = pages
+        .iter()
+        .map(|ids| selected_shapes(document, ids))
+        .collect();
+    export_pages(&document.name, &pages, options)
+}
+
+/// Export one page per frame, in z-order, or the whole document as a single page if it
+/// has no frames. Shapes outside the frames are left out.
+pub fn export_frames_pdf(document: &CanvasDocument, options: &PdfExportOptions) -> Option<Vec<u8>> {
+    let frames: Vec<Vec<ShapeId>> = document
+        .shapes_ordered()
+        .filter(|shape| shape.as_group().is_some_and(|group| group.frame.is_some()))
+        .map(|shape| vec![shape.id()])
+        .collect();
+    if frames.is_empty() {
+        export_document_pdf(document, options)
+    } else {
+        export_pages_pdf(document, &frames, options)
+    }
+}
+
+fn export_pages(title: &str, pages: &[Vec<&Shape>], options: &PdfExportOptions) -> Option<Vec<u8>> {
+    let mut resources = Resources::default();
+    let mut rendered = Vec::new();
+    for shapes in pages {
+        let Some(bounds) = content_bounds(shapes) else {
+            continue;
+        };
+        let (page_size, transform) = page_layout(bounds, options);
+
+        let mut page = PageWriter {
+            content: String::new(),
+            resources: &mut resources,
+        };
+        if let Some(color) = options.background {
+            page.fill(
+                &Rect::from_origin_size(Point::ZERO, page_size).to_path(0.1),
+                color,
+            );
+        }
+        // Document coordinates (y down) onto the page (y up)
+        page.begin_group(transform);
+        for shape in shapes {
+            draw_shape(&mut page, shape);
+        }
+        page.end_group();
+        rendered.push((page_size, page.content));
+    }
+    if rendered.is_empty() {
+        return None;
+    }
+    Some(write_file(title, &rendered, &resources))
+}
+
+/// Page size and the transform from document coordinates to page space for `bounds`.
+fn page_layout(bounds: Rect, options: &PdfExportOptions) -> (Size, Affine) {
+    let margin = options.margin.max(0.0);
+    let content = bounds.size();
+    let (page, scale) = match options.page_size.dimensions() {
+        None => (
+            Size::new(content.width + margin * 2.0, content.height + margin * 2.0),
+            1.0,
+        ),
+        Some(portrait) => {
+            let page = if content.width > content.height {
+                Size::new(portrait.height, portrait.width)
+            } else {
+                portrait
+            };
+            // Shrink to fit the printable area, never enlarge
+            let available = Size::new(
+                (page.width - margin * 2.0).max(1.0),
+                (page.height - margin * 2.0).max(1.0),
+            );
+            let scale = (available.width / content.width.max(f64::EPSILON))
+                .min(available.height / content.height.max(f64::EPSILON))
+                .min(1.0);
+            (page, scale)
+        }
+    };
+    // Center the content on the page
+    let left = (page.width - content.width * scale) / 2.0;
+    let top = (page.height - content.height * scale) / 2.0;
+    let transform = Affine::new([
+        scale,
+        0.0,
+        0.0,
+        -scale,
+        left - bounds.x0 * scale,
+        page.height - top + bounds.y0 * scale,
+    ]);
+    (page, transform)
+}
+
+/// A font embedded in the file, with the glyphs used from it.
+struct PdfFont {
+    data: &'static [u8],
+    /// Used glyph IDs and the text they represent (for ToUnicode).
+    glyphs: BTreeMap<u16, Option<char>>,
+}
+
+/// An image XObject: 8-bit RGB samples with an optional alpha mask.
+struct PdfImage {
+    width: u32,
+    height: u32,
+    rgb: Vec<u8>,
+    alpha: Option<Vec<u8>>,
+}
+
+/// Resources shared by all pages.
+#[derive(Default)]
+struct Resources {
+    fonts: Vec<PdfFont>,
+    images: Vec<PdfImage>,
+    /// Alpha values used by fills and strokes, one graphics state each.
+    alphas: BTreeSet<u8>,
+}
+
+impl Resources {
+    /// Index of the font with the given data, registering it on first use.
+    fn font(&mut self, data: &'static [u8]) -> usize {
+        if let Some(index) = self.fonts.iter().position(|f| std::ptr::eq(f.data, data)) {
+            return index;
+        }
+        self.fonts.push(PdfFont {
+            data,
+            glyphs: BTreeMap::new(),
+        });
+        self.fonts.len() - 1
+    }
+}
+
+/// Writes the content stream of one page.
+struct PageWriter<'a> {
+    content: String,
+    resources: &'a mut Resources,
+}
+
+impl PageWriter<'_> {
+    fn path(&mut self, path: &BezPath) {
+        let mut last = Point::ZERO;
+        for el in path.elements() {
+            match *el {
+                PathEl::MoveTo(p) => {
+                    let _ = writeln!(self.content, "{} {} m", num(p.x), num(p.y));
+                    last = p;
+                }
+                PathEl::LineTo(p) => {
+                    let _ = writeln!(self.content, "{} {} l", num(p.x), num(p.y));
+                    last = p;
+                }
+                PathEl::QuadTo(p1, p2) => {
+                    // PDF has no quadratic curves; raise to cubic
+                    let c1 = last + (p1 - last) * (2.0 / 3.0);
+                    let c2 = p2 + (p1 - p2) * (2.0 / 3.0);
+                    self.curve(c1, c2, p2);
+                    last = p2;
+                }
+                PathEl::CurveTo(p1, p2, p3) => {
+                    self.curve(p1, p2, p3);
+                    last = p3;
+                }
+                PathEl::ClosePath => self.content.push_str("h\n"),
+            }
+        }
+    }
+
+    fn curve(&mut self, p1: Point, p2: Point, p3: Point) {
+        let _ = writeln!(
+            self.content,
+            "{} {} {} {} {} {} c",
+            num(p1.x),
+            num(p1.y),
+            num(p2.x),
+            num(p2.y),
+            num(p3.x),
+            num(p3.y)
+        );
+    }
+
+    /// Set the fill (`rg`) or stroke (`RG`) color, with a graphics state for alpha.
+    fn color(&mut self, operator: &str, color: Color) {
+        let rgba = color.to_rgba8();
+        if rgba.a < 255 {
+            self.resources.alphas.insert(rgba.a);
+            let _ = writeln!(self.content, "/A{} gs", rgba.a);
+        }
+        let _ = writeln!(
+            self.content,
+            "{} {} {} {operator}",
+            num(rgba.r as f64 / 255.0),
+            num(rgba.g as f64 / 255.0),
+            num(rgba.b as f64 / 255.0)
+        );
+    }
+
+    /// Show glyphs of a font with a text matrix, in the current fill color.
+    fn glyphs(&mut self, font: usize, size: f64, matrix: Affine, glyphs: &[u16]) {
+        let [a, b, c, d, e, f] = matrix.as_coeffs();
+        let _ = write!(
+            self.content,
+            "BT /F{font} {} Tf {} {} {} {} {} {} Tm <",
+            num(size),
+            num(a),
+            num(b),
+            num(c),
+            num(d),
+            num(e),
+            num(f)
+        );
+        for glyph in glyphs {
+            let _ = write!(self.content, "{glyph:04X}");
+        }
+        self.content.push_str("> Tj ET\n");
+    }
+}
+
+impl MathSink for PageWriter<'_> {
+    fn fill_path(&mut self, transform: Affine, color: Color, path: &BezPath) {
+        self.fill(&(transform * path.clone()), color);
+    }
+
+    fn glyph(&mut self, glyph: MathGlyph<'_>) {
+        // Only bundled fonts can be embedded; anything else is drawn as a path
+        let Some(data) = crate::fonts::bundled_data(glyph.font_data) else {
+            self.fill_path(glyph.outline_transform, glyph.color, glyph.outline);
+            return;
+        };
+        let font = self.resources.font(data);
+        self.resources.fonts[font]
+            .glyphs
+            .insert(glyph.glyph_id, glyph.codepoint);
+        self.content.push_str("q\n");
+        self.color("rg", glyph.color);
+        self.glyphs(font, 1.0, glyph.em_transform, &[glyph.glyph_id]);
+        self.content.push_str("Q\n");
+    }
+}
+
+impl VectorSink for PageWriter<'_> {
+    fn begin_group(&mut self, transform: Affine) {
+        let [a, b, c, d, e, f] = transform.as_coeffs();
+        let _ = writeln!(
+            self.content,
+            "q {} {} {} {} {} {} cm",
+            num(a),
+            num(b),
+            num(c),
+            num(d),
+            num(e),
+            num(f)
+        );
+    }
+
+    fn end_group(&mut self) {
+        self.content.push_str("Q\n");
+    }
+
+    fn begin_clip(&mut self, path: &BezPath) {
+        self.content.push_str("q\n");
+        self.path(path);
+        self.content.push_str("W n\n");
+    }
+
+    fn end_clip(&mut self) {
+        self.content.push_str("Q\n");
+    }
+
+    fn fill(&mut self, path: &BezPath, color: Color) {
+        self.content.push_str("q\n");
+        self.color("rg", color);
+        self.path(path);
+        self.content.push_str("f\nQ\n");
+    }
+
+    fn fill_even_odd(&mut self, path: &BezPath, color: Color) {
+        self.content.push_str("q\n");
+        self.color("rg", color);
+        self.path(path);
+        self.content.push_str("f*\nQ\n");
+    }
+
+    fn stroke(&mut self, path: &BezPath, color: Color, width: f64, dashes: Option<[f64; 2]>) {
+        self.content.push_str("q\n");
+        self.color("RG", color);
+        let _ = writeln!(self.content, "{} w 1 J 1 j", num(width));
+        if let Some([dash, gap]) = dashes {
+            let _ = writeln!(self.content, "[{} {}] 0 d", num(dash), num(gap));
+        }
+        self.path(path);
+        self.content.push_str("S\nQ\n");
+    }
+
+    fn text(&mut self, text: &Text) {
+        if text.content.is_empty() {
+            return;
+        }
+        let data = bundled_font(&text.font_family, &text.font_weight).data;
+        let Ok(face) = ttf_parser::Face::parse(data, 0) else {
+            return;
+        };
+        let font = self.resources.font(data);
+
+        for line in layout_lines(&face, text) {
+            // Flip the text back upright in the y-down document space
+            let mut matrix = Affine::new([1.0, 0.0, 0.0, -1.0, text.position.x, line.baseline]);
+            let scale = text.font_size / face.units_per_em() as f64;
+            for (run, color) in color_runs(text, &line) {
+                let mut glyphs = Vec::new();
+                let mut advance = 0.0;
+                for ch in run.chars() {
+                    let glyph = face.glyph_index(ch).map_or(0, |g| g.0);
+                    self.resources.fonts[font].glyphs.insert(glyph, Some(ch));
+                    advance += face
+                        .glyph_hor_advance(ttf_parser::GlyphId(glyph))
+                        .unwrap_or(0) as f64
+                        * scale;
+                    glyphs.push(glyph);
+                }
+                self.content.push_str("q\n");
+                self.color("rg", color);
+                self.glyphs(font, text.font_size, matrix, &glyphs);
+                self.content.push_str("Q\n");
+                matrix = matrix.then_translate(kurbo::Vec2::new(advance, 0.0));
+            }
+        }
+    }
+
+    fn image(&mut self, image: &Image) {
+        if image.format == ImageFormat::Svg {
+            draw_svg_image(self, image);
+            return;
+        }
+        let Ok(decoded) = ::image::load_from_memory(image.data()) else {
+            return;
+        };
+        let rgba = decoded.to_rgba8();
+        let (width, height) = rgba.dimensions();
+        let mut rgb = Vec::with_capacity((width * height * 3) as usize);
+        let mut alpha = Vec::with_capacity((width * height) as usize);
+        for pixel in rgba.pixels() {
+            rgb.extend_from_slice(&pixel.0[..3]);
+            alpha.push(pixel.0[3]);
+        }
+        let alpha = alpha.iter().any(|&a| a < 255).then_some(alpha);
+        self.resources.images.push(PdfImage {
+            width,
+            height,
+            rgb,
+            alpha,
+        });
+
+        // The unit square, flipped so the first row is the top of the image
+        let bounds = image.bounds();
+        let _ = writeln!(
+            self.content,
+            "q {} 0 0 {} {} {} cm /Im{} Do Q",
+            num(bounds.width()),
+            num(-bounds.height()),
+            num(bounds.x0),
+            num(bounds.y1),
+            self.resources.images.len() - 1
+        );
+    }
+}
+
+/// Serializes numbered objects and the cross-reference table.
+struct PdfFile {
+    buf: Vec<u8>,
+    /// Byte offset of each object, indexed by object number - 1.
+    offsets: Vec<usize>,
+}
+
+impl PdfFile {
+    fn new() -> Self {
+        let mut buf = b"%PDF-1.7\n".to_vec();
+        // Binary marker so transfer tools treat the file as binary
+        buf.extend_from_slice(b"%\xE2\xE3\xCF\xD3\n");
+        Self {
+            buf,
+            offsets: Vec::new(),
+        }
+    }
+
+    /// Reserve an object number; the object is written later with `object` or `stream`.
+    fn reserve(&mut self) -> usize {
+        self.offsets.push(0);
+        self.offsets.len()
+    }
+
+    fn object(&mut self, id: usize, body: &str) {
+        self.offsets[id - 1] = self.buf.len();
+        self.buf
+            .extend_from_slice(format!("{id} 0 obj\n{body}\nendobj\n").as_bytes());
+    }
+
+    /// Write a Flate-compressed stream object. `dict` holds extra dictionary entries.
+    fn stream(&mut self, id: usize, dict: &str, data: &[u8]) {
+        let compressed = compress_to_vec_zlib(data, COMPRESSION_LEVEL);
+        self.offsets[id - 1] = self.buf.len();
+        self.buf.extend_from_slice(
+            format!(
+                "{id} 0 obj\n<< {dict}{}/Filter /FlateDecode /Length {} >>\nstream\n",
+                if dict.is_empty() { "" } else { " " },
+                compressed.len()
+            )
+            .as_bytes(),
+        );
+        self.buf.extend_from_slice(&compressed);
+        self.buf.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self, root: usize, info: usize) -> Vec<u8> {
+        let xref = self.buf.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            let _ = writeln!(table, "{offset:010} 00000 n ");
+        }
+        let _ = write!(
+            table,
+            "trailer\n<< /Size {} /Root {root} 0 R /Info {info} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.offsets.len() + 1
+        );
+        self.buf.extend_from_slice(table.as_bytes());
+        self.buf
+    }
+}
+
+fn write_file(title: &str, pages: &[(Size, String)], resources: &Resources) -> Vec<u8> {
+    let mut file = PdfFile::new();
+    let catalog = file.reserve();
+    let page_tree = file.reserve();
+    let info = file.reserve();
+    let resources_id = file.reserve();
+
+    file.object(
+        catalog,
+        &format!("<< /Type /Catalog /Pages {page_tree} 0 R >>"),
+    );
+    file.object(
+        info,
+        &format!("<< /Title {} /Producer (DrafftInk) >>", text_string(title)),
+    );
+
+    // Shared resources
+    let mut fonts = String::new();
+    for (index, font) in resources.fonts.iter().enumerate() {
+        let id = write_font(&mut file, index, font);
+        let _ = write!(fonts, "/F{index} {id} 0 R ");
+    }
+    let mut images = String::new();
+    for (index, image) in resources.images.iter().enumerate() {
+        let id = write_image(&mut file, image);
+        let _ = write!(images, "/Im{index} {id} 0 R ");
+    }
+    let mut states = String::new();
+    for alpha in &resources.alphas {
+        let value = num(*alpha as f64 / 255.0);
+        let _ = write!(
+            states,
+            "/A{alpha} << /Type /ExtGState /ca {value} /CA {value} >> "
+        );
+    }
+    file.object(
+        resources_id,
+        &format!("<< /Font << {fonts}>> /XObject << {images}>> /ExtGState << {states}>> >>"),
+    );
+
+    // Pages
+    let mut kids = String::new();
+    for (size, content) in pages {
+        let page = file.reserve();
+        let contents = file.reserve();
+        file.stream(contents, "", content.as_bytes());
+        file.object(
+            page,
+            &format!(
+                "<< /Type /Page /Parent {page_tree} 0 R /MediaBox [0 0 {} {}] /Resources {resources_id} 0 R /Contents {contents} 0 R >>",
+                num(size.width),
+                num(size.height)
+            ),
+        );
+        let _ = write!(kids, "{page} 0 R ");
+    }
+    file.object(
+        page_tree,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.trim_end(),
+            pages.len()
+        ),
+    );
+
+    file.finish(catalog, info)
+}
+
+/// Write a font as a Type 0 font with Identity-H encoding (glyph IDs as codes).
+/// Returns the object number of the font dictionary.
+fn write_font(file: &mut PdfFile, index: usize, font: &PdfFont) -> usize {
+    let type0 = file.reserve();
+    let cid_font = file.reserve();
+    let descriptor = file.reserve();
+    let font_file = file.reserve();
+    let to_unicode = file.reserve();
+
+    let face = ttf_parser::Face::parse(font.data, 0).ok();
+    let units_per_em = face.as_ref().map_or(1000.0, |f| f.units_per_em() as f64);
+    // Font metrics in PDF glyph space (1/1000 em)
+    let em = |value: f64| (value * 1000.0 / units_per_em).round();
+    let is_cff = face.as_ref().is_some_and(|f| f.tables().cff.is_some());
+    let base_name = face
+        .as_ref()
+        .and_then(postscript_name)
+        .unwrap_or_else(|| format!("DrafftInkFont{index}"));
+
+    let mut widths = String::new();
+    for &glyph in font.glyphs.keys() {
+        let advance = face
+            .as_ref()
+            .and_then(|f| f.glyph_hor_advance(ttf_parser::GlyphId(glyph)))
+            .unwrap_or(0);
+        let _ = write!(widths, "{glyph} [{}] ", em(advance as f64));
+    }
+
+    if is_cff {
+        file.stream(font_file, "/Subtype /OpenType", font.data);
+    } else {
+        file.stream(
+            font_file,
+            &format!("/Length1 {}", font.data.len()),
+            font.data,
+        );
+    }
+
+    let (bbox, ascent, descent, cap_height, italic_angle) = match &face {
+        Some(face) => {
+            let bbox = face.global_bounding_box();
+            (
+                format!(
+                    "[{} {} {} {}]",
+                    em(bbox.x_min as f64),
+                    em(bbox.y_min as f64),
+                    em(bbox.x_max as f64),
+                    em(bbox.y_max as f64)
+                ),
+                em(face.ascender() as f64),
+                em(face.descender() as f64),
+                em(face.capital_height().unwrap_or(face.ascender()) as f64),
+                face.italic_angle() as f64,
+            )
+        }
+        None => ("[0 0 1000 1000]".to_string(), 800.0, -200.0, 700.0, 0.0),
+    };
+    file.object(
+        descriptor,
+        &format!(
+            "<< /Type /FontDescriptor /FontName /{base_name} /Flags 4 /FontBBox {bbox} /ItalicAngle {} /Ascent {ascent} /Descent {descent} /CapHeight {cap_height} /StemV 80 /{} {font_file} 0 R >>",
+            num(italic_angle),
+            if is_cff { "FontFile3" } else { "FontFile2" }
+        ),
+    );
+    file.object(
+        cid_font,
+        &format!(
+            "<< /Type /Font /Subtype /{} /BaseFont /{base_name} /CIDSystemInfo << /Registry (Adobe) /Ordering (Identity) /Supplement 0 >> /FontDescriptor {descriptor} 0 R /W [{}]{} >>",
+            if is_cff { "CIDFontType0" } else { "CIDFontType2" },
+            widths.trim_end(),
+            if is_cff { "" } else { " /CIDToGIDMap /Identity" }
+        ),
+    );
+    file.stream(to_unicode, "", to_unicode_cmap(&font.glyphs).as_bytes());
+    file.object(
+        type0,
+        &format!(
+            "<< /Type /Font /Subtype /Type0 /BaseFont /{base_name} /Encoding /Identity-H /DescendantFonts [{cid_font} 0 R] /ToUnicode {to_unicode} 0 R >>"
+        ),
+    );
+    type0
+}
+
+/// PostScript name of a face, restricted to characters valid in a PDF name.
+fn postscript_name(face: &ttf_parser::Face) -> Option<String> {
+    let name = face
+        .names()
+        .into_iter()
+        .find(|n| n.name_id == ttf_parser::name_id::POST_SCRIPT_NAME && n.is_unicode())
+        .and_then(|n| n.to_string())?;
+    let name: String = name
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '+'))
+        .collect();
+    (!name.is_empty()).then_some(name)
+}
+
+/// CMap from glyph IDs back to Unicode, for text extraction.
+fn to_unicode_cmap(glyphs: &BTreeMap<u16, Option<char>>) -> String {
+    let mapped: Vec<(u16, char)> = glyphs
+        .iter()
+        .filter_map(|(&glyph, &ch)| Some((glyph, ch?)))
+        .collect();
+    let mut cmap = String::from(
+        "/CIDInit /ProcSet findresource begin\n12 dict begin\nbegincmap\n\
+         /CIDSystemInfo << /Registry (Adobe) /Ordering (UCS) /Supplement 0 >> def\n\
+         /CMapName /Adobe-Identity-UCS def\n/CMapType 2 def\n\
+         1 begincodespacerange\n<0000> <FFFF>\nendcodespacerange\n",
+    );
+    // At most 100 entries per block
+    for chunk in mapped.chunks(100) {
+        let _ = writeln!(cmap, "{} beginbfchar", chunk.len());
+        for &(glyph, ch) in chunk {
+            let mut units = [0u16; 2];
+            let hex: String = ch
+                .encode_utf16(&mut units)
+                .iter()
+                .map(|u| format!("{u:04X}"))
+                .collect();
+            let _ = writeln!(cmap, "<{glyph:04X}> <{hex}>");
+        }
+        cmap.push_str("endbfchar\n");
+    }
+    cmap.push_str("endcmap\nCMapName currentdict /CMap defineresource pop\nend\nend\n");
+    cmap
+}
+
+/// Write an image XObject (and its alpha mask). Returns the image's object number.
+fn write_image(file: &mut PdfFile, image: &PdfImage) -> usize {
+    let id = file.reserve();
+    let mask = image.alpha.as_ref().map(|alpha| {
+        let mask = file.reserve();
+        file.stream(
+            mask,
+            &format!(
+                "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8",
+                image.width, image.height
+            ),
+            alpha,
+        );
+        mask
+    });
+    file.stream(
+        id,
+        &format!(
+            "/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8{}",
+            image.width,
+            image.height,
+            mask.map(|m| format!(" /SMask {m} 0 R")).unwrap_or_default()
+        ),
+        &image.rgb,
+    );
+    id
+}
+
+/// A PDF text string: literal for ASCII, UTF-16BE with BOM otherwise.
+fn text_string(text: &str) -> String {
+    if text.is_ascii() {
+        let escaped = text
+            .replace('\\', "\\\\")
+            .replace('(', "\\(")
+            .replace(')', "\\)");
+        format!("({escaped})")
+    } else {
+        let hex: String = text.encode_utf16().map(|u| format!("{u:04X}")).collect();
+        format!("<FEFF{hex}>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drafftink_core::shapes::Rectangle;
+
+    fn document() -> CanvasDocument {
+        let mut doc = CanvasDocument::new();
+        let rect = Rectangle::new(Point::new(0.0, 0.0), 200.0, 100.0);
+        doc.add_shape(Shape::Rectangle(rect));
+        let text = Text::new(Point::new(10.0, 150.0), "Hi (there)".to_string());
+        doc.add_shape(Shape::Text(text));
+        doc
+    }
+
+    fn contains(haystack: &[u8], needle: &str) -> bool {
+        haystack
+            .windows(needle.len())
+            .any(|w| w == needle.as_bytes())
+    }
+
+    #[test]
+    fn test_document_structure() {
+        let pdf = export_document_pdf(&document(), &PdfExportOptions::default()).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.7"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        assert!(contains(&pdf, "/Count 1 >>"));
+        // Text font is embedded once, with a ToUnicode map
+        assert!(contains(&pdf, "/Subtype /Type0"));
+        assert!(contains(&pdf, "/FontFile2"));
+        assert!(contains(&pdf, "/ToUnicode"));
+
+        // The xref offsets point at the objects
+        let text = String::from_utf8_lossy(&pdf);
+        let xref = text.rfind("xref\n").unwrap();
+        for (i, line) in text[xref..].lines().skip(3).take(4).enumerate() {
+            let offset: usize = line[..10].parse().unwrap();
+            assert!(text[offset..].starts_with(&format!("{} 0 obj", i + 1)));
+        }
+    }
+
+    #[test]
+    fn test_page_presets() {
+        let doc = document();
+        let a4 = PdfExportOptions {
+            page_size: PdfPageSize::A4,
+            ..Default::default()
+        };
+        let pdf = export_document_pdf(&doc, &a4).unwrap();
+        // Wide content switches to landscape
+        assert!(contains(&pdf, "/MediaBox [0 0 841.89 595.28]"));
+
+        let fit = PdfExportOptions {
+            margin: 10.0,
+            ..Default::default()
+        };
+        let rect_id = doc.z_order[0];
+        let pdf = export_selection_pdf(&doc, &[rect_id], &fit).unwrap();
+        assert!(contains(&pdf, "/MediaBox [0 0 220 120]"));
+        assert!(!contains(&pdf, "/Type0"));
+    }
+
+    #[test]
+    fn test_multiple_pages() {
+        let doc = document();
+        let pages = vec![vec![doc.z_order[0]], vec![], vec![doc.z_order[1]]];
+        let pdf = export_pages_pdf(&doc, &pages, &PdfExportOptions::default()).unwrap();
+        assert!(contains(&pdf, "/Count 2"));
+        assert!(export_pages_pdf(&doc, &[vec![]], &PdfExportOptions::default()).is_none());
+    }
+
+    #[test]
+    fn test_frame_pages() {
+        use drafftink_core::shapes::Group;
+
+        let mut doc = document();
+        let options = PdfExportOptions::default();
+        assert!(contains(
+            &export_frames_pdf(&doc, &options).unwrap(),
+            "/Count 1"
+        ));
+
+        for x in [0.0, 500.0] {
+            let rect = Rectangle::new(Point::new(x, 300.0), 100.0, 100.0);
+            let frame = Group::new(vec![Shape::Rectangle(rect)]).with_frame("Slide");
+            doc.add_shape(Shape::Group(frame));
+        }
+        let pdf = export_frames_pdf(&doc, &options).unwrap();
+        assert!(contains(&pdf, "/Count 2"));
+        assert!(contains(&pdf, "/MediaBox [0 0 172 172]"));
+    }
+
+    #[test]
+    fn test_text_string() {
+        assert_eq!(text_string("a (b)"), "(a \\(b\\))");
+        assert_eq!(text_string("é"), "<FEFF00E9>");
+    }
+}
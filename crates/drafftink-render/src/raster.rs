@@ -0,0 +1,575 @@
+//! Scanline rasterizer for the CPU renderer.
+//!
+//! Paths are flattened with kurbo and scan-converted by accumulating the signed
+//! area each edge covers in every pixel, which gives exact analytic coverage
+//! for edges that don't overlap within a pixel. Pixels are premultiplied RGBA
+//! blended in sRGB space, like Vello.
+
+use kurbo::{Affine, BezPath, PathEl, Point, Rect, Stroke, StrokeOpts};
+use peniko::{Color, Fill};
+
+/// Flattening tolerance in device pixels.
+const TOLERANCE: f64 = 0.05;
+
+/// A premultiplied RGBA color with components in `0..=1`.
+type Rgba = [f32; 4];
+
+/// Premultiply a color.
+fn premultiply(color: Color) -> Rgba {
+    let [r, g, b, a] = color.components;
+    [r * a, g * a, b * a, a]
+}
+
+/// A decoded image, ready to be sampled.
+pub(crate) struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Vec<Rgba>,
+}
+
+impl Texture {
+    /// Convert straight-alpha RGBA8 data.
+    pub fn from_rgba8(width: u32, height: u32, data: &[u8]) -> Self {
+        let pixels = data
+            .chunks_exact(4)
+            .map(|p| {
+                let a = p[3] as f32 / 255.0;
+                [
+                    p[0] as f32 / 255.0 * a,
+                    p[1] as f32 / 255.0 * a,
+                    p[2] as f32 / 255.0 * a,
+                    a,
+                ]
+            })
+            .collect();
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Bilinear sample at `p` in pixel coordinates, clamped to the edges.
+    fn sample(&self, p: Point) -> Rgba {
+        let max_x = self.width as f64 - 1.0;
+        let max_y = self.height as f64 - 1.0;
+        let x = (p.x - 0.5).clamp(0.0, max_x);
+        let y = (p.y - 0.5).clamp(0.0, max_y);
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = ((x - x0) as f32, (y - y0) as f32);
+        let (x0, y0) = (x0 as usize, y0 as usize);
+        let x1 = (x0 + 1).min(self.width as usize - 1);
+        let y1 = (y0 + 1).min(self.height as usize - 1);
+        let at = |x: usize, y: usize| self.pixels[y * self.width as usize + x];
+        let (p00, p10, p01, p11) = (at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1));
+        std::array::from_fn(|i| {
+            let top = p00[i] + (p10[i] - p00[i]) * fx;
+            let bottom = p01[i] + (p11[i] - p01[i]) * fx;
+            top + (bottom - top) * fy
+        })
+    }
+}
+
+/// What fills the covered pixels.
+pub(crate) enum Paint<'a> {
+    Solid(Color),
+    /// An image whose pixel space is mapped to device space by `transform`.
+    Image {
+        texture: &'a Texture,
+        transform: Affine,
+    },
+}
+
+/// Coverage of a pixel rectangle, row-major.
+struct Coverage {
+    x0: usize,
+    y0: usize,
+    width: usize,
+    height: usize,
+    values: Vec<f32>,
+}
+
+impl Coverage {
+    fn get(&self, x: usize, y: usize) -> f32 {
+        if x < self.x0 || y < self.y0 || x >= self.x0 + self.width || y >= self.y0 + self.height {
+            return 0.0;
+        }
+        self.values[(y - self.y0) * self.width + x - self.x0]
+    }
+}
+
+/// An RGBA pixel buffer with a stack of clip masks.
+pub(crate) struct Pixmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgba>,
+    clips: Vec<Coverage>,
+}
+
+impl Pixmap {
+    /// A transparent pixmap.
+    pub fn new(width: u32, height: u32) -> Self {
+        let (width, height) = (width as usize, height as usize);
+        Self {
+            width,
+            height,
+            pixels: vec![[0.0; 4]; width * height],
+            clips: Vec::new(),
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height as u32
+    }
+
+    /// Fill every pixel with `color` and drop all clips.
+    pub fn clear(&mut self, color: Color) {
+        self.pixels.fill(premultiply(color));
+        self.clips.clear();
+    }
+
+    /// Fill `path`, mapped by `transform`.
+    pub fn fill(&mut self, path: &BezPath, transform: Affine, rule: Fill, paint: Paint<'_>) {
+        if let Some(coverage) = self.coverage(path, transform, rule) {
+            self.composite(&coverage, &paint);
+        }
+    }
+
+    /// Stroke `path`, mapped by `transform`. The stroke is outlined in path
+    /// coordinates, so non-uniform transforms distort it like Vello does.
+    pub fn stroke(&mut self, path: &BezPath, transform: Affine, style: &Stroke, color: Color) {
+        let scale = transform.determinant().abs().sqrt();
+        if scale == 0.0 || style.width <= 0.0 {
+            return;
+        }
+        let outline = kurbo::stroke(path, style, &StrokeOpts::default(), TOLERANCE / scale);
+        self.fill(&outline, transform, Fill::NonZero, Paint::Solid(color));
+    }
+
+    /// Clip everything drawn until the matching [`Pixmap::pop_clip`] to `path`.
+    pub fn push_clip(&mut self, path: &BezPath, transform: Affine) {
+        let coverage = self
+            .coverage(path, transform, Fill::NonZero)
+            .unwrap_or(Coverage {
+                x0: 0,
+                y0: 0,
+                width: 0,
+                height: 0,
+                values: Vec::new(),
+            });
+        self.clips.push(coverage);
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.clips.pop();
+    }
+
+    /// Straight-alpha RGBA8 pixels.
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for &[r, g, b, a] in &self.pixels {
+            let unpremultiply = if a > 0.0 { 1.0 / a } else { 0.0 };
+            for c in [r * unpremultiply, g * unpremultiply, b * unpremultiply, a] {
+                data.push((c.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+        }
+        data
+    }
+
+    /// Blend `paint` over the covered pixels.
+    fn composite(&mut self, coverage: &Coverage, paint: &Paint<'_>) {
+        let (solid, inverse) = match paint {
+            Paint::Solid(color) => (premultiply(*color), None),
+            Paint::Image { transform, .. } => ([0.0; 4], Some(transform.inverse())),
+        };
+        for row in 0..coverage.height {
+            let y = coverage.y0 + row;
+            for col in 0..coverage.width {
+                let cov = coverage.values[row * coverage.width + col];
+                if cov <= 0.0 {
+                    continue;
+                }
+                let x = coverage.x0 + col;
+                let src = match (paint, inverse) {
+                    (Paint::Image { texture, .. }, Some(inverse)) => {
+                        texture.sample(inverse * Point::new(x as f64 + 0.5, y as f64 + 0.5))
+                    }
+                    _ => solid,
+                };
+                let dst = &mut self.pixels[y * self.width + x];
+                let alpha = src[3] * cov;
+                for (d, s) in dst.iter_mut().zip(src) {
+                    *d = s * cov + *d * (1.0 - alpha);
+                }
+            }
+        }
+    }
+
+    /// Coverage of `path` within the pixmap and the current clip, or `None` if
+    /// nothing is covered.
+    fn coverage(&self, path: &BezPath, transform: Affine, rule: Fill) -> Option<Coverage> {
+        let lines = flatten(path, transform);
+        let mut bounds = lines
+            .iter()
+            .flat_map(|&(p0, p1)| [p0, p1])
+            .map(|p| Rect::from_points(p, p))
+            .reduce(|a, b| a.union(b))?;
+        bounds = bounds.intersect(Rect::new(0.0, 0.0, self.width as f64, self.height as f64));
+        let clip = self.clips.last();
+        if let Some(clip) = clip {
+            bounds = bounds.intersect(Rect::new(
+                clip.x0 as f64,
+                clip.y0 as f64,
+                (clip.x0 + clip.width) as f64,
+                (clip.y0 + clip.height) as f64,
+            ));
+        }
+        let x0 = bounds.x0.floor().max(0.0) as usize;
+        let y0 = bounds.y0.floor().max(0.0) as usize;
+        let x1 = (bounds.x1.ceil() as usize).min(self.width);
+        let y1 = (bounds.y1.ceil() as usize).min(self.height);
+        if x1 <= x0 || y1 <= y0 {
+            return None;
+        }
+
+        let mut accumulator = Accumulator::new(x1 - x0, y1 - y0);
+        let offset = kurbo::Vec2::new(x0 as f64, y0 as f64);
+        for (p0, p1) in lines {
+            accumulator.line(p0 - offset, p1 - offset);
+        }
+        let mut coverage = accumulator.finish(rule);
+        coverage.x0 = x0;
+        coverage.y0 = y0;
+        if let Some(clip) = clip {
+            for row in 0..coverage.height {
+                for col in 0..coverage.width {
+                    coverage.values[row * coverage.width + col] *= clip.get(x0 + col, y0 + row);
+                }
+            }
+        }
+        Some(coverage)
+    }
+}
+
+/// Flatten `path` into device-space line segments, closing every subpath.
+fn flatten(path: &BezPath, transform: Affine) -> Vec<(Point, Point)> {
+    let mut lines = Vec::new();
+    let mut start = Point::ZERO;
+    let mut last = Point::ZERO;
+    let transformed = transform * path.clone();
+    kurbo::flatten(&transformed, TOLERANCE, |el| match el {
+        PathEl::MoveTo(p) => {
+            if last != start {
+                lines.push((last, start));
+            }
+            start = p;
+            last = p;
+        }
+        PathEl::LineTo(p) => {
+            lines.push((last, p));
+            last = p;
+        }
+        PathEl::ClosePath => {
+            if last != start {
+                lines.push((last, start));
+            }
+            last = start;
+        }
+        // `flatten` only emits lines
+        PathEl::QuadTo(..) | PathEl::CurveTo(..) => {}
+    });
+    if last != start {
+        lines.push((last, start));
+    }
+    lines
+}
+
+/// Accumulates the signed area of edges per pixel; a prefix sum along each row
+/// then gives the winding number, with fractional values at antialiased edges.
+struct Accumulator {
+    width: usize,
+    height: usize,
+    /// Row stride; two spare cells take the contributions of edges at the right border.
+    stride: usize,
+    cells: Vec<f32>,
+}
+
+impl Accumulator {
+    fn new(width: usize, height: usize) -> Self {
+        let stride = width + 2;
+        Self {
+            width,
+            height,
+            stride,
+            cells: vec![0.0; stride * height],
+        }
+    }
+
+    /// Add an edge. Parts left of the area act as if they were on its left
+    /// border, so they still contribute winding; parts to the right are ignored.
+    fn line(&mut self, p0: Point, p1: Point) {
+        let right = self.width as f64;
+        let mut cuts = [0.0, 1.0, 1.0, 1.0];
+        let dx = p1.x - p0.x;
+        if dx != 0.0 {
+            for (i, x) in [0.0, right].into_iter().enumerate() {
+                let t = (x - p0.x) / dx;
+                if t > 0.0 && t < 1.0 {
+                    cuts[i + 1] = t;
+                }
+            }
+        }
+        cuts[1..3].sort_by(f64::total_cmp);
+        let clamp = |p: Point| Point::new(p.x.clamp(0.0, right), p.y);
+        for pair in cuts.windows(2) {
+            if pair[1] > pair[0] {
+                let a = p0.lerp(p1, pair[0]);
+                let b = p0.lerp(p1, pair[1]);
+                self.clamped_line(clamp(a), clamp(b));
+            }
+        }
+    }
+
+    /// Add an edge that lies within `0..=width` horizontally.
+    fn clamped_line(&mut self, p0: Point, p1: Point) {
+        if p0.y == p1.y {
+            return;
+        }
+        let (dir, p0, p1) = if p0.y < p1.y {
+            (1.0, p0, p1)
+        } else {
+            (-1.0, p1, p0)
+        };
+        let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+        let mut x = p0.x;
+        if p0.y < 0.0 {
+            x -= p0.y * dxdy;
+        }
+        let first_row = p0.y.max(0.0) as usize;
+        let last_row = (p1.y.ceil().max(0.0) as usize).min(self.height);
+        for row in first_row..last_row {
+            let line = row * self.stride;
+            let dy = ((row + 1) as f64).min(p1.y) - (row as f64).max(p0.y);
+            let x_next = x + dxdy * dy;
+            let d = (dy * dir) as f32;
+            let (left, right) = if x < x_next { (x, x_next) } else { (x_next, x) };
+            let left_floor = left.floor();
+            let left_cell = left_floor as usize;
+            let right_cell = right.ceil() as usize;
+            if right_cell <= left_cell + 1 {
+                // The edge stays within one pixel column
+                let middle = (0.5 * (x + x_next) - left_floor) as f32;
+                self.cells[line + left_cell] += d - d * middle;
+                self.cells[line + left_cell + 1] += d * middle;
+            } else {
+                // Spread the trapezoid over the columns the edge crosses
+                let s = (1.0 / (right - left)) as f32;
+                let left_frac = (left - left_floor) as f32;
+                let a0 = 0.5 * s * (1.0 - left_frac) * (1.0 - left_frac);
+                let right_frac = (right - right_cell as f64 + 1.0) as f32;
+                let am = 0.5 * s * right_frac * right_frac;
+                self.cells[line + left_cell] += d * a0;
+                if right_cell == left_cell + 2 {
+                    self.cells[line + left_cell + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - left_frac);
+                    self.cells[line + left_cell + 1] += d * (a1 - a0);
+                    for cell in left_cell + 2..right_cell - 1 {
+                        self.cells[line + cell] += d * s;
+                    }
+                    let a2 = a1 + (right_cell - left_cell - 3) as f32 * s;
+                    self.cells[line + right_cell - 1] += d * (1.0 - a2 - am);
+                }
+                self.cells[line + right_cell] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    /// Integrate the rows into coverage values.
+    fn finish(self, rule: Fill) -> Coverage {
+        let mut values = Vec::with_capacity(self.width * self.height);
+        for row in self.cells.chunks_exact(self.stride) {
+            let mut winding = 0.0f32;
+            for &cell in &row[..self.width] {
+                winding += cell;
+                let coverage = match rule {
+                    Fill::NonZero => winding.abs().min(1.0),
+                    Fill::EvenOdd => {
+                        let folded = winding.abs() % 2.0;
+                        if folded > 1.0 { 2.0 - folded } else { folded }
+                    }
+                };
+                values.push(coverage);
+            }
+        }
+        Coverage {
+            x0: 0,
+            y0: 0,
+            width: self.width,
+            height: self.height,
+            values,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use kurbo::{Circle, Shape};
+
+    fn alpha(pixmap: &Pixmap, x: u32, y: u32) -> u8 {
+        pixmap.to_rgba8()[((y * pixmap.width() + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn test_pixel_aligned_rect() {
+        let mut pixmap = Pixmap::new(8, 8);
+        let rect = Rect::new(2.0, 2.0, 6.0, 5.0).to_path(0.1);
+        pixmap.fill(
+            &rect,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(Color::BLACK),
+        );
+        for y in 0..8 {
+            for x in 0..8 {
+                let inside = (2..6).contains(&x) && (2..5).contains(&y);
+                assert_eq!(
+                    alpha(&pixmap, x, y),
+                    if inside { 255 } else { 0 },
+                    "({x}, {y})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_antialiased_edges() {
+        let mut pixmap = Pixmap::new(4, 4);
+        let rect = Rect::new(0.5, -10.0, 2.25, 10.0).to_path(0.1);
+        pixmap.fill(
+            &rect,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(Color::BLACK),
+        );
+        assert_eq!(alpha(&pixmap, 0, 1), 128);
+        assert_eq!(alpha(&pixmap, 1, 1), 255);
+        assert_eq!(alpha(&pixmap, 2, 1), 64);
+        assert_eq!(alpha(&pixmap, 3, 1), 0);
+
+        // Area of a circle, and a shape extending past every border
+        let mut pixmap = Pixmap::new(40, 40);
+        let circle = Circle::new((20.0, 20.0), 10.0).to_path(0.01);
+        pixmap.fill(
+            &circle,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(Color::BLACK),
+        );
+        let area: f64 = pixmap
+            .to_rgba8()
+            .chunks(4)
+            .map(|p| p[3] as f64 / 255.0)
+            .sum();
+        assert!((area - std::f64::consts::PI * 100.0).abs() < 3.0, "{area}");
+
+        let mut pixmap = Pixmap::new(4, 4);
+        let big = Circle::new((2.0, 2.0), 100.0).to_path(0.1);
+        pixmap.fill(
+            &big,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(Color::BLACK),
+        );
+        assert!(pixmap.to_rgba8().chunks(4).all(|p| p[3] == 255));
+    }
+
+    #[test]
+    fn test_fill_rules() {
+        let mut path = Rect::new(0.0, 0.0, 6.0, 6.0).to_path(0.1);
+        path.extend(Rect::new(2.0, 2.0, 4.0, 4.0).to_path(0.1));
+        let mut nonzero = Pixmap::new(6, 6);
+        nonzero.fill(
+            &path,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(Color::BLACK),
+        );
+        let mut even_odd = Pixmap::new(6, 6);
+        even_odd.fill(
+            &path,
+            Affine::IDENTITY,
+            Fill::EvenOdd,
+            Paint::Solid(Color::BLACK),
+        );
+        assert_eq!(alpha(&nonzero, 3, 3), 255);
+        assert_eq!(alpha(&even_odd, 3, 3), 0);
+        assert_eq!(alpha(&even_odd, 1, 1), 255);
+    }
+
+    #[test]
+    fn test_clip_and_blend() {
+        let mut pixmap = Pixmap::new(4, 1);
+        pixmap.clear(Color::WHITE);
+        pixmap.push_clip(
+            &Rect::new(0.0, 0.0, 2.0, 1.0).to_path(0.1),
+            Affine::IDENTITY,
+        );
+        let half_red = Color::from_rgba8(255, 0, 0, 128);
+        let all = Rect::new(0.0, 0.0, 4.0, 1.0).to_path(0.1);
+        pixmap.fill(
+            &all,
+            Affine::IDENTITY,
+            Fill::NonZero,
+            Paint::Solid(half_red),
+        );
+        pixmap.pop_clip();
+        let data = pixmap.to_rgba8();
+        assert_eq!(&data[0..4], &[255, 127, 127, 255]);
+        assert_eq!(&data[8..12], &[255, 255, 255, 255]);
+
+        // Strokes are centered on the path
+        let mut pixmap = Pixmap::new(6, 3);
+        let mut line = BezPath::new();
+        line.move_to((0.0, 1.5));
+        line.line_to((6.0, 1.5));
+        let style = Stroke::new(1.0).with_caps(kurbo::Cap::Butt);
+        pixmap.stroke(&line, Affine::IDENTITY, &style, Color::BLACK);
+        assert_eq!(alpha(&pixmap, 3, 0), 0);
+        assert_eq!(alpha(&pixmap, 3, 1), 255);
+        assert_eq!(alpha(&pixmap, 3, 2), 0);
+    }
+
+    #[test]
+    fn test_image_paint() {
+        // A 2x1 image, red then blue, scaled up to 4x2 pixels
+        let texture = Texture::from_rgba8(2, 1, &[255, 0, 0, 255, 0, 0, 255, 255]);
+        let mut pixmap = Pixmap::new(4, 2);
+        let rect = Rect::new(0.0, 0.0, 2.0, 1.0).to_path(0.1);
+        let transform = Affine::scale(2.0);
+        let paint = Paint::Image {
+            texture: &texture,
+            transform,
+        };
+        pixmap.fill(&rect, transform, Fill::NonZero, paint);
+        let data = pixmap.to_rgba8();
+        assert_eq!(&data[0..4], &[255, 0, 0, 255]);
+        assert_eq!(&data[12..16], &[0, 0, 255, 255]);
+        // Bilinear filtering between the two texels
+        assert_eq!(&data[4..8], &[191, 0, 64, 255]);
+    }
+}
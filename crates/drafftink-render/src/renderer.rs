@@ -1,5 +1,6 @@
 //! Renderer trait abstraction.
 
+use crate::diff_overlay::DiffOverlay;
 use drafftink_core::canvas::Canvas;
 use drafftink_core::shapes::Shape;
 use drafftink_core::snap::SmartGuide;
@@ -22,6 +23,54 @@ pub enum RendererError {
 #[allow(dead_code)]
 pub type RenderResult<T> = Result<T, RendererError>;
 
+/// Result of PNG rendering - contains the raw RGBA pixel data and dimensions.
+#[derive(Debug)]
+pub struct PngRenderResult {
+    /// RGBA pixel data (4 bytes per pixel).
+    pub rgba_data: Vec<u8>,
+    /// Image width in pixels.
+    pub width: u32,
+    /// Image height in pixels.
+    pub height: u32,
+}
+
+impl PngRenderResult {
+    /// Encode the pixels as a PNG file, optionally embedding the scene JSON so
+    /// the file can be opened again as a document (see [`extract_scene_from_png`]).
+    pub fn to_png(&self, scene_json: Option<&str>) -> RenderResult<Vec<u8>> {
+        let error = |e: png::EncodingError| RendererError::RenderFailed(e.to_string());
+        let mut png_data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png_data, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        // Embed scene data as compressed zTXt chunk
+        if let Some(json) = scene_json {
+            encoder
+                .add_ztxt_chunk(PNG_SCENE_KEYWORD.to_string(), json.to_string())
+                .map_err(error)?;
+        }
+        let mut writer = encoder.write_header().map_err(error)?;
+        writer.write_image_data(&self.rgba_data).map_err(error)?;
+        writer.finish().map_err(error)?;
+        Ok(png_data)
+    }
+}
+
+/// Keyword of the PNG text chunk holding the embedded scene JSON.
+pub const PNG_SCENE_KEYWORD: &str = "application/vnd.drafftink+json";
+
+/// Extract the scene JSON embedded by [`PngRenderResult::to_png`], if present.
+pub fn extract_scene_from_png(png_data: &[u8]) -> Option<String> {
+    let decoder = png::Decoder::new(std::io::Cursor::new(png_data));
+    let reader = decoder.read_info().ok()?;
+    reader
+        .info()
+        .compressed_latin1_text
+        .iter()
+        .find(|chunk| chunk.keyword == PNG_SCENE_KEYWORD)
+        .and_then(|chunk| chunk.get_text().ok())
+}
+
 /// Grid display style.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum GridStyle {
@@ -116,6 +165,8 @@ pub struct RenderContext<'a> {
     pub eraser_cursor: Option<(kurbo::Point, f64)>,
     /// Laser pointer position and trail.
     pub laser_pointer: Option<(kurbo::Point, Vec<(kurbo::Point, f64)>)>,
+    /// Changes to highlight when comparing the canvas with an older version.
+    pub diff_overlay: Option<DiffOverlay<'a>>,
 }
 
 impl<'a> RenderContext<'a> {
@@ -136,6 +187,7 @@ impl<'a> RenderContext<'a> {
             smart_guides: Vec::new(),
             eraser_cursor: None,
             laser_pointer: None,
+            diff_overlay: None,
         }
     }
 
@@ -207,6 +259,12 @@ impl<'a> RenderContext<'a> {
         self.laser_pointer = pointer;
         self
     }
+
+    /// Set the changes to highlight against an older version.
+    pub fn with_diff_overlay(mut self, overlay: Option<DiffOverlay<'a>>) -> Self {
+        self.diff_overlay = overlay;
+        self
+    }
 }
 
 /// Trait for rendering backends.
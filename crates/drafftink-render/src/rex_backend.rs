@@ -1,13 +1,54 @@
-//! Vello backend for ReX math rendering with font fallback.
+//! ReX math rendering backend with font fallback.
+//!
+//! Glyphs and rules are emitted into a [`MathSink`]: a Vello scene for on-screen
+//! rendering, or one of the vector exporters.
 
-use kurbo::{Affine, BezPath, Point};
+use crate::fonts::{GELPEN_REGULAR, GlyphPath, XITS_MATH};
+use drafftink_core::shapes::Math;
+use kurbo::{Affine, BezPath};
 use peniko::Color;
 use rex::font::backend::ttf_parser::TtfMathFont;
 use rex::font::common::GlyphId;
 use rex::render::{Backend, Cursor, FontBackend, GraphicsBackend, RGBA};
 use std::collections::HashMap;
+#[cfg(feature = "vello-renderer")]
 use vello::Scene;
 
+/// Destination for the filled paths that make up rendered math.
+pub trait MathSink {
+    /// Fill `path` with `color`, after applying `transform`.
+    fn fill_path(&mut self, transform: Affine, color: Color, path: &BezPath);
+
+    /// Draw a font glyph. Sinks that embed fonts can write it as text; by default
+    /// the outline is filled.
+    fn glyph(&mut self, glyph: MathGlyph<'_>) {
+        self.fill_path(glyph.outline_transform, glyph.color, glyph.outline);
+    }
+}
+
+/// A glyph of rendered math.
+pub struct MathGlyph<'a> {
+    /// Font file the glyph comes from.
+    pub font_data: &'a [u8],
+    pub glyph_id: u16,
+    /// The character the glyph represents, if known.
+    pub codepoint: Option<char>,
+    /// Maps em space (y up, 1 unit = font size) to output coordinates.
+    pub em_transform: Affine,
+    /// Maps `outline` to output coordinates.
+    pub outline_transform: Affine,
+    /// Glyph outline in font units.
+    pub outline: &'a BezPath,
+    pub color: Color,
+}
+
+#[cfg(feature = "vello-renderer")]
+impl MathSink for Scene {
+    fn fill_path(&mut self, transform: Affine, color: Color, path: &BezPath) {
+        self.fill(vello::peniko::Fill::NonZero, transform, color, None, path);
+    }
+}
+
 /// Map Unicode math alphanumeric symbols to ASCII equivalents.
 fn math_to_ascii(c: char) -> Option<char> {
     let cp = c as u32;
@@ -44,9 +85,9 @@ fn math_to_ascii(c: char) -> Option<char> {
     }
 }
 
-/// Vello backend for ReX rendering with primary font fallback.
-pub struct VelloBackend<'a, 'f, 'p> {
-    scene: &'a mut Scene,
+/// ReX backend with primary font fallback, drawing into a [`MathSink`].
+pub struct MathBackend<'a, 'f, 'p, S: MathSink> {
+    sink: &'a mut S,
     math_font: &'f TtfMathFont<'f>,
     primary_font: Option<&'p ttf_parser::Face<'p>>,
     /// Maps math font glyph IDs to codepoints for fallback lookup.
@@ -56,9 +97,13 @@ pub struct VelloBackend<'a, 'f, 'p> {
     current_color: Color,
 }
 
-impl<'a, 'f, 'p> VelloBackend<'a, 'f, 'p> {
+/// ReX backend drawing into a Vello scene.
+#[cfg(feature = "vello-renderer")]
+pub type VelloBackend<'a, 'f, 'p> = MathBackend<'a, 'f, 'p, Scene>;
+
+impl<'a, 'f, 'p, S: MathSink> MathBackend<'a, 'f, 'p, S> {
     pub fn new(
-        scene: &'a mut Scene,
+        sink: &'a mut S,
         math_font: &'f TtfMathFont<'f>,
         primary_font: Option<&'p ttf_parser::Face<'p>>,
         transform: Affine,
@@ -87,7 +132,7 @@ impl<'a, 'f, 'p> VelloBackend<'a, 'f, 'p> {
         }
 
         Self {
-            scene,
+            sink,
             math_font,
             primary_font,
             glyph_to_codepoint,
@@ -98,34 +143,7 @@ impl<'a, 'f, 'p> VelloBackend<'a, 'f, 'p> {
     }
 }
 
-struct PathBuilder(BezPath);
-
-impl ttf_parser::OutlineBuilder for PathBuilder {
-    fn move_to(&mut self, x: f32, y: f32) {
-        self.0.move_to(Point::new(x as f64, y as f64));
-    }
-    fn line_to(&mut self, x: f32, y: f32) {
-        self.0.line_to(Point::new(x as f64, y as f64));
-    }
-    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
-        self.0.quad_to(
-            Point::new(x1 as f64, y1 as f64),
-            Point::new(x as f64, y as f64),
-        );
-    }
-    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
-        self.0.curve_to(
-            Point::new(x1 as f64, y1 as f64),
-            Point::new(x2 as f64, y2 as f64),
-            Point::new(x as f64, y as f64),
-        );
-    }
-    fn close(&mut self) {
-        self.0.close_path();
-    }
-}
-
-impl<'f, 'p> FontBackend<TtfMathFont<'f>> for VelloBackend<'_, 'f, 'p> {
+impl<'f, 'p, S: MathSink> FontBackend<TtfMathFont<'f>> for MathBackend<'_, 'f, 'p, S> {
     fn symbol(&mut self, pos: Cursor, gid: GlyphId, scale: f64, _ctx: &TtfMathFont<'f>) {
         // Try primary font first if available
         if let Some(primary) = self.primary_font {
@@ -136,22 +154,22 @@ impl<'f, 'p> FontBackend<TtfMathFont<'f>> for VelloBackend<'_, 'f, 'p> {
                     // Use primary font (slightly smaller to match text tool rendering)
                     let units_per_em = primary.units_per_em() as f64;
                     let adjusted_scale = scale * 0.75;
-                    let glyph_transform = self.transform
+                    let em_transform = self.transform
                         * Affine::translate(kurbo::Vec2::new(pos.x, pos.y))
-                        * Affine::scale_non_uniform(
-                            adjusted_scale / units_per_em,
-                            -adjusted_scale / units_per_em,
-                        );
+                        * Affine::scale_non_uniform(adjusted_scale, -adjusted_scale);
+                    let glyph_transform = em_transform * Affine::scale(1.0 / units_per_em);
 
-                    let mut builder = PathBuilder(BezPath::new());
+                    let mut builder = GlyphPath(BezPath::new());
                     if primary.outline_glyph(primary_gid, &mut builder).is_some() {
-                        self.scene.fill(
-                            vello::peniko::Fill::NonZero,
-                            glyph_transform,
-                            self.current_color,
-                            None,
-                            &builder.0,
-                        );
+                        self.sink.glyph(MathGlyph {
+                            font_data: primary.raw_face().data,
+                            glyph_id: primary_gid.0,
+                            codepoint: Some(lookup_char),
+                            em_transform,
+                            outline_transform: glyph_transform,
+                            outline: &builder.0,
+                            color: self.current_color,
+                        });
                         return;
                     }
                 }
@@ -171,36 +189,35 @@ impl<'f, 'p> FontBackend<TtfMathFont<'f>> for VelloBackend<'_, 'f, 'p> {
             sx as f64, ky as f64, kx as f64, sy as f64, tx as f64, ty as f64,
         ]);
 
-        let glyph_transform = self.transform
+        let em_transform = self.transform
             * Affine::translate(kurbo::Vec2::new(pos.x, pos.y))
-            * Affine::scale_non_uniform(scale, -scale)
-            * font_matrix;
+            * Affine::scale_non_uniform(scale, -scale);
+        let glyph_transform = em_transform * font_matrix;
 
-        let mut builder = PathBuilder(BezPath::new());
+        let mut builder = GlyphPath(BezPath::new());
         self.math_font
             .font()
             .outline_glyph(gid.into(), &mut builder);
 
-        self.scene.fill(
-            vello::peniko::Fill::NonZero,
-            glyph_transform,
-            self.current_color,
-            None,
-            &builder.0,
-        );
+        let glyph_id: u16 = gid.into();
+        self.sink.glyph(MathGlyph {
+            font_data: self.math_font.font().raw_face().data,
+            glyph_id,
+            codepoint: self.glyph_to_codepoint.get(&glyph_id).copied(),
+            em_transform,
+            outline_transform: glyph_transform,
+            outline: &builder.0,
+            color: self.current_color,
+        });
     }
 }
 
-impl GraphicsBackend for VelloBackend<'_, '_, '_> {
+impl<S: MathSink> GraphicsBackend for MathBackend<'_, '_, '_, S> {
     fn rule(&mut self, pos: Cursor, width: f64, height: f64) {
+        use kurbo::Shape as _;
         let rect = kurbo::Rect::new(pos.x, pos.y, pos.x + width, pos.y + height);
-        self.scene.fill(
-            vello::peniko::Fill::NonZero,
-            self.transform,
-            self.current_color,
-            None,
-            &rect,
-        );
+        self.sink
+            .fill_path(self.transform, self.current_color, &rect.to_path(0.1));
     }
 
     fn begin_color(&mut self, RGBA(r, g, b, a): RGBA) {
@@ -215,4 +232,58 @@ impl GraphicsBackend for VelloBackend<'_, '_, '_> {
     }
 }
 
-impl<'f, 'p> Backend<TtfMathFont<'f>> for VelloBackend<'_, 'f, 'p> {}
+impl<'f, 'p, S: MathSink> Backend<TtfMathFont<'f>> for MathBackend<'_, 'f, 'p, S> {}
+
+/// Lay out a math shape with ReX and draw it into `sink`.
+///
+/// The layout size is cached on the shape for bounds calculation. On failure the
+/// returned message says which stage failed, so callers can draw a placeholder.
+pub fn render_math<S: MathSink>(
+    math: &Math,
+    transform: Affine,
+    sink: &mut S,
+) -> Result<(), &'static str> {
+    use rex::layout::engine::LayoutBuilder;
+    use rex::render::Renderer as RexRenderer;
+
+    // Parse fonts
+    let math_face = ttf_parser::Face::parse(XITS_MATH, 0).map_err(|_| "Font parse error")?;
+    let math_font = TtfMathFont::new(math_face).map_err(|_| "No MATH table")?;
+    // Primary font (GelPen) for text glyphs - fallback to math font if unavailable
+    let primary_face = ttf_parser::Face::parse(GELPEN_REGULAR, 0).ok();
+
+    // Parse LaTeX
+    let parse_nodes = rex::parser::parse(&math.latex).map_err(|_| "Parse error")?;
+
+    // Layout
+    let layout_engine = LayoutBuilder::new(&math_font)
+        .font_size(math.font_size)
+        .build();
+    let layout = layout_engine
+        .layout(&parse_nodes)
+        .map_err(|_| "Layout error")?;
+
+    // Cache size for bounds calculation
+    let size = layout.size();
+    math.set_cached_size(size.width, size.height, size.depth);
+
+    // Position: math.position is baseline origin, apply rotation around center
+    let center_x = math.position.x + size.width / 2.0;
+    let center_y = math.position.y - size.height / 2.0 - size.depth / 2.0;
+    let math_transform = transform
+        * Affine::translate((center_x, center_y))
+        * Affine::rotate(math.rotation)
+        * Affine::translate((-center_x, -center_y))
+        * Affine::translate((math.position.x, math.position.y));
+
+    let color: Color = math.style.stroke_color.into();
+    let mut backend = MathBackend::new(
+        sink,
+        &math_font,
+        primary_face.as_ref(),
+        math_transform,
+        color,
+    );
+    RexRenderer::new().render(&layout, &mut backend);
+    Ok(())
+}
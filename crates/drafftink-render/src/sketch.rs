@@ -0,0 +1,300 @@
+//! Hand-drawn geometry shared by the renderers and exporters.
+//!
+//! Everything here is deterministic for a given shape seed, so every output
+//! (screen, PNG, SVG) draws the same wobble and fill pattern.
+
+use drafftink_core::shapes::{FillPattern, Freehand};
+use kurbo::{BezPath, PathEl, Point, Rect, Vec2};
+use roughr::core::{FillStyle, OptionsBuilder};
+
+/// Simple seeded random number generator (xorshift32).
+/// Used for deterministic hand-drawn effects.
+struct SimpleRng {
+    state: u32,
+}
+
+impl SimpleRng {
+    fn new(seed: u32) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Random float in range [-1, 1]
+    fn next_f64(&mut self) -> f64 {
+        (self.next_u32() as f64 / u32::MAX as f64) * 2.0 - 1.0
+    }
+
+    /// Random offset scaled by amount
+    fn offset(&mut self, amount: f64) -> f64 {
+        self.next_f64() * amount
+    }
+}
+
+/// Apply hand-drawn effect to a path based on roughness level.
+/// This mimics the Excalidraw/rough.js algorithm:
+/// - Endpoints are randomly offset (lines overshoot/undershoot at corners)
+/// - Lines have a slight bow (curve in the middle)
+/// - Each stroke_index produces completely different randomness
+///
+/// roughness: 0 = clean, 1 = slight wobble, 2 = very sketchy
+/// seed: stable random seed from the shape's style (persisted, doesn't change on transform)
+/// stroke_index: 0 or 1 for multi-stroke effect (different random offsets)
+pub(crate) fn apply_hand_drawn_effect(
+    path: &BezPath,
+    roughness: f64,
+    zoom: f64,
+    seed: u32,
+    stroke_index: u32,
+) -> BezPath {
+    if roughness <= 0.0 {
+        return path.clone();
+    }
+
+    // Scale effect inversely with zoom so it looks consistent at all zoom levels
+    let scale = 1.0 / zoom.sqrt();
+
+    // Values tuned to match Excalidraw/rough.js feel
+    // These create the "overshoot" effect at corners
+    let max_randomness_offset = roughness * 2.0 * scale;
+    let bowing = roughness * 1.0;
+
+    // Use the shape's stable seed combined with stroke_index for deterministic randomness
+    // The seed is stored in the shape's style, so it doesn't change when the shape is transformed
+    let combined_seed = seed.wrapping_add(stroke_index.wrapping_mul(99991)); // Large prime for very different sequences
+    let mut rng = SimpleRng::new(combined_seed);
+
+    let mut result = BezPath::new();
+    let mut last_point = Point::ZERO;
+
+    for el in path.elements() {
+        match el {
+            PathEl::MoveTo(p) => {
+                // Offset the start point
+                let wobbled = Point::new(
+                    p.x + rng.offset(max_randomness_offset),
+                    p.y + rng.offset(max_randomness_offset),
+                );
+                result.move_to(wobbled);
+                last_point = *p;
+            }
+            PathEl::LineTo(p) => {
+                // This is the key rough.js algorithm for lines:
+                // 1. Calculate line length
+                // 2. Add bowing (perpendicular offset at midpoint)
+                // 3. Offset both endpoints randomly (creates overshoot)
+
+                let dx = p.x - last_point.x;
+                let dy = p.y - last_point.y;
+                let len = (dx * dx + dy * dy).sqrt();
+
+                // Calculate bowing amount - proportional to length
+                let bow_offset = bowing * roughness * len / 200.0;
+                let bow = rng.offset(bow_offset) * scale;
+
+                // Perpendicular vector for bowing
+                let (perp_x, perp_y) = if len > 0.001 {
+                    (-dy / len, dx / len)
+                } else {
+                    (0.0, 0.0)
+                };
+
+                // Control point with bowing
+                let mid_x = (last_point.x + p.x) / 2.0 + perp_x * bow;
+                let mid_y = (last_point.y + p.y) / 2.0 + perp_y * bow;
+
+                // End point with random offset (creates overshoot at corners)
+                let end = Point::new(
+                    p.x + rng.offset(max_randomness_offset),
+                    p.y + rng.offset(max_randomness_offset),
+                );
+
+                // Use quadratic bezier for the bowed line
+                result.quad_to(Point::new(mid_x, mid_y), end);
+                last_point = *p;
+            }
+            PathEl::QuadTo(p1, p2) => {
+                let wobbled_p1 = Point::new(
+                    p1.x + rng.offset(max_randomness_offset * 0.7),
+                    p1.y + rng.offset(max_randomness_offset * 0.7),
+                );
+                let wobbled_p2 = Point::new(
+                    p2.x + rng.offset(max_randomness_offset),
+                    p2.y + rng.offset(max_randomness_offset),
+                );
+                result.quad_to(wobbled_p1, wobbled_p2);
+                last_point = *p2;
+            }
+            PathEl::CurveTo(p1, p2, p3) => {
+                let wobbled_p1 = Point::new(
+                    p1.x + rng.offset(max_randomness_offset * 0.5),
+                    p1.y + rng.offset(max_randomness_offset * 0.5),
+                );
+                let wobbled_p2 = Point::new(
+                    p2.x + rng.offset(max_randomness_offset * 0.5),
+                    p2.y + rng.offset(max_randomness_offset * 0.5),
+                );
+                let wobbled_p3 = Point::new(
+                    p3.x + rng.offset(max_randomness_offset),
+                    p3.y + rng.offset(max_randomness_offset),
+                );
+                result.curve_to(wobbled_p1, wobbled_p2, wobbled_p3);
+                last_point = *p3;
+            }
+            PathEl::ClosePath => {
+                // Don't close - let the overshoot show at the closing corner too
+                // The path visually closes but endpoints won't match perfectly
+                result.close_path();
+            }
+        }
+    }
+
+    result
+}
+
+/// Generate fill pattern lines within the given bounds using roughr.
+pub(crate) fn generate_fill_pattern(
+    pattern: FillPattern,
+    bounds: Rect,
+    stroke_width: f64,
+    seed: u32,
+) -> BezPath {
+    use roughr::core::{OpSetType, OpType};
+
+    let fill_style = match pattern {
+        FillPattern::Solid => return BezPath::new(),
+        FillPattern::Hachure => FillStyle::Hachure,
+        FillPattern::ZigZag => FillStyle::ZigZag,
+        FillPattern::CrossHatch => FillStyle::CrossHatch,
+        FillPattern::Dots => FillStyle::Dots,
+        FillPattern::Dashed => FillStyle::Dashed,
+        FillPattern::ZigZagLine => FillStyle::ZigZagLine,
+    };
+
+    let fill_color: roughr::Srgba = roughr::Srgba::new(0.0, 0.0, 0.0, 1.0);
+    let options = OptionsBuilder::default()
+        .seed(seed as u64)
+        .fill_style(fill_style)
+        .fill(fill_color)
+        .stroke(fill_color)
+        .fill_weight((stroke_width * 0.5) as f32)
+        .hachure_gap((stroke_width * 4.0) as f32)
+        .build()
+        .unwrap();
+
+    let generator = roughr::generator::Generator::default();
+    let drawing = generator.rectangle::<f64>(
+        bounds.x0,
+        bounds.y0,
+        bounds.width(),
+        bounds.height(),
+        &Some(options),
+    );
+
+    // Extract FillSketch ops directly from drawable.sets (like the official vello example)
+    let mut path = BezPath::new();
+    for set in drawing.sets.iter() {
+        if set.op_set_type == OpSetType::FillSketch {
+            for op in set.ops.iter() {
+                match op.op {
+                    OpType::Move => {
+                        path.move_to(Point::new(op.data[0], op.data[1]));
+                    }
+                    OpType::LineTo => {
+                        path.line_to(Point::new(op.data[0], op.data[1]));
+                    }
+                    OpType::BCurveTo => {
+                        path.curve_to(
+                            Point::new(op.data[0], op.data[1]),
+                            Point::new(op.data[2], op.data[3]),
+                            Point::new(op.data[4], op.data[5]),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    path
+}
+
+/// Build the filled outline of a pressure-sensitive freehand stroke.
+/// Returns `None` when the stroke has fewer than two distinct points.
+pub(crate) fn pressure_outline(freehand: &Freehand) -> Option<BezPath> {
+    if freehand.points.len() < 2 {
+        return None;
+    }
+
+    let base_size = freehand.style.stroke_width * 2.3;
+    let thinning = 0.6;
+
+    // Build a filled polygon that represents the variable-width stroke
+    let mut left_points: Vec<Point> = Vec::new();
+    let mut right_points: Vec<Point> = Vec::new();
+
+    for i in 0..freehand.points.len() {
+        let point = freehand.points[i];
+        let pressure = freehand.pressure_at(i);
+        // Apply easing: sin((pressure * π) / 2)
+        let eased_pressure = (pressure * std::f64::consts::PI / 2.0).sin();
+        // Apply thinning: size * (1 - thinning * (1 - pressure))
+        let width = base_size * (1.0 - thinning * (1.0 - eased_pressure));
+
+        // Calculate perpendicular direction
+        let dir = if i == 0 {
+            // First point: use direction to next point
+            let next = freehand.points[i + 1];
+            Vec2::new(next.x - point.x, next.y - point.y)
+        } else if i == freehand.points.len() - 1 {
+            // Last point: use direction from previous point
+            let prev = freehand.points[i - 1];
+            Vec2::new(point.x - prev.x, point.y - prev.y)
+        } else {
+            // Middle points: average of incoming and outgoing directions
+            let prev = freehand.points[i - 1];
+            let next = freehand.points[i + 1];
+            Vec2::new(next.x - prev.x, next.y - prev.y)
+        };
+
+        let len = dir.hypot();
+        if len < f64::EPSILON {
+            continue;
+        }
+
+        // Perpendicular unit vector
+        let perp = Vec2::new(-dir.y / len, dir.x / len);
+        let half_width = width / 2.0;
+
+        left_points.push(Point::new(
+            point.x + perp.x * half_width,
+            point.y + perp.y * half_width,
+        ));
+        right_points.push(Point::new(
+            point.x - perp.x * half_width,
+            point.y - perp.y * half_width,
+        ));
+    }
+
+    if left_points.is_empty() {
+        return None;
+    }
+
+    // Build the path: left side forward, right side backward
+    let mut path = BezPath::new();
+    path.move_to(left_points[0]);
+    for point in left_points.iter().skip(1) {
+        path.line_to(*point);
+    }
+    // Connect to right side (reversed)
+    for point in right_points.iter().rev() {
+        path.line_to(*point);
+    }
+    path.close_path();
+    Some(path)
+}
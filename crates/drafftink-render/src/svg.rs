@@ -0,0 +1,483 @@
+//! SVG export of documents and selections.
+//!
+//! Produces a standalone SVG that matches the on-screen rendering: the same hand-drawn
+//! jitter and fill patterns (from the shape seeds), text as `<text>` with the bundled
+//! fonts embedded or as outlined glyph paths, math as glyph paths, and images as data
+//! URIs. The scene JSON can be embedded in the SVG metadata so exported files can be
+//! opened again as documents (see [`extract_scene_from_svg`]).
+
+use crate::fonts::{BundledFont, GlyphPath, bundled_font};
+use crate::rex_backend::MathSink;
+use crate::vector::{
+    VectorSink, color_runs, content_bounds, draw_shape, layout_lines, num, selected_shapes,
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::shapes::{Image, Shape, ShapeId, ShapeTrait, Text};
+use kurbo::{Affine, BezPath, Rect};
+use peniko::Color;
+use std::fmt::Write as _;
+
+/// MIME type of the scene JSON embedded in exported SVGs.
+pub const SVG_SCENE_MIME: &str = "application/vnd.drafftink+json";
+/// XML namespace of the embedded scene element.
+const SCENE_NAMESPACE: &str = "https://drafft.ink/ns/scene";
+
+/// How text shapes are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SvgTextMode {
+    /// `<text>` elements, with the bundled fonts embedded as `@font-face` rules.
+    /// Text stays selectable and searchable.
+    #[default]
+    EmbeddedFonts,
+    /// Glyph outlines as paths. Looks identical everywhere, without font data.
+    Outlined,
+}
+
+impl SvgTextMode {
+    /// All modes, in menu order.
+    pub const ALL: [SvgTextMode; 2] = [SvgTextMode::EmbeddedFonts, SvgTextMode::Outlined];
+
+    /// Display name.
+    pub fn name(self) -> &'static str {
+        match self {
+            SvgTextMode::EmbeddedFonts => "Fonts",
+            SvgTextMode::Outlined => "Paths",
+        }
+    }
+}
+
+/// Options for SVG export.
+#[derive(Debug, Clone)]
+pub struct SvgExportOptions {
+    /// Space around the content, in document units.
+    pub padding: f64,
+    /// Background fill, or `None` for a transparent background.
+    pub background: Option<Color>,
+    /// How text is written.
+    pub text_mode: SvgTextMode,
+    /// Embed the scene JSON in the SVG metadata for re-import.
+    pub embed_scene: bool,
+}
+
+impl Default for SvgExportOptions {
+    fn default() -> Self {
+        Self {
+            padding: 20.0,
+            background: Some(Color::WHITE),
+            text_mode: SvgTextMode::default(),
+            embed_scene: true,
+        }
+    }
+}
+
+/// Export the whole document as SVG. Returns `None` if the document is empty.
+pub fn export_document_svg(
+    document: &CanvasDocument,
+    options: &SvgExportOptions,
+) -> Option<String> {
+    let shapes: Vec<&Shape> = document.shapes_ordered().collect();
+    let scene_json = options
+        .embed_scene
+        .then(|| document.to_json().ok())
+        .flatten();
+    export_shapes(&shapes, scene_json.as_deref(), options)
+}
+
+/// Export the selected shapes as SVG. The embedded scene contains only the selection.
+/// Returns `None` if none of the shapes exist.
+pub fn export_selection_svg(
+    document: &CanvasDocument,
+    selection: &[ShapeId],
+    options: &SvgExportOptions,
+) -> Option<String> {
+    let shapes = selected_shapes(document, selection);
+    let scene_json = options
+        .embed_scene
+        .then(|| document.export_selection(selection).to_json().ok())
+        .flatten();
+    export_shapes(&shapes, scene_json.as_deref(), options)
+}
+
+/// Extract the scene JSON embedded by [`export_document_svg`], if present.
+pub fn extract_scene_from_svg(svg: &str) -> Option<String> {
+    let start = svg.find("<drafftink:scene")?;
+    let content_start = start + svg[start..].find('>')? + 1;
+    let content_end = content_start + svg[content_start..].find("</drafftink:scene>")?;
+    let encoded: String = svg[content_start..content_end]
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    let bytes = STANDARD.decode(encoded).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn export_shapes(
+    shapes: &[&Shape],
+    scene_json: Option<&str>,
+    options: &SvgExportOptions,
+) -> Option<String> {
+    let view = content_bounds(shapes)?.inflate(options.padding, options.padding);
+
+    let mut writer = SvgWriter::new(options.text_mode);
+    for shape in shapes {
+        draw_shape(&mut writer, shape);
+    }
+    Some(writer.finish(view, options.background, scene_json))
+}
+
+/// Accumulates SVG body and `<defs>` while walking the shapes.
+struct SvgWriter {
+    body: String,
+    defs: String,
+    text_mode: SvgTextMode,
+    /// Fonts referenced by `<text>` elements, embedded once each.
+    fonts: Vec<BundledFont>,
+    next_clip_id: usize,
+}
+
+impl SvgWriter {
+    fn new(text_mode: SvgTextMode) -> Self {
+        Self {
+            body: String::new(),
+            defs: String::new(),
+            text_mode,
+            fonts: Vec::new(),
+            next_clip_id: 0,
+        }
+    }
+
+    fn finish(self, view: Rect, background: Option<Color>, scene_json: Option<&str>) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}" height="{h}" viewBox="{x} {y} {w} {h}">"#,
+            x = num(view.x0),
+            y = num(view.y0),
+            w = num(view.width()),
+            h = num(view.height()),
+        );
+        out.push('\n');
+
+        if let Some(json) = scene_json {
+            let _ = writeln!(
+                out,
+                r#"<metadata><drafftink:scene xmlns:drafftink="{SCENE_NAMESPACE}" type="{SVG_SCENE_MIME}" encoding="base64">{}</drafftink:scene></metadata>"#,
+                STANDARD.encode(json)
+            );
+        }
+
+        if !self.defs.is_empty() || !self.fonts.is_empty() {
+            out.push_str("<defs>\n");
+            if !self.fonts.is_empty() {
+                out.push_str("<style>\n");
+                for font in &self.fonts {
+                    let _ = writeln!(
+                        out,
+                        "@font-face {{ font-family: '{}'; font-weight: {}; font-style: {}; src: url(data:font/ttf;base64,{}) format('truetype'); }}",
+                        font.name,
+                        if font.bold { "bold" } else { "normal" },
+                        if font.italic { "italic" } else { "normal" },
+                        STANDARD.encode(font.data)
+                    );
+                }
+                out.push_str("</style>\n");
+            }
+            out.push_str(&self.defs);
+            out.push_str("</defs>\n");
+        }
+
+        if let Some(color) = background {
+            let _ = writeln!(
+                out,
+                r#"<rect x="{}" y="{}" width="{}" height="{}"{}/>"#,
+                num(view.x0),
+                num(view.y0),
+                num(view.width()),
+                num(view.height()),
+                paint("fill", color)
+            );
+        }
+        out.push_str(&self.body);
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// Open a `<g>` with the given attributes; closed by `end_group`/`end_clip`.
+    fn open_group(&mut self, attrs: &str) {
+        let _ = writeln!(self.body, "<g {attrs}>");
+    }
+
+    fn embedded_text(&mut self, text: &Text, font: BundledFont, face: &ttf_parser::Face) {
+        if !self.fonts.contains(&font) {
+            self.fonts.push(font);
+        }
+        let default_color = text.style.stroke_with_opacity();
+        let _ = write!(
+            self.body,
+            r#"<text font-family="'{}'" font-size="{}"{}{}{} xml:space="preserve">"#,
+            font.name,
+            num(text.font_size),
+            if font.bold {
+                r#" font-weight="bold""#
+            } else {
+                ""
+            },
+            if font.italic {
+                r#" font-style="italic""#
+            } else {
+                ""
+            },
+            paint("fill", default_color)
+        );
+        for line in layout_lines(face, text) {
+            let _ = write!(
+                self.body,
+                r#"<tspan x="{}" y="{}">"#,
+                num(text.position.x),
+                num(line.baseline)
+            );
+            for (run, color) in color_runs(text, &line) {
+                if color == default_color {
+                    self.body.push_str(&escape_xml(&run));
+                } else {
+                    let _ = write!(
+                        self.body,
+                        "<tspan{}>{}</tspan>",
+                        paint("fill", color),
+                        escape_xml(&run)
+                    );
+                }
+            }
+            self.body.push_str("</tspan>");
+        }
+        self.body.push_str("</text>\n");
+    }
+
+    fn outlined_text(&mut self, text: &Text, face: &ttf_parser::Face) {
+        let scale = text.font_size / face.units_per_em() as f64;
+        for line in layout_lines(face, text) {
+            let mut x = text.position.x;
+            for (run, color) in color_runs(text, &line) {
+                let mut path = BezPath::new();
+                for ch in run.chars() {
+                    let Some(glyph) = face.glyph_index(ch) else {
+                        x += text.font_size * 0.5;
+                        continue;
+                    };
+                    let mut outline = GlyphPath(BezPath::new());
+                    if face.outline_glyph(glyph, &mut outline).is_some() {
+                        let transform = Affine::translate((x, line.baseline))
+                            * Affine::scale_non_uniform(scale, -scale);
+                        path.extend((transform * outline.0).elements().iter().copied());
+                    }
+                    x += face.glyph_hor_advance(glyph).unwrap_or(0) as f64 * scale;
+                }
+                if !path.elements().is_empty() {
+                    self.fill(&path, color);
+                }
+            }
+        }
+    }
+}
+
+impl MathSink for SvgWriter {
+    fn fill_path(&mut self, transform: Affine, color: Color, path: &BezPath) {
+        self.fill(&(transform * path.clone()), color);
+    }
+}
+
+impl VectorSink for SvgWriter {
+    fn begin_group(&mut self, transform: Affine) {
+        let [a, b, c, d, e, f] = transform.as_coeffs();
+        self.open_group(&format!(
+            r#"transform="matrix({} {} {} {} {} {})""#,
+            num(a),
+            num(b),
+            num(c),
+            num(d),
+            num(e),
+            num(f)
+        ));
+    }
+
+    fn end_group(&mut self) {
+        self.body.push_str("</g>\n");
+    }
+
+    fn begin_clip(&mut self, path: &BezPath) {
+        let clip_id = format!("clip{}", self.next_clip_id);
+        self.next_clip_id += 1;
+        let _ = writeln!(
+            self.defs,
+            r#"<clipPath id="{clip_id}"><path d="{}"/></clipPath>"#,
+            path.to_svg()
+        );
+        self.open_group(&format!(r#"clip-path="url(#{clip_id})""#));
+    }
+
+    fn end_clip(&mut self) {
+        self.body.push_str("</g>\n");
+    }
+
+    fn fill(&mut self, path: &BezPath, color: Color) {
+        let _ = writeln!(
+            self.body,
+            r#"<path d="{}"{}/>"#,
+            path.to_svg(),
+            paint("fill", color)
+        );
+    }
+
+    fn fill_even_odd(&mut self, path: &BezPath, color: Color) {
+        let _ = writeln!(
+            self.body,
+            r#"<path d="{}"{} fill-rule="evenodd"/>"#,
+            path.to_svg(),
+            paint("fill", color)
+        );
+    }
+
+    fn stroke(&mut self, path: &BezPath, color: Color, width: f64, dashes: Option<[f64; 2]>) {
+        let dash_attr = dashes
+            .map(|[dash, gap]| format!(r#" stroke-dasharray="{} {}""#, num(dash), num(gap)))
+            .unwrap_or_default();
+        let _ = writeln!(
+            self.body,
+            r#"<path d="{}" fill="none"{} stroke-width="{}" stroke-linecap="round" stroke-linejoin="round"{}/>"#,
+            path.to_svg(),
+            paint("stroke", color),
+            num(width),
+            dash_attr
+        );
+    }
+
+    fn text(&mut self, text: &Text) {
+        if text.content.is_empty() {
+            return;
+        }
+        let font = bundled_font(&text.font_family, &text.font_weight);
+        let Ok(face) = ttf_parser::Face::parse(font.data, 0) else {
+            return;
+        };
+        match self.text_mode {
+            SvgTextMode::EmbeddedFonts => self.embedded_text(text, font, &face),
+            SvgTextMode::Outlined => self.outlined_text(text, &face),
+        }
+    }
+
+    fn image(&mut self, image: &Image) {
+        let bounds = image.bounds();
+        let _ = writeln!(
+            self.body,
+            r#"<image x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="none" href="data:{};base64,{}"/>"#,
+            num(bounds.x0),
+            num(bounds.y0),
+            num(bounds.width()),
+            num(bounds.height()),
+            image.format.mime_type(),
+            image.asset.to_base64()
+        );
+    }
+}
+
+/// `fill`/`stroke` attribute with a separate opacity when not fully opaque.
+fn paint(attr: &str, color: Color) -> String {
+    let rgba = color.to_rgba8();
+    let mut out = format!(r##" {attr}="#{:02x}{:02x}{:02x}""##, rgba.r, rgba.g, rgba.b);
+    if rgba.a < 255 {
+        let _ = write!(out, r#" {attr}-opacity="{}""#, num(rgba.a as f64 / 255.0));
+    }
+    out
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use drafftink_core::shapes::{Rectangle, SerializableColor};
+    use kurbo::Point;
+
+    fn document() -> CanvasDocument {
+        let mut doc = CanvasDocument::new();
+        let mut rect = Rectangle::new(Point::new(10.0, 20.0), 100.0, 50.0);
+        rect.style.fill_color = Some(SerializableColor::new(255, 0, 0, 255));
+        doc.add_shape(Shape::Rectangle(rect));
+        let text = Text::new(Point::new(0.0, 100.0), "Hello <world>\nsecond".to_string());
+        doc.add_shape(Shape::Text(text));
+        doc
+    }
+
+    #[test]
+    fn test_export_document() {
+        let svg = export_document_svg(&document(), &SvgExportOptions::default()).unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(r##"fill="#ff0000""##));
+        // Text is real, escaped text with the font embedded
+        assert!(svg.contains("Hello &lt;world&gt;"));
+        assert!(svg.contains("@font-face"));
+        assert_eq!(svg.matches("<tspan x=").count(), 2);
+    }
+
+    #[test]
+    fn test_scene_round_trip() {
+        let doc = document();
+        let svg = export_document_svg(&doc, &SvgExportOptions::default()).unwrap();
+        let json = extract_scene_from_svg(&svg).unwrap();
+        let restored = CanvasDocument::from_json(&json).unwrap();
+        assert_eq!(restored.shapes.len(), doc.shapes.len());
+
+        let plain = SvgExportOptions {
+            embed_scene: false,
+            ..Default::default()
+        };
+        let svg = export_document_svg(&doc, &plain).unwrap();
+        assert!(extract_scene_from_svg(&svg).is_none());
+    }
+
+    #[test]
+    fn test_selection_padding_and_background() {
+        let doc = document();
+        let rect_id = doc.z_order[0];
+        let options = SvgExportOptions {
+            padding: 5.0,
+            background: None,
+            text_mode: SvgTextMode::Outlined,
+            embed_scene: true,
+        };
+        let svg = export_selection_svg(&doc, &[rect_id], &options).unwrap();
+        assert!(svg.contains(r#"viewBox="5 15 110 60""#));
+        assert!(!svg.contains("<rect"));
+        assert!(!svg.contains("<text"));
+
+        let json = extract_scene_from_svg(&svg).unwrap();
+        assert_eq!(CanvasDocument::from_json(&json).unwrap().shapes.len(), 1);
+        assert!(export_selection_svg(&doc, &[], &options).is_none());
+    }
+
+    #[test]
+    fn test_outlined_text() {
+        let options = SvgExportOptions {
+            text_mode: SvgTextMode::Outlined,
+            ..Default::default()
+        };
+        let svg = export_document_svg(&document(), &options).unwrap();
+        assert!(!svg.contains("<text"));
+        assert!(!svg.contains("@font-face"));
+    }
+
+    #[test]
+    fn test_num_formatting() {
+        assert_eq!(num(1.0), "1");
+        assert_eq!(num(1.23456), "1.235");
+        assert_eq!(num(-0.0001), "0");
+        assert_eq!(num(0.5), "0.5");
+    }
+}
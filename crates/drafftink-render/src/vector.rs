@@ -0,0 +1,301 @@
+//! Shape drawing shared by the vector exporters (SVG, PDF).
+//!
+//! [`draw_shape`] mirrors the canvas renderer: the same hand-drawn jitter, fill
+//! patterns, dashes, pressure outlines and math layout, drawn through a small
+//! [`VectorSink`] interface. Each exporter writes text and images in its own format;
+//! the CPU renderer rasterizes the same calls.
+
+use crate::rex_backend::{MathSink, render_math};
+use crate::sketch::{apply_hand_drawn_effect, generate_fill_pattern, pressure_outline};
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::shapes::{
+    FillPattern, Image, Shape, ShapeId, ShapeStyle, ShapeTrait, StrokeStyle, Text,
+};
+use drafftink_core::svg::{SvgItem, parse_svg};
+use kurbo::{Affine, BezPath, Rect, Shape as KurboShape};
+use peniko::Color;
+
+/// Output of the vector exporters.
+///
+/// Coordinates are document coordinates (y down). Groups and clips are strictly nested.
+pub(crate) trait VectorSink: MathSink {
+    /// Start a group drawn with an extra transform.
+    fn begin_group(&mut self, transform: Affine);
+    fn end_group(&mut self);
+    /// Start a group clipped to `path`.
+    fn begin_clip(&mut self, path: &BezPath);
+    fn end_clip(&mut self);
+    fn fill(&mut self, path: &BezPath, color: Color);
+    /// Fill with the even-odd rule, used by imported SVG graphics.
+    fn fill_even_odd(&mut self, path: &BezPath, color: Color);
+    /// Stroke with round caps and joins, optionally dashed (`[dash, gap]`).
+    fn stroke(&mut self, path: &BezPath, color: Color, width: f64, dashes: Option<[f64; 2]>);
+    fn text(&mut self, text: &Text);
+    fn image(&mut self, image: &Image);
+
+    /// Zoom the hand-drawn effect is computed for; exports are drawn at 1x.
+    fn zoom(&self) -> f64 {
+        1.0
+    }
+}
+
+/// The selected shapes in document z-order. Shapes inside groups, which are not
+/// in the top-level order, follow in selection order.
+pub(crate) fn selected_shapes<'a>(
+    document: &'a CanvasDocument,
+    selection: &[ShapeId],
+) -> Vec<&'a Shape> {
+    let mut shapes: Vec<&Shape> = document
+        .shapes_ordered()
+        .filter(|s| selection.contains(&s.id()))
+        .collect();
+    for &id in selection {
+        if shapes.iter().any(|s| s.id() == id) {
+            continue;
+        }
+        if let Some(shape) = document.get_shape(id) {
+            shapes.push(shape);
+        }
+    }
+    shapes
+}
+
+/// Union of the rotated bounds of `shapes`, or `None` if there are none.
+pub(crate) fn content_bounds(shapes: &[&Shape]) -> Option<Rect> {
+    shapes
+        .iter()
+        .map(|s| s.rotated_bounds())
+        .reduce(|a, b| a.union(b))
+}
+
+/// Draw a shape the way the canvas renderer does.
+pub(crate) fn draw_shape<S: VectorSink>(sink: &mut S, shape: &Shape) {
+    // Groups draw their children rotated with the group, like the canvas renderer
+    if let Shape::Group(group) = shape {
+        let rotated = group.rotation.abs() > 0.001;
+        if rotated {
+            sink.begin_group(group.child_transform());
+        }
+        for child in group.children() {
+            draw_shape(sink, child);
+        }
+        if rotated {
+            sink.end_group();
+        }
+        return;
+    }
+
+    let rotation = shape.rotation();
+    let rotated = rotation.abs() > 0.001;
+    if rotated {
+        sink.begin_group(Affine::rotate_about(rotation, shape.bounds().center()));
+    }
+
+    match shape {
+        Shape::Text(text) => sink.text(text),
+        Shape::Image(image) => sink.image(image),
+        Shape::Math(math) => {
+            if render_math(math, Affine::IDENTITY, sink).is_err() {
+                // Same placeholder as the canvas renderer
+                let outline = math.bounds().to_path(0.1);
+                sink.fill(&outline, Color::from_rgba8(255, 200, 200, 100));
+                sink.stroke(&outline, Color::from_rgba8(255, 100, 100, 255), 1.0, None);
+            }
+        }
+        Shape::Line(line) => {
+            let path = shape.to_path();
+            if line.is_closed() {
+                styled_fill(sink, &path, shape.style());
+            }
+            stroke_only(sink, &path, shape.style(), line.stroke_style)
+        }
+        Shape::Arrow(arrow) => {
+            stroke_only(sink, &shape.to_path(), shape.style(), arrow.stroke_style)
+        }
+        Shape::Freehand(freehand) => {
+            if freehand.has_pressure() {
+                // Pressure strokes are filled outlines (no sloppiness)
+                if let Some(outline) = pressure_outline(freehand) {
+                    sink.fill(&outline, freehand.style.stroke_with_opacity());
+                }
+            } else {
+                stroke_only(sink, &shape.to_path(), shape.style(), StrokeStyle::Solid);
+            }
+        }
+        _ => styled_path(sink, &shape.to_path(), shape.style()),
+    }
+
+    if rotated {
+        sink.end_group();
+    }
+}
+
+/// A closed shape: fill (solid or pattern) and hand-drawn strokes.
+fn styled_path<S: VectorSink>(sink: &mut S, path: &BezPath, style: &ShapeStyle) {
+    styled_fill(sink, path, style);
+    strokes(sink, path, style, None);
+}
+
+/// Solid or pattern fill of a closed path, if the style has a fill color.
+fn styled_fill<S: VectorSink>(sink: &mut S, path: &BezPath, style: &ShapeStyle) {
+    let roughness = style.sloppiness.roughness();
+
+    if let Some(fill_color) = style.fill_with_opacity() {
+        let fill_path = if roughness > 0.0 {
+            apply_hand_drawn_effect(path, roughness * 0.3, sink.zoom(), style.seed, 0)
+        } else {
+            path.clone()
+        };
+
+        match style.fill_pattern {
+            FillPattern::Solid => sink.fill(&fill_path, fill_color),
+            pattern => {
+                // Light wash of the fill color behind the pattern lines
+                let rgba = fill_color.to_rgba8();
+                let wash = Color::from_rgba8(rgba.r, rgba.g, rgba.b, (rgba.a as f32 * 0.15) as u8);
+                sink.fill(&fill_path, wash);
+
+                let pattern_path = generate_fill_pattern(
+                    pattern,
+                    path.bounding_box(),
+                    style.stroke_width,
+                    style.seed,
+                );
+                sink.begin_clip(&fill_path);
+                sink.stroke(&pattern_path, fill_color, style.stroke_width * 0.5, None);
+                sink.end_clip();
+            }
+        }
+    }
+}
+
+/// Draw an SVG image's content as vector graphics, scaled into the image bounds.
+/// Returns `false` if the document can't be parsed.
+pub(crate) fn draw_svg_image<S: VectorSink>(sink: &mut S, image: &Image) -> bool {
+    let Some(graphic) = std::str::from_utf8(image.data())
+        .ok()
+        .and_then(|source| parse_svg(source).ok())
+    else {
+        return false;
+    };
+    let bounds = image.bounds();
+    sink.begin_group(
+        Affine::translate(bounds.origin().to_vec2())
+            * Affine::scale_non_uniform(
+                bounds.width() / graphic.size.width,
+                bounds.height() / graphic.size.height,
+            ),
+    );
+    for item in &graphic.items {
+        match item {
+            SvgItem::Path(path) => {
+                if let Some(fill) = path.fill {
+                    if path.even_odd {
+                        sink.fill_even_odd(&path.path, fill.into());
+                    } else {
+                        sink.fill(&path.path, fill.into());
+                    }
+                }
+                if let Some(stroke) = path.stroke {
+                    sink.stroke(&path.path, stroke.into(), path.stroke_width, None);
+                }
+            }
+            SvgItem::Text(text) => sink.text(&text.to_text()),
+            SvgItem::Image(nested) => sink.image(&nested.to_image()),
+        }
+    }
+    sink.end_group();
+    true
+}
+
+/// An open shape: strokes only, with an optional dash pattern.
+fn stroke_only<S: VectorSink>(
+    sink: &mut S,
+    path: &BezPath,
+    style: &ShapeStyle,
+    stroke_style: StrokeStyle,
+) {
+    let dashes = match stroke_style {
+        StrokeStyle::Solid => None,
+        StrokeStyle::Dashed => Some([style.stroke_width * 4.0, style.stroke_width * 2.0]),
+        StrokeStyle::Dotted => Some([style.stroke_width, style.stroke_width * 2.0]),
+    };
+    strokes(sink, path, style, dashes);
+}
+
+/// Two jittered strokes for hand-drawn styles, one clean stroke otherwise.
+fn strokes<S: VectorSink>(
+    sink: &mut S,
+    path: &BezPath,
+    style: &ShapeStyle,
+    dashes: Option<[f64; 2]>,
+) {
+    let roughness = style.sloppiness.roughness();
+    let color = style.stroke_with_opacity();
+    if roughness > 0.0 {
+        for stroke_index in 0..2 {
+            let rough =
+                apply_hand_drawn_effect(path, roughness, sink.zoom(), style.seed, stroke_index);
+            sink.stroke(&rough, color, style.stroke_width, dashes);
+        }
+    } else {
+        sink.stroke(path, color, style.stroke_width, dashes);
+    }
+}
+
+/// One line of laid-out text.
+pub(crate) struct TextLine {
+    pub text: String,
+    /// Index of the line's first character in the text content.
+    pub first_char: usize,
+    pub baseline: f64,
+}
+
+/// Split text into lines with baselines from the font's vertical metrics,
+/// matching Parley's default line height.
+pub(crate) fn layout_lines(face: &ttf_parser::Face, text: &Text) -> Vec<TextLine> {
+    let scale = text.font_size / face.units_per_em() as f64;
+    let ascent = face.ascender() as f64 * scale;
+    let line_height =
+        (face.ascender() as f64 - face.descender() as f64 + face.line_gap() as f64) * scale;
+
+    let mut lines = Vec::new();
+    let mut first_char = 0;
+    for (i, line) in text.content.split('\n').enumerate() {
+        lines.push(TextLine {
+            text: line.to_string(),
+            first_char,
+            baseline: text.position.y + ascent + i as f64 * line_height,
+        });
+        first_char += line.chars().count() + 1;
+    }
+    lines
+}
+
+/// Split a line into runs of equally colored characters.
+pub(crate) fn color_runs(text: &Text, line: &TextLine) -> Vec<(String, Color)> {
+    let default = text.style.stroke_with_opacity();
+    let mut runs: Vec<(String, Color)> = Vec::new();
+    for (offset, ch) in line.text.chars().enumerate() {
+        let color = match text.char_colors.get(line.first_char + offset) {
+            Some(Some(color)) => (*color).into(),
+            _ => default,
+        };
+        match runs.last_mut() {
+            Some((run, run_color)) if *run_color == color => run.push(ch),
+            _ => runs.push((ch.to_string(), color)),
+        }
+    }
+    runs
+}
+
+/// Format a number compactly (at most 3 decimals, no trailing zeros).
+pub(crate) fn num(value: f64) -> String {
+    let s = format!("{:.3}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    if s == "-0" {
+        "0".to_string()
+    } else {
+        s.to_string()
+    }
+}
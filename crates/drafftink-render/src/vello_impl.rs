@@ -1,44 +1,25 @@
 //! Vello-based renderer implementation.
 
+use crate::diff_overlay::DiffOutline;
+use crate::fonts::{
+    GELPEN_HEAVY, GELPEN_LIGHT, GELPEN_REGULAR, GELPEN_SERIF_HEAVY, GELPEN_SERIF_LIGHT,
+    GELPEN_SERIF_MEDIUM, NOTO_SANS, NOTO_SANS_BOLD, NOTO_SANS_ITALIC, VANILLA_EXTRACT,
+    bundled_font,
+};
 use crate::renderer::{RenderContext, Renderer, ShapeRenderer};
+use crate::sketch::{apply_hand_drawn_effect, generate_fill_pattern, pressure_outline};
 use crate::text_editor::TextEditState;
 use drafftink_core::selection::{Handle, HandleKind, get_handles};
-use drafftink_core::shapes::{FillPattern, Shape, ShapeStyle, ShapeTrait, StrokeStyle};
-use kurbo::{Affine, BezPath, PathEl, Point, Rect, Shape as KurboShape, Stroke};
+use drafftink_core::shapes::{
+    FillPattern, Image, ImageFormat, Shape, ShapeStyle, ShapeTrait, StrokeStyle, Text,
+};
+use drafftink_core::svg::{SvgGraphic, SvgItem, parse_svg};
+use kurbo::{Affine, BezPath, Point, Rect, Shape as KurboShape, Stroke};
 use parley::layout::PositionedLayoutItem;
 use parley::{FontContext, LayoutContext};
 use peniko::{Brush, Color, Fill};
-use roughr::core::{FillStyle, OptionsBuilder};
 use vello::Scene;
 
-/// Result of PNG rendering - contains the raw RGBA pixel data and dimensions.
-#[derive(Debug)]
-pub struct PngRenderResult {
-    /// RGBA pixel data (4 bytes per pixel).
-    pub rgba_data: Vec<u8>,
-    /// Image width in pixels.
-    pub width: u32,
-    /// Image height in pixels.
-    pub height: u32,
-}
-
-/// Embedded GelPen fonts (Regular, Light, Heavy variants)
-static GELPEN_REGULAR: &[u8] = include_bytes!("../assets/GelPen.ttf");
-static GELPEN_LIGHT: &[u8] = include_bytes!("../assets/GelPenLight.ttf");
-static GELPEN_HEAVY: &[u8] = include_bytes!("../assets/GelPenHeavy.ttf");
-/// Embedded VanillaExtract font
-static VANILLA_EXTRACT: &[u8] = include_bytes!("../assets/VanillaExtract.ttf");
-/// Embedded GelPenSerif fonts for handwritten style
-static GELPEN_SERIF_LIGHT: &[u8] = include_bytes!("../assets/GelPenSerifLight.ttf");
-static GELPEN_SERIF_MEDIUM: &[u8] = include_bytes!("../assets/GelPenSerifMedium.ttf");
-static GELPEN_SERIF_HEAVY: &[u8] = include_bytes!("../assets/GelPenSerifHeavy.ttf");
-/// Embedded XITS Math font for LaTeX rendering
-static XITS_MATH: &[u8] = include_bytes!("../assets/rex-xits.otf");
-/// Embedded Noto Sans for UI elements
-static NOTO_SANS: &[u8] = include_bytes!("../assets/NotoSans-Regular.ttf");
-static NOTO_SANS_BOLD: &[u8] = include_bytes!("../assets/NotoSans-Bold.ttf");
-static NOTO_SANS_ITALIC: &[u8] = include_bytes!("../assets/NotoSans-Italic.ttf");
-
 /// Cached text layout data for rendering.
 #[derive(Clone)]
 struct CachedTextLayout {
@@ -54,6 +35,14 @@ struct CachedTextLayout {
     height: f64,
 }
 
+/// Parsed content of an SVG image. Text and nested images are kept as shapes so
+/// their layouts and decoded pixels stay cached under stable IDs.
+struct CachedSvg {
+    graphic: SvgGraphic,
+    texts: Vec<Text>,
+    images: Vec<Image>,
+}
+
 /// Vello-based renderer for GPU-accelerated 2D graphics.
 pub struct VelloRenderer {
     /// The Vello scene being built.
@@ -67,8 +56,10 @@ pub struct VelloRenderer {
     /// Current zoom level (for zoom-independent UI elements).
     zoom: f64,
     /// Image cache to avoid re-decoding images every frame.
-    /// Key is the shape ID (as string), value is the decoded peniko ImageData.
+    /// Key is the asset ID, so images of the same content are decoded once.
     image_cache: std::collections::HashMap<String, peniko::ImageData>,
+    /// Parsed SVG images by asset ID (`None` if the document failed to parse).
+    svg_cache: std::collections::HashMap<String, Option<std::sync::Arc<CachedSvg>>>,
     /// Shape path cache for hand-drawn effects.
     /// Key: (shape_id, seed, stroke_index, roughness_bits, zoom_bucket, path_hash)
     shape_cache: std::collections::HashMap<(String, u32, u32, u64, i32, u64), BezPath>,
@@ -86,224 +77,6 @@ impl Default for VelloRenderer {
 fn convert_rect(rect: &parley::BoundingBox) -> Rect {
     Rect::new(rect.x0, rect.y0, rect.x1, rect.y1)
 }
-
-/// Simple seeded random number generator (xorshift32).
-/// Used for deterministic hand-drawn effects.
-struct SimpleRng {
-    state: u32,
-}
-
-impl SimpleRng {
-    fn new(seed: u32) -> Self {
-        Self { state: seed.max(1) }
-    }
-
-    fn next_u32(&mut self) -> u32 {
-        let mut x = self.state;
-        x ^= x << 13;
-        x ^= x >> 17;
-        x ^= x << 5;
-        self.state = x;
-        x
-    }
-
-    /// Random float in range [-1, 1]
-    fn next_f64(&mut self) -> f64 {
-        (self.next_u32() as f64 / u32::MAX as f64) * 2.0 - 1.0
-    }
-
-    /// Random offset scaled by amount
-    fn offset(&mut self, amount: f64) -> f64 {
-        self.next_f64() * amount
-    }
-}
-
-/// Apply hand-drawn effect to a path based on roughness level.
-/// This mimics the Excalidraw/rough.js algorithm:
-/// - Endpoints are randomly offset (lines overshoot/undershoot at corners)
-/// - Lines have a slight bow (curve in the middle)
-/// - Each stroke_index produces completely different randomness
-///
-/// roughness: 0 = clean, 1 = slight wobble, 2 = very sketchy
-/// seed: stable random seed from the shape's style (persisted, doesn't change on transform)
-/// stroke_index: 0 or 1 for multi-stroke effect (different random offsets)
-fn apply_hand_drawn_effect(
-    path: &BezPath,
-    roughness: f64,
-    zoom: f64,
-    seed: u32,
-    stroke_index: u32,
-) -> BezPath {
-    if roughness <= 0.0 {
-        return path.clone();
-    }
-
-    // Scale effect inversely with zoom so it looks consistent at all zoom levels
-    let scale = 1.0 / zoom.sqrt();
-
-    // Values tuned to match Excalidraw/rough.js feel
-    // These create the "overshoot" effect at corners
-    let max_randomness_offset = roughness * 2.0 * scale;
-    let bowing = roughness * 1.0;
-
-    // Use the shape's stable seed combined with stroke_index for deterministic randomness
-    // The seed is stored in the shape's style, so it doesn't change when the shape is transformed
-    let combined_seed = seed.wrapping_add(stroke_index.wrapping_mul(99991)); // Large prime for very different sequences
-    let mut rng = SimpleRng::new(combined_seed);
-
-    let mut result = BezPath::new();
-    let mut last_point = Point::ZERO;
-
-    for el in path.elements() {
-        match el {
-            PathEl::MoveTo(p) => {
-                // Offset the start point
-                let wobbled = Point::new(
-                    p.x + rng.offset(max_randomness_offset),
-                    p.y + rng.offset(max_randomness_offset),
-                );
-                result.move_to(wobbled);
-                last_point = *p;
-            }
-            PathEl::LineTo(p) => {
-                // This is the key rough.js algorithm for lines:
-                // 1. Calculate line length
-                // 2. Add bowing (perpendicular offset at midpoint)
-                // 3. Offset both endpoints randomly (creates overshoot)
-
-                let dx = p.x - last_point.x;
-                let dy = p.y - last_point.y;
-                let len = (dx * dx + dy * dy).sqrt();
-
-                // Calculate bowing amount - proportional to length
-                let bow_offset = bowing * roughness * len / 200.0;
-                let bow = rng.offset(bow_offset) * scale;
-
-                // Perpendicular vector for bowing
-                let (perp_x, perp_y) = if len > 0.001 {
-                    (-dy / len, dx / len)
-                } else {
-                    (0.0, 0.0)
-                };
-
-                // Control point with bowing
-                let mid_x = (last_point.x + p.x) / 2.0 + perp_x * bow;
-                let mid_y = (last_point.y + p.y) / 2.0 + perp_y * bow;
-
-                // End point with random offset (creates overshoot at corners)
-                let end = Point::new(
-                    p.x + rng.offset(max_randomness_offset),
-                    p.y + rng.offset(max_randomness_offset),
-                );
-
-                // Use quadratic bezier for the bowed line
-                result.quad_to(Point::new(mid_x, mid_y), end);
-                last_point = *p;
-            }
-            PathEl::QuadTo(p1, p2) => {
-                let wobbled_p1 = Point::new(
-                    p1.x + rng.offset(max_randomness_offset * 0.7),
-                    p1.y + rng.offset(max_randomness_offset * 0.7),
-                );
-                let wobbled_p2 = Point::new(
-                    p2.x + rng.offset(max_randomness_offset),
-                    p2.y + rng.offset(max_randomness_offset),
-                );
-                result.quad_to(wobbled_p1, wobbled_p2);
-                last_point = *p2;
-            }
-            PathEl::CurveTo(p1, p2, p3) => {
-                let wobbled_p1 = Point::new(
-                    p1.x + rng.offset(max_randomness_offset * 0.5),
-                    p1.y + rng.offset(max_randomness_offset * 0.5),
-                );
-                let wobbled_p2 = Point::new(
-                    p2.x + rng.offset(max_randomness_offset * 0.5),
-                    p2.y + rng.offset(max_randomness_offset * 0.5),
-                );
-                let wobbled_p3 = Point::new(
-                    p3.x + rng.offset(max_randomness_offset),
-                    p3.y + rng.offset(max_randomness_offset),
-                );
-                result.curve_to(wobbled_p1, wobbled_p2, wobbled_p3);
-                last_point = *p3;
-            }
-            PathEl::ClosePath => {
-                // Don't close - let the overshoot show at the closing corner too
-                // The path visually closes but endpoints won't match perfectly
-                result.close_path();
-            }
-        }
-    }
-
-    result
-}
-
-/// Generate fill pattern lines within the given bounds using roughr.
-fn generate_fill_pattern(
-    pattern: FillPattern,
-    bounds: Rect,
-    stroke_width: f64,
-    seed: u32,
-) -> BezPath {
-    use roughr::core::{OpSetType, OpType};
-
-    let fill_style = match pattern {
-        FillPattern::Solid => return BezPath::new(),
-        FillPattern::Hachure => FillStyle::Hachure,
-        FillPattern::ZigZag => FillStyle::ZigZag,
-        FillPattern::CrossHatch => FillStyle::CrossHatch,
-        FillPattern::Dots => FillStyle::Dots,
-        FillPattern::Dashed => FillStyle::Dashed,
-        FillPattern::ZigZagLine => FillStyle::ZigZagLine,
-    };
-
-    let fill_color: roughr::Srgba = roughr::Srgba::new(0.0, 0.0, 0.0, 1.0);
-    let options = OptionsBuilder::default()
-        .seed(seed as u64)
-        .fill_style(fill_style)
-        .fill(fill_color)
-        .stroke(fill_color)
-        .fill_weight((stroke_width * 0.5) as f32)
-        .hachure_gap((stroke_width * 4.0) as f32)
-        .build()
-        .unwrap();
-
-    let generator = roughr::generator::Generator::default();
-    let drawing = generator.rectangle::<f64>(
-        bounds.x0,
-        bounds.y0,
-        bounds.width(),
-        bounds.height(),
-        &Some(options),
-    );
-
-    // Extract FillSketch ops directly from drawable.sets (like the official vello example)
-    let mut path = BezPath::new();
-    for set in drawing.sets.iter() {
-        if set.op_set_type == OpSetType::FillSketch {
-            for op in set.ops.iter() {
-                match op.op {
-                    OpType::Move => {
-                        path.move_to(Point::new(op.data[0], op.data[1]));
-                    }
-                    OpType::LineTo => {
-                        path.line_to(Point::new(op.data[0], op.data[1]));
-                    }
-                    OpType::BCurveTo => {
-                        path.curve_to(
-                            Point::new(op.data[0], op.data[1]),
-                            Point::new(op.data[2], op.data[3]),
-                            Point::new(op.data[4], op.data[5]),
-                        );
-                    }
-                }
-            }
-        }
-    }
-    path
-}
-
 impl VelloRenderer {
     /// Create a new Vello renderer.
     pub fn new() -> Self {
@@ -357,6 +130,7 @@ impl VelloRenderer {
             layout_cx: LayoutContext::new(),
             zoom: 1.0,
             image_cache: std::collections::HashMap::new(),
+            svg_cache: std::collections::HashMap::new(),
             shape_cache: std::collections::HashMap::new(),
             text_cache: std::collections::HashMap::new(),
         }
@@ -583,11 +357,54 @@ impl VelloRenderer {
         path: &BezPath,
         style: &ShapeStyle,
         transform: Affine,
+    ) {
+        self.render_fill(shape_id, path, style, transform);
+
+        let roughness = style.sloppiness.roughness();
+        let seed = style.seed;
+
+        // For hand-drawn style, draw multiple strokes like rough.js
+        if roughness > 0.0 {
+            let stroke = Stroke::new(style.stroke_width);
+
+            // First stroke
+            let path1 = self.get_cached_hand_drawn(shape_id, path, roughness, seed, 0);
+            self.scene.stroke(
+                &stroke,
+                transform,
+                style.stroke_with_opacity(),
+                None,
+                &path1,
+            );
+
+            // Second stroke with different seed
+            let path2 = self.get_cached_hand_drawn(shape_id, path, roughness, seed, 1);
+            self.scene.stroke(
+                &stroke,
+                transform,
+                style.stroke_with_opacity(),
+                None,
+                &path2,
+            );
+        } else {
+            // Clean stroke for Architect mode
+            let stroke = Stroke::new(style.stroke_width);
+            self.scene
+                .stroke(&stroke, transform, style.stroke_with_opacity(), None, path);
+        }
+    }
+
+    /// Fill a closed path with the style's fill color and pattern, if any.
+    fn render_fill(
+        &mut self,
+        shape_id: &str,
+        path: &BezPath,
+        style: &ShapeStyle,
+        transform: Affine,
     ) {
         let roughness = style.sloppiness.roughness();
         let seed = style.seed;
 
-        // Fill if present
         if let Some(fill_color) = style.fill_with_opacity() {
             let fill_path = if roughness > 0.0 {
                 self.get_cached_hand_drawn(shape_id, path, roughness * 0.3, seed, 0)
@@ -625,36 +442,6 @@ impl VelloRenderer {
                 }
             }
         }
-
-        // For hand-drawn style, draw multiple strokes like rough.js
-        if roughness > 0.0 {
-            let stroke = Stroke::new(style.stroke_width);
-
-            // First stroke
-            let path1 = self.get_cached_hand_drawn(shape_id, path, roughness, seed, 0);
-            self.scene.stroke(
-                &stroke,
-                transform,
-                style.stroke_with_opacity(),
-                None,
-                &path1,
-            );
-
-            // Second stroke with different seed
-            let path2 = self.get_cached_hand_drawn(shape_id, path, roughness, seed, 1);
-            self.scene.stroke(
-                &stroke,
-                transform,
-                style.stroke_with_opacity(),
-                None,
-                &path2,
-            );
-        } else {
-            // Clean stroke for Architect mode
-            let stroke = Stroke::new(style.stroke_width);
-            self.scene
-                .stroke(&stroke, transform, style.stroke_with_opacity(), None, path);
-        }
     }
 
     /// Render a path with stroke only (no fill) - used for lines and arrows.
@@ -713,81 +500,11 @@ impl VelloRenderer {
         freehand: &drafftink_core::shapes::Freehand,
         transform: Affine,
     ) {
-        use kurbo::Vec2;
-
-        if freehand.points.len() < 2 {
+        let Some(path) = pressure_outline(freehand) else {
             return;
-        }
-
-        let style = &freehand.style;
-        let base_size = style.stroke_width * 2.3;
-        let thinning = 0.6;
-        let color = style.stroke_with_opacity();
-
-        // Build a filled polygon that represents the variable-width stroke
-        let mut left_points: Vec<Point> = Vec::new();
-        let mut right_points: Vec<Point> = Vec::new();
-
-        for i in 0..freehand.points.len() {
-            let point = freehand.points[i];
-            let pressure = freehand.pressure_at(i);
-            // Apply easing: sin((pressure * π) / 2)
-            let eased_pressure = (pressure * std::f64::consts::PI / 2.0).sin();
-            // Apply thinning: size * (1 - thinning * (1 - pressure))
-            let width = base_size * (1.0 - thinning * (1.0 - eased_pressure));
-
-            // Calculate perpendicular direction
-            let dir = if i == 0 {
-                // First point: use direction to next point
-                let next = freehand.points[i + 1];
-                Vec2::new(next.x - point.x, next.y - point.y)
-            } else if i == freehand.points.len() - 1 {
-                // Last point: use direction from previous point
-                let prev = freehand.points[i - 1];
-                Vec2::new(point.x - prev.x, point.y - prev.y)
-            } else {
-                // Middle points: average of incoming and outgoing directions
-                let prev = freehand.points[i - 1];
-                let next = freehand.points[i + 1];
-                Vec2::new(next.x - prev.x, next.y - prev.y)
-            };
-
-            let len = dir.hypot();
-            if len < f64::EPSILON {
-                continue;
-            }
-
-            // Perpendicular unit vector
-            let perp = Vec2::new(-dir.y / len, dir.x / len);
-            let half_width = width / 2.0;
-
-            left_points.push(Point::new(
-                point.x + perp.x * half_width,
-                point.y + perp.y * half_width,
-            ));
-            right_points.push(Point::new(
-                point.x - perp.x * half_width,
-                point.y - perp.y * half_width,
-            ));
-        }
-
-        if left_points.is_empty() {
-            return;
-        }
-
-        // Build the path: left side forward, right side backward
-        let mut path = BezPath::new();
-        path.move_to(left_points[0]);
-        for point in left_points.iter().skip(1) {
-            path.line_to(*point);
-        }
-        // Connect to right side (reversed)
-        for point in right_points.iter().rev() {
-            path.line_to(*point);
-        }
-        path.close_path();
-
+        };
         // Fill the path (no sloppiness - freehand is already hand-drawn)
+        let color = freehand.style.stroke_with_opacity();
         self.scene
             .fill(Fill::NonZero, transform, color, None, &path);
     }
@@ -816,8 +533,6 @@ impl VelloRenderer {
             return;
         }
 
-        use drafftink_core::shapes::{FontFamily, FontWeight};
-
         // Build cache key from content hash
         let mut hasher = std::collections::hash_map::DefaultHasher::new();
         text.content.hash(&mut hasher);
@@ -836,6 +551,7 @@ impl VelloRenderer {
         text.style.stroke_color.r.hash(&mut hasher);
         text.style.stroke_color.g.hash(&mut hasher);
         text.style.stroke_color.b.hash(&mut hasher);
+        text.style.opacity.to_bits().hash(&mut hasher);
         let cache_key = (text.id().to_string(), hasher.finish());
 
         // Check cache
@@ -861,38 +577,14 @@ impl VelloRenderer {
         let brush = Brush::Solid(style.stroke_with_opacity());
         let font_size = text.font_size as f32;
 
-        let (font_name, parley_weight, is_italic) = match (&text.font_family, &text.font_weight) {
-            (FontFamily::GelPen, FontWeight::Light) => {
-                ("GelPenLight", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::GelPen, FontWeight::Regular) => {
-                ("GelPen", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::GelPen, FontWeight::Heavy) => {
-                ("GelPenHeavy", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::NotoSans, FontWeight::Light) => {
-                ("Noto Sans", parley::FontWeight::NORMAL, true)
-            }
-            (FontFamily::NotoSans, FontWeight::Regular) => {
-                ("Noto Sans", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::NotoSans, FontWeight::Heavy) => {
-                ("Noto Sans", parley::FontWeight::BOLD, false)
-            }
-            (FontFamily::GelPenSerif, FontWeight::Light) => {
-                ("GelPenSerifLight", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::GelPenSerif, FontWeight::Regular) => {
-                ("GelPenSerif", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::GelPenSerif, FontWeight::Heavy) => {
-                ("GelPenSerifHeavy", parley::FontWeight::NORMAL, false)
-            }
-            (FontFamily::VanillaExtract, _) => {
-                ("Vanilla Extract", parley::FontWeight::NORMAL, false)
-            }
+        let font = bundled_font(&text.font_family, &text.font_weight);
+        let font_name = font.name;
+        let parley_weight = if font.bold {
+            parley::FontWeight::BOLD
+        } else {
+            parley::FontWeight::NORMAL
         };
+        let is_italic = font.italic;
 
         let mut builder =
             self.layout_cx
@@ -1019,35 +711,34 @@ impl VelloRenderer {
     fn render_image(&mut self, image: &drafftink_core::shapes::Image, transform: Affine) {
         use std::sync::Arc;
 
-        let id_str = image.id().to_string();
+        if image.format == ImageFormat::Svg {
+            self.render_svg_image(image, transform);
+            return;
+        }
+
+        let asset_id = image.asset.id();
 
         // Check if we have a cached decoded image
-        let image_data = if let Some(cached) = self.image_cache.get(&id_str) {
+        let image_data = if let Some(cached) = self.image_cache.get(asset_id) {
             cached.clone()
         } else {
-            // Decode the image data
-            if let Some(raw_data) = image.data() {
-                // Try to decode using the image crate
-                if let Ok(decoded) = ::image::load_from_memory(&raw_data) {
-                    let rgba = decoded.to_rgba8();
-                    let (width, height) = rgba.dimensions();
-                    let blob = peniko::Blob::new(Arc::new(rgba.into_vec()));
-                    let img_data = peniko::ImageData {
-                        data: blob,
-                        format: peniko::ImageFormat::Rgba8,
-                        width,
-                        height,
-                        alpha_type: peniko::ImageAlphaType::Alpha,
-                    };
-                    self.image_cache.insert(id_str.clone(), img_data.clone());
-                    img_data
-                } else {
-                    // Failed to decode - draw placeholder
-                    self.render_image_placeholder(image, transform);
-                    return;
-                }
+            // Try to decode using the image crate
+            if let Ok(decoded) = ::image::load_from_memory(image.data()) {
+                let rgba = decoded.to_rgba8();
+                let (width, height) = rgba.dimensions();
+                let blob = peniko::Blob::new(Arc::new(rgba.into_vec()));
+                let img_data = peniko::ImageData {
+                    data: blob,
+                    format: peniko::ImageFormat::Rgba8,
+                    width,
+                    height,
+                    alpha_type: peniko::ImageAlphaType::Alpha,
+                };
+                self.image_cache
+                    .insert(asset_id.to_string(), img_data.clone());
+                img_data
             } else {
-                // No data - draw placeholder
+                // Failed to decode - draw placeholder
                 self.render_image_placeholder(image, transform);
                 return;
             }
@@ -1065,6 +756,84 @@ impl VelloRenderer {
         self.scene.draw_image(&image_data.into(), image_transform);
     }
 
+    /// Render an SVG image as vector graphics, scaled into the image bounds.
+    fn render_svg_image(&mut self, image: &Image, transform: Affine) {
+        let asset_id = image.asset.id();
+        let cached = match self.svg_cache.get(asset_id) {
+            Some(cached) => cached.clone(),
+            None => {
+                let cached = std::str::from_utf8(image.data())
+                    .ok()
+                    .and_then(|source| parse_svg(source).ok())
+                    .map(|graphic| {
+                        let mut texts = Vec::new();
+                        let mut images = Vec::new();
+                        for item in &graphic.items {
+                            match item {
+                                SvgItem::Text(text) => texts.push(text.to_text()),
+                                SvgItem::Image(nested) => images.push(nested.to_image()),
+                                SvgItem::Path(_) => {}
+                            }
+                        }
+                        std::sync::Arc::new(CachedSvg {
+                            graphic,
+                            texts,
+                            images,
+                        })
+                    });
+                self.svg_cache.insert(asset_id.to_string(), cached.clone());
+                cached
+            }
+        };
+        let Some(svg) = cached else {
+            self.render_image_placeholder(image, transform);
+            return;
+        };
+
+        let bounds = image.bounds();
+        let size = svg.graphic.size;
+        let svg_transform = transform
+            * Affine::translate((bounds.x0, bounds.y0))
+            * Affine::scale_non_uniform(bounds.width() / size.width, bounds.height() / size.height);
+
+        let mut texts = svg.texts.iter();
+        let mut images = svg.images.iter();
+        for item in &svg.graphic.items {
+            match item {
+                SvgItem::Path(path) => {
+                    if let Some(fill) = path.fill {
+                        let rule = if path.even_odd {
+                            Fill::EvenOdd
+                        } else {
+                            Fill::NonZero
+                        };
+                        self.scene
+                            .fill(rule, svg_transform, Color::from(fill), None, &path.path);
+                    }
+                    if let Some(stroke) = path.stroke {
+                        self.scene.stroke(
+                            &Stroke::new(path.stroke_width),
+                            svg_transform,
+                            Color::from(stroke),
+                            None,
+                            &path.path,
+                        );
+                    }
+                }
+                SvgItem::Text(_) => {
+                    if let Some(text) = texts.next() {
+                        self.render_text(text, svg_transform);
+                    }
+                }
+                SvgItem::Image(_) => {
+                    if let Some(nested) = images.next() {
+                        self.render_image(nested, svg_transform);
+                    }
+                }
+            }
+        }
+    }
+
     /// Render a placeholder for images that couldn't be loaded.
     fn render_image_placeholder(
         &mut self,
@@ -1110,62 +879,9 @@ impl VelloRenderer {
 
     /// Render a math (LaTeX) shape using ReX.
     fn render_math(&mut self, math: &drafftink_core::shapes::Math, transform: Affine) {
-        use crate::rex_backend::VelloBackend;
-        use rex::font::backend::ttf_parser::TtfMathFont;
-        use rex::layout::engine::LayoutBuilder;
-        use rex::render::Renderer as RexRenderer;
-
-        // Parse fonts
-        let Ok(math_face) = ttf_parser::Face::parse(XITS_MATH, 0) else {
-            self.render_math_error(math, transform, "Font parse error");
-            return;
-        };
-        let Ok(math_font) = TtfMathFont::new(math_face) else {
-            self.render_math_error(math, transform, "No MATH table");
-            return;
-        };
-        // Primary font (GelPen) for text glyphs - fallback to math font if unavailable
-        let primary_face = ttf_parser::Face::parse(GELPEN_REGULAR, 0).ok();
-
-        // Parse LaTeX
-        let Ok(parse_nodes) = rex::parser::parse(&math.latex) else {
-            self.render_math_error(math, transform, "Parse error");
-            return;
-        };
-
-        // Layout
-        let layout_engine = LayoutBuilder::new(&math_font)
-            .font_size(math.font_size)
-            .build();
-        let Ok(layout) = layout_engine.layout(&parse_nodes) else {
-            self.render_math_error(math, transform, "Layout error");
-            return;
-        };
-
-        // Cache size for bounds calculation
-        let size = layout.size();
-        math.set_cached_size(size.width, size.height, size.depth);
-
-        // Position: math.position is baseline origin, apply rotation around center
-        let center_x = math.position.x + size.width / 2.0;
-        let center_y = math.position.y - size.height / 2.0 - size.depth / 2.0;
-        let math_transform = transform
-            * Affine::translate((center_x, center_y))
-            * Affine::rotate(math.rotation)
-            * Affine::translate((-center_x, -center_y))
-            * Affine::translate((math.position.x, math.position.y));
-
-        // Render using our Vello backend with font fallback
-        let color: Color = math.style.stroke_color.into();
-        let mut backend = VelloBackend::new(
-            &mut self.scene,
-            &math_font,
-            primary_face.as_ref(),
-            math_transform,
-            color,
-        );
-        let renderer = RexRenderer::new();
-        renderer.render(&layout, &mut backend);
+        if let Err(msg) = crate::rex_backend::render_math(math, transform, &mut self.scene) {
+            self.render_math_error(math, transform, msg);
+        }
     }
 
     /// Render error placeholder for math that couldn't be rendered.
@@ -1203,45 +919,18 @@ impl VelloRenderer {
         transform: Affine,
         anchor: Option<Point>,
     ) {
-        use drafftink_core::shapes::{FontFamily as ShapeFontFamily, FontWeight};
-
         let style = &text.style;
         let brush = Brush::Solid(style.stroke_with_opacity());
 
         // Determine font name and parley weight based on family and weight
-        // Use same logic as render_text - all Roboto variants use "Roboto" family with weight
-        let (font_name, parley_weight, is_italic) = match (&text.font_family, &text.font_weight) {
-            (ShapeFontFamily::GelPen, FontWeight::Light) => {
-                ("GelPenLight", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::GelPen, FontWeight::Regular) => {
-                ("GelPen", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::GelPen, FontWeight::Heavy) => {
-                ("GelPenHeavy", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::NotoSans, FontWeight::Light) => {
-                ("Noto Sans", parley::FontWeight::NORMAL, true)
-            }
-            (ShapeFontFamily::NotoSans, FontWeight::Regular) => {
-                ("Noto Sans", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::NotoSans, FontWeight::Heavy) => {
-                ("Noto Sans", parley::FontWeight::BOLD, false)
-            }
-            (ShapeFontFamily::GelPenSerif, FontWeight::Light) => {
-                ("GelPenSerifLight", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::GelPenSerif, FontWeight::Regular) => {
-                ("GelPenSerif", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::GelPenSerif, FontWeight::Heavy) => {
-                ("GelPenSerifHeavy", parley::FontWeight::NORMAL, false)
-            }
-            (ShapeFontFamily::VanillaExtract, _) => {
-                ("Vanilla Extract", parley::FontWeight::NORMAL, false)
-            }
+        let font = bundled_font(&text.font_family, &text.font_weight);
+        let font_name = font.name;
+        let parley_weight = if font.bold {
+            parley::FontWeight::BOLD
+        } else {
+            parley::FontWeight::NORMAL
         };
+        let is_italic = font.italic;
 
         // Configure the editor styles
         edit_state.set_font_size(text.font_size as f32);
@@ -1655,6 +1344,13 @@ impl Renderer for VelloRenderer {
             (-ctx.canvas.camera.offset.y + ctx.viewport_size.height) / ctx.canvas.camera.zoom,
         );
 
+        // Draw shapes removed since the compared version as ghosts beneath the rest
+        if let Some(overlay) = ctx.diff_overlay {
+            for ghost in overlay.ghosts() {
+                self.render_shape(&ghost, camera_transform, false);
+            }
+        }
+
         // Draw all shapes in z-order (skip shape being edited or off-screen)
         for shape in ctx.canvas.document.shapes_ordered() {
             if ctx.editing_shape_id == Some(shape.id()) {
@@ -1668,11 +1364,38 @@ impl Renderer for VelloRenderer {
             }
         }
 
+        // When editing inside a group, dim everything else and redraw the group's children on top
+        if let Some(Shape::Group(group)) = ctx
+            .canvas
+            .editing_group()
+            .and_then(|id| ctx.canvas.document.find_shape(id))
+        {
+            let veil = ctx.background_color.with_alpha(0.65);
+            self.scene
+                .fill(Fill::NonZero, camera_transform, veil, None, &world_viewport);
+            let context_transform = camera_transform * ctx.canvas.context_transform();
+            self.render_group_outline(group.bounds(), context_transform);
+            for child in group.children() {
+                if ctx.editing_shape_id == Some(child.id()) {
+                    continue;
+                }
+                let is_selected = ctx.canvas.is_selected(child.id());
+                self.render_shape(child, context_transform, is_selected);
+            }
+        }
+
         // Draw preview shape if tool is active
         if let Some(preview) = ctx.canvas.tool_manager.preview_shape() {
             self.render_shape(&preview, camera_transform, false);
         }
 
+        // Outline the changes against the compared version
+        if let Some(overlay) = ctx.diff_overlay {
+            for outline in overlay.outlines(&ctx.canvas.document) {
+                self.render_diff_outline(&outline, camera_transform);
+            }
+        }
+
         // Draw selection rectangle (marquee)
         if let Some(rect) = ctx.selection_rect {
             self.render_selection_rect(rect, camera_transform);
@@ -2126,6 +1849,47 @@ impl VelloRenderer {
         }
     }
 
+    /// Render the dashed outline around a group that is being edited.
+    /// Stroke width and dash pattern are scaled inversely with zoom.
+    fn render_group_outline(&mut self, bounds: Rect, transform: Affine) {
+        let padding = 6.0 / self.zoom;
+        let rect = bounds.inflate(padding, padding);
+        let stroke_width = 1.0 / self.zoom;
+        let dash_len = 6.0 / self.zoom;
+        let stroke = Stroke::new(stroke_width).with_dashes(0.0, [dash_len, dash_len]);
+        self.scene
+            .stroke(&stroke, transform, self.selection_color, None, &rect);
+    }
+
+    /// Render the outline of a changed shape when comparing versions.
+    /// The focused change is bolder and tinted; sizes are scaled inversely with zoom.
+    fn render_diff_outline(&mut self, outline: &DiffOutline, transform: Affine) {
+        let (padding, width) = if outline.focused {
+            (8.0, 3.0)
+        } else {
+            (4.0, 1.5)
+        };
+        let rect = outline
+            .rect
+            .inflate(padding / self.zoom, padding / self.zoom);
+        if outline.focused {
+            self.scene.fill(
+                Fill::NonZero,
+                transform,
+                outline.color.with_alpha(0.12),
+                None,
+                &rect,
+            );
+        }
+        let mut stroke = Stroke::new(width / self.zoom);
+        if outline.dashed {
+            let dash_len = 6.0 / self.zoom;
+            stroke = stroke.with_dashes(0.0, [dash_len, dash_len]);
+        }
+        self.scene
+            .stroke(&stroke, transform, outline.color, None, &rect);
+    }
+
     /// Render a selection rectangle (marquee).
     /// Stroke width and dash pattern are scaled inversely with zoom.
     fn render_selection_rect(&mut self, rect: Rect, transform: Affine) {
@@ -2409,10 +2173,10 @@ impl ShapeRenderer for VelloRenderer {
                 self.render_text(text, shape_transform);
             }
             Shape::Group(group) => {
-                // Render each child in the group
+                // Render each child in the group, rotated with it
                 for child in group.children() {
                     // Children are not individually selected when the group is selected
-                    self.render_shape(child, transform, false);
+                    self.render_shape(child, shape_transform, false);
                 }
             }
             Shape::Image(image) => {
@@ -2420,6 +2184,14 @@ impl ShapeRenderer for VelloRenderer {
             }
             Shape::Line(line) => {
                 let path = shape.to_path();
+                if line.is_closed() {
+                    self.render_fill(
+                        &shape.id().to_string(),
+                        &path,
+                        shape.style(),
+                        shape_transform,
+                    );
+                }
                 self.render_stroke_only(&path, shape.style(), line.stroke_style, shape_transform);
             }
             Shape::Arrow(arrow) => {
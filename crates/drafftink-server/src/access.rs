@@ -0,0 +1,108 @@
+//! Room access control.
+//!
+//! Rooms are open to anyone who knows their name until their owner protects them with
+//! an edit token, optionally alongside a view token for read-only links. From then on
+//! any editor may change them.
+//!
+//! The owner is the first peer to join since the room was loaded. It isn't saved with
+//! the room, as peer ids only last for one connection, so an open room reloaded from
+//! disk belongs to whoever joins it first.
+//!
+//! Tokens are generated by clients and only their SHA-256 hashes are kept, so the data
+//! directory can't be used to join a room. Setting new tokens revokes the old ones.
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// Shortest token accepted, so rooms can't be protected by guessable tokens.
+pub const MIN_TOKEN_LEN: usize = 16;
+
+/// What a peer may do in a room.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// Change the board and its access tokens.
+    Edit,
+    /// Follow the board; sync data is rejected.
+    View,
+}
+
+/// Hashed tokens protecting a room.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoomAccess {
+    /// Hash of the edit token.
+    edit: String,
+    /// Hash of the view token, if read-only links were created.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    view: Option<String>,
+}
+
+impl RoomAccess {
+    pub fn new(edit_token: &str, view_token: Option<&str>) -> Self {
+        Self {
+            edit: hash_token(edit_token),
+            view: view_token.map(hash_token),
+        }
+    }
+}
+
+/// The role `token` grants in a room protected by `access` (open rooms grant
+/// everyone edit access). `None` if it grants none.
+pub fn role(access: Option<&RoomAccess>, token: Option<&str>) -> Option<Role> {
+    let Some(access) = access else {
+        return Some(Role::Edit);
+    };
+    let hash = hash_token(token?);
+    if hash == access.edit {
+        Some(Role::Edit)
+    } else if access.view.as_ref() == Some(&hash) {
+        Some(Role::View)
+    } else {
+        None
+    }
+}
+
+fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EDIT: &str = "edit-token-0123456789";
+    const VIEW: &str = "view-token-0123456789";
+
+    #[test]
+    fn test_role() {
+        let access = RoomAccess::new(EDIT, Some(VIEW));
+        assert_eq!(role(Some(&access), Some(EDIT)), Some(Role::Edit));
+        assert_eq!(role(Some(&access), Some(VIEW)), Some(Role::View));
+        assert_eq!(role(Some(&access), Some("wrong-token-0123456789")), None);
+        assert_eq!(role(Some(&access), None), None);
+        // The hashes don't work as tokens
+        assert_eq!(role(Some(&access), Some(&hash_token(EDIT))), None);
+
+        // Without a view token, only the edit token is accepted
+        let access = RoomAccess::new(EDIT, None);
+        assert_eq!(role(Some(&access), Some(EDIT)), Some(Role::Edit));
+        assert_eq!(role(Some(&access), Some(VIEW)), None);
+        assert_eq!(role(Some(&access), Some("")), None);
+    }
+
+    #[test]
+    fn test_open_room() {
+        assert_eq!(role(None, None), Some(Role::Edit));
+        assert_eq!(role(None, Some(VIEW)), Some(Role::Edit));
+    }
+
+    #[test]
+    fn test_tokens_not_stored() {
+        let json = serde_json::to_string(&RoomAccess::new(EDIT, Some(VIEW))).unwrap();
+        assert!(!json.contains(EDIT) && !json.contains(VIEW));
+        let access: RoomAccess = serde_json::from_str(&json).unwrap();
+        assert_eq!(role(Some(&access), Some(VIEW)), Some(Role::View));
+    }
+}
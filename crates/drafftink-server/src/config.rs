@@ -0,0 +1,416 @@
+//! Server configuration.
+//!
+//! Settings come from command-line flags, then `DRAFFTINK_*` environment variables,
+//! then a TOML file given with `--config`, then defaults. The file uses the flags'
+//! names with underscores, with the limits in a `[limits]` table:
+//!
+//! ```toml
+//! bind = "127.0.0.1"
+//! port = 8443
+//! allowed_origins = ["https://drafft.ink"]
+//! data_dir = "/var/lib/drafftink"
+//! log_format = "json"
+//! tls_cert = "/etc/drafftink/cert.pem"
+//! tls_key = "/etc/drafftink/key.pem"
+//!
+//! [limits]
+//! max_room_peers = 20
+//! ```
+
+use crate::limits::Limits;
+use axum::http::HeaderValue;
+use clap::{Parser, ValueEnum};
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Port when none is configured.
+const DEFAULT_PORT: u16 = 3030;
+/// Data directory when none is configured.
+const DEFAULT_DATA_DIR: &str = "data";
+/// Room retention when none is configured.
+const DEFAULT_RETENTION_DAYS: u64 = 30;
+/// Seconds in a day of room retention.
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Command-line flags, each also read from an environment variable.
+#[derive(Debug, Parser)]
+#[command(
+    name = "drafftink-server",
+    version,
+    about = "WebSocket relay server for DrafftInk collaboration"
+)]
+pub struct Args {
+    /// TOML configuration file.
+    #[arg(short, long, env = "DRAFFTINK_CONFIG")]
+    config: Option<PathBuf>,
+    /// Address to listen on [default: 0.0.0.0].
+    #[arg(long, env = "DRAFFTINK_BIND")]
+    bind: Option<IpAddr>,
+    /// Port to listen on [default: 3030].
+    #[arg(short, long, env = "DRAFFTINK_PORT")]
+    port: Option<u16>,
+    /// Origins allowed to connect, comma separated [default: any].
+    #[arg(long, env = "DRAFFTINK_ALLOWED_ORIGINS", value_delimiter = ',')]
+    allowed_origins: Option<Vec<String>>,
+    /// Where rooms are saved [default: data].
+    #[arg(long, env = "DRAFFTINK_DATA_DIR")]
+    data_dir: Option<PathBuf>,
+    /// Days a room is kept after its last change, 0 for ever [default: 30].
+    #[arg(long, env = "DRAFFTINK_ROOM_RETENTION_DAYS")]
+    room_retention_days: Option<u64>,
+    /// Largest message accepted, in bytes [default: 16 MiB].
+    #[arg(long, env = "DRAFFTINK_MAX_MESSAGE_BYTES")]
+    max_message_bytes: Option<usize>,
+    /// Messages per second per connection [default: 100].
+    #[arg(long, env = "DRAFFTINK_PEER_RATE_LIMIT")]
+    peer_rate_limit: Option<u32>,
+    /// Messages per second per room [default: 500].
+    #[arg(long, env = "DRAFFTINK_ROOM_RATE_LIMIT")]
+    room_rate_limit: Option<u32>,
+    /// Rooms in memory at once [default: 10000].
+    #[arg(long, env = "DRAFFTINK_MAX_ROOMS")]
+    max_rooms: Option<usize>,
+    /// Peers per room [default: 100].
+    #[arg(long, env = "DRAFFTINK_MAX_ROOM_PEERS")]
+    max_room_peers: Option<usize>,
+    /// Log filter, e.g. `drafftink_server=debug` [default: RUST_LOG, or info].
+    #[arg(long, env = "DRAFFTINK_LOG")]
+    log: Option<String>,
+    /// Log output format [default: text].
+    #[arg(long, env = "DRAFFTINK_LOG_FORMAT", value_enum)]
+    log_format: Option<LogFormat>,
+    /// PEM certificate chain, to serve `wss://` (needs --tls-key).
+    #[arg(long, env = "DRAFFTINK_TLS_CERT")]
+    tls_cert: Option<PathBuf>,
+    /// PEM private key, to serve `wss://` (needs --tls-cert).
+    #[arg(long, env = "DRAFFTINK_TLS_KEY")]
+    tls_key: Option<PathBuf>,
+}
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines
+    #[default]
+    Text,
+    /// One JSON object per line
+    Json,
+}
+
+/// The server's settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: IpAddr,
+    pub port: u16,
+    /// Origins allowed to connect (any if empty or `*`)
+    pub allowed_origins: Vec<String>,
+    pub data_dir: PathBuf,
+    /// Days a room is kept after its last change (0 keeps rooms forever)
+    pub room_retention_days: u64,
+    /// Log filter (`RUST_LOG` syntax)
+    pub log: Option<String>,
+    pub log_format: LogFormat,
+    /// Certificate chain and private key (PEM) for TLS
+    pub tls_cert: Option<PathBuf>,
+    pub tls_key: Option<PathBuf>,
+    pub limits: Limits,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            port: DEFAULT_PORT,
+            allowed_origins: Vec::new(),
+            data_dir: PathBuf::from(DEFAULT_DATA_DIR),
+            room_retention_days: DEFAULT_RETENTION_DAYS,
+            log: None,
+            log_format: LogFormat::default(),
+            tls_cert: None,
+            tls_key: None,
+            limits: Limits::default(),
+        }
+    }
+}
+
+impl Config {
+    /// The configuration from `args` and the file they name, if any.
+    pub fn load(args: Args) -> Result<Self, String> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let text = std::fs::read_to_string(path)
+                    .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+                toml::from_str(&text).map_err(|e| format!("Invalid {}: {}", path.display(), e))?
+            }
+            None => Self::default(),
+        };
+        config.apply(args);
+        config.allowed_origins = config
+            .allowed_origins
+            .iter()
+            .map(|origin| origin.trim().trim_end_matches('/').to_string())
+            .filter(|origin| !origin.is_empty())
+            .collect();
+        if let Some(origin) = config
+            .allowed_origins
+            .iter()
+            .find(|origin| *origin != "*" && HeaderValue::from_str(origin).is_err())
+        {
+            return Err(format!("Invalid allowed origin {:?}", origin));
+        }
+        if config
+            .room_retention_days
+            .checked_mul(SECS_PER_DAY)
+            .is_none()
+        {
+            return Err(format!(
+                "Room retention of {} days is too long",
+                config.room_retention_days
+            ));
+        }
+        if config.tls_cert.is_some() != config.tls_key.is_some() {
+            return Err("TLS needs both a certificate and a private key".to_string());
+        }
+        Ok(config)
+    }
+
+    /// How long rooms are kept after their last change (`None` keeps them forever)
+    pub fn retention(&self) -> Option<Duration> {
+        (self.room_retention_days > 0)
+            .then(|| Duration::from_secs(self.room_retention_days.saturating_mul(SECS_PER_DAY)))
+    }
+
+    /// Override settings with the flags (and environment variables) that are set.
+    fn apply(&mut self, args: Args) {
+        set(&mut self.bind, args.bind);
+        set(&mut self.port, args.port);
+        set(&mut self.allowed_origins, args.allowed_origins);
+        set(&mut self.data_dir, args.data_dir);
+        set(&mut self.room_retention_days, args.room_retention_days);
+        set(&mut self.limits.max_message_bytes, args.max_message_bytes);
+        set(&mut self.limits.peer_rate_limit, args.peer_rate_limit);
+        set(&mut self.limits.room_rate_limit, args.room_rate_limit);
+        set(&mut self.limits.max_rooms, args.max_rooms);
+        set(&mut self.limits.max_room_peers, args.max_room_peers);
+        set(&mut self.log_format, args.log_format);
+        self.log = args.log.or(self.log.take());
+        self.tls_cert = args.tls_cert.or(self.tls_cert.take());
+        self.tls_key = args.tls_key.or(self.tls_key.take());
+    }
+
+    /// Whether a browser page from `origin` may connect
+    pub fn origin_allowed(&self, origin: &str) -> bool {
+        self.allowed_origins.is_empty()
+            || self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == "*" || allowed == origin.trim_end_matches('/'))
+    }
+}
+
+fn set<T>(setting: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *setting = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Serializes the tests, which all read the process environment.
+    static ENV: Mutex<()> = Mutex::new(());
+
+    /// Load the configuration from `flags`, with `env` set for the duration.
+    fn load(flags: &[&str], env: &[(&str, &str)]) -> Result<Config, String> {
+        let _guard = ENV.lock().unwrap_or_else(|e| e.into_inner());
+        // SAFETY: tests that touch the environment hold `ENV`
+        unsafe {
+            for (name, value) in env {
+                std::env::set_var(name, value);
+            }
+        }
+        let args =
+            Args::try_parse_from(std::iter::once("drafftink-server").chain(flags.iter().copied()));
+        unsafe {
+            for (name, _) in env {
+                std::env::remove_var(name);
+            }
+        }
+        Config::load(args.map_err(|e| e.to_string())?)
+    }
+
+    fn config_file(toml: &str) -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(file.path(), toml).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_defaults() {
+        let config = load(&[], &[]).unwrap();
+        assert_eq!(config.bind, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(config.port, DEFAULT_PORT);
+        assert_eq!(config.data_dir, PathBuf::from(DEFAULT_DATA_DIR));
+        assert_eq!(config.room_retention_days, DEFAULT_RETENTION_DAYS);
+        assert_eq!(config.log_format, LogFormat::Text);
+        assert!(config.allowed_origins.is_empty());
+        assert!(config.tls_cert.is_none() && config.tls_key.is_none());
+    }
+
+    #[test]
+    fn test_precedence() {
+        let file = config_file(
+            r#"
+            port = 8443
+            data_dir = "/var/lib/drafftink"
+            log_format = "json"
+            allowed_origins = ["https://drafft.ink/", " "]
+
+            [limits]
+            max_room_peers = 20
+            peer_rate_limit = 50
+            "#,
+        );
+        let path = file.path().to_str().unwrap();
+
+        // The file overrides the defaults
+        let config = load(&["--config", path], &[]).unwrap();
+        assert_eq!(config.port, 8443);
+        assert_eq!(config.data_dir, PathBuf::from("/var/lib/drafftink"));
+        assert_eq!(config.log_format, LogFormat::Json);
+        assert_eq!(config.allowed_origins, vec!["https://drafft.ink"]);
+        assert_eq!(config.limits.max_room_peers, 20);
+        assert_eq!(
+            config.limits.room_rate_limit,
+            Limits::default().room_rate_limit
+        );
+
+        // The environment overrides the file, and can name it
+        let env = [
+            ("DRAFFTINK_CONFIG", path),
+            ("DRAFFTINK_PORT", "9000"),
+            ("DRAFFTINK_MAX_ROOM_PEERS", "5"),
+            (
+                "DRAFFTINK_ALLOWED_ORIGINS",
+                "https://a.example,https://b.example/",
+            ),
+        ];
+        let config = load(&[], &env).unwrap();
+        assert_eq!(config.port, 9000);
+        assert_eq!(config.limits.max_room_peers, 5);
+        assert_eq!(config.limits.peer_rate_limit, 50);
+        assert_eq!(
+            config.allowed_origins,
+            vec!["https://a.example", "https://b.example"]
+        );
+
+        // Flags override the environment
+        let config = load(&["--port", "9100", "--log-format", "text"], &env).unwrap();
+        assert_eq!(config.port, 9100);
+        assert_eq!(config.log_format, LogFormat::Text);
+        assert_eq!(config.limits.max_room_peers, 5);
+    }
+
+    #[test]
+    fn test_invalid_values() {
+        for flags in [
+            &["--port", "70000"][..],
+            &["--bind", "localhost"],
+            &["--log-format", "xml"],
+            &["--max-rooms", "many"],
+        ] {
+            let error = load(flags, &[]).unwrap_err();
+            assert!(error.contains("invalid value"), "{:?}: {}", flags, error);
+        }
+        let error = load(&[], &[("DRAFFTINK_PORT", "http")]).unwrap_err();
+        assert!(error.contains("invalid value"), "{}", error);
+
+        let file = config_file("port = \"http\"");
+        let error = load(&["--config", file.path().to_str().unwrap()], &[]).unwrap_err();
+        assert!(error.starts_with("Invalid "), "{}", error);
+
+        // Misspelled settings aren't silently ignored
+        let file = config_file("[limits]\nmax_peers = 5");
+        let error = load(&["--config", file.path().to_str().unwrap()], &[]).unwrap_err();
+        assert!(error.contains("max_peers"), "{}", error);
+
+        let error = load(&["--config", "/nonexistent/drafftink.toml"], &[]).unwrap_err();
+        assert!(
+            error.starts_with("Cannot read /nonexistent/drafftink.toml"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_allowed_origins() {
+        let config = load(&["--allowed-origins", "*"], &[]).unwrap();
+        assert_eq!(config.allowed_origins, vec!["*"]);
+        assert!(config.origin_allowed("https://anywhere.example"));
+
+        let config = load(&["--allowed-origins", "https://drafft.ink/,*"], &[]).unwrap();
+        assert!(config.origin_allowed("https://anywhere.example"));
+
+        let config = load(&["--allowed-origins", "https://drafft.ink"], &[]).unwrap();
+        assert!(config.origin_allowed("https://drafft.ink/"));
+        assert!(!config.origin_allowed("https://evil.example"));
+
+        // Origins that can't be compared with a request's aren't dropped silently
+        let error = load(
+            &[
+                "--allowed-origins",
+                "https://drafft.ink,https://bell\u{7}.example",
+            ],
+            &[],
+        )
+        .unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid allowed origin \"https://bell\\u{7}.example\""
+        );
+    }
+
+    #[test]
+    fn test_retention() {
+        assert_eq!(
+            load(&[], &[]).unwrap().retention(),
+            Some(Duration::from_secs(DEFAULT_RETENTION_DAYS * SECS_PER_DAY))
+        );
+        let config = load(&["--room-retention-days", "0"], &[]).unwrap();
+        assert_eq!(config.retention(), None);
+
+        let days = (u64::MAX / SECS_PER_DAY).to_string();
+        let config = load(&["--room-retention-days", &days], &[]).unwrap();
+        assert!(config.retention().is_some());
+        let days = (u64::MAX / SECS_PER_DAY + 1).to_string();
+        let error = load(&[], &[("DRAFFTINK_ROOM_RETENTION_DAYS", &days)]).unwrap_err();
+        assert_eq!(
+            error,
+            format!("Room retention of {} days is too long", days)
+        );
+    }
+
+    #[test]
+    fn test_tls_needs_cert_and_key() {
+        const ERROR: &str = "TLS needs both a certificate and a private key";
+        assert_eq!(load(&["--tls-cert", "cert.pem"], &[]).unwrap_err(), ERROR);
+        assert_eq!(
+            load(&[], &[("DRAFFTINK_TLS_KEY", "key.pem")]).unwrap_err(),
+            ERROR
+        );
+        let file = config_file("tls_cert = \"cert.pem\"");
+        let path = file.path().to_str().unwrap();
+        assert_eq!(load(&["--config", path], &[]).unwrap_err(), ERROR);
+
+        // Both may come from different layers
+        let config = load(&["--config", path, "--tls-key", "key.pem"], &[]).unwrap();
+        assert_eq!(config.tls_cert, Some(PathBuf::from("cert.pem")));
+        assert_eq!(config.tls_key, Some(PathBuf::from("key.pem")));
+    }
+}
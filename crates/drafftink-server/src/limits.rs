@@ -0,0 +1,179 @@
+//! Limits protecting the relay from oversized messages and floods.
+//!
+//! Every connection and every room has a token bucket: each message takes a token,
+//! and tokens refill at the configured rate up to one second's worth, so short
+//! bursts (a quick drag) pass while sustained floods are throttled. Throttled
+//! messages are dropped; clients resend their whole state with the next sync, and
+//! awareness only matters in its latest state.
+
+use serde::Deserialize;
+use std::time::Instant;
+
+/// Largest message accepted by default.
+const DEFAULT_MAX_MESSAGE_BYTES: usize = 16 * 1024 * 1024;
+/// Messages per second per connection by default.
+const DEFAULT_PEER_RATE_LIMIT: u32 = 100;
+/// Messages per second per room by default.
+const DEFAULT_ROOM_RATE_LIMIT: u32 = 500;
+/// Rooms in memory by default.
+const DEFAULT_MAX_ROOMS: usize = 10_000;
+/// Peers per room by default.
+const DEFAULT_MAX_ROOM_PEERS: usize = 100;
+
+/// Server limits (0 disables a limit)
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Largest text or binary message, in bytes
+    pub max_message_bytes: usize,
+    /// Messages each connection may send per second
+    pub peer_rate_limit: u32,
+    /// Messages the peers of a room may send per second, together
+    pub room_rate_limit: u32,
+    /// Rooms in memory at once
+    pub max_rooms: usize,
+    /// Peers in a room at once
+    pub max_room_peers: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_message_bytes: DEFAULT_MAX_MESSAGE_BYTES,
+            peer_rate_limit: DEFAULT_PEER_RATE_LIMIT,
+            room_rate_limit: DEFAULT_ROOM_RATE_LIMIT,
+            max_rooms: DEFAULT_MAX_ROOMS,
+            max_room_peers: DEFAULT_MAX_ROOM_PEERS,
+        }
+    }
+}
+
+impl Limits {
+    /// Whether a message of `len` bytes is too large
+    pub fn message_too_large(&self, len: usize) -> bool {
+        self.max_message_bytes > 0 && len > self.max_message_bytes
+    }
+
+    /// Size at which the WebSocket layer drops the connection instead of buffering
+    /// the message. Messages between the limit and this get an error reply.
+    pub fn hard_message_bytes(&self) -> Option<usize> {
+        (self.max_message_bytes > 0).then(|| self.max_message_bytes.saturating_mul(2))
+    }
+}
+
+/// A token bucket allowing `rate` messages per second, in bursts of up to `rate`
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    /// A full bucket (a rate of 0 allows everything)
+    pub fn new(rate: u32) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: Instant::now(),
+        }
+    }
+
+    /// Take a token for a message. Returns whether the message is allowed.
+    pub fn try_acquire(&mut self) -> bool {
+        self.try_acquire_at(Instant::now())
+    }
+
+    /// Take a token for a message arriving at `now`.
+    fn try_acquire_at(&mut self, now: Instant) -> bool {
+        if self.rate == 0.0 {
+            return true;
+        }
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = self.last.max(now);
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn test_burst() {
+        let mut limiter = RateLimiter::new(4);
+        let start = limiter.last;
+        // A full bucket lets a second's worth through at once, then nothing more
+        for _ in 0..4 {
+            assert!(limiter.try_acquire_at(start));
+        }
+        assert!(!limiter.try_acquire_at(start));
+        assert!(!limiter.try_acquire_at(start + ms(125)));
+    }
+
+    #[test]
+    fn test_refill() {
+        let mut limiter = RateLimiter::new(4);
+        let start = limiter.last;
+        while limiter.try_acquire_at(start) {}
+
+        // One token every 250 ms
+        assert!(!limiter.try_acquire_at(start + ms(125)));
+        assert!(limiter.try_acquire_at(start + ms(250)));
+        assert!(!limiter.try_acquire_at(start + ms(375)));
+        assert!(limiter.try_acquire_at(start + ms(500)));
+
+        // A long pause refills the bucket, but only up to one second's worth
+        let later = start + Duration::from_secs(60);
+        let allowed = (0..100).filter(|_| limiter.try_acquire_at(later)).count();
+        assert_eq!(allowed, 4);
+
+        // A clock going backwards adds no tokens, now or later
+        assert!(!limiter.try_acquire_at(start));
+        assert!(!limiter.try_acquire_at(later));
+        assert!(limiter.try_acquire_at(later + ms(250)));
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let mut limiter = RateLimiter::new(0);
+        let start = limiter.last;
+        assert!((0..10_000).all(|_| limiter.try_acquire_at(start)));
+    }
+
+    #[test]
+    fn test_message_size() {
+        let limits = Limits {
+            max_message_bytes: 1024,
+            ..Limits::default()
+        };
+        assert!(!limits.message_too_large(0));
+        assert!(!limits.message_too_large(1024));
+        assert!(limits.message_too_large(1025));
+        assert_eq!(limits.hard_message_bytes(), Some(2048));
+
+        // 0 disables the limit
+        let limits = Limits {
+            max_message_bytes: 0,
+            ..Limits::default()
+        };
+        assert!(!limits.message_too_large(usize::MAX));
+        assert_eq!(limits.hard_message_bytes(), None);
+
+        let limits = Limits {
+            max_message_bytes: usize::MAX,
+            ..Limits::default()
+        };
+        assert_eq!(limits.hard_message_bytes(), Some(usize::MAX));
+    }
+}
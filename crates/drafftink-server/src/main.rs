@@ -1,45 +1,116 @@
 //! DrafftInk WebSocket Relay Server
 //!
-//! A simple relay server that broadcasts CRDT updates between clients in the same room.
+//! A relay server that broadcasts CRDT updates between clients in the same room.
+//! Each room keeps a Loro document that every update is merged into, so peers joining
+//! late receive the whole board: a snapshot, or only the updates they're missing if
+//! they send the version they already have. Rooms outlive their peers: each room's
+//! document is saved to a data directory and reloaded when someone joins again.
+//! Rooms can be protected with an edit token and a read-only view token (see
+//! [`access`]).
+//!
+//! End-to-end encrypted rooms are joined with a check of the room key, and only
+//! peers with the same key check are let in. Their sync data is sealed, so the server
+//! can't merge it: it relays it unchanged and keeps the latest for new joiners
+//! (clients send whole snapshots).
+//!
+//! ## Configuration
+//!
+//! The bind address and port, allowed origins, data directory, room retention,
+//! limits, logging and TLS certificate are set with flags, `DRAFFTINK_*` environment
+//! variables or a TOML file (see [`config`] and `--help`). Rooms are saved to `data`
+//! and kept 30 days after their last change by default.
+//!
+//! Messages over 16 MiB are rejected, and connections and rooms are rate limited
+//! (see [`limits`]); setting a limit to 0 disables it. Rejected messages get an
+//! `error` reply with a `code` (see [`ErrorCode`]). A peer that falls too far behind
+//! its room's traffic is sent the whole board again instead of silently missing
+//! updates. On Ctrl+C or SIGTERM the server stops accepting connections and saves
+//! every changed room before exiting.
 //!
 //! ## Protocol
 //!
 //! Messages are JSON with the following format:
 //! ```json
-//! { "type": "join", "room": "room-id" }
+//! { "type": "join", "room": "room-id", "version": "<base64-encoded-version-vector>", "token": "<access-token>", "key_check": "<room-key-check>" }
+//! { "type": "set_access", "edit_token": "<token>", "view_token": "<token>" }
 //! { "type": "sync", "data": "<base64-encoded-loro-bytes>" }
 //! { "type": "awareness", "peer_id": 123, "cursor": { "x": 100, "y": 200 } }
 //! ```
 
+use access::{MIN_TOKEN_LEN, Role, RoomAccess};
 use axum::{
     Router,
     extract::{
         State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    response::IntoResponse,
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
     routing::get,
 };
+use axum_server::{Handle, tls_rustls::RustlsConfig};
+use clap::Parser;
+use config::{Args, Config, LogFormat};
 use dashmap::DashMap;
 use futures_util::{SinkExt, StreamExt};
+use limits::RateLimiter;
+use loro::{ExportMode, LoroDoc, LoroResult, VersionVector};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, net::SocketAddr, sync::Arc};
-use tokio::sync::broadcast;
-use tower_http::cors::CorsLayer;
-use tracing::{info, warn};
+use std::{collections::HashSet, net::SocketAddr, sync::Arc, time::Duration};
+use store::{RoomStore, SavedRoom};
+use tokio::sync::broadcast::{self, error::RecvError};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+use tracing::{error, info, warn};
+use tracing_subscriber::EnvFilter;
 use uuid::Uuid;
 
+mod access;
+mod config;
+mod limits;
+mod store;
+
 /// Server configuration
 const CHANNEL_CAPACITY: usize = 256;
+/// Longest room ID accepted (in bytes), so every room can be saved under its name.
+const MAX_ROOM_ID_LEN: usize = 100;
+/// How often changed rooms are saved.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// How often expired rooms are deleted from the data directory.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long open connections get to finish when shutting down.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(5);
+/// Log filter when neither the configuration nor `RUST_LOG` sets one.
+const DEFAULT_LOG_FILTER: &str = "drafftink_server=info,tower_http=info";
+/// Sender of the board sent to peers that fell behind their room.
+const SERVER_PEER_ID: &str = "server";
 
 /// A message sent between clients
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum ClientMessage {
     /// Join a room
-    Join { room: String },
+    Join {
+        room: String,
+        /// Version the client already has (base64 encoded Loro version vector),
+        /// so only the updates it's missing are sent
+        #[serde(default)]
+        version: Option<String>,
+        /// Edit or view token, for protected rooms
+        #[serde(default)]
+        token: Option<String>,
+        /// Check of the room key, for end-to-end encrypted rooms
+        #[serde(default)]
+        key_check: Option<String>,
+    },
     /// Leave current room
     Leave,
+    /// Protect the current room with new tokens, revoking the old ones, or open it
+    /// to everyone if `edit_token` is `None`. Needs edit access.
+    SetAccess {
+        edit_token: Option<String>,
+        #[serde(default)]
+        view_token: Option<String>,
+    },
     /// Sync CRDT data (base64 encoded Loro bytes)
     Sync { data: String },
     /// Awareness update (cursor position, selection, etc.)
@@ -59,6 +130,9 @@ pub struct AwarenessState {
     /// User name/color
     #[serde(skip_serializing_if = "Option::is_none")]
     pub user: Option<UserInfo>,
+    /// Cursor and user sealed with the room key, in end-to-end encrypted rooms
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sealed: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -84,7 +158,16 @@ pub enum ServerMessage {
         /// Initial sync data (if room has history)
         #[serde(skip_serializing_if = "Option::is_none")]
         initial_sync: Option<String>,
+        /// Whether the room is protected by tokens
+        protected: bool,
+        /// Whether the peer joined with a view token
+        read_only: bool,
     },
+    /// The room's access changed
+    Access { protected: bool, read_only: bool },
+    /// The peer was refused or removed from a room, e.g. for a missing or revoked
+    /// token
+    Removed { room: String, message: String },
     /// Peer joined the room
     PeerJoined { peer_id: String },
     /// Peer left the room
@@ -98,8 +181,74 @@ pub enum ServerMessage {
         #[serde(flatten)]
         state: AwarenessState,
     },
-    /// Error message
-    Error { message: String },
+    /// A message was rejected
+    Error { code: ErrorCode, message: String },
+}
+
+/// Why a message was rejected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// Not a valid protocol message
+    InvalidMessage,
+    /// Larger than the server accepts
+    MessageTooLarge,
+    /// Too many messages from the peer or its room; the message was dropped
+    RateLimited,
+    /// The room ID is not accepted
+    InvalidRoom,
+    /// The room has as many peers as it may
+    RoomFull,
+    /// The server has as many rooms as it may
+    ServerFull,
+    /// The peer's role doesn't allow the request, or it doesn't own the open room
+    /// it tries to protect
+    Forbidden,
+    /// Access tokens that are too short
+    InvalidToken,
+    /// Sync data from a peer with read-only access
+    ReadOnly,
+    /// Sync data that can't be decoded or merged
+    InvalidSyncData,
+}
+
+/// What a room keeps of its board
+enum Board {
+    /// A document merged from every peer's sync data
+    Merged(LoroDoc),
+    /// The latest sync data of an end-to-end encrypted room (base64 encoded), which
+    /// the server can't read. `key_check` identifies the room key.
+    Sealed {
+        key_check: String,
+        data: Option<String>,
+    },
+}
+
+impl Board {
+    /// An empty board, sealed if a room key check is given
+    fn new(key_check: Option<String>) -> Self {
+        match key_check {
+            Some(key_check) => Board::Sealed {
+                key_check,
+                data: None,
+            },
+            None => Board::Merged(LoroDoc::new()),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Board::Merged(doc) => doc.oplog_frontiers().is_empty(),
+            Board::Sealed { data, .. } => data.is_none(),
+        }
+    }
+
+    fn key_check(&self) -> Option<&str> {
+        match self {
+            Board::Merged(_) => None,
+            Board::Sealed { key_check, .. } => Some(key_check),
+        }
+    }
 }
 
 /// Room state
@@ -108,71 +257,333 @@ struct Room {
     tx: broadcast::Sender<(String, ServerMessage)>,
     /// Connected peer IDs
     peers: HashSet<String>,
-    /// Last sync data (for new joiners)
-    last_sync: Option<String>,
+    /// The room's board
+    board: Board,
+    /// Tokens protecting the room (`None` for open rooms)
+    access: Option<RoomAccess>,
+    /// The peer that opened the room or last changed its access, the only one who
+    /// may protect it while it's open (not saved, see [`access`])
+    owner: Option<String>,
+    /// Whether `board` or `access` changed since the room was last saved
+    dirty: bool,
+    /// Limits the messages the room's peers send together
+    limiter: RateLimiter,
 }
 
 impl Room {
-    fn new() -> Self {
+    fn new(board: Board, access: Option<RoomAccess>, rate_limit: u32) -> Self {
         let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
         Self {
             tx,
             peers: HashSet::new(),
-            last_sync: None,
+            board,
+            access,
+            owner: None,
+            dirty: false,
+            limiter: RateLimiter::new(rate_limit),
         }
     }
+
+    /// Check that a peer joining with `key_check` has the room's key (or none, if the
+    /// room isn't end-to-end encrypted). A room nobody is in and that has no board yet
+    /// takes the peer's encryption instead.
+    fn check_key(&mut self, key_check: Option<&str>) -> Result<(), String> {
+        if self.board.key_check() == key_check {
+            return Ok(());
+        }
+        if self.peers.is_empty() && self.board.is_empty() {
+            self.board = Board::new(key_check.map(str::to_string));
+            return Ok(());
+        }
+        Err(match (self.board.key_check(), key_check) {
+            (Some(_), None) => {
+                "This room is end-to-end encrypted: open it with a link that includes its key"
+            }
+            (Some(_), Some(_)) => "Wrong encryption key for this room",
+            (None, _) => "This room is not end-to-end encrypted",
+        }
+        .to_string())
+    }
+
+    /// Sync data for a peer joining with `version` (base64 encoded): the updates it's
+    /// missing, or a snapshot if it sent no (valid) version. Encrypted rooms send
+    /// their latest data. `None` while the room is empty.
+    fn initial_sync(&self, version: Option<&[u8]>) -> Option<String> {
+        let doc = match &self.board {
+            Board::Merged(doc) => doc,
+            Board::Sealed { data, .. } => return data.clone(),
+        };
+        if doc.oplog_frontiers().is_empty() {
+            return None;
+        }
+        let since = version.and_then(|bytes| VersionVector::decode(bytes).ok());
+        let mode = match &since {
+            Some(since) => ExportMode::updates(since),
+            None => ExportMode::Snapshot,
+        };
+        doc.export(mode).ok().map(|bytes| base64_encode(&bytes))
+    }
+}
+
+/// A room from what was saved of it (with an empty document if it can't be read).
+fn load_room(room_id: &str, saved: Option<SavedRoom>, rate_limit: u32) -> Room {
+    let Some(saved) = saved else {
+        return Room::new(Board::new(None), None, rate_limit);
+    };
+    if let Some(key_check) = saved.key_check {
+        info!("Loaded encrypted room {} from the store", room_id);
+        let data = (!saved.data.is_empty()).then_some(saved.data);
+        return Room::new(Board::Sealed { key_check, data }, saved.access, rate_limit);
+    }
+    let doc = LoroDoc::new();
+    let imported = base64_decode(&saved.data).map(|bytes| doc.import(&bytes));
+    match imported {
+        Some(Ok(_)) => info!("Loaded room {} from the store", room_id),
+        _ => warn!(
+            "Saved room {} is not a valid document; starting empty",
+            room_id
+        ),
+    }
+    Room::new(Board::Merged(doc), saved.access, rate_limit)
+}
+
+/// What a peer gets when joining a room
+struct JoinedRoom {
+    rx: broadcast::Receiver<(String, ServerMessage)>,
+    initial_sync: Option<String>,
+    peer_count: usize,
+    role: Role,
+    protected: bool,
+}
+
+/// What a peer that fell behind its room is sent again
+struct Resync {
+    /// The peer's role, which may have changed in the messages it missed
+    role: Role,
+    protected: bool,
+    /// The whole board (`None` while the room is empty)
+    data: Option<String>,
 }
 
 /// Shared application state
 struct AppState {
-    /// Active rooms
+    /// Rooms in memory: those with peers, and empty ones not yet saved
     rooms: DashMap<String, Room>,
+    /// Saved rooms
+    store: RoomStore,
+    /// Settings, including message limits and room caps
+    config: Config,
 }
 
 impl AppState {
-    fn new() -> Self {
+    fn new(store: RoomStore, config: Config) -> Self {
         Self {
             rooms: DashMap::new(),
+            store,
+            config,
         }
     }
 
-    /// Add peer to room
-    fn join_room(
+    /// Add peer to room, loading the room from the store if it isn't in memory.
+    /// `version` is the encoded version vector the peer already has. Fails with the
+    /// message to send the peer if `token` doesn't grant access to a protected room,
+    /// `key_check` doesn't match the room's encryption, or the room or server is full.
+    async fn join_room(
         &self,
         room_id: &str,
         peer_id: &str,
-    ) -> (
-        broadcast::Receiver<(String, ServerMessage)>,
-        Option<String>,
-        usize,
-    ) {
-        let mut room = self
-            .rooms
-            .entry(room_id.to_string())
-            .or_insert_with(Room::new);
-        room.peers.insert(peer_id.to_string());
-        let rx = room.tx.subscribe();
-        let initial_sync = room.last_sync.clone();
-        let peer_count = room.peers.len();
-        (rx, initial_sync, peer_count)
+        version: Option<&[u8]>,
+        token: Option<&str>,
+        key_check: Option<&str>,
+    ) -> Result<JoinedRoom, ServerMessage> {
+        let removed = |message: String| ServerMessage::Removed {
+            room: room_id.to_string(),
+            message,
+        };
+        loop {
+            if let Some(mut room) = self.rooms.get_mut(room_id) {
+                let Some(role) = access::role(room.access.as_ref(), token) else {
+                    return Err(removed(match token {
+                        Some(_) => "Invalid access token for this room".to_string(),
+                        None => "This room needs an access token".to_string(),
+                    }));
+                };
+                room.check_key(key_check).map_err(removed)?;
+                let max_peers = self.config.limits.max_room_peers;
+                if max_peers > 0 && room.peers.len() >= max_peers {
+                    return Err(ServerMessage::Error {
+                        code: ErrorCode::RoomFull,
+                        message: format!("This room is full ({} peers)", max_peers),
+                    });
+                }
+                room.peers.insert(peer_id.to_string());
+                room.owner.get_or_insert_with(|| peer_id.to_string());
+                return Ok(JoinedRoom {
+                    rx: room.tx.subscribe(),
+                    initial_sync: room.initial_sync(version),
+                    peer_count: room.peers.len(),
+                    role,
+                    protected: room.access.is_some(),
+                });
+            }
+            let max_rooms = self.config.limits.max_rooms;
+            if max_rooms > 0 && self.rooms.len() >= max_rooms {
+                warn!("Refusing room {}: {} rooms in memory", room_id, max_rooms);
+                return Err(ServerMessage::Error {
+                    code: ErrorCode::ServerFull,
+                    message: "The server has too many open rooms, try again later".to_string(),
+                });
+            }
+            // Only saved rooms are dropped from memory, so the store is up to date;
+            // retry if the room is dropped again before the peer is added
+            let saved = self.store.load(room_id).await;
+            let room = load_room(room_id, saved, self.config.limits.room_rate_limit);
+            self.rooms
+                .entry(room_id.to_string())
+                .or_insert_with(|| room);
+        }
+    }
+
+    /// The role `token` grants in a room (`None` if none, or the room is gone)
+    fn role(&self, room_id: &str, token: Option<&str>) -> Option<Role> {
+        let room = self.rooms.get(room_id)?;
+        access::role(room.access.as_ref(), token)
     }
 
-    /// Remove peer from room
+    /// Replace a room's tokens and tell its peers, who check their own tokens again.
+    /// Open rooms can only be protected by their owner; protected rooms by any editor.
+    /// Returns whether the room is now protected, or `None` if `from` may not change it.
+    fn set_access(&self, room_id: &str, from: &str, access: Option<RoomAccess>) -> Option<bool> {
+        let protected = access.is_some();
+        if let Some(mut room) = self.rooms.get_mut(room_id) {
+            if room.access.is_none() && room.owner.as_deref() != Some(from) {
+                return None;
+            }
+            room.access = access;
+            room.owner = Some(from.to_string());
+            room.dirty = true;
+            // Each peer fills in its own role
+            let _ = room.tx.send((
+                from.to_string(),
+                ServerMessage::Access {
+                    protected,
+                    read_only: false,
+                },
+            ));
+        }
+        Some(protected)
+    }
+
+    /// Remove peer from room. Empty rooms stay in memory until they are saved.
     fn leave_room(&self, room_id: &str, peer_id: &str) {
         if let Some(mut room) = self.rooms.get_mut(room_id) {
             room.peers.remove(peer_id);
-            // Clean up empty rooms
-            if room.peers.is_empty() {
-                drop(room);
-                self.rooms.remove(room_id);
-            }
         }
     }
 
-    /// Update room's last sync data
-    fn update_sync(&self, room_id: &str, data: String) {
+    /// Merge sync data into the room's document, or keep it as the latest data of
+    /// an encrypted room
+    fn import_sync(&self, room_id: &str, bytes: &[u8]) -> LoroResult<()> {
         if let Some(mut room) = self.rooms.get_mut(room_id) {
-            room.last_sync = Some(data);
+            match &mut room.board {
+                Board::Merged(doc) => {
+                    doc.import(bytes)?;
+                }
+                Board::Sealed { data, .. } => *data = Some(base64_encode(bytes)),
+            }
+            room.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// Save rooms that changed, then drop empty rooms from memory; they are
+    /// loaded again when someone joins. Saving compacts the document's history
+    /// into a single snapshot.
+    async fn flush(&self) {
+        let changed: Vec<(String, SavedRoom)> = self
+            .rooms
+            .iter_mut()
+            .filter(|room| room.dirty)
+            .filter_map(|mut room| {
+                let (data, key_check) = match &room.board {
+                    Board::Merged(doc) => {
+                        doc.compact_change_store();
+                        let snapshot = doc.export(ExportMode::Snapshot).ok()?;
+                        (base64_encode(&snapshot), None)
+                    }
+                    Board::Sealed { key_check, data } => {
+                        (data.clone().unwrap_or_default(), Some(key_check.clone()))
+                    }
+                };
+                room.dirty = false;
+                let saved = SavedRoom {
+                    data,
+                    access: room.access.clone(),
+                    key_check,
+                };
+                Some((room.key().clone(), saved))
+            })
+            .collect();
+        for (room_id, saved) in changed {
+            if let Err(e) = self.store.save(&room_id, saved).await {
+                warn!("Failed to save room {}: {}", room_id, e);
+                // Keep it in memory and try again next time
+                if let Some(mut room) = self.rooms.get_mut(&room_id) {
+                    room.dirty = true;
+                }
+            }
+        }
+        self.rooms
+            .retain(|_, room| !room.peers.is_empty() || room.dirty);
+    }
+
+    /// Delete expired rooms from the store.
+    async fn sweep(&self) {
+        let active = self.rooms.iter().map(|room| room.key().clone()).collect();
+        let deleted = self.store.sweep(active).await;
+        if deleted > 0 {
+            info!("Deleted {} expired rooms", deleted);
+        }
+    }
+
+    /// Take a message from a room's rate limit. Returns whether it's allowed.
+    fn allow_message(&self, room_id: &str) -> bool {
+        self.rooms
+            .get_mut(room_id)
+            .is_none_or(|mut room| room.limiter.try_acquire())
+    }
+
+    /// Catch up a peer that fell behind its room: its access is checked again, since
+    /// it may have missed its token being revoked. `None` if `token` no longer grants
+    /// access (or the room is gone).
+    fn resync(&self, room_id: &str, token: Option<&str>) -> Option<Resync> {
+        let room = self.rooms.get(room_id)?;
+        let role = access::role(room.access.as_ref(), token)?;
+        Some(Resync {
+            role,
+            protected: room.access.is_some(),
+            data: room.initial_sync(None),
+        })
+    }
+
+    /// Take a peer whose token was revoked out of a room. Returns the message telling
+    /// it so.
+    fn remove_revoked(&self, room_id: &str, peer_id: &str) -> ServerMessage {
+        self.leave_room(room_id, peer_id);
+        self.broadcast(
+            room_id,
+            peer_id,
+            ServerMessage::PeerLeft {
+                peer_id: peer_id.to_string(),
+            },
+        );
+        info!(
+            "Peer {} removed from room {}: token revoked",
+            peer_id, room_id
+        );
+        ServerMessage::Removed {
+            room: room_id.to_string(),
+            message: "Your access to this room was revoked".to_string(),
         }
     }
 
@@ -186,29 +597,169 @@ impl AppState {
 
 #[tokio::main]
 async fn main() {
+    let config = match Config::load(Args::parse()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("drafftink-server: {}", e);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize tracing
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "drafftink_server=info,tower_http=info".into()),
-        )
-        .init();
+    let filter = match &config.log {
+        Some(filter) => EnvFilter::try_new(filter).unwrap_or_else(|e| {
+            eprintln!("drafftink-server: invalid log filter {:?}: {}", filter, e);
+            std::process::exit(2);
+        }),
+        None => EnvFilter::try_from_default_env().unwrap_or_else(|_| DEFAULT_LOG_FILTER.into()),
+    };
+    match config.log_format {
+        LogFormat::Text => tracing_subscriber::fmt().with_env_filter(filter).init(),
+        LogFormat::Json => tracing_subscriber::fmt()
+            .json()
+            .with_env_filter(filter)
+            .init(),
+    }
+
+    let store = match RoomStore::open(&config.data_dir, config.retention()) {
+        Ok(store) => store,
+        Err(e) => {
+            error!("Cannot open data directory {:?}: {}", config.data_dir, e);
+            std::process::exit(1);
+        }
+    };
+    info!("Saving rooms to {:?}", config.data_dir);
+    info!("Limits: {:?}", config.limits);
+    if !config.allowed_origins.is_empty() {
+        info!("Allowed origins: {}", config.allowed_origins.join(", "));
+    }
+
+    let addr = SocketAddr::new(config.bind, config.port);
+    let tls = match (&config.tls_cert, &config.tls_key) {
+        (Some(cert), Some(key)) => {
+            // Pick the provider explicitly, in case several are compiled in
+            let _ = rustls::crypto::ring::default_provider().install_default();
+            match RustlsConfig::from_pem_file(cert, key).await {
+                Ok(tls) => Some(tls),
+                Err(e) => {
+                    error!(
+                        "Cannot load TLS certificate {:?} and key {:?}: {}",
+                        cert, key, e
+                    );
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    let cors = cors_layer(&config.allowed_origins);
 
-    let state = Arc::new(AppState::new());
+    let state = Arc::new(AppState::new(store, config));
+
+    // Save changed rooms and delete expired ones in the background
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            let mut interval = tokio::time::interval(FLUSH_INTERVAL);
+            loop {
+                interval.tick().await;
+                state.flush().await;
+            }
+        }
+    });
+    tokio::spawn({
+        let state = state.clone();
+        async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                state.sweep().await;
+            }
+        }
+    });
 
     let app = Router::new()
         .route("/", get(index))
         .route("/ws", get(ws_handler))
         .route("/health", get(health))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .layer(cors)
+        .with_state(state.clone())
+        .into_make_service();
+
+    let handle = Handle::new();
+    tokio::spawn(shutdown_on_signal(handle.clone()));
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 3030));
     info!("DrafftInk relay server listening on {}", addr);
-    info!("WebSocket endpoint: ws://localhost:3030/ws");
+    let served = match tls {
+        Some(tls) => {
+            info!("WebSocket endpoint: wss://{}/ws", addr);
+            axum_server::bind_rustls(addr, tls)
+                .handle(handle)
+                .serve(app)
+                .await
+        }
+        None => {
+            info!("WebSocket endpoint: ws://{}/ws", addr);
+            axum_server::bind(addr).handle(handle).serve(app).await
+        }
+    };
+    if let Err(e) = &served {
+        error!("Server error on {}: {}", addr, e);
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // Save what changed since the last flush before exiting
+    state.flush().await;
+    info!("Server stopped");
+    if served.is_err() {
+        std::process::exit(1);
+    }
+}
+
+/// Wait for Ctrl+C (or SIGTERM on Unix), then stop accepting connections and give
+/// open ones a moment to finish.
+async fn shutdown_on_signal(handle: Handle) {
+    let ctrl_c = async {
+        let _ = tokio::signal::ctrl_c().await;
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    info!("Shutting down");
+    handle.graceful_shutdown(Some(SHUTDOWN_GRACE));
+}
+
+/// CORS for the HTTP endpoints: any origin, or only the allowed ones
+fn cors_layer(allowed_origins: &[String]) -> CorsLayer {
+    if allowed_origins.is_empty() {
+        return CorsLayer::permissive();
+    }
+    let origins = if allowed_origins.iter().any(|origin| origin == "*") {
+        AllowOrigin::any()
+    } else {
+        // Origins are checked when the configuration is loaded
+        let origins: Vec<HeaderValue> = allowed_origins
+            .iter()
+            .filter_map(|origin| origin.parse().ok())
+            .collect();
+        AllowOrigin::list(origins)
+    };
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
 }
 
 /// Index page
@@ -222,8 +773,24 @@ async fn health() -> &'static str {
 }
 
 /// WebSocket upgrade handler
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> impl IntoResponse {
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    State(state): State<Arc<AppState>>,
+) -> Response {
+    // Browsers always send their page's origin; other clients may send none
+    let origin = headers.get(header::ORIGIN).and_then(|o| o.to_str().ok());
+    if origin.is_some_and(|origin| !state.config.origin_allowed(origin)) {
+        warn!("Refused WebSocket from origin {:?}", origin);
+        return (StatusCode::FORBIDDEN, "Origin not allowed").into_response();
+    }
+    // Far larger messages are cut off without being buffered, closing the connection
+    let ws = match state.config.limits.hard_message_bytes() {
+        Some(max) => ws.max_message_size(max).max_frame_size(max),
+        None => ws,
+    };
     ws.on_upgrade(move |socket| handle_socket(socket, state))
+        .into_response()
 }
 
 /// Handle a WebSocket connection
@@ -234,35 +801,88 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     let (mut sender, mut receiver) = socket.split();
     let mut current_room: Option<String> = None;
     let mut room_rx: Option<broadcast::Receiver<(String, ServerMessage)>> = None;
+    // Token the peer joined its room with, and what it grants
+    let mut token: Option<String> = None;
+    let mut role = Role::Edit;
+    let mut limiter = RateLimiter::new(state.config.limits.peer_rate_limit);
+    // Whether the last message was throttled by the connection's or the room's limit,
+    // so floods get one error, not one each
+    let mut throttled = false;
+    let mut room_throttled = false;
 
     loop {
         tokio::select! {
             // Handle incoming messages from client
             msg = receiver.next() => {
+                let len = match &msg {
+                    Some(Ok(Message::Text(text))) => Some(text.len()),
+                    Some(Ok(Message::Binary(data))) => Some(data.len()),
+                    _ => None,
+                };
+                if let Some(len) = len {
+                    if state.config.limits.message_too_large(len) {
+                        warn!("Message from {} too large ({} bytes)", peer_id, len);
+                        let reply = error_message(ErrorCode::MessageTooLarge, format!("Messages are limited to {} bytes", state.config.limits.max_message_bytes));
+                        if sender.send(reply).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    if !limiter.try_acquire() {
+                        if let Some(reply) = throttle(&mut throttled, "Too many messages, slow down") {
+                            warn!("Throttling messages from {}", peer_id);
+                            if sender.send(reply).await.is_err() {
+                                break;
+                            }
+                        }
+                        continue;
+                    }
+                    throttled = false;
+                }
                 match msg {
                     Some(Ok(Message::Text(text))) => {
                         match serde_json::from_str::<ClientMessage>(&text) {
                             Ok(client_msg) => {
                                 match client_msg {
-                                    ClientMessage::Join { room } => {
+                                    ClientMessage::Join { room, version, token: join_token, key_check } => {
+                                        if room.len() > MAX_ROOM_ID_LEN {
+                                            let _ = sender.send(error_message(ErrorCode::InvalidRoom, format!("Room ID longer than {} bytes", MAX_ROOM_ID_LEN))).await;
+                                            continue;
+                                        }
+
                                         // Leave current room if any
-                                        if let Some(ref old_room) = current_room {
-                                            state.leave_room(old_room, &peer_id);
-                                            state.broadcast(old_room, &peer_id, ServerMessage::PeerLeft {
+                                        if let Some(old_room) = current_room.take() {
+                                            state.leave_room(&old_room, &peer_id);
+                                            state.broadcast(&old_room, &peer_id, ServerMessage::PeerLeft {
                                                 peer_id: peer_id.clone(),
                                             });
+                                            room_rx = None;
                                         }
 
                                         // Join new room
-                                        let (rx, initial_sync, peer_count) = state.join_room(&room, &peer_id);
-                                        room_rx = Some(rx);
+                                        let version = version.as_deref().and_then(base64_decode);
+                                        let joined = match state.join_room(&room, &peer_id, version.as_deref(), join_token.as_deref(), key_check.as_deref()).await {
+                                            Ok(joined) => joined,
+                                            Err(denied) => {
+                                                warn!("Peer {} denied access to room {}", peer_id, room);
+                                                if sender.send(Message::Text(serde_json::to_string(&denied).unwrap().into())).await.is_err() {
+                                                    break;
+                                                }
+                                                continue;
+                                            }
+                                        };
+                                        room_rx = Some(joined.rx);
                                         current_room = Some(room.clone());
+                                        token = join_token;
+                                        role = joined.role;
 
                                         // Send joined confirmation
                                         let joined = ServerMessage::Joined {
                                             room: room.clone(),
-                                            peer_count,
-                                            initial_sync,
+                                            peer_count: joined.peer_count,
+                                            initial_sync: joined.initial_sync,
+                                            protected: joined.protected,
+                                            read_only: role == Role::View,
                                         };
                                         if sender.send(Message::Text(serde_json::to_string(&joined).unwrap().into())).await.is_err() {
                                             break;
@@ -286,11 +906,53 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                         current_room = None;
                                         room_rx = None;
                                     }
+                                    ClientMessage::SetAccess { edit_token, view_token } => {
+                                        if let Some(ref room) = current_room {
+                                            if role != Role::Edit {
+                                                let _ = sender.send(error_message(ErrorCode::Forbidden, "Only editors can change access to this room")).await;
+                                                continue;
+                                            }
+                                            let too_short = edit_token.iter().chain(&view_token).any(|t| t.len() < MIN_TOKEN_LEN);
+                                            if too_short {
+                                                let _ = sender.send(error_message(ErrorCode::InvalidToken, format!("Access tokens need at least {} characters", MIN_TOKEN_LEN))).await;
+                                                continue;
+                                            }
+                                            let access = edit_token.as_deref().map(|edit| RoomAccess::new(edit, view_token.as_deref()));
+                                            let Some(protected) = state.set_access(room, &peer_id, access) else {
+                                                let _ = sender.send(error_message(ErrorCode::Forbidden, "Only the peer who opened this room can protect it")).await;
+                                                continue;
+                                            };
+                                            token = edit_token;
+                                            let msg = ServerMessage::Access { protected, read_only: false };
+                                            if sender.send(Message::Text(serde_json::to_string(&msg).unwrap().into())).await.is_err() {
+                                                break;
+                                            }
+                                            info!("Peer {} changed access to room {} (protected: {})", peer_id, room, protected);
+                                        }
+                                    }
                                     ClientMessage::Sync { data } => {
                                         if let Some(ref room) = current_room {
-                                            // Store as last sync for new joiners
-                                            state.update_sync(room, data.clone());
-                                            // Broadcast to others
+                                            if role == Role::View {
+                                                let _ = sender.send(error_message(ErrorCode::ReadOnly, "Read-only access: changes are not accepted")).await;
+                                                continue;
+                                            }
+                                            if !state.allow_message(room) {
+                                                if let Some(reply) = throttle(&mut room_throttled, "Too many messages in this room, slow down") {
+                                                    let _ = sender.send(reply).await;
+                                                }
+                                                continue;
+                                            }
+                                            room_throttled = false;
+                                            let Some(bytes) = base64_decode(&data) else {
+                                                let _ = sender.send(error_message(ErrorCode::InvalidSyncData, "Invalid sync data: not base64")).await;
+                                                continue;
+                                            };
+                                            // Merge for new joiners, then broadcast to others
+                                            if let Err(e) = state.import_sync(room, &bytes) {
+                                                warn!("Invalid sync data from {}: {}", peer_id, e);
+                                                let _ = sender.send(error_message(ErrorCode::InvalidSyncData, format!("Invalid sync data: {}", e))).await;
+                                                continue;
+                                            }
                                             state.broadcast(room, &peer_id, ServerMessage::Sync {
                                                 from: peer_id.clone(),
                                                 data,
@@ -299,6 +961,13 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                                     }
                                     ClientMessage::Awareness { peer_id: awareness_peer_id, state: awareness_state } => {
                                         if let Some(ref room) = current_room {
+                                            if !state.allow_message(room) {
+                                                if let Some(reply) = throttle(&mut room_throttled, "Too many messages in this room, slow down") {
+                                                    let _ = sender.send(reply).await;
+                                                }
+                                                continue;
+                                            }
+                                            room_throttled = false;
                                             state.broadcast(room, &peer_id, ServerMessage::Awareness {
                                                 from: peer_id.clone(),
                                                 peer_id: awareness_peer_id,
@@ -310,21 +979,32 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
                             }
                             Err(e) => {
                                 warn!("Invalid message from {}: {}", peer_id, e);
-                                let err = ServerMessage::Error {
-                                    message: format!("Invalid message: {}", e),
-                                };
-                                let _ = sender.send(Message::Text(serde_json::to_string(&err).unwrap().into())).await;
+                                let _ = sender.send(error_message(ErrorCode::InvalidMessage, format!("Invalid message: {}", e))).await;
                             }
                         }
                     }
                     Some(Ok(Message::Binary(data))) => {
                         // Binary messages are treated as raw sync data
                         if let Some(ref room) = current_room {
-                            let data_b64 = base64_encode(&data);
-                            state.update_sync(room, data_b64.clone());
+                            if role == Role::View {
+                                let _ = sender.send(error_message(ErrorCode::ReadOnly, "Read-only access: changes are not accepted")).await;
+                                continue;
+                            }
+                            if !state.allow_message(room) {
+                                if let Some(reply) = throttle(&mut room_throttled, "Too many messages in this room, slow down") {
+                                    let _ = sender.send(reply).await;
+                                }
+                                continue;
+                            }
+                            room_throttled = false;
+                            if let Err(e) = state.import_sync(room, &data) {
+                                warn!("Invalid sync data from {}: {}", peer_id, e);
+                                let _ = sender.send(error_message(ErrorCode::InvalidSyncData, format!("Invalid sync data: {}", e))).await;
+                                continue;
+                            }
                             state.broadcast(room, &peer_id, ServerMessage::Sync {
                                 from: peer_id.clone(),
-                                data: data_b64,
+                                data: base64_encode(&data),
                             });
                         }
                     }
@@ -342,16 +1022,67 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
             // Handle broadcast messages from room
             msg = async {
                 match &mut room_rx {
-                    Some(rx) => rx.recv().await.ok(),
+                    Some(rx) => rx.recv().await,
                     None => {
                         // No room joined, just wait forever
-                        std::future::pending::<Option<(String, ServerMessage)>>().await
+                        std::future::pending::<Result<(String, ServerMessage), RecvError>>().await
                     }
                 }
             } => {
+                let msg = match msg {
+                    Ok(msg) => Some(msg),
+                    // Too slow to keep up with the room: send our access and the whole
+                    // board instead of leaving the peer without the updates it missed
+                    Err(RecvError::Lagged(missed)) => {
+                        warn!("Peer {} missed {} messages; resyncing", peer_id, missed);
+                        let Some(room) = current_room.clone() else {
+                            continue;
+                        };
+                        match state.resync(&room, token.as_deref()) {
+                            Some(resync) => {
+                                role = resync.role;
+                                let access = ServerMessage::Access { protected: resync.protected, read_only: role == Role::View };
+                                if sender.send(Message::Text(serde_json::to_string(&access).unwrap().into())).await.is_err() {
+                                    break;
+                                }
+                                resync.data.map(|data| {
+                                    let from = SERVER_PEER_ID.to_string();
+                                    (from.clone(), ServerMessage::Sync { from, data })
+                                })
+                            }
+                            None => {
+                                let removed = state.remove_revoked(&room, &peer_id);
+                                current_room = None;
+                                room_rx = None;
+                                Some((SERVER_PEER_ID.to_string(), removed))
+                            }
+                        }
+                    }
+                    Err(RecvError::Closed) => {
+                        room_rx = None;
+                        None
+                    }
+                };
                 if let Some((from, server_msg)) = msg {
                     // Don't echo back to sender
                     if from != peer_id {
+                        let server_msg = match (server_msg, current_room.clone()) {
+                            // Access changed: check our token again
+                            (ServerMessage::Access { protected, .. }, Some(room)) => {
+                                match state.role(&room, token.as_deref()) {
+                                    Some(new_role) => {
+                                        role = new_role;
+                                        ServerMessage::Access { protected, read_only: role == Role::View }
+                                    }
+                                    None => {
+                                        current_room = None;
+                                        room_rx = None;
+                                        state.remove_revoked(&room, &peer_id)
+                                    }
+                                }
+                            }
+                            (server_msg, _) => server_msg,
+                        };
                         let json = serde_json::to_string(&server_msg).unwrap();
                         if sender.send(Message::Text(json.into())).await.is_err() {
                             break;
@@ -376,6 +1107,22 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     info!("Connection closed: {}", peer_id);
 }
 
+/// A text frame carrying an error message
+fn error_message(code: ErrorCode, message: impl Into<String>) -> Message {
+    let err = ServerMessage::Error {
+        code,
+        message: message.into(),
+    };
+    Message::Text(serde_json::to_string(&err).unwrap().into())
+}
+
+/// A rate limit error for the first of a run of throttled messages
+fn throttle(throttled: &mut bool, message: &str) -> Option<Message> {
+    let first = !*throttled;
+    *throttled = true;
+    first.then(|| error_message(ErrorCode::RateLimited, message))
+}
+
 const B64_CHARS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
 
 /// Simple base64 encoding
@@ -405,3 +1152,343 @@ fn base64_encode(data: &[u8]) -> String {
 
     result
 }
+
+/// Simple base64 decoding. Returns `None` for invalid input.
+fn base64_decode(input: &str) -> Option<Vec<u8>> {
+    let input = input.trim_end_matches('=');
+    let mut result = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buf = 0u32;
+    let mut bits = 0;
+
+    for c in input.bytes() {
+        let val = B64_CHARS.iter().position(|&b| b == c)? as u32;
+        buf = (buf << 6) | val;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use loro::LoroValue;
+    use tempfile::{TempDir, tempdir};
+
+    /// Server state saving rooms to a temporary directory (kept alive by the `TempDir`).
+    fn test_state(config: Config) -> (AppState, TempDir) {
+        let dir = tempdir().unwrap();
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        (AppState::new(store, config), dir)
+    }
+
+    /// A document edited by `peer`, with `key` set to `value` in its "shapes" map.
+    fn edited(peer: u64, key: &str, value: &str) -> LoroDoc {
+        let doc = LoroDoc::new();
+        doc.set_peer_id(peer).unwrap();
+        doc.get_map("shapes").insert(key, value).unwrap();
+        doc.commit();
+        doc
+    }
+
+    fn updates(doc: &LoroDoc) -> Vec<u8> {
+        doc.export(ExportMode::all_updates()).unwrap()
+    }
+
+    fn merged(room: &Room) -> &LoroDoc {
+        match &room.board {
+            Board::Merged(doc) => doc,
+            Board::Sealed { .. } => panic!("expected a merged board"),
+        }
+    }
+
+    fn is_snapshot(data: &str) -> bool {
+        let bytes = base64_decode(data).unwrap();
+        LoroDoc::decode_import_blob_meta(&bytes, false)
+            .unwrap()
+            .mode
+            .is_snapshot()
+    }
+
+    #[test]
+    fn test_load_room() {
+        // Nothing saved: an empty, open room
+        let room = load_room("new", None, 0);
+        assert!(room.board.is_empty());
+        assert_eq!(room.board.key_check(), None);
+        assert!(room.access.is_none());
+        assert!(!room.dirty);
+
+        // A saved document, with its tokens
+        let doc = edited(1, "a", "box");
+        let access = RoomAccess::new("edit-token-0123456789", None);
+        let saved = SavedRoom {
+            data: base64_encode(&doc.export(ExportMode::Snapshot).unwrap()),
+            access: Some(access.clone()),
+            key_check: None,
+        };
+        let room = load_room("saved", Some(saved), 0);
+        assert_eq!(merged(&room).get_deep_value(), doc.get_deep_value());
+        assert_eq!(room.access, Some(access));
+
+        // Unreadable data starts the room empty instead of failing
+        for data in ["not base64!", "AAAA"] {
+            let saved = SavedRoom {
+                data: data.to_string(),
+                access: None,
+                key_check: None,
+            };
+            assert!(load_room("corrupt", Some(saved), 0).board.is_empty());
+        }
+
+        // Encrypted rooms keep their sealed data as is
+        let saved = SavedRoom {
+            data: "c2VhbGVk".to_string(),
+            access: None,
+            key_check: Some("check".to_string()),
+        };
+        let room = load_room("sealed", Some(saved.clone()), 0);
+        assert_eq!(room.board.key_check(), Some("check"));
+        assert_eq!(room.initial_sync(None).as_deref(), Some("c2VhbGVk"));
+        let empty = SavedRoom {
+            data: String::new(),
+            ..saved
+        };
+        assert!(load_room("sealed", Some(empty), 0).board.is_empty());
+    }
+
+    #[test]
+    fn test_merge_concurrent_updates() {
+        let (state, _dir) = test_state(Config::default());
+        // Two peers edit the same board without seeing each other's changes
+        let alice = edited(1, "a", "box");
+        alice.get_map("shapes").insert("title", "Alice's").unwrap();
+        alice.commit();
+        let bob = edited(2, "b", "arrow");
+        bob.get_map("shapes").insert("title", "Bob's").unwrap();
+        bob.commit();
+
+        // Whatever order their updates arrive in, the rooms converge
+        for (room_id, first, second) in [("ab", &alice, &bob), ("ba", &bob, &alice)] {
+            state
+                .rooms
+                .insert(room_id.to_string(), load_room(room_id, None, 0));
+            state.import_sync(room_id, &updates(first)).unwrap();
+            state.import_sync(room_id, &updates(second)).unwrap();
+            assert!(state.rooms.get(room_id).unwrap().dirty);
+        }
+        let ab = merged(&state.rooms.get("ab").unwrap()).get_deep_value();
+        let ba = merged(&state.rooms.get("ba").unwrap()).get_deep_value();
+        assert_eq!(ab, ba);
+        alice.import(&updates(&bob)).unwrap();
+        assert_eq!(ab, alice.get_deep_value());
+        let LoroValue::Map(board) = ab else {
+            panic!("expected a map");
+        };
+        let LoroValue::Map(shapes) = &board["shapes"] else {
+            panic!("expected the shapes map");
+        };
+        assert_eq!(shapes.len(), 3);
+
+        // Data that isn't a Loro update is rejected
+        assert!(state.import_sync("ab", b"not an update").is_err());
+    }
+
+    #[test]
+    fn test_initial_sync() {
+        let empty = load_room("empty", None, 0);
+        assert_eq!(empty.initial_sync(None), None);
+
+        // The room has a peer's first change and a later one
+        let client = edited(1, "a", "box");
+        let mut room = load_room("room", None, 0);
+        let Board::Merged(doc) = &mut room.board else {
+            panic!("expected a merged board");
+        };
+        doc.import(&updates(&client)).unwrap();
+        doc.import(&updates(&edited(2, "b", "arrow"))).unwrap();
+
+        // A peer sending its version gets only what it's missing
+        let version = client.oplog_vv().encode();
+        let delta = room.initial_sync(Some(&version)).unwrap();
+        assert!(!is_snapshot(&delta));
+        client.import(&base64_decode(&delta).unwrap()).unwrap();
+        assert_eq!(client.get_deep_value(), merged(&room).get_deep_value());
+
+        // A peer without a (valid) version gets a snapshot
+        for version in [None, Some(&b"garbage"[..])] {
+            let data = room.initial_sync(version).unwrap();
+            assert!(is_snapshot(&data));
+            let fresh = LoroDoc::new();
+            fresh.import(&base64_decode(&data).unwrap()).unwrap();
+            assert_eq!(fresh.get_deep_value(), merged(&room).get_deep_value());
+        }
+
+        // A peer that is up to date gets no changes
+        let delta = room
+            .initial_sync(Some(&client.oplog_vv().encode()))
+            .unwrap();
+        let before = client.oplog_vv();
+        client.import(&base64_decode(&delta).unwrap()).unwrap();
+        assert_eq!(client.oplog_vv(), before);
+    }
+
+    #[tokio::test]
+    async fn test_set_access_in_open_room() {
+        let (state, _dir) = test_state(Config::default());
+        let token = "edit-token-0123456789";
+        let protect = || Some(RoomAccess::new(token, None));
+        let join = |peer, token| state.join_room("room", peer, None, token, None);
+        join("first", None).await.unwrap();
+        join("second", None).await.unwrap();
+
+        // Only the peer that opened the room may protect it
+        assert_eq!(state.set_access("room", "second", protect()), None);
+        assert!(state.rooms.get("room").unwrap().access.is_none());
+        assert_eq!(state.set_access("room", "first", protect()), Some(true));
+        assert_eq!(state.role("room", None), None);
+
+        // Once protected, any editor may change the tokens
+        join("editor", Some(token)).await.unwrap();
+        assert_eq!(state.set_access("room", "editor", None), Some(false));
+        assert_eq!(state.role("room", None), Some(Role::Edit));
+
+        // ...and becomes the owner of the open room
+        state.leave_room("room", "first");
+        assert_eq!(state.set_access("room", "first", protect()), None);
+        assert_eq!(state.set_access("room", "editor", protect()), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_owner_after_reload() {
+        let (state, _dir) = test_state(Config::default());
+        state
+            .join_room("room", "old", None, None, None)
+            .await
+            .unwrap();
+        state
+            .import_sync("room", &updates(&edited(1, "a", "box")))
+            .unwrap();
+        state.leave_room("room", "old");
+        state.flush().await;
+        assert!(state.rooms.is_empty());
+
+        // The owner isn't saved: the first peer to join the reloaded room owns it,
+        // even if the old owner comes back
+        state
+            .join_room("room", "new", None, None, None)
+            .await
+            .unwrap();
+        state
+            .join_room("room", "old", None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(state.set_access("room", "old", None), None);
+        assert_eq!(state.set_access("room", "new", None), Some(false));
+    }
+
+    #[tokio::test]
+    async fn test_lagged_peer_resyncs() {
+        let (state, _dir) = test_state(Config::default());
+        let mut joined = state
+            .join_room("room", "slow", None, None, None)
+            .await
+            .unwrap();
+        let doc = edited(1, "a", "box");
+        state.import_sync("room", &updates(&doc)).unwrap();
+
+        // More messages than the channel holds pass while the peer isn't reading
+        for _ in 0..CHANNEL_CAPACITY + 10 {
+            state.broadcast(
+                "room",
+                "busy",
+                ServerMessage::PeerJoined {
+                    peer_id: "busy".to_string(),
+                },
+            );
+        }
+        assert!(matches!(joined.rx.recv().await, Err(RecvError::Lagged(10))));
+
+        // The peer gets the whole board from the server
+        let resync = state.resync("room", None).unwrap();
+        assert_eq!(resync.role, Role::Edit);
+        assert!(!resync.protected);
+        let data = resync.data.unwrap();
+        assert!(is_snapshot(&data));
+        let fresh = LoroDoc::new();
+        fresh.import(&base64_decode(&data).unwrap()).unwrap();
+        assert_eq!(fresh.get_deep_value(), doc.get_deep_value());
+
+        // Nothing to resync for an empty room
+        state
+            .join_room("empty", "peer", None, None, None)
+            .await
+            .unwrap();
+        assert!(state.resync("empty", None).unwrap().data.is_none());
+        assert!(state.resync("gone", None).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_lagged_peer_token_revoked() {
+        let (state, _dir) = test_state(Config::default());
+        let (edit, view) = ("edit-token-0123456789", "view-token-0123456789");
+        state
+            .join_room("room", "owner", None, None, None)
+            .await
+            .unwrap();
+        let access = Some(RoomAccess::new(edit, Some(view)));
+        state.set_access("room", "owner", access).unwrap();
+        let mut viewer = state
+            .join_room("room", "viewer", None, Some(view), None)
+            .await
+            .unwrap();
+        let mut editor = state
+            .join_room("room", "editor", None, Some(edit), None)
+            .await
+            .unwrap();
+
+        // New tokens revoke the old ones while the peers aren't reading, and the
+        // `Access` broadcast is lost in the overflow
+        let new_edit = "edit-token-abcdefghij";
+        let access = Some(RoomAccess::new(new_edit, None));
+        state.set_access("room", "owner", access).unwrap();
+        for _ in 0..CHANNEL_CAPACITY + 10 {
+            state.broadcast(
+                "room",
+                "busy",
+                ServerMessage::PeerJoined {
+                    peer_id: "busy".to_string(),
+                },
+            );
+        }
+        for rx in [&mut viewer.rx, &mut editor.rx] {
+            assert!(matches!(rx.recv().await, Err(RecvError::Lagged(_))));
+        }
+
+        // Catching up checks the tokens again
+        assert!(state.resync("room", Some(view)).is_none());
+        assert!(state.resync("room", Some(edit)).is_none());
+        assert!(matches!(
+            state.remove_revoked("room", "viewer"),
+            ServerMessage::Removed { .. }
+        ));
+        assert!(!state.rooms.get("room").unwrap().peers.contains("viewer"));
+        let resync = state.resync("room", Some(new_edit)).unwrap();
+        assert_eq!(resync.role, Role::Edit);
+        assert!(resync.protected);
+    }
+
+    #[test]
+    fn test_cors_wildcard() {
+        // A list containing `*` would panic in tower-http
+        let _ = cors_layer(&["*".to_string()]);
+        let _ = cors_layer(&["https://drafft.ink".to_string(), "*".to_string()]);
+        let _ = cors_layer(&["https://drafft.ink".to_string()]);
+        let _ = cors_layer(&[]);
+    }
+}
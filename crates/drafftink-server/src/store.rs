@@ -0,0 +1,350 @@
+//! Persistent room storage.
+//!
+//! Each room's document is kept in its own file under `<data dir>/rooms`,
+//! named after the hex-encoded room ID so any room name maps to a safe file name.
+//! Files are replaced atomically (write to a temporary file, fsync, rename), so a
+//! crash mid-write leaves the previous version intact. Rooms untouched for longer
+//! than the retention period are deleted.
+
+use crate::access::RoomAccess;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::{info, warn};
+use uuid::Uuid;
+
+/// Version of the room file format.
+const ROOM_FILE_VERSION: u32 = 1;
+
+/// Extension of room files.
+const ROOM_FILE_EXTENSION: &str = "json";
+
+/// Marker in the names of files being written.
+const TEMP_MARKER: &str = ".tmp-";
+
+/// A room as stored on disk.
+#[derive(Debug, Serialize, Deserialize)]
+struct RoomFile {
+    version: u32,
+    room: String,
+    #[serde(flatten)]
+    saved: SavedRoom,
+}
+
+/// What is saved of a room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedRoom {
+    /// Snapshot of the room's document (base64 encoded Loro bytes).
+    pub data: String,
+    /// Tokens protecting the room (`None` for open rooms).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access: Option<RoomAccess>,
+    /// Check of the room key, for end-to-end encrypted rooms, whose `data` is the
+    /// latest sealed sync data instead (empty if there is none yet).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_check: Option<String>,
+}
+
+/// Room files in a data directory.
+#[derive(Debug, Clone)]
+pub struct RoomStore {
+    dir: PathBuf,
+    /// How long a room is kept after its last write (`None` keeps rooms forever).
+    retention: Option<Duration>,
+}
+
+impl RoomStore {
+    /// Open (creating if needed) the room store in `data_dir`, removing files left
+    /// over from interrupted writes.
+    pub fn open(data_dir: &Path, retention: Option<Duration>) -> io::Result<Self> {
+        let dir = data_dir.join("rooms");
+        fs::create_dir_all(&dir)?;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if is_temp_file(&path) {
+                warn!("Removing incomplete write {:?}", path);
+                let _ = fs::remove_file(&path);
+            }
+        }
+        Ok(Self { dir, retention })
+    }
+
+    /// Load a room. Rooms past their retention are deleted instead.
+    pub async fn load(&self, room: &str) -> Option<SavedRoom> {
+        let store = self.clone();
+        let room = room.to_string();
+        tokio::task::spawn_blocking(move || store.load_blocking(&room))
+            .await
+            .ok()
+            .flatten()
+    }
+
+    /// Save a room, replacing what was stored.
+    pub async fn save(&self, room: &str, saved: SavedRoom) -> io::Result<()> {
+        let store = self.clone();
+        let room = room.to_string();
+        tokio::task::spawn_blocking(move || store.save_blocking(&room, saved))
+            .await
+            .map_err(io::Error::other)?
+    }
+
+    /// Delete rooms past their retention, except those in `active`. Returns how many
+    /// were deleted.
+    pub async fn sweep(&self, active: HashSet<String>) -> usize {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || store.sweep_blocking(&active))
+            .await
+            .unwrap_or(0)
+    }
+
+    fn load_blocking(&self, room: &str) -> Option<SavedRoom> {
+        let path = self.room_path(room);
+        if self.is_expired(&path) {
+            info!("Room {} expired; deleting it", room);
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        let json = match fs::read_to_string(&path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                warn!("Failed to read room {}: {}", room, e);
+                return None;
+            }
+        };
+        match serde_json::from_str::<RoomFile>(&json) {
+            Ok(file) if file.version <= ROOM_FILE_VERSION => Some(file.saved),
+            Ok(file) => {
+                warn!("Room {} has unsupported version {}", room, file.version);
+                None
+            }
+            Err(e) => {
+                warn!("Room {} is corrupt: {}", room, e);
+                None
+            }
+        }
+    }
+
+    fn save_blocking(&self, room: &str, saved: SavedRoom) -> io::Result<()> {
+        let file = RoomFile {
+            version: ROOM_FILE_VERSION,
+            room: room.to_string(),
+            saved,
+        };
+        let json = serde_json::to_vec(&file)?;
+        write_atomic(&self.room_path(room), &json)
+    }
+
+    fn sweep_blocking(&self, active: &HashSet<String>) -> usize {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return 0;
+        };
+        let mut deleted = 0;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let Some(room) = room_from_path(&path) else {
+                continue;
+            };
+            if !active.contains(&room) && self.is_expired(&path) && fs::remove_file(&path).is_ok() {
+                info!("Room {} expired; deleted it", room);
+                deleted += 1;
+            }
+        }
+        deleted
+    }
+
+    /// Whether a room file was last written longer ago than the retention period.
+    fn is_expired(&self, path: &Path) -> bool {
+        let Some(retention) = self.retention else {
+            return false;
+        };
+        fs::metadata(path)
+            .and_then(|m| m.modified())
+            .ok()
+            .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+            .is_some_and(|age| age > retention)
+    }
+
+    fn room_path(&self, room: &str) -> PathBuf {
+        let name: String = room.bytes().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}.{}", name, ROOM_FILE_EXTENSION))
+    }
+}
+
+/// Replace the file at `path` with `bytes` so that it holds either the old or the
+/// new contents, never a mix, even if the process dies mid-write.
+fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    let mut temp_name = file_name.to_os_string();
+    temp_name.push(format!("{}{}", TEMP_MARKER, Uuid::new_v4()));
+    let temp = path.with_file_name(temp_name);
+
+    let result = (|| -> io::Result<()> {
+        let mut file = File::create(&temp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+        fs::rename(&temp, path)?;
+        // Persist the rename itself
+        #[cfg(unix)]
+        if let Some(dir) = path.parent() {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+/// The room a room file belongs to, decoded from its name.
+fn room_from_path(path: &Path) -> Option<String> {
+    // Not `file_stem`: the empty room's file is just `.json`
+    let name = path
+        .file_name()?
+        .to_str()?
+        .strip_suffix(ROOM_FILE_EXTENSION)?
+        .strip_suffix('.')?;
+    if name.len() % 2 != 0 {
+        return None;
+    }
+    let bytes = (0..name.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(name.get(i..i + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    String::from_utf8(bytes).ok()
+}
+
+fn is_temp_file(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.contains(TEMP_MARKER))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn saved(data: &str) -> SavedRoom {
+        SavedRoom {
+            data: data.to_string(),
+            access: Some(RoomAccess::new("edit-token-0123456789", None)),
+            key_check: None,
+        }
+    }
+
+    /// Make a room's file look like it was last written `age` ago.
+    fn age_room(store: &RoomStore, room: &str, age: Duration) {
+        let file = File::options()
+            .write(true)
+            .open(store.room_path(room))
+            .unwrap();
+        file.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_save_load_round_trip() {
+        let dir = tempdir().unwrap();
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        assert!(store.load("team").await.is_none());
+
+        store.save("team", saved("AAAA")).await.unwrap();
+        let loaded = store.load("team").await.unwrap();
+        assert_eq!(loaded.data, "AAAA");
+        assert_eq!(loaded.access, saved("").access);
+        assert_eq!(loaded.key_check, None);
+
+        // Saving again replaces the room, without leaving temporary files
+        store.save("team", saved("BBBB")).await.unwrap();
+        assert_eq!(store.load("team").await.unwrap().data, "BBBB");
+        let files: Vec<_> = fs::read_dir(dir.path().join("rooms")).unwrap().collect();
+        assert_eq!(files.len(), 1);
+    }
+
+    #[test]
+    fn test_leftover_temp_file_ignored() {
+        let dir = tempdir().unwrap();
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        store.save_blocking("team", saved("AAAA")).unwrap();
+
+        // A write interrupted after creating its temporary file
+        let mut temp_name = store.room_path("team").into_os_string();
+        temp_name.push(format!("{}{}", TEMP_MARKER, Uuid::new_v4()));
+        let temp = PathBuf::from(temp_name);
+        fs::write(&temp, b"{\"version\": 1, \"ro").unwrap();
+        assert_eq!(room_from_path(&temp), None);
+        assert_eq!(store.sweep_blocking(&HashSet::new()), 0);
+
+        // Reopening removes it and keeps the last complete write
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        assert!(!temp.exists());
+        assert_eq!(store.load_blocking("team").unwrap().data, "AAAA");
+    }
+
+    #[test]
+    fn test_expiry() {
+        let dir = tempdir().unwrap();
+        let day = Duration::from_secs(24 * 60 * 60);
+        let store = RoomStore::open(dir.path(), Some(day)).unwrap();
+        for room in ["old", "fresh", "busy", "loaded"] {
+            store.save_blocking(room, saved(room)).unwrap();
+        }
+        for room in ["old", "busy", "loaded"] {
+            age_room(&store, room, 2 * day);
+        }
+
+        // Loading an expired room deletes it
+        assert!(store.load_blocking("loaded").is_none());
+        assert!(!store.room_path("loaded").exists());
+
+        // Sweeping deletes expired rooms that aren't in use
+        let active = HashSet::from(["busy".to_string()]);
+        assert_eq!(store.sweep_blocking(&active), 1);
+        assert!(!store.room_path("old").exists());
+        assert_eq!(store.load_blocking("fresh").unwrap().data, "fresh");
+        assert!(store.room_path("busy").exists());
+
+        // Without a retention period nothing expires
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        assert_eq!(store.sweep_blocking(&HashSet::new()), 0);
+        assert_eq!(store.load_blocking("busy").unwrap().data, "busy");
+    }
+
+    #[test]
+    fn test_hostile_room_ids() {
+        let dir = tempdir().unwrap();
+        let store = RoomStore::open(dir.path(), None).unwrap();
+        let longest = "é".repeat(crate::MAX_ROOM_ID_LEN / 2);
+        let rooms = [
+            "../x",
+            "/etc/passwd",
+            "a/../../b",
+            "..",
+            "",
+            "nul\0",
+            &longest,
+        ];
+        for room in rooms {
+            let path = store.room_path(room);
+            assert_eq!(path.parent(), Some(store.dir.as_path()), "{:?}", room);
+            assert_eq!(room_from_path(&path).as_deref(), Some(room));
+            store.save_blocking(room, saved(room)).unwrap();
+            assert_eq!(store.load_blocking(room).unwrap().data, room);
+        }
+        // Nothing was written outside the store
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+        assert_eq!(fs::read_dir(&store.dir).unwrap().count(), rooms.len());
+
+        // IDs too long for a file name fail to save instead of being truncated
+        let too_long = "x".repeat(200);
+        assert!(store.save_blocking(&too_long, saved("")).is_err());
+        assert!(store.load_blocking(&too_long).is_none());
+        assert_eq!(fs::read_dir(&store.dir).unwrap().count(), rooms.len());
+    }
+}
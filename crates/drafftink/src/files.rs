@@ -0,0 +1,188 @@
+//! Reading and writing boards by file extension.
+
+use std::path::{Path, PathBuf};
+
+use drafftink_core::bundle::BUNDLE_EXTENSION;
+use drafftink_core::canvas::CanvasDocument;
+
+use crate::{CliError, CliResult};
+
+/// File formats, chosen by extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Native JSON (the default for unknown extensions).
+    Json,
+    /// Zip bundle with deduplicated assets.
+    Bundle,
+    Excalidraw,
+    Tldraw,
+    Drawio,
+    Mermaid,
+    Dot,
+    /// SVG: the embedded scene if present, otherwise the drawing itself.
+    Svg,
+    /// PNG: only the embedded scene can be read.
+    Png,
+    /// PDF: only written, by `render`.
+    Pdf,
+}
+
+impl Format {
+    /// Determine the format from a file's extension.
+    pub fn from_path(path: &Path) -> Self {
+        match extension(path).as_str() {
+            BUNDLE_EXTENSION => Format::Bundle,
+            "excalidraw" => Format::Excalidraw,
+            "tldr" => Format::Tldraw,
+            "drawio" => Format::Drawio,
+            "mmd" | "mermaid" => Format::Mermaid,
+            "dot" | "gv" => Format::Dot,
+            "svg" => Format::Svg,
+            "png" => Format::Png,
+            "pdf" => Format::Pdf,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// Lowercase extension of a path, or an empty string.
+pub fn extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+/// Open a board in any format the app can open.
+pub fn load(path: &Path) -> CliResult<CanvasDocument> {
+    let import_error = |message: String| CliError::Import {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    match Format::from_path(path) {
+        Format::Bundle => {
+            CanvasDocument::from_bundle(&read(path)?).map_err(|source| CliError::Bundle {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+        Format::Png => {
+            let json = drafftink_render::extract_scene_from_png(&read(path)?)
+                .ok_or_else(|| CliError::NoScene(path.to_path_buf()))?;
+            CanvasDocument::from_json(&json).map_err(|e| import_error(e.to_string()))
+        }
+        format => {
+            let text = read_to_string(path)?;
+            match format {
+                Format::Excalidraw => CanvasDocument::from_excalidraw(&text),
+                Format::Tldraw => {
+                    CanvasDocument::from_tldraw(&text).map(|(document, unsupported)| {
+                        if !unsupported.is_empty() {
+                            eprintln!(
+                                "warning: {}: skipped {} unsupported elements: {}",
+                                path.display(),
+                                unsupported.total(),
+                                unsupported
+                            );
+                        }
+                        document
+                    })
+                }
+                Format::Drawio => CanvasDocument::from_drawio(&text),
+                Format::Mermaid => CanvasDocument::from_mermaid(&text),
+                Format::Dot => CanvasDocument::from_dot(&text),
+                Format::Svg => match drafftink_render::extract_scene_from_svg(&text) {
+                    Some(json) => CanvasDocument::from_json(&json).map_err(|e| e.to_string()),
+                    None => CanvasDocument::from_svg(&text),
+                },
+                _ => CanvasDocument::from_json(&text).map_err(|e| e.to_string()),
+            }
+            .map_err(import_error)
+        }
+    }
+}
+
+/// Save a board as JSON, Excalidraw, draw.io or a bundle.
+pub fn save(document: &CanvasDocument, path: &Path) -> CliResult<()> {
+    let data = match Format::from_path(path) {
+        // Unknown extensions are read as JSON, but only `.json` is written as JSON
+        Format::Json if extension(path) == "json" => document
+            .to_json()
+            .map_err(|e| CliError::Export(e.to_string()))?
+            .into_bytes(),
+        Format::Bundle => document
+            .to_bundle()
+            .map_err(|e| CliError::Export(e.to_string()))?,
+        Format::Excalidraw => document.to_excalidraw().into_bytes(),
+        Format::Drawio => document.to_drawio().into_bytes(),
+        _ => return Err(CliError::UnsupportedOutput(extension(path))),
+    };
+    write(path, &data)
+}
+
+/// Read a file.
+pub fn read(path: &Path) -> CliResult<Vec<u8>> {
+    std::fs::read(path).map_err(|source| io_error(path, source))
+}
+
+/// Read a text file.
+pub fn read_to_string(path: &Path) -> CliResult<String> {
+    std::fs::read_to_string(path).map_err(|source| io_error(path, source))
+}
+
+/// Write a file.
+pub fn write(path: &Path, data: &[u8]) -> CliResult<()> {
+    std::fs::write(path, data).map_err(|source| io_error(path, source))
+}
+
+fn io_error(path: &Path, source: std::io::Error) -> CliError {
+    CliError::Io {
+        path: PathBuf::from(path),
+        source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_extension() {
+        let cases = [
+            ("board.json", Format::Json),
+            ("board.drafftink", Format::Bundle),
+            ("board.excalidraw", Format::Excalidraw),
+            ("board.tldr", Format::Tldraw),
+            ("board.drawio", Format::Drawio),
+            ("flow.mmd", Format::Mermaid),
+            ("flow.mermaid", Format::Mermaid),
+            ("graph.dot", Format::Dot),
+            ("graph.gv", Format::Dot),
+            ("export.svg", Format::Svg),
+            ("export.png", Format::Png),
+            ("EXPORT.PNG", Format::Png),
+            ("slides.pdf", Format::Pdf),
+            ("notes.txt", Format::Json),
+            ("board", Format::Json),
+        ];
+        for (path, format) in cases {
+            assert_eq!(Format::from_path(Path::new(path)), format, "{}", path);
+        }
+        assert_eq!(extension(Path::new("dir/Board.Excalidraw")), "excalidraw");
+        assert_eq!(extension(Path::new("board")), "");
+    }
+
+    #[test]
+    fn test_save_rejects_unwritable_formats() {
+        let document = CanvasDocument::new();
+        for path in ["board.svg", "board.pdf", "board.mmd", "board.txt"] {
+            let result = save(&document, Path::new(path));
+            assert!(
+                matches!(result, Err(CliError::UnsupportedOutput(_))),
+                "{}",
+                path
+            );
+        }
+    }
+}
@@ -1,13 +0,0 @@
-//! # DrafftInk
-//!
-//! This is a placeholder crate to reserve the `drafftink` name.
-//!
-//! The actual DrafftInk application is under active development.
-//! For more information, visit: <https://github.com/patwie/drafft-ink>
-//!
-//! ## Status
-//!
-//! Publishing to crates.io is currently blocked by waiting for
-//! [vello](https://github.com/linebender/vello) to publish a version with wgpu 27 support.
-
-#![doc(html_root_url = "https://docs.rs/drafftink/0.1.0")]
@@ -0,0 +1,517 @@
+//! DrafftInk command-line tool
+//!
+//! Renders, converts and inspects boards without a window or GPU:
+//!
+//! ```text
+//! drafftink-cli render board.json -o board.png --scale 2
+//! drafftink-cli render board.excalidraw -o ideas.svg --frame Ideas
+//! drafftink-cli render board.excalidraw -o slides.pdf --page-size a4
+//! drafftink-cli convert board.excalidraw board.drafftink
+//! drafftink-cli validate board.drafftink
+//! drafftink-cli stats board.json --json
+//! drafftink-cli extract export.png -o board.json
+//! ```
+
+mod files;
+mod stats;
+
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand};
+use drafftink_core::bundle::BundleError;
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::shapes::{ImageFormat, Shape, ShapeId};
+use drafftink_render::{
+    CpuRenderer, PdfExportOptions, PdfPageSize, SvgExportOptions, SvgTextMode, export_document_svg,
+    export_frames_pdf, export_selection_pdf, export_selection_svg,
+};
+use thiserror::Error;
+
+use files::Format;
+
+/// Errors reported by the command-line tool.
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{path}: {source}")]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    #[error("Failed to read {path}: {message}")]
+    Import { path: PathBuf, message: String },
+    #[error("Failed to read {path}: {source}")]
+    Bundle { path: PathBuf, source: BundleError },
+    #[error("{0} has no embedded DrafftInk scene")]
+    NoScene(PathBuf),
+    #[error("Cannot write {0} files")]
+    UnsupportedOutput(String),
+    #[error("No frame named \"{0}\"")]
+    FrameNotFound(String),
+    #[error("Nothing to render")]
+    Empty,
+    #[error("Failed to write output: {0}")]
+    Export(String),
+    #[error("Found {0} problem(s)")]
+    Invalid(usize),
+}
+
+/// Result type for command-line operations.
+pub type CliResult<T> = Result<T, CliError>;
+
+#[derive(Parser)]
+#[command(
+    name = "drafftink-cli",
+    version,
+    about = "Render, convert and inspect DrafftInk boards"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a board (or one of its frames) to PNG, SVG or PDF.
+    ///
+    /// PDFs of a whole board get one page per frame, if it has any.
+    Render {
+        /// Input board (any format the app can open).
+        input: PathBuf,
+        /// Output file; the extension selects PNG, SVG or PDF.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Render only the frame with this name.
+        #[arg(long)]
+        frame: Option<String>,
+        /// Resolution multiplier for PNG output.
+        #[arg(long, default_value_t = 1.0)]
+        scale: f64,
+        /// Don't embed the scene, so the output can't be reopened as a board.
+        #[arg(long)]
+        no_scene: bool,
+        /// Space around the content of SVG output, in document units.
+        #[arg(long, default_value_t = SvgExportOptions::default().padding)]
+        padding: f64,
+        /// Leave the background of SVG output transparent.
+        #[arg(long)]
+        transparent: bool,
+        /// Write SVG text as glyph outlines instead of text with embedded fonts.
+        #[arg(long)]
+        text_as_paths: bool,
+        /// Page size of PDF output: a4, letter or fit.
+        #[arg(long, default_value = "fit", value_parser = parse_page_size)]
+        page_size: PdfPageSize,
+        /// Margin around the content of PDF pages, in points.
+        #[arg(long, default_value_t = PdfExportOptions::default().margin)]
+        margin: f64,
+    },
+    /// Convert a board between JSON, Excalidraw, draw.io and the bundle format.
+    Convert {
+        /// Input board.
+        input: PathBuf,
+        /// Output file; the extension selects the format.
+        output: PathBuf,
+    },
+    /// Check that a board opens and its images are intact.
+    Validate {
+        /// Input board.
+        input: PathBuf,
+    },
+    /// Print shape counts, image sizes, bounds and frames.
+    Stats {
+        /// Input board.
+        input: PathBuf,
+        /// Print the statistics as JSON.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Extract the scene JSON embedded in an exported PNG.
+    Extract {
+        /// Exported PNG.
+        input: PathBuf,
+        /// Write the scene here instead of to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+fn main() -> ExitCode {
+    match run(Cli::parse().command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> CliResult<()> {
+    match command {
+        Command::Render {
+            input,
+            output,
+            frame,
+            scale,
+            no_scene,
+            padding,
+            transparent,
+            text_as_paths,
+            page_size,
+            margin,
+        } => {
+            let document = files::load(&input)?;
+            let mut svg = SvgExportOptions {
+                padding,
+                embed_scene: !no_scene,
+                ..Default::default()
+            };
+            if transparent {
+                svg.background = None;
+            }
+            if text_as_paths {
+                svg.text_mode = SvgTextMode::Outlined;
+            }
+            let options = RenderOptions {
+                scale,
+                embed_scene: !no_scene,
+                svg,
+                pdf: PdfExportOptions {
+                    page_size,
+                    margin,
+                    ..Default::default()
+                },
+            };
+            render(&document, &output, frame.as_deref(), &options)
+        }
+        Command::Convert { input, output } => {
+            let document = files::load(&input)?;
+            files::save(&document, &output)
+        }
+        Command::Validate { input } => {
+            let document = files::load(&input)?;
+            let problems = validate(&document);
+            for problem in &problems {
+                eprintln!("{}: {}", input.display(), problem);
+            }
+            if problems.is_empty() {
+                println!("{}: ok ({} shapes)", input.display(), document.shapes.len());
+                Ok(())
+            } else {
+                Err(CliError::Invalid(problems.len()))
+            }
+        }
+        Command::Stats { input, json } => {
+            let document = files::load(&input)?;
+            let stats = stats::Stats::of(&document);
+            if json {
+                println!("{}", stats.to_json());
+            } else {
+                print!("{}", stats);
+            }
+            Ok(())
+        }
+        Command::Extract { input, output } => {
+            let data = files::read(&input)?;
+            let json = drafftink_render::extract_scene_from_png(&data)
+                .ok_or_else(|| CliError::NoScene(input.clone()))?;
+            match output {
+                Some(path) => files::write(&path, json.as_bytes()),
+                None => {
+                    println!("{}", json);
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// Parse a PDF page size by name.
+fn parse_page_size(name: &str) -> Result<PdfPageSize, String> {
+    PdfPageSize::ALL
+        .into_iter()
+        .find(|size| size.name().eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            format!(
+                "unknown page size \"{}\" (expected a4, letter or fit)",
+                name
+            )
+        })
+}
+
+/// How [`render`] draws its output.
+struct RenderOptions {
+    /// Resolution multiplier for PNG output.
+    scale: f64,
+    /// Embed the scene in PNG output.
+    embed_scene: bool,
+    /// SVG output options.
+    svg: SvgExportOptions,
+    /// Page layout of PDF output.
+    pdf: PdfExportOptions,
+}
+
+/// Render the document, or one of its frames, to `output`.
+fn render(
+    document: &CanvasDocument,
+    output: &Path,
+    frame: Option<&str>,
+    options: &RenderOptions,
+) -> CliResult<()> {
+    let selection = match frame {
+        Some(name) => {
+            Some(find_frame(document, name).ok_or_else(|| CliError::FrameNotFound(name.into()))?)
+        }
+        None => None,
+    };
+
+    match Format::from_path(output) {
+        Format::Png => {
+            let mut renderer = CpuRenderer::new();
+            let image = match selection {
+                Some(id) => renderer.render_selection(document, &[id], options.scale),
+                None => renderer.render_document(document, options.scale),
+            }
+            .ok_or(CliError::Empty)?;
+            let scene_json = if options.embed_scene {
+                let scene = match selection {
+                    Some(id) => document.export_selection(&[id]).to_json(),
+                    None => document.to_json(),
+                };
+                Some(scene.map_err(|e| CliError::Export(e.to_string()))?)
+            } else {
+                None
+            };
+            let png = image
+                .to_png(scene_json.as_deref())
+                .map_err(|e| CliError::Export(e.to_string()))?;
+            files::write(output, &png)
+        }
+        Format::Svg => {
+            let svg = match selection {
+                Some(id) => export_selection_svg(document, &[id], &options.svg),
+                None => export_document_svg(document, &options.svg),
+            }
+            .ok_or(CliError::Empty)?;
+            files::write(output, svg.as_bytes())
+        }
+        Format::Pdf => {
+            let pdf = match selection {
+                Some(id) => export_selection_pdf(document, &[id], &options.pdf),
+                None => export_frames_pdf(document, &options.pdf),
+            }
+            .ok_or(CliError::Empty)?;
+            files::write(output, &pdf)
+        }
+        _ => Err(CliError::UnsupportedOutput(files::extension(output))),
+    }
+}
+
+/// Find a frame by name.
+///
+/// Boards have no native frames; frames imported from Excalidraw and tldraw
+/// become a top-level group marked with the frame's name.
+pub fn find_frame(document: &CanvasDocument, name: &str) -> Option<ShapeId> {
+    document
+        .shapes_ordered()
+        .find(|shape| frame_name(shape) == Some(name))
+        .map(|shape| shape.id())
+}
+
+/// The name of a frame group, or `None` if the shape isn't a frame.
+pub fn frame_name(shape: &Shape) -> Option<&str> {
+    shape.as_group()?.frame.as_deref()
+}
+
+/// Check the shapes of a document, returning a description of each problem.
+fn validate(document: &CanvasDocument) -> Vec<String> {
+    let mut problems = Vec::new();
+    for shape in document.shapes_ordered() {
+        validate_shape(shape, &mut problems);
+    }
+    problems
+}
+
+fn validate_shape(shape: &Shape, problems: &mut Vec<String>) {
+    let bounds = shape.bounds();
+    if ![bounds.x0, bounds.y0, bounds.x1, bounds.y1]
+        .iter()
+        .all(|v| v.is_finite())
+    {
+        problems.push(format!("shape {} has non-finite geometry", shape.id()));
+    }
+    match shape {
+        Shape::Group(group) => {
+            for child in group.children() {
+                validate_shape(child, problems);
+            }
+        }
+        Shape::Image(image) => {
+            let data = image.asset.bytes();
+            if data.is_empty() {
+                problems.push(format!("image {} has no data", shape.id()));
+            } else {
+                match ImageFormat::from_magic_bytes(data) {
+                    Some(format) if format == image.format => {}
+                    Some(format) => problems.push(format!(
+                        "image {} is declared as {} but contains {}",
+                        shape.id(),
+                        image.format.mime_type(),
+                        format.mime_type()
+                    )),
+                    None => {
+                        problems.push(format!("image {} contains unrecognized data", shape.id()))
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A board with a frame "Ideas" holding an ellipse, and a box labeled "Todo".
+    pub(crate) fn board() -> CanvasDocument {
+        let scene = json!({
+            "type": "excalidraw",
+            "elements": [
+                { "id": "f", "type": "frame", "x": 0, "y": 0, "width": 400, "height": 200,
+                  "name": "Ideas" },
+                { "id": "e", "type": "ellipse", "x": 20, "y": 20, "width": 50, "height": 50,
+                  "frameId": "f" },
+                { "id": "box", "type": "rectangle", "x": 500, "y": 0, "width": 100, "height": 50,
+                  "boundElements": [{ "id": "label", "type": "text" }] },
+                { "id": "label", "type": "text", "x": 520, "y": 15, "text": "Todo",
+                  "containerId": "box" },
+            ],
+        });
+        CanvasDocument::from_excalidraw(&scene.to_string()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_render() {
+        let cli = Cli::try_parse_from([
+            "drafftink-cli",
+            "render",
+            "board.excalidraw",
+            "-o",
+            "ideas.svg",
+            "--frame",
+            "Ideas",
+            "--scale",
+            "2",
+            "--padding",
+            "5",
+            "--transparent",
+            "--text-as-paths",
+            "--page-size",
+            "A4",
+            "--margin",
+            "0",
+        ])
+        .unwrap();
+        let Command::Render {
+            input,
+            output,
+            frame,
+            scale,
+            no_scene,
+            padding,
+            transparent,
+            text_as_paths,
+            page_size,
+            margin,
+        } = cli.command
+        else {
+            panic!("expected render");
+        };
+        assert_eq!(input, PathBuf::from("board.excalidraw"));
+        assert_eq!(output, PathBuf::from("ideas.svg"));
+        assert_eq!(frame.as_deref(), Some("Ideas"));
+        assert_eq!(scale, 2.0);
+        assert!(!no_scene);
+        assert_eq!(padding, 5.0);
+        assert!(transparent);
+        assert!(text_as_paths);
+        assert_eq!(page_size, PdfPageSize::A4);
+        assert_eq!(margin, 0.0);
+    }
+
+    #[test]
+    fn test_parse_defaults_and_errors() {
+        let cli =
+            Cli::try_parse_from(["drafftink-cli", "render", "a.json", "-o", "a.png"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Render {
+                frame: None,
+                scale,
+                no_scene: false,
+                padding,
+                transparent: false,
+                text_as_paths: false,
+                page_size: PdfPageSize::FitToContent,
+                margin,
+                ..
+            } if scale == 1.0 && padding == 20.0 && margin == 36.0
+        ));
+        assert!(
+            Cli::try_parse_from([
+                "drafftink-cli",
+                "render",
+                "a.json",
+                "-o",
+                "a.pdf",
+                "--page-size",
+                "tabloid"
+            ])
+            .is_err()
+        );
+
+        let cli = Cli::try_parse_from(["drafftink-cli", "stats", "a.json", "--json"]).unwrap();
+        assert!(matches!(cli.command, Command::Stats { json: true, .. }));
+
+        // Render needs an output, convert needs both paths
+        assert!(Cli::try_parse_from(["drafftink-cli", "render", "a.json"]).is_err());
+        assert!(Cli::try_parse_from(["drafftink-cli", "convert", "a.json"]).is_err());
+        assert!(Cli::try_parse_from(["drafftink-cli", "frobnicate", "a.json"]).is_err());
+    }
+
+    #[test]
+    fn test_find_frame() {
+        let document = board();
+        let id = find_frame(&document, "Ideas").unwrap();
+        let frame = document.get_shape(id).unwrap().as_group().unwrap();
+        assert_eq!(frame.children().len(), 3);
+
+        // The labeled box is a rectangle and a text too, but not a frame
+        let labeled = document
+            .shapes_ordered()
+            .find(|shape| shape.id() != id)
+            .unwrap();
+        assert!(matches!(
+            labeled.as_group().unwrap().children(),
+            [Shape::Rectangle(_), Shape::Text(_)]
+        ));
+        assert_eq!(frame_name(labeled), None);
+        assert_eq!(find_frame(&document, "Todo"), None);
+    }
+
+    #[test]
+    fn test_render_missing_frame() {
+        let options = RenderOptions {
+            scale: 1.0,
+            embed_scene: true,
+            svg: SvgExportOptions::default(),
+            pdf: PdfExportOptions::default(),
+        };
+        for output in ["out.svg", "out.pdf"] {
+            let result = render(&board(), Path::new(output), Some("Todo"), &options);
+            assert!(matches!(result, Err(CliError::FrameNotFound(name)) if name == "Todo"));
+        }
+    }
+}
@@ -0,0 +1,203 @@
+//! Board statistics.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+
+use drafftink_core::canvas::CanvasDocument;
+use drafftink_core::shapes::Shape;
+use kurbo::Rect;
+use serde_json::{Value, json};
+
+/// Shape counts, image sizes, bounds and frames of a board.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Document name.
+    pub name: String,
+    /// Number of shapes by kind, including shapes inside groups.
+    pub shapes: BTreeMap<&'static str, usize>,
+    /// Number of image shapes.
+    pub images: usize,
+    /// Number of distinct image assets.
+    pub unique_assets: usize,
+    /// Total size of the distinct image assets, in bytes.
+    pub image_bytes: usize,
+    /// Bounds of all shapes, if there are any.
+    pub bounds: Option<Rect>,
+    /// Names of the frames, back to front.
+    pub frames: Vec<String>,
+}
+
+impl Stats {
+    /// Compute the statistics of a document.
+    pub fn of(document: &CanvasDocument) -> Self {
+        let mut stats = Stats {
+            name: document.name.clone(),
+            bounds: document.bounds(),
+            ..Default::default()
+        };
+        let mut assets = HashSet::new();
+        for shape in document.shapes_ordered() {
+            if let Some(name) = crate::frame_name(shape) {
+                stats.frames.push(name.to_string());
+            }
+            stats.count(shape, &mut assets);
+        }
+        stats
+    }
+
+    fn count<'a>(&mut self, shape: &'a Shape, assets: &mut HashSet<&'a str>) {
+        *self.shapes.entry(kind(shape)).or_default() += 1;
+        match shape {
+            Shape::Group(group) => {
+                for child in group.children() {
+                    self.count(child, assets);
+                }
+            }
+            Shape::Image(image) => {
+                self.images += 1;
+                if assets.insert(image.asset.id()) {
+                    self.unique_assets += 1;
+                    self.image_bytes += image.asset.len();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Total number of shapes.
+    pub fn total(&self) -> usize {
+        self.shapes.values().sum()
+    }
+
+    /// The statistics as a JSON value.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "name": self.name,
+            "shapes": self.shapes,
+            "total": self.total(),
+            "images": self.images,
+            "uniqueAssets": self.unique_assets,
+            "imageBytes": self.image_bytes,
+            "bounds": self.bounds.map(|b| json!({
+                "x": b.x0,
+                "y": b.y0,
+                "width": b.width(),
+                "height": b.height(),
+            })),
+            "frames": self.frames,
+        })
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "name:   {}", self.name)?;
+        write!(f, "shapes: {}", self.total())?;
+        for (i, (kind, count)) in self.shapes.iter().enumerate() {
+            write!(f, "{}{} {}", if i == 0 { " (" } else { ", " }, count, kind)?;
+        }
+        writeln!(f, "{}", if self.shapes.is_empty() { "" } else { ")" })?;
+        writeln!(
+            f,
+            "images: {} ({} unique, {} bytes)",
+            self.images, self.unique_assets, self.image_bytes
+        )?;
+        match self.bounds {
+            Some(b) => writeln!(
+                f,
+                "bounds: {:.0}, {:.0}, {:.0} × {:.0}",
+                b.x0,
+                b.y0,
+                b.width(),
+                b.height()
+            )?,
+            None => writeln!(f, "bounds: empty")?,
+        }
+        if !self.frames.is_empty() {
+            writeln!(f, "frames: {}", self.frames.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Lowercase name of a shape's kind.
+fn kind(shape: &Shape) -> &'static str {
+    match shape {
+        Shape::Rectangle(_) => "rectangle",
+        Shape::Ellipse(_) => "ellipse",
+        Shape::Line(_) => "line",
+        Shape::Arrow(_) => "arrow",
+        Shape::Freehand(_) => "freehand",
+        Shape::Text(_) => "text",
+        Shape::Group(_) => "group",
+        Shape::Image(_) => "image",
+        Shape::Math(_) => "math",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_of_board() {
+        let stats = Stats::of(&crate::tests::board());
+        assert_eq!(stats.frames, vec!["Ideas".to_string()]);
+        assert_eq!(stats.shapes["group"], 2);
+        assert_eq!(stats.shapes["rectangle"], 2);
+        assert_eq!(stats.shapes["text"], 2);
+        assert_eq!(stats.shapes["ellipse"], 1);
+        assert_eq!(stats.total(), 7);
+        assert_eq!(stats.images, 0);
+        assert!(stats.bounds.is_some());
+    }
+
+    fn sample() -> Stats {
+        Stats {
+            name: "Plan".to_string(),
+            shapes: BTreeMap::from([("ellipse", 1), ("rectangle", 2)]),
+            images: 2,
+            unique_assets: 1,
+            image_bytes: 512,
+            bounds: Some(Rect::new(-10.0, 20.0, 290.0, 220.0)),
+            frames: vec!["Ideas".to_string(), "Done".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_json_output() {
+        assert_eq!(
+            sample().to_json(),
+            json!({
+                "name": "Plan",
+                "shapes": { "ellipse": 1, "rectangle": 2 },
+                "total": 3,
+                "images": 2,
+                "uniqueAssets": 1,
+                "imageBytes": 512,
+                "bounds": { "x": -10.0, "y": 20.0, "width": 300.0, "height": 200.0 },
+                "frames": ["Ideas", "Done"],
+            })
+        );
+        assert_eq!(Stats::default().to_json()["bounds"], Value::Null);
+    }
+
+    #[test]
+    fn test_text_output() {
+        assert_eq!(
+            sample().to_string(),
+            "name:   Plan\n\
+             shapes: 3 (1 ellipse, 2 rectangle)\n\
+             images: 2 (1 unique, 512 bytes)\n\
+             bounds: -10, 20, 300 × 200\n\
+             frames: Ideas, Done\n"
+        );
+        assert_eq!(
+            Stats::default().to_string(),
+            "name:   \n\
+             shapes: 0\n\
+             images: 0 (0 unique, 0 bytes)\n\
+             bounds: empty\n"
+        );
+    }
+}